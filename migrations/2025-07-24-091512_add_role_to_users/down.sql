@@ -0,0 +1,2 @@
+-- Remove role field from users table
+ALTER TABLE users DROP COLUMN role;
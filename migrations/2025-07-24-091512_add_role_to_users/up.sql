@@ -0,0 +1,2 @@
+-- Add role field to users table
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user';
@@ -0,0 +1,30 @@
+CREATE TABLE signing_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    organization_id INTEGER NOT NULL,
+    key_id TEXT NOT NULL UNIQUE,
+    algorithm TEXT NOT NULL DEFAULT 'ed25519',
+    public_key TEXT NOT NULL,
+    private_key TEXT NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_signing_keys_organization_id ON signing_keys (organization_id);
+
+CREATE TABLE package_signatures (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_version_id INTEGER NOT NULL,
+    signing_key_id INTEGER NOT NULL,
+    integrity TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (package_version_id, signing_key_id),
+    FOREIGN KEY (package_version_id) REFERENCES package_versions (id) ON DELETE CASCADE,
+    FOREIGN KEY (signing_key_id) REFERENCES signing_keys (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_package_signatures_package_version_id ON package_signatures (package_version_id);
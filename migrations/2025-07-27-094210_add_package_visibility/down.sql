@@ -0,0 +1,2 @@
+DROP TABLE package_visibility_changes;
+ALTER TABLE packages DROP COLUMN visibility;
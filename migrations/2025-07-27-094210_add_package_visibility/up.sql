@@ -0,0 +1,16 @@
+ALTER TABLE packages ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+
+CREATE TABLE package_visibility_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_id INTEGER NOT NULL,
+    package_name TEXT NOT NULL,
+    old_visibility TEXT NOT NULL,
+    new_visibility TEXT NOT NULL,
+    changed_by INTEGER,
+    reason TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (package_id) REFERENCES packages (id) ON DELETE CASCADE,
+    FOREIGN KEY (changed_by) REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_package_visibility_changes_package_id ON package_visibility_changes (package_id);
@@ -0,0 +1,3 @@
+ALTER TABLE user_tokens DROP COLUMN ip_address;
+ALTER TABLE user_tokens DROP COLUMN user_agent;
+ALTER TABLE user_tokens DROP COLUMN last_used_at;
@@ -0,0 +1,3 @@
+ALTER TABLE user_tokens ADD COLUMN last_used_at TIMESTAMP;
+ALTER TABLE user_tokens ADD COLUMN user_agent TEXT;
+ALTER TABLE user_tokens ADD COLUMN ip_address TEXT;
@@ -0,0 +1 @@
+DROP TABLE scope_policies;
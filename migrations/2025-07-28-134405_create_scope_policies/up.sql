@@ -0,0 +1,11 @@
+CREATE TABLE scope_policies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    scope TEXT NOT NULL UNIQUE,
+    publish_access TEXT NOT NULL DEFAULT 'members',
+    allow_upstream BOOLEAN NOT NULL DEFAULT 1,
+    allow_anonymous BOOLEAN NOT NULL DEFAULT 1,
+    updated_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (updated_by) REFERENCES users (id) ON DELETE SET NULL
+);
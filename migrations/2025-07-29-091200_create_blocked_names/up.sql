@@ -0,0 +1,10 @@
+CREATE TABLE blocked_names (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pattern TEXT NOT NULL UNIQUE,
+    is_regex BOOLEAN NOT NULL DEFAULT 0,
+    block_upstream BOOLEAN NOT NULL DEFAULT 0,
+    reason TEXT,
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE SET NULL
+);
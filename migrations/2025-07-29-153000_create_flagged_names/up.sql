@@ -0,0 +1,15 @@
+CREATE TABLE flagged_names (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_name TEXT NOT NULL UNIQUE,
+    similar_to TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    published_by INTEGER,
+    reviewed_by INTEGER,
+    reviewed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (published_by) REFERENCES users (id) ON DELETE SET NULL,
+    FOREIGN KEY (reviewed_by) REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_flagged_names_status ON flagged_names (status);
@@ -0,0 +1,21 @@
+CREATE TABLE advisories (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    source TEXT NOT NULL DEFAULT 'osv',
+    advisory_id TEXT NOT NULL,
+    package_name TEXT NOT NULL,
+    title TEXT NOT NULL,
+    overview TEXT,
+    url TEXT NOT NULL,
+    severity TEXT NOT NULL DEFAULT 'moderate',
+    vulnerable_versions TEXT NOT NULL,
+    patched_versions TEXT,
+    cwe TEXT,
+    cves TEXT,
+    cvss_vector TEXT,
+    published_at TIMESTAMP,
+    modified_at TIMESTAMP,
+    synced_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (source, advisory_id, package_name)
+);
+
+CREATE INDEX idx_advisories_package_name ON advisories (package_name);
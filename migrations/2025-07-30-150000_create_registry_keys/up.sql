@@ -0,0 +1,13 @@
+-- Keys the registry signs package metadata with, in the format npm verifies, and cached
+-- keys of the upstream registry whose signatures are served for proxied packages
+CREATE TABLE registry_keys (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    key_id TEXT NOT NULL UNIQUE,
+    source TEXT NOT NULL DEFAULT 'local',
+    keytype TEXT NOT NULL,
+    scheme TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    private_key TEXT,
+    expires_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
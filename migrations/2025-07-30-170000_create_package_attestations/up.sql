@@ -0,0 +1,10 @@
+-- Sigstore bundles uploaded by `npm publish --provenance`
+CREATE TABLE package_attestations (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package_version_id INTEGER NOT NULL,
+    predicate_type TEXT NOT NULL,
+    bundle TEXT NOT NULL, -- Sigstore bundle JSON
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (package_version_id) REFERENCES package_versions (id) ON DELETE CASCADE,
+    UNIQUE (package_version_id, predicate_type)
+);
@@ -0,0 +1,15 @@
+CREATE TABLE retention_policies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    organization_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    keep_last INTEGER,
+    max_age_days INTEGER,
+    prerelease_only BOOLEAN NOT NULL DEFAULT 0,
+    dry_run BOOLEAN NOT NULL DEFAULT 1,
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (organization_id, name),
+    FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE SET NULL
+);
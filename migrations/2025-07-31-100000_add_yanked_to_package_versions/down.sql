@@ -0,0 +1,2 @@
+ALTER TABLE package_versions DROP COLUMN yank_reason;
+ALTER TABLE package_versions DROP COLUMN yanked_at;
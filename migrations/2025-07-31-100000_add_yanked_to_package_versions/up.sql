@@ -0,0 +1,2 @@
+ALTER TABLE package_versions ADD COLUMN yanked_at TIMESTAMP;
+ALTER TABLE package_versions ADD COLUMN yank_reason TEXT;
@@ -0,0 +1,10 @@
+CREATE TABLE version_tombstones (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    deleted_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (package_name, version),
+    FOREIGN KEY (deleted_by) REFERENCES users (id) ON DELETE SET NULL
+);
@@ -0,0 +1,16 @@
+CREATE TABLE quarantined_packages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name TEXT NOT NULL,
+    major INTEGER,
+    status TEXT NOT NULL DEFAULT 'pending',
+    requested_by INTEGER,
+    reviewed_by INTEGER,
+    reviewed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (requested_by) REFERENCES users (id) ON DELETE SET NULL,
+    FOREIGN KEY (reviewed_by) REFERENCES users (id) ON DELETE SET NULL
+);
+
+-- One entry per package and one per major version of it
+CREATE UNIQUE INDEX idx_quarantined_packages_name_major ON quarantined_packages (name, IFNULL(major, -1));
+CREATE INDEX idx_quarantined_packages_status ON quarantined_packages (status);
@@ -0,0 +1,9 @@
+CREATE TABLE allowed_packages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pattern TEXT NOT NULL UNIQUE,
+    is_regex BOOLEAN NOT NULL DEFAULT 0,
+    reason TEXT,
+    created_by INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE SET NULL
+);
@@ -0,0 +1,10 @@
+CREATE TABLE version_downloads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    day DATE NOT NULL,
+    downloads INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (package_name, version, day)
+);
+
+CREATE INDEX idx_version_downloads_package_day ON version_downloads (package_name, day);
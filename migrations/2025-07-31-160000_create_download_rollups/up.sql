@@ -0,0 +1,11 @@
+CREATE TABLE download_rollups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    period TEXT NOT NULL,
+    period_start DATE NOT NULL,
+    downloads INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (package_name, version, period, period_start)
+);
+
+CREATE INDEX idx_download_rollups_package_start ON download_rollups (package_name, period_start);
@@ -0,0 +1 @@
+DROP TABLE tarball_entries;
@@ -0,0 +1,10 @@
+-- Files inside a version's tarball, indexed the first time they are listed
+CREATE TABLE tarball_entries (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    package_version_id INTEGER NOT NULL,
+    path TEXT NOT NULL, -- relative to the package root, without the `package/` prefix
+    size_bytes BIGINT NOT NULL,
+    content_type TEXT NOT NULL,
+    FOREIGN KEY (package_version_id) REFERENCES package_versions (id) ON DELETE CASCADE,
+    UNIQUE (package_version_id, path)
+);
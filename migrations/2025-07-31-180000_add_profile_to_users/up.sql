@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN display_name TEXT;
+ALTER TABLE users ADD COLUMN avatar_url TEXT;
+-- JSON object of link labels to URLs, e.g. {"github": "https://github.com/jkuri"}
+ALTER TABLE users ADD COLUMN links TEXT;
@@ -0,0 +1,28 @@
+CREATE TABLE notification_subscriptions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    events TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    endpoint TEXT,
+    frequency TEXT NOT NULL DEFAULT 'immediate',
+    last_sent_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_notification_subscriptions_user_id ON notification_subscriptions (user_id);
+
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    subscription_id INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    package_name TEXT NOT NULL,
+    version TEXT,
+    message TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at TIMESTAMP,
+    FOREIGN KEY (subscription_id) REFERENCES notification_subscriptions (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_notifications_pending ON notifications (subscription_id, sent_at);
@@ -0,0 +1,13 @@
+CREATE TABLE client_certificates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    fingerprint TEXT UNIQUE,
+    subject TEXT UNIQUE,
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    CHECK ((fingerprint IS NULL) <> (subject IS NULL))
+);
+
+CREATE INDEX idx_client_certificates_user_id ON client_certificates (user_id);
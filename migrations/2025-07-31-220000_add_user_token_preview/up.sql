@@ -0,0 +1,2 @@
+-- Tokens are stored hashed, the preview keeps the beginning of the token recognizable
+ALTER TABLE user_tokens ADD COLUMN token_preview TEXT NOT NULL DEFAULT '';
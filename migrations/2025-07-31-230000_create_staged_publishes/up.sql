@@ -0,0 +1,11 @@
+CREATE TABLE staged_publishes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    publisher_id INTEGER NOT NULL,
+    request_path TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (publisher_id) REFERENCES users (id) ON DELETE CASCADE,
+    UNIQUE (package_name, version)
+);
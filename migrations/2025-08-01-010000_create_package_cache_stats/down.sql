@@ -0,0 +1 @@
+DROP TABLE package_cache_stats;
@@ -0,0 +1,12 @@
+CREATE TABLE package_cache_stats (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    package_name TEXT NOT NULL,
+    day DATE NOT NULL,
+    hits BIGINT NOT NULL DEFAULT 0,
+    misses BIGINT NOT NULL DEFAULT 0,
+    cache_bytes BIGINT NOT NULL DEFAULT 0,
+    upstream_bytes BIGINT NOT NULL DEFAULT 0,
+    UNIQUE (package_name, day)
+);
+
+CREATE INDEX idx_package_cache_stats_day ON package_cache_stats (day);
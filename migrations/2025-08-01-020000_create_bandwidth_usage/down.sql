@@ -0,0 +1 @@
+DROP TABLE bandwidth_usage;
@@ -0,0 +1,12 @@
+-- user_id and token_id are 0 for anonymous requests and requests without a token, so that
+-- the unique constraint holds for them too
+CREATE TABLE bandwidth_usage (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    day DATE NOT NULL,
+    package_name TEXT NOT NULL,
+    user_id INTEGER NOT NULL DEFAULT 0,
+    token_id INTEGER NOT NULL DEFAULT 0,
+    bytes BIGINT NOT NULL DEFAULT 0,
+    requests BIGINT NOT NULL DEFAULT 0,
+    UNIQUE (day, package_name, user_id, token_id)
+);
@@ -0,0 +1,3 @@
+-- Auth provider plugin that created the account, NULL for local accounts. Plugin logins
+-- only map onto accounts of the same plugin.
+ALTER TABLE users ADD COLUMN auth_plugin TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE package_versions DROP COLUMN registry_signature;
+ALTER TABLE package_versions DROP COLUMN registry_key_id;
@@ -0,0 +1,4 @@
+-- Registry key signature of `name@version:integrity`, made once when the version is first
+-- served and cleared when its integrity changes
+ALTER TABLE package_versions ADD COLUMN registry_key_id TEXT;
+ALTER TABLE package_versions ADD COLUMN registry_signature TEXT;
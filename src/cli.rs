@@ -0,0 +1,311 @@
+use crate::services::repository_import::RepositoryImportOptions;
+use crate::services::seed::SeedOptions;
+use crate::services::verdaccio::VerdaccioImportOptions;
+use clap::{Args, Parser, Subcommand};
+
+/// The `clef` command line. Without a command it runs the registry.
+#[derive(Parser, Debug, Clone, PartialEq)]
+#[command(
+    name = "clef",
+    version,
+    about = "A private npm registry and caching proxy",
+    after_help = "All commands read the same CLEF_* environment variables and clef.toml as the \
+                  server."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Cli {
+    /// The command to run, `serve` when none was given
+    pub fn into_command(self) -> Command {
+        self.command.unwrap_or(Command::Serve)
+    }
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Run the registry (default)
+    Serve,
+    /// Manage user accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Manage access tokens
+    #[command(subcommand)]
+    Token(TokenCommand),
+    /// Find cache files without records, delete expired tokens
+    ///
+    /// Reports cache files that have no database record and records whose file is missing,
+    /// and deletes revoked and expired tokens. Files changed in the last 10 minutes are
+    /// skipped.
+    Gc {
+        /// Delete orphaned files and the records of missing proxied files
+        #[arg(long)]
+        delete: bool,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check stored tarballs against their recorded size and checksums
+    ///
+    /// Checks every stored file exists with its recorded size and that tarballs match the
+    /// shasum and integrity of their version. Exits with status 1 when problems are found.
+    Verify {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run every consistency check and print a JSON report
+    ///
+    /// Checks stored files against their sizes and checksums, the cache directory against
+    /// the database, and database rows that reference missing rows, without changing
+    /// anything. Prints a JSON report and exits with status 1 when a check fails.
+    Doctor,
+    /// Apply pending database migrations
+    ///
+    /// Applies pending database migrations and exits. The server also applies them on start,
+    /// unless CLEF_STRICT_MIGRATIONS is set, then it refuses to start until they are applied.
+    Migrate {
+        /// Print the schema version and pending migrations without applying them
+        #[arg(long)]
+        status: bool,
+    },
+    /// Populate the registry with synthetic packages for benchmarking
+    ///
+    /// Populates the database and cache with synthetic packages for benchmarking.
+    Seed(SeedOptions),
+    /// Import packages from another registry
+    #[command(subcommand)]
+    Import(ImportCommand),
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum UserCommand {
+    /// Create a user account
+    ///
+    /// Creates a user account, regardless of CLEF_REGISTRATION_ENABLED.
+    Create {
+        username: String,
+        /// Email address (default: <USERNAME>@localhost)
+        #[arg(long)]
+        email: Option<String>,
+        #[command(flatten)]
+        password: PasswordArgs,
+        /// Grant the admin role
+        #[arg(long)]
+        admin: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum TokenCommand {
+    /// Issue an access token for a user
+    ///
+    /// Issues an access token for an existing user and prints it.
+    Create {
+        username: String,
+        /// Create a publish token that never expires, instead of a 30 day auth token
+        #[arg(long)]
+        publish: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+pub enum ImportCommand {
+    /// Import the packages and users of a Verdaccio installation
+    ///
+    /// Imports the packages of a Verdaccio storage directory, with their versions, tarballs
+    /// and dist-tags, and the users of its htpasswd file. Existing users and versions are
+    /// kept.
+    Verdaccio(VerdaccioImportOptions),
+    /// Import the packages of a Nexus npm repository
+    ///
+    /// Copies the packages of a Nexus npm repository into this registry, with their
+    /// versions, tarballs and dist-tags. Versions that exist here are kept.
+    Nexus(RepositoryImportOptions),
+    /// Import the packages of an Artifactory npm repository
+    ///
+    /// Copies the packages of an Artifactory npm repository into this registry, with their
+    /// versions, tarballs and dist-tags. Versions that exist here are kept.
+    Artifactory(RepositoryImportOptions),
+}
+
+/// Where the user gets the password of a new account from, exactly one is required
+#[derive(Args, Debug, Clone, PartialEq)]
+#[group(required = true, multiple = false)]
+pub struct PasswordArgs {
+    /// Password, visible to other local users in the process list
+    #[arg(long, value_name = "PASS")]
+    password: Option<String>,
+    /// Read the password from the first line of stdin instead
+    #[arg(long)]
+    password_stdin: bool,
+}
+
+/// Where the user gets the password of a new account from
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordSource {
+    Argument(String),
+    Stdin,
+}
+
+impl PasswordArgs {
+    pub fn source(self) -> PasswordSource {
+        match self.password {
+            Some(password) => PasswordSource::Argument(password),
+            None => PasswordSource::Stdin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::repository_import::RepositoryKind;
+    use clap::CommandFactory;
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> Result<Command, clap::Error> {
+        Cli::try_parse_from(std::iter::once("clef").chain(args.iter().copied()))
+            .map(Cli::into_command)
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_command_parsing() {
+        assert_eq!(parse(&[]).unwrap(), Command::Serve);
+        assert_eq!(parse(&["serve"]).unwrap(), Command::Serve);
+        assert_eq!(
+            parse(&["user", "create", "ci", "--password", "pw", "--admin"]).unwrap(),
+            Command::User(UserCommand::Create {
+                username: "ci".to_string(),
+                email: None,
+                password: PasswordArgs {
+                    password: Some("pw".to_string()),
+                    password_stdin: false,
+                },
+                admin: true,
+            })
+        );
+        assert_eq!(
+            parse(&["token", "create", "--publish", "ci"]).unwrap(),
+            Command::Token(TokenCommand::Create {
+                username: "ci".to_string(),
+                publish: true,
+            })
+        );
+        assert_eq!(
+            parse(&["verify", "--json"]).unwrap(),
+            Command::Verify { json: true }
+        );
+        assert_eq!(parse(&["doctor"]).unwrap(), Command::Doctor);
+        assert_eq!(
+            parse(&["migrate", "--status"]).unwrap(),
+            Command::Migrate { status: true }
+        );
+        assert_eq!(
+            parse(&["gc", "--delete"]).unwrap(),
+            Command::Gc {
+                delete: true,
+                json: false,
+            }
+        );
+        assert!(matches!(
+            parse(&["seed", "--packages", "3"]).unwrap(),
+            Command::Seed(SeedOptions { packages: 3, .. })
+        ));
+        assert!(matches!(
+            parse(&["import", "verdaccio", "--storage", "storage"]).unwrap(),
+            Command::Import(ImportCommand::Verdaccio(VerdaccioImportOptions {
+                include_cached: false,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            parse(&[
+                "import",
+                "nexus",
+                "--url",
+                "https://nexus",
+                "--repository",
+                "npm"
+            ])
+            .unwrap(),
+            Command::Import(ImportCommand::Nexus(RepositoryImportOptions {
+                kind: RepositoryKind::Nexus,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_password_source() {
+        let password = |args: &[&str]| match parse(args).unwrap() {
+            Command::User(UserCommand::Create { password, .. }) => password.source(),
+            other => panic!("Unexpected command {other:?}"),
+        };
+
+        assert_eq!(
+            password(&["user", "create", "ci", "--password", "pw"]),
+            PasswordSource::Argument("pw".to_string())
+        );
+        assert_eq!(
+            password(&["user", "create", "ci", "--password-stdin"]),
+            PasswordSource::Stdin
+        );
+    }
+
+    #[test]
+    fn test_command_parsing_errors() {
+        let kind = |args: &[&str]| parse(args).unwrap_err().kind();
+
+        assert_eq!(kind(&["--help"]), ErrorKind::DisplayHelp);
+        assert_eq!(kind(&["gc", "-h"]), ErrorKind::DisplayHelp);
+        assert_eq!(
+            kind(&["user", "create", "--password", "pw"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            kind(&["user", "create", "ci"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            kind(&[
+                "user",
+                "create",
+                "ci",
+                "--password",
+                "pw",
+                "--password-stdin"
+            ]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            kind(&["user", "create", "ci", "other", "--password-stdin"]),
+            ErrorKind::UnknownArgument
+        );
+        assert_eq!(
+            kind(&["token", "create", "ci", "--forever"]),
+            ErrorKind::UnknownArgument
+        );
+        assert_eq!(kind(&["migrate", "now"]), ErrorKind::UnknownArgument);
+        assert_eq!(
+            kind(&["user"]),
+            ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        );
+        assert_eq!(
+            kind(&["import"]),
+            ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        );
+        assert_eq!(
+            kind(&["import", "artifactory"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(kind(&["start"]), ErrorKind::InvalidSubcommand);
+    }
+}
@@ -0,0 +1,237 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::advisory::*;
+use crate::schema::{advisories, packages};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+/// Security advisory database operations
+pub struct AdvisoryOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> AdvisoryOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Gets the advisories of the given packages
+    pub fn get_advisories_for_packages(
+        &self,
+        package_names: &[&str],
+    ) -> Result<Vec<Advisory>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut found = Vec::new();
+        // Stay well below SQLite's bound parameter limit
+        for chunk in package_names.chunks(500) {
+            found.extend(
+                advisories::table
+                    .filter(advisories::package_name.eq_any(chunk))
+                    .order(advisories::id.asc())
+                    .load::<Advisory>(&mut conn)?,
+            );
+        }
+
+        Ok(found)
+    }
+
+    /// Last modification time of every advisory from a source, keyed by advisory id
+    pub fn get_advisory_versions(
+        &self,
+        source: &str,
+    ) -> Result<HashMap<String, Option<NaiveDateTime>>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let rows: Vec<(String, Option<NaiveDateTime>)> = advisories::table
+            .filter(advisories::source.eq(source))
+            .select((advisories::advisory_id, advisories::modified_at))
+            .load(&mut conn)?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Replaces all rows of an advisory with the given ones, one per affected package
+    pub fn replace_advisory(
+        &self,
+        source: &str,
+        advisory_id: &str,
+        rows: &[NewAdvisory],
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            diesel::delete(
+                advisories::table
+                    .filter(advisories::source.eq(source))
+                    .filter(advisories::advisory_id.eq(advisory_id)),
+            )
+            .execute(conn)?;
+
+            diesel::insert_into(advisories::table)
+                .values(rows)
+                .execute(conn)?;
+
+            Ok(())
+        })
+    }
+
+    /// Deletes all rows of an advisory. Returns the number of deleted rows.
+    pub fn delete_advisory(
+        &self,
+        source: &str,
+        advisory_id: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(
+            advisories::table
+                .filter(advisories::source.eq(source))
+                .filter(advisories::advisory_id.eq(advisory_id)),
+        )
+        .execute(&mut conn)
+    }
+
+    /// Advisory count, number of affected packages and the time of the last sync of a source
+    pub fn get_advisory_stats(
+        &self,
+        source: &str,
+    ) -> Result<(i64, i64, Option<NaiveDateTime>), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let count: i64 = advisories::table
+            .filter(advisories::source.eq(source))
+            .count()
+            .get_result(&mut conn)?;
+        let packages: i64 = advisories::table
+            .filter(advisories::source.eq(source))
+            .select(diesel::dsl::count_distinct(advisories::package_name))
+            .first(&mut conn)?;
+        let last_synced_at: Option<NaiveDateTime> = advisories::table
+            .filter(advisories::source.eq(source))
+            .select(diesel::dsl::max(advisories::synced_at))
+            .first(&mut conn)?;
+
+        Ok((count, packages, last_synced_at))
+    }
+
+    /// Lists the advisories of a source, newest first
+    pub fn list_advisories(&self, source: &str) -> Result<Vec<Advisory>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        advisories::table
+            .filter(advisories::source.eq(source))
+            .order(advisories::id.desc())
+            .load::<Advisory>(&mut conn)
+    }
+
+    pub fn create_advisory(
+        &self,
+        new_advisory: &NewAdvisory,
+    ) -> Result<Advisory, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(advisories::table)
+            .values(new_advisory)
+            .returning(Advisory::as_returning())
+            .get_result(&mut conn)
+    }
+
+    /// Updates an advisory of a source, returns `None` if there is no such advisory
+    pub fn update_advisory(
+        &self,
+        id: i32,
+        source: &str,
+        changes: &NewAdvisory,
+    ) -> Result<Option<Advisory>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(
+            advisories::table
+                .filter(advisories::id.eq(id))
+                .filter(advisories::source.eq(source)),
+        )
+        .set(changes)
+        .returning(Advisory::as_returning())
+        .get_result(&mut conn)
+        .optional()
+    }
+
+    /// Deletes an advisory row of a source. Returns the number of deleted rows.
+    pub fn delete_advisory_by_id(
+        &self,
+        id: i32,
+        source: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(
+            advisories::table
+                .filter(advisories::id.eq(id))
+                .filter(advisories::source.eq(source)),
+        )
+        .execute(&mut conn)
+    }
+
+    /// Names of packages proxied from the upstream registry. Locally published packages
+    /// aren't in public advisory databases.
+    pub fn get_upstream_package_names(&self) -> Result<Vec<String>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        packages::table
+            .filter(packages::author_id.is_null())
+            .select(packages::name)
+            .order(packages::name.asc())
+            .load::<String>(&mut conn)
+    }
+}
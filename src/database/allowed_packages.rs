@@ -0,0 +1,59 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::allowed_package::*;
+use crate::schema::allowed_packages;
+use diesel::prelude::*;
+
+/// Upstream allowlist database operations
+pub struct AllowedPackageOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> AllowedPackageOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Lists all allowlist entries ordered by pattern
+    pub fn list_allowed_packages(&self) -> Result<Vec<AllowedPackage>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        allowed_packages::table
+            .order(allowed_packages::pattern.asc())
+            .load::<AllowedPackage>(&mut conn)
+    }
+
+    /// Adds a allowlist entry
+    pub fn create_allowed_package(
+        &self,
+        entry: &NewAllowedPackage,
+    ) -> Result<AllowedPackage, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(allowed_packages::table)
+            .values(entry)
+            .get_result::<AllowedPackage>(&mut conn)
+    }
+
+    /// Deletes a allowlist entry. Returns the number of deleted rows.
+    pub fn delete_allowed_package(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(allowed_packages::table.filter(allowed_packages::id.eq(id)))
+            .execute(&mut conn)
+    }
+}
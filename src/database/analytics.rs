@@ -1,6 +1,12 @@
 use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::dashboard::RegistryTotals;
+use crate::models::downloads::*;
 use crate::models::package::*;
-use crate::schema::{package_files, package_versions, packages};
+use crate::schema::{
+    download_rollups, organizations, package_files, package_versions, packages, users,
+    version_downloads,
+};
+use chrono::NaiveDate;
 use diesel::prelude::*;
 use log::{debug, info};
 
@@ -18,6 +24,22 @@ impl<'a> AnalyticsOperations<'a> {
     pub fn get_popular_packages(
         &self,
         limit: i64,
+    ) -> Result<Vec<PopularPackage>, diesel::result::Error> {
+        self.popular_packages(limit, false)
+    }
+
+    /// Gets popular public packages, for lists shown to every reader
+    pub fn get_public_popular_packages(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PopularPackage>, diesel::result::Error> {
+        self.popular_packages(limit, true)
+    }
+
+    fn popular_packages(
+        &self,
+        limit: i64,
+        public_only: bool,
     ) -> Result<Vec<PopularPackage>, diesel::result::Error> {
         let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
             diesel::result::Error::DatabaseError(
@@ -26,8 +48,13 @@ impl<'a> AnalyticsOperations<'a> {
             )
         })?;
 
-        let results: Vec<(Package, PackageVersion, PackageFile)> = packages::table
+        let mut query = packages::table
             .inner_join(package_versions::table.inner_join(package_files::table))
+            .into_boxed();
+        if public_only {
+            query = query.filter(packages::visibility.eq(PackageVisibility::Public.to_string()));
+        }
+        let results: Vec<(Package, PackageVersion, PackageFile)> = query
             .order(package_files::access_count.desc())
             .load::<(Package, (PackageVersion, PackageFile))>(&mut conn)?
             .into_iter()
@@ -109,4 +136,419 @@ impl<'a> AnalyticsOperations<'a> {
 
         Ok((total_packages as usize, total_size_bytes))
     }
+
+    /// Numbers of packages, versions, users and organizations
+    pub fn get_registry_totals(&self) -> Result<RegistryTotals, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        Ok(RegistryTotals {
+            packages: packages::table.count().get_result(&mut conn)?,
+            versions: package_versions::table.count().get_result(&mut conn)?,
+            users: users::table.count().get_result(&mut conn)?,
+            organizations: organizations::table.count().get_result(&mut conn)?,
+        })
+    }
+
+    /// Bytes stored for packages published by a user outside of any organization
+    pub fn get_user_storage_usage(
+        &self,
+        user_id: i32,
+    ) -> Result<StorageUsage, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let files = packages::table
+            .inner_join(package_versions::table.inner_join(package_files::table))
+            .filter(packages::author_id.eq(user_id))
+            .filter(packages::organization_id.is_null())
+            .select((
+                packages::id,
+                package_versions::id,
+                package_files::size_bytes,
+            ))
+            .load::<(i32, i32, i64)>(&mut conn)?;
+
+        Ok(StorageUsage::from_files(&files))
+    }
+
+    /// Bytes stored for packages of an organization
+    pub fn get_organization_storage_usage(
+        &self,
+        organization_id: i32,
+    ) -> Result<StorageUsage, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let files = packages::table
+            .inner_join(package_versions::table.inner_join(package_files::table))
+            .filter(packages::organization_id.eq(organization_id))
+            .select((
+                packages::id,
+                package_versions::id,
+                package_files::size_bytes,
+            ))
+            .load::<(i32, i32, i64)>(&mut conn)?;
+
+        Ok(StorageUsage::from_files(&files))
+    }
+
+    /// Counts a download of a package version on a day
+    pub fn record_version_download(
+        &self,
+        package_name: &str,
+        version: &str,
+        day: NaiveDate,
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(version_downloads::table)
+            .values(&NewVersionDownload {
+                package_name: package_name.to_string(),
+                version: version.to_string(),
+                day,
+                downloads: 1,
+            })
+            .on_conflict((
+                version_downloads::package_name,
+                version_downloads::version,
+                version_downloads::day,
+            ))
+            .do_update()
+            .set(version_downloads::downloads.eq(version_downloads::downloads + 1))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Daily download counts of the versions of a package between two days, inclusive
+    pub fn get_version_downloads(
+        &self,
+        package_name: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<VersionDownload>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        version_downloads::table
+            .filter(version_downloads::package_name.eq(package_name))
+            .filter(version_downloads::day.between(from, to))
+            .order((
+                version_downloads::day.asc(),
+                version_downloads::version.asc(),
+            ))
+            .select(VersionDownload::as_select())
+            .load(&mut conn)
+    }
+
+    /// Week and month aggregates of the versions of a package starting between two days,
+    /// inclusive
+    pub fn get_download_rollups(
+        &self,
+        package_name: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DownloadRollup>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        download_rollups::table
+            .filter(download_rollups::package_name.eq(package_name))
+            .filter(download_rollups::period_start.between(from, to))
+            .order((
+                download_rollups::period_start.asc(),
+                download_rollups::version.asc(),
+            ))
+            .select(DownloadRollup::as_select())
+            .load(&mut conn)
+    }
+
+    /// Daily download counts of all packages before a day
+    pub fn get_version_downloads_before(
+        &self,
+        before: NaiveDate,
+    ) -> Result<Vec<VersionDownload>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        version_downloads::table
+            .filter(version_downloads::day.lt(before))
+            .select(VersionDownload::as_select())
+            .load(&mut conn)
+    }
+
+    /// Aggregates of a period of all packages starting before a day
+    pub fn get_download_rollups_before(
+        &self,
+        period: &str,
+        before: NaiveDate,
+    ) -> Result<Vec<DownloadRollup>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        download_rollups::table
+            .filter(download_rollups::period.eq(period))
+            .filter(download_rollups::period_start.lt(before))
+            .select(DownloadRollup::as_select())
+            .load(&mut conn)
+    }
+
+    /// Adds aggregates and removes the rows they were built from in one transaction. The
+    /// rows removed are the daily counts before `before`, or with `from_period` the
+    /// aggregates of that period starting before it.
+    pub fn roll_up_downloads(
+        &self,
+        from_period: Option<&str>,
+        before: NaiveDate,
+        rollups: &[NewDownloadRollup],
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            for rollup in rollups {
+                diesel::insert_into(download_rollups::table)
+                    .values(rollup)
+                    .on_conflict((
+                        download_rollups::package_name,
+                        download_rollups::version,
+                        download_rollups::period,
+                        download_rollups::period_start,
+                    ))
+                    .do_update()
+                    .set(
+                        download_rollups::downloads
+                            .eq(download_rollups::downloads + rollup.downloads),
+                    )
+                    .execute(conn)?;
+            }
+
+            match from_period {
+                Some(period) => diesel::delete(
+                    download_rollups::table
+                        .filter(download_rollups::period.eq(period))
+                        .filter(download_rollups::period_start.lt(before)),
+                )
+                .execute(conn),
+                None => diesel::delete(
+                    version_downloads::table.filter(version_downloads::day.lt(before)),
+                )
+                .execute(conn),
+            }
+        })
+    }
+
+    /// Deletes daily counts and aggregates older than a day. Returns the number of rows
+    /// deleted.
+    pub fn delete_download_stats_before(
+        &self,
+        before: NaiveDate,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            let daily =
+                diesel::delete(version_downloads::table.filter(version_downloads::day.lt(before)))
+                    .execute(conn)?;
+            let rollups = diesel::delete(
+                download_rollups::table.filter(download_rollups::period_start.lt(before)),
+            )
+            .execute(conn)?;
+            Ok(daily + rollups)
+        })
+    }
+
+    /// Downloads per package since a day, from daily counts and aggregates starting since
+    pub fn get_downloads_since(
+        &self,
+        package_names: &[String],
+        since: NaiveDate,
+    ) -> Result<std::collections::HashMap<String, i64>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let daily = version_downloads::table
+            .filter(version_downloads::package_name.eq_any(package_names))
+            .filter(version_downloads::day.ge(since))
+            .select((
+                version_downloads::package_name,
+                version_downloads::downloads,
+            ))
+            .load::<(String, i64)>(&mut conn)?;
+        let rollups = download_rollups::table
+            .filter(download_rollups::package_name.eq_any(package_names))
+            .filter(download_rollups::period_start.ge(since))
+            .select((download_rollups::package_name, download_rollups::downloads))
+            .load::<(String, i64)>(&mut conn)?;
+
+        let mut downloads = std::collections::HashMap::new();
+        for (name, count) in daily.into_iter().chain(rollups) {
+            *downloads.entry(name).or_insert(0) += count;
+        }
+        Ok(downloads)
+    }
+
+    /// Packages with the most downloads since a day, most downloaded first
+    pub fn get_most_downloaded_since(
+        &self,
+        since: NaiveDate,
+        limit: i64,
+    ) -> Result<Vec<(String, i64)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let daily = version_downloads::table
+            .filter(version_downloads::day.ge(since))
+            .select((
+                version_downloads::package_name,
+                version_downloads::downloads,
+            ))
+            .load::<(String, i64)>(&mut conn)?;
+
+        let mut downloads: std::collections::HashMap<String, i64> =
+            std::collections::HashMap::new();
+        for (name, count) in daily {
+            *downloads.entry(name).or_insert(0) += count;
+        }
+        let mut packages: Vec<(String, i64)> = downloads.into_iter().collect();
+        packages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        packages.truncate(limit.max(0) as usize);
+        Ok(packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DatabaseService;
+    use crate::models::package::{NewPackageVisibilityChange, PackageVisibility, PopularPackage};
+    use chrono::Duration;
+
+    #[test]
+    fn test_public_popular_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+
+        for name in ["open-pkg", "secret-pkg"] {
+            let package = database.create_or_get_package(name, None, None).unwrap();
+            let version = database
+                .create_or_get_package_version(package.id, "1.0.0")
+                .unwrap();
+            let file = database
+                .create_or_update_package_file(
+                    version.id,
+                    &format!("{name}-1.0.0.tgz"),
+                    10,
+                    "",
+                    "",
+                    None,
+                    None,
+                )
+                .unwrap();
+            database.update_file_access_info(file.id).unwrap();
+        }
+        let secret = database
+            .create_or_get_package("secret-pkg", None, None)
+            .unwrap();
+        database
+            .set_package_visibility(&NewPackageVisibilityChange {
+                package_id: secret.id,
+                package_name: secret.name.clone(),
+                old_visibility: PackageVisibility::Public.to_string(),
+                new_visibility: PackageVisibility::Private.to_string(),
+                changed_by: None,
+                reason: None,
+                created_at: chrono::Utc::now().naive_utc(),
+            })
+            .unwrap();
+
+        let names = |packages: Vec<PopularPackage>| {
+            let mut names: Vec<String> = packages.into_iter().map(|p| p.name).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(database.get_popular_packages(10).unwrap()),
+            ["open-pkg", "secret-pkg"]
+        );
+        assert_eq!(
+            names(database.get_public_popular_packages(10).unwrap()),
+            ["open-pkg"]
+        );
+    }
+
+    #[test]
+    fn test_most_downloaded_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+
+        let today = chrono::Utc::now().date_naive();
+        let last_month = today - Duration::days(30);
+        database.queue_version_download("lodash", "4.17.21", today);
+        database.queue_version_download("lodash", "4.17.20", today);
+        database.queue_version_download("react", "18.3.1", today);
+        database.queue_version_download("react", "18.3.1", last_month);
+        database.queue_version_download("react", "18.3.1", last_month);
+        database.flush_counters().unwrap();
+
+        let since = today - Duration::days(7);
+        assert_eq!(
+            database.get_most_downloaded_since(since, 10).unwrap(),
+            [("lodash".to_string(), 2), ("react".to_string(), 1)]
+        );
+        assert_eq!(
+            database.get_most_downloaded_since(since, 1).unwrap().len(),
+            1
+        );
+    }
 }
@@ -0,0 +1,57 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::signing::*;
+use crate::schema::package_attestations;
+use diesel::prelude::*;
+
+/// Provenance attestation database operations
+pub struct AttestationOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> AttestationOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores an attestation, replacing an earlier one of the same predicate type
+    pub fn create_package_attestation(
+        &self,
+        new_attestation: &NewPackageAttestation,
+    ) -> Result<PackageAttestation, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(package_attestations::table)
+            .values(new_attestation)
+            .on_conflict((
+                package_attestations::package_version_id,
+                package_attestations::predicate_type,
+            ))
+            .do_update()
+            .set(package_attestations::bundle.eq(&new_attestation.bundle))
+            .returning(PackageAttestation::as_returning())
+            .get_result(&mut conn)
+    }
+
+    /// Gets all attestations of a package version
+    pub fn get_package_attestations(
+        &self,
+        package_version_id: i32,
+    ) -> Result<Vec<PackageAttestation>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        package_attestations::table
+            .filter(package_attestations::package_version_id.eq(package_version_id))
+            .order(package_attestations::id.asc())
+            .load::<PackageAttestation>(&mut conn)
+    }
+}
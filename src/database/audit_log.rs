@@ -0,0 +1,117 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::audit::*;
+use crate::schema::{audit_log, users};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Audit log database operations
+pub struct AuditLogOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> AuditLogOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Appends an entry to the audit log
+    pub fn create_audit_log_entry(
+        &self,
+        entry: &NewAuditLogEntry,
+    ) -> Result<AuditLogEntry, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(audit_log::table)
+            .values(entry)
+            .get_result::<AuditLogEntry>(&mut conn)
+    }
+
+    /// Latest audit log entries of an organization with the username and role of
+    /// each actor, newest first
+    pub fn list_organization_audit_log(
+        &self,
+        organization_id: i32,
+        limit: i64,
+    ) -> Result<Vec<AuditLogRow>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        audit_log::table
+            .left_join(users::table)
+            .filter(audit_log::organization_id.eq(organization_id))
+            .order((audit_log::created_at.desc(), audit_log::id.desc()))
+            .limit(limit)
+            .select((
+                AuditLogEntry::as_select(),
+                (users::username, users::role).nullable(),
+            ))
+            .load::<AuditLogRow>(&mut conn)
+    }
+
+    /// Latest audit log entries of a package with the username and role of
+    /// each actor, newest first
+    pub fn list_package_audit_log(
+        &self,
+        package_name: &str,
+        limit: i64,
+    ) -> Result<Vec<AuditLogRow>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        audit_log::table
+            .left_join(users::table)
+            .filter(audit_log::package_name.eq(package_name))
+            .order((audit_log::created_at.desc(), audit_log::id.desc()))
+            .limit(limit)
+            .select((
+                AuditLogEntry::as_select(),
+                (users::username, users::role).nullable(),
+            ))
+            .load::<AuditLogRow>(&mut conn)
+    }
+
+    /// Publishes per user to the packages of an organization, with the time of the latest
+    pub fn get_organization_publish_activity(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<(i32, i64, NaiveDateTime)>, diesel::result::Error> {
+        use diesel::dsl::count_star;
+
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let rows = audit_log::table
+            .filter(audit_log::organization_id.eq(organization_id))
+            .filter(audit_log::action.eq(PUBLISH_ACTION))
+            .filter(audit_log::actor_id.is_not_null())
+            .group_by(audit_log::actor_id)
+            .select((
+                audit_log::actor_id,
+                count_star(),
+                diesel::dsl::max(audit_log::created_at),
+            ))
+            .load::<(Option<i32>, i64, Option<NaiveDateTime>)>(&mut conn)?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(actor, count, latest)| Some((actor?, count, latest?)))
+            .collect())
+    }
+}
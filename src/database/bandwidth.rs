@@ -0,0 +1,77 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::bandwidth::BandwidthUsageRow;
+use crate::schema::{bandwidth_usage, organizations, packages, user_tokens, users};
+use chrono::NaiveDate;
+use diesel::prelude::*;
+
+/// Bytes served per package, user and token
+pub struct BandwidthOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> BandwidthOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Usage between two days (inclusive) with the names of users, tokens and the
+    /// organizations owning the packages
+    pub fn get_usage(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<BandwidthUsageRow>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        bandwidth_usage::table
+            .left_join(users::table.on(users::id.eq(bandwidth_usage::user_id)))
+            .left_join(user_tokens::table.on(user_tokens::id.eq(bandwidth_usage::token_id)))
+            .left_join(packages::table.on(packages::name.eq(bandwidth_usage::package_name)))
+            .left_join(
+                organizations::table.on(organizations::id.nullable().eq(packages::organization_id)),
+            )
+            .filter(bandwidth_usage::day.between(from, to))
+            .select((
+                bandwidth_usage::package_name,
+                bandwidth_usage::user_id,
+                bandwidth_usage::token_id,
+                bandwidth_usage::bytes,
+                bandwidth_usage::requests,
+                users::username.nullable(),
+                user_tokens::token_preview.nullable(),
+                organizations::name.nullable(),
+            ))
+            .load(&mut conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DatabaseService;
+
+    #[test]
+    fn test_usage_adds_up_flushed_counters() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+
+        database.queue_bandwidth("lodash", None, None, 100);
+        database.flush_counters().unwrap();
+        database.queue_bandwidth("lodash", None, None, 50);
+        database.queue_bandwidth("lodash", Some(7), Some(3), 10);
+        database.flush_counters().unwrap();
+
+        let today = chrono::Utc::now().date_naive();
+        let mut usage = database.get_bandwidth_usage(today, today).unwrap();
+        usage.sort_by_key(|row| row.user_id);
+        assert_eq!(usage.len(), 2);
+        assert_eq!((usage[0].bytes, usage[0].requests), (150, 2));
+        assert_eq!((usage[1].user_id, usage[1].token_id), (7, 3));
+        assert_eq!(usage[1].username, None);
+        assert_eq!(usage[1].organization, None);
+    }
+}
@@ -0,0 +1,58 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::blocked_name::*;
+use crate::schema::blocked_names;
+use diesel::prelude::*;
+
+/// Package name blocklist database operations
+pub struct BlockedNameOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> BlockedNameOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Lists all blocklist entries ordered by pattern
+    pub fn list_blocked_names(&self) -> Result<Vec<BlockedName>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        blocked_names::table
+            .order(blocked_names::pattern.asc())
+            .load::<BlockedName>(&mut conn)
+    }
+
+    /// Adds a blocklist entry
+    pub fn create_blocked_name(
+        &self,
+        entry: &NewBlockedName,
+    ) -> Result<BlockedName, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(blocked_names::table)
+            .values(entry)
+            .get_result::<BlockedName>(&mut conn)
+    }
+
+    /// Deletes a blocklist entry. Returns the number of deleted rows.
+    pub fn delete_blocked_name(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(blocked_names::table.filter(blocked_names::id.eq(id))).execute(&mut conn)
+    }
+}
@@ -1,7 +1,9 @@
 use super::connection::{DbPool, get_connection_with_retry};
-use crate::models::cache::{CacheStatsRecord, NewCacheStatsRecord, UpdateCacheStatsRecord};
-use crate::schema::cache_stats;
-use chrono::Utc;
+use crate::models::cache::{
+    CacheStatsRecord, NewCacheStatsRecord, PackageCacheStat, UpdateCacheStatsRecord,
+};
+use crate::schema::{cache_stats, package_cache_stats};
+use chrono::{NaiveDate, Utc};
 use diesel::prelude::*;
 
 /// Cache statistics database operations
@@ -151,4 +153,34 @@ impl<'a> CacheStatsOperations<'a> {
 
         Ok(())
     }
+
+    /// Daily cache stats between two days (inclusive), of one package or of all of them
+    pub fn get_package_cache_stats(
+        &self,
+        package_name: Option<&str>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<PackageCacheStat>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = package_cache_stats::table
+            .filter(package_cache_stats::day.between(from, to))
+            .into_boxed();
+        if let Some(package_name) = package_name {
+            query = query.filter(package_cache_stats::package_name.eq(package_name));
+        }
+
+        query
+            .order((
+                package_cache_stats::package_name.asc(),
+                package_cache_stats::day.asc(),
+            ))
+            .select(PackageCacheStat::as_select())
+            .load(&mut conn)
+    }
 }
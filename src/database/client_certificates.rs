@@ -0,0 +1,127 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::client_certificate::*;
+use crate::schema::client_certificates;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Client certificate database operations
+pub struct ClientCertificateOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> ClientCertificateOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn create_client_certificate(
+        &self,
+        certificate: &NewClientCertificate,
+    ) -> Result<ClientCertificate, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(client_certificates::table)
+            .values(certificate)
+            .get_result::<ClientCertificate>(&mut conn)
+    }
+
+    /// Certificates of a user, oldest first
+    pub fn list_client_certificates(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<ClientCertificate>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        client_certificates::table
+            .filter(client_certificates::user_id.eq(user_id))
+            .order(client_certificates::id.asc())
+            .load::<ClientCertificate>(&mut conn)
+    }
+
+    /// The certificate matching a fingerprint, or failing that a subject
+    pub fn find_client_certificate(
+        &self,
+        fingerprint: Option<&str>,
+        subject: Option<&str>,
+    ) -> Result<Option<ClientCertificate>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        if let Some(fingerprint) = fingerprint {
+            let certificate = client_certificates::table
+                .filter(client_certificates::fingerprint.eq(fingerprint))
+                .first::<ClientCertificate>(&mut conn)
+                .optional()?;
+            if certificate.is_some() {
+                return Ok(certificate);
+            }
+        }
+
+        match subject {
+            Some(subject) => client_certificates::table
+                .filter(client_certificates::subject.eq(subject))
+                .first::<ClientCertificate>(&mut conn)
+                .optional(),
+            None => Ok(None),
+        }
+    }
+
+    /// Records a use of the certificate unless one was recorded after `stale_before`
+    pub fn touch_client_certificate(
+        &self,
+        id: i32,
+        now: NaiveDateTime,
+        stale_before: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(
+            client_certificates::table.find(id).filter(
+                client_certificates::last_used_at
+                    .is_null()
+                    .or(client_certificates::last_used_at.lt(stale_before)),
+            ),
+        )
+        .set(client_certificates::last_used_at.eq(now))
+        .execute(&mut conn)
+    }
+
+    pub fn delete_client_certificate(
+        &self,
+        user_id: i32,
+        id: i32,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(
+            client_certificates::table
+                .find(id)
+                .filter(client_certificates::user_id.eq(user_id)),
+        )
+        .execute(&mut conn)
+    }
+}
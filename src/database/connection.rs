@@ -1,26 +1,182 @@
+use crate::config::AppConfig;
+use crate::models::{AppliedMigration, DatabasePoolStats, SchemaStatus};
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use diesel::migration::MigrationSource;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool};
-use diesel::sqlite::SqliteConnection;
+use diesel::r2d2::event::{CheckoutEvent, TimeoutEvent};
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, HandleEvent, Pool};
+use diesel::sqlite::{Sqlite, SqliteConnection};
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
 use log::{info, warn};
+use std::collections::HashMap;
 use std::path::Path;
-use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
 pub type DbPool = Pool<ConnectionManager<SqliteConnection>>;
 pub type DbConnection = diesel::r2d2::PooledConnection<ConnectionManager<SqliteConnection>>;
 
+/// Pool size and locking behaviour of the SQLite database
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolOptions {
+    pub max_size: u32,
+    /// How long a connection waits for a lock held by another one before failing with
+    /// `database is locked`
+    pub busy_timeout_ms: u64,
+    /// Value of `PRAGMA synchronous`: OFF, NORMAL, FULL or EXTRA
+    pub synchronous: String,
+    /// Queries taking at least this long are logged, 0 disables it
+    pub slow_query_ms: u64,
+    /// Fail instead of applying pending migrations
+    pub strict_migrations: bool,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 20,
+            busy_timeout_ms: 60000,
+            synchronous: "NORMAL".to_string(),
+            slow_query_ms: 500,
+            strict_migrations: false,
+        }
+    }
+}
+
+impl PoolOptions {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let synchronous = config.db_synchronous.to_uppercase();
+        Self {
+            max_size: config.db_pool_size.max(1),
+            busy_timeout_ms: config.db_busy_timeout_ms,
+            synchronous: if matches!(synchronous.as_str(), "OFF" | "NORMAL" | "FULL" | "EXTRA") {
+                synchronous
+            } else {
+                warn!(
+                    "Unknown synchronous mode '{}', using NORMAL",
+                    config.db_synchronous
+                );
+                "NORMAL".to_string()
+            },
+            slow_query_ms: config.db_slow_query_ms,
+            strict_migrations: config.strict_migrations,
+        }
+    }
+}
+
+/// Counters of the connection pool and the queries run through it since startup
+#[derive(Debug, Default)]
+pub struct DbMetrics {
+    checkouts: AtomicU64,
+    checkout_wait_micros: AtomicU64,
+    max_checkout_wait_micros: AtomicU64,
+    checkout_timeouts: AtomicU64,
+    queries: AtomicU64,
+    slow_queries: AtomicU64,
+}
+
+impl DbMetrics {
+    /// The counters together with the current connections of `pool`
+    pub fn stats(&self, pool: &DbPool) -> DatabasePoolStats {
+        let state = pool.state();
+        let checkouts = self.checkouts.load(Ordering::Relaxed);
+        let wait_micros = self.checkout_wait_micros.load(Ordering::Relaxed);
+        DatabasePoolStats {
+            max_size: pool.max_size(),
+            connections: state.connections,
+            in_use: state.connections - state.idle_connections,
+            idle: state.idle_connections,
+            checkouts,
+            checkout_timeouts: self.checkout_timeouts.load(Ordering::Relaxed),
+            avg_wait_ms: if checkouts > 0 {
+                wait_micros as f64 / checkouts as f64 / 1000.0
+            } else {
+                0.0
+            },
+            max_wait_ms: self.max_checkout_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+            queries: self.queries.load(Ordering::Relaxed),
+            slow_queries: self.slow_queries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Records how long requests wait for a connection
+#[derive(Debug)]
+struct PoolEventHandler(Arc<DbMetrics>);
+
+impl HandleEvent for PoolEventHandler {
+    fn handle_checkout(&self, event: CheckoutEvent) {
+        let micros = event.duration().as_micros() as u64;
+        self.0.checkouts.fetch_add(1, Ordering::Relaxed);
+        self.0
+            .checkout_wait_micros
+            .fetch_add(micros, Ordering::Relaxed);
+        self.0
+            .max_checkout_wait_micros
+            .fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn handle_timeout(&self, _event: TimeoutEvent) {
+        self.0.checkout_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts the queries of a connection and logs the slow ones
+struct QueryTimer {
+    metrics: Arc<DbMetrics>,
+    slow_query: Duration,
+    started_at: Option<Instant>,
+}
+
+impl Instrumentation for QueryTimer {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } => self.started_at = Some(Instant::now()),
+            InstrumentationEvent::FinishQuery { query, .. } => {
+                let Some(started_at) = self.started_at.take() else {
+                    return;
+                };
+                self.metrics.queries.fetch_add(1, Ordering::Relaxed);
+
+                let elapsed = started_at.elapsed();
+                if self.slow_query.is_zero() || elapsed < self.slow_query {
+                    return;
+                }
+                self.metrics.slow_queries.fetch_add(1, Ordering::Relaxed);
+                // Bound values may be password hashes or tokens, only the statement is logged
+                let query = query.to_string();
+                let statement = query.split(" -- binds:").next().unwrap_or_default();
+                warn!("Slow query took {} ms: {statement}", elapsed.as_millis());
+            }
+            _ => {}
+        }
+    }
+}
+
 /// SQLite connection customizer to enable WAL mode and set pragmas for better concurrency
 #[derive(Debug)]
-pub struct SqliteConnectionCustomizer;
+pub struct SqliteConnectionCustomizer {
+    busy_timeout_ms: u64,
+    synchronous: String,
+    slow_query_ms: u64,
+    metrics: Arc<DbMetrics>,
+}
 
 impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqliteConnectionCustomizer {
     fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
         use diesel::sql_query;
 
+        conn.set_instrumentation(QueryTimer {
+            metrics: self.metrics.clone(),
+            slow_query: Duration::from_millis(self.slow_query_ms),
+            started_at: None,
+        });
+
         // Set busy timeout first (before WAL mode) - this one is critical
-        sql_query("PRAGMA busy_timeout = 60000") // 60 seconds
+        sql_query(format!("PRAGMA busy_timeout = {}", self.busy_timeout_ms))
             .execute(conn)
             .map_err(diesel::r2d2::Error::QueryError)?;
 
@@ -48,8 +204,10 @@ impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqliteConnec
             warn!("Failed to enable foreign keys: {e}");
         }
 
-        // Optimize for concurrent access - use NORMAL instead of FULL for better performance
-        if let Err(e) = sql_query("PRAGMA synchronous = NORMAL").execute(conn) {
+        // NORMAL is safe with WAL and avoids syncing on every commit
+        if let Err(e) =
+            sql_query(format!("PRAGMA synchronous = {}", self.synchronous)).execute(conn)
+        {
             warn!("Failed to set synchronous mode: {e}");
         }
 
@@ -78,7 +236,11 @@ impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqliteConnec
 }
 
 /// Creates a new database connection pool with optimized settings
-pub fn create_pool(database_url: &str) -> Result<DbPool, Box<dyn std::error::Error>> {
+pub fn create_pool(
+    database_url: &str,
+    options: &PoolOptions,
+    metrics: &Arc<DbMetrics>,
+) -> Result<DbPool, Box<dyn std::error::Error>> {
     // Ensure the database directory exists
     if let Some(parent) = Path::new(database_url).parent() {
         std::fs::create_dir_all(parent)?;
@@ -86,24 +248,108 @@ pub fn create_pool(database_url: &str) -> Result<DbPool, Box<dyn std::error::Err
 
     let manager = ConnectionManager::<SqliteConnection>::new(database_url);
     let pool = Pool::builder()
-        .max_size(20) // Increase pool size for better concurrency
-        .min_idle(Some(2)) // Keep some connections ready
+        .max_size(options.max_size)
+        .min_idle(Some(options.max_size.min(2))) // Keep some connections ready
         .connection_timeout(Duration::from_secs(60)) // Increase timeout
         .idle_timeout(Some(Duration::from_secs(300))) // 5 minutes idle timeout
         .max_lifetime(Some(Duration::from_secs(1800))) // 30 minutes max lifetime
-        .connection_customizer(Box::new(SqliteConnectionCustomizer))
+        .connection_customizer(Box::new(SqliteConnectionCustomizer {
+            busy_timeout_ms: options.busy_timeout_ms,
+            synchronous: options.synchronous.clone(),
+            slow_query_ms: options.slow_query_ms,
+            metrics: metrics.clone(),
+        }))
+        .event_handler(Box::new(PoolEventHandler(metrics.clone())))
         .build(manager)?;
 
     // Run migrations
     let mut conn = pool.get()?;
-    conn.run_pending_migrations(MIGRATIONS)
-        .map_err(|e| format!("Failed to run migrations: {e}"))?;
+    if options.strict_migrations {
+        let pending = schema_status(&mut conn)
+            .map_err(|e| format!("Failed to check migrations: {e}"))?
+            .pending;
+        if !pending.is_empty() {
+            return Err(format!(
+                "{} pending migrations ({}), run `clef migrate` first",
+                pending.len(),
+                pending.join(", ")
+            )
+            .into());
+        }
+    } else {
+        conn.run_pending_migrations(MIGRATIONS)
+            .map_err(|e| format!("Failed to run migrations: {e}"))?;
+    }
 
     info!("Database initialized successfully with WAL mode and optimized settings");
 
     Ok(pool)
 }
 
+/// Applies pending migrations over a single connection and returns the versions applied
+pub fn run_pending_migrations(
+    database_url: &str,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    if let Some(parent) = Path::new(database_url).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut conn = SqliteConnection::establish(database_url)?;
+    let applied = conn
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|e| format!("Failed to run migrations: {e}"))?;
+
+    Ok(applied.iter().map(ToString::to_string).collect())
+}
+
+#[derive(QueryableByName)]
+struct MigrationRow {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    version: String,
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    run_on: chrono::NaiveDateTime,
+}
+
+/// Migrations applied to the database and those still pending
+pub fn schema_status(
+    conn: &mut SqliteConnection,
+) -> Result<SchemaStatus, Box<dyn std::error::Error + Send + Sync>> {
+    // Also creates the table of applied migrations in a new database
+    let pending: Vec<String> = conn
+        .pending_migrations(MIGRATIONS)?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect();
+
+    let names: HashMap<String, String> = MigrationSource::<Sqlite>::migrations(&MIGRATIONS)?
+        .iter()
+        .map(|migration| {
+            (
+                migration.name().version().to_string(),
+                migration.name().to_string(),
+            )
+        })
+        .collect();
+    let applied: Vec<AppliedMigration> = diesel::sql_query(
+        "SELECT version, run_on FROM __diesel_schema_migrations ORDER BY version",
+    )
+    .load::<MigrationRow>(conn)?
+    .into_iter()
+    .map(|row| AppliedMigration {
+        name: names.get(&row.version).cloned(),
+        version: row.version,
+        run_on: row.run_on,
+    })
+    .collect();
+
+    Ok(SchemaStatus {
+        version: applied.last().map(|migration| migration.version.clone()),
+        up_to_date: pending.is_empty(),
+        applied,
+        pending,
+    })
+}
+
 /// Gets a connection from the pool with retry logic and exponential backoff
 pub fn get_connection_with_retry(pool: &DbPool) -> Result<DbConnection, diesel::r2d2::Error> {
     // Retry connection acquisition with exponential backoff
@@ -130,3 +376,37 @@ pub fn get_connection_with_retry(pool: &DbPool) -> Result<DbConnection, diesel::
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strict_migrations() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_url = dir.path().join("clef.db");
+        let database_url = database_url.to_str().unwrap();
+        let metrics = Arc::new(DbMetrics::default());
+        let strict = PoolOptions {
+            strict_migrations: true,
+            ..PoolOptions::default()
+        };
+
+        let error = create_pool(database_url, &strict, &metrics).unwrap_err();
+        assert!(error.to_string().contains("run `clef migrate` first"));
+
+        let applied = run_pending_migrations(database_url).unwrap();
+        let pool = create_pool(database_url, &strict, &metrics).unwrap();
+        let status = schema_status(&mut pool.get().unwrap()).unwrap();
+        assert!(status.up_to_date);
+        assert!(status.pending.is_empty());
+        assert_eq!(status.applied.len(), applied.len());
+        assert_eq!(status.version.as_ref(), applied.last());
+        assert!(
+            status
+                .applied
+                .iter()
+                .all(|migration| migration.name.is_some())
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::bandwidth::NewBandwidthUsage;
+use crate::models::cache::{NewCacheStatsRecord, NewPackageCacheStat};
+use crate::models::downloads::NewVersionDownload;
+use crate::schema::{
+    bandwidth_usage, cache_stats, package_cache_stats, package_files, version_downloads,
+};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use std::collections::HashMap;
+
+/// Counter updates collected on the request path, written later in one batch
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PendingCounters {
+    /// Downloads per (package, version, day)
+    pub downloads: HashMap<(String, String, NaiveDate), i64>,
+    /// Reads of a package file and when it was last read
+    pub file_accesses: HashMap<i32, (i32, NaiveDateTime)>,
+    pub cache_hits: i64,
+    pub cache_misses: i64,
+    /// Cache lookups and bytes served per (package, day)
+    pub package_cache: HashMap<(String, NaiveDate), PackageCacheCounts>,
+    /// Bytes and requests served per (day, package, user id, token id), ids are 0 when
+    /// missing
+    pub bandwidth: HashMap<(NaiveDate, String, i32, i32), (i64, i64)>,
+}
+
+/// Cache lookups of a package and the bytes served for it from the cache and upstream
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PackageCacheCounts {
+    pub hits: i64,
+    pub misses: i64,
+    pub cache_bytes: i64,
+    pub upstream_bytes: i64,
+}
+
+impl PackageCacheCounts {
+    fn add(&mut self, other: PackageCacheCounts) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.cache_bytes += other.cache_bytes;
+        self.upstream_bytes += other.upstream_bytes;
+    }
+}
+
+impl PendingCounters {
+    pub fn is_empty(&self) -> bool {
+        self.downloads.is_empty()
+            && self.file_accesses.is_empty()
+            && self.package_cache.is_empty()
+            && self.bandwidth.is_empty()
+            && self.cache_hits == 0
+            && self.cache_misses == 0
+    }
+
+    /// Adds counters that couldn't be written back to the ones queued since
+    pub fn merge(&mut self, other: PendingCounters) {
+        for (key, count) in other.downloads {
+            *self.downloads.entry(key).or_default() += count;
+        }
+        for (file_id, (count, at)) in other.file_accesses {
+            let entry = self.file_accesses.entry(file_id).or_insert((0, at));
+            entry.0 += count;
+            entry.1 = entry.1.max(at);
+        }
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        for (key, counts) in other.package_cache {
+            self.package_cache.entry(key).or_default().add(counts);
+        }
+        for (key, (bytes, requests)) in other.bandwidth {
+            let entry = self.bandwidth.entry(key).or_default();
+            entry.0 += bytes;
+            entry.1 += requests;
+        }
+    }
+
+    /// Adds to the counts of a package for today
+    pub fn count_package_cache(&mut self, package_name: &str, counts: PackageCacheCounts) {
+        let today = Utc::now().date_naive();
+        self.package_cache
+            .entry((package_name.to_string(), today))
+            .or_default()
+            .add(counts);
+    }
+}
+
+/// Batched writes of download, file access and cache counters
+pub struct CounterOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> CounterOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Adds the pending counters to their rows in one transaction
+    pub fn write(&self, pending: &PendingCounters) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            for ((package_name, version, day), downloads) in &pending.downloads {
+                diesel::insert_into(version_downloads::table)
+                    .values(&NewVersionDownload {
+                        package_name: package_name.clone(),
+                        version: version.clone(),
+                        day: *day,
+                        downloads: *downloads,
+                    })
+                    .on_conflict((
+                        version_downloads::package_name,
+                        version_downloads::version,
+                        version_downloads::day,
+                    ))
+                    .do_update()
+                    .set(
+                        version_downloads::downloads
+                            .eq(version_downloads::downloads
+                                + excluded(version_downloads::downloads)),
+                    )
+                    .execute(conn)?;
+            }
+
+            for (file_id, (count, last_accessed)) in &pending.file_accesses {
+                diesel::update(package_files::table.find(file_id))
+                    .set((
+                        package_files::access_count.eq(package_files::access_count + count),
+                        package_files::last_accessed.eq(last_accessed),
+                    ))
+                    .execute(conn)?;
+            }
+
+            for ((package_name, day), counts) in &pending.package_cache {
+                diesel::insert_into(package_cache_stats::table)
+                    .values(&NewPackageCacheStat {
+                        package_name: package_name.clone(),
+                        day: *day,
+                        hits: counts.hits,
+                        misses: counts.misses,
+                        cache_bytes: counts.cache_bytes,
+                        upstream_bytes: counts.upstream_bytes,
+                    })
+                    .on_conflict((package_cache_stats::package_name, package_cache_stats::day))
+                    .do_update()
+                    .set((
+                        package_cache_stats::hits
+                            .eq(package_cache_stats::hits + excluded(package_cache_stats::hits)),
+                        package_cache_stats::misses
+                            .eq(package_cache_stats::misses + excluded(package_cache_stats::misses)),
+                        package_cache_stats::cache_bytes.eq(package_cache_stats::cache_bytes
+                            + excluded(package_cache_stats::cache_bytes)),
+                        package_cache_stats::upstream_bytes.eq(package_cache_stats::upstream_bytes
+                            + excluded(package_cache_stats::upstream_bytes)),
+                    ))
+                    .execute(conn)?;
+            }
+
+            for ((day, package_name, user_id, token_id), (bytes, requests)) in &pending.bandwidth
+            {
+                diesel::insert_into(bandwidth_usage::table)
+                    .values(&NewBandwidthUsage {
+                        day: *day,
+                        package_name: package_name.clone(),
+                        user_id: *user_id,
+                        token_id: *token_id,
+                        bytes: *bytes,
+                        requests: *requests,
+                    })
+                    .on_conflict((
+                        bandwidth_usage::day,
+                        bandwidth_usage::package_name,
+                        bandwidth_usage::user_id,
+                        bandwidth_usage::token_id,
+                    ))
+                    .do_update()
+                    .set((
+                        bandwidth_usage::bytes
+                            .eq(bandwidth_usage::bytes + excluded(bandwidth_usage::bytes)),
+                        bandwidth_usage::requests
+                            .eq(bandwidth_usage::requests + excluded(bandwidth_usage::requests)),
+                    ))
+                    .execute(conn)?;
+            }
+
+            if pending.cache_hits > 0 || pending.cache_misses > 0 {
+                let now = Utc::now().naive_utc();
+                let updated = diesel::update(cache_stats::table)
+                    .set((
+                        cache_stats::hit_count.eq(cache_stats::hit_count + pending.cache_hits),
+                        cache_stats::miss_count.eq(cache_stats::miss_count + pending.cache_misses),
+                        cache_stats::updated_at.eq(now),
+                    ))
+                    .execute(conn)?;
+                if updated == 0 {
+                    diesel::insert_into(cache_stats::table)
+                        .values(&NewCacheStatsRecord {
+                            hit_count: pending.cache_hits,
+                            miss_count: pending.cache_misses,
+                            created_at: now,
+                            updated_at: now,
+                        })
+                        .execute(conn)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 31).unwrap();
+        let earlier = day.and_hms_opt(10, 0, 0).unwrap();
+        let later = day.and_hms_opt(11, 0, 0).unwrap();
+        let key = ("lodash".to_string(), "4.17.21".to_string(), day);
+
+        let mut queued = PendingCounters {
+            downloads: HashMap::from([(key.clone(), 2)]),
+            file_accesses: HashMap::from([(1, (1, later))]),
+            cache_hits: 3,
+            ..Default::default()
+        };
+        queued.merge(PendingCounters {
+            downloads: HashMap::from([(key.clone(), 1)]),
+            file_accesses: HashMap::from([(1, (2, earlier)), (2, (1, earlier))]),
+            cache_misses: 1,
+            ..Default::default()
+        });
+
+        assert_eq!(queued.downloads[&key], 3);
+        assert_eq!(queued.file_accesses[&1], (3, later));
+        assert_eq!(queued.file_accesses[&2], (1, earlier));
+        assert_eq!((queued.cache_hits, queued.cache_misses), (3, 1));
+        assert!(!queued.is_empty());
+        assert!(PendingCounters::default().is_empty());
+    }
+
+    #[test]
+    fn test_merge_package_cache() {
+        let mut queued = PendingCounters::default();
+        queued.count_package_cache(
+            "lodash",
+            PackageCacheCounts {
+                hits: 1,
+                cache_bytes: 100,
+                ..Default::default()
+            },
+        );
+        let mut failed = PendingCounters::default();
+        failed.count_package_cache(
+            "lodash",
+            PackageCacheCounts {
+                misses: 1,
+                upstream_bytes: 50,
+                ..Default::default()
+            },
+        );
+        queued.merge(failed);
+
+        let key = ("lodash".to_string(), Utc::now().date_naive());
+        assert_eq!(
+            queued.package_cache[&key],
+            PackageCacheCounts {
+                hits: 1,
+                misses: 1,
+                cache_bytes: 100,
+                upstream_bytes: 50,
+            }
+        );
+        assert!(!queued.is_empty());
+    }
+}
@@ -108,6 +108,57 @@ impl<'a> FileOperations<'a> {
             .map(|opt| opt.map(|(pkg, (ver, file))| (pkg, ver, file)))
     }
 
+    /// Lists every stored file with its package and version
+    pub fn list_package_files(
+        &self,
+    ) -> Result<Vec<(Package, PackageVersion, PackageFile)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        packages::table
+            .inner_join(package_versions::table.inner_join(package_files::table))
+            .order((packages::name.asc(), package_files::filename.asc()))
+            .load::<(Package, (PackageVersion, PackageFile))>(&mut conn)
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(pkg, (ver, file))| (pkg, ver, file))
+                    .collect()
+            })
+    }
+
+    /// Files whose version row no longer exists
+    pub fn list_files_without_version(&self) -> Result<Vec<PackageFile>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        package_files::table
+            .filter(
+                package_files::package_version_id
+                    .ne_all(package_versions::table.select(package_versions::id)),
+            )
+            .load::<PackageFile>(&mut conn)
+    }
+
+    /// Deletes a package file record
+    pub fn delete_package_file(&self, file_id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(package_files::table.find(file_id)).execute(&mut conn)
+    }
+
     /// Updates file access information (last accessed time and access count)
     pub fn update_file_access_info(&self, file_id: i32) -> Result<(), diesel::result::Error> {
         let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
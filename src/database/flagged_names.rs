@@ -0,0 +1,103 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::flagged_name::*;
+use crate::schema::flagged_names;
+use chrono::Utc;
+use diesel::prelude::*;
+
+/// Typosquatting review database operations
+pub struct FlaggedNameOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> FlaggedNameOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Gets the flag of a package name, if it was ever flagged
+    pub fn get_flagged_name(
+        &self,
+        package_name: &str,
+    ) -> Result<Option<FlaggedName>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        flagged_names::table
+            .filter(flagged_names::package_name.eq(package_name))
+            .first::<FlaggedName>(&mut conn)
+            .optional()
+    }
+
+    /// Lists flagged names, newest first, optionally only those with the given status
+    pub fn list_flagged_names(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<FlaggedName>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = flagged_names::table
+            .order(flagged_names::created_at.desc())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(flagged_names::status.eq(status));
+        }
+
+        query.load::<FlaggedName>(&mut conn)
+    }
+
+    /// Records a flagged name. A name that was flagged before keeps its existing record.
+    pub fn create_flagged_name(
+        &self,
+        flagged: &NewFlaggedName,
+    ) -> Result<FlaggedName, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(flagged_names::table)
+            .values(flagged)
+            .on_conflict(flagged_names::package_name)
+            .do_nothing()
+            .execute(&mut conn)?;
+
+        flagged_names::table
+            .filter(flagged_names::package_name.eq(&flagged.package_name))
+            .first::<FlaggedName>(&mut conn)
+    }
+
+    /// Sets the review status of a flagged name
+    pub fn review_flagged_name(
+        &self,
+        id: i32,
+        status: &str,
+        reviewer_id: i32,
+    ) -> Result<Option<FlaggedName>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(flagged_names::table.filter(flagged_names::id.eq(id)))
+            .set((
+                flagged_names::status.eq(status),
+                flagged_names::reviewed_by.eq(Some(reviewer_id)),
+                flagged_names::reviewed_at.eq(Some(Utc::now().naive_utc())),
+            ))
+            .get_result::<FlaggedName>(&mut conn)
+            .optional()
+    }
+}
@@ -0,0 +1,131 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::hook::*;
+use crate::schema::{hooks, users};
+use chrono::Utc;
+use diesel::prelude::*;
+
+/// npm hook database operations
+pub struct HookOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> HookOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn get_hook(&self, id: i32) -> Result<Option<Hook>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        hooks::table
+            .filter(hooks::id.eq(id))
+            .first::<Hook>(&mut conn)
+            .optional()
+    }
+
+    /// Lists hooks oldest first with the username of their owner, all of them or those of
+    /// one user
+    pub fn list_hooks(
+        &self,
+        user_id: Option<i32>,
+    ) -> Result<Vec<(Hook, String)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = hooks::table
+            .inner_join(users::table)
+            .select((Hook::as_select(), users::username))
+            .order(hooks::id.asc())
+            .into_boxed();
+        if let Some(user_id) = user_id {
+            query = query.filter(hooks::user_id.eq(user_id));
+        }
+
+        query.load::<(Hook, String)>(&mut conn)
+    }
+
+    pub fn create_hook(&self, hook: &NewHook) -> Result<Hook, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(hooks::table)
+            .values(hook)
+            .get_result::<Hook>(&mut conn)
+    }
+
+    /// Changes the endpoint of a hook, and its secret when one is given
+    pub fn update_hook(
+        &self,
+        id: i32,
+        endpoint: &str,
+        secret: Option<&str>,
+    ) -> Result<Hook, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let target = hooks::table.filter(hooks::id.eq(id));
+        let now = Utc::now().naive_utc();
+        match secret {
+            Some(secret) => diesel::update(target)
+                .set((
+                    hooks::endpoint.eq(endpoint),
+                    hooks::secret.eq(secret),
+                    hooks::updated_at.eq(now),
+                ))
+                .get_result::<Hook>(&mut conn),
+            None => diesel::update(target)
+                .set((hooks::endpoint.eq(endpoint), hooks::updated_at.eq(now)))
+                .get_result::<Hook>(&mut conn),
+        }
+    }
+
+    pub fn delete_hook(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(hooks::table.filter(hooks::id.eq(id))).execute(&mut conn)
+    }
+
+    /// Records the outcome of a delivery, 0 when the endpoint couldn't be reached
+    pub fn record_hook_delivery(
+        &self,
+        id: i32,
+        response_code: i32,
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(hooks::table.filter(hooks::id.eq(id)))
+            .set((
+                hooks::last_delivery.eq(Some(Utc::now().naive_utc())),
+                hooks::response_code.eq(response_code),
+            ))
+            .execute(&mut conn)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::invitation::*;
+use crate::schema::invitations;
+use diesel::prelude::*;
+
+/// Invitation-related database operations
+pub struct InvitationOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> InvitationOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a new invitation
+    pub fn create_invitation(
+        &self,
+        new_invitation: &NewInvitation,
+    ) -> Result<Invitation, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(invitations::table)
+            .values(new_invitation)
+            .get_result::<Invitation>(&mut conn)
+    }
+
+    /// Gets an invitation by its token
+    pub fn get_invitation_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<Invitation>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        invitations::table
+            .filter(invitations::token.eq(token))
+            .first::<Invitation>(&mut conn)
+            .optional()
+    }
+
+    /// Lists all invitations, newest first
+    pub fn list_invitations(&self) -> Result<Vec<Invitation>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        invitations::table
+            .order(invitations::created_at.desc())
+            .load::<Invitation>(&mut conn)
+    }
+
+    /// Marks an unused invitation as redeemed by a user. Returns false if it was already used.
+    pub fn redeem_invitation(
+        &self,
+        invitation_id: i32,
+        user_id: i32,
+    ) -> Result<bool, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let updated = diesel::update(
+            invitations::table
+                .filter(invitations::id.eq(invitation_id))
+                .filter(invitations::used_at.is_null()),
+        )
+        .set((
+            invitations::used_at.eq(chrono::Utc::now().naive_utc()),
+            invitations::used_by.eq(user_id),
+        ))
+        .execute(&mut conn)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Deletes an invitation
+    pub fn delete_invitation(&self, invitation_id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(invitations::table.find(invitation_id)).execute(&mut conn)
+    }
+}
@@ -165,6 +165,22 @@ impl<'a> MetadataCacheOperations<'a> {
         })
     }
 
+    /// Lists all metadata cache entries
+    pub fn list_metadata_cache_entries(
+        &self,
+    ) -> Result<Vec<MetadataCacheRecord>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        metadata_cache::table
+            .order(metadata_cache::package_name.asc())
+            .load::<MetadataCacheRecord>(&mut conn)
+    }
+
     /// Delete metadata cache entry
     pub fn delete_metadata_cache_entry(
         &self,
@@ -7,33 +7,105 @@
 //! - `files`: Package file-related database operations
 //! - `analytics`: Analytics and statistics operations
 //! - `cache_stats`: Cache statistics operations
+//! - `bandwidth`: Bytes served per package, user and token
+//! - `client_certificates`: Client certificates users authenticate with over mutual TLS
+//! - `counters`: Batched writes of download, file access and cache counters
 //! - `metadata_cache`: Metadata cache operations
 //! - `package_owners`: Package ownership management operations
 //! - `organizations`: Organization and membership management operations
+//! - `invitations`: Invitation tokens for invite-only registration
+//! - `signing_keys`: Organization signing keys and package signatures
+//! - `registry_keys`: Registry keys for npm signature verification
+//! - `attestations`: Provenance attestations of published versions
+//! - `scope_policies`: Per-scope publish, upstream and anonymous access policies
+//! - `advisories`: Security advisories used for audit reports
+//! - `blocked_names`: Package name blocklist managed by admins
+//! - `allowed_packages`: Upstream packages allowed when proxying is allowlist only
+//! - `flagged_names`: Package names flagged as possible typosquats
+//! - `pinned_packages`: Packages kept cached and refreshed ahead of their TTL
+//! - `retention_policies`: Organization rules for deleting old versions
+//! - `audit_log`: Record of changes made by users and scheduled jobs
+//! - `tarball_entries`: Files inside version tarballs, indexed for browsing
+//! - `tombstones`: Deleted versions that can't be published again
+//! - `hooks`: npm hooks notified about package changes
+//! - `notifications`: Notification subscriptions and the queue of notifications to send
+//! - `service_accounts`: Non-interactive organization accounts for CI pipelines
+//! - `staged_publishes`: Publishes awaiting promotion into the registry
+//! - `quarantine`: Upstream packages and majors awaiting admin approval
+//! - `tasks`: Queue of long-running operations run in the background
+//! - `optimize`: `VACUUM`, `REINDEX` and `ANALYZE` for maintenance windows
 //! - `service`: Main DatabaseService that provides a unified interface
 
+pub mod advisories;
+pub mod allowed_packages;
 pub mod analytics;
+pub mod attestations;
+pub mod audit_log;
+pub mod bandwidth;
+pub mod blocked_names;
 pub mod cache_stats;
+pub mod client_certificates;
 pub mod connection;
+pub mod counters;
 pub mod files;
+pub mod flagged_names;
+pub mod hooks;
+pub mod invitations;
 pub mod metadata_cache;
+pub mod notifications;
+pub mod optimize;
 pub mod organizations;
 pub mod package_owners;
 pub mod package_tags;
 pub mod packages;
+pub mod pinned_packages;
+pub mod quarantine;
+pub mod registry_keys;
+pub mod retention_policies;
+pub mod scope_policies;
 pub mod service;
+pub mod service_accounts;
+pub mod signing_keys;
+pub mod staged_publishes;
+pub mod tarball_entries;
+pub mod tasks;
+pub mod tombstones;
 pub mod versions;
 
 // Re-export the main types and service for easy access
-pub use connection::{DbConnection, DbPool, MIGRATIONS};
+pub use connection::{
+    DbConnection, DbPool, MIGRATIONS, PoolOptions, run_pending_migrations, schema_status,
+};
 pub use service::DatabaseService;
 
 // Re-export operation structs for advanced usage
+pub use advisories::AdvisoryOperations;
+pub use allowed_packages::AllowedPackageOperations;
 pub use analytics::AnalyticsOperations;
+pub use attestations::AttestationOperations;
+pub use audit_log::AuditLogOperations;
+pub use bandwidth::BandwidthOperations;
+pub use blocked_names::BlockedNameOperations;
 pub use cache_stats::CacheStatsOperations;
+pub use client_certificates::ClientCertificateOperations;
 pub use files::FileOperations;
+pub use flagged_names::FlaggedNameOperations;
+pub use hooks::HookOperations;
+pub use invitations::InvitationOperations;
 pub use metadata_cache::MetadataCacheOperations;
+pub use notifications::NotificationOperations;
+pub use optimize::OptimizeOperations;
 pub use organizations::OrganizationOperations;
 pub use package_owners::PackageOwnerOperations;
 pub use packages::PackageOperations;
+pub use pinned_packages::PinnedPackageOperations;
+pub use quarantine::QuarantineOperations;
+pub use registry_keys::RegistryKeyOperations;
+pub use retention_policies::RetentionPolicyOperations;
+pub use scope_policies::ScopePolicyOperations;
+pub use service_accounts::ServiceAccountOperations;
+pub use signing_keys::SigningKeyOperations;
+pub use staged_publishes::StagedPublishOperations;
+pub use tasks::TaskOperations;
+pub use tombstones::TombstoneOperations;
 pub use versions::VersionOperations;
@@ -0,0 +1,184 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::notification::*;
+use crate::schema::{notification_subscriptions, notifications};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Notification subscription and queue database operations
+pub struct NotificationOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> NotificationOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn create_notification_subscription(
+        &self,
+        subscription: &NewNotificationSubscription,
+    ) -> Result<NotificationSubscription, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(notification_subscriptions::table)
+            .values(subscription)
+            .get_result::<NotificationSubscription>(&mut conn)
+    }
+
+    pub fn get_notification_subscription(
+        &self,
+        id: i32,
+    ) -> Result<Option<NotificationSubscription>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        notification_subscriptions::table
+            .find(id)
+            .first::<NotificationSubscription>(&mut conn)
+            .optional()
+    }
+
+    /// Lists subscriptions oldest first, all of them or those of one user
+    pub fn list_notification_subscriptions(
+        &self,
+        user_id: Option<i32>,
+    ) -> Result<Vec<NotificationSubscription>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = notification_subscriptions::table
+            .order(notification_subscriptions::id.asc())
+            .into_boxed();
+        if let Some(user_id) = user_id {
+            query = query.filter(notification_subscriptions::user_id.eq(user_id));
+        }
+
+        query.load::<NotificationSubscription>(&mut conn)
+    }
+
+    pub fn delete_notification_subscription(
+        &self,
+        id: i32,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(notification_subscriptions::table.find(id)).execute(&mut conn)
+    }
+
+    /// Queues notifications for delivery
+    pub fn create_notifications(
+        &self,
+        entries: &[NewNotification],
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(notifications::table)
+            .values(entries)
+            .execute(&mut conn)
+    }
+
+    /// Subscriptions with notifications that weren't sent yet
+    pub fn get_subscriptions_with_pending_notifications(
+        &self,
+    ) -> Result<Vec<NotificationSubscription>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let pending = notifications::table
+            .filter(notifications::sent_at.is_null())
+            .select(notifications::subscription_id)
+            .distinct();
+
+        notification_subscriptions::table
+            .filter(notification_subscriptions::id.eq_any(pending))
+            .order(notification_subscriptions::id.asc())
+            .load::<NotificationSubscription>(&mut conn)
+    }
+
+    /// Unsent notifications of a subscription, oldest first
+    pub fn get_pending_notifications(
+        &self,
+        subscription_id: i32,
+    ) -> Result<Vec<Notification>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        notifications::table
+            .filter(notifications::subscription_id.eq(subscription_id))
+            .filter(notifications::sent_at.is_null())
+            .order(notifications::id.asc())
+            .load::<Notification>(&mut conn)
+    }
+
+    /// Marks notifications as sent and records the delivery on their subscription
+    pub fn mark_notifications_sent(
+        &self,
+        subscription_id: i32,
+        ids: &[i32],
+        sent_at: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            diesel::update(notification_subscriptions::table.find(subscription_id))
+                .set(notification_subscriptions::last_sent_at.eq(sent_at))
+                .execute(conn)?;
+
+            diesel::update(notifications::table.filter(notifications::id.eq_any(ids)))
+                .set(notifications::sent_at.eq(sent_at))
+                .execute(conn)
+        })
+    }
+
+    /// Deletes notifications created before `before`, sent or not
+    pub fn delete_notifications_before(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(notifications::table.filter(notifications::created_at.lt(before)))
+            .execute(&mut conn)
+    }
+}
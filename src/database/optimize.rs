@@ -0,0 +1,109 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::maintenance::DatabaseOptimizeReport;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use std::time::Instant;
+
+#[derive(QueryableByName)]
+struct PageCount {
+    #[diesel(sql_type = BigInt)]
+    page_count: i64,
+}
+
+#[derive(QueryableByName)]
+struct PageSize {
+    #[diesel(sql_type = BigInt)]
+    page_size: i64,
+}
+
+#[derive(QueryableByName)]
+struct FreelistCount {
+    #[diesel(sql_type = BigInt)]
+    freelist_count: i64,
+}
+
+/// `VACUUM`, `REINDEX` and `ANALYZE` of the SQLite database
+pub struct OptimizeOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> OptimizeOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Runs `ANALYZE`, then `REINDEX` and `VACUUM` when asked to. Both rebuilds hold the write
+    /// lock until they finish.
+    pub fn optimize(
+        &self,
+        vacuum: bool,
+        reindex: bool,
+    ) -> Result<DatabaseOptimizeReport, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let started = Instant::now();
+        let size_before_bytes = Self::size_bytes(&mut conn)?;
+        let free_pages_before = sql_query("PRAGMA freelist_count")
+            .get_result::<FreelistCount>(&mut conn)?
+            .freelist_count as u64;
+
+        sql_query("ANALYZE").execute(&mut conn)?;
+        if reindex {
+            sql_query("REINDEX").execute(&mut conn)?;
+        }
+        if vacuum {
+            sql_query("VACUUM").execute(&mut conn)?;
+            // The rebuilt file is written to the WAL first, the checkpoint shrinks the file
+            sql_query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&mut conn)?;
+        }
+
+        let size_after_bytes = Self::size_bytes(&mut conn)?;
+        Ok(DatabaseOptimizeReport {
+            vacuumed: vacuum,
+            reindexed: reindex,
+            size_before_bytes,
+            size_after_bytes,
+            reclaimed_bytes: size_before_bytes.saturating_sub(size_after_bytes),
+            free_pages_before,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    fn size_bytes(conn: &mut SqliteConnection) -> Result<u64, diesel::result::Error> {
+        let pages = sql_query("PRAGMA page_count")
+            .get_result::<PageCount>(conn)?
+            .page_count;
+        let page_size = sql_query("PRAGMA page_size")
+            .get_result::<PageSize>(conn)?
+            .page_size;
+        Ok((pages * page_size) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DatabaseService;
+
+    #[test]
+    fn test_optimize() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+
+        let report = database.optimize(true, true).unwrap();
+        assert!(report.vacuumed && report.reindexed);
+        assert!(report.size_after_bytes > 0);
+        // The statistics of ANALYZE may take more pages than a new database has free
+        assert_eq!(
+            report.reclaimed_bytes,
+            report
+                .size_before_bytes
+                .saturating_sub(report.size_after_bytes)
+        );
+    }
+}
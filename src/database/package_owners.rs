@@ -1,7 +1,8 @@
 use super::connection::{DbPool, get_connection_with_retry};
 use crate::models::organization::OrganizationRole;
 use crate::models::package::*;
-use crate::schema::{organization_members, package_owners, packages};
+use crate::models::user::User;
+use crate::schema::{organization_members, package_owners, packages, users};
 use diesel::prelude::*;
 
 /// Package ownership-related database operations
@@ -17,6 +18,7 @@ impl<'a> PackageOwnerOperations<'a> {
     /// Checks if a user has read permission for a package
     /// For scoped packages, checks organization membership
     /// For regular packages, all are public by default
+    /// Private packages require ownership, organization membership or the admin role
     pub fn has_read_permission(
         &self,
         package_name: &str,
@@ -36,6 +38,37 @@ impl<'a> PackageOwnerOperations<'a> {
             .optional()?;
 
         match package {
+            Some(pkg) if pkg.is_private() => {
+                // Private packages are only readable by owners, organization members and admins
+                let Some(uid) = user_id else {
+                    return Ok(false);
+                };
+
+                let is_owner = package_owners::table
+                    .filter(package_owners::package_name.eq(package_name))
+                    .filter(package_owners::user_id.eq(uid))
+                    .first::<PackageOwner>(&mut conn)
+                    .optional()?
+                    .is_some();
+
+                let is_member = match pkg.organization_id {
+                    Some(org_id) => organization_members::table
+                        .filter(organization_members::organization_id.eq(org_id))
+                        .filter(organization_members::user_id.eq(uid))
+                        .first::<crate::models::organization::OrganizationMember>(&mut conn)
+                        .optional()?
+                        .is_some(),
+                    None => false,
+                };
+
+                let is_admin = users::table
+                    .find(uid)
+                    .first::<User>(&mut conn)
+                    .optional()?
+                    .is_some_and(|user| user.is_admin());
+
+                Ok(is_owner || is_member || is_admin)
+            }
             Some(pkg) => {
                 // Package exists locally
                 // If it's published locally (has author_id), it's public regardless of organization
@@ -205,6 +238,61 @@ impl<'a> PackageOwnerOperations<'a> {
             .load::<PackageOwner>(&mut conn)
     }
 
+    /// Usernames and emails of the owners of a package
+    pub fn get_package_maintainers(
+        &self,
+        package_name: &str,
+    ) -> Result<Vec<(String, String)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        package_owners::table
+            .inner_join(users::table)
+            .filter(package_owners::package_name.eq(package_name))
+            .order(package_owners::created_at.asc())
+            .select((users::username, users::email))
+            .load::<(String, String)>(&mut conn)
+    }
+
+    /// Packages a user owns with their permission level, sorted by name
+    pub fn get_user_packages(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<(Package, String)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let owned: Vec<(String, String)> = package_owners::table
+            .filter(package_owners::user_id.eq(user_id))
+            .select((
+                package_owners::package_name,
+                package_owners::permission_level,
+            ))
+            .load(&mut conn)?;
+        let levels: std::collections::HashMap<String, String> = owned.into_iter().collect();
+
+        let packages = packages::table
+            .filter(packages::name.eq_any(levels.keys()))
+            .order(packages::name.asc())
+            .load::<Package>(&mut conn)?;
+
+        Ok(packages
+            .into_iter()
+            .filter_map(|package| {
+                let level = levels.get(&package.name)?.clone();
+                Some((package, level))
+            })
+            .collect())
+    }
+
     /// Adds a user as an owner of a package
     pub fn add_package_owner(
         &self,
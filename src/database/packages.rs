@@ -1,6 +1,6 @@
 use super::connection::{DbPool, get_connection_with_retry};
 use crate::models::package::*;
-use crate::schema::{organizations, packages};
+use crate::schema::{organizations, package_owners, packages};
 use diesel::prelude::*;
 
 /// Package-related database operations
@@ -102,6 +102,29 @@ impl<'a> PackageOperations<'a> {
             .optional()
     }
 
+    /// Packages whose name, description or keywords contain every search term
+    pub fn search_packages(&self, terms: &[&str]) -> Result<Vec<Package>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = packages::table.into_boxed();
+        for term in terms {
+            let pattern = format!("%{term}%");
+            query = query.filter(
+                packages::name
+                    .like(pattern.clone())
+                    .or(packages::description.like(pattern.clone()))
+                    .or(packages::keywords.like(pattern)),
+            );
+        }
+
+        query.order(packages::name.asc()).load::<Package>(&mut conn)
+    }
+
     /// Updates package metadata (homepage, repository_url, license, keywords)
     pub fn update_package_metadata(
         &self,
@@ -239,7 +262,9 @@ impl<'a> PackageOperations<'a> {
         Ok(result)
     }
 
-    /// Gets packages with pagination, optional search, and sorting
+    /// Gets packages with pagination, optional search, and sorting, optionally only those of
+    /// an organization. Private packages are only included when `include_private` is set.
+    #[allow(clippy::too_many_arguments)]
     pub fn get_packages_paginated(
         &self,
         limit: i64,
@@ -247,6 +272,8 @@ impl<'a> PackageOperations<'a> {
         search_query: Option<&str>,
         sort_column: Option<&str>,
         sort_order: Option<&str>,
+        include_private: bool,
+        organization_id: Option<i32>,
     ) -> Result<(Vec<PackageWithVersions>, i64), diesel::result::Error> {
         use crate::schema::{package_files, package_versions};
 
@@ -257,170 +284,50 @@ impl<'a> PackageOperations<'a> {
             )
         })?;
 
-        // Get total count first
-        let total_count: i64 = if let Some(search) = search_query {
-            let search_pattern = format!("%{search}%");
-            packages::table
-                .filter(
+        // Search and visibility filters shared by the count and the page query
+        let filtered = || {
+            let mut query = packages::table.into_boxed();
+            if let Some(search) = search_query {
+                let search_pattern = format!("%{search}%");
+                query = query.filter(
                     packages::name
-                        .like(&search_pattern)
-                        .or(packages::description.like(&search_pattern)),
-                )
-                .count()
-                .get_result(&mut conn)?
-        } else {
-            packages::table.count().get_result(&mut conn)?
+                        .like(search_pattern.clone())
+                        .or(packages::description.like(search_pattern)),
+                );
+            }
+            if !include_private {
+                query = query.filter(packages::visibility.eq(PackageVisibility::Public.as_str()));
+            }
+            if let Some(organization_id) = organization_id {
+                query = query.filter(packages::organization_id.eq(organization_id));
+            }
+            query
         };
 
+        // Get total count first
+        let total_count: i64 = filtered().count().get_result(&mut conn)?;
+
         // Apply sorting
         let sort_col = sort_column.unwrap_or("created_at");
         let sort_ord = sort_order.unwrap_or("desc");
 
-        // Get paginated packages with search and sorting
-        let paginated_packages = if let Some(search) = search_query {
-            let search_pattern = format!("%{search}%");
-            match (sort_col, sort_ord) {
-                ("name", "asc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::name.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("name", "desc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::name.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("created_at", "asc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::created_at.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("created_at", "desc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::created_at.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("updated_at", "asc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::updated_at.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("updated_at", "desc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::updated_at.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("id", "asc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::id.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("id", "desc") => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::id.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                _ => packages::table
-                    .filter(
-                        packages::name
-                            .like(&search_pattern)
-                            .or(packages::description.like(&search_pattern)),
-                    )
-                    .order(packages::created_at.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-            }
-        } else {
-            match (sort_col, sort_ord) {
-                ("name", "asc") => packages::table
-                    .order(packages::name.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("name", "desc") => packages::table
-                    .order(packages::name.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("created_at", "asc") => packages::table
-                    .order(packages::created_at.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("created_at", "desc") => packages::table
-                    .order(packages::created_at.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("updated_at", "asc") => packages::table
-                    .order(packages::updated_at.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("updated_at", "desc") => packages::table
-                    .order(packages::updated_at.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("id", "asc") => packages::table
-                    .order(packages::id.asc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                ("id", "desc") => packages::table
-                    .order(packages::id.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-                _ => packages::table
-                    .order(packages::created_at.desc())
-                    .limit(limit)
-                    .offset(offset)
-                    .load::<Package>(&mut conn)?,
-            }
+        let query = match (sort_col, sort_ord) {
+            ("name", "asc") => filtered().order(packages::name.asc()),
+            ("name", "desc") => filtered().order(packages::name.desc()),
+            ("created_at", "asc") => filtered().order(packages::created_at.asc()),
+            ("updated_at", "asc") => filtered().order(packages::updated_at.asc()),
+            ("updated_at", "desc") => filtered().order(packages::updated_at.desc()),
+            ("id", "asc") => filtered().order(packages::id.asc()),
+            ("id", "desc") => filtered().order(packages::id.desc()),
+            _ => filtered().order(packages::created_at.desc()),
         };
 
+        // Get paginated packages with search and sorting
+        let paginated_packages = query
+            .limit(limit)
+            .offset(offset)
+            .load::<Package>(&mut conn)?;
+
         let mut result = Vec::new();
 
         // For each package, get its versions and files
@@ -512,6 +419,43 @@ impl<'a> PackageOperations<'a> {
         Ok(result)
     }
 
+    /// Latest versions added to public packages, newest first. `published` limits them to
+    /// versions published here (`true`) or proxied from upstream (`false`).
+    pub fn get_recent_versions(
+        &self,
+        limit: i64,
+        published: Option<bool>,
+    ) -> Result<Vec<(Package, PackageVersion)>, diesel::result::Error> {
+        use crate::schema::package_versions;
+
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = package_versions::table
+            .inner_join(packages::table)
+            .filter(packages::visibility.eq(PackageVisibility::Public.to_string()))
+            .filter(package_versions::yanked_at.is_null())
+            .into_boxed();
+        query = match published {
+            Some(true) => query.filter(packages::author_id.is_not_null()),
+            Some(false) => query.filter(packages::author_id.is_null()),
+            None => query,
+        };
+
+        query
+            .order((
+                package_versions::created_at.desc(),
+                package_versions::id.desc(),
+            ))
+            .limit(limit)
+            .select((Package::as_select(), PackageVersion::as_select()))
+            .load::<(Package, PackageVersion)>(&mut conn)
+    }
+
     /// Creates a package with organization link for scoped packages
     pub fn create_or_get_package_with_organization(
         &self,
@@ -689,4 +633,96 @@ impl<'a> PackageOperations<'a> {
             .filter(packages::organization_id.eq(organization_id))
             .load::<Package>(&mut conn)
     }
+
+    /// Changes a package's visibility and records the change in one transaction
+    pub fn set_package_visibility(
+        &self,
+        change: &NewPackageVisibilityChange,
+    ) -> Result<PackageVisibilityChange, diesel::result::Error> {
+        use crate::schema::package_visibility_changes;
+
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            diesel::update(packages::table.find(change.package_id))
+                .set((
+                    packages::visibility.eq(&change.new_visibility),
+                    packages::updated_at.eq(change.created_at),
+                ))
+                .execute(conn)?;
+
+            diesel::insert_into(package_visibility_changes::table)
+                .values(change)
+                .get_result::<PackageVisibilityChange>(conn)
+        })
+    }
+
+    /// Moves a package to a new author and organization and replaces its owners with the
+    /// given users as admins, all in one transaction
+    pub fn transfer_package(
+        &self,
+        package_id: i32,
+        author_id: Option<i32>,
+        organization_id: Option<i32>,
+        owner_ids: &[i32],
+    ) -> Result<Package, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            let package = diesel::update(packages::table.find(package_id))
+                .set((
+                    packages::author_id.eq(author_id),
+                    packages::organization_id.eq(organization_id),
+                    packages::updated_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .get_result::<Package>(conn)?;
+
+            diesel::delete(
+                package_owners::table.filter(package_owners::package_name.eq(&package.name)),
+            )
+            .execute(conn)?;
+
+            let owners = owner_ids
+                .iter()
+                .map(|&user_id| {
+                    NewPackageOwner::new(package.name.clone(), user_id, "admin".to_string())
+                })
+                .collect::<Vec<_>>();
+            diesel::insert_into(package_owners::table)
+                .values(&owners)
+                .execute(conn)?;
+
+            Ok(package)
+        })
+    }
+
+    /// Gets the visibility change history of a package, newest first
+    pub fn get_visibility_changes(
+        &self,
+        package_id: i32,
+    ) -> Result<Vec<PackageVisibilityChange>, diesel::result::Error> {
+        use crate::schema::package_visibility_changes;
+
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        package_visibility_changes::table
+            .filter(package_visibility_changes::package_id.eq(package_id))
+            .order(package_visibility_changes::created_at.desc())
+            .load::<PackageVisibilityChange>(&mut conn)
+    }
 }
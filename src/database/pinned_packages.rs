@@ -0,0 +1,58 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::pinned_package::*;
+use crate::schema::pinned_packages;
+use diesel::prelude::*;
+
+/// Pinned package database operations
+pub struct PinnedPackageOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> PinnedPackageOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Lists all pinned packages ordered by name
+    pub fn list_pinned_packages(&self) -> Result<Vec<PinnedPackage>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        pinned_packages::table
+            .order(pinned_packages::name.asc())
+            .load::<PinnedPackage>(&mut conn)
+    }
+
+    /// Pins a package
+    pub fn create_pinned_package(
+        &self,
+        entry: &NewPinnedPackage,
+    ) -> Result<PinnedPackage, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(pinned_packages::table)
+            .values(entry)
+            .get_result::<PinnedPackage>(&mut conn)
+    }
+
+    /// Unpins a package. Returns the number of deleted rows.
+    pub fn delete_pinned_package(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(pinned_packages::table.filter(pinned_packages::id.eq(id))).execute(&mut conn)
+    }
+}
@@ -0,0 +1,103 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::quarantine::*;
+use crate::schema::quarantined_packages;
+use chrono::Utc;
+use diesel::prelude::*;
+
+/// Upstream package quarantine database operations
+pub struct QuarantineOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> QuarantineOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Gets the quarantine entries of a package, the package entry and those of its majors
+    pub fn get_quarantine_entries(
+        &self,
+        package_name: &str,
+    ) -> Result<Vec<QuarantinedPackage>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        quarantined_packages::table
+            .filter(quarantined_packages::name.eq(package_name))
+            .load::<QuarantinedPackage>(&mut conn)
+    }
+
+    /// Lists quarantine entries, newest first, optionally only those with the given status
+    pub fn list_quarantined_packages(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<QuarantinedPackage>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = quarantined_packages::table
+            .order(quarantined_packages::created_at.desc())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(quarantined_packages::status.eq(status));
+        }
+
+        query.load::<QuarantinedPackage>(&mut conn)
+    }
+
+    /// Records quarantine entries. Entries that exist already keep their status.
+    pub fn create_quarantined_packages(
+        &self,
+        entries: &[NewQuarantinedPackage],
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            let mut created = 0;
+            for entry in entries {
+                created += diesel::insert_into(quarantined_packages::table)
+                    .values(entry)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+            Ok(created)
+        })
+    }
+
+    /// Sets the review status of a quarantine entry
+    pub fn review_quarantined_package(
+        &self,
+        id: i32,
+        status: &str,
+        reviewer_id: i32,
+    ) -> Result<Option<QuarantinedPackage>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(quarantined_packages::table.filter(quarantined_packages::id.eq(id)))
+            .set((
+                quarantined_packages::status.eq(status),
+                quarantined_packages::reviewed_by.eq(Some(reviewer_id)),
+                quarantined_packages::reviewed_at.eq(Some(Utc::now().naive_utc())),
+            ))
+            .get_result::<QuarantinedPackage>(&mut conn)
+            .optional()
+    }
+}
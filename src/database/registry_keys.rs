@@ -0,0 +1,90 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::signing::*;
+use crate::schema::registry_keys;
+use diesel::prelude::*;
+
+/// Registry signing key database operations
+pub struct RegistryKeyOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> RegistryKeyOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Lists all registry keys, local keys first
+    pub fn list_registry_keys(&self) -> Result<Vec<RegistryKey>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        registry_keys::table
+            .order((
+                registry_keys::source.asc(),
+                registry_keys::created_at.desc(),
+            ))
+            .load::<RegistryKey>(&mut conn)
+    }
+
+    /// Gets the newest local key, the one packages are signed with
+    pub fn get_local_registry_key(&self) -> Result<Option<RegistryKey>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        registry_keys::table
+            .filter(registry_keys::source.eq("local"))
+            .filter(registry_keys::private_key.is_not_null())
+            .order(registry_keys::id.desc())
+            .first::<RegistryKey>(&mut conn)
+            .optional()
+    }
+
+    pub fn create_registry_key(
+        &self,
+        new_key: &NewRegistryKey,
+    ) -> Result<RegistryKey, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(registry_keys::table)
+            .values(new_key)
+            .returning(RegistryKey::as_returning())
+            .get_result(&mut conn)
+    }
+
+    /// Replaces the cached keys of the upstream registry
+    pub fn replace_upstream_registry_keys(
+        &self,
+        keys: &[NewRegistryKey],
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            diesel::delete(registry_keys::table.filter(registry_keys::source.eq("upstream")))
+                .execute(conn)?;
+
+            diesel::insert_into(registry_keys::table)
+                .values(keys)
+                .execute(conn)?;
+
+            Ok(())
+        })
+    }
+}
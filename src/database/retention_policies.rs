@@ -0,0 +1,84 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::retention::*;
+use crate::schema::retention_policies;
+use diesel::prelude::*;
+
+/// Retention policy database operations
+pub struct RetentionPolicyOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> RetentionPolicyOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Lists the retention policies of an organization, or of all organizations
+    pub fn list_retention_policies(
+        &self,
+        organization_id: Option<i32>,
+    ) -> Result<Vec<RetentionPolicy>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let mut query = retention_policies::table.into_boxed();
+        if let Some(organization_id) = organization_id {
+            query = query.filter(retention_policies::organization_id.eq(organization_id));
+        }
+
+        query
+            .order((
+                retention_policies::organization_id.asc(),
+                retention_policies::name.asc(),
+            ))
+            .load::<RetentionPolicy>(&mut conn)
+    }
+
+    /// Creates a retention policy or replaces the one with the same name
+    pub fn upsert_retention_policy(
+        &self,
+        policy: &NewRetentionPolicy,
+    ) -> Result<RetentionPolicy, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(retention_policies::table)
+            .values(policy)
+            .on_conflict((
+                retention_policies::organization_id,
+                retention_policies::name,
+            ))
+            .do_update()
+            .set(policy)
+            .get_result::<RetentionPolicy>(&mut conn)
+    }
+
+    /// Deletes a retention policy by name. Returns the number of deleted rows.
+    pub fn delete_retention_policy(
+        &self,
+        organization_id: i32,
+        name: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(
+            retention_policies::table
+                .filter(retention_policies::organization_id.eq(organization_id))
+                .filter(retention_policies::name.eq(name)),
+        )
+        .execute(&mut conn)
+    }
+}
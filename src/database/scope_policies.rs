@@ -0,0 +1,80 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::scope_policy::*;
+use crate::schema::scope_policies;
+use diesel::prelude::*;
+
+/// Per-scope access policy database operations
+pub struct ScopePolicyOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> ScopePolicyOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Gets the policy of a scope (including the leading `@`)
+    pub fn get_scope_policy(
+        &self,
+        scope: &str,
+    ) -> Result<Option<ScopePolicy>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        scope_policies::table
+            .filter(scope_policies::scope.eq(scope))
+            .first::<ScopePolicy>(&mut conn)
+            .optional()
+    }
+
+    /// Lists all scope policies ordered by scope
+    pub fn list_scope_policies(&self) -> Result<Vec<ScopePolicy>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        scope_policies::table
+            .order(scope_policies::scope.asc())
+            .load::<ScopePolicy>(&mut conn)
+    }
+
+    /// Creates the policy of a scope or replaces the existing one
+    pub fn upsert_scope_policy(
+        &self,
+        policy: &NewScopePolicy,
+    ) -> Result<ScopePolicy, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(scope_policies::table)
+            .values(policy)
+            .on_conflict(scope_policies::scope)
+            .do_update()
+            .set(policy)
+            .get_result::<ScopePolicy>(&mut conn)
+    }
+
+    /// Deletes the policy of a scope. Returns the number of deleted rows.
+    pub fn delete_scope_policy(&self, scope: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(scope_policies::table.filter(scope_policies::scope.eq(scope)))
+            .execute(&mut conn)
+    }
+}
@@ -1,30 +1,109 @@
+use super::advisories::AdvisoryOperations;
+use super::allowed_packages::AllowedPackageOperations;
 use super::analytics::AnalyticsOperations;
+use super::attestations::AttestationOperations;
+use super::audit_log::AuditLogOperations;
+use super::bandwidth::BandwidthOperations;
+use super::blocked_names::BlockedNameOperations;
 use super::cache_stats::CacheStatsOperations;
-use super::connection::{DbConnection, DbPool, create_pool, get_connection_with_retry};
+use super::client_certificates::ClientCertificateOperations;
+use super::connection::{
+    DbConnection, DbMetrics, DbPool, PoolOptions, create_pool, get_connection_with_retry,
+    schema_status,
+};
+use super::counters::{CounterOperations, PackageCacheCounts, PendingCounters};
 use super::files::{CompletePackageParams, FileOperations, PackageFileParams};
+use super::flagged_names::FlaggedNameOperations;
+use super::hooks::HookOperations;
+use super::invitations::InvitationOperations;
 use super::metadata_cache::MetadataCacheOperations;
+use super::notifications::NotificationOperations;
+use super::optimize::OptimizeOperations;
 use super::organizations::OrganizationOperations;
 use super::package_owners::PackageOwnerOperations;
 use super::packages::PackageOperations;
+use super::pinned_packages::PinnedPackageOperations;
+use super::quarantine::QuarantineOperations;
+use super::registry_keys::RegistryKeyOperations;
+use super::retention_policies::RetentionPolicyOperations;
+use super::scope_policies::ScopePolicyOperations;
+use super::service_accounts::ServiceAccountOperations;
+use super::signing_keys::SigningKeyOperations;
+use super::staged_publishes::StagedPublishOperations;
+use super::tarball_entries::TarballEntryOperations;
+use super::tasks::TaskOperations;
+use super::tombstones::TombstoneOperations;
 use super::versions::VersionOperations;
+use crate::models::advisory::{Advisory, NewAdvisory};
+use crate::models::allowed_package::{AllowedPackage, NewAllowedPackage};
+use crate::models::audit::{AuditLogEntry, AuditLogRow, NewAuditLogEntry};
+use crate::models::blocked_name::{BlockedName, NewBlockedName};
+use crate::models::cache::DatabasePoolStats;
+use crate::models::client_certificate::{ClientCertificate, NewClientCertificate};
+use crate::models::dashboard::RegistryTotals;
+use crate::models::downloads::{DownloadRollup, NewDownloadRollup, VersionDownload};
+use crate::models::flagged_name::{FlaggedName, NewFlaggedName};
+use crate::models::hook::{Hook, NewHook};
+use crate::models::invitation::{Invitation, NewInvitation};
+use crate::models::maintenance::DatabaseOptimizeReport;
 use crate::models::metadata_cache::{MetadataCacheRecord, MetadataCacheStats};
+use crate::models::migration::SchemaStatus;
+use crate::models::notification::{
+    NewNotification, NewNotificationSubscription, Notification, NotificationSubscription,
+};
 use crate::models::organization::*;
 use crate::models::package::*;
-use crate::models::user::User;
+use crate::models::pinned_package::{NewPinnedPackage, PinnedPackage};
+use crate::models::quarantine::{NewQuarantinedPackage, QuarantinedPackage};
+use crate::models::retention::{NewRetentionPolicy, RetentionPolicy};
+use crate::models::scope_policy::{NewScopePolicy, ScopePolicy};
+use crate::models::service_account::ServiceAccount;
+use crate::models::signing::{
+    NewPackageAttestation, NewPackageSignature, NewRegistryKey, NewSigningKey, PackageAttestation,
+    PackageSignature, RegistryKey, SigningKey,
+};
+use crate::models::staged_publish::{NewStagedPublish, StagedPublish};
+use crate::models::task::{NewTask, Task, TaskStatus};
+use crate::models::tombstone::{NewVersionTombstone, VersionTombstone};
+use crate::models::user::{NewUser, UpdateUserProfile, User};
 use crate::schema::users;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 /// Main database service that provides a unified interface to all database operations
 #[derive(Debug)]
 pub struct DatabaseService {
     pub pool: DbPool,
+    metrics: Arc<DbMetrics>,
+    /// Counter updates waiting for the next flush
+    counters: Mutex<PendingCounters>,
 }
 
 impl DatabaseService {
     /// Creates a new DatabaseService with an initialized connection pool
     pub fn new(database_url: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let pool = create_pool(database_url)?;
-        Ok(Self { pool })
+        Self::with_options(database_url, &PoolOptions::default())
+    }
+
+    /// Creates a new DatabaseService with the given pool size and pragmas
+    pub fn with_options(
+        database_url: &str,
+        options: &PoolOptions,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let metrics = Arc::new(DbMetrics::default());
+        let pool = create_pool(database_url, options, &metrics)?;
+        Ok(Self {
+            pool,
+            metrics,
+            counters: Mutex::new(PendingCounters::default()),
+        })
+    }
+
+    /// Connections of the pool, time spent waiting for them and query counts
+    pub fn pool_stats(&self) -> DatabasePoolStats {
+        self.metrics.stats(&self.pool)
     }
 
     pub fn run_migrations(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -39,6 +118,12 @@ impl DatabaseService {
         }
     }
 
+    /// Applied and pending migrations
+    pub fn schema_status(&self) -> Result<SchemaStatus, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = get_connection_with_retry(&self.pool)?;
+        schema_status(&mut conn)
+    }
+
     /// Gets a connection from the pool with retry logic
     pub fn get_connection(&self) -> Result<DbConnection, diesel::r2d2::Error> {
         get_connection_with_retry(&self.pool)
@@ -74,6 +159,11 @@ impl DatabaseService {
         ops.get_package_by_name(name)
     }
 
+    pub fn search_packages(&self, terms: &[&str]) -> Result<Vec<Package>, diesel::result::Error> {
+        let ops = PackageOperations::new(&self.pool);
+        ops.search_packages(terms)
+    }
+
     pub fn get_package_with_versions(
         &self,
         name: &str,
@@ -97,6 +187,7 @@ impl DatabaseService {
         ops.get_recent_packages(limit)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn get_packages_paginated(
         &self,
         limit: i64,
@@ -104,9 +195,55 @@ impl DatabaseService {
         search_query: Option<&str>,
         sort_column: Option<&str>,
         sort_order: Option<&str>,
+        include_private: bool,
+        organization_id: Option<i32>,
     ) -> Result<(Vec<PackageWithVersions>, i64), diesel::result::Error> {
         let ops = PackageOperations::new(&self.pool);
-        ops.get_packages_paginated(limit, offset, search_query, sort_column, sort_order)
+        ops.get_packages_paginated(
+            limit,
+            offset,
+            search_query,
+            sort_column,
+            sort_order,
+            include_private,
+            organization_id,
+        )
+    }
+
+    pub fn set_package_visibility(
+        &self,
+        change: &NewPackageVisibilityChange,
+    ) -> Result<PackageVisibilityChange, diesel::result::Error> {
+        let ops = PackageOperations::new(&self.pool);
+        ops.set_package_visibility(change)
+    }
+
+    pub fn get_recent_versions(
+        &self,
+        limit: i64,
+        published: Option<bool>,
+    ) -> Result<Vec<(Package, PackageVersion)>, diesel::result::Error> {
+        let ops = PackageOperations::new(&self.pool);
+        ops.get_recent_versions(limit, published)
+    }
+
+    pub fn transfer_package(
+        &self,
+        package_id: i32,
+        author_id: Option<i32>,
+        organization_id: Option<i32>,
+        owner_ids: &[i32],
+    ) -> Result<Package, diesel::result::Error> {
+        let ops = PackageOperations::new(&self.pool);
+        ops.transfer_package(package_id, author_id, organization_id, owner_ids)
+    }
+
+    pub fn get_visibility_changes(
+        &self,
+        package_id: i32,
+    ) -> Result<Vec<PackageVisibilityChange>, diesel::result::Error> {
+        let ops = PackageOperations::new(&self.pool);
+        ops.get_visibility_changes(package_id)
     }
 
     pub fn update_package_metadata(
@@ -141,6 +278,26 @@ impl DatabaseService {
         ops.create_or_get_package_version_with_metadata(package_id, version, package_json)
     }
 
+    pub fn set_version_checksums(
+        &self,
+        version_id: i32,
+        shasum: &str,
+        integrity: &str,
+    ) -> Result<(), diesel::result::Error> {
+        let ops = VersionOperations::new(&self.pool);
+        ops.set_version_checksums(version_id, shasum, integrity)
+    }
+
+    pub fn set_version_registry_signature(
+        &self,
+        version_id: i32,
+        key_id: &str,
+        signature: &str,
+    ) -> Result<(), diesel::result::Error> {
+        let ops = VersionOperations::new(&self.pool);
+        ops.set_version_registry_signature(version_id, key_id, signature)
+    }
+
     pub fn create_or_get_package_version_with_metadata_and_update(
         &self,
         package_id: i32,
@@ -157,6 +314,16 @@ impl DatabaseService {
         )
     }
 
+    pub fn set_version_yanked(
+        &self,
+        version_id: i32,
+        yanked_at: Option<NaiveDateTime>,
+        reason: Option<String>,
+    ) -> Result<PackageVersion, diesel::result::Error> {
+        let ops = VersionOperations::new(&self.pool);
+        ops.set_version_yanked(version_id, yanked_at, reason)
+    }
+
     pub fn get_package_versions(
         &self,
         package_id: i32,
@@ -165,6 +332,25 @@ impl DatabaseService {
         ops.get_package_versions(package_id)
     }
 
+    pub fn list_published_versions_without_files(
+        &self,
+    ) -> Result<Vec<(Package, PackageVersion)>, diesel::result::Error> {
+        let ops = VersionOperations::new(&self.pool);
+        ops.list_published_versions_without_files()
+    }
+
+    pub fn list_versions_without_package(
+        &self,
+    ) -> Result<Vec<PackageVersion>, diesel::result::Error> {
+        let ops = VersionOperations::new(&self.pool);
+        ops.list_versions_without_package()
+    }
+
+    pub fn delete_package_version(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = VersionOperations::new(&self.pool);
+        ops.delete_package_version(id)
+    }
+
     // Package file operations
     #[allow(clippy::too_many_arguments)]
     pub fn create_or_update_package_file(
@@ -198,6 +384,23 @@ impl DatabaseService {
         ops.get_package_file(package_name, filename)
     }
 
+    pub fn list_package_files(
+        &self,
+    ) -> Result<Vec<(Package, PackageVersion, PackageFile)>, diesel::result::Error> {
+        let ops = FileOperations::new(&self.pool);
+        ops.list_package_files()
+    }
+
+    pub fn list_files_without_version(&self) -> Result<Vec<PackageFile>, diesel::result::Error> {
+        let ops = FileOperations::new(&self.pool);
+        ops.list_files_without_version()
+    }
+
+    pub fn delete_package_file(&self, file_id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = FileOperations::new(&self.pool);
+        ops.delete_package_file(file_id)
+    }
+
     pub fn update_file_access_info(&self, file_id: i32) -> Result<(), diesel::result::Error> {
         let ops = FileOperations::new(&self.pool);
         ops.update_file_access_info(file_id)
@@ -212,6 +415,22 @@ impl DatabaseService {
     }
 
     // Analytics operations
+    pub fn get_user_storage_usage(
+        &self,
+        user_id: i32,
+    ) -> Result<StorageUsage, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_user_storage_usage(user_id)
+    }
+
+    pub fn get_organization_storage_usage(
+        &self,
+        organization_id: i32,
+    ) -> Result<StorageUsage, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_organization_storage_usage(organization_id)
+    }
+
     pub fn get_popular_packages(
         &self,
         limit: i64,
@@ -220,11 +439,200 @@ impl DatabaseService {
         ops.get_popular_packages(limit)
     }
 
+    pub fn get_public_popular_packages(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<PopularPackage>, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_public_popular_packages(limit)
+    }
+
     pub fn get_cache_stats(&self) -> Result<(usize, i64), diesel::result::Error> {
         let ops = AnalyticsOperations::new(&self.pool);
         ops.get_cache_stats()
     }
 
+    pub fn get_registry_totals(&self) -> Result<RegistryTotals, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_registry_totals()
+    }
+
+    pub fn record_version_download(
+        &self,
+        package_name: &str,
+        version: &str,
+        day: NaiveDate,
+    ) -> Result<(), diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.record_version_download(package_name, version, day)
+    }
+
+    /// Queues a download of a package version, written with the next counter flush
+    pub fn queue_version_download(&self, package_name: &str, version: &str, day: NaiveDate) {
+        let key = (package_name.to_string(), version.to_string(), day);
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .downloads
+            .entry(key)
+            .or_default() += 1;
+    }
+
+    /// Queues a read of a package file, written with the next counter flush
+    pub fn queue_file_access(&self, file_id: i32) {
+        let now = chrono::Utc::now().naive_utc();
+        let mut counters = self.counters.lock().unwrap();
+        let access = counters.file_accesses.entry(file_id).or_insert((0, now));
+        access.0 += 1;
+        access.1 = now;
+    }
+
+    /// Queues a cache hit that served `bytes` of a package
+    pub fn queue_cache_hit(&self, package_name: &str, bytes: u64) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.cache_hits += 1;
+        counters.count_package_cache(
+            package_name,
+            PackageCacheCounts {
+                hits: 1,
+                cache_bytes: bytes as i64,
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn queue_cache_miss(&self, package_name: &str) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.cache_misses += 1;
+        counters.count_package_cache(
+            package_name,
+            PackageCacheCounts {
+                misses: 1,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Queues `bytes` of a package served to a user, through one of their tokens when
+    /// `token_id` is given
+    pub fn queue_bandwidth(
+        &self,
+        package_name: &str,
+        user_id: Option<i32>,
+        token_id: Option<i32>,
+        bytes: u64,
+    ) {
+        let key = (
+            chrono::Utc::now().date_naive(),
+            package_name.to_string(),
+            user_id.unwrap_or(0),
+            token_id.unwrap_or(0),
+        );
+        let mut counters = self.counters.lock().unwrap();
+        let entry = counters.bandwidth.entry(key).or_default();
+        entry.0 += bytes as i64;
+        entry.1 += 1;
+    }
+
+    /// Queues `bytes` of a package fetched from the upstream registry
+    pub fn queue_upstream_transfer(&self, package_name: &str, bytes: u64) {
+        self.counters.lock().unwrap().count_package_cache(
+            package_name,
+            PackageCacheCounts {
+                upstream_bytes: bytes as i64,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Writes the queued counters in one transaction. When that fails they stay queued
+    /// for the next attempt.
+    pub fn flush_counters(&self) -> Result<(), diesel::result::Error> {
+        let pending = std::mem::take(&mut *self.counters.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let ops = CounterOperations::new(&self.pool);
+        ops.write(&pending).inspect_err(|_| {
+            self.counters.lock().unwrap().merge(pending.clone());
+        })
+    }
+
+    pub fn get_version_downloads(
+        &self,
+        package_name: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<VersionDownload>, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_version_downloads(package_name, from, to)
+    }
+
+    pub fn get_download_rollups(
+        &self,
+        package_name: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DownloadRollup>, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_download_rollups(package_name, from, to)
+    }
+
+    pub fn get_version_downloads_before(
+        &self,
+        before: NaiveDate,
+    ) -> Result<Vec<VersionDownload>, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_version_downloads_before(before)
+    }
+
+    pub fn get_download_rollups_before(
+        &self,
+        period: &str,
+        before: NaiveDate,
+    ) -> Result<Vec<DownloadRollup>, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_download_rollups_before(period, before)
+    }
+
+    pub fn roll_up_downloads(
+        &self,
+        from_period: Option<&str>,
+        before: NaiveDate,
+        rollups: &[NewDownloadRollup],
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.roll_up_downloads(from_period, before, rollups)
+    }
+
+    pub fn get_downloads_since(
+        &self,
+        package_names: &[String],
+        since: NaiveDate,
+    ) -> Result<HashMap<String, i64>, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_downloads_since(package_names, since)
+    }
+
+    pub fn get_most_downloaded_since(
+        &self,
+        since: NaiveDate,
+        limit: i64,
+    ) -> Result<Vec<(String, i64)>, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.get_most_downloaded_since(since, limit)
+    }
+
+    pub fn delete_download_stats_before(
+        &self,
+        before: NaiveDate,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = AnalyticsOperations::new(&self.pool);
+        ops.delete_download_stats_before(before)
+    }
+
     // Cache stats operations
     pub fn get_persistent_cache_stats(
         &self,
@@ -242,6 +650,25 @@ impl DatabaseService {
         ops.update_cache_stats(hit_count, miss_count)
     }
 
+    pub fn get_bandwidth_usage(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<crate::models::BandwidthUsageRow>, diesel::result::Error> {
+        let ops = BandwidthOperations::new(&self.pool);
+        ops.get_usage(from, to)
+    }
+
+    pub fn get_package_cache_stats(
+        &self,
+        package_name: Option<&str>,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<crate::models::cache::PackageCacheStat>, diesel::result::Error> {
+        let ops = CacheStatsOperations::new(&self.pool);
+        ops.get_package_cache_stats(package_name, from, to)
+    }
+
     pub fn increment_cache_hit_count(&self) -> Result<(), diesel::result::Error> {
         let ops = CacheStatsOperations::new(&self.pool);
         ops.increment_hit_count()
@@ -280,11 +707,26 @@ impl DatabaseService {
         ops.update_metadata_access_info(package_name)
     }
 
+    pub fn list_metadata_cache_entries(
+        &self,
+    ) -> Result<Vec<MetadataCacheRecord>, diesel::result::Error> {
+        let ops = MetadataCacheOperations::new(&self.pool);
+        ops.list_metadata_cache_entries()
+    }
+
     pub fn get_metadata_cache_stats(&self) -> Result<MetadataCacheStats, diesel::result::Error> {
         let ops = MetadataCacheOperations::new(&self.pool);
         ops.get_metadata_cache_stats()
     }
 
+    pub fn delete_metadata_cache_entry(
+        &self,
+        package_name: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = MetadataCacheOperations::new(&self.pool);
+        ops.delete_metadata_cache_entry(package_name)
+    }
+
     pub fn clear_metadata_cache(&self) -> Result<usize, diesel::result::Error> {
         let ops = MetadataCacheOperations::new(&self.pool);
         ops.clear_metadata_cache()
@@ -337,6 +779,22 @@ impl DatabaseService {
         ops.get_package_owners(package_name)
     }
 
+    pub fn get_package_maintainers(
+        &self,
+        package_name: &str,
+    ) -> Result<Vec<(String, String)>, diesel::result::Error> {
+        let ops = PackageOwnerOperations::new(&self.pool);
+        ops.get_package_maintainers(package_name)
+    }
+
+    pub fn get_user_packages(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<(Package, String)>, diesel::result::Error> {
+        let ops = PackageOwnerOperations::new(&self.pool);
+        ops.get_user_packages(user_id)
+    }
+
     pub fn add_package_owner(
         &self,
         package_name: &str,
@@ -507,22 +965,798 @@ impl DatabaseService {
         PackageOperations::extract_organization_name(package_name)
     }
 
-    // User operations
-    pub fn get_user_by_username(
+    // Invitation operations
+    pub fn create_invitation(
         &self,
-        username: &str,
-    ) -> Result<Option<User>, diesel::result::Error> {
-        let mut conn = get_connection_with_retry(&self.pool).map_err(|e| {
-            diesel::result::Error::DatabaseError(
-                diesel::result::DatabaseErrorKind::UnableToSendCommand,
-                Box::new(e.to_string()),
-            )
-        })?;
+        new_invitation: &NewInvitation,
+    ) -> Result<Invitation, diesel::result::Error> {
+        let ops = InvitationOperations::new(&self.pool);
+        ops.create_invitation(new_invitation)
+    }
 
-        users::table
-            .filter(users::username.eq(username))
-            .filter(users::is_active.eq(true))
-            .first::<User>(&mut conn)
-            .optional()
+    pub fn get_invitation_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<Invitation>, diesel::result::Error> {
+        let ops = InvitationOperations::new(&self.pool);
+        ops.get_invitation_by_token(token)
+    }
+
+    pub fn list_invitations(&self) -> Result<Vec<Invitation>, diesel::result::Error> {
+        let ops = InvitationOperations::new(&self.pool);
+        ops.list_invitations()
+    }
+
+    pub fn redeem_invitation(
+        &self,
+        invitation_id: i32,
+        user_id: i32,
+    ) -> Result<bool, diesel::result::Error> {
+        let ops = InvitationOperations::new(&self.pool);
+        ops.redeem_invitation(invitation_id, user_id)
+    }
+
+    pub fn delete_invitation(&self, invitation_id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = InvitationOperations::new(&self.pool);
+        ops.delete_invitation(invitation_id)
+    }
+
+    // Signing key operations
+    pub fn create_signing_key(
+        &self,
+        new_key: &NewSigningKey,
+    ) -> Result<SigningKey, diesel::result::Error> {
+        let ops = SigningKeyOperations::new(&self.pool);
+        ops.create_signing_key(new_key)
+    }
+
+    pub fn list_signing_keys(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<SigningKey>, diesel::result::Error> {
+        let ops = SigningKeyOperations::new(&self.pool);
+        ops.list_signing_keys(organization_id)
+    }
+
+    pub fn get_active_signing_keys(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<SigningKey>, diesel::result::Error> {
+        let ops = SigningKeyOperations::new(&self.pool);
+        ops.get_active_signing_keys(organization_id)
+    }
+
+    pub fn revoke_signing_key(
+        &self,
+        organization_id: i32,
+        key_id: &str,
+    ) -> Result<bool, diesel::result::Error> {
+        let ops = SigningKeyOperations::new(&self.pool);
+        ops.revoke_signing_key(organization_id, key_id)
+    }
+
+    pub fn create_package_signature(
+        &self,
+        new_signature: &NewPackageSignature,
+    ) -> Result<PackageSignature, diesel::result::Error> {
+        let ops = SigningKeyOperations::new(&self.pool);
+        ops.create_package_signature(new_signature)
+    }
+
+    pub fn get_package_signatures(
+        &self,
+        package_version_id: i32,
+    ) -> Result<Vec<(PackageSignature, SigningKey)>, diesel::result::Error> {
+        let ops = SigningKeyOperations::new(&self.pool);
+        ops.get_package_signatures(package_version_id)
+    }
+
+    // Attestation operations
+    pub fn create_package_attestation(
+        &self,
+        new_attestation: &NewPackageAttestation,
+    ) -> Result<PackageAttestation, diesel::result::Error> {
+        let ops = AttestationOperations::new(&self.pool);
+        ops.create_package_attestation(new_attestation)
+    }
+
+    pub fn get_package_attestations(
+        &self,
+        package_version_id: i32,
+    ) -> Result<Vec<PackageAttestation>, diesel::result::Error> {
+        let ops = AttestationOperations::new(&self.pool);
+        ops.get_package_attestations(package_version_id)
+    }
+
+    // Registry key operations
+    pub fn list_registry_keys(&self) -> Result<Vec<RegistryKey>, diesel::result::Error> {
+        let ops = RegistryKeyOperations::new(&self.pool);
+        ops.list_registry_keys()
+    }
+
+    pub fn get_local_registry_key(&self) -> Result<Option<RegistryKey>, diesel::result::Error> {
+        let ops = RegistryKeyOperations::new(&self.pool);
+        ops.get_local_registry_key()
+    }
+
+    pub fn create_registry_key(
+        &self,
+        new_key: &NewRegistryKey,
+    ) -> Result<RegistryKey, diesel::result::Error> {
+        let ops = RegistryKeyOperations::new(&self.pool);
+        ops.create_registry_key(new_key)
+    }
+
+    pub fn replace_upstream_registry_keys(
+        &self,
+        keys: &[NewRegistryKey],
+    ) -> Result<(), diesel::result::Error> {
+        let ops = RegistryKeyOperations::new(&self.pool);
+        ops.replace_upstream_registry_keys(keys)
+    }
+
+    // Scope policy operations
+    pub fn get_scope_policy(
+        &self,
+        scope: &str,
+    ) -> Result<Option<ScopePolicy>, diesel::result::Error> {
+        let ops = ScopePolicyOperations::new(&self.pool);
+        ops.get_scope_policy(scope)
+    }
+
+    pub fn list_scope_policies(&self) -> Result<Vec<ScopePolicy>, diesel::result::Error> {
+        let ops = ScopePolicyOperations::new(&self.pool);
+        ops.list_scope_policies()
+    }
+
+    pub fn upsert_scope_policy(
+        &self,
+        policy: &NewScopePolicy,
+    ) -> Result<ScopePolicy, diesel::result::Error> {
+        let ops = ScopePolicyOperations::new(&self.pool);
+        ops.upsert_scope_policy(policy)
+    }
+
+    pub fn delete_scope_policy(&self, scope: &str) -> Result<usize, diesel::result::Error> {
+        let ops = ScopePolicyOperations::new(&self.pool);
+        ops.delete_scope_policy(scope)
+    }
+
+    // Advisory operations
+    pub fn get_advisories_for_packages(
+        &self,
+        package_names: &[&str],
+    ) -> Result<Vec<Advisory>, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.get_advisories_for_packages(package_names)
+    }
+
+    pub fn get_advisory_versions(
+        &self,
+        source: &str,
+    ) -> Result<HashMap<String, Option<NaiveDateTime>>, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.get_advisory_versions(source)
+    }
+
+    pub fn replace_advisory(
+        &self,
+        source: &str,
+        advisory_id: &str,
+        rows: &[NewAdvisory],
+    ) -> Result<(), diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.replace_advisory(source, advisory_id, rows)
+    }
+
+    pub fn delete_advisory(
+        &self,
+        source: &str,
+        advisory_id: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.delete_advisory(source, advisory_id)
+    }
+
+    pub fn get_advisory_stats(
+        &self,
+        source: &str,
+    ) -> Result<(i64, i64, Option<NaiveDateTime>), diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.get_advisory_stats(source)
+    }
+
+    pub fn list_advisories(&self, source: &str) -> Result<Vec<Advisory>, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.list_advisories(source)
+    }
+
+    pub fn create_advisory(
+        &self,
+        new_advisory: &NewAdvisory,
+    ) -> Result<Advisory, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.create_advisory(new_advisory)
+    }
+
+    pub fn update_advisory(
+        &self,
+        id: i32,
+        source: &str,
+        changes: &NewAdvisory,
+    ) -> Result<Option<Advisory>, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.update_advisory(id, source, changes)
+    }
+
+    pub fn delete_advisory_by_id(
+        &self,
+        id: i32,
+        source: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.delete_advisory_by_id(id, source)
+    }
+
+    pub fn get_upstream_package_names(&self) -> Result<Vec<String>, diesel::result::Error> {
+        let ops = AdvisoryOperations::new(&self.pool);
+        ops.get_upstream_package_names()
+    }
+
+    // Name blocklist operations
+    pub fn list_blocked_names(&self) -> Result<Vec<BlockedName>, diesel::result::Error> {
+        let ops = BlockedNameOperations::new(&self.pool);
+        ops.list_blocked_names()
+    }
+
+    pub fn create_blocked_name(
+        &self,
+        entry: &NewBlockedName,
+    ) -> Result<BlockedName, diesel::result::Error> {
+        let ops = BlockedNameOperations::new(&self.pool);
+        ops.create_blocked_name(entry)
+    }
+
+    pub fn delete_blocked_name(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = BlockedNameOperations::new(&self.pool);
+        ops.delete_blocked_name(id)
+    }
+
+    // Upstream allowlist operations
+    pub fn list_allowed_packages(&self) -> Result<Vec<AllowedPackage>, diesel::result::Error> {
+        let ops = AllowedPackageOperations::new(&self.pool);
+        ops.list_allowed_packages()
+    }
+
+    pub fn create_allowed_package(
+        &self,
+        entry: &NewAllowedPackage,
+    ) -> Result<AllowedPackage, diesel::result::Error> {
+        let ops = AllowedPackageOperations::new(&self.pool);
+        ops.create_allowed_package(entry)
+    }
+
+    pub fn delete_allowed_package(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = AllowedPackageOperations::new(&self.pool);
+        ops.delete_allowed_package(id)
+    }
+
+    // Pinned package operations
+    pub fn list_pinned_packages(&self) -> Result<Vec<PinnedPackage>, diesel::result::Error> {
+        let ops = PinnedPackageOperations::new(&self.pool);
+        ops.list_pinned_packages()
+    }
+
+    pub fn create_pinned_package(
+        &self,
+        entry: &NewPinnedPackage,
+    ) -> Result<PinnedPackage, diesel::result::Error> {
+        let ops = PinnedPackageOperations::new(&self.pool);
+        ops.create_pinned_package(entry)
+    }
+
+    pub fn delete_pinned_package(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = PinnedPackageOperations::new(&self.pool);
+        ops.delete_pinned_package(id)
+    }
+
+    // Retention policy operations
+    pub fn list_retention_policies(
+        &self,
+        organization_id: Option<i32>,
+    ) -> Result<Vec<RetentionPolicy>, diesel::result::Error> {
+        let ops = RetentionPolicyOperations::new(&self.pool);
+        ops.list_retention_policies(organization_id)
+    }
+
+    pub fn upsert_retention_policy(
+        &self,
+        policy: &NewRetentionPolicy,
+    ) -> Result<RetentionPolicy, diesel::result::Error> {
+        let ops = RetentionPolicyOperations::new(&self.pool);
+        ops.upsert_retention_policy(policy)
+    }
+
+    pub fn delete_retention_policy(
+        &self,
+        organization_id: i32,
+        name: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = RetentionPolicyOperations::new(&self.pool);
+        ops.delete_retention_policy(organization_id, name)
+    }
+
+    // Audit log operations
+    pub fn create_audit_log_entry(
+        &self,
+        entry: &NewAuditLogEntry,
+    ) -> Result<AuditLogEntry, diesel::result::Error> {
+        let ops = AuditLogOperations::new(&self.pool);
+        ops.create_audit_log_entry(entry)
+    }
+
+    pub fn list_organization_audit_log(
+        &self,
+        organization_id: i32,
+        limit: i64,
+    ) -> Result<Vec<AuditLogRow>, diesel::result::Error> {
+        let ops = AuditLogOperations::new(&self.pool);
+        ops.list_organization_audit_log(organization_id, limit)
+    }
+
+    pub fn list_package_audit_log(
+        &self,
+        package_name: &str,
+        limit: i64,
+    ) -> Result<Vec<AuditLogRow>, diesel::result::Error> {
+        let ops = AuditLogOperations::new(&self.pool);
+        ops.list_package_audit_log(package_name, limit)
+    }
+
+    pub fn get_organization_publish_activity(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<(i32, i64, NaiveDateTime)>, diesel::result::Error> {
+        let ops = AuditLogOperations::new(&self.pool);
+        ops.get_organization_publish_activity(organization_id)
+    }
+
+    // Version tombstone operations
+    pub fn get_version_tombstone(
+        &self,
+        package_name: &str,
+        version: &str,
+    ) -> Result<Option<VersionTombstone>, diesel::result::Error> {
+        let ops = TombstoneOperations::new(&self.pool);
+        ops.get_version_tombstone(package_name, version)
+    }
+
+    pub fn create_version_tombstone(
+        &self,
+        tombstone: &NewVersionTombstone,
+    ) -> Result<(), diesel::result::Error> {
+        let ops = TombstoneOperations::new(&self.pool);
+        ops.create_version_tombstone(tombstone)
+    }
+
+    // Tarball file listing operations
+    pub fn get_tarball_entries(
+        &self,
+        package_version_id: i32,
+    ) -> Result<Vec<TarballEntry>, diesel::result::Error> {
+        let ops = TarballEntryOperations::new(&self.pool);
+        ops.get_tarball_entries(package_version_id)
+    }
+
+    pub fn create_tarball_entries(
+        &self,
+        entries: &[NewTarballEntry],
+    ) -> Result<(), diesel::result::Error> {
+        let ops = TarballEntryOperations::new(&self.pool);
+        ops.create_tarball_entries(entries)
+    }
+
+    // Typosquatting review operations
+    pub fn get_flagged_name(
+        &self,
+        package_name: &str,
+    ) -> Result<Option<FlaggedName>, diesel::result::Error> {
+        let ops = FlaggedNameOperations::new(&self.pool);
+        ops.get_flagged_name(package_name)
+    }
+
+    pub fn list_flagged_names(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<FlaggedName>, diesel::result::Error> {
+        let ops = FlaggedNameOperations::new(&self.pool);
+        ops.list_flagged_names(status)
+    }
+
+    pub fn create_flagged_name(
+        &self,
+        flagged: &NewFlaggedName,
+    ) -> Result<FlaggedName, diesel::result::Error> {
+        let ops = FlaggedNameOperations::new(&self.pool);
+        ops.create_flagged_name(flagged)
+    }
+
+    pub fn review_flagged_name(
+        &self,
+        id: i32,
+        status: &str,
+        reviewer_id: i32,
+    ) -> Result<Option<FlaggedName>, diesel::result::Error> {
+        let ops = FlaggedNameOperations::new(&self.pool);
+        ops.review_flagged_name(id, status, reviewer_id)
+    }
+
+    // Upstream quarantine operations
+    pub fn get_quarantine_entries(
+        &self,
+        package_name: &str,
+    ) -> Result<Vec<QuarantinedPackage>, diesel::result::Error> {
+        let ops = QuarantineOperations::new(&self.pool);
+        ops.get_quarantine_entries(package_name)
+    }
+
+    pub fn list_quarantined_packages(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<QuarantinedPackage>, diesel::result::Error> {
+        let ops = QuarantineOperations::new(&self.pool);
+        ops.list_quarantined_packages(status)
+    }
+
+    pub fn create_quarantined_packages(
+        &self,
+        entries: &[NewQuarantinedPackage],
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = QuarantineOperations::new(&self.pool);
+        ops.create_quarantined_packages(entries)
+    }
+
+    pub fn review_quarantined_package(
+        &self,
+        id: i32,
+        status: &str,
+        reviewer_id: i32,
+    ) -> Result<Option<QuarantinedPackage>, diesel::result::Error> {
+        let ops = QuarantineOperations::new(&self.pool);
+        ops.review_quarantined_package(id, status, reviewer_id)
+    }
+
+    // npm hook operations
+    pub fn get_hook(&self, id: i32) -> Result<Option<Hook>, diesel::result::Error> {
+        let ops = HookOperations::new(&self.pool);
+        ops.get_hook(id)
+    }
+
+    pub fn list_hooks(
+        &self,
+        user_id: Option<i32>,
+    ) -> Result<Vec<(Hook, String)>, diesel::result::Error> {
+        let ops = HookOperations::new(&self.pool);
+        ops.list_hooks(user_id)
+    }
+
+    pub fn create_hook(&self, hook: &NewHook) -> Result<Hook, diesel::result::Error> {
+        let ops = HookOperations::new(&self.pool);
+        ops.create_hook(hook)
+    }
+
+    pub fn update_hook(
+        &self,
+        id: i32,
+        endpoint: &str,
+        secret: Option<&str>,
+    ) -> Result<Hook, diesel::result::Error> {
+        let ops = HookOperations::new(&self.pool);
+        ops.update_hook(id, endpoint, secret)
+    }
+
+    pub fn delete_hook(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = HookOperations::new(&self.pool);
+        ops.delete_hook(id)
+    }
+
+    pub fn record_hook_delivery(
+        &self,
+        id: i32,
+        response_code: i32,
+    ) -> Result<(), diesel::result::Error> {
+        let ops = HookOperations::new(&self.pool);
+        ops.record_hook_delivery(id, response_code)
+    }
+
+    // Notification operations
+    pub fn create_notification_subscription(
+        &self,
+        subscription: &NewNotificationSubscription,
+    ) -> Result<NotificationSubscription, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.create_notification_subscription(subscription)
+    }
+
+    pub fn get_notification_subscription(
+        &self,
+        id: i32,
+    ) -> Result<Option<NotificationSubscription>, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.get_notification_subscription(id)
+    }
+
+    pub fn list_notification_subscriptions(
+        &self,
+        user_id: Option<i32>,
+    ) -> Result<Vec<NotificationSubscription>, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.list_notification_subscriptions(user_id)
+    }
+
+    pub fn delete_notification_subscription(
+        &self,
+        id: i32,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.delete_notification_subscription(id)
+    }
+
+    pub fn create_notifications(
+        &self,
+        entries: &[NewNotification],
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.create_notifications(entries)
+    }
+
+    pub fn get_subscriptions_with_pending_notifications(
+        &self,
+    ) -> Result<Vec<NotificationSubscription>, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.get_subscriptions_with_pending_notifications()
+    }
+
+    pub fn get_pending_notifications(
+        &self,
+        subscription_id: i32,
+    ) -> Result<Vec<Notification>, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.get_pending_notifications(subscription_id)
+    }
+
+    pub fn mark_notifications_sent(
+        &self,
+        subscription_id: i32,
+        ids: &[i32],
+        sent_at: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.mark_notifications_sent(subscription_id, ids, sent_at)
+    }
+
+    pub fn delete_notifications_before(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = NotificationOperations::new(&self.pool);
+        ops.delete_notifications_before(before)
+    }
+
+    // Client certificate operations
+    pub fn create_client_certificate(
+        &self,
+        certificate: &NewClientCertificate,
+    ) -> Result<ClientCertificate, diesel::result::Error> {
+        let ops = ClientCertificateOperations::new(&self.pool);
+        ops.create_client_certificate(certificate)
+    }
+
+    pub fn list_client_certificates(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<ClientCertificate>, diesel::result::Error> {
+        let ops = ClientCertificateOperations::new(&self.pool);
+        ops.list_client_certificates(user_id)
+    }
+
+    pub fn find_client_certificate(
+        &self,
+        fingerprint: Option<&str>,
+        subject: Option<&str>,
+    ) -> Result<Option<ClientCertificate>, diesel::result::Error> {
+        let ops = ClientCertificateOperations::new(&self.pool);
+        ops.find_client_certificate(fingerprint, subject)
+    }
+
+    pub fn touch_client_certificate(
+        &self,
+        id: i32,
+        now: NaiveDateTime,
+        stale_before: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = ClientCertificateOperations::new(&self.pool);
+        ops.touch_client_certificate(id, now, stale_before)
+    }
+
+    pub fn delete_client_certificate(
+        &self,
+        user_id: i32,
+        id: i32,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = ClientCertificateOperations::new(&self.pool);
+        ops.delete_client_certificate(user_id, id)
+    }
+
+    // Service account operations
+    pub fn create_service_account(
+        &self,
+        user: &NewUser,
+        organization_id: i32,
+        description: Option<String>,
+    ) -> Result<(User, ServiceAccount), diesel::result::Error> {
+        let ops = ServiceAccountOperations::new(&self.pool);
+        ops.create_service_account(user, organization_id, description)
+    }
+
+    pub fn get_service_account(
+        &self,
+        organization_id: i32,
+        username: &str,
+    ) -> Result<Option<(User, ServiceAccount)>, diesel::result::Error> {
+        let ops = ServiceAccountOperations::new(&self.pool);
+        ops.get_service_account(organization_id, username)
+    }
+
+    pub fn list_service_accounts(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<(User, ServiceAccount)>, diesel::result::Error> {
+        let ops = ServiceAccountOperations::new(&self.pool);
+        ops.list_service_accounts(organization_id)
+    }
+
+    // Staged publish operations
+    pub fn create_staged_publish(
+        &self,
+        staged: &NewStagedPublish,
+    ) -> Result<StagedPublish, diesel::result::Error> {
+        let ops = StagedPublishOperations::new(&self.pool);
+        ops.create_staged_publish(staged)
+    }
+
+    pub fn get_staged_publish(
+        &self,
+        id: i32,
+    ) -> Result<Option<(StagedPublish, String)>, diesel::result::Error> {
+        let ops = StagedPublishOperations::new(&self.pool);
+        ops.get_staged_publish(id)
+    }
+
+    pub fn find_staged_publish(
+        &self,
+        package_name: &str,
+        version: &str,
+    ) -> Result<Option<StagedPublish>, diesel::result::Error> {
+        let ops = StagedPublishOperations::new(&self.pool);
+        ops.find_staged_publish(package_name, version)
+    }
+
+    pub fn list_staged_publishes(
+        &self,
+        package_name: &str,
+    ) -> Result<Vec<(StagedPublish, String)>, diesel::result::Error> {
+        let ops = StagedPublishOperations::new(&self.pool);
+        ops.list_staged_publishes(package_name)
+    }
+
+    pub fn delete_staged_publish(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let ops = StagedPublishOperations::new(&self.pool);
+        ops.delete_staged_publish(id)
+    }
+
+    // Optimize operations
+    pub fn optimize(
+        &self,
+        vacuum: bool,
+        reindex: bool,
+    ) -> Result<DatabaseOptimizeReport, diesel::result::Error> {
+        let ops = OptimizeOperations::new(&self.pool);
+        ops.optimize(vacuum, reindex)
+    }
+
+    // Task queue operations
+    pub fn create_task(&self, task: &NewTask) -> Result<Task, diesel::result::Error> {
+        let ops = TaskOperations::new(&self.pool);
+        ops.create_task(task)
+    }
+
+    pub fn get_task(&self, id: i32) -> Result<Option<Task>, diesel::result::Error> {
+        let ops = TaskOperations::new(&self.pool);
+        ops.get_task(id)
+    }
+
+    pub fn claim_next_task(
+        &self,
+        now: NaiveDateTime,
+    ) -> Result<Option<Task>, diesel::result::Error> {
+        let ops = TaskOperations::new(&self.pool);
+        ops.claim_next_task(now)
+    }
+
+    pub fn finish_task(
+        &self,
+        id: i32,
+        status: TaskStatus,
+        result: Option<&str>,
+        error: Option<&str>,
+        now: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let ops = TaskOperations::new(&self.pool);
+        ops.finish_task(id, status, result, error, now)
+    }
+
+    pub fn requeue_running_tasks(&self) -> Result<usize, diesel::result::Error> {
+        let ops = TaskOperations::new(&self.pool);
+        ops.requeue_running_tasks()
+    }
+
+    pub fn delete_finished_tasks(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<Vec<Task>, diesel::result::Error> {
+        let ops = TaskOperations::new(&self.pool);
+        ops.delete_finished_tasks(before)
+    }
+
+    // User operations
+    pub fn get_user_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(&self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        users::table
+            .filter(users::username.eq(username))
+            .filter(users::is_active.eq(true))
+            .first::<User>(&mut conn)
+            .optional()
+    }
+
+    pub fn get_user_by_id(&self, user_id: i32) -> Result<Option<User>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(&self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        users::table
+            .filter(users::id.eq(user_id))
+            .filter(users::is_active.eq(true))
+            .first::<User>(&mut conn)
+            .optional()
+    }
+
+    pub fn update_user_profile(
+        &self,
+        user_id: i32,
+        profile: &UpdateUserProfile,
+    ) -> Result<User, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(&self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(users::table.find(user_id))
+            .set(profile)
+            .get_result::<User>(&mut conn)
     }
 }
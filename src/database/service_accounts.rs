@@ -0,0 +1,97 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::organization::{NewOrganizationMember, OrganizationRole};
+use crate::models::service_account::*;
+use crate::models::user::{NewUser, User};
+use crate::schema::{organization_members, service_accounts, users};
+use diesel::prelude::*;
+
+/// Service account database operations
+pub struct ServiceAccountOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> ServiceAccountOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates the user of a service account and makes it a member of its organization
+    pub fn create_service_account(
+        &self,
+        user: &NewUser,
+        organization_id: i32,
+        description: Option<String>,
+    ) -> Result<(User, ServiceAccount), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            let user = diesel::insert_into(users::table)
+                .values(user)
+                .get_result::<User>(conn)?;
+
+            let account = diesel::insert_into(service_accounts::table)
+                .values(&NewServiceAccount {
+                    user_id: user.id,
+                    organization_id,
+                    description,
+                })
+                .get_result::<ServiceAccount>(conn)?;
+
+            diesel::insert_into(organization_members::table)
+                .values(&NewOrganizationMember::new(
+                    user.id,
+                    organization_id,
+                    OrganizationRole::Member.to_string(),
+                ))
+                .execute(conn)?;
+
+            Ok((user, account))
+        })
+    }
+
+    pub fn get_service_account(
+        &self,
+        organization_id: i32,
+        username: &str,
+    ) -> Result<Option<(User, ServiceAccount)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        service_accounts::table
+            .inner_join(users::table)
+            .filter(service_accounts::organization_id.eq(organization_id))
+            .filter(users::username.eq(username))
+            .select((User::as_select(), ServiceAccount::as_select()))
+            .first::<(User, ServiceAccount)>(&mut conn)
+            .optional()
+    }
+
+    /// Service accounts of an organization by name
+    pub fn list_service_accounts(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<(User, ServiceAccount)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        service_accounts::table
+            .inner_join(users::table)
+            .filter(service_accounts::organization_id.eq(organization_id))
+            .order(users::username.asc())
+            .select((User::as_select(), ServiceAccount::as_select()))
+            .load::<(User, ServiceAccount)>(&mut conn)
+    }
+}
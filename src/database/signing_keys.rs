@@ -0,0 +1,139 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::signing::*;
+use crate::schema::{package_signatures, signing_keys};
+use diesel::prelude::*;
+
+/// Organization signing key and package signature database operations
+pub struct SigningKeyOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> SigningKeyOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Stores a new signing key
+    pub fn create_signing_key(
+        &self,
+        new_key: &NewSigningKey,
+    ) -> Result<SigningKey, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(signing_keys::table)
+            .values(new_key)
+            .get_result::<SigningKey>(&mut conn)
+    }
+
+    /// Lists all signing keys of an organization, including revoked ones
+    pub fn list_signing_keys(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<SigningKey>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        signing_keys::table
+            .filter(signing_keys::organization_id.eq(organization_id))
+            .order(signing_keys::created_at.desc())
+            .load::<SigningKey>(&mut conn)
+    }
+
+    /// Gets the keys an organization currently signs with
+    pub fn get_active_signing_keys(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<SigningKey>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        signing_keys::table
+            .filter(signing_keys::organization_id.eq(organization_id))
+            .filter(signing_keys::is_active.eq(true))
+            .load::<SigningKey>(&mut conn)
+    }
+
+    /// Revokes an organization's key. Returns false if no active key matched.
+    pub fn revoke_signing_key(
+        &self,
+        organization_id: i32,
+        key_id: &str,
+    ) -> Result<bool, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        let updated = diesel::update(
+            signing_keys::table
+                .filter(signing_keys::organization_id.eq(organization_id))
+                .filter(signing_keys::key_id.eq(key_id))
+                .filter(signing_keys::is_active.eq(true)),
+        )
+        .set((
+            signing_keys::is_active.eq(false),
+            signing_keys::revoked_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(&mut conn)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Stores a signature for a package version, replacing any previous one from the same key
+    pub fn create_package_signature(
+        &self,
+        new_signature: &NewPackageSignature,
+    ) -> Result<PackageSignature, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(
+            package_signatures::table
+                .filter(package_signatures::package_version_id.eq(new_signature.package_version_id))
+                .filter(package_signatures::signing_key_id.eq(new_signature.signing_key_id)),
+        )
+        .execute(&mut conn)?;
+
+        diesel::insert_into(package_signatures::table)
+            .values(new_signature)
+            .get_result::<PackageSignature>(&mut conn)
+    }
+
+    /// Gets all signatures of a package version together with the key that made them
+    pub fn get_package_signatures(
+        &self,
+        package_version_id: i32,
+    ) -> Result<Vec<(PackageSignature, SigningKey)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        package_signatures::table
+            .inner_join(signing_keys::table)
+            .filter(package_signatures::package_version_id.eq(package_version_id))
+            .select((PackageSignature::as_select(), SigningKey::as_select()))
+            .load::<(PackageSignature, SigningKey)>(&mut conn)
+    }
+}
@@ -0,0 +1,101 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::staged_publish::*;
+use crate::schema::{staged_publishes, users};
+use diesel::prelude::*;
+
+/// Staged publish database operations
+pub struct StagedPublishOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> StagedPublishOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn create_staged_publish(
+        &self,
+        staged: &NewStagedPublish,
+    ) -> Result<StagedPublish, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(staged_publishes::table)
+            .values(staged)
+            .get_result::<StagedPublish>(&mut conn)
+    }
+
+    /// A staged publish with the username of its publisher
+    pub fn get_staged_publish(
+        &self,
+        id: i32,
+    ) -> Result<Option<(StagedPublish, String)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        staged_publishes::table
+            .inner_join(users::table)
+            .filter(staged_publishes::id.eq(id))
+            .select((StagedPublish::as_select(), users::username))
+            .first::<(StagedPublish, String)>(&mut conn)
+            .optional()
+    }
+
+    pub fn find_staged_publish(
+        &self,
+        package_name: &str,
+        version: &str,
+    ) -> Result<Option<StagedPublish>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        staged_publishes::table
+            .filter(staged_publishes::package_name.eq(package_name))
+            .filter(staged_publishes::version.eq(version))
+            .first::<StagedPublish>(&mut conn)
+            .optional()
+    }
+
+    /// Staged publishes of a package with the usernames of their publishers, oldest first
+    pub fn list_staged_publishes(
+        &self,
+        package_name: &str,
+    ) -> Result<Vec<(StagedPublish, String)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        staged_publishes::table
+            .inner_join(users::table)
+            .filter(staged_publishes::package_name.eq(package_name))
+            .order(staged_publishes::id.asc())
+            .select((StagedPublish::as_select(), users::username))
+            .load::<(StagedPublish, String)>(&mut conn)
+    }
+
+    pub fn delete_staged_publish(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(staged_publishes::table.find(id)).execute(&mut conn)
+    }
+}
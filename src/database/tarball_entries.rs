@@ -0,0 +1,57 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::package::*;
+use crate::schema::tarball_entries;
+use diesel::prelude::*;
+
+/// Tarball file listing database operations
+pub struct TarballEntryOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> TarballEntryOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Indexed files of a version sorted by path, empty if it wasn't indexed yet
+    pub fn get_tarball_entries(
+        &self,
+        package_version_id: i32,
+    ) -> Result<Vec<TarballEntry>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        tarball_entries::table
+            .filter(tarball_entries::package_version_id.eq(package_version_id))
+            .order(tarball_entries::path.asc())
+            .load::<TarballEntry>(&mut conn)
+    }
+
+    /// Stores the files of a version in one transaction. Entries that already exist are kept,
+    /// so concurrent first listings of the same version don't conflict.
+    pub fn create_tarball_entries(
+        &self,
+        entries: &[NewTarballEntry],
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            for entry in entries {
+                diesel::insert_into(tarball_entries::table)
+                    .values(entry)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,131 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::task::*;
+use crate::schema::tasks;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+/// Task queue database operations
+pub struct TaskOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> TaskOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn create_task(&self, task: &NewTask) -> Result<Task, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(tasks::table)
+            .values(task)
+            .get_result::<Task>(&mut conn)
+    }
+
+    pub fn get_task(&self, id: i32) -> Result<Option<Task>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        tasks::table.find(id).first::<Task>(&mut conn).optional()
+    }
+
+    /// Marks the oldest queued task as running and returns it
+    pub fn claim_next_task(
+        &self,
+        now: NaiveDateTime,
+    ) -> Result<Option<Task>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        conn.transaction(|conn| {
+            let Some(id) = tasks::table
+                .filter(tasks::status.eq(TaskStatus::Queued.as_str()))
+                .order(tasks::id.asc())
+                .select(tasks::id)
+                .first::<i32>(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+
+            diesel::update(tasks::table.find(id))
+                .set((
+                    tasks::status.eq(TaskStatus::Running.as_str()),
+                    tasks::started_at.eq(now),
+                ))
+                .get_result::<Task>(conn)
+                .map(Some)
+        })
+    }
+
+    pub fn finish_task(
+        &self,
+        id: i32,
+        status: TaskStatus,
+        result: Option<&str>,
+        error: Option<&str>,
+        now: NaiveDateTime,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(tasks::table.find(id))
+            .set((
+                tasks::status.eq(status.as_str()),
+                tasks::result.eq(result),
+                tasks::error.eq(error),
+                tasks::finished_at.eq(now),
+            ))
+            .execute(&mut conn)
+    }
+
+    /// Queues tasks that were running when the server stopped again
+    pub fn requeue_running_tasks(&self) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(tasks::table.filter(tasks::status.eq(TaskStatus::Running.as_str())))
+            .set((
+                tasks::status.eq(TaskStatus::Queued.as_str()),
+                tasks::started_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(&mut conn)
+    }
+
+    /// Deletes tasks that finished before `before`, returning them
+    pub fn delete_finished_tasks(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<Vec<Task>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(tasks::table.filter(tasks::finished_at.lt(before)))
+            .get_results::<Task>(&mut conn)
+    }
+}
@@ -0,0 +1,55 @@
+use super::connection::{DbPool, get_connection_with_retry};
+use crate::models::tombstone::*;
+use crate::schema::version_tombstones;
+use diesel::prelude::*;
+
+/// Version tombstone database operations
+pub struct TombstoneOperations<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> TombstoneOperations<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Gets the tombstone of a deleted version
+    pub fn get_version_tombstone(
+        &self,
+        package_name: &str,
+        version: &str,
+    ) -> Result<Option<VersionTombstone>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        version_tombstones::table
+            .filter(version_tombstones::package_name.eq(package_name))
+            .filter(version_tombstones::version.eq(version))
+            .first::<VersionTombstone>(&mut conn)
+            .optional()
+    }
+
+    /// Records a deleted version, keeping the first tombstone if there already is one
+    pub fn create_version_tombstone(
+        &self,
+        tombstone: &NewVersionTombstone,
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::insert_into(version_tombstones::table)
+            .values(tombstone)
+            .on_conflict_do_nothing()
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+}
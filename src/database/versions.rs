@@ -1,6 +1,6 @@
 use super::connection::{DbPool, get_connection_with_retry};
 use crate::models::package::*;
-use crate::schema::package_versions;
+use crate::schema::{package_files, package_versions, packages};
 use diesel::prelude::*;
 
 /// Package version-related database operations
@@ -145,6 +145,12 @@ impl<'a> VersionOperations<'a> {
             .and_then(|shasum| shasum.as_str())
             .map(|s| s.to_string());
 
+        let integrity = package_json
+            .get("dist")
+            .and_then(|dist| dist.get("integrity"))
+            .and_then(|integrity| integrity.as_str())
+            .map(|s| s.to_string());
+
         // Extract README content if available
         let readme = package_json
             .get("readme")
@@ -171,6 +177,7 @@ impl<'a> VersionOperations<'a> {
             peer_dependencies,
             engines,
             shasum,
+            integrity,
             readme,
             created_at,
         };
@@ -194,6 +201,7 @@ impl<'a> VersionOperations<'a> {
                 package_versions::peer_dependencies.eq(&new_version.peer_dependencies),
                 package_versions::engines.eq(&new_version.engines),
                 package_versions::shasum.eq(&new_version.shasum),
+                package_versions::integrity.eq(&new_version.integrity),
                 package_versions::readme.eq(&new_version.readme),
                 package_versions::updated_at.eq(chrono::Utc::now().naive_utc()),
             ))
@@ -211,6 +219,79 @@ impl<'a> VersionOperations<'a> {
             .first::<PackageVersion>(&mut conn)
     }
 
+    /// Stores the checksums of a version's tarball as computed by the registry
+    pub fn set_version_checksums(
+        &self,
+        version_id: i32,
+        shasum: &str,
+        integrity: &str,
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(package_versions::table.filter(package_versions::id.eq(version_id)))
+            .set((
+                package_versions::shasum.eq(shasum),
+                package_versions::integrity.eq(integrity),
+                // The registry signature covers the old integrity
+                package_versions::registry_key_id.eq(None::<String>),
+                package_versions::registry_signature.eq(None::<String>),
+            ))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Stores the registry signature of a version's integrity
+    pub fn set_version_registry_signature(
+        &self,
+        version_id: i32,
+        key_id: &str,
+        signature: &str,
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(package_versions::table.filter(package_versions::id.eq(version_id)))
+            .set((
+                package_versions::registry_key_id.eq(key_id),
+                package_versions::registry_signature.eq(signature),
+            ))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Yanks a version, or restores it when `yanked_at` is `None`
+    pub fn set_version_yanked(
+        &self,
+        version_id: i32,
+        yanked_at: Option<chrono::NaiveDateTime>,
+        reason: Option<String>,
+    ) -> Result<PackageVersion, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::update(package_versions::table.filter(package_versions::id.eq(version_id)))
+            .set((
+                package_versions::yanked_at.eq(yanked_at),
+                package_versions::yank_reason.eq(reason),
+            ))
+            .get_result::<PackageVersion>(&mut conn)
+    }
+
     /// Gets all versions for a package
     pub fn get_package_versions(
         &self,
@@ -228,4 +309,53 @@ impl<'a> VersionOperations<'a> {
             .order(package_versions::created_at.desc())
             .load::<PackageVersion>(&mut conn)
     }
+
+    /// Versions of published packages that have no stored file
+    pub fn list_published_versions_without_files(
+        &self,
+    ) -> Result<Vec<(Package, PackageVersion)>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        packages::table
+            .inner_join(package_versions::table.left_join(package_files::table))
+            .filter(packages::author_id.is_not_null())
+            .filter(package_files::id.is_null())
+            .select((Package::as_select(), PackageVersion::as_select()))
+            .load::<(Package, PackageVersion)>(&mut conn)
+    }
+
+    /// Versions whose package row no longer exists
+    pub fn list_versions_without_package(
+        &self,
+    ) -> Result<Vec<PackageVersion>, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        package_versions::table
+            .filter(package_versions::package_id.ne_all(packages::table.select(packages::id)))
+            .load::<PackageVersion>(&mut conn)
+    }
+
+    /// Deletes a version, its files, signatures and attestations. Returns the number of
+    /// deleted versions.
+    pub fn delete_package_version(&self, id: i32) -> Result<usize, diesel::result::Error> {
+        let mut conn = get_connection_with_retry(self.pool).map_err(|e| {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UnableToSendCommand,
+                Box::new(e.to_string()),
+            )
+        })?;
+
+        diesel::delete(package_versions::table.filter(package_versions::id.eq(id)))
+            .execute(&mut conn)
+    }
 }
@@ -1,10 +1,14 @@
-use rocket::response::{Responder, Response};
+use crate::fairings::RequestId;
+use rocket::response::Responder;
+use rocket::serde::json::Json;
 use rocket::{Request, http::Status};
-use std::io::Cursor;
+use serde::Serialize;
+use utoipa::ToSchema;
 
 #[derive(Debug)]
 pub enum ApiError {
     UpstreamError(String),
+    GatewayTimeout(String),
     ParseError(String),
     NetworkError(String),
     CacheError(String),
@@ -14,30 +18,122 @@ pub enum ApiError {
     Forbidden(String),
     NotFound(String),
     Conflict(String),
+    PayloadTooLarge(String),
+    TooManyRequests(String),
+    ServiceUnavailable(String),
     InternalServerError(String),
 }
 
+/// npm style error code, `E` followed by the HTTP status. npm reports it as the code of the
+/// failed request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub enum ErrorCode {
+    E400,
+    E401,
+    E403,
+    E404,
+    E409,
+    E413,
+    E429,
+    E500,
+    E502,
+    E503,
+    E504,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> Status {
+        match self {
+            ErrorCode::E400 => Status::BadRequest,
+            ErrorCode::E401 => Status::Unauthorized,
+            ErrorCode::E403 => Status::Forbidden,
+            ErrorCode::E404 => Status::NotFound,
+            ErrorCode::E409 => Status::Conflict,
+            ErrorCode::E413 => Status::PayloadTooLarge,
+            ErrorCode::E429 => Status::TooManyRequests,
+            ErrorCode::E500 => Status::InternalServerError,
+            ErrorCode::E502 => Status::BadGateway,
+            ErrorCode::E503 => Status::ServiceUnavailable,
+            ErrorCode::E504 => Status::GatewayTimeout,
+        }
+    }
+}
+
+/// JSON body of error responses. npm, yarn and pnpm show `error` to the user, `reason` is
+/// the reason phrase of the status.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+    pub reason: String,
+    pub code: ErrorCode,
+    /// Id of the failed request, also sent in the `X-Request-Id` header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::UpstreamError(_) => ErrorCode::E502,
+            ApiError::GatewayTimeout(_) => ErrorCode::E504,
+            ApiError::ParseError(_) => ErrorCode::E400,
+            ApiError::NetworkError(_) => ErrorCode::E502,
+            ApiError::CacheError(_) => ErrorCode::E500,
+            ApiError::DatabaseError(_) => ErrorCode::E500,
+            ApiError::BadRequest(_) => ErrorCode::E400,
+            ApiError::Unauthorized(_) => ErrorCode::E401,
+            ApiError::Forbidden(_) => ErrorCode::E403,
+            ApiError::NotFound(_) => ErrorCode::E404,
+            ApiError::Conflict(_) => ErrorCode::E409,
+            ApiError::PayloadTooLarge(_) => ErrorCode::E413,
+            ApiError::TooManyRequests(_) => ErrorCode::E429,
+            ApiError::ServiceUnavailable(_) => ErrorCode::E503,
+            ApiError::InternalServerError(_) => ErrorCode::E500,
+        }
+    }
+
+    pub fn to_body(&self) -> ErrorBody {
+        let code = self.code();
+        ErrorBody {
+            error: self.to_string(),
+            reason: code.status().reason_lossy().to_string(),
+            code,
+            request_id: None,
+        }
+    }
+}
+
 impl<'r> Responder<'r, 'static> for ApiError {
-    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
-        let (status, message) = match self {
-            ApiError::UpstreamError(msg) => (Status::BadGateway, msg),
-            ApiError::ParseError(msg) => (Status::BadRequest, msg),
-            ApiError::NetworkError(msg) => (Status::BadGateway, msg),
-            ApiError::CacheError(msg) => (Status::InternalServerError, msg),
-            ApiError::DatabaseError(msg) => (Status::InternalServerError, msg),
-            ApiError::BadRequest(msg) => (Status::BadRequest, msg),
-            ApiError::Unauthorized(msg) => (Status::Unauthorized, msg),
-            ApiError::Forbidden(msg) => (Status::Forbidden, msg),
-            ApiError::NotFound(msg) => (Status::NotFound, msg),
-            ApiError::Conflict(msg) => (Status::Conflict, msg),
-            ApiError::InternalServerError(msg) => (Status::InternalServerError, msg),
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let status = self.code().status();
+        let body = ErrorBody {
+            request_id: Some(RequestId::of(request).to_string()),
+            ..self.to_body()
         };
+        (status, Json(body)).respond_to(request)
+    }
+}
 
-        Response::build()
-            .status(status)
-            .header(rocket::http::ContentType::Plain)
-            .sized_body(message.len(), Cursor::new(message))
-            .ok()
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            ApiError::UpstreamError(msg)
+            | ApiError::GatewayTimeout(msg)
+            | ApiError::ParseError(msg)
+            | ApiError::NetworkError(msg)
+            | ApiError::CacheError(msg)
+            | ApiError::DatabaseError(msg)
+            | ApiError::BadRequest(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::Forbidden(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::Conflict(msg)
+            | ApiError::PayloadTooLarge(msg)
+            | ApiError::TooManyRequests(msg)
+            | ApiError::ServiceUnavailable(msg)
+            | ApiError::InternalServerError(msg) => msg,
+        };
+        f.write_str(message)
     }
 }
 
@@ -46,3 +142,27 @@ impl From<reqwest::Error> for ApiError {
         ApiError::NetworkError(format!("Network error: {err}"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_body() {
+        let body = ApiError::Forbidden("You can't publish 'lodash'".to_string()).to_body();
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            serde_json::json!({
+                "error": "You can't publish 'lodash'",
+                "reason": "Forbidden",
+                "code": "E403",
+            })
+        );
+
+        assert_eq!(
+            ApiError::GatewayTimeout(String::new()).code().status(),
+            Status::GatewayTimeout
+        );
+        assert_eq!(ApiError::ParseError(String::new()).code(), ErrorCode::E400);
+    }
+}
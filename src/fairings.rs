@@ -1,6 +1,182 @@
-use log::info;
+use crate::config::ListenAddress;
+use crate::models::RegistryReader;
+use crate::redact;
+use crate::routes::packages::package_of_path;
+use crate::services::access_log::{AccessLog, AccessLogEntry};
+use crate::services::ip_filter::RouteGroup;
+use crate::services::{IpFilter, MaintenanceMode, RateLimiter};
+use crate::state::AppState;
+use log::{error, info, warn};
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::{Data, Request};
+use rocket::http::uri::Origin;
+use rocket::http::{Header, HeaderMap, Method, Status};
+use rocket::request::{self, FromRequest};
+use rocket::route::{Handler, Outcome};
+use rocket::tokio::io::{AsyncRead, AsyncWrite, copy_bidirectional};
+use rocket::tokio::net::{TcpListener, TcpStream, UnixListener};
+use rocket::{Build, Data, Orbit, Request, Response, Rocket};
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Header carrying the request id, both on responses and on requests to the upstream registry
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Headers of npm clients passed on to the upstream registry
+const FORWARDED_CLIENT_HEADERS: [&str; 4] = ["npm-command", "npm-session", "User-Agent", "Accept"];
+
+/// Headers of upstream responses relayed back to the client, so users still see notices and
+/// deprecation warnings of the upstream registry
+const RELAYED_UPSTREAM_HEADERS: [&str; 3] = ["npm-notice", "Warning", "Deprecation"];
+
+/// Media type of abbreviated package documents, the cache only keeps full documents
+const ABBREVIATED_METADATA: &str = "application/vnd.npm.install-v1+json";
+
+rocket::tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+    static CURRENT_CLIENT_HEADERS: ClientHeaders;
+}
+
+/// Id of a request, taken from an incoming `X-Request-Id` header when it looks sane and
+/// generated otherwise
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    fn from_header(value: Option<&str>) -> Self {
+        match value {
+            Some(id) if Self::is_valid(id) => Self(id.to_string()),
+            _ => Self(uuid::Uuid::new_v4().simple().to_string()),
+        }
+    }
+
+    /// Incoming ids end up in log lines and upstream headers, so only short ids made of
+    /// characters that can't break either are honored
+    fn is_valid(id: &str) -> bool {
+        (1..=128).contains(&id.len())
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+    }
+
+    /// The id of a request, assigned the first time it is asked for
+    pub fn of<'r>(req: &'r Request<'_>) -> &'r RequestId {
+        req.local_cache(|| Self::from_header(req.headers().get_one(REQUEST_ID_HEADER)))
+    }
+
+    /// The id of the request the current task is handling
+    pub fn current() -> Option<RequestId> {
+        CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+    }
+
+    /// Runs a future with this id as the current request id
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT_REQUEST_ID.scope(self, future).await
+    }
+
+    /// Adds the current request id to a request to the upstream registry so a failed
+    /// install can be followed across both registries, along with the npm client headers
+    /// of the request
+    pub fn forward(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let forwarded = CURRENT_CLIENT_HEADERS
+            .try_with(|client| client.forwarded.clone())
+            .unwrap_or_default();
+        let request = forwarded
+            .into_iter()
+            .fold(request, |request, (name, value)| {
+                request.header(name, value)
+            });
+        match Self::current() {
+            Some(id) => request.header(REQUEST_ID_HEADER, id.0),
+            None => request,
+        }
+    }
+}
+
+/// Headers of the npm client making the current request that are forwarded upstream, and
+/// the notices of upstream responses to relay back to it
+#[derive(Debug, Clone, Default)]
+pub struct ClientHeaders {
+    forwarded: Vec<(&'static str, String)>,
+    relayed: Arc<Mutex<Vec<(&'static str, String)>>>,
+}
+
+impl ClientHeaders {
+    fn of_headers(headers: &HeaderMap<'_>) -> Self {
+        let forwarded = FORWARDED_CLIENT_HEADERS
+            .into_iter()
+            .filter_map(|name| Some((name, headers.get_one(name)?)))
+            // Upstream requests with a header value reqwest rejects would fail to send
+            .filter(|(_, value)| reqwest::header::HeaderValue::from_str(value).is_ok())
+            .filter(|(name, value)| {
+                !name.eq_ignore_ascii_case("Accept") || !value.contains(ABBREVIATED_METADATA)
+            })
+            .map(|(name, value)| (name, value.to_string()))
+            .collect();
+        Self {
+            forwarded,
+            relayed: Arc::default(),
+        }
+    }
+
+    /// Keeps the notices of an upstream response for the response of the current request
+    pub fn relay(headers: &reqwest::header::HeaderMap) {
+        let _ = CURRENT_CLIENT_HEADERS.try_with(|client| {
+            let mut relayed = client.relayed.lock().unwrap();
+            for name in RELAYED_UPSTREAM_HEADERS {
+                for value in headers.get_all(name) {
+                    let Ok(value) = value.to_str() else {
+                        continue;
+                    };
+                    if !relayed.iter().any(|(n, v)| *n == name && v == value) {
+                        relayed.push((name, value.to_string()));
+                    }
+                }
+            }
+        });
+    }
+
+    fn apply_to(&self, res: &mut Response<'_>) {
+        for (name, value) in self.relayed.lock().unwrap().drain(..) {
+            res.adjoin_header(Header::new(name, value));
+        }
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Route handler that runs the wrapped handler with the request id as the current one, so
+/// log lines and upstream requests made while handling the request carry it. Notices of
+/// upstream responses are added to the response.
+#[derive(Clone)]
+pub struct RequestIdScope(Box<dyn Handler>);
+
+impl RequestIdScope {
+    pub fn wrap(mut route: rocket::Route) -> rocket::Route {
+        route.handler = Box::new(Self(route.handler.clone()));
+        route
+    }
+}
+
+#[rocket::async_trait]
+impl Handler for RequestIdScope {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let client = ClientHeaders::of_headers(req.headers());
+        let handle = CURRENT_CLIENT_HEADERS.scope(client.clone(), self.0.handle(req, data));
+        let mut outcome = RequestId::of(req).clone().scope(handle).await;
+        if let Outcome::Success(res) = &mut outcome {
+            client.apply_to(res);
+        }
+        outcome
+    }
+}
 
 pub struct RequestLogger;
 
@@ -9,16 +185,552 @@ impl Fairing for RequestLogger {
     fn info(&self) -> Info {
         Info {
             name: "Request Logger",
-            kind: Kind::Request,
+            kind: Kind::Request | Kind::Response,
         }
     }
 
     async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
         info!(
-            "{} {} {}",
+            "[{}] {} {} {}",
+            RequestId::of(req),
             req.method(),
-            req.uri(),
+            redact::scrub(&req.uri().to_string()),
             req.headers().get_one("User-Agent").unwrap_or("Unknown")
         );
     }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        res.set_header(Header::new(REQUEST_ID_HEADER, RequestId::of(req).0.clone()));
+    }
+}
+
+/// The request line and start of a request, recorded before maintenance mode may rewrite it
+struct RequestStart {
+    at: Instant,
+    method: Method,
+    uri: String,
+}
+
+impl RequestStart {
+    fn of(req: &Request<'_>) -> Self {
+        Self {
+            at: Instant::now(),
+            method: req.method(),
+            uri: req.uri().to_string(),
+        }
+    }
+}
+
+/// Writes every served request to the access log, when one is configured
+pub struct AccessLogger {
+    log: Option<AccessLog>,
+}
+
+impl AccessLogger {
+    pub fn new(log: Option<AccessLog>) -> Self {
+        Self { log }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for AccessLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Access Logger",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        if self.log.is_some() {
+            req.local_cache(|| RequestStart::of(req));
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(log) = &self.log else {
+            return;
+        };
+
+        let header = |name| req.headers().get_one(name).map(str::to_string);
+        let start = req.local_cache(|| RequestStart::of(req));
+        log.write(&AccessLogEntry {
+            time: chrono::Utc::now(),
+            remote_addr: req
+                .rocket()
+                .state::<AppState>()
+                .and_then(|state| IpFilter::client_ip(req, &state.config))
+                .map(|ip| ip.to_string()),
+            method: start.method.to_string(),
+            uri: start.uri.clone(),
+            status: res.status().code,
+            bytes: res.body().preset_size(),
+            referer: header("Referer"),
+            user_agent: header("User-Agent"),
+            request_id: RequestId::of(req).to_string(),
+            duration_ms: start.at.elapsed().as_millis() as u64,
+        });
+    }
+}
+
+/// Counts requests and tarball downloads in flight for the live dashboard. A download stays
+/// active until its request is dropped, after the response body was sent.
+pub struct LiveMetricsRecorder;
+
+impl LiveMetricsRecorder {
+    fn is_tarball_download(req: &Request<'_>) -> bool {
+        let path = req.uri().path();
+        req.method() == Method::Get
+            && path.starts_with("/registry/")
+            && path.contains("/-/")
+            && path.ends_with(".tgz")
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for LiveMetricsRecorder {
+    fn info(&self) -> Info {
+        Info {
+            name: "Live Metrics Recorder",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(state) = req.rocket().state::<AppState>() else {
+            return;
+        };
+        state.live_metrics.record_request();
+        if Self::is_tarball_download(req) {
+            let download = state.live_metrics.start_download();
+            req.local_cache(|| Some(download));
+        }
+    }
+}
+
+/// Attributes the bytes of package metadata and tarballs served to the package and to the
+/// user and token that read them
+pub struct BandwidthRecorder;
+
+#[rocket::async_trait]
+impl Fairing for BandwidthRecorder {
+    fn info(&self) -> Info {
+        Info {
+            name: "Bandwidth Recorder",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if req.method() != Method::Get || res.status() != Status::Ok {
+            return;
+        }
+        let Some(state) = req.rocket().state::<AppState>() else {
+            return;
+        };
+        let Some(package) = package_of_path(req.uri().path().as_str()) else {
+            return;
+        };
+        let Some(bytes) = res.body().preset_size() else {
+            return;
+        };
+
+        let RegistryReader(user) = req.local_cache(|| RegistryReader(None));
+        state.database.queue_bandwidth(
+            &package,
+            user.as_ref().map(|user| user.user_id),
+            user.as_ref().and_then(|user| user.token_id),
+            bytes as u64,
+        );
+    }
+}
+
+/// Route that answers write requests turned away during maintenance
+const MAINTENANCE_ROUTE: &str = "/api/v1/maintenance/unavailable";
+
+/// Routes write requests to the maintenance response while maintenance mode is enabled.
+/// Fairings can't answer a request themselves, so the request is rewritten instead.
+pub struct MaintenanceGuard;
+
+#[rocket::async_trait]
+impl Fairing for MaintenanceGuard {
+    fn info(&self) -> Info {
+        Info {
+            name: "Maintenance Guard",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(state) = req.rocket().state::<AppState>() else {
+            return;
+        };
+        if !state.maintenance.is_enabled()
+            || !MaintenanceMode::blocks(req.method(), req.uri().path().as_str())
+        {
+            return;
+        }
+
+        info!(
+            "Rejecting {} {} during maintenance",
+            req.method(),
+            req.uri()
+        );
+        req.set_method(Method::Get);
+        req.set_uri(Origin::parse(MAINTENANCE_ROUTE).expect("valid maintenance route"));
+    }
+}
+
+/// Route that answers requests from addresses the allow and deny rules turn away
+const IP_DENIED_ROUTE: &str = "/api/v1/access/denied";
+
+/// Address of a request turned away by the IP guard
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeniedClient(pub Option<IpAddr>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DeniedClient {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(*req.local_cache(DeniedClient::default))
+    }
+}
+
+/// Applies the CIDR allow and deny rules, globally and per route group. Like the maintenance
+/// guard it rewrites rejected requests, to a route answering 403.
+pub struct IpGuard;
+
+#[rocket::async_trait]
+impl Fairing for IpGuard {
+    fn info(&self) -> Info {
+        Info {
+            name: "IP Guard",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(state) = req.rocket().state::<AppState>() else {
+            return;
+        };
+        if !IpFilter::is_enabled(&state.config) {
+            return;
+        }
+
+        let group = RouteGroup::of(req.method(), req.uri().path().segments());
+        let client = IpFilter::client_ip(req, &state.config);
+        // Rules can't be checked without the address, so the request is turned away
+        let allowed = match client {
+            Some(ip) => IpFilter::allows(ip, group, &state.config),
+            None => false,
+        };
+        if allowed {
+            return;
+        }
+
+        warn!(
+            "Rejecting {} {} from {}",
+            req.method(),
+            req.uri().path(),
+            client.map_or_else(|| "an unknown address".to_string(), |ip| ip.to_string())
+        );
+        req.local_cache(|| DeniedClient(client));
+        req.set_method(Method::Get);
+        req.set_uri(Origin::parse(IP_DENIED_ROUTE).expect("valid access denied route"));
+    }
+}
+
+/// Route that answers clients over the request rate limit
+const RATE_LIMITED_ROUTE: &str = "/api/v1/access/rate-limited";
+
+/// Requests are counted in windows of a minute
+const RATE_LIMIT_WINDOW_SECS: u64 = 60;
+
+/// Seconds until a client over the rate limit may make requests again
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimitedClient(pub u64);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimitedClient {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(*req.local_cache(RateLimitedClient::default))
+    }
+}
+
+/// Limits the requests a client address makes per minute, across every instance when Redis
+/// is configured. Rejected requests are rewritten to a route answering 429. Health checks
+/// aren't counted.
+pub struct RateLimitGuard;
+
+#[rocket::async_trait]
+impl Fairing for RateLimitGuard {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate Limit Guard",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(state) = req.rocket().state::<AppState>() else {
+            return;
+        };
+        if state.config.rate_limit_per_minute == 0 || req.uri().path() == "/api/v1/health" {
+            return;
+        }
+        let Some(client) = IpFilter::client_ip(req, &state.config) else {
+            return;
+        };
+
+        let hits = state
+            .rate_limiter
+            .hit(&format!("requests:{client}"), RATE_LIMIT_WINDOW_SECS)
+            .await;
+        if hits <= state.config.rate_limit_per_minute {
+            return;
+        }
+
+        // Only the first rejection of a window is logged, clients tend to keep retrying
+        if hits == state.config.rate_limit_per_minute + 1 {
+            warn!(
+                "Rate limiting {client}, over {} requests per minute",
+                state.config.rate_limit_per_minute
+            );
+        }
+        let retry_after = RateLimiter::retry_after(RATE_LIMIT_WINDOW_SECS);
+        req.local_cache(|| RateLimitedClient(retry_after));
+        req.set_method(Method::Get);
+        req.set_uri(Origin::parse(RATE_LIMITED_ROUTE).expect("valid rate limited route"));
+    }
+}
+
+enum BoundListener {
+    Tcp(std::net::TcpListener),
+    Unix(std::os::unix::net::UnixListener),
+}
+
+/// Accepts connections on additional TCP addresses and Unix sockets and forwards them to
+/// the main listener. Rocket only binds a single address. Clients of these listeners appear
+/// to connect from the loopback address.
+pub struct ExtraListeners {
+    addresses: Vec<ListenAddress>,
+    bound: Mutex<Vec<BoundListener>>,
+}
+
+impl ExtraListeners {
+    pub fn new(addresses: Vec<ListenAddress>) -> Self {
+        Self {
+            addresses,
+            bound: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn bind(address: &ListenAddress) -> std::io::Result<BoundListener> {
+        let listener = match address {
+            ListenAddress::Tcp(addr) => BoundListener::Tcp(std::net::TcpListener::bind(addr)?),
+            ListenAddress::Unix(path) => {
+                Self::remove_stale_socket(path)?;
+                BoundListener::Unix(std::os::unix::net::UnixListener::bind(path)?)
+            }
+        };
+
+        match &listener {
+            BoundListener::Tcp(listener) => listener.set_nonblocking(true)?,
+            BoundListener::Unix(listener) => listener.set_nonblocking(true)?,
+        }
+        Ok(listener)
+    }
+
+    /// A socket left behind by a previous run would make binding fail
+    fn remove_stale_socket(path: &Path) -> std::io::Result<()> {
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+            _ => Ok(()),
+        }
+    }
+
+    async fn forward<S>(mut inbound: S, target: SocketAddr)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        match TcpStream::connect(target).await {
+            Ok(mut outbound) => {
+                let _ = outbound.set_nodelay(true);
+                let _ = copy_bidirectional(&mut inbound, &mut outbound).await;
+            }
+            Err(e) => warn!("Failed to forward connection to {target}: {e}"),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for ExtraListeners {
+    fn info(&self) -> Info {
+        Info {
+            name: "Extra Listeners",
+            kind: Kind::Ignite | Kind::Liftoff | Kind::Shutdown,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        let mut bound = Vec::new();
+        for address in &self.addresses {
+            match Self::bind(address) {
+                Ok(listener) => bound.push(listener),
+                Err(e) => {
+                    error!("Failed to listen on {address}: {e}");
+                    return Err(rocket);
+                }
+            }
+        }
+
+        *self.bound.lock().unwrap() = bound;
+        Ok(rocket)
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        // Connect to the main listener over loopback when it's bound to all interfaces
+        let target = match rocket.config().address {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ip => ip,
+        };
+        let target = SocketAddr::new(target, rocket.config().port);
+
+        let bound = std::mem::take(&mut *self.bound.lock().unwrap());
+        for (address, listener) in self.addresses.iter().zip(bound) {
+            match listener {
+                BoundListener::Tcp(listener) => match TcpListener::from_std(listener) {
+                    Ok(listener) => {
+                        info!("Also listening on {address}");
+                        rocket::tokio::spawn(async move {
+                            while let Ok((stream, _)) = listener.accept().await {
+                                rocket::tokio::spawn(Self::forward(stream, target));
+                            }
+                        });
+                    }
+                    Err(e) => error!("Failed to listen on {address}: {e}"),
+                },
+                BoundListener::Unix(listener) => match UnixListener::from_std(listener) {
+                    Ok(listener) => {
+                        info!("Also listening on {address}");
+                        rocket::tokio::spawn(async move {
+                            while let Ok((stream, _)) = listener.accept().await {
+                                rocket::tokio::spawn(Self::forward(stream, target));
+                            }
+                        });
+                    }
+                    Err(e) => error!("Failed to listen on {address}: {e}"),
+                },
+            }
+        }
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        for address in &self.addresses {
+            if let ListenAddress::Unix(path) = address {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id_from_header() {
+        let id = RequestId::from_header(Some("abc-123_x.y:z"));
+        assert_eq!(id.0, "abc-123_x.y:z");
+
+        for invalid in [None, Some(""), Some("a b"), Some("id\r\nX-Evil: 1")] {
+            let id = RequestId::from_header(invalid);
+            assert_eq!(id.0.len(), 32);
+            assert!(id.0.chars().all(|c| c.is_ascii_hexdigit()));
+        }
+        assert_ne!(
+            RequestId::from_header(Some(&"a".repeat(129))).0,
+            "a".repeat(129)
+        );
+    }
+
+    #[test]
+    fn test_client_headers_of_headers() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            ("npm-command", "install"),
+            ("npm-session", "5a2c7e1f0b9d4e3a"),
+            ("User-Agent", "npm/10.8.2 node/v22.6.0 linux x64"),
+            ("Accept", "application/vnd.npm.install-v1+json; q=1.0, */*"),
+            ("Authorization", "Bearer npm_secret"),
+        ] {
+            headers.add(Header::new(name, value));
+        }
+        let client = ClientHeaders::of_headers(&headers);
+        assert_eq!(
+            client.forwarded,
+            [
+                ("npm-command", "install".to_string()),
+                ("npm-session", "5a2c7e1f0b9d4e3a".to_string()),
+                (
+                    "User-Agent",
+                    "npm/10.8.2 node/v22.6.0 linux x64".to_string()
+                ),
+            ]
+        );
+
+        headers.replace(Header::new("Accept", "application/json"));
+        let client = ClientHeaders::of_headers(&headers);
+        assert!(
+            client
+                .forwarded
+                .contains(&("Accept", "application/json".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_headers_forwarded_and_relayed() {
+        let client = ClientHeaders {
+            forwarded: vec![("npm-command", "ci".to_string())],
+            relayed: Arc::default(),
+        };
+        let mut upstream = reqwest::header::HeaderMap::new();
+        upstream.insert(
+            "npm-notice",
+            "New major version of npm available".parse().unwrap(),
+        );
+        upstream.insert("Warning", "299 - \"deprecated\"".parse().unwrap());
+        upstream.insert("Server", "cloudflare".parse().unwrap());
+
+        let request = CURRENT_CLIENT_HEADERS
+            .scope(client.clone(), async {
+                ClientHeaders::relay(&upstream);
+                ClientHeaders::relay(&upstream);
+                RequestId::forward(reqwest::Client::new().get("http://localhost/lodash"))
+            })
+            .await
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["npm-command"], "ci");
+
+        let mut response = Response::new();
+        client.apply_to(&mut response);
+        assert_eq!(
+            response.headers().get_one("npm-notice"),
+            Some("New major version of npm available")
+        );
+        assert_eq!(response.headers().get("Warning").count(), 1);
+        assert!(response.headers().get_one("Server").is_none());
+
+        // Outside of a request nothing is forwarded or relayed
+        ClientHeaders::relay(&upstream);
+        let request = RequestId::forward(reqwest::Client::new().get("http://localhost/lodash"))
+            .build()
+            .unwrap();
+        assert!(!request.headers().contains_key("npm-command"));
+    }
 }
@@ -1,48 +1,196 @@
+pub mod cli;
 pub mod config;
 pub mod database;
 pub mod error;
 pub mod fairings;
 pub mod models;
+pub mod redact;
 pub mod routes;
 pub mod schema;
+pub mod secrets;
 pub mod services;
 pub mod state;
+pub mod versions;
 
 use rocket::Config;
+use rocket::data::{Limits, ToByteUnit};
+use rocket::fairing::AdHoc;
 use rocket_cors::{AllowedOrigins, CorsOptions};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub use config::AppConfig;
 pub use database::DatabaseService;
-pub use fairings::RequestLogger;
+pub use fairings::{
+    AccessLogger, BandwidthRecorder, ExtraListeners, IpGuard, LiveMetricsRecorder,
+    MaintenanceGuard, RateLimitGuard, RequestId, RequestLogger,
+};
 pub use services::CacheService;
 pub use state::AppState;
 
-pub fn create_rocket() -> rocket::Rocket<rocket::Build> {
-    // Load configuration from environment
-    let config = AppConfig::from_env();
+/// Builds the shared application state: database (with migrations), bootstrap admin and cache
+pub fn create_state(config: AppConfig) -> AppState {
+    // Secrets read from files and Vault, kept current by the refresh task
+    let secrets = Arc::new(secrets::SecretStore::new(config.secrets.clone()));
 
     // Create HTTP client
-    let client = reqwest::Client::new();
+    let client =
+        create_http_client(&config, &secrets).expect("Failed to create upstream HTTP client");
 
     // Initialize database service first
     let database = Arc::new(
-        DatabaseService::new(&config.database_url).expect("Failed to initialize database"),
+        DatabaseService::with_options(
+            &config.database_url,
+            &database::PoolOptions::from_config(&config),
+        )
+        .expect("Failed to initialize database"),
     );
 
+    // Create the bootstrap admin account if configured
+    services::AuthService::bootstrap_admin(&database, &config)
+        .expect("Failed to create bootstrap admin user");
+
+    // Tokens from before they were stored hashed
+    services::AuthService::hash_stored_tokens(&database, &config)
+        .expect("Failed to hash stored tokens");
+
+    // Create the key published packages are signed with
+    services::SigningService::ensure_registry_key(&database)
+        .expect("Failed to create registry signing key");
+
     // Initialize cache service with database for persistent stats
     let cache = Arc::new(
         CacheService::new_with_database(config.clone(), Some(&database))
             .expect("Failed to initialize cache"),
     );
 
+    let maintenance = Arc::new(services::MaintenanceMode::new(
+        config.maintenance_mode,
+        config.maintenance_retry_after_secs,
+    ));
+
+    // Script deciding on publishes and installs
+    let policy = Arc::new(
+        services::PolicyEngine::load(config.policy_script.as_deref())
+            .expect("Failed to load policy script"),
+    );
+
+    // WASM auth providers and publish validators
+    let plugins =
+        Arc::new(services::PluginHost::load(&config.plugins).expect("Failed to load plugins"));
+
+    // Cache sweeping, download stats rollups, advisory sync and retention policies
+    let jobs = Arc::new(services::JobScheduler::new(&config));
+
+    // Request rate limits and login throttling, shared through Redis when configured
+    let rate_limiter = Arc::new(services::RateLimiter::new(&config));
+
+    // Caps on concurrent upstream requests
+    let upstream_limiter = Arc::new(services::UpstreamLimiter::new(&config));
+
     // Create app state
-    let state = AppState {
+    AppState {
         config,
         client,
         cache,
         database,
+        events: Arc::new(services::EventBus::new()),
+        maintenance,
+        secrets,
+        policy,
+        plugins,
+        jobs,
+        rate_limiter,
+        dashboard: Arc::new(services::DashboardCache::default()),
+        changes_feed: Arc::new(services::ChangesFeedCursor::default()),
+        live_metrics: Arc::new(services::LiveMetrics::default()),
+        upstream_limiter,
+    }
+}
+
+/// Builds the client used for all upstream requests
+pub fn create_http_client(
+    config: &AppConfig,
+    secrets: &Arc<secrets::SecretStore>,
+) -> Result<reqwest::Client, reqwest::Error> {
+    let mut builder =
+        reqwest::Client::builder().pool_max_idle_per_host(config.upstream_pool_max_idle);
+
+    if config.upstream_connect_timeout_ms > 0 {
+        builder =
+            builder.connect_timeout(Duration::from_millis(config.upstream_connect_timeout_ms));
+    }
+    if config.upstream_read_timeout_ms > 0 {
+        builder = builder.read_timeout(Duration::from_millis(config.upstream_read_timeout_ms));
+    }
+    builder = match config.upstream_pool_idle_timeout_secs {
+        0 => builder.pool_idle_timeout(None),
+        secs => builder.pool_idle_timeout(Duration::from_secs(secs)),
+    };
+    builder = match config.upstream_http2.as_str() {
+        "always" => builder.http2_prior_knowledge(),
+        "never" => builder.http1_only(),
+        _ => builder,
+    };
+    if let Some(proxy) = &config.http_proxy {
+        let mut proxy = match &config.http_proxy_username {
+            Some(username) => proxy_with_credentials(proxy, username, config, secrets)?,
+            None => reqwest::Proxy::all(proxy)?,
+        };
+        if !config.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&config.no_proxy.join(",")));
+        }
+        builder = builder.proxy(proxy);
+    }
+
+    builder.build()
+}
+
+/// Proxy authenticating with the current password, read again for every connection so a
+/// password rotated in its file or Vault is used once the secrets are refreshed. A custom
+/// proxy doesn't apply `no_proxy` itself, the matcher does.
+fn proxy_with_credentials(
+    proxy: &str,
+    username: &str,
+    config: &AppConfig,
+    secrets: &Arc<secrets::SecretStore>,
+) -> Result<reqwest::Proxy, reqwest::Error> {
+    let static_proxy = reqwest::Proxy::all(proxy)?;
+    // Proxies may be given without a scheme, like reqwest takes them
+    let proxy_url = reqwest::Url::parse(proxy)
+        .ok()
+        .filter(reqwest::Url::has_host)
+        .or_else(|| reqwest::Url::parse(&format!("http://{proxy}")).ok());
+    // Only decides which destinations are proxied, the credentials are added below
+    let matcher = proxy_url.as_ref().map(|url| {
+        hyper_util::client::proxy::matcher::Matcher::builder()
+            .all(url.as_str())
+            .no(config.no_proxy.join(","))
+            .build()
+    });
+    let proxy_url = proxy_url.and_then(|mut url| url.set_username(username).is_ok().then_some(url));
+    let (Some(matcher), Some(proxy_url)) = (matcher, proxy_url) else {
+        let password = config.http_proxy_password.as_deref().unwrap_or_default();
+        return Ok(static_proxy.basic_auth(username, password));
     };
+    let secrets = secrets.clone();
+    let password = config.http_proxy_password.clone();
+
+    Ok(reqwest::Proxy::custom(move |url| {
+        matcher.intercept(&url.as_str().parse().ok()?)?;
+        let password = secrets
+            .get("CLEF_HTTP_PROXY_PASSWORD")
+            .or_else(|| password.clone())
+            .unwrap_or_default();
+        let mut proxy_url = proxy_url.clone();
+        proxy_url.set_password(Some(&password)).ok()?;
+        Some(proxy_url)
+    }))
+}
+
+pub fn create_rocket() -> rocket::Rocket<rocket::Build> {
+    // Load configuration from environment
+    let state = create_state(AppConfig::from_env());
 
     // Configure CORS
     let cors = CorsOptions::default()
@@ -54,12 +202,63 @@ pub fn create_rocket() -> rocket::Rocket<rocket::Build> {
     let rocket_config = Config {
         port: state.config.port,
         address: state.config.host.parse().expect("Invalid host address"),
+        limits: Limits::default().limit("json", state.config.json_body_limit().bytes()),
         ..Config::default()
     };
 
+    let extra_listeners = ExtraListeners::new(state.config.listen.clone());
+    let access_logger = AccessLogger::new(services::AccessLog::from_config(&state.config));
+    let jobs_state = state.clone();
+    let tasks_state = state.clone();
+    let hook_state = state.clone();
+    let notification_state = state.clone();
+    let counters_state = state.clone();
+    let secrets = state.secrets.clone();
+    let secrets_refresh_secs = state.config.secrets_refresh_secs;
+    let shutdown_database = state.database.clone();
+
     rocket::custom(&rocket_config)
         .manage(state)
+        .attach(AdHoc::on_liftoff("Job scheduler", |_| {
+            Box::pin(async move { services::JobScheduler::spawn(jobs_state) })
+        }))
+        .attach(AdHoc::on_liftoff("Task worker", |_| {
+            Box::pin(async move { services::TaskService::spawn_worker(tasks_state) })
+        }))
+        .attach(AdHoc::on_liftoff("Hook delivery", |_| {
+            Box::pin(async move { services::HookService::spawn_dispatcher(hook_state) })
+        }))
+        .attach(AdHoc::on_liftoff("Notification delivery", |_| {
+            Box::pin(
+                async move { services::NotificationService::spawn_dispatcher(notification_state) },
+            )
+        }))
+        .attach(AdHoc::on_liftoff("Counter flush", |_| {
+            Box::pin(
+                async move { services::DownloadStatsService::spawn_counter_flush(counters_state) },
+            )
+        }))
+        .attach(AdHoc::on_liftoff("Secrets refresh", move |_| {
+            Box::pin(
+                async move { secrets::SecretStore::spawn_refresh(secrets, secrets_refresh_secs) },
+            )
+        }))
+        .attach(AdHoc::on_shutdown("Counter flush", |_| {
+            Box::pin(async move {
+                if let Err(e) = shutdown_database.flush_counters() {
+                    log::warn!("Writing download and cache counters on shutdown failed: {e}");
+                }
+            })
+        }))
         .attach(cors)
         .attach(RequestLogger)
+        .attach(access_logger)
+        .attach(LiveMetricsRecorder)
+        .attach(BandwidthRecorder)
+        .attach(IpGuard)
+        .attach(RateLimitGuard)
+        .attach(MaintenanceGuard)
+        .attach(extra_listeners)
         .mount("/", routes::get_routes())
+        .register("/", routes::get_catchers())
 }
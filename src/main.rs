@@ -1,9 +1,397 @@
-use rocket::launch;
+use clap::Parser;
+use clef::cli::{Cli, Command, ImportCommand, PasswordSource, TokenCommand, UserCommand};
+use clef::error::ApiError;
+use clef::models::{RegisterRequest, UserRole};
+use clef::services::repository_import::{
+    RepositoryImportOptions, RepositoryImportService, RepositoryKind,
+};
+use clef::services::seed::{SeedOptions, SeedService};
+use clef::services::verdaccio::{VerdaccioImportOptions, VerdaccioImportService};
+use clef::services::{AuthService, DoctorService, StorageService};
+use clef::{RequestId, redact};
+use std::io::{BufRead, Write};
 
-#[launch]
-async fn rocket() -> _ {
-    // Initialize logging
-    env_logger::init();
+#[rocket::main]
+async fn main() {
+    // Initialize logging, lines logged while handling a request start with its id. Tokens
+    // and passwords are scrubbed from every line, including those logged by dependencies.
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            let style = buf.default_level_style(record.level());
+            let request_id = RequestId::current()
+                .map(|id| format!("[{id}] "))
+                .unwrap_or_default();
+            writeln!(
+                buf,
+                "[{} {style}{:<5}{style:#} {}] {request_id}{}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                redact::scrub(&record.args().to_string())
+            )
+        })
+        .init();
 
-    clef::create_rocket()
+    match Cli::parse().into_command() {
+        Command::Serve => serve().await,
+        Command::User(UserCommand::Create {
+            username,
+            email,
+            password,
+            admin,
+        }) => user_create(username, email, password.source(), admin),
+        Command::Token(TokenCommand::Create { username, publish }) => {
+            token_create(&username, publish)
+        }
+        Command::Gc { delete, json } => gc(delete, json).await,
+        Command::Verify { json } => verify(json),
+        Command::Doctor => doctor().await,
+        Command::Migrate { status } => migrate(status),
+        Command::Seed(options) => seed(options).await,
+        Command::Import(ImportCommand::Verdaccio(options)) => import_verdaccio(options).await,
+        Command::Import(ImportCommand::Nexus(options)) => {
+            import_repository(RepositoryImportOptions {
+                kind: RepositoryKind::Nexus,
+                ..options
+            })
+            .await
+        }
+        Command::Import(ImportCommand::Artifactory(options)) => {
+            import_repository(RepositoryImportOptions {
+                kind: RepositoryKind::Artifactory,
+                ..options
+            })
+            .await
+        }
+    }
+}
+
+async fn serve() {
+    if let Err(e) = clef::create_rocket().launch().await {
+        eprintln!("Failed to launch clef: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Exits with the error of a failed command
+fn fail(message: &str, e: ApiError) -> ! {
+    eprintln!("{message}: {e}");
+    std::process::exit(1);
+}
+
+/// `clef user create`: creates an account without going through registration
+fn user_create(username: String, email: Option<String>, password: PasswordSource, admin: bool) {
+    let password = match password {
+        PasswordSource::Argument(password) => password,
+        PasswordSource::Stdin => {
+            let mut line = String::new();
+            if let Err(e) = std::io::stdin().lock().read_line(&mut line) {
+                eprintln!("Failed to read the password from stdin: {e}");
+                std::process::exit(1);
+            }
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.is_empty() {
+        eprintln!("The password must not be empty");
+        std::process::exit(2);
+    }
+
+    let state = clef::create_state(clef::AppConfig::from_env());
+    let email = email.unwrap_or_else(|| format!("{username}@localhost"));
+
+    let user = AuthService::register_user(
+        &state.database,
+        RegisterRequest {
+            name: username.clone(),
+            email,
+            password,
+            invite_token: None,
+        },
+    )
+    .unwrap_or_else(|e| fail("Failed to create user", e));
+
+    let role = if admin {
+        AuthService::set_user_role(&state.database, &username, UserRole::Admin)
+            .unwrap_or_else(|e| fail("Failed to grant the admin role", e))
+            .role
+    } else {
+        user.role
+    };
+
+    println!(
+        "Created user '{}' <{}> with role {role}",
+        user.username, user.email
+    );
+}
+
+/// `clef token create`: prints a new token for a user
+fn token_create(username: &str, publish: bool) {
+    let state = clef::create_state(clef::AppConfig::from_env());
+
+    let token = AuthService::create_token(
+        &state.database,
+        &state.secrets.token_keys(&state.config),
+        username,
+        publish,
+    )
+    .unwrap_or_else(|e| fail("Failed to create token", e));
+
+    println!("{token}");
+}
+
+/// `clef gc`: reports cache files and records that don't belong together, removes them
+/// with `--delete`, and removes tokens that can no longer be used
+async fn gc(delete: bool, json: bool) {
+    let state = clef::create_state(clef::AppConfig::from_env());
+
+    let pruned = AuthService::prune_tokens(&state.database)
+        .unwrap_or_else(|e| fail("Failed to prune tokens", e));
+
+    let report = state
+        .cache
+        .collect_garbage(&state.database, delete)
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Cache garbage collection failed: {e}");
+            std::process::exit(1);
+        });
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("Failed to serialize report")
+        );
+        return;
+    }
+
+    for file in &report.orphaned_files {
+        println!("orphaned file: {file}");
+    }
+    for missing in &report.missing_files {
+        let kept = if missing.removable {
+            ""
+        } else {
+            " (published, kept)"
+        };
+        println!("missing file: {} {}{kept}", missing.name, missing.path);
+    }
+    println!(
+        "Scanned {} files: {} orphaned ({:.2} MB), {} records without a file",
+        report.scanned_files,
+        report.orphaned_files.len(),
+        report.orphaned_bytes as f64 / 1024.0 / 1024.0,
+        report.missing_files.len()
+    );
+    if delete {
+        println!(
+            "Removed {} files and {} records",
+            report.files_removed, report.records_removed
+        );
+    } else if !report.orphaned_files.is_empty()
+        || report.missing_files.iter().any(|missing| missing.removable)
+    {
+        println!("Run 'clef gc --delete' to remove them");
+    }
+    println!("Deleted {pruned} revoked or expired tokens");
+}
+
+/// `clef verify`: checks stored files against the database
+fn verify(json: bool) {
+    let state = clef::create_state(clef::AppConfig::from_env());
+
+    let report =
+        StorageService::verify(&state.database).unwrap_or_else(|e| fail("Verification failed", e));
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("Failed to serialize report")
+        );
+    } else {
+        for problem in &report.problems {
+            println!(
+                "{}@{} {}: {} ({})",
+                problem.name, problem.version, problem.filename, problem.error, problem.path
+            );
+        }
+        println!(
+            "Checked {} files ({:.2} MB): {} problems",
+            report.files,
+            report.bytes as f64 / 1024.0 / 1024.0,
+            report.problems.len()
+        );
+    }
+
+    if !report.problems.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// `clef doctor`: prints the report of every consistency check
+async fn doctor() {
+    let state = clef::create_state(clef::AppConfig::from_env());
+
+    let report = DoctorService::run(&state)
+        .await
+        .unwrap_or_else(|e| fail("Doctor failed", e));
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("Failed to serialize report")
+    );
+
+    if !report.ok {
+        std::process::exit(1);
+    }
+}
+
+/// `clef migrate`: applies pending migrations without starting the server
+fn migrate(status: bool) {
+    let config = clef::AppConfig::from_env();
+    if status {
+        return migration_status(&config.database_url);
+    }
+
+    match clef::database::run_pending_migrations(&config.database_url) {
+        Ok(applied) if applied.is_empty() => println!("Database is up to date"),
+        Ok(applied) => {
+            for version in &applied {
+                println!("Applied {version}");
+            }
+            println!("Applied {} migrations", applied.len());
+        }
+        Err(e) => {
+            eprintln!("Migration failed: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `clef migrate --status`: the schema version and pending migrations, applying nothing
+fn migration_status(database_url: &str) {
+    use diesel::Connection;
+
+    let status = diesel::SqliteConnection::establish(database_url)
+        .map_err(|e| e.to_string())
+        .and_then(|mut conn| clef::database::schema_status(&mut conn).map_err(|e| e.to_string()));
+    match status {
+        Ok(status) => {
+            println!(
+                "Schema version: {}",
+                status.version.as_deref().unwrap_or("none")
+            );
+            println!("Applied migrations: {}", status.applied.len());
+            for name in &status.pending {
+                println!("Pending {name}");
+            }
+            if status.up_to_date {
+                println!("Database is up to date");
+            } else {
+                println!("{} pending migrations", status.pending.len());
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to read the schema status: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `clef seed`: fills the database and cache with synthetic packages for load testing
+async fn seed(options: SeedOptions) {
+    let state = clef::create_state(clef::AppConfig::from_env());
+
+    match SeedService::seed(&options, &state).await {
+        Ok(report) => {
+            println!(
+                "Seeded {} packages: {} versions imported, {} already present ({:.2?})",
+                report.packages,
+                report.imported_versions,
+                report.skipped_versions,
+                report.seed_time
+            );
+            if let Some(warm_time) = report.warm_time {
+                println!(
+                    "Generated metadata for {} packages in {warm_time:.2?} ({:.2?} per package)",
+                    report.packages,
+                    warm_time / report.packages as u32
+                );
+            }
+            println!("Fixtures written to {}", report.fixtures_path.display());
+        }
+        Err(e) => {
+            eprintln!("Seeding failed: {e:?}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `clef import verdaccio`: migrates a Verdaccio storage directory and htpasswd file
+async fn import_verdaccio(options: VerdaccioImportOptions) {
+    let state = clef::create_state(clef::AppConfig::from_env());
+
+    let report = VerdaccioImportService::import(&options, &state)
+        .await
+        .unwrap_or_else(|e| fail("Import failed", e));
+
+    for username in &report.users_without_password {
+        println!("user without password: {username} (not a bcrypt hash, reset the password)");
+    }
+    for version in &report.missing_tarballs {
+        println!("missing tarball: {version}");
+    }
+    if !report.cached_packages.is_empty() {
+        println!(
+            "Skipped {} packages cached from uplinks, import them with --include-cached",
+            report.cached_packages.len()
+        );
+    }
+    for (package, error) in &report.failed_packages {
+        println!("failed: {package}: {error}");
+    }
+    println!(
+        "Imported {} users ({} already existed) and {} packages: {} versions imported, {} already present",
+        report.users_created,
+        report.users_skipped,
+        report.packages,
+        report.imported_versions,
+        report.skipped_versions
+    );
+
+    if !report.failed_packages.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// `clef import nexus|artifactory`: copies the packages of a repository manager's npm repository
+async fn import_repository(mut options: RepositoryImportOptions) {
+    if options.password_stdin {
+        let mut line = String::new();
+        if let Err(e) = std::io::stdin().lock().read_line(&mut line) {
+            eprintln!("Failed to read the password from stdin: {e}");
+            std::process::exit(1);
+        }
+        options.secret = Some(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    let state = clef::create_state(clef::AppConfig::from_env());
+
+    let report = RepositoryImportService::import(&options, &state)
+        .await
+        .unwrap_or_else(|e| fail("Import failed", e));
+
+    for version in &report.missing_tarballs {
+        println!("missing tarball: {version}");
+    }
+    for (package, error) in &report.failed_packages {
+        println!("failed: {package}: {error}");
+    }
+    println!(
+        "Imported {} packages: {} versions imported, {} already present",
+        report.packages, report.imported_versions, report.skipped_versions
+    );
+
+    if !report.failed_packages.is_empty() {
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,88 @@
+use crate::schema::advisories;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Advisory model - a known vulnerability of one package, used to answer `npm audit`
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = advisories)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Advisory {
+    pub id: i32,
+    /// Where the advisory comes from, `osv` for advisories synced from OSV.dev and
+    /// `internal` for advisories defined by registry admins
+    pub source: String,
+    /// Identifier in the source database, e.g. `GHSA-xxxx-xxxx-xxxx`
+    pub advisory_id: String,
+    pub package_name: String,
+    pub title: String,
+    pub overview: Option<String>,
+    pub url: String,
+    /// info, low, moderate, high or critical
+    pub severity: String,
+    /// npm style range, e.g. `>=1.0.0 <1.2.3 || <0.9.0`
+    pub vulnerable_versions: String,
+    pub patched_versions: Option<String>,
+    pub cwe: Option<String>,  // JSON array as text
+    pub cves: Option<String>, // JSON array as text
+    pub cvss_vector: Option<String>,
+    pub published_at: Option<NaiveDateTime>,
+    pub modified_at: Option<NaiveDateTime>,
+    pub synced_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Debug, Clone)]
+#[diesel(table_name = advisories)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewAdvisory {
+    pub source: String,
+    pub advisory_id: String,
+    pub package_name: String,
+    pub title: String,
+    pub overview: Option<String>,
+    pub url: String,
+    pub severity: String,
+    pub vulnerable_versions: String,
+    pub patched_versions: Option<String>,
+    pub cwe: Option<String>,
+    pub cves: Option<String>,
+    pub cvss_vector: Option<String>,
+    pub published_at: Option<NaiveDateTime>,
+    pub modified_at: Option<NaiveDateTime>,
+    pub synced_at: NaiveDateTime,
+}
+
+/// Result of syncing the advisory store from OSV.dev
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct AdvisorySyncReport {
+    pub packages_checked: usize,
+    pub advisories_updated: usize,
+    pub advisories_removed: usize,
+    pub duration_ms: u128,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AdvisoryStatusResponse {
+    pub advisories: i64,
+    pub packages: i64,
+    pub last_synced_at: Option<NaiveDateTime>,
+    pub internal_advisories: i64,
+    pub osv_url: String,
+    pub sync_interval_hours: u64,
+}
+
+/// An advisory defined by registry admins, e.g. to keep an old major of a package out of
+/// internal projects
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct InternalAdvisoryRequest {
+    pub package_name: String,
+    pub title: String,
+    pub overview: Option<String>,
+    pub url: Option<String>,
+    pub severity: Option<String>,
+    pub vulnerable_versions: String,
+    pub patched_versions: Option<String>,
+    pub cwe: Option<Vec<String>>,
+    pub cves: Option<Vec<String>>,
+}
@@ -0,0 +1,43 @@
+use crate::schema::allowed_packages;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Allowed package model - package names (or name patterns) that may be proxied from upstream
+// when only allowlisted packages are
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = allowed_packages)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AllowedPackage {
+    pub id: i32,
+    pub pattern: String,
+    pub is_regex: bool,
+    pub reason: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = allowed_packages)]
+pub struct NewAllowedPackage {
+    pub pattern: String,
+    pub is_regex: bool,
+    pub reason: Option<String>,
+    pub created_by: Option<i32>,
+}
+
+// Request/Response models
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AllowedPackageRequest {
+    pub pattern: String,
+    pub is_regex: Option<bool>,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AllowlistResponse {
+    /// Whether packages missing from the allowlist are refused
+    pub enabled: bool,
+    pub entries: Vec<AllowedPackage>,
+}
@@ -0,0 +1,53 @@
+use chrono::NaiveDateTime;
+use rocket::serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Identifier written into every package archive
+pub const PACKAGE_ARCHIVE_FORMAT: &str = "clef-package-archive";
+/// Current archive format version
+pub const PACKAGE_ARCHIVE_VERSION: u32 = 1;
+
+/// Self-contained export of a package: metadata, versions, dist-tags and tarballs
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct PackageArchive {
+    pub format: String,
+    pub format_version: u32,
+    pub exported_at: NaiveDateTime,
+    pub name: String,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub repository_url: Option<String>,
+    pub license: Option<String>,
+    pub keywords: Option<String>,
+    pub dist_tags: HashMap<String, String>,
+    pub versions: Vec<ArchivedVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ArchivedVersion {
+    pub version: String,
+    pub created_at: NaiveDateTime,
+    /// Version manifest (package.json) as served by the registry
+    pub manifest: Value,
+    pub files: Vec<ArchivedFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ArchivedFile {
+    pub filename: String,
+    pub content_type: Option<String>,
+    pub size_bytes: i64,
+    /// Base64 encoded file contents
+    pub data: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageImportResponse {
+    pub ok: bool,
+    pub package: String,
+    pub imported_versions: Vec<String>,
+    pub skipped_versions: Vec<String>,
+    pub dist_tags: HashMap<String, String>,
+}
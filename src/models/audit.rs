@@ -0,0 +1,77 @@
+use crate::models::user::UserRole;
+use crate::schema::audit_log;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Action recorded for every published version
+pub const PUBLISH_ACTION: &str = "package.publish";
+
+// Audit log entry - a change made to the registry on behalf of a user or a scheduled job
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AuditLogEntry {
+    pub id: i32,
+    /// Dotted action name, e.g. `retention.delete_version`
+    pub action: String,
+    /// Missing for changes made by the registry itself
+    pub actor_id: Option<i32>,
+    pub organization_id: Option<i32>,
+    pub package_name: Option<String>,
+    pub version: Option<String>,
+    /// JSON document with action specific details
+    pub details: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Default)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditLogEntry {
+    pub action: String,
+    pub actor_id: Option<i32>,
+    pub organization_id: Option<i32>,
+    pub package_name: Option<String>,
+    pub version: Option<String>,
+    pub details: Option<String>,
+}
+
+/// An audit log entry with the username and role of its actor, as loaded from the database
+pub type AuditLogRow = (AuditLogEntry, Option<(String, String)>);
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AuditLogResponse {
+    pub entries: Vec<AuditLogActorEntry>,
+}
+
+/// An audit log entry with the name of the user who made the change
+#[derive(Serialize, Debug, ToSchema)]
+pub struct AuditLogActorEntry {
+    #[serde(flatten)]
+    pub entry: AuditLogEntry,
+    /// Missing for changes made by the registry itself or by deleted users
+    pub actor: Option<String>,
+    /// Whether the change was made by an organization's service account, e.g. from CI
+    pub service_account: bool,
+}
+
+impl From<AuditLogRow> for AuditLogActorEntry {
+    fn from((entry, actor): AuditLogRow) -> Self {
+        let service_account = actor
+            .as_ref()
+            .is_some_and(|(_, role)| UserRole::from_role_str(role) == Some(UserRole::Service));
+
+        Self {
+            entry,
+            actor: actor.map(|(username, _)| username),
+            service_account,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageHistoryResponse {
+    pub package: String,
+    pub entries: Vec<AuditLogActorEntry>,
+}
@@ -1,28 +1,32 @@
+use crate::services::IpFilter;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::{
     State,
     http::Status,
     request::{FromRequest, Outcome, Request},
 };
+use utoipa::ToSchema;
 
 // Authentication request/response models
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct LoginRequest {
     pub name: String,
     pub password: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct LoginResponse {
     pub ok: bool,
     pub token: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, ToSchema)]
 pub struct RegisterRequest {
     pub name: String,
     pub email: String,
     pub password: String,
+    #[serde(default)]
+    pub invite_token: Option<String>,
 }
 
 // npm login uses a specific CouchDB-style user document format
@@ -37,7 +41,7 @@ pub struct NpmUserDocument {
     pub date: Option<String>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct NpmUserResponse {
     pub ok: bool,
     pub id: String,
@@ -58,21 +62,133 @@ pub struct WhoamiResponse {
 }
 
 // npm logout endpoint response
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct LogoutResponse {
     pub ok: bool,
 }
 
-// Authentication guard for extracting user from Authorization header
+// Email verification and password reset
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    pub password: String,
+}
+
+// Client details recorded on tokens (user agent and IP address)
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn of_request(request: &Request<'_>) -> Self {
+        Self {
+            user_agent: request
+                .headers()
+                .get_one("User-Agent")
+                .map(|ua| ua.chars().take(512).collect()),
+            ip_address: request
+                .rocket()
+                .state::<crate::state::AppState>()
+                .and_then(|state| IpFilter::client_ip(request, &state.config))
+                .map(|ip| ip.to_string()),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo::of_request(request))
+    }
+}
+
+// Authentication guard for extracting user from a client certificate or Authorization header
 #[derive(Debug, Clone)]
 pub struct AuthenticatedUser {
     pub username: String,
     pub user_id: i32,
+    pub is_admin: bool,
+    /// Id of the token the request was authenticated with, missing for client certificates
+    pub token_id: Option<i32>,
 }
 
 impl AuthenticatedUser {
-    pub fn new(username: String, user_id: i32) -> Self {
-        Self { username, user_id }
+    pub fn new(username: String, user_id: i32, is_admin: bool, token_id: Option<i32>) -> Self {
+        Self {
+            username,
+            user_id,
+            is_admin,
+            token_id,
+        }
+    }
+
+    /// Resolves the user of a request. A client certificate forwarded by a trusted proxy
+    /// wins over the Authorization header, which is refused when certificates are required.
+    /// `Ok(None)` for anonymous requests.
+    fn resolve(
+        request: &Request<'_>,
+        state: &crate::state::AppState,
+    ) -> Result<Option<Self>, crate::error::ApiError> {
+        use crate::error::ApiError;
+        use crate::services::{AuthService, ClientCertificateService, ServiceAccountService};
+
+        let (user, token_id) = if let Some(certificate) =
+            ClientCertificateService::presented(request, &state.config)
+        {
+            let user = ClientCertificateService::authenticate(&state.database, &certificate)?;
+            (user, None)
+        } else {
+            // npm sends "Bearer <token>" format
+            let Some(auth_value) = request.headers().get_one("Authorization") else {
+                return Ok(None);
+            };
+            if ClientCertificateService::is_required(&state.config) {
+                return Err(ApiError::Unauthorized(
+                    "This registry requires a client certificate".to_string(),
+                ));
+            }
+            let token = auth_value.strip_prefix("Bearer ").ok_or_else(|| {
+                ApiError::Unauthorized("Invalid authorization format".to_string())
+            })?;
+            let client = ClientInfo::of_request(request);
+            let (user, user_token) = AuthService::validate_token(
+                &state.database,
+                &state.secrets.token_keys(&state.config),
+                token,
+                &client,
+            )
+            .map_err(|_| ApiError::Unauthorized("Invalid token".to_string()))?;
+            (user, Some(user_token.id))
+        };
+
+        if user.is_service_account()
+            && !ServiceAccountService::allows(request.method(), request.uri().path().segments())
+        {
+            return Err(ApiError::Forbidden(
+                "Service accounts can only read and publish packages".to_string(),
+            ));
+        }
+
+        Ok(Some(AuthenticatedUser {
+            is_admin: user.is_admin(),
+            username: user.username,
+            user_id: user.id,
+            token_id,
+        }))
     }
 }
 
@@ -81,40 +197,17 @@ impl<'r> FromRequest<'r> for AuthenticatedUser {
     type Error = crate::error::ApiError;
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        use crate::services::AuthService;
         use crate::state::AppState;
 
         let state = request.guard::<&State<AppState>>().await.unwrap();
 
-        // Get Authorization header
-        let auth_header = request.headers().get_one("Authorization");
-
-        if let Some(auth_value) = auth_header {
-            // npm sends "Bearer <token>" format
-            if let Some(token) = auth_value.strip_prefix("Bearer ") {
-                match AuthService::validate_token(&state.database, token) {
-                    Ok(user) => Outcome::Success(AuthenticatedUser {
-                        username: user.username,
-                        user_id: user.id,
-                    }),
-                    Err(_) => Outcome::Error((
-                        Status::Unauthorized,
-                        crate::error::ApiError::Unauthorized("Invalid token".to_string()),
-                    )),
-                }
-            } else {
-                Outcome::Error((
-                    Status::Unauthorized,
-                    crate::error::ApiError::Unauthorized(
-                        "Invalid authorization format".to_string(),
-                    ),
-                ))
-            }
-        } else {
-            Outcome::Error((
+        match AuthenticatedUser::resolve(request, state) {
+            Ok(Some(user)) => Outcome::Success(user),
+            Ok(None) => Outcome::Error((
                 Status::Unauthorized,
                 crate::error::ApiError::Unauthorized("Authorization header required".to_string()),
-            ))
+            )),
+            Err(e) => Outcome::Error((e.code().status(), e)),
         }
     }
 }
@@ -128,7 +221,6 @@ impl<'r> FromRequest<'r> for OptionalAuthenticatedUser {
     type Error = ();
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        use crate::services::AuthService;
         use crate::state::AppState;
 
         let state = match request.guard::<&State<AppState>>().await {
@@ -136,26 +228,70 @@ impl<'r> FromRequest<'r> for OptionalAuthenticatedUser {
             _ => return Outcome::Success(OptionalAuthenticatedUser(None)),
         };
 
-        // Get Authorization header
-        let auth_header = request.headers().get_one("Authorization");
+        // Invalid credentials are treated like none
+        let user = AuthenticatedUser::resolve(request, state).unwrap_or(None);
+        Outcome::Success(OptionalAuthenticatedUser(user))
+    }
+}
 
-        if let Some(auth_value) = auth_header {
-            // npm sends "Bearer <token>" format
-            if let Some(token) = auth_value.strip_prefix("Bearer ") {
-                match AuthService::validate_token(&state.database, token) {
-                    Ok(user) => {
-                        Outcome::Success(OptionalAuthenticatedUser(Some(AuthenticatedUser {
-                            username: user.username,
-                            user_id: user.id,
-                        })))
-                    }
-                    Err(_) => Outcome::Success(OptionalAuthenticatedUser(None)), // Invalid token = no auth
-                }
-            } else {
-                Outcome::Success(OptionalAuthenticatedUser(None)) // Invalid format = no auth
-            }
+// Registry read guard - like OptionalAuthenticatedUser, but rejects anonymous requests
+// when the registry runs in private mode (CLEF_REQUIRE_AUTH)
+#[derive(Debug, Clone)]
+pub struct RegistryReader(pub Option<AuthenticatedUser>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RegistryReader {
+    type Error = crate::error::ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        use crate::state::AppState;
+
+        let require_auth = match request.guard::<&State<AppState>>().await {
+            Outcome::Success(state) => state.config.require_auth,
+            _ => false,
+        };
+
+        let OptionalAuthenticatedUser(user) = match request.guard().await {
+            Outcome::Success(user) => user,
+            _ => OptionalAuthenticatedUser(None),
+        };
+
+        if user.is_none() && require_auth {
+            return Outcome::Error((
+                Status::Unauthorized,
+                crate::error::ApiError::Unauthorized(
+                    "This registry requires authentication, run `npm login`".to_string(),
+                ),
+            ));
+        }
+
+        // Kept for the response fairings, which attribute served bytes to the reader
+        Outcome::Success(request.local_cache(|| RegistryReader(user)).clone())
+    }
+}
+
+// Admin guard - an authenticated user with the admin role
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthenticatedUser);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = crate::error::ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match request.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => user,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        if user.is_admin {
+            Outcome::Success(AdminUser(user))
         } else {
-            Outcome::Success(OptionalAuthenticatedUser(None)) // No header = no auth
+            Outcome::Error((
+                Status::Forbidden,
+                crate::error::ApiError::Forbidden("Admin privileges required".to_string()),
+            ))
         }
     }
 }
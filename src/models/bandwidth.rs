@@ -0,0 +1,78 @@
+use crate::schema::bandwidth_usage;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use rocket::serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = bandwidth_usage)]
+pub struct NewBandwidthUsage {
+    pub day: NaiveDate,
+    pub package_name: String,
+    /// 0 for anonymous requests
+    pub user_id: i32,
+    /// 0 for requests not authenticated with a token
+    pub token_id: i32,
+    pub bytes: i64,
+    pub requests: i64,
+}
+
+/// Bytes served for a package to a user or token in a period, with the names of the user,
+/// token and the organization owning the package
+#[derive(Queryable, Debug, Clone, PartialEq)]
+pub struct BandwidthUsageRow {
+    pub package_name: String,
+    pub user_id: i32,
+    pub token_id: i32,
+    pub bytes: i64,
+    pub requests: i64,
+    pub username: Option<String>,
+    pub token_preview: Option<String>,
+    pub organization: Option<String>,
+}
+
+/// What served bytes are added up by
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BandwidthGrouping {
+    Package,
+    User,
+    Token,
+    Org,
+}
+
+impl BandwidthGrouping {
+    pub fn from_grouping_str(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "package" => Some(Self::Package),
+            "user" => Some(Self::User),
+            "token" => Some(Self::Token),
+            "org" => Some(Self::Org),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes served for one package, user, token or organization
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct BandwidthEntry {
+    /// Package name, username, token preview or organization name. Missing for anonymous
+    /// requests, requests without a token and packages outside an organization.
+    pub key: Option<String>,
+    /// Owner of the token, when grouped by token
+    pub username: Option<String>,
+    pub bytes: i64,
+    pub requests: i64,
+}
+
+/// Bytes served between two days (inclusive), largest consumers first
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BandwidthReport {
+    pub period: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub group_by: BandwidthGrouping,
+    pub total_bytes: i64,
+    pub total_requests: i64,
+    pub entries: Vec<BandwidthEntry>,
+}
@@ -0,0 +1,69 @@
+use crate::schema::blocked_names;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Blocked name model - package names (or name patterns) that may not be published
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = blocked_names)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct BlockedName {
+    pub id: i32,
+    pub pattern: String,
+    pub is_regex: bool,
+    /// Whether matching packages are also refused when proxying the upstream registry
+    pub block_upstream: bool,
+    pub reason: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = blocked_names)]
+pub struct NewBlockedName {
+    pub pattern: String,
+    pub is_regex: bool,
+    pub block_upstream: bool,
+    pub reason: Option<String>,
+    pub created_by: Option<i32>,
+}
+
+/// Where a blocklist entry comes from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlockedNameSource {
+    /// Added at runtime through the admin API
+    Admin,
+    /// `CLEF_BLOCKED_NAMES`
+    Config,
+    /// Built-in Node.js core module names
+    NodeCore,
+}
+
+/// The blocklist entry a package name matched
+#[derive(Serialize, Debug, Clone)]
+pub struct BlockedNameMatch {
+    pub pattern: String,
+    pub source: BlockedNameSource,
+    pub block_upstream: bool,
+    pub reason: Option<String>,
+}
+
+// Request/Response models
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct BlockedNameRequest {
+    pub pattern: String,
+    pub is_regex: Option<bool>,
+    pub block_upstream: Option<bool>,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BlockedNameListResponse {
+    pub entries: Vec<BlockedName>,
+    /// Entries from the configuration, which can't be changed at runtime
+    pub configured: Vec<String>,
+    pub configured_block_upstream: bool,
+    pub node_core_reserved: bool,
+}
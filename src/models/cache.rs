@@ -1,8 +1,9 @@
-use crate::models::package::{PackageWithVersions, PopularPackage};
-use crate::schema::cache_stats;
-use chrono::NaiveDateTime;
+use crate::models::package::{PackageWithVersions, PaginationMetadata, PopularPackage};
+use crate::schema::{cache_stats, package_cache_stats};
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
 use rocket::serde::Serialize;
+use utoipa::ToSchema;
 
 #[derive(Debug, Clone)]
 pub struct CacheEntry {
@@ -10,6 +11,8 @@ pub struct CacheEntry {
     pub created_at: u64,
     pub size: u64,
     pub etag: Option<String>,
+    /// Upstream `Last-Modified` of a tarball, as an HTTP date
+    pub last_modified: Option<String>,
 }
 
 #[derive(Debug)]
@@ -20,7 +23,7 @@ pub struct CacheStats {
     pub miss_count: u64,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct CacheAnalytics {
     pub total_packages: i64,
     pub total_size_bytes: i64,
@@ -31,6 +34,42 @@ pub struct CacheAnalytics {
     pub metadata_cache_entries: i64,
     pub metadata_cache_size_bytes: i64,
     pub metadata_cache_size_mb: f64,
+    pub hot_cache: HotCacheStats,
+}
+
+/// Usage of the in-memory metadata cache since startup
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct HotCacheStats {
+    pub enabled: bool,
+    pub entries: u64,
+    pub size_bytes: u64,
+    pub max_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub hit_rate: f64,
+    /// Whether documents are shared with other instances through Redis
+    pub shared: bool,
+    /// Hits on documents another instance put in Redis, included in `hits`
+    pub shared_hits: u64,
+}
+
+/// Connections of the database pool and the queries run since startup
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct DatabasePoolStats {
+    pub max_size: u32,
+    pub connections: u32,
+    pub in_use: u32,
+    pub idle: u32,
+    /// Connections handed out by the pool
+    pub checkouts: u64,
+    /// Requests that gave up waiting for a connection
+    pub checkout_timeouts: u64,
+    pub avg_wait_ms: f64,
+    pub max_wait_ms: f64,
+    pub queries: u64,
+    /// Queries slower than `CLEF_DB_SLOW_QUERY_MS`
+    pub slow_queries: u64,
 }
 
 // Database model for persistent cache stats
@@ -62,7 +101,7 @@ pub struct UpdateCacheStatsRecord {
     pub updated_at: Option<NaiveDateTime>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CacheStatsResponse {
     pub enabled: bool,
     pub total_entries: usize,
@@ -74,3 +113,240 @@ pub struct CacheStatsResponse {
     pub cache_dir: String,
     pub ttl_hours: u64,
 }
+
+// Package cache stats model - cache lookups of one package and the bytes served for it on
+// one day (UTC)
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = package_cache_stats)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PackageCacheStat {
+    pub id: i32,
+    pub package_name: String,
+    pub day: NaiveDate,
+    pub hits: i64,
+    pub misses: i64,
+    pub cache_bytes: i64,
+    pub upstream_bytes: i64,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = package_cache_stats)]
+pub struct NewPackageCacheStat {
+    pub package_name: String,
+    pub day: NaiveDate,
+    pub hits: i64,
+    pub misses: i64,
+    pub cache_bytes: i64,
+    pub upstream_bytes: i64,
+}
+
+/// Cache lookups of a package and where the bytes served for it came from
+#[derive(Serialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct PackageCacheUsage {
+    pub package: String,
+    pub hits: i64,
+    pub misses: i64,
+    /// Percentage of lookups that were hits
+    pub hit_rate: f64,
+    /// Bytes of metadata and tarballs served from the cache
+    pub cache_bytes: i64,
+    /// Bytes fetched from the upstream registry
+    pub upstream_bytes: i64,
+    /// Upstream traffic avoided by serving from the cache, the same as `cache_bytes`
+    pub bytes_saved: i64,
+}
+
+/// Per-package cache usage between two days, inclusive
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageCacheAnalytics {
+    pub period: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Packages by bytes served from the cache, largest first
+    pub packages: Vec<PackageCacheUsage>,
+}
+
+/// Result of warming the cache from a lockfile
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct PrefetchReport {
+    /// Distinct package names in the lockfile
+    pub packages: usize,
+    /// Distinct package versions in the lockfile
+    pub versions: usize,
+    pub already_cached: usize,
+    pub fetched: usize,
+    pub failed: Vec<PrefetchFailure>,
+    pub duration_ms: u128,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PrefetchFailure {
+    pub name: String,
+    /// Missing when the package metadata couldn't be fetched
+    pub version: Option<String>,
+    pub error: String,
+}
+
+/// Result of checking stored tarballs against their database records
+#[derive(Serialize, Debug, Default)]
+pub struct StorageVerifyReport {
+    pub files: usize,
+    pub bytes: u64,
+    pub problems: Vec<StorageProblem>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StorageProblem {
+    pub name: String,
+    pub version: String,
+    pub filename: String,
+    pub path: String,
+    pub error: String,
+}
+
+/// Result of comparing the cache directory with the database
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct CacheGcReport {
+    pub scanned_files: usize,
+    /// Cache files without a database record, relative to the cache directory
+    pub orphaned_files: Vec<String>,
+    pub orphaned_bytes: u64,
+    /// Database records whose file is missing
+    pub missing_files: Vec<MissingCacheFile>,
+    /// Whether orphaned files and removable records were deleted
+    pub deleted: bool,
+    pub files_removed: usize,
+    pub records_removed: usize,
+    pub duration_ms: u128,
+}
+
+/// Space on the filesystem holding the cache directory
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+pub struct DiskUsage {
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    /// Free space usable without root privileges
+    pub available_bytes: u64,
+}
+
+impl DiskUsage {
+    pub fn used_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.free_bytes)
+    }
+
+    /// Used share of the space available to clef, as reported by `df`
+    pub fn used_percent(&self) -> f64 {
+        let usable = self.used_bytes() + self.available_bytes;
+        if usable == 0 {
+            return 0.0;
+        }
+        self.used_bytes() as f64 / usable as f64 * 100.0
+    }
+
+    /// How much has to be freed to bring usage down to `percent`
+    pub fn bytes_above(&self, percent: u64) -> u64 {
+        let usable = self.used_bytes() + self.available_bytes;
+        let target = (usable as f64 * percent.min(100) as f64 / 100.0) as u64;
+        self.used_bytes().saturating_sub(target)
+    }
+}
+
+/// Cached tarballs removed because the cache volume crossed the high watermark
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct DiskEvictionReport {
+    pub used_percent_before: f64,
+    pub used_percent_after: f64,
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+    /// Packages that lost at least one cached tarball
+    pub packages: Vec<String>,
+    pub duration_ms: u128,
+}
+
+/// Watermark eviction settings and what it removed since startup
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct DiskEvictionStats {
+    pub enabled: bool,
+    pub high_watermark_percent: u64,
+    pub low_watermark_percent: u64,
+    pub usage: Option<DiskUsage>,
+    pub runs: u64,
+    pub files_evicted: u64,
+    pub bytes_evicted: u64,
+}
+
+/// What a file in a package's cache directory holds
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheEntryKind {
+    Tarball,
+    /// The package document served to package managers
+    Metadata,
+    /// The document of a single version
+    VersionMetadata,
+    /// The `package.json` stored next to a tarball
+    Manifest,
+    Other,
+}
+
+/// A cached file of a package
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct CacheEntryInfo {
+    pub package: String,
+    pub filename: String,
+    pub kind: CacheEntryKind,
+    pub size_bytes: u64,
+    /// When the file was last written
+    pub cached_at: Option<NaiveDateTime>,
+    pub age_secs: Option<u64>,
+    pub etag: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct CacheEntryListResponse {
+    pub entries: Vec<CacheEntryInfo>,
+    pub total_entries: usize,
+    pub total_size_bytes: u64,
+    pub pagination: PaginationMetadata,
+}
+
+/// Files and records removed when invalidating the cache of one package
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct CacheInvalidationReport {
+    pub package: String,
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+    pub records_removed: usize,
+    /// Tarballs of locally published packages are kept, only their metadata is dropped
+    pub kept_published_files: bool,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct MissingCacheFile {
+    pub name: String,
+    pub filename: String,
+    pub path: String,
+    /// Records of published tarballs are only reported, their content can't be fetched again
+    pub removable: bool,
+}
+
+/// Result of `clef doctor`: every consistency check between the database and storage
+#[derive(Serialize, Debug)]
+pub struct DoctorReport {
+    /// Whether no check found a problem
+    pub ok: bool,
+    pub checked_at: NaiveDateTime,
+    pub storage: StorageVerifyReport,
+    pub cache: CacheGcReport,
+    pub dangling_rows: Vec<DanglingRow>,
+    pub duration_ms: u128,
+}
+
+/// A database row that references something that doesn't exist
+#[derive(Serialize, Debug)]
+pub struct DanglingRow {
+    pub table: String,
+    pub id: i32,
+    pub name: Option<String>,
+    pub problem: String,
+}
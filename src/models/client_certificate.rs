@@ -0,0 +1,48 @@
+use crate::schema::client_certificates;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Client certificate - maps a certificate presented over mutual TLS to a user, by its
+// SHA-256 fingerprint or by the subject the TLS terminating proxy verified
+#[derive(Queryable, Selectable, Serialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = client_certificates)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ClientCertificate {
+    pub id: i32,
+    #[serde(skip)]
+    pub user_id: i32,
+    /// Lowercase hex SHA-256 of the DER encoded certificate
+    pub fingerprint: Option<String>,
+    /// Subject DN or one of the SANs, as forwarded by the proxy
+    pub subject: Option<String>,
+    pub description: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = client_certificates)]
+pub struct NewClientCertificate {
+    pub user_id: i32,
+    pub fingerprint: Option<String>,
+    pub subject: Option<String>,
+    pub description: Option<String>,
+}
+
+// Request/Response models
+/// Exactly one of `fingerprint` and `subject` is required
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ClientCertificateRequest {
+    /// SHA-256 fingerprint in hex, colons and case don't matter
+    pub fingerprint: Option<String>,
+    /// Subject DN or one of the SANs of the certificate
+    pub subject: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ClientCertificatesResponse {
+    pub certificates: Vec<ClientCertificate>,
+}
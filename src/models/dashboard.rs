@@ -0,0 +1,60 @@
+use crate::models::package::{PopularPackage, RecentVersion};
+use chrono::NaiveDateTime;
+use rocket::serde::Serialize;
+use utoipa::ToSchema;
+
+/// Row counts of the registry
+#[derive(Serialize, Debug, Clone, Default, ToSchema)]
+pub struct RegistryTotals {
+    pub packages: i64,
+    pub versions: i64,
+    pub users: i64,
+    pub organizations: i64,
+}
+
+/// Size and effectiveness of the cache
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct CacheSummary {
+    pub enabled: bool,
+    /// Bytes of every stored tarball
+    pub size_bytes: i64,
+    pub hit_rate: f64,
+    pub metadata_entries: i64,
+    pub metadata_size_bytes: i64,
+}
+
+/// Whether the upstream registry answered its ping
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct UpstreamStatus {
+    pub url: String,
+    pub reachable: bool,
+    pub status: Option<u16>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Everything the dashboard shows, in one response
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct DashboardSummary {
+    pub totals: RegistryTotals,
+    pub cache: CacheSummary,
+    pub top_packages: Vec<PopularPackage>,
+    /// Versions published to this registry, newest first
+    pub recent_publishes: Vec<RecentVersion>,
+    pub upstream: UpstreamStatus,
+    /// When the summary was put together, it is cached for `DASHBOARD_CACHE_SECS`
+    pub generated_at: NaiveDateTime,
+}
+
+/// Request rate, cache effectiveness and downloads in flight, streamed by `/api/v1/ws`
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LiveMetricsSample {
+    /// Requests per second since the previous sample
+    pub requests_per_sec: f64,
+    /// Share of cache lookups since the previous sample that were hits, absent when there
+    /// were none
+    pub cache_hit_rate: Option<f64>,
+    /// Tarball downloads being served right now
+    pub active_downloads: u64,
+    pub sampled_at: NaiveDateTime,
+}
@@ -0,0 +1,106 @@
+use crate::schema::{download_rollups, version_downloads};
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use rocket::serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+// Version download model - downloads of one version of a package on one day (UTC)
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = version_downloads)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct VersionDownload {
+    pub id: i32,
+    pub package_name: String,
+    pub version: String,
+    pub day: NaiveDate,
+    pub downloads: i64,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = version_downloads)]
+pub struct NewVersionDownload {
+    pub package_name: String,
+    pub version: String,
+    pub day: NaiveDate,
+    pub downloads: i64,
+}
+
+// Download rollup model - downloads of one version in a week or month, aggregated from
+// daily stats once they are old enough
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = download_rollups)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DownloadRollup {
+    pub id: i32,
+    pub package_name: String,
+    pub version: String,
+    /// week or month
+    pub period: String,
+    pub period_start: NaiveDate,
+    pub downloads: i64,
+}
+
+#[derive(Insertable, Debug, Clone, PartialEq)]
+#[diesel(table_name = download_rollups)]
+pub struct NewDownloadRollup {
+    pub package_name: String,
+    pub version: String,
+    pub period: String,
+    pub period_start: NaiveDate,
+    pub downloads: i64,
+}
+
+/// Outcome of rolling up and expiring download stats
+#[derive(Serialize, Debug, Default)]
+pub struct DownloadRollupReport {
+    pub daily_rows_rolled_up: usize,
+    pub weekly_rows_rolled_up: usize,
+    pub rows_expired: usize,
+}
+
+/// Size of the buckets of a download time series
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadGranularity {
+    Day,
+    Week,
+    Month,
+}
+
+impl DownloadGranularity {
+    pub fn from_granularity_str(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "day" => Some(Self::Day),
+            "week" => Some(Self::Week),
+            "month" => Some(Self::Month),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+/// Downloads in one bucket of a time series, starting at `start`
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DownloadPoint {
+    pub start: NaiveDate,
+    pub downloads: i64,
+    pub versions: BTreeMap<String, i64>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageDownloadsResponse {
+    pub package: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub granularity: DownloadGranularity,
+    pub total: i64,
+    pub points: Vec<DownloadPoint>,
+}
@@ -0,0 +1,45 @@
+use rocket::serde::Serialize;
+
+/// Registry-wide events published on the in-process event bus
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RegistryEvent {
+    PackageVisibilityChanged {
+        package: String,
+        from: String,
+        to: String,
+        actor: String,
+        reason: Option<String>,
+    },
+    VersionPublished {
+        package: String,
+        version: String,
+        actor: String,
+    },
+    VersionUnpublished {
+        package: String,
+        version: String,
+        actor: String,
+    },
+    VersionYanked {
+        package: String,
+        version: String,
+        yanked: bool,
+        actor: String,
+    },
+    /// A security advisory affecting a package was added
+    AdvisoryPublished {
+        package: String,
+        advisory_id: String,
+        title: String,
+        severity: String,
+    },
+    /// Cached tarballs were removed because the cache volume ran low on space
+    CacheEvicted {
+        files: usize,
+        bytes_freed: u64,
+        used_percent: f64,
+        high_watermark_percent: u64,
+        low_watermark_percent: u64,
+    },
+}
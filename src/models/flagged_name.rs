@@ -0,0 +1,83 @@
+use crate::schema::flagged_names;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Flagged name model - new package names that look like a typo of a popular package
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = flagged_names)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct FlaggedName {
+    pub id: i32,
+    pub package_name: String,
+    /// The popular package the name resembles
+    pub similar_to: String,
+    pub reason: String,
+    pub status: String,
+    pub published_by: Option<i32>,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = flagged_names)]
+pub struct NewFlaggedName {
+    pub package_name: String,
+    pub similar_to: String,
+    pub reason: String,
+    pub status: String,
+    pub published_by: Option<i32>,
+}
+
+/// Review state of a flagged name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlaggedNameStatus {
+    /// Published anyway, only logged for admins to look at
+    Warned,
+    /// Publishing is held until an admin approves the name
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl FlaggedNameStatus {
+    pub fn from_status_str(status: &str) -> Option<Self> {
+        match status.to_lowercase().as_str() {
+            "warned" => Some(Self::Warned),
+            "pending" => Some(Self::Pending),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Warned => "warned",
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+impl std::fmt::Display for FlaggedNameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FlaggedName {
+    pub fn status(&self) -> FlaggedNameStatus {
+        FlaggedNameStatus::from_status_str(&self.status).unwrap_or(FlaggedNameStatus::Pending)
+    }
+}
+
+// Response models
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FlaggedNameListResponse {
+    pub mode: String,
+    pub flagged: Vec<FlaggedName>,
+}
@@ -0,0 +1,94 @@
+use crate::schema::hooks;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Hook model - an `npm hook` endpoint notified about changes to a package, scope or the
+// packages of a user
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = hooks)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Hook {
+    pub id: i32,
+    pub user_id: i32,
+    /// package, scope or owner
+    pub hook_type: String,
+    pub name: String,
+    pub endpoint: String,
+    pub secret: String,
+    pub last_delivery: Option<NaiveDateTime>,
+    pub response_code: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = hooks)]
+pub struct NewHook {
+    pub user_id: i32,
+    pub hook_type: String,
+    pub name: String,
+    pub endpoint: String,
+    pub secret: String,
+}
+
+// Request/Response models, in the shape of the npm hooks API
+#[derive(Deserialize, Debug)]
+pub struct HookRequest {
+    #[serde(rename = "type")]
+    pub hook_type: String,
+    pub name: String,
+    pub endpoint: String,
+    pub secret: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HookUpdateRequest {
+    pub endpoint: String,
+    pub secret: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HookResponse {
+    pub id: String,
+    pub username: String,
+    pub name: String,
+    pub endpoint: String,
+    #[serde(rename = "type")]
+    pub hook_type: String,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+    pub deleted: bool,
+    pub delivered: bool,
+    pub last_delivery: Option<NaiveDateTime>,
+    pub response_code: i32,
+    pub status: String,
+}
+
+impl HookResponse {
+    /// The secret is never echoed back
+    pub fn from_hook(hook: Hook, username: &str, deleted: bool) -> Self {
+        Self {
+            id: hook.id.to_string(),
+            username: username.to_string(),
+            name: hook.name,
+            endpoint: hook.endpoint,
+            hook_type: hook.hook_type,
+            created: hook.created_at,
+            updated: hook.updated_at,
+            deleted,
+            delivered: hook.last_delivery.is_some(),
+            last_delivery: hook.last_delivery,
+            response_code: hook.response_code,
+            status: "active".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct HookListResponse {
+    pub objects: Vec<HookResponse>,
+    pub total: usize,
+    pub urls: Value,
+}
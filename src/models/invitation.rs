@@ -0,0 +1,59 @@
+use crate::schema::invitations;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Default lifetime of an invitation
+pub const DEFAULT_INVITATION_EXPIRY_DAYS: i64 = 7;
+
+// Invitation model - single-use token allowing registration when self-registration is disabled
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = invitations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Invitation {
+    pub id: i32,
+    pub token: String,
+    pub email: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub used_by: Option<i32>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = invitations)]
+pub struct NewInvitation {
+    pub token: String,
+    pub email: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+impl NewInvitation {
+    pub fn new(email: Option<String>, created_by: Option<i32>, expires_in_days: i64) -> Self {
+        let now = chrono::Utc::now().naive_utc();
+        Self {
+            token: uuid::Uuid::new_v4().simple().to_string(),
+            email,
+            created_by,
+            created_at: now,
+            expires_at: now + chrono::Duration::days(expires_in_days),
+        }
+    }
+}
+
+impl Invitation {
+    pub fn is_usable(&self) -> bool {
+        self.used_at.is_none() && self.expires_at > chrono::Utc::now().naive_utc()
+    }
+}
+
+// Request/Response models for API
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateInvitationRequest {
+    pub email: Option<String>,
+    pub expires_in_days: Option<i64>,
+}
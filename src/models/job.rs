@@ -0,0 +1,24 @@
+use chrono::NaiveDateTime;
+use rocket::serde::Serialize;
+use utoipa::ToSchema;
+
+/// A background job and how its runs went since startup
+#[derive(Serialize, Debug, Clone, Default, ToSchema)]
+pub struct JobStatus {
+    pub name: String,
+    pub description: String,
+    /// Cron expression, `every <duration>`, or `disabled`
+    pub schedule: String,
+    pub enabled: bool,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_started_at: Option<NaiveDateTime>,
+    pub last_finished_at: Option<NaiveDateTime>,
+    pub last_duration_ms: Option<u64>,
+    /// `succeeded`, `failed` or `skipped`
+    pub last_outcome: Option<String>,
+    /// Summary of the last run, or why it failed or was skipped
+    pub last_message: Option<String>,
+    pub next_run_at: Option<NaiveDateTime>,
+}
@@ -0,0 +1,58 @@
+use chrono::NaiveDateTime;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Maintenance mode as reported by the status endpoints
+#[derive(Serialize, Debug, Clone, Default, ToSchema)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    pub message: Option<String>,
+    /// Seconds clients are asked to wait before retrying a write
+    pub retry_after_secs: u64,
+    pub enabled_at: Option<NaiveDateTime>,
+    pub enabled_by: Option<String>,
+}
+
+// Request models
+#[derive(Deserialize, Debug, Default, ToSchema)]
+pub struct MaintenanceRequest {
+    pub message: Option<String>,
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Size of the database file before and after an optimization
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+pub struct DatabaseOptimizeReport {
+    pub vacuumed: bool,
+    pub reindexed: bool,
+    pub size_before_bytes: u64,
+    pub size_after_bytes: u64,
+    pub reclaimed_bytes: u64,
+    /// Unused pages before the optimization, which `VACUUM` returns to the file system
+    pub free_pages_before: u64,
+    pub duration_ms: u64,
+}
+
+/// Database optimization run during a maintenance window. `ANALYZE` always runs.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DatabaseOptimizeRequest {
+    /// Rebuild the database file to reclaim free pages, default true
+    #[serde(default = "default_vacuum")]
+    pub vacuum: bool,
+    /// Rebuild every index
+    #[serde(default)]
+    pub reindex: bool,
+}
+
+fn default_vacuum() -> bool {
+    true
+}
+
+impl Default for DatabaseOptimizeRequest {
+    fn default() -> Self {
+        Self {
+            vacuum: true,
+            reindex: false,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use chrono::NaiveDateTime;
+use rocket::serde::Serialize;
+use utoipa::ToSchema;
+
+/// A migration recorded as applied to the database
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct AppliedMigration {
+    pub version: String,
+    /// Directory name of the migration, unknown for migrations of a newer release
+    pub name: Option<String>,
+    pub run_on: NaiveDateTime,
+}
+
+/// Version of the database schema against the migrations this release ships
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct SchemaStatus {
+    /// Version of the last applied migration
+    pub version: Option<String>,
+    pub up_to_date: bool,
+    pub applied: Vec<AppliedMigration>,
+    /// Names of the migrations `clef migrate` would apply
+    pub pending: Vec<String>,
+}
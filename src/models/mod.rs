@@ -1,18 +1,72 @@
 // Re-export all models from their respective modules
+pub mod advisory;
+pub mod allowed_package;
+pub mod archive;
+pub mod audit;
 pub mod auth;
+pub mod bandwidth;
+pub mod blocked_name;
 pub mod cache;
+pub mod client_certificate;
+pub mod dashboard;
+pub mod downloads;
+pub mod event;
+pub mod flagged_name;
+pub mod hook;
+pub mod invitation;
+pub mod job;
+pub mod maintenance;
 pub mod metadata_cache;
+pub mod migration;
+pub mod notification;
 pub mod npm;
 pub mod organization;
 pub mod package;
 pub mod package_tag;
+pub mod pinned_package;
+pub mod quarantine;
+pub mod retention;
+pub mod scope_policy;
+pub mod search;
+pub mod service_account;
+pub mod signing;
+pub mod staged_publish;
+pub mod task;
+pub mod tombstone;
 pub mod user;
 
 // Re-export commonly used models
+pub use advisory::*;
+pub use allowed_package::*;
+pub use archive::*;
+pub use audit::*;
 pub use auth::*;
+pub use bandwidth::*;
+pub use blocked_name::*;
 pub use cache::*;
+pub use client_certificate::*;
+pub use dashboard::*;
+pub use downloads::*;
+pub use event::*;
+pub use flagged_name::*;
+pub use hook::*;
+pub use invitation::*;
+pub use job::*;
+pub use maintenance::*;
+pub use migration::*;
+pub use notification::*;
 pub use npm::*;
 pub use organization::*;
 pub use package::*;
 pub use package_tag::*;
+pub use pinned_package::*;
+pub use quarantine::*;
+pub use retention::*;
+pub use scope_policy::*;
+pub use search::*;
+pub use service_account::*;
+pub use signing::*;
+pub use staged_publish::*;
+pub use task::*;
+pub use tombstone::*;
 pub use user::*;
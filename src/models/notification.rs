@@ -0,0 +1,131 @@
+use crate::schema::{notification_subscriptions, notifications};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Events a subscription can ask to be notified about
+pub const NOTIFICATION_EVENTS: [&str; 3] = ["version", "deprecation", "advisory"];
+
+// Notification subscription - a user following a package or a scope on one channel
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = notification_subscriptions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NotificationSubscription {
+    pub id: i32,
+    pub user_id: i32,
+    /// Package name or `@scope`
+    pub name: String,
+    /// Comma separated events, see [`NOTIFICATION_EVENTS`]
+    pub events: String,
+    /// email, slack, discord or webhook
+    pub channel: String,
+    /// URL notifications are posted to, unused for email
+    pub endpoint: Option<String>,
+    /// immediate, daily or weekly
+    pub frequency: String,
+    pub last_sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = notification_subscriptions)]
+pub struct NewNotificationSubscription {
+    pub user_id: i32,
+    pub name: String,
+    pub events: String,
+    pub channel: String,
+    pub endpoint: Option<String>,
+    pub frequency: String,
+}
+
+// Notification - an event waiting to be sent to a subscription, alone or in a digest
+#[derive(Queryable, Selectable, Serialize, Debug, Clone)]
+#[diesel(table_name = notifications)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Notification {
+    #[serde(skip)]
+    pub id: i32,
+    #[serde(skip)]
+    pub subscription_id: i32,
+    pub event: String,
+    pub package_name: String,
+    pub version: Option<String>,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+    #[serde(skip)]
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = notifications)]
+pub struct NewNotification {
+    pub subscription_id: i32,
+    pub event: String,
+    pub package_name: String,
+    pub version: Option<String>,
+    pub message: String,
+}
+
+// Request/Response models
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct NotificationSubscriptionRequest {
+    /// Package name or `@scope`
+    pub name: String,
+    /// Defaults to every event
+    pub events: Option<Vec<String>>,
+    pub channel: String,
+    /// Slack, Discord or webhook URL
+    pub endpoint: Option<String>,
+    /// Defaults to immediate
+    pub frequency: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct NotificationSubscriptionResponse {
+    pub id: i32,
+    pub name: String,
+    pub events: Vec<String>,
+    pub channel: String,
+    pub endpoint: Option<String>,
+    pub frequency: String,
+    pub last_sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<NotificationSubscription> for NotificationSubscriptionResponse {
+    fn from(subscription: NotificationSubscription) -> Self {
+        Self {
+            id: subscription.id,
+            events: subscription.events().map(str::to_string).collect(),
+            name: subscription.name,
+            channel: subscription.channel,
+            endpoint: subscription.endpoint,
+            frequency: subscription.frequency,
+            last_sent_at: subscription.last_sent_at,
+            created_at: subscription.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct NotificationSubscriptionsResponse {
+    pub subscriptions: Vec<NotificationSubscriptionResponse>,
+}
+
+impl NotificationSubscription {
+    pub fn events(&self) -> impl Iterator<Item = &str> {
+        self.events.split(',').filter(|event| !event.is_empty())
+    }
+
+    /// Whether the subscription follows `package`, by name or by its scope
+    pub fn matches(&self, package: &str) -> bool {
+        match self.name.strip_prefix('@') {
+            Some(scope) if !self.name.contains('/') => package
+                .strip_prefix('@')
+                .and_then(|rest| rest.strip_prefix(scope))
+                .is_some_and(|rest| rest.starts_with('/')),
+            _ => self.name == package,
+        }
+    }
+}
@@ -48,6 +48,9 @@ pub struct NpmPublishResponse {
     pub ok: bool,
     pub id: String,
     pub rev: String,
+    /// Id of the staged publish, for `?staged=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub staged_id: Option<i32>,
 }
 
 // Security audit models
@@ -1,10 +1,12 @@
+use crate::models::package::StorageUsageResponse;
 use crate::schema::{organization_members, organizations};
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 // Organization model
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[diesel(table_name = organizations)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Organization {
@@ -35,7 +37,7 @@ pub struct UpdateOrganization {
 }
 
 // Organization member model
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[diesel(table_name = organization_members)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct OrganizationMember {
@@ -62,13 +64,13 @@ pub struct UpdateOrganizationMember {
 }
 
 // Combined models for complex queries
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct OrganizationWithMembers {
     pub organization: Organization,
     pub members: Vec<OrganizationMemberWithUser>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct OrganizationMemberWithUser {
     pub member: OrganizationMember,
     pub username: String,
@@ -76,26 +78,26 @@ pub struct OrganizationMemberWithUser {
 }
 
 // Request/Response models for API
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct CreateOrganizationRequest {
     pub name: String,
     pub display_name: Option<String>,
     pub description: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct UpdateOrganizationRequest {
     pub display_name: Option<String>,
     pub description: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct AddMemberRequest {
     pub username: String,
     pub role: String, // "owner", "admin", "member"
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct UpdateMemberRequest {
     pub role: String,
 }
@@ -178,6 +180,26 @@ impl NewOrganizationMember {
     }
 }
 
+/// Publishes of one member to the packages of an organization
+#[derive(Serialize, Debug, ToSchema)]
+pub struct MemberPublishActivity {
+    pub username: String,
+    pub role: String,
+    pub publishes: i64,
+    pub last_published_at: Option<NaiveDateTime>,
+}
+
+/// Storage, downloads and publish activity of an organization
+#[derive(Serialize, Debug, ToSchema)]
+pub struct OrganizationUsageResponse {
+    #[serde(flatten)]
+    pub storage: StorageUsageResponse,
+    pub downloads: i64,
+    pub downloads_last_30_days: i64,
+    /// Every member, most active publisher first
+    pub members: Vec<MemberPublishActivity>,
+}
+
 // Validation functions
 pub fn validate_organization_name(name: &str) -> Result<(), String> {
     if name.is_empty() {
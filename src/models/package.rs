@@ -1,7 +1,11 @@
-use crate::schema::{package_files, package_owners, package_versions, packages};
+use crate::schema::{
+    package_files, package_owners, package_versions, package_visibility_changes, packages,
+    tarball_entries,
+};
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// Parameters for creating a package version with metadata
 #[derive(Debug)]
@@ -14,12 +18,13 @@ pub struct PackageVersionMetadata {
     pub peer_dependencies: Option<String>,
     pub engines: Option<String>,
     pub shasum: Option<String>,
+    pub integrity: Option<String>,
     pub readme: Option<String>,
     pub created_at: Option<NaiveDateTime>,
 }
 
 // Package model - stores package-level metadata
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[diesel(table_name = packages)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct Package {
@@ -34,6 +39,7 @@ pub struct Package {
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub organization_id: Option<i32>,
+    pub visibility: String,
 }
 
 #[derive(Insertable, Debug)]
@@ -64,7 +70,7 @@ pub struct UpdatePackage {
 }
 
 // Package version model - stores version-specific metadata
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[diesel(table_name = package_versions)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct PackageVersion {
@@ -82,6 +88,17 @@ pub struct PackageVersion {
     pub readme: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    /// Subresource integrity (sha512) of the tarball, `dist.integrity`
+    pub integrity: Option<String>,
+    /// When the version was yanked. Yanked versions are left out of the package document
+    /// but can still be fetched by exact version.
+    pub yanked_at: Option<NaiveDateTime>,
+    pub yank_reason: Option<String>,
+    /// Registry key signature over `integrity`, served as `dist.signatures`
+    #[serde(skip)]
+    pub registry_key_id: Option<String>,
+    #[serde(skip)]
+    pub registry_signature: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -100,6 +117,7 @@ pub struct NewPackageVersion {
     pub readme: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub integrity: Option<String>,
 }
 
 #[derive(AsChangeset, Debug)]
@@ -117,7 +135,7 @@ pub struct UpdatePackageVersion {
 }
 
 // Package file model - stores file-specific metadata and cache info
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[diesel(table_name = package_files)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct PackageFile {
@@ -158,19 +176,19 @@ pub struct UpdatePackageFile {
 }
 
 // Combined models for complex queries
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct PackageWithVersions {
     pub package: Package,
     pub versions: Vec<PackageVersionWithFiles>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct PackageVersionWithFiles {
     pub version: PackageVersion,
     pub files: Vec<PackageFile>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone, ToSchema)]
 pub struct PopularPackage {
     pub name: String,
     pub total_downloads: i64,
@@ -178,8 +196,41 @@ pub struct PopularPackage {
     pub total_size_bytes: i64,
 }
 
+/// Storage taken by the packages of a user or organization
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, ToSchema)]
+pub struct StorageUsage {
+    pub packages: usize,
+    pub versions: usize,
+    pub bytes: u64,
+}
+
+impl StorageUsage {
+    /// Aggregates `(package id, version id, size)` rows of stored files
+    pub fn from_files(files: &[(i32, i32, i64)]) -> Self {
+        let packages: std::collections::HashSet<i32> = files.iter().map(|f| f.0).collect();
+        let versions: std::collections::HashSet<i32> = files.iter().map(|f| f.1).collect();
+
+        Self {
+            packages: packages.len(),
+            versions: versions.len(),
+            bytes: files.iter().map(|f| f.2.max(0) as u64).sum(),
+        }
+    }
+}
+
+/// Usage of a user or organization against its quota
+#[derive(Serialize, Debug, ToSchema)]
+pub struct StorageUsageResponse {
+    pub name: String,
+    #[serde(flatten)]
+    pub usage: StorageUsage,
+    /// Missing when there is no quota
+    pub quota_bytes: Option<u64>,
+    pub remaining_bytes: Option<u64>,
+}
+
 // Analytics and API response structs
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct PackageListResponse {
     pub packages: Vec<PackageWithVersions>,
     pub total_count: i64,
@@ -188,7 +239,7 @@ pub struct PackageListResponse {
     pub pagination: PaginationMetadata,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 pub struct PaginationMetadata {
     pub page: i64,
     pub limit: i64,
@@ -197,13 +248,106 @@ pub struct PaginationMetadata {
     pub has_prev: bool,
 }
 
-#[derive(Serialize, Debug)]
+impl PackageListResponse {
+    /// A page of `limit` packages out of `total_count`, with the size of the page's files
+    pub fn new(
+        packages: Vec<PackageWithVersions>,
+        total_count: i64,
+        page: i64,
+        limit: i64,
+    ) -> Self {
+        // Calculate total size from all files across all versions
+        let total_size_bytes = packages
+            .iter()
+            .flat_map(|pkg| &pkg.versions)
+            .flat_map(|ver| &ver.files)
+            .map(|file| file.size_bytes)
+            .sum::<i64>();
+
+        let total_pages = (total_count + limit - 1) / limit; // Ceiling division
+
+        Self {
+            packages,
+            total_count,
+            total_size_bytes,
+            total_size_mb: total_size_bytes as f64 / 1024.0 / 1024.0,
+            pagination: PaginationMetadata {
+                page,
+                limit,
+                total_pages,
+                has_next: page < total_pages,
+                has_prev: page > 1,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
 pub struct PackageVersionsResponse {
     pub package: Package,
     pub versions: Vec<PackageVersionWithFiles>,
     pub total_size_bytes: i64,
 }
 
+/// A README rendered to sanitized HTML
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageReadme {
+    pub name: String,
+    pub version: String,
+    pub html: String,
+}
+
+// Tarball entry model - a file inside a version's tarball, indexed on first listing
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = tarball_entries)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct TarballEntry {
+    #[serde(skip)]
+    pub id: i32,
+    #[serde(skip)]
+    pub package_version_id: i32,
+    /// Path relative to the package root
+    pub path: String,
+    pub size_bytes: i64,
+    pub content_type: String,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = tarball_entries)]
+pub struct NewTarballEntry {
+    pub package_version_id: i32,
+    pub path: String,
+    pub size_bytes: i64,
+    pub content_type: String,
+}
+
+/// Files of a published or cached version, sorted by path
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageFilesResponse {
+    pub name: String,
+    pub version: String,
+    pub files: Vec<TarballEntry>,
+    pub total_size_bytes: i64,
+}
+
+/// Unpacked size of a version and of everything installing it pulls in
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageSizeResponse {
+    pub name: String,
+    pub version: String,
+    pub unpacked_size_bytes: i64,
+    pub file_count: i64,
+    /// Unpacked size of the version and its dependencies, each dependency version counted once
+    pub install_size_bytes: i64,
+    pub install_file_count: i64,
+    pub dependency_count: i64,
+    /// Dependencies left out of the install size because they couldn't be resolved or
+    /// their size is unknown
+    pub unresolved: Vec<String>,
+    /// Whether the dependency tree was cut off at the size limit
+    pub truncated: bool,
+}
+
 // Package ownership models (unchanged)
 #[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = package_owners)]
@@ -225,6 +369,118 @@ pub struct NewPackageOwner {
     pub created_at: NaiveDateTime,
 }
 
+// Package visibility
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackageVisibility {
+    Public,
+    Private,
+}
+
+impl PackageVisibility {
+    pub fn from_visibility_str(visibility: &str) -> Option<Self> {
+        match visibility.to_lowercase().as_str() {
+            "public" => Some(Self::Public),
+            "private" => Some(Self::Private),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Private => "private",
+        }
+    }
+}
+
+impl std::fmt::Display for PackageVisibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Package {
+    pub fn is_private(&self) -> bool {
+        PackageVisibility::from_visibility_str(&self.visibility) == Some(PackageVisibility::Private)
+    }
+}
+
+// Audit record of a package switching between public and private
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = package_visibility_changes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PackageVisibilityChange {
+    pub id: i32,
+    pub package_id: i32,
+    pub package_name: String,
+    pub old_visibility: String,
+    pub new_visibility: String,
+    pub changed_by: Option<i32>,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = package_visibility_changes)]
+pub struct NewPackageVisibilityChange {
+    pub package_id: i32,
+    pub package_name: String,
+    pub old_visibility: String,
+    pub new_visibility: String,
+    pub changed_by: Option<i32>,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Debug, Default, ToSchema)]
+pub struct YankVersionRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct UpdateVisibilityRequest {
+    pub visibility: String, // "public", "private"
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PackageVisibilityResponse {
+    pub package: String,
+    pub visibility: String,
+    pub history: Vec<PackageVisibilityChange>,
+}
+
+/// A version that recently entered the registry
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct RecentVersion {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    /// `published` for versions published here, `proxied` for versions cached from upstream
+    pub source: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RecentVersionsResponse {
+    pub versions: Vec<RecentVersion>,
+}
+
+/// Exactly one of `to_user` and `to_organization` must be set
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct TransferPackageRequest {
+    pub to_user: Option<String>,
+    pub to_organization: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct TransferPackageResponse {
+    pub package: String,
+    pub organization: Option<String>,
+    /// Usernames of the package owners after the transfer
+    pub owners: Vec<String>,
+}
+
 // Implementation methods
 impl NewPackage {
     pub fn new(name: String, description: Option<String>, author_id: Option<i32>) -> Self {
@@ -282,6 +538,7 @@ impl NewPackageVersion {
             readme: None,
             created_at: now,
             updated_at: now,
+            integrity: None,
         }
     }
 
@@ -306,6 +563,7 @@ impl NewPackageVersion {
             readme: metadata.readme,
             created_at,
             updated_at: now,
+            integrity: metadata.integrity,
         }
     }
 }
@@ -344,3 +602,82 @@ impl NewPackageOwner {
         }
     }
 }
+
+// Validation functions
+pub fn validate_package_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Package name cannot be empty".to_string());
+    }
+
+    if name.len() > 214 {
+        return Err("Package name cannot be longer than 214 characters".to_string());
+    }
+
+    // npm names are `name` or `@scope/name`, and each part becomes a directory name
+    // - Can contain letters, numbers, hyphens, underscores, dots and tildes
+    // - Cannot start with a dot or underscore
+    let parts: Vec<&str> = match name.strip_prefix('@') {
+        Some(scoped) => match scoped.split_once('/') {
+            Some((scope, base)) => vec![scope, base],
+            None => return Err("Scoped package name must be @scope/name".to_string()),
+        },
+        None => vec![name],
+    };
+
+    for part in parts {
+        if part.is_empty() {
+            return Err("Package name parts cannot be empty".to_string());
+        }
+
+        if part.starts_with('.') || part.starts_with('_') {
+            return Err("Package name cannot start with a dot or underscore".to_string());
+        }
+
+        for char in part.chars() {
+            if !char.is_ascii_alphanumeric() && !matches!(char, '-' | '_' | '.' | '~') {
+                return Err(format!(
+                    "Package name cannot contain '{char}', only letters, numbers, hyphens, underscores, dots and tildes"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_package_name() {
+        for name in [
+            "lodash",
+            "JSONStream",
+            "@babel/core",
+            "@types/node",
+            "a.b-c_d~e",
+        ] {
+            assert!(validate_package_name(name).is_ok(), "{name}");
+        }
+
+        for name in [
+            "",
+            "a/b",
+            "a/../b",
+            "..",
+            ".hidden",
+            "_private",
+            "@scope",
+            "@scope/",
+            "@/name",
+            "@scope/a/b",
+            "@scope/..",
+            "with space",
+            "back\\slash",
+        ] {
+            assert!(validate_package_name(name).is_err(), "{name}");
+        }
+        assert!(validate_package_name(&"a".repeat(215)).is_err());
+    }
+}
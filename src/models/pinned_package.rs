@@ -0,0 +1,48 @@
+use crate::models::PrefetchFailure;
+use crate::schema::pinned_packages;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Pinned package model - a package that is kept cached and refreshed ahead of its TTL
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = pinned_packages)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PinnedPackage {
+    pub id: i32,
+    pub name: String,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = pinned_packages)]
+pub struct NewPinnedPackage {
+    pub name: String,
+    pub created_by: Option<i32>,
+}
+
+// Request/Response models
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct PinnedPackageRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PinnedPackageListResponse {
+    pub entries: Vec<PinnedPackage>,
+    /// Entries from the configuration, which can't be changed at runtime
+    pub configured: Vec<String>,
+    pub refresh_minutes: u64,
+}
+
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct PinnedRefreshReport {
+    pub packages: usize,
+    /// Packages whose metadata was fetched again because it would expire before the next run
+    pub metadata_refreshed: usize,
+    pub tarballs_fetched: usize,
+    pub failed: Vec<PrefetchFailure>,
+    pub duration_ms: u128,
+}
@@ -0,0 +1,87 @@
+use crate::schema::quarantined_packages;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Quarantined package model - an upstream package, or a major version of one, that is only
+// served once an admin approved it
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = quarantined_packages)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct QuarantinedPackage {
+    pub id: i32,
+    pub name: String,
+    /// Missing for the entry of the package itself
+    pub major: Option<i32>,
+    pub status: String,
+    pub requested_by: Option<i32>,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = quarantined_packages)]
+pub struct NewQuarantinedPackage {
+    pub name: String,
+    pub major: Option<i32>,
+    pub status: String,
+    pub requested_by: Option<i32>,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<NaiveDateTime>,
+}
+
+/// Review state of a quarantined package
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuarantineStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl QuarantineStatus {
+    pub fn from_status_str(status: &str) -> Option<Self> {
+        match status.to_lowercase().as_str() {
+            "pending" => Some(Self::Pending),
+            "approved" => Some(Self::Approved),
+            "rejected" => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+impl std::fmt::Display for QuarantineStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl QuarantinedPackage {
+    pub fn status(&self) -> QuarantineStatus {
+        QuarantineStatus::from_status_str(&self.status).unwrap_or(QuarantineStatus::Pending)
+    }
+
+    /// `name` or `name@major` for messages
+    pub fn label(&self) -> String {
+        match self.major {
+            Some(major) => format!("{}@{major}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// Response models
+#[derive(Serialize, Debug, ToSchema)]
+pub struct QuarantineListResponse {
+    pub mode: String,
+    pub entries: Vec<QuarantinedPackage>,
+}
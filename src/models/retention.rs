@@ -0,0 +1,77 @@
+use crate::schema::retention_policies;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Retention policy model - an organization rule for deleting old versions of its packages.
+// A version is deleted when it matches every rule that is set.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = retention_policies)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RetentionPolicy {
+    pub id: i32,
+    pub organization_id: i32,
+    pub name: String,
+    /// Keep this many of the newest matching versions of each package
+    pub keep_last: Option<i32>,
+    /// Only delete versions not published or downloaded for this many days
+    pub max_age_days: Option<i32>,
+    /// Only consider prerelease versions such as `2.0.0-beta.1`
+    pub prerelease_only: bool,
+    /// Report what would be deleted without deleting it
+    pub dry_run: bool,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[diesel(table_name = retention_policies)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewRetentionPolicy {
+    pub organization_id: i32,
+    pub name: String,
+    pub keep_last: Option<i32>,
+    pub max_age_days: Option<i32>,
+    pub prerelease_only: bool,
+    pub dry_run: bool,
+    pub created_by: Option<i32>,
+    pub updated_at: NaiveDateTime,
+}
+
+// Request/Response models
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct RetentionPolicyRequest {
+    pub keep_last: Option<i32>,
+    pub max_age_days: Option<i32>,
+    pub prerelease_only: Option<bool>,
+    /// New policies only report what they would delete until this is set to false
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct RetentionPolicyListResponse {
+    pub policies: Vec<RetentionPolicy>,
+    pub interval_hours: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct RetentionCandidate {
+    pub policy: String,
+    pub package: String,
+    pub version: String,
+    pub reason: String,
+    pub deleted: bool,
+}
+
+#[derive(Serialize, Debug, Default, ToSchema)]
+pub struct RetentionReport {
+    pub policies: usize,
+    pub packages: usize,
+    /// Versions the policies matched, deleted unless the policy or the run is a dry run
+    pub versions: Vec<RetentionCandidate>,
+    pub deleted: usize,
+    pub bytes_freed: u64,
+    pub duration_ms: u128,
+}
@@ -0,0 +1,89 @@
+use crate::schema::scope_policies;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Scope policy model - admin defined rules for every package in a scope (e.g. @internal)
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = scope_policies)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ScopePolicy {
+    pub id: i32,
+    pub scope: String,
+    pub publish_access: String,
+    /// Whether packages of this scope that aren't published locally may be fetched upstream
+    pub allow_upstream: bool,
+    /// Whether packages of this scope can be installed without a token
+    pub allow_anonymous: bool,
+    pub updated_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[diesel(table_name = scope_policies)]
+pub struct NewScopePolicy {
+    pub scope: String,
+    pub publish_access: String,
+    pub allow_upstream: bool,
+    pub allow_anonymous: bool,
+    pub updated_by: Option<i32>,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Who may publish packages in a scope
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopePublishAccess {
+    /// Members of the scope's organization (the default without a policy)
+    Members,
+    /// Owners and admins of the scope's organization
+    Admins,
+    /// Registry administrators only
+    RegistryAdmins,
+}
+
+impl ScopePublishAccess {
+    pub fn from_access_str(access: &str) -> Option<Self> {
+        match access.to_lowercase().as_str() {
+            "members" => Some(Self::Members),
+            "admins" => Some(Self::Admins),
+            "registry-admins" => Some(Self::RegistryAdmins),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Members => "members",
+            Self::Admins => "admins",
+            Self::RegistryAdmins => "registry-admins",
+        }
+    }
+}
+
+impl std::fmt::Display for ScopePublishAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl ScopePolicy {
+    pub fn publish_access(&self) -> ScopePublishAccess {
+        ScopePublishAccess::from_access_str(&self.publish_access)
+            .unwrap_or(ScopePublishAccess::Members)
+    }
+}
+
+// Request/Response models
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ScopePolicyRequest {
+    pub publish_access: Option<String>,
+    pub allow_upstream: Option<bool>,
+    pub allow_anonymous: Option<bool>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ScopePolicyListResponse {
+    pub policies: Vec<ScopePolicy>,
+}
@@ -0,0 +1,64 @@
+use rocket::serde::Serialize;
+
+/// Response of the npm search endpoint, `npm search` and `/-/v1/search`
+#[derive(Serialize, Debug)]
+pub struct SearchResponse {
+    pub objects: Vec<SearchObject>,
+    pub total: usize,
+    pub time: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchObject {
+    pub package: SearchPackage,
+    pub score: SearchScore,
+    /// Relevance to the search text combined with the final score, results are sorted by it
+    pub search_score: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchPackage {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub keywords: Vec<String>,
+    pub date: String,
+    pub links: SearchLinks,
+    /// The first owner of the package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<SearchUser>,
+    pub maintainers: Vec<SearchUser>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SearchUser {
+    pub username: String,
+    pub email: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct SearchLinks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+}
+
+/// npms style score, every value between 0 and 1
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct SearchScore {
+    #[serde(rename = "final")]
+    pub final_score: f64,
+    pub detail: SearchScoreDetail,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct SearchScoreDetail {
+    pub quality: f64,
+    pub popularity: f64,
+    pub maintenance: f64,
+}
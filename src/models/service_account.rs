@@ -0,0 +1,55 @@
+use crate::schema::service_accounts;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Service account - a non-interactive user owned by an organization, for CI pipelines.
+// It can't log in and only holds publish tokens.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = service_accounts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ServiceAccount {
+    pub id: i32,
+    pub user_id: i32,
+    pub organization_id: i32,
+    pub description: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = service_accounts)]
+pub struct NewServiceAccount {
+    pub user_id: i32,
+    pub organization_id: i32,
+    pub description: Option<String>,
+}
+
+// Request/Response models
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateServiceAccountRequest {
+    /// Username of the account, unique across the registry
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ServiceAccountResponse {
+    pub name: String,
+    pub organization: String,
+    pub description: Option<String>,
+    pub is_active: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ServiceAccountsResponse {
+    pub service_accounts: Vec<ServiceAccountResponse>,
+}
+
+/// A publish token issued to a service account, only shown once
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ServiceAccountTokenResponse {
+    pub name: String,
+    pub token: String,
+}
@@ -0,0 +1,176 @@
+use crate::schema::{package_attestations, package_signatures, registry_keys, signing_keys};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Algorithm used for organization signing keys
+pub const SIGNING_KEY_ALGORITHM: &str = "ed25519";
+
+/// Key type and scheme of registry keys, the only one npm clients verify
+pub const REGISTRY_KEY_TYPE: &str = "ecdsa-sha2-nistp256";
+
+// Signing key model - an organization keypair used to sign published dists
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[diesel(table_name = signing_keys)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct SigningKey {
+    pub id: i32,
+    pub organization_id: i32,
+    pub key_id: String,
+    pub algorithm: String,
+    /// Base64 encoded SubjectPublicKeyInfo (DER)
+    pub public_key: String,
+    /// Base64 encoded private key seed, never returned by the API
+    #[serde(skip_serializing, default)]
+    pub private_key: String,
+    pub is_active: bool,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = signing_keys)]
+pub struct NewSigningKey {
+    pub organization_id: i32,
+    pub key_id: String,
+    pub algorithm: String,
+    pub public_key: String,
+    pub private_key: String,
+    pub is_active: bool,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+// Package signature model - signature over a published version's integrity
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = package_signatures)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PackageSignature {
+    pub id: i32,
+    pub package_version_id: i32,
+    pub signing_key_id: i32,
+    pub integrity: String,
+    pub signature: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = package_signatures)]
+pub struct NewPackageSignature {
+    pub package_version_id: i32,
+    pub signing_key_id: i32,
+    pub integrity: String,
+    pub signature: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Registry key model - a key `npm audit signatures` verifies `dist.signatures` with. Local keys
+// sign packages published to clef, upstream keys are cached from the upstream registry.
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = registry_keys)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RegistryKey {
+    pub id: i32,
+    pub key_id: String,
+    /// `local` or `upstream`
+    pub source: String,
+    pub keytype: String,
+    pub scheme: String,
+    /// Base64 encoded SubjectPublicKeyInfo (DER)
+    pub public_key: String,
+    /// Base64 encoded PKCS#8 private key of local keys, never returned by the API
+    #[serde(skip_serializing, default)]
+    pub private_key: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = registry_keys)]
+pub struct NewRegistryKey {
+    pub key_id: String,
+    pub source: String,
+    pub keytype: String,
+    pub scheme: String,
+    pub public_key: String,
+    pub private_key: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A key as served by `/-/npm/v1/keys`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NpmRegistryKey {
+    pub expires: Option<String>,
+    pub keyid: String,
+    pub keytype: String,
+    pub scheme: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NpmRegistryKeysResponse {
+    pub keys: Vec<NpmRegistryKey>,
+}
+
+// Package attestation model - a Sigstore bundle from `npm publish --provenance`
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = package_attestations)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PackageAttestation {
+    pub id: i32,
+    pub package_version_id: i32,
+    pub predicate_type: String,
+    pub bundle: String, // Sigstore bundle JSON
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = package_attestations)]
+pub struct NewPackageAttestation {
+    pub package_version_id: i32,
+    pub predicate_type: String,
+    pub bundle: String,
+}
+
+/// An attestation as served by `/-/npm/v1/attestations/<name>@<version>`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NpmAttestation {
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String,
+    pub bundle: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NpmAttestationsResponse {
+    pub attestations: Vec<NpmAttestation>,
+}
+
+// Request/Response models for API
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SigningKeyListResponse {
+    pub organization: String,
+    pub keys: Vec<SigningKey>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SignatureCheck {
+    pub keyid: String,
+    pub sig: String,
+    pub key_active: bool,
+    pub valid: bool,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SignatureVerificationResponse {
+    pub package: String,
+    pub version: String,
+    /// Integrity of the tarball currently stored by the registry
+    pub integrity: Option<String>,
+    pub verified: bool,
+    pub signatures: Vec<SignatureCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -0,0 +1,60 @@
+use crate::schema::staged_publishes;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::Serialize;
+use utoipa::ToSchema;
+
+// Staged publish - a validated `npm publish` kept out of the registry until it's promoted
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = staged_publishes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct StagedPublish {
+    pub id: i32,
+    pub package_name: String,
+    pub version: String,
+    pub publisher_id: i32,
+    /// The publish request as it was received, replayed on promotion
+    pub request_path: String,
+    /// Size of the tarball
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = staged_publishes)]
+pub struct NewStagedPublish {
+    pub package_name: String,
+    pub version: String,
+    pub publisher_id: i32,
+    pub request_path: String,
+    pub size_bytes: i64,
+}
+
+// Request/Response models
+#[derive(Serialize, Debug, ToSchema)]
+pub struct StagedPublishResponse {
+    pub id: i32,
+    pub package: String,
+    pub version: String,
+    pub publisher: String,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl StagedPublishResponse {
+    pub fn new(staged: StagedPublish, publisher: String) -> Self {
+        Self {
+            id: staged.id,
+            package: staged.package_name,
+            version: staged.version,
+            publisher,
+            size_bytes: staged.size_bytes,
+            created_at: staged.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct StagedPublishesResponse {
+    pub staged: Vec<StagedPublishResponse>,
+}
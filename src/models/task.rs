@@ -0,0 +1,139 @@
+use crate::schema::tasks;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+// Task - a long-running operation queued in the database and run by the task worker
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = tasks)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Task {
+    pub id: i32,
+    pub kind: String,
+    pub status: String,
+    /// JSON the task runs with
+    pub input: Option<String>,
+    /// JSON report of a finished task
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = tasks)]
+pub struct NewTask {
+    pub kind: String,
+    pub status: String,
+    pub input: Option<String>,
+    pub created_by: Option<i32>,
+}
+
+/// Operations run as tasks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskKind {
+    ReprocessCache,
+    Prefetch,
+    Export,
+    OptimizeDatabase,
+}
+
+impl TaskKind {
+    pub fn from_kind_str(kind: &str) -> Option<Self> {
+        match kind {
+            "reprocess_cache" => Some(Self::ReprocessCache),
+            "prefetch" => Some(Self::Prefetch),
+            "export" => Some(Self::Export),
+            "optimize_database" => Some(Self::OptimizeDatabase),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ReprocessCache => "reprocess_cache",
+            Self::Prefetch => "prefetch",
+            Self::Export => "export",
+            Self::OptimizeDatabase => "optimize_database",
+        }
+    }
+}
+
+/// Progress of a task
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl TaskStatus {
+    pub fn from_status_str(status: &str) -> Option<Self> {
+        match status {
+            "queued" => Some(Self::Queued),
+            "running" => Some(Self::Running),
+            "succeeded" => Some(Self::Succeeded),
+            "failed" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl std::fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Task {
+    pub fn status(&self) -> TaskStatus {
+        TaskStatus::from_status_str(&self.status).unwrap_or(TaskStatus::Queued)
+    }
+}
+
+// Response models
+#[derive(Serialize, Debug, ToSchema)]
+pub struct TaskResponse {
+    pub id: i32,
+    /// `reprocess_cache`, `prefetch` or `export`
+    pub kind: String,
+    /// `queued`, `running`, `succeeded` or `failed`
+    pub status: String,
+    /// Report of a succeeded task, the same the operation used to respond with
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl From<Task> for TaskResponse {
+    fn from(task: Task) -> Self {
+        Self {
+            id: task.id,
+            kind: task.kind,
+            status: task.status,
+            result: task
+                .result
+                .and_then(|result| serde_json::from_str(&result).ok()),
+            error: task.error,
+            created_at: task.created_at,
+            started_at: task.started_at,
+            finished_at: task.finished_at,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::schema::version_tombstones;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Version tombstone model - a deleted version whose version string can't be published again
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = version_tombstones)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct VersionTombstone {
+    pub id: i32,
+    pub package_name: String,
+    pub version: String,
+    /// Why the version was deleted, e.g. unpublished or removed by a retention policy
+    pub reason: String,
+    pub deleted_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = version_tombstones)]
+pub struct NewVersionTombstone {
+    pub package_name: String,
+    pub version: String,
+    pub reason: String,
+    pub deleted_by: Option<i32>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UnpublishResponse {
+    pub package: String,
+    pub version: String,
+    pub bytes_freed: u64,
+    /// Whether a tombstone keeps the version from being published again
+    pub tombstone: bool,
+}
@@ -1,10 +1,13 @@
+use crate::models::package::Package;
 use crate::schema::{user_tokens, users};
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use rocket::serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
 
 // User authentication models
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[diesel(table_name = users)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct User {
@@ -16,6 +19,14 @@ pub struct User {
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub is_active: bool,
+    pub role: String,
+    pub email_verified: bool,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    /// JSON object of link labels to URLs
+    pub links: Option<String>,
+    /// Auth provider plugin that created the account, `None` for local accounts
+    pub auth_plugin: Option<String>,
 }
 
 #[derive(Insertable, Debug)]
@@ -27,6 +38,9 @@ pub struct NewUser {
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub is_active: bool,
+    pub role: String,
+    pub email_verified: bool,
+    pub auth_plugin: Option<String>,
 }
 
 #[derive(AsChangeset, Debug)]
@@ -36,6 +50,100 @@ pub struct UpdateUser {
     pub password_hash: Option<String>,
     pub updated_at: Option<NaiveDateTime>,
     pub is_active: Option<bool>,
+    pub role: Option<String>,
+    pub email_verified: Option<bool>,
+}
+
+/// Profile fields a user edits themselves. `None` clears a field.
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = users, treat_none_as_null = true)]
+pub struct UpdateUserProfile {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub links: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Public profile of a user, without the email address
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserProfile {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    /// Link labels to URLs, e.g. `github` or `homepage`
+    pub links: BTreeMap<String, String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<&User> for UserProfile {
+    fn from(user: &User) -> Self {
+        Self {
+            username: user.username.clone(),
+            display_name: user.display_name.clone(),
+            avatar_url: user.avatar_url.clone(),
+            links: user
+                .links
+                .as_deref()
+                .and_then(|links| serde_json::from_str(links).ok())
+                .unwrap_or_default(),
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// Replaces the profile of the current user, fields left out are cleared
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct UpdateProfileRequest {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub links: BTreeMap<String, String>,
+}
+
+/// A package a user owns, with their permission on it
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserPackage {
+    #[serde(flatten)]
+    pub package: Package,
+    pub permission_level: String,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserPackagesResponse {
+    pub username: String,
+    pub packages: Vec<UserPackage>,
+}
+
+// Registry-wide user roles
+#[derive(Debug, PartialEq)]
+pub enum UserRole {
+    Admin,
+    User,
+    /// Non-interactive account owned by an organization, see [`ServiceAccount`]
+    ///
+    /// [`ServiceAccount`]: crate::models::ServiceAccount
+    Service,
+}
+
+impl UserRole {
+    pub fn from_role_str(role: &str) -> Option<Self> {
+        match role.to_lowercase().as_str() {
+            "admin" => Some(Self::Admin),
+            "user" => Some(Self::User),
+            "service" => Some(Self::Service),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for UserRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Admin => write!(f, "admin"),
+            Self::User => write!(f, "user"),
+            Self::Service => write!(f, "service"),
+        }
+    }
 }
 
 #[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
@@ -44,22 +152,33 @@ pub struct UpdateUser {
 pub struct UserToken {
     pub id: i32,
     pub user_id: i32,
+    /// Digest of the token, the token itself isn't stored
     pub token: String,
     pub token_type: String,
     pub created_at: NaiveDateTime,
     pub expires_at: Option<NaiveDateTime>,
     pub is_active: bool,
+    pub last_used_at: Option<NaiveDateTime>,
+    /// User agent and IP of the most recent client that used the token
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// The first characters of the token, to tell tokens apart
+    pub token_preview: String,
 }
 
 #[derive(Insertable, Debug)]
 #[diesel(table_name = user_tokens)]
 pub struct NewUserToken {
     pub user_id: i32,
+    /// The token until it's replaced by its digest on insert
     pub token: String,
     pub token_type: String,
     pub created_at: NaiveDateTime,
     pub expires_at: Option<NaiveDateTime>,
     pub is_active: bool,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub token_preview: String,
 }
 
 #[derive(AsChangeset, Debug)]
@@ -85,6 +204,21 @@ impl NewUser {
             created_at: now,
             updated_at: now,
             is_active: true,
+            role: UserRole::User.to_string(),
+            email_verified: false,
+            auth_plugin: None,
+        })
+    }
+
+    /// User row of a service account. Its password is random and never shown, and its
+    /// email address can't receive mail.
+    pub fn new_service_account(username: String) -> Result<Self, bcrypt::BcryptError> {
+        let email = format!("{username}@service-accounts.invalid");
+        let password = uuid::Uuid::new_v4().to_string();
+
+        Ok(Self {
+            role: UserRole::Service.to_string(),
+            ..Self::new(username, email, password)?
         })
     }
 }
@@ -93,6 +227,14 @@ impl User {
     pub fn verify_password(&self, password: &str) -> Result<bool, bcrypt::BcryptError> {
         bcrypt::verify(password, &self.password_hash)
     }
+
+    pub fn is_admin(&self) -> bool {
+        UserRole::from_role_str(&self.role) == Some(UserRole::Admin)
+    }
+
+    pub fn is_service_account(&self) -> bool {
+        UserRole::from_role_str(&self.role) == Some(UserRole::Service)
+    }
 }
 
 impl NewUserToken {
@@ -103,11 +245,14 @@ impl NewUserToken {
 
         Self {
             user_id,
-            token,
             token_type: "auth".to_string(),
             created_at: now,
             expires_at: Some(expires_at),
             is_active: true,
+            user_agent: None,
+            ip_address: None,
+            token_preview: Self::preview(&token),
+            token,
         }
     }
 
@@ -117,11 +262,80 @@ impl NewUserToken {
 
         Self {
             user_id,
-            token,
             token_type: "publish".to_string(),
             created_at: now,
             expires_at: None, // Publish tokens don't expire
             is_active: true,
+            user_agent: None,
+            ip_address: None,
+            token_preview: Self::preview(&token),
+            token,
+        }
+    }
+
+    pub fn preview(token: &str) -> String {
+        token.chars().take(8).collect()
+    }
+}
+
+/// An active token as shown to its owner. The token value itself is never returned.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct TokenSession {
+    pub id: i32,
+    pub token_type: String,
+    pub token_preview: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// Whether this is the token that made the request
+    pub current: bool,
+}
+
+impl TokenSession {
+    pub fn from_token(token: UserToken, current_token_id: Option<i32>) -> Self {
+        Self {
+            current: current_token_id == Some(token.id),
+            id: token.id,
+            token_type: token.token_type,
+            token_preview: format!("{}...", token.token_preview),
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            user_agent: token.user_agent,
+            ip_address: token.ip_address,
         }
     }
 }
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct SessionListResponse {
+    pub sessions: Vec<TokenSession>,
+}
+
+// Admin user management models
+#[derive(Serialize, Debug, ToSchema)]
+pub struct UserListResponse {
+    pub users: Vec<User>,
+    pub total_count: usize,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct UpdateUserRoleRequest {
+    pub role: String, // "admin", "user"
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ResetPasswordRequest {
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ResetPasswordResponse {
+    pub ok: bool,
+    pub username: String,
+    /// Generated temporary password, only set when none was supplied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
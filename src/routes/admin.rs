@@ -0,0 +1,874 @@
+use crate::config::ConfigSetting;
+use crate::error::{ApiError, ErrorBody};
+use crate::models::auth::AdminUser;
+use crate::models::{
+    Advisory, AdvisoryStatusResponse, AdvisorySyncReport, AllowedPackage, AllowedPackageRequest,
+    AllowlistResponse, BandwidthReport, BlockedName, BlockedNameListResponse, BlockedNameRequest,
+    ClientCertificate, ClientCertificateRequest, CreateInvitationRequest,
+    DEFAULT_INVITATION_EXPIRY_DAYS, DatabaseOptimizeRequest, FlaggedName, FlaggedNameListResponse,
+    FlaggedNameStatus, InternalAdvisoryRequest, Invitation, JobStatus, MaintenanceRequest,
+    MaintenanceStatus, NewInvitation, PackageArchive, PackageImportResponse, PinnedPackage,
+    PinnedPackageListResponse, PinnedPackageRequest, PinnedRefreshReport, QuarantineListResponse,
+    QuarantineStatus, QuarantinedPackage, ResetPasswordRequest, ResetPasswordResponse,
+    SchemaStatus, ScopePolicy, ScopePolicyListResponse, ScopePolicyRequest, TaskResponse,
+    UpdateUserRoleRequest, User, UserListResponse, UserRole,
+};
+use crate::services::{
+    AdvisoryService, AllowlistService, ArchiveService, AuthService, BandwidthService,
+    ClientCertificateService, MaintenanceMode, NameBlocklistService, PinnedPackageService,
+    QuarantineService, ScopePolicyService, TaskService, TyposquatService,
+};
+use crate::state::AppState;
+use log::{debug, error, info};
+use rocket::data::ToByteUnit;
+use rocket::response::status::Accepted;
+use rocket::serde::json::{Json, Value};
+use rocket::{Data, State, delete, get, post, put};
+
+/// Queue an export of a package with all versions, dist-tags and tarballs as a single
+/// archive, downloaded from the task once it succeeded
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 202, body = TaskResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/export?<package>")]
+pub async fn export_package(
+    package: &str,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Accepted<Json<TaskResponse>>, ApiError> {
+    let task = TaskService::enqueue_export(package, &admin.0, state)?;
+    Ok(Accepted(Json(task)))
+}
+
+/// Import a package archive produced by the export endpoint
+#[utoipa::path(
+    tag = "admin",
+    request_body = PackageArchive,
+    responses((status = 200, body = PackageImportResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/import", data = "<data>")]
+pub async fn import_package(
+    data: Data<'_>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageImportResponse>, ApiError> {
+    // Archives carry every tarball of a package, so allow much larger bodies than JSON requests
+    let limit = 512_u32.mebibytes();
+    let body = data.open(limit).into_bytes().await.map_err(|e| {
+        error!("Failed to read request body: {e}");
+        ApiError::BadRequest(format!("Failed to read request body: {e}"))
+    })?;
+    if !body.is_complete() {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "The package archive exceeds the maximum size of {limit}"
+        )));
+    }
+    let body = body.into_inner();
+
+    debug!("Read {} bytes of archive data", body.len());
+
+    let archive: PackageArchive = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid package archive: {e}")))?;
+
+    let response = ArchiveService::import_package(archive, admin.0.user_id, state).await?;
+    Ok(Json(response))
+}
+
+/// Effective runtime configuration with secrets redacted and the source of each value
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Vec<ConfigSetting>)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/config")]
+pub async fn get_config(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Vec<ConfigSetting>>, ApiError> {
+    Ok(Json(state.config.effective_settings()))
+}
+
+/// List all users, including disabled ones
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = UserListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/users")]
+pub async fn list_users(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<UserListResponse>, ApiError> {
+    let users = AuthService::list_users(&state.database)?;
+    let total_count = users.len();
+
+    Ok(Json(UserListResponse { users, total_count }))
+}
+
+/// Disable a user and revoke their tokens
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = User)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/users/<username>/disable")]
+pub async fn disable_user(
+    username: &str,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<User>, ApiError> {
+    if admin.0.username == username {
+        return Err(ApiError::BadRequest(
+            "You cannot disable your own account".to_string(),
+        ));
+    }
+
+    let user = AuthService::set_user_active(&state.database, username, false)?;
+    info!("Admin {} disabled user {username}", admin.0.username);
+
+    Ok(Json(user))
+}
+
+/// Re-enable a disabled user
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = User)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/users/<username>/enable")]
+pub async fn enable_user(
+    username: &str,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<User>, ApiError> {
+    let user = AuthService::set_user_active(&state.database, username, true)?;
+    info!("Admin {} enabled user {username}", admin.0.username);
+
+    Ok(Json(user))
+}
+
+/// Change a user's role
+#[utoipa::path(
+    tag = "admin",
+    request_body = UpdateUserRoleRequest,
+    responses((status = 200, body = User)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/admin/users/<username>/role", data = "<request>")]
+pub async fn update_user_role(
+    username: &str,
+    request: Json<UpdateUserRoleRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<User>, ApiError> {
+    let role = UserRole::from_role_str(&request.role)
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid role '{}'", request.role)))?;
+
+    if admin.0.username == username && role != UserRole::Admin {
+        return Err(ApiError::BadRequest(
+            "You cannot remove your own admin role".to_string(),
+        ));
+    }
+
+    let user = AuthService::set_user_role(&state.database, username, role)?;
+    info!(
+        "Admin {} set role of user {username} to {}",
+        admin.0.username, user.role
+    );
+
+    Ok(Json(user))
+}
+
+/// Register a client certificate of a user by its SHA-256 fingerprint, or by its subject or
+/// one of its SANs. Only admins can, a certificate identity belongs to a single user and
+/// nobody should be able to claim another's first.
+#[utoipa::path(
+    tag = "admin",
+    request_body = ClientCertificateRequest,
+    responses((status = 200, body = ClientCertificate)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/users/<username>/certificates", data = "<request>")]
+pub async fn add_user_certificate(
+    username: &str,
+    request: Json<ClientCertificateRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<ClientCertificate>, ApiError> {
+    let user = AuthService::get_user_by_username(&state.database, username)?
+        .ok_or_else(|| ApiError::NotFound(format!("User '{username}' not found")))?;
+
+    let certificate =
+        ClientCertificateService::add(&state.database, user.id, request.into_inner())?;
+    info!(
+        "Admin {} registered client certificate {} for user {username}",
+        admin.0.username, certificate.id
+    );
+
+    Ok(Json(certificate))
+}
+
+/// Delete a user
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/users/<username>")]
+pub async fn delete_user(
+    username: &str,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    if admin.0.username == username {
+        return Err(ApiError::BadRequest(
+            "You cannot delete your own account".to_string(),
+        ));
+    }
+
+    AuthService::delete_user(&state.database, username)?;
+    info!("Admin {} deleted user {username}", admin.0.username);
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Reset a user's password. A temporary password is generated when none is supplied.
+#[utoipa::path(
+    tag = "admin",
+    request_body = Option<ResetPasswordRequest>,
+    responses((status = 200, body = ResetPasswordResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/users/<username>/reset-password", data = "<request>")]
+pub async fn reset_user_password(
+    username: &str,
+    request: Option<Json<ResetPasswordRequest>>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<ResetPasswordResponse>, ApiError> {
+    let requested_password = request.and_then(|r| r.into_inner().password);
+
+    let (password, generated) = match requested_password {
+        Some(password) if password.is_empty() => {
+            return Err(ApiError::BadRequest("Password cannot be empty".to_string()));
+        }
+        Some(password) => (password, false),
+        None => (uuid::Uuid::new_v4().simple().to_string(), true),
+    };
+
+    AuthService::reset_password(&state.database, username, &password)?;
+    info!(
+        "Admin {} reset password of user {username}",
+        admin.0.username
+    );
+
+    Ok(Json(ResetPasswordResponse {
+        ok: true,
+        username: username.to_string(),
+        password: generated.then_some(password),
+    }))
+}
+
+/// Create an invitation token for registering a new account
+#[utoipa::path(
+    tag = "admin",
+    request_body = CreateInvitationRequest,
+    responses((status = 200, body = Invitation)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/invitations", data = "<request>")]
+pub async fn create_invitation(
+    request: Json<CreateInvitationRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Invitation>, ApiError> {
+    let expires_in_days = request
+        .expires_in_days
+        .unwrap_or(DEFAULT_INVITATION_EXPIRY_DAYS);
+
+    if expires_in_days <= 0 {
+        return Err(ApiError::BadRequest(
+            "expires_in_days must be positive".to_string(),
+        ));
+    }
+
+    let new_invitation = NewInvitation::new(
+        request.email.clone(),
+        Some(admin.0.user_id),
+        expires_in_days,
+    );
+
+    let invitation = state
+        .database
+        .create_invitation(&new_invitation)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    info!(
+        "Admin {} created invitation {}",
+        admin.0.username, invitation.id
+    );
+
+    Ok(Json(invitation))
+}
+
+/// List all invitations
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Vec<Invitation>)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/invitations")]
+pub async fn list_invitations(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Vec<Invitation>>, ApiError> {
+    let invitations = state
+        .database
+        .list_invitations()
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    Ok(Json(invitations))
+}
+
+/// Revoke an invitation
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/invitations/<id>")]
+pub async fn delete_invitation(
+    id: i32,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let deleted = state
+        .database
+        .delete_invitation(id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    if deleted == 0 {
+        return Err(ApiError::NotFound(format!("Invitation {id} not found")));
+    }
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// List all scope policies
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = ScopePolicyListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/scope-policies")]
+pub async fn list_scope_policies(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<ScopePolicyListResponse>, ApiError> {
+    let policies = state
+        .database
+        .list_scope_policies()
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    Ok(Json(ScopePolicyListResponse { policies }))
+}
+
+/// Create or update the policy of a scope (publish access, upstream lookups, anonymous installs)
+#[utoipa::path(
+    tag = "admin",
+    request_body = ScopePolicyRequest,
+    responses((status = 200, body = ScopePolicy)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/admin/scope-policies/<scope>", data = "<request>")]
+pub async fn set_scope_policy(
+    scope: &str,
+    request: Json<ScopePolicyRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<ScopePolicy>, ApiError> {
+    let policy = ScopePolicyService::set_policy(scope, request.into_inner(), &admin.0, state)?;
+    Ok(Json(policy))
+}
+
+/// Remove the policy of a scope, restoring the default behavior
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/scope-policies/<scope>")]
+pub async fn delete_scope_policy(
+    scope: &str,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    ScopePolicyService::delete_policy(scope, state)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// List the package name blocklist, including configured and reserved names
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = BlockedNameListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/blocklist")]
+pub async fn list_blocked_names(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<BlockedNameListResponse>, ApiError> {
+    Ok(Json(NameBlocklistService::list(state)?))
+}
+
+/// Block a package name or name pattern from being published
+#[utoipa::path(
+    tag = "admin",
+    request_body = BlockedNameRequest,
+    responses((status = 200, body = BlockedName)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/blocklist", data = "<request>")]
+pub async fn add_blocked_name(
+    request: Json<BlockedNameRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<BlockedName>, ApiError> {
+    let entry = NameBlocklistService::add_entry(request.into_inner(), &admin.0, state)?;
+    Ok(Json(entry))
+}
+
+/// Remove a blocklist entry
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/blocklist/<id>")]
+pub async fn delete_blocked_name(
+    id: i32,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    NameBlocklistService::delete_entry(id, state)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// List the upstream allowlist and whether proxying is limited to it
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = AllowlistResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/allowlist")]
+pub async fn list_allowed_packages(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<AllowlistResponse>, ApiError> {
+    Ok(Json(AllowlistService::list(state)?))
+}
+
+/// Allow a package name or name pattern to be proxied from upstream
+#[utoipa::path(
+    tag = "admin",
+    request_body = AllowedPackageRequest,
+    responses((status = 200, body = AllowedPackage)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/allowlist", data = "<request>")]
+pub async fn add_allowed_package(
+    request: Json<AllowedPackageRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<AllowedPackage>, ApiError> {
+    let entry = AllowlistService::add_entry(request.into_inner(), &admin.0, state)?;
+    Ok(Json(entry))
+}
+
+/// Remove an allowlist entry
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/allowlist/<id>")]
+pub async fn delete_allowed_package(
+    id: i32,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    AllowlistService::delete_entry(id, state)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// List pinned packages, including configured ones
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = PinnedPackageListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/pinned-packages")]
+pub async fn list_pinned_packages(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<PinnedPackageListResponse>, ApiError> {
+    Ok(Json(PinnedPackageService::list(state)?))
+}
+
+/// Pin a package so it is always kept cached and never evicted
+#[utoipa::path(
+    tag = "admin",
+    request_body = PinnedPackageRequest,
+    responses((status = 200, body = PinnedPackage)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/pinned-packages", data = "<request>")]
+pub async fn add_pinned_package(
+    request: Json<PinnedPackageRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<PinnedPackage>, ApiError> {
+    let entry = PinnedPackageService::add(request.into_inner(), &admin.0, state)?;
+    Ok(Json(entry))
+}
+
+/// Unpin a package
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/pinned-packages/<id>")]
+pub async fn delete_pinned_package(
+    id: i32,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    PinnedPackageService::delete(id, state)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Refresh the metadata and dist-tag tarballs of all pinned packages now
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = PinnedRefreshReport)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/pinned-packages/refresh")]
+pub async fn refresh_pinned_packages(
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<PinnedRefreshReport>, ApiError> {
+    info!(
+        "Admin {} started a pinned package refresh",
+        admin.0.username
+    );
+    Ok(Json(PinnedPackageService::refresh(state, true).await?))
+}
+
+/// List package names flagged as possible typosquats, optionally filtered by status
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = FlaggedNameListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/flagged-names?<status>")]
+pub async fn list_flagged_names(
+    status: Option<&str>,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<FlaggedNameListResponse>, ApiError> {
+    Ok(Json(TyposquatService::list(status, state)?))
+}
+
+/// Approve a flagged name so it can be published
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = FlaggedName)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/flagged-names/<id>/approve")]
+pub async fn approve_flagged_name(
+    id: i32,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<FlaggedName>, ApiError> {
+    let flagged = TyposquatService::review(id, FlaggedNameStatus::Approved, &admin.0, state)?;
+    Ok(Json(flagged))
+}
+
+/// Reject a flagged name, publishing it stays forbidden
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = FlaggedName)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/flagged-names/<id>/reject")]
+pub async fn reject_flagged_name(
+    id: i32,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<FlaggedName>, ApiError> {
+    let flagged = TyposquatService::review(id, FlaggedNameStatus::Rejected, &admin.0, state)?;
+    Ok(Json(flagged))
+}
+
+/// Current maintenance mode
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = MaintenanceStatus)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/maintenance")]
+pub async fn get_maintenance(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Json<MaintenanceStatus> {
+    Json(state.maintenance.status())
+}
+
+/// Enable maintenance mode, rejecting writes with 503 while reads keep working
+#[utoipa::path(
+    tag = "admin",
+    request_body = Option<MaintenanceRequest>,
+    responses((status = 200, body = MaintenanceStatus)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/admin/maintenance", data = "<request>")]
+pub async fn enable_maintenance(
+    request: Option<Json<MaintenanceRequest>>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Json<MaintenanceStatus> {
+    let request = request.map(Json::into_inner).unwrap_or_default();
+    Json(MaintenanceMode::enable(request, &admin.0, state))
+}
+
+/// Disable maintenance mode
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = MaintenanceStatus)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/maintenance")]
+pub async fn disable_maintenance(
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Json<MaintenanceStatus> {
+    Json(MaintenanceMode::disable(&admin.0, state))
+}
+
+/// Queue `ANALYZE`, with `VACUUM` and `REINDEX` while maintenance mode is on. The task result
+/// reports the space reclaimed.
+#[utoipa::path(
+    tag = "admin",
+    request_body = Option<DatabaseOptimizeRequest>,
+    responses((status = 202, body = TaskResponse), (status = 409, body = ErrorBody)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/database/optimize", data = "<request>")]
+pub async fn optimize_database(
+    request: Option<Json<DatabaseOptimizeRequest>>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Accepted<Json<TaskResponse>>, ApiError> {
+    let request = request.map(Json::into_inner).unwrap_or_default();
+    let task = TaskService::enqueue_optimize_database(request, &admin.0, state)?;
+    Ok(Accepted(Json(task)))
+}
+
+/// List quarantined upstream packages and majors, optionally filtered by status
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = QuarantineListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/quarantine?<status>")]
+pub async fn list_quarantined_packages(
+    status: Option<&str>,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<QuarantineListResponse>, ApiError> {
+    Ok(Json(QuarantineService::list(status, state)?))
+}
+
+/// Approve a quarantined package or major so it is served
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = QuarantinedPackage)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/quarantine/<id>/approve")]
+pub async fn approve_quarantined_package(
+    id: i32,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<QuarantinedPackage>, ApiError> {
+    let entry = QuarantineService::review(id, QuarantineStatus::Approved, &admin.0, state).await?;
+    Ok(Json(entry))
+}
+
+/// Reject a quarantined package or major, it stays unavailable
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = QuarantinedPackage)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/quarantine/<id>/reject")]
+pub async fn reject_quarantined_package(
+    id: i32,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<QuarantinedPackage>, ApiError> {
+    let entry = QuarantineService::review(id, QuarantineStatus::Rejected, &admin.0, state).await?;
+    Ok(Json(entry))
+}
+
+/// Background jobs with their schedules and the outcome of their last run
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Vec<JobStatus>)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/jobs")]
+pub async fn list_jobs(_admin: AdminUser, state: &State<AppState>) -> Json<Vec<JobStatus>> {
+    Json(state.jobs.statuses())
+}
+
+/// Bytes of package metadata and tarballs served over a period like `30d` or `4w`, added up
+/// per package, user, token or organization
+#[utoipa::path(
+    tag = "admin",
+    params(
+        ("period" = Option<String>, Query, description = "Days like 30d or weeks like 4w, 30d by default"),
+        ("group_by" = Option<String>, Query, description = "package, user, token or org, package by default"),
+        ("limit" = Option<usize>, Query, description = "Entries returned, 100 by default")
+    ),
+    responses((status = 200, body = BandwidthReport)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/bandwidth?<period>&<group_by>&<limit>")]
+pub async fn bandwidth_report(
+    period: Option<&str>,
+    group_by: Option<&str>,
+    limit: Option<usize>,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<BandwidthReport>, ApiError> {
+    BandwidthService::report(period, group_by, limit, state).map(Json)
+}
+
+/// Schema version of the database with its applied and pending migrations
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = SchemaStatus)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/schema")]
+pub async fn schema_status(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<SchemaStatus>, ApiError> {
+    state
+        .database
+        .schema_status()
+        .map(Json)
+        .map_err(|e| ApiError::DatabaseError(format!("Failed to read schema status: {e}")))
+}
+
+/// Advisory store statistics and sync settings
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = AdvisoryStatusResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/advisories")]
+pub async fn advisory_status(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<AdvisoryStatusResponse>, ApiError> {
+    Ok(Json(AdvisoryService::status(state)?))
+}
+
+/// Sync advisories from OSV.dev now instead of waiting for the next scheduled sync
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = AdvisorySyncReport)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/advisories/sync")]
+pub async fn sync_advisories(
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<AdvisorySyncReport>, ApiError> {
+    info!("Admin {} started an advisory sync", admin.0.username);
+    Ok(Json(AdvisoryService::sync(state).await?))
+}
+
+/// List advisories defined by registry admins
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Vec<Advisory>)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/admin/advisories/internal")]
+pub async fn list_internal_advisories(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Vec<Advisory>>, ApiError> {
+    Ok(Json(AdvisoryService::list_internal(state)?))
+}
+
+/// Add an internal advisory, reported to npm clients alongside OSV.dev advisories
+#[utoipa::path(
+    tag = "admin",
+    request_body = InternalAdvisoryRequest,
+    responses((status = 200, body = Advisory)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/admin/advisories/internal", data = "<request>")]
+pub async fn create_internal_advisory(
+    request: Json<InternalAdvisoryRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Advisory>, ApiError> {
+    let advisory = AdvisoryService::create_internal(request.into_inner(), &admin.0, state)?;
+    Ok(Json(advisory))
+}
+
+/// Replace an internal advisory
+#[utoipa::path(
+    tag = "admin",
+    request_body = InternalAdvisoryRequest,
+    responses((status = 200, body = Advisory)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/admin/advisories/internal/<id>", data = "<request>")]
+pub async fn update_internal_advisory(
+    id: i32,
+    request: Json<InternalAdvisoryRequest>,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Advisory>, ApiError> {
+    let advisory = AdvisoryService::update_internal(id, request.into_inner(), &admin.0, state)?;
+    Ok(Json(advisory))
+}
+
+/// Delete an internal advisory
+#[utoipa::path(
+    tag = "admin",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/admin/advisories/internal/<id>")]
+pub async fn delete_internal_advisory(
+    id: i32,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    AdvisoryService::delete_internal(id, state)?;
+    Ok(Json(serde_json::json!({"ok": true})))
+}
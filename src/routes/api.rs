@@ -1,19 +1,46 @@
-use crate::error::ApiError;
+use crate::error::{ApiError, ErrorBody, ErrorCode};
+use crate::fairings::{DeniedClient, RateLimitedClient, RequestId};
 use crate::models::{
-    CacheAnalytics, CacheStatsResponse, PackageListResponse, PackageVersionsResponse,
-    PopularPackage,
+    AuditLogActorEntry, AuthenticatedUser, CacheAnalytics, CacheEntryListResponse, CacheGcReport,
+    CacheInvalidationReport, CacheStatsResponse, DashboardSummary, LiveMetricsSample,
+    MaintenanceStatus, OptionalAuthenticatedUser, PackageArchive, PackageCacheAnalytics,
+    PackageDownloadsResponse, PackageFilesResponse, PackageHistoryResponse, PackageListResponse,
+    PackageReadme, PackageSizeResponse, PackageVersion, PackageVersionsResponse, PackageVisibility,
+    PackageVisibilityChange, PackageVisibilityResponse, PopularPackage, RecentVersionsResponse,
+    RegistryReader, TaskResponse, TransferPackageRequest, TransferPackageResponse,
+    UnpublishResponse, UpdateVisibilityRequest, YankVersionRequest,
 };
+use crate::routes::packages::ensure_read_access;
 use crate::state::AppState;
-use log::{debug, info};
+use crate::versions;
+use log::{debug, error, info, warn};
+use rocket::data::ToByteUnit;
+use rocket::http::{ContentType, Header};
+use rocket::response::Responder;
+use rocket::response::status::Accepted;
 use rocket::serde::json::Json;
-use rocket::{State, delete, get, post};
+use rocket::tokio::fs::File;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::{Data, Request, Shutdown, State, delete, get, post, put};
 use serde_json;
 
 // Import auth types from models
-use crate::models::{LoginRequest, LoginResponse, NpmUserResponse, RegisterRequest};
+use crate::models::{
+    AdminUser, ClientInfo, LoginRequest, LoginResponse, NpmUserResponse, RegisterRequest,
+};
 use crate::services::auth::AuthService;
+use crate::services::{
+    AccountService, CacheAnalyticsService, DashboardService, DiskWatermarkService,
+    DownloadStatsService, FeedService, InstallSizeService, LiveMetricsService, LiveMetricsSocket,
+    MetricsService, PackageFilesService, PinnedPackageService, ReadmeService, TaskService,
+    TransferService, UnpublishService, VisibilityService, YankService,
+};
 
 // Health check endpoint
+#[utoipa::path(
+    tag = "status",
+    responses((status = 200, body = Object))
+)]
 #[get("/api/v1/health")]
 pub async fn health_check() -> Json<serde_json::Value> {
     Json(serde_json::json!({
@@ -21,7 +48,126 @@ pub async fn health_check() -> Json<serde_json::Value> {
     }))
 }
 
+/// Metrics in the Prometheus text format
+#[utoipa::path(
+    tag = "status",
+    responses((status = 200, body = String, content_type = "text/plain"))
+)]
+#[get("/api/v1/metrics")]
+pub async fn metrics(state: &State<AppState>) -> (ContentType, String) {
+    (
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        MetricsService::render(state),
+    )
+}
+
+/// 503 answered to writes during maintenance. `Retry-After` tells clients when to try again.
+#[derive(Responder)]
+#[response(status = 503, content_type = "json")]
+pub struct MaintenanceResponse {
+    body: Json<serde_json::Value>,
+    retry_after: Header<'static>,
+}
+
+/// Whether the registry is in maintenance mode
+#[utoipa::path(
+    tag = "status",
+    responses((status = 200, body = MaintenanceStatus))
+)]
+#[get("/api/v1/maintenance")]
+pub async fn maintenance_status(state: &State<AppState>) -> Json<MaintenanceStatus> {
+    Json(state.maintenance.status())
+}
+
+/// Target of write requests rewritten by the maintenance guard
+#[utoipa::path(
+    tag = "status",
+    responses((
+        status = 503,
+        body = Object,
+        headers(("Retry-After" = u64, description = "Seconds until writes are likely accepted again"))
+    ))
+)]
+#[get("/api/v1/maintenance/unavailable")]
+pub async fn maintenance_unavailable(
+    state: &State<AppState>,
+) -> Result<MaintenanceResponse, ApiError> {
+    let status = state.maintenance.status();
+    if !status.enabled {
+        return Err(ApiError::NotFound("Not in maintenance mode".to_string()));
+    }
+
+    let reason = status.message.unwrap_or_else(|| {
+        "The registry is in maintenance mode and read-only, try again later".to_string()
+    });
+    Ok(MaintenanceResponse {
+        body: Json(serde_json::json!({
+            "error": "service unavailable",
+            "reason": reason,
+            "code": ErrorCode::E503,
+            "request_id": RequestId::current().map(|id| id.0),
+        })),
+        retry_after: Header::new("Retry-After", status.retry_after_secs.to_string()),
+    })
+}
+
+/// Target of requests rewritten by the IP guard
+#[utoipa::path(
+    tag = "status",
+    responses((status = 403, body = ErrorBody))
+)]
+#[get("/api/v1/access/denied")]
+pub async fn access_denied(denied: DeniedClient) -> ApiError {
+    match denied.0 {
+        Some(ip) => ApiError::Forbidden(format!(
+            "Requests from {ip} are not allowed by this registry"
+        )),
+        None => ApiError::Forbidden("Requests from your address are not allowed".to_string()),
+    }
+}
+
+/// 429 answered to clients over the rate limit
+#[derive(Responder)]
+#[response(status = 429, content_type = "json")]
+pub struct RateLimitedResponse {
+    body: Json<ErrorBody>,
+    retry_after: Header<'static>,
+}
+
+/// Target of requests rewritten by the rate limit guard
+#[utoipa::path(
+    tag = "status",
+    responses((
+        status = 429,
+        body = ErrorBody,
+        headers(("Retry-After" = u64, description = "Seconds until requests are accepted again"))
+    ))
+)]
+#[get("/api/v1/access/rate-limited")]
+pub async fn rate_limited(limited: RateLimitedClient) -> Result<RateLimitedResponse, ApiError> {
+    if limited.0 == 0 {
+        return Err(ApiError::NotFound("Not rate limited".to_string()));
+    }
+
+    Ok(RateLimitedResponse {
+        body: Json(ErrorBody {
+            request_id: RequestId::current().map(|id| id.0),
+            ..ApiError::TooManyRequests(format!(
+                "Too many requests, try again in {} seconds",
+                limited.0
+            ))
+            .to_body()
+        }),
+        retry_after: Header::new("Retry-After", limited.0.to_string()),
+    })
+}
+
 // Analytics endpoints
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageListResponse)),
+    security((), ("bearer" = []))
+)]
 #[get("/api/v1/packages?<limit>&<page>&<search>&<sort>&<order>")]
 pub async fn list_packages(
     limit: Option<i64>,
@@ -29,6 +175,7 @@ pub async fn list_packages(
     search: Option<String>,
     sort: Option<String>,
     order: Option<String>,
+    user: OptionalAuthenticatedUser,
     state: &State<AppState>,
 ) -> Result<Json<PackageListResponse>, ApiError> {
     let limit = limit.unwrap_or(20).clamp(1, 100); // Default 20, max 100
@@ -57,53 +204,54 @@ pub async fn list_packages(
 
     let (packages, total_count) = state
         .database
-        .get_packages_paginated(limit, offset, search_query, sort_column, sort_order)
+        .get_packages_paginated(
+            limit,
+            offset,
+            search_query,
+            sort_column,
+            sort_order,
+            // Private packages are only listed for admins
+            user.0.as_ref().is_some_and(|u| u.is_admin),
+            None,
+        )
         .map_err(|e| ApiError::ParseError(format!("Failed to list packages: {e}")))?;
 
-    // Calculate total size from all files across all versions
-    let total_size_bytes = packages
-        .iter()
-        .flat_map(|pkg| &pkg.versions)
-        .flat_map(|ver| &ver.files)
-        .map(|file| file.size_bytes)
-        .sum::<i64>();
-
-    let total_size_mb = total_size_bytes as f64 / 1024.0 / 1024.0;
-
-    // Calculate pagination metadata
-    let total_pages = (total_count + limit - 1) / limit; // Ceiling division
-    let has_next = page < total_pages;
-    let has_prev = page > 1;
-
-    let pagination = crate::models::package::PaginationMetadata {
-        page,
-        limit,
-        total_pages,
-        has_next,
-        has_prev,
-    };
-
-    Ok(Json(PackageListResponse {
+    Ok(Json(PackageListResponse::new(
         packages,
         total_count,
-        total_size_bytes,
-        total_size_mb,
-        pagination,
-    }))
+        page,
+        limit,
+    )))
 }
 
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageVersionsResponse)),
+    security((), ("bearer" = []))
+)]
 #[get("/api/v1/packages/<name>")]
 pub async fn get_package_versions(
     name: &str,
+    user: OptionalAuthenticatedUser,
     state: &State<AppState>,
 ) -> Result<Json<PackageVersionsResponse>, ApiError> {
+    let has_access = state
+        .database
+        .has_read_permission(name, user.0.as_ref().map(|u| u.user_id))
+        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+    if !has_access {
+        return Err(ApiError::NotFound(format!("Package '{name}' not found")));
+    }
+
     let package_with_versions = state
         .database
         .get_package_with_versions(name)
         .map_err(|e| ApiError::ParseError(format!("Failed to get package versions: {e}")))?;
 
     match package_with_versions {
-        Some(pkg_with_versions) => {
+        Some(mut pkg_with_versions) => {
+            versions::sort_descending(&mut pkg_with_versions.versions, |v| &v.version.version);
             let total_size_bytes = pkg_with_versions
                 .versions
                 .iter()
@@ -121,6 +269,267 @@ pub async fn get_package_versions(
     }
 }
 
+/// The README of a version rendered to HTML, `latest` unless `version` names a version,
+/// dist-tag or range
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageReadme)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/packages/<name>/readme?<version>")]
+pub async fn get_package_readme(
+    name: &str,
+    version: Option<&str>,
+    user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<PackageReadme>, ApiError> {
+    ensure_read_access(name, &user, state)?;
+
+    ReadmeService::get(name, version, state).map(Json)
+}
+
+/// Files in the tarball of a version with their sizes and media types
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageFilesResponse)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/packages/<name>/<version>/files")]
+pub async fn get_package_files(
+    name: &str,
+    version: &str,
+    user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<PackageFilesResponse>, ApiError> {
+    ensure_read_access(name, &user, state)?;
+
+    PackageFilesService::list(name, version, state).map(Json)
+}
+
+/// Unpacked size and file count of a version, and the size of installing it with its
+/// dependencies
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageSizeResponse)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/packages/<name>/<version>/size")]
+pub async fn get_package_size(
+    name: &str,
+    version: &str,
+    user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<PackageSizeResponse>, ApiError> {
+    ensure_read_access(name, &user, state)?;
+
+    InstallSizeService::report(name, version, user.0.as_ref(), state)
+        .await
+        .map(Json)
+}
+
+/// Publishes, unpublishes, yanks, dist-tag changes and ownership transfers of a package,
+/// newest first
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageHistoryResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/packages/<name>/history?<limit>")]
+pub async fn get_package_history(
+    name: &str,
+    limit: Option<i64>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageHistoryResponse>, ApiError> {
+    let has_access = state
+        .database
+        .has_read_permission(name, Some(user.user_id))
+        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+    if !has_access {
+        return Err(ApiError::NotFound(format!("Package '{name}' not found")));
+    }
+
+    let entries = state
+        .database
+        .list_package_audit_log(name, limit.unwrap_or(100).clamp(1, 1000))
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .into_iter()
+        .map(AuditLogActorEntry::from)
+        .collect();
+
+    Ok(Json(PackageHistoryResponse {
+        package: name.to_string(),
+        entries,
+    }))
+}
+
+/// Downloads of a package per day, week or month, split by version
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageDownloadsResponse)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/packages/<name>/downloads?<from>&<to>&<granularity>")]
+pub async fn get_package_downloads(
+    name: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+    granularity: Option<&str>,
+    user: OptionalAuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageDownloadsResponse>, ApiError> {
+    let has_access = state
+        .database
+        .has_read_permission(name, user.0.as_ref().map(|u| u.user_id))
+        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+    if !has_access {
+        return Err(ApiError::NotFound(format!("Package '{name}' not found")));
+    }
+
+    DownloadStatsService::series(name, from, to, granularity, state).map(Json)
+}
+
+/// Switch a package between public and private
+#[utoipa::path(
+    tag = "packages",
+    request_body = UpdateVisibilityRequest,
+    responses((status = 200, body = PackageVisibilityChange)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/packages/<name>/visibility", data = "<request>")]
+pub async fn update_package_visibility(
+    name: &str,
+    request: Json<UpdateVisibilityRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageVisibilityChange>, ApiError> {
+    let request = request.into_inner();
+    let visibility =
+        PackageVisibility::from_visibility_str(&request.visibility).ok_or_else(|| {
+            ApiError::BadRequest(format!("Invalid visibility '{}'", request.visibility))
+        })?;
+
+    let change =
+        VisibilityService::set_visibility(name, visibility, request.reason, &user, state).await?;
+
+    Ok(Json(change))
+}
+
+/// Transfer a package to another user or to an organization. The new owners replace the
+/// current ones.
+#[utoipa::path(
+    tag = "packages",
+    request_body = TransferPackageRequest,
+    responses((status = 200, body = TransferPackageResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/packages/<name>/transfer", data = "<request>")]
+pub async fn transfer_package(
+    name: &str,
+    request: Json<TransferPackageRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<TransferPackageResponse>, ApiError> {
+    TransferService::transfer(name, request.into_inner(), &user, state)
+        .await
+        .map(Json)
+}
+
+/// Yank a published version. It is left out of the package document and dist-tags but
+/// can still be installed by exact version.
+#[utoipa::path(
+    tag = "packages",
+    request_body = Option<YankVersionRequest>,
+    responses((status = 200, body = PackageVersion)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/packages/<name>/versions/<version>/yank", data = "<request>")]
+pub async fn yank_version(
+    name: &str,
+    version: &str,
+    request: Option<Json<YankVersionRequest>>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageVersion>, ApiError> {
+    let reason = request.and_then(|request| request.into_inner().reason);
+    YankService::set_yanked(name, version, true, reason, &user, state)
+        .await
+        .map(Json)
+}
+
+/// Unpublish a version. With immutable versions only within the grace period after
+/// publishing, and the version can't be published again.
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = UnpublishResponse)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/packages/<name>/versions/<version>")]
+pub async fn unpublish_version(
+    name: &str,
+    version: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<UnpublishResponse>, ApiError> {
+    UnpublishService::unpublish(name, version, &user, state)
+        .await
+        .map(Json)
+}
+
+/// Restore a yanked version
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageVersion)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/packages/<name>/versions/<version>/yank")]
+pub async fn unyank_version(
+    name: &str,
+    version: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageVersion>, ApiError> {
+    YankService::set_yanked(name, version, false, None, &user, state)
+        .await
+        .map(Json)
+}
+
+/// Current visibility of a package and its change history
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = PackageVisibilityResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/packages/<name>/visibility")]
+pub async fn get_package_visibility(
+    name: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageVisibilityResponse>, ApiError> {
+    let has_access = state
+        .database
+        .has_read_permission(name, Some(user.user_id))
+        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+    if !has_access {
+        return Err(ApiError::NotFound(format!("Package '{name}' not found")));
+    }
+
+    let (package, history) = VisibilityService::history(name, state)?;
+
+    Ok(Json(PackageVisibilityResponse {
+        package: package.name,
+        visibility: package.visibility,
+        history,
+    }))
+}
+
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = Vec<PopularPackage>))
+)]
 #[get("/api/v1/packages/popular?<limit>")]
 pub async fn get_popular_packages(
     limit: Option<i64>,
@@ -135,6 +544,91 @@ pub async fn get_popular_packages(
     Ok(Json(popular_packages))
 }
 
+/// Versions recently published to or proxied through the registry, newest first.
+/// `source` is `published` or `proxied` to list only one kind.
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = RecentVersionsResponse)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/packages/recent?<limit>&<source>")]
+pub async fn get_recent_versions(
+    limit: Option<i64>,
+    source: Option<&str>,
+    _user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<RecentVersionsResponse>, ApiError> {
+    let versions = FeedService::recent(limit, source, state)?;
+
+    Ok(Json(RecentVersionsResponse { versions }))
+}
+
+/// Totals, cache usage, top packages, recent publishes and upstream status for the
+/// dashboard, cached for 30 seconds
+#[utoipa::path(
+    tag = "status",
+    responses((status = 200, body = DashboardSummary)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/dashboard")]
+pub async fn get_dashboard(
+    _user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<DashboardSummary>, ApiError> {
+    DashboardService::summary(state).await.map(Json)
+}
+
+/// Live metrics over a WebSocket when the client asks for an upgrade, as server-sent events
+/// otherwise
+pub struct LiveMetricsResponse<'r> {
+    state: &'r AppState,
+    shutdown: Shutdown,
+}
+
+impl<'r> Responder<'r, 'r> for LiveMetricsResponse<'r> {
+    fn respond_to(self, req: &'r Request<'_>) -> rocket::response::Result<'r> {
+        let mut response =
+            LiveMetricsService::events(self.state, self.shutdown.clone()).respond_to(req)?;
+        if let Some(key) = req.headers().get_one("Sec-WebSocket-Key") {
+            response.set_header(Header::new(
+                "Sec-WebSocket-Accept",
+                LiveMetricsService::accept_key(key),
+            ));
+            response.add_upgrade(
+                "websocket",
+                LiveMetricsSocket {
+                    state: self.state,
+                    shutdown: self.shutdown,
+                },
+            );
+        }
+        Ok(response)
+    }
+}
+
+/// Request rate, cache hit rate and active downloads, one sample a second. Served over a
+/// WebSocket, or as server-sent events where WebSocket isn't available.
+#[utoipa::path(
+    tag = "status",
+    responses((status = 200, body = LiveMetricsSample, content_type = "text/event-stream")),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/ws")]
+pub async fn live_metrics<'r>(
+    _user: RegistryReader,
+    shutdown: Shutdown,
+    state: &'r State<AppState>,
+) -> LiveMetricsResponse<'r> {
+    LiveMetricsResponse {
+        state: state.inner(),
+        shutdown,
+    }
+}
+
+#[utoipa::path(
+    tag = "cache",
+    responses((status = 200, body = CacheAnalytics))
+)]
 #[get("/api/v1/analytics")]
 pub async fn get_cache_analytics(
     state: &State<AppState>,
@@ -203,13 +697,64 @@ pub async fn get_cache_analytics(
         metadata_cache_entries: metadata_stats.total_entries,
         metadata_cache_size_bytes: metadata_stats.total_size_bytes,
         metadata_cache_size_mb: metadata_stats.total_size_mb,
+        hot_cache: state.cache.hot_cache_stats(),
     };
 
     info!("Analytics response prepared successfully");
     Ok(Json(analytics))
 }
 
+/// Per-package cache hit rate and bytes served from the cache and upstream over a period
+/// like `7d` or `4w`. Listing every package requires an admin.
+#[utoipa::path(
+    tag = "cache",
+    params(
+        ("package" = Option<String>, Query, description = "Only this package"),
+        ("period" = Option<String>, Query, description = "Days like 7d or weeks like 4w, 7d by default")
+    ),
+    responses((status = 200, body = PackageCacheAnalytics)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/cache/analytics?<package>&<period>")]
+pub async fn get_package_cache_analytics(
+    package: Option<&str>,
+    period: Option<&str>,
+    user: OptionalAuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageCacheAnalytics>, ApiError> {
+    match package {
+        Some(package) => {
+            let has_access = state
+                .database
+                .has_read_permission(package, user.0.as_ref().map(|u| u.user_id))
+                .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+            if !has_access {
+                return Err(ApiError::NotFound(format!("Package '{package}' not found")));
+            }
+        }
+        None => match &user.0 {
+            Some(user) if user.is_admin => {}
+            Some(_) => {
+                return Err(ApiError::Forbidden(
+                    "Admin privileges required to list every package".to_string(),
+                ));
+            }
+            None => {
+                return Err(ApiError::Unauthorized(
+                    "Authentication required to list every package".to_string(),
+                ));
+            }
+        },
+    }
+
+    CacheAnalyticsService::package_usage(package, period, state).map(Json)
+}
+
 // Cache management endpoints
+#[utoipa::path(
+    tag = "cache",
+    responses((status = 200, body = CacheStatsResponse))
+)]
 #[get("/api/v1/cache/stats")]
 pub async fn get_cache_stats(
     state: &State<AppState>,
@@ -242,15 +787,24 @@ pub async fn get_cache_stats(
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    tag = "cache",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
 #[delete("/api/v1/cache")]
-pub async fn clear_cache(state: &State<AppState>) -> Result<Json<serde_json::Value>, ApiError> {
+pub async fn clear_cache(
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
     if !state.config.cache_enabled {
         return Err(ApiError::ParseError("Cache is disabled".to_string()));
     }
 
+    let pinned = PinnedPackageService::names(state)?;
     state
         .cache
-        .clear()
+        .clear(&pinned)
         .await
         .map_err(|e| ApiError::ParseError(format!("Failed to clear cache: {e}")))?;
 
@@ -259,6 +813,98 @@ pub async fn clear_cache(state: &State<AppState>) -> Result<Json<serde_json::Val
     })))
 }
 
+#[utoipa::path(
+    tag = "cache",
+    params(
+        ("package" = Option<String>, Query, description = "Only the files of this package"),
+        ("limit" = Option<i64>, Query, description = "Entries per page, at most 1000"),
+        ("page" = Option<i64>, Query, description = "Page number, starting at 1")
+    ),
+    responses((status = 200, body = CacheEntryListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/cache/entries?<package>&<limit>&<page>")]
+pub async fn list_cache_entries(
+    package: Option<&str>,
+    limit: Option<i64>,
+    page: Option<i64>,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<CacheEntryListResponse>, ApiError> {
+    if let Some(package) = package {
+        validate_cached_package_name(package)?;
+    }
+    let limit = limit.unwrap_or(100).clamp(1, 1000);
+    let page = page.unwrap_or(1).max(1);
+
+    let entries = state
+        .cache
+        .list_entries(package)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to list cache entries: {e}")))?;
+
+    let total_entries = entries.len();
+    let total_size_bytes = entries.iter().map(|entry| entry.size_bytes).sum();
+    let total_pages = (total_entries as i64 + limit - 1) / limit;
+    let entries = entries
+        .into_iter()
+        .skip(((page - 1) * limit) as usize)
+        .take(limit as usize)
+        .collect();
+
+    Ok(Json(CacheEntryListResponse {
+        entries,
+        total_entries,
+        total_size_bytes,
+        pagination: crate::models::package::PaginationMetadata {
+            page,
+            limit,
+            total_pages,
+            has_next: page < total_pages,
+            has_prev: page > 1,
+        },
+    }))
+}
+
+#[utoipa::path(
+    tag = "cache",
+    responses(
+        (status = 200, body = CacheInvalidationReport),
+        (status = 400, body = ErrorBody)
+    ),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/cache/packages/<name>")]
+pub async fn invalidate_cached_package(
+    name: &str,
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<CacheInvalidationReport>, ApiError> {
+    validate_cached_package_name(name)?;
+
+    let report = state
+        .cache
+        .invalidate_package(name, &state.database)
+        .await
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to invalidate cache: {e}")))?;
+
+    info!("Admin {} invalidated the cache of {name}", admin.0.username);
+    Ok(Json(report))
+}
+
+/// Package names end up in cache paths and must not leave the cache directory
+fn validate_cached_package_name(package: &str) -> Result<(), ApiError> {
+    if package.is_empty() || package.contains("..") || package.starts_with('/') {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid package name '{package}'"
+        )));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    tag = "cache",
+    responses((status = 200, body = Object))
+)]
 #[get("/api/v1/cache/health")]
 pub async fn cache_health(state: &State<AppState>) -> Result<Json<serde_json::Value>, ApiError> {
     let stats = state
@@ -276,48 +922,172 @@ pub async fn cache_health(state: &State<AppState>) -> Result<Json<serde_json::Va
     Ok(Json(serde_json::json!({
         "status": health_status,
         "enabled": state.config.cache_enabled,
-        "total_size_mb": stats.total_size_bytes as f64 / 1024.0 / 1024.0
+        "total_size_mb": stats.total_size_bytes as f64 / 1024.0 / 1024.0,
+        "database": state.database.pool_stats(),
+        "disk": DiskWatermarkService::stats(state)
     })))
 }
 
+/// Queue reprocessing of the cached files, poll the task for the number of processed files
+#[utoipa::path(
+    tag = "cache",
+    responses((status = 202, body = TaskResponse)),
+    security(("bearer" = []))
+)]
 #[post("/api/v1/cache/reprocess")]
-pub async fn reprocess_cache(state: &State<AppState>) -> Result<Json<serde_json::Value>, ApiError> {
+pub async fn reprocess_cache(
+    admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Accepted<Json<TaskResponse>>, ApiError> {
+    let task = TaskService::enqueue_reprocess_cache(&admin.0, state)?;
+    Ok(Accepted(Json(task)))
+}
+
+/// Report cache files without a database record and records without a file, and delete
+/// them with `?delete=true`
+#[utoipa::path(
+    tag = "cache",
+    responses((status = 200, body = CacheGcReport)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/cache/gc?<delete>")]
+pub async fn collect_cache_garbage(
+    delete: Option<bool>,
+    _admin: AdminUser,
+    state: &State<AppState>,
+) -> Result<Json<CacheGcReport>, ApiError> {
     if !state.config.cache_enabled {
         return Err(ApiError::ParseError("Cache is disabled".to_string()));
     }
 
-    let processed_count = state
+    let report = state
         .cache
-        .reprocess_cached_files(&state.database)
+        .collect_garbage(&state.database, delete.unwrap_or(false))
         .await
-        .map_err(|e| ApiError::ParseError(format!("Failed to reprocess cache: {e}")))?;
+        .map_err(|e| {
+            ApiError::InternalServerError(format!("Cache garbage collection failed: {e}"))
+        })?;
 
-    Ok(Json(serde_json::json!({
-        "message": "Cache reprocessing completed",
-        "processed_files": processed_count
-    })))
+    Ok(Json(report))
+}
+
+/// Queue warming the cache with every package of a `package-lock.json` or
+/// `pnpm-lock.yaml`, the task's result is a prefetch report
+#[utoipa::path(
+    tag = "cache",
+    request_body(
+        content = String,
+        content_type = "text/plain",
+        description = "A `package-lock.json` or `pnpm-lock.yaml`"
+    ),
+    responses((status = 202, body = TaskResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/prefetch", data = "<data>")]
+pub async fn prefetch(
+    data: Data<'_>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Accepted<Json<TaskResponse>>, ApiError> {
+    if !state.config.cache_enabled {
+        return Err(ApiError::ParseError("Cache is disabled".to_string()));
+    }
+
+    let mut body = String::new();
+    let mut stream = data.open(state.config.json_body_limit().bytes());
+    stream.read_to_string(&mut body).await.map_err(|e| {
+        error!("Failed to read request body: {e}");
+        ApiError::BadRequest(format!("Failed to read request body: {e}"))
+    })?;
+
+    let task = TaskService::enqueue_prefetch(&body, &user, state)?;
+    Ok(Accepted(Json(task)))
+}
+
+/// Status of a queued operation, with its result once it succeeded
+#[utoipa::path(
+    tag = "tasks",
+    responses((status = 200, body = TaskResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/tasks/<id>")]
+pub async fn get_task(
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<TaskResponse>, ApiError> {
+    let task = TaskService::get(id, &user, state)?;
+    Ok(Json(task.into()))
+}
+
+/// Download the package archive of a succeeded export task
+#[utoipa::path(
+    tag = "tasks",
+    responses((status = 200, body = PackageArchive)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/tasks/<id>/download")]
+pub async fn download_task_result(
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<(ContentType, File), ApiError> {
+    let task = TaskService::get(id, &user, state)?;
+    let path = TaskService::export_path(&task, state)?;
+    let file = File::open(&path)
+        .await
+        .map_err(|_| ApiError::NotFound(format!("The archive of task {id} no longer exists")))?;
+    Ok((ContentType::JSON, file))
 }
 
 // Authentication endpoints (simple login/register, not npm-specific)
+#[utoipa::path(
+    tag = "auth",
+    request_body = LoginRequest,
+    responses((status = 200, body = LoginResponse))
+)]
 #[post("/api/v1/login", data = "<login_request>")]
 pub async fn login(
     login_request: Json<LoginRequest>,
+    client: ClientInfo,
     state: &State<AppState>,
 ) -> Result<Json<LoginResponse>, ApiError> {
-    let (_user, token) =
-        AuthService::authenticate_user(&state.database, login_request.into_inner())?;
+    let (_user, token) = AuthService::authenticate_user(
+        &state.database,
+        &state.config,
+        &state.plugins,
+        &state.rate_limiter,
+        &state.secrets.token_keys(&state.config),
+        login_request.into_inner(),
+        &client,
+    )
+    .await?;
 
     Ok(Json(LoginResponse { ok: true, token }))
 }
 
+#[utoipa::path(
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses((status = 200, body = NpmUserResponse))
+)]
 #[post("/api/v1/register", data = "<register_request>")]
 pub async fn register(
     register_request: Json<RegisterRequest>,
+    client: ClientInfo,
     state: &State<AppState>,
 ) -> Result<Json<NpmUserResponse>, ApiError> {
     let register_data = register_request.into_inner();
 
-    let user = AuthService::register_user(&state.database, register_data.clone())?;
+    let user =
+        AuthService::register_new_user(&state.database, &state.config, register_data.clone())?;
+
+    if let Err(e) = AccountService::send_verification_email(state, &user).await {
+        warn!(
+            "Failed to send verification email to {}: {e:?}",
+            user.username
+        );
+    }
 
     // Create authentication token for the new user
     let login_request = LoginRequest {
@@ -325,7 +1095,16 @@ pub async fn register(
         password: register_data.password.clone(),
     };
 
-    let (_user, token) = AuthService::authenticate_user(&state.database, login_request)?;
+    let (_user, token) = AuthService::authenticate_user(
+        &state.database,
+        &state.config,
+        &state.plugins,
+        &state.rate_limiter,
+        &state.secrets.token_keys(&state.config),
+        login_request,
+        &client,
+    )
+    .await?;
 
     Ok(Json(NpmUserResponse {
         ok: true,
@@ -1,13 +1,16 @@
 use crate::error::ApiError;
 use crate::models::{
-    AuthenticatedUser, LoginRequest, LogoutResponse, NpmUserDocument, NpmUserResponse,
-    RegisterRequest, WhoamiResponse,
+    AuthenticatedUser, ClientCertificatesResponse, ClientInfo, LoginRequest, LogoutResponse,
+    NpmUserDocument, NpmUserResponse, PasswordResetConfirmRequest, PasswordResetRequest,
+    RegisterRequest, SessionListResponse, StorageUsageResponse, VerifyEmailRequest, WhoamiResponse,
 };
-use crate::services::AuthService;
+use crate::services::{AccountService, AuthService, ClientCertificateService, QuotaService};
 use crate::state::AppState;
 
+use log::warn;
 use rocket::serde::Serialize;
-use rocket::{State, put, serde::json::Json};
+use rocket::serde::json::Value;
+use rocket::{State, post, put, serde::json::Json};
 
 #[derive(Serialize, Debug)]
 pub struct NpmErrorResponse {
@@ -20,6 +23,7 @@ pub struct NpmErrorResponse {
 pub async fn npm_login(
     user_id: &str,
     user_doc: Json<NpmUserDocument>,
+    client: ClientInfo,
     state: &State<AppState>,
 ) -> Result<Json<NpmUserResponse>, ApiError> {
     // Validate the user_id format (should be org.couchdb.user:username)
@@ -46,7 +50,16 @@ pub async fn npm_login(
             password: user_doc.password.clone(),
         };
 
-        let (_user, token) = AuthService::authenticate_user(&state.database, login_request)?;
+        let (_user, token) = AuthService::authenticate_user(
+            &state.database,
+            &state.config,
+            &state.plugins,
+            &state.rate_limiter,
+            &state.secrets.token_keys(&state.config),
+            login_request,
+            &client,
+        )
+        .await?;
 
         Ok(Json(NpmUserResponse {
             ok: true,
@@ -65,9 +78,21 @@ pub async fn npm_login(
             name: user_doc.name.clone(),
             email,
             password: user_doc.password.clone(),
+            invite_token: None,
         };
 
-        let _user = AuthService::register_user(&state.database, register_request)?;
+        let user =
+            AuthService::register_new_user(&state.database, &state.config, register_request)?;
+
+        // npm login without an email gets a placeholder address, nothing to verify there
+        if user_doc.email.is_some()
+            && let Err(e) = AccountService::send_verification_email(state, &user).await
+        {
+            warn!(
+                "Failed to send verification email to {}: {e:?}",
+                user.username
+            );
+        }
 
         // Create authentication token for the new user
         let login_request = LoginRequest {
@@ -75,7 +100,16 @@ pub async fn npm_login(
             password: user_doc.password.clone(),
         };
 
-        let (_user, token) = AuthService::authenticate_user(&state.database, login_request)?;
+        let (_user, token) = AuthService::authenticate_user(
+            &state.database,
+            &state.config,
+            &state.plugins,
+            &state.rate_limiter,
+            &state.secrets.token_keys(&state.config),
+            login_request,
+            &client,
+        )
+        .await?;
 
         Ok(Json(NpmUserResponse {
             ok: true,
@@ -102,7 +136,186 @@ pub async fn npm_logout(
     state: &State<AppState>,
 ) -> Result<Json<LogoutResponse>, ApiError> {
     // Revoke the token
-    AuthService::revoke_token(&state.database, token)?;
+    AuthService::revoke_token(
+        &state.database,
+        &state.secrets.token_keys(&state.config),
+        token,
+    )?;
+
+    Ok(Json(LogoutResponse { ok: true }))
+}
+
+/// Send a new email verification link to the current user
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/auth/verify-email/send")]
+pub async fn send_verification_email(
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let user = AuthService::get_user_by_username(&state.database, &user.username)?
+        .ok_or_else(|| ApiError::Unauthorized("User not found".to_string()))?;
+
+    if user.email_verified {
+        return Err(ApiError::BadRequest(
+            "Email is already verified".to_string(),
+        ));
+    }
+
+    AccountService::send_verification_email(state, &user).await?;
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Confirm an email address with the token from the verification email
+#[utoipa::path(
+    tag = "auth",
+    request_body = VerifyEmailRequest,
+    responses((status = 200, body = Object))
+)]
+#[post("/api/v1/auth/verify-email", data = "<request>")]
+pub async fn verify_email(
+    request: Json<VerifyEmailRequest>,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let user = AccountService::verify_email(&state.database, &state.config, &request.token)?;
+
+    Ok(Json(
+        serde_json::json!({ "ok": true, "username": user.username }),
+    ))
+}
+
+/// Link target of the verification email
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, body = Object))
+)]
+#[get("/api/v1/auth/verify-email?<token>")]
+pub async fn verify_email_link(
+    token: &str,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let user = AccountService::verify_email(&state.database, &state.config, token)?;
+
+    Ok(Json(
+        serde_json::json!({ "ok": true, "username": user.username }),
+    ))
+}
+
+/// Request a password reset email. Always succeeds for unknown addresses.
+#[utoipa::path(
+    tag = "auth",
+    request_body = PasswordResetRequest,
+    responses((status = 200, body = Object))
+)]
+#[post("/api/v1/auth/password-reset/request", data = "<request>")]
+pub async fn request_password_reset(
+    request: Json<PasswordResetRequest>,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    AccountService::request_password_reset(state, &request.email).await?;
 
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Set a new password with the token from the password reset email
+#[utoipa::path(
+    tag = "auth",
+    request_body = PasswordResetConfirmRequest,
+    responses((status = 200, body = Object))
+)]
+#[post("/api/v1/auth/password-reset", data = "<request>")]
+pub async fn reset_password(
+    request: Json<PasswordResetConfirmRequest>,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let user = AccountService::reset_password(
+        &state.database,
+        &state.config,
+        &request.token,
+        &request.password,
+    )?;
+
+    Ok(Json(
+        serde_json::json!({ "ok": true, "username": user.username }),
+    ))
+}
+
+/// Active tokens of the current user, with when and from where they were last used
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, body = SessionListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/user/sessions")]
+pub async fn list_sessions(
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<SessionListResponse>, ApiError> {
+    let sessions = AuthService::list_sessions(&state.database, user.user_id, user.token_id)?;
+    Ok(Json(SessionListResponse { sessions }))
+}
+
+/// Storage used by the packages the current user published outside of organizations
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, body = StorageUsageResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/user/usage")]
+pub async fn get_user_usage(
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<StorageUsageResponse>, ApiError> {
+    QuotaService::user_usage(user.user_id, &user.username, state).map(Json)
+}
+
+/// Revoke a single token of the current user, e.g. a leaked CI token
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, body = LogoutResponse)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/user/sessions/<id>")]
+pub async fn revoke_session(
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<LogoutResponse>, ApiError> {
+    AuthService::revoke_session(&state.database, user.user_id, id)?;
+    Ok(Json(LogoutResponse { ok: true }))
+}
+
+/// Client certificates the current user authenticates with over mutual TLS
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, body = ClientCertificatesResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/user/certificates")]
+pub async fn list_client_certificates(
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<ClientCertificatesResponse>, ApiError> {
+    let certificates = ClientCertificateService::list(&state.database, user.user_id)?;
+    Ok(Json(ClientCertificatesResponse { certificates }))
+}
+
+/// Remove a client certificate of the current user
+#[utoipa::path(
+    tag = "auth",
+    responses((status = 200, body = LogoutResponse)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/user/certificates/<id>")]
+pub async fn remove_client_certificate(
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<LogoutResponse>, ApiError> {
+    ClientCertificateService::remove(&state.database, user.user_id, id)?;
     Ok(Json(LogoutResponse { ok: true }))
 }
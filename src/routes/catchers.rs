@@ -0,0 +1,55 @@
+use crate::error::ErrorCode;
+use crate::fairings::RequestId;
+use crate::state::AppState;
+use rocket::http::{Header, Status};
+use rocket::serde::json::{Json, Value, json};
+use rocket::{Request, Responder, catch};
+
+#[derive(Responder)]
+#[response(status = 401, content_type = "json")]
+pub struct UnauthorizedResponse {
+    body: Json<Value>,
+    authenticate: Header<'static>,
+}
+
+/// JSON 401 with a `WWW-Authenticate` challenge. npm reports this as E401 and asks the
+/// user to run `npm login` instead of failing with an opaque HTML page.
+#[catch(401)]
+pub fn unauthorized(request: &Request<'_>) -> UnauthorizedResponse {
+    UnauthorizedResponse {
+        body: Json(json!({
+            "error": "authentication required",
+            "reason": "You must be logged in to access this registry, run `npm login`",
+            "code": ErrorCode::E401,
+            "request_id": RequestId::of(request).to_string(),
+        })),
+        authenticate: Header::new("WWW-Authenticate", "Bearer realm=\"clef\""),
+    }
+}
+
+/// Request bodies over the JSON limit never reach the publish route, so the size limit
+/// is explained here
+#[catch(413)]
+pub fn payload_too_large(request: &Request<'_>) -> (Status, Json<Value>) {
+    let max_size = request
+        .rocket()
+        .state::<AppState>()
+        .map(|state| state.config.max_publish_size_bytes);
+
+    let reason = match max_size {
+        Some(max_size) => {
+            format!("The request body is too large, packages may be at most {max_size} bytes")
+        }
+        None => "The request body is too large".to_string(),
+    };
+
+    (
+        Status::PayloadTooLarge,
+        Json(json!({
+            "error": "payload too large",
+            "reason": reason,
+            "code": ErrorCode::E413,
+            "request_id": RequestId::of(request).to_string(),
+        })),
+    )
+}
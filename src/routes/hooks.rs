@@ -0,0 +1,69 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, HookListResponse, HookRequest, HookResponse, HookUpdateRequest,
+};
+use crate::services::HookService;
+use crate::state::AppState;
+use rocket::serde::json::Json;
+use rocket::{State, delete, get, post, put};
+
+// npm hook API used by `npm hook`
+
+#[post("/registry/-/npm/v1/hooks/hook", data = "<request>")]
+pub async fn add_hook(
+    request: Json<HookRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<HookResponse>, ApiError> {
+    Ok(Json(HookService::create(
+        request.into_inner(),
+        &user,
+        state,
+    )?))
+}
+
+#[get("/registry/-/npm/v1/hooks?<package>&<limit>&<offset>")]
+pub async fn list_hooks(
+    package: Option<&str>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<HookListResponse>, ApiError> {
+    Ok(Json(HookService::list(
+        package, limit, offset, &user, state,
+    )?))
+}
+
+#[get("/registry/-/npm/v1/hooks/hook/<id>")]
+pub async fn get_hook(
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<HookResponse>, ApiError> {
+    Ok(Json(HookService::get(id, &user, state)?))
+}
+
+#[put("/registry/-/npm/v1/hooks/hook/<id>", data = "<request>")]
+pub async fn update_hook(
+    id: i32,
+    request: Json<HookUpdateRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<HookResponse>, ApiError> {
+    Ok(Json(HookService::update(
+        id,
+        request.into_inner(),
+        &user,
+        state,
+    )?))
+}
+
+#[delete("/registry/-/npm/v1/hooks/hook/<id>")]
+pub async fn delete_hook(
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<HookResponse>, ApiError> {
+    Ok(Json(HookService::delete(id, &user, state)?))
+}
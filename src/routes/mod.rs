@@ -1,35 +1,159 @@
+pub mod admin;
 pub mod api;
 pub mod auth;
+pub mod catchers;
+pub mod hooks;
+pub mod openapi;
 pub mod organizations;
 pub mod packages;
 pub mod publish;
+pub mod search;
 pub mod security;
+pub mod signing;
 pub mod static_files;
+pub mod users;
 
-use rocket::routes;
+use crate::fairings::RequestIdScope;
+use rocket::{catchers, routes};
 
 pub fn get_routes() -> Vec<rocket::Route> {
     let api_routes = routes![
         // API routes with /api/v1/ prefix
         api::health_check,
+        api::metrics,
+        openapi::openapi_json,
+        api::maintenance_status,
+        api::maintenance_unavailable,
+        api::access_denied,
+        api::rate_limited,
         api::list_packages,
         api::get_package_versions,
+        api::get_package_readme,
+        api::get_package_files,
+        api::get_package_size,
+        api::get_package_history,
+        api::get_package_downloads,
+        api::update_package_visibility,
+        api::transfer_package,
+        api::get_package_visibility,
+        api::yank_version,
+        api::unyank_version,
+        api::unpublish_version,
         api::get_popular_packages,
+        api::get_recent_versions,
+        api::get_dashboard,
+        api::live_metrics,
         api::get_cache_analytics,
+        api::get_package_cache_analytics,
         api::get_cache_stats,
         api::clear_cache,
+        api::list_cache_entries,
+        api::invalidate_cached_package,
         api::cache_health,
         api::reprocess_cache,
+        api::collect_cache_garbage,
+        api::prefetch,
+        api::get_task,
+        api::download_task_result,
         api::login,
         api::register,
+        // Account routes
+        auth::send_verification_email,
+        auth::verify_email,
+        auth::verify_email_link,
+        auth::request_password_reset,
+        auth::reset_password,
+        // Session routes
+        auth::list_sessions,
+        auth::get_user_usage,
+        auth::revoke_session,
+        auth::list_client_certificates,
+        auth::remove_client_certificate,
+        // Admin routes
+        admin::export_package,
+        admin::import_package,
+        admin::get_config,
+        admin::list_users,
+        admin::disable_user,
+        admin::enable_user,
+        admin::update_user_role,
+        admin::add_user_certificate,
+        admin::delete_user,
+        admin::reset_user_password,
+        admin::create_invitation,
+        admin::list_invitations,
+        admin::delete_invitation,
+        admin::list_scope_policies,
+        admin::set_scope_policy,
+        admin::delete_scope_policy,
+        admin::list_blocked_names,
+        admin::add_blocked_name,
+        admin::delete_blocked_name,
+        admin::list_allowed_packages,
+        admin::add_allowed_package,
+        admin::delete_allowed_package,
+        admin::list_pinned_packages,
+        admin::add_pinned_package,
+        admin::delete_pinned_package,
+        admin::refresh_pinned_packages,
+        admin::list_flagged_names,
+        admin::approve_flagged_name,
+        admin::reject_flagged_name,
+        admin::get_maintenance,
+        admin::enable_maintenance,
+        admin::disable_maintenance,
+        admin::optimize_database,
+        admin::list_quarantined_packages,
+        admin::approve_quarantined_package,
+        admin::reject_quarantined_package,
+        admin::list_jobs,
+        admin::bandwidth_report,
+        admin::schema_status,
+        admin::advisory_status,
+        admin::sync_advisories,
+        admin::list_internal_advisories,
+        admin::create_internal_advisory,
+        admin::update_internal_advisory,
+        admin::delete_internal_advisory,
+        // User profile routes
+        users::get_user_profile,
+        users::get_user_packages,
+        users::update_profile,
+        users::list_notification_subscriptions,
+        users::create_notification_subscription,
+        users::delete_notification_subscription,
         // Organization routes
         organizations::create_organization,
         organizations::get_organization,
+        organizations::list_organization_packages,
+        organizations::get_organization_usage,
         organizations::update_organization,
         organizations::delete_organization,
         organizations::add_member,
         organizations::update_member_role,
         organizations::remove_member,
+        organizations::list_retention_policies,
+        organizations::set_retention_policy,
+        organizations::delete_retention_policy,
+        organizations::apply_retention_policies,
+        organizations::get_audit_log,
+        organizations::list_service_accounts,
+        organizations::create_service_account,
+        organizations::delete_service_account,
+        organizations::create_service_account_token,
+        organizations::list_service_account_tokens,
+        organizations::revoke_service_account_tokens,
+        organizations::list_service_account_certificates,
+        organizations::add_service_account_certificate,
+        organizations::remove_service_account_certificate,
+        // Signing key routes
+        signing::create_signing_key,
+        signing::list_signing_keys,
+        signing::revoke_signing_key,
+        signing::verify_signatures,
+        signing::npm_registry_keys,
+        signing::npm_attestations,
+        signing::npm_attestations_root,
         // Registry routes (used by npm client - no prefix change)
         // Scoped package routes (higher priority)
         packages::handle_scoped_package_metadata,
@@ -44,10 +168,22 @@ pub fn get_routes() -> Vec<rocket::Route> {
         // Catch-all route (lowest priority)
         packages::handle_package_request,
         packages::handle_package_head_request,
+        // Files served out of tarballs
+        packages::handle_package_file,
+        // Feed of versions entering the registry
+        packages::packages_feed,
         // Security routes (used by npm client)
         security::security_advisories_bulk,
         security::security_audits,
         security::security_audits_quick,
+        // npm search route
+        search::search,
+        // npm hook routes
+        hooks::add_hook,
+        hooks::list_hooks,
+        hooks::get_hook,
+        hooks::update_hook,
+        hooks::delete_hook,
         // NPM-specific auth routes (used by npm client)
         auth::npm_login,
         auth::npm_whoami,
@@ -55,10 +191,17 @@ pub fn get_routes() -> Vec<rocket::Route> {
         // NPM publish routes
         publish::npm_publish_scoped,
         publish::npm_publish,
+        publish::list_staged_publishes,
+        publish::promote_staged_publish,
+        publish::discard_staged_publish,
     ];
 
     // Add static file routes (lowest priority)
     let mut all_routes = api_routes;
     all_routes.extend(static_files::get_static_routes());
-    all_routes
+    all_routes.into_iter().map(RequestIdScope::wrap).collect()
+}
+
+pub fn get_catchers() -> Vec<rocket::Catcher> {
+    catchers![catchers::unauthorized, catchers::payload_too_large]
 }
@@ -0,0 +1,218 @@
+use super::{admin, api, auth, organizations, publish, signing, users};
+use crate::error::{ErrorBody, ErrorCode};
+use rocket::get;
+use rocket::serde::json::Json;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::{Content, RefOr, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+/// OpenAPI document of the `/api/v1` routes. Registry routes used by package managers
+/// follow the npm registry API and aren't part of it.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Clef API", description = "Management API of the Clef npm registry"),
+    paths(
+        api::health_check,
+        api::metrics,
+        api::maintenance_status,
+        api::maintenance_unavailable,
+        api::access_denied,
+        api::rate_limited,
+        api::list_packages,
+        api::get_package_versions,
+        api::get_package_readme,
+        api::get_package_files,
+        api::get_package_size,
+        api::get_package_history,
+        api::get_package_downloads,
+        api::update_package_visibility,
+        api::transfer_package,
+        api::yank_version,
+        api::unpublish_version,
+        api::unyank_version,
+        publish::list_staged_publishes,
+        publish::promote_staged_publish,
+        publish::discard_staged_publish,
+        api::get_package_visibility,
+        api::get_popular_packages,
+        api::get_recent_versions,
+        api::get_dashboard,
+        api::live_metrics,
+        api::get_cache_analytics,
+        api::get_package_cache_analytics,
+        api::get_cache_stats,
+        api::clear_cache,
+        api::list_cache_entries,
+        api::invalidate_cached_package,
+        api::cache_health,
+        api::reprocess_cache,
+        api::collect_cache_garbage,
+        api::prefetch,
+        api::get_task,
+        api::download_task_result,
+        api::login,
+        api::register,
+        auth::send_verification_email,
+        auth::verify_email,
+        auth::verify_email_link,
+        auth::request_password_reset,
+        auth::reset_password,
+        auth::list_sessions,
+        auth::get_user_usage,
+        auth::revoke_session,
+        auth::list_client_certificates,
+        auth::remove_client_certificate,
+        admin::export_package,
+        admin::import_package,
+        admin::get_config,
+        admin::list_users,
+        admin::disable_user,
+        admin::enable_user,
+        admin::update_user_role,
+        admin::add_user_certificate,
+        admin::delete_user,
+        admin::reset_user_password,
+        admin::create_invitation,
+        admin::list_invitations,
+        admin::delete_invitation,
+        admin::list_scope_policies,
+        admin::set_scope_policy,
+        admin::delete_scope_policy,
+        admin::list_blocked_names,
+        admin::add_blocked_name,
+        admin::delete_blocked_name,
+        admin::list_allowed_packages,
+        admin::add_allowed_package,
+        admin::delete_allowed_package,
+        admin::list_pinned_packages,
+        admin::add_pinned_package,
+        admin::delete_pinned_package,
+        admin::refresh_pinned_packages,
+        admin::list_flagged_names,
+        admin::approve_flagged_name,
+        admin::reject_flagged_name,
+        admin::get_maintenance,
+        admin::enable_maintenance,
+        admin::disable_maintenance,
+        admin::optimize_database,
+        admin::list_quarantined_packages,
+        admin::approve_quarantined_package,
+        admin::reject_quarantined_package,
+        admin::list_jobs,
+        admin::bandwidth_report,
+        admin::schema_status,
+        admin::advisory_status,
+        admin::sync_advisories,
+        admin::list_internal_advisories,
+        admin::create_internal_advisory,
+        admin::update_internal_advisory,
+        admin::delete_internal_advisory,
+        users::get_user_profile,
+        users::get_user_packages,
+        users::update_profile,
+        users::list_notification_subscriptions,
+        users::create_notification_subscription,
+        users::delete_notification_subscription,
+        organizations::create_organization,
+        organizations::get_organization,
+        organizations::list_organization_packages,
+        organizations::get_organization_usage,
+        organizations::update_organization,
+        organizations::delete_organization,
+        organizations::add_member,
+        organizations::update_member_role,
+        organizations::remove_member,
+        organizations::list_retention_policies,
+        organizations::set_retention_policy,
+        organizations::delete_retention_policy,
+        organizations::apply_retention_policies,
+        organizations::get_audit_log,
+        organizations::list_service_accounts,
+        organizations::create_service_account,
+        organizations::delete_service_account,
+        organizations::create_service_account_token,
+        organizations::list_service_account_tokens,
+        organizations::revoke_service_account_tokens,
+        organizations::list_service_account_certificates,
+        organizations::add_service_account_certificate,
+        organizations::remove_service_account_certificate,
+        signing::create_signing_key,
+        signing::list_signing_keys,
+        signing::revoke_signing_key,
+        signing::verify_signatures,
+    ),
+    tags(
+        (name = "status", description = "Health and maintenance status"),
+        (name = "packages", description = "Packages, versions and download statistics"),
+        (name = "cache", description = "Upstream cache"),
+        (name = "tasks", description = "Long-running operations queued in the background"),
+        (name = "auth", description = "Accounts, sessions and email verification"),
+        (name = "users", description = "User profiles and the packages they own"),
+        (name = "organizations", description = "Organizations, members and retention policies"),
+        (name = "signing", description = "Signing keys and signature verification"),
+        (name = "admin", description = "Registry administration, admins only"),
+    ),
+    components(schemas(ErrorBody, ErrorCode)),
+    modifiers(&BearerAuth, &ErrorResponses)
+)]
+pub struct ApiDoc;
+
+/// Tokens from `/api/v1/login` or `npm login`, sent as `Authorization: Bearer <token>`
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+/// Every operation can fail with an `ApiError`, documented once as the `Error` response
+/// with an `ErrorBody`
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let error = ResponseBuilder::new()
+            .description("The request failed, `error` explains why")
+            .content(
+                "application/json",
+                Content::new(Some(RefOr::Ref(utoipa::openapi::Ref::from_schema_name(
+                    "ErrorBody",
+                )))),
+            )
+            .build();
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .responses
+            .insert("Error".to_string(), RefOr::T(error));
+
+        for item in openapi.paths.paths.values_mut() {
+            for operation in [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                operation.responses.responses.insert(
+                    "default".to_string(),
+                    RefOr::Ref(utoipa::openapi::Ref::from_response_name("Error")),
+                );
+            }
+        }
+    }
+}
+
+/// OpenAPI 3 document of the management API
+#[get("/api/v1/openapi.json")]
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
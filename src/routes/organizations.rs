@@ -1,11 +1,24 @@
 use crate::error::ApiError;
 use crate::models::auth::AuthenticatedUser;
 use crate::models::organization::*;
+use crate::models::{
+    AuditLogActorEntry, AuditLogResponse, ClientCertificate, ClientCertificateRequest,
+    ClientCertificatesResponse, CreateServiceAccountRequest, PackageListResponse, RetentionPolicy,
+    RetentionPolicyListResponse, RetentionPolicyRequest, RetentionReport, ServiceAccountResponse,
+    ServiceAccountTokenResponse, ServiceAccountsResponse, SessionListResponse,
+};
+use crate::services::{OrganizationService, RetentionService, ServiceAccountService};
 use crate::state::AppState;
 use rocket::serde::json::Json;
 use rocket::{State, delete, get, post, put};
 
 /// Create a new organization
+#[utoipa::path(
+    tag = "organizations",
+    request_body = CreateOrganizationRequest,
+    responses((status = 200, body = Organization)),
+    security(("bearer" = []))
+)]
 #[post("/api/v1/organizations", data = "<request>")]
 pub async fn create_organization(
     request: Json<CreateOrganizationRequest>,
@@ -35,6 +48,11 @@ pub async fn create_organization(
 }
 
 /// Get organization by name
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = OrganizationWithMembers)),
+    security(("bearer" = []))
+)]
 #[get("/api/v1/organizations/<name>")]
 pub async fn get_organization(
     name: &str,
@@ -70,7 +88,75 @@ pub async fn get_organization(
     }))
 }
 
+/// Packages published under an organization's scope, private ones included
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = PackageListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/organizations/<name>/packages?<limit>&<page>&<search>")]
+pub async fn list_organization_packages(
+    name: &str,
+    limit: Option<i64>,
+    page: Option<i64>,
+    search: Option<&str>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<PackageListResponse>, ApiError> {
+    let organization = member_organization(name, &user, state)?;
+    OrganizationService::packages(&organization, limit, page, search, state).map(Json)
+}
+
+/// Storage used by the packages of an organization against its quota, their downloads and
+/// the publishes of each member
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = OrganizationUsageResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/organizations/<name>/usage")]
+pub async fn get_organization_usage(
+    name: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<OrganizationUsageResponse>, ApiError> {
+    let organization = member_organization(name, &user, state)?;
+    OrganizationService::usage(&organization, state).map(Json)
+}
+
+/// The organization, if the user is a member of it
+fn member_organization(
+    name: &str,
+    user: &AuthenticatedUser,
+    state: &AppState,
+) -> Result<Organization, ApiError> {
+    let organization = state
+        .database
+        .get_organization_by_name(name)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .ok_or_else(|| ApiError::NotFound(format!("Organization '{name}' not found")))?;
+
+    let is_member = state
+        .database
+        .check_organization_permission(organization.id, user.user_id, OrganizationRole::Member)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    if !is_member {
+        return Err(ApiError::Forbidden(
+            "You are not a member of this organization".to_string(),
+        ));
+    }
+
+    Ok(organization)
+}
+
 /// Update organization
+#[utoipa::path(
+    tag = "organizations",
+    request_body = UpdateOrganizationRequest,
+    responses((status = 200, body = Organization)),
+    security(("bearer" = []))
+)]
 #[put("/api/v1/organizations/<name>", data = "<request>")]
 pub async fn update_organization(
     name: &str,
@@ -109,6 +195,11 @@ pub async fn update_organization(
 }
 
 /// Delete organization
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
 #[delete("/api/v1/organizations/<name>")]
 pub async fn delete_organization(
     name: &str,
@@ -152,6 +243,12 @@ pub async fn delete_organization(
 }
 
 /// Add member to organization
+#[utoipa::path(
+    tag = "organizations",
+    request_body = AddMemberRequest,
+    responses((status = 200, body = OrganizationMember)),
+    security(("bearer" = []))
+)]
 #[post("/api/v1/organizations/<name>/members", data = "<request>")]
 pub async fn add_member(
     name: &str,
@@ -187,6 +284,12 @@ pub async fn add_member(
         .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
         .ok_or_else(|| ApiError::NotFound(format!("User '{}' not found", request.username)))?;
 
+    if target_user.is_service_account() {
+        return Err(ApiError::BadRequest(
+            "Service accounts belong to the organization that created them".to_string(),
+        ));
+    }
+
     let member = state
         .database
         .add_organization_member(organization.id, target_user.id, &request.role)
@@ -202,6 +305,12 @@ pub async fn add_member(
 }
 
 /// Update member role
+#[utoipa::path(
+    tag = "organizations",
+    request_body = UpdateMemberRequest,
+    responses((status = 200, body = OrganizationMember)),
+    security(("bearer" = []))
+)]
 #[put("/api/v1/organizations/<name>/members/<username>", data = "<request>")]
 pub async fn update_member_role(
     name: &str,
@@ -238,6 +347,12 @@ pub async fn update_member_role(
         .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
         .ok_or_else(|| ApiError::NotFound(format!("User '{username}' not found")))?;
 
+    if target_user.is_service_account() {
+        return Err(ApiError::BadRequest(
+            "Service accounts are always plain members".to_string(),
+        ));
+    }
+
     let updated_member = state
         .database
         .update_organization_member_role(organization.id, target_user.id, &request.role)
@@ -247,6 +362,11 @@ pub async fn update_member_role(
 }
 
 /// Remove member from organization
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
 #[delete("/api/v1/organizations/<name>/members/<username>")]
 pub async fn remove_member(
     name: &str,
@@ -298,3 +418,282 @@ pub async fn remove_member(
         "message": format!("User '{}' removed from organization '{}'", username, name)
     })))
 }
+
+/// List the retention policies of an organization
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = RetentionPolicyListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/organizations/<name>/retention")]
+pub async fn list_retention_policies(
+    name: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<RetentionPolicyListResponse>, ApiError> {
+    RetentionService::list(name, &user, state).map(Json)
+}
+
+/// Create or replace a retention policy, owners only
+#[utoipa::path(
+    tag = "organizations",
+    request_body = RetentionPolicyRequest,
+    responses((status = 200, body = RetentionPolicy)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/organizations/<name>/retention/<policy>", data = "<request>")]
+pub async fn set_retention_policy(
+    name: &str,
+    policy: &str,
+    request: Json<RetentionPolicyRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<RetentionPolicy>, ApiError> {
+    RetentionService::set_policy(name, policy, request.into_inner(), &user, state).map(Json)
+}
+
+/// Delete a retention policy, owners only
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/organizations/<name>/retention/<policy>")]
+pub async fn delete_retention_policy(
+    name: &str,
+    policy: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    RetentionService::delete_policy(name, policy, &user, state)?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Retention policy '{policy}' deleted from organization '{name}'")
+    })))
+}
+
+/// Apply the retention policies of an organization now. With `dry_run=true` nothing is
+/// deleted, otherwise each policy's own dry run setting applies.
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = RetentionReport)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/organizations/<name>/retention/apply?<dry_run>")]
+pub async fn apply_retention_policies(
+    name: &str,
+    dry_run: Option<bool>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<RetentionReport>, ApiError> {
+    RetentionService::apply_organization(name, dry_run.unwrap_or(false), &user, state)
+        .await
+        .map(Json)
+}
+
+/// Latest audit log entries of an organization, admins only
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = AuditLogResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/organizations/<name>/audit?<limit>")]
+pub async fn get_audit_log(
+    name: &str,
+    limit: Option<i64>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<AuditLogResponse>, ApiError> {
+    let organization = state
+        .database
+        .get_organization_by_name(name)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .ok_or_else(|| ApiError::NotFound(format!("Organization '{name}' not found")))?;
+
+    let is_admin = user.is_admin
+        || state
+            .database
+            .check_organization_permission(organization.id, user.user_id, OrganizationRole::Admin)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    if !is_admin {
+        return Err(ApiError::Forbidden(
+            "You don't have permission to view the audit log of this organization".to_string(),
+        ));
+    }
+
+    let entries = state
+        .database
+        .list_organization_audit_log(organization.id, limit.unwrap_or(100).clamp(1, 1000))
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .into_iter()
+        .map(AuditLogActorEntry::from)
+        .collect();
+
+    Ok(Json(AuditLogResponse { entries }))
+}
+
+/// List the service accounts of an organization, admins only
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = ServiceAccountsResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/organizations/<name>/service-accounts")]
+pub async fn list_service_accounts(
+    name: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<ServiceAccountsResponse>, ApiError> {
+    ServiceAccountService::list(name, &user, state).map(Json)
+}
+
+/// Create a service account, a non-interactive member for CI pipelines. Admins only.
+#[utoipa::path(
+    tag = "organizations",
+    request_body = CreateServiceAccountRequest,
+    responses((status = 200, body = ServiceAccountResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/organizations/<name>/service-accounts", data = "<request>")]
+pub async fn create_service_account(
+    name: &str,
+    request: Json<CreateServiceAccountRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<ServiceAccountResponse>, ApiError> {
+    ServiceAccountService::create(name, request.into_inner(), &user, state).map(Json)
+}
+
+/// Delete a service account and its tokens, admins only
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/organizations/<name>/service-accounts/<account>")]
+pub async fn delete_service_account(
+    name: &str,
+    account: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    ServiceAccountService::delete(name, account, &user, state)?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Service account '{account}' deleted from organization '{name}'")
+    })))
+}
+
+/// Issue a publish token for a service account. The token is only shown once.
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = ServiceAccountTokenResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/organizations/<name>/service-accounts/<account>/tokens")]
+pub async fn create_service_account_token(
+    name: &str,
+    account: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<ServiceAccountTokenResponse>, ApiError> {
+    ServiceAccountService::issue_token(name, account, &user, state).map(Json)
+}
+
+/// List the active tokens of a service account with their last use, admins only
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = SessionListResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/organizations/<name>/service-accounts/<account>/tokens")]
+pub async fn list_service_account_tokens(
+    name: &str,
+    account: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<SessionListResponse>, ApiError> {
+    let sessions = ServiceAccountService::tokens(name, account, &user, state)?;
+    Ok(Json(SessionListResponse { sessions }))
+}
+
+/// Revoke every token of a service account
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/organizations/<name>/service-accounts/<account>/tokens")]
+pub async fn revoke_service_account_tokens(
+    name: &str,
+    account: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    ServiceAccountService::revoke_tokens(name, account, &user, state)?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Tokens of service account '{account}' revoked")
+    })))
+}
+
+/// List the client certificates of a service account, admins only
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = ClientCertificatesResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/organizations/<name>/service-accounts/<account>/certificates")]
+pub async fn list_service_account_certificates(
+    name: &str,
+    account: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<ClientCertificatesResponse>, ApiError> {
+    let certificates = ServiceAccountService::certificates(name, account, &user, state)?;
+    Ok(Json(ClientCertificatesResponse { certificates }))
+}
+
+/// Register a client certificate for a service account, registry admins only
+#[utoipa::path(
+    tag = "organizations",
+    request_body = ClientCertificateRequest,
+    responses((status = 200, body = ClientCertificate)),
+    security(("bearer" = []))
+)]
+#[post(
+    "/api/v1/organizations/<name>/service-accounts/<account>/certificates",
+    data = "<request>"
+)]
+pub async fn add_service_account_certificate(
+    name: &str,
+    account: &str,
+    request: Json<ClientCertificateRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<ClientCertificate>, ApiError> {
+    ServiceAccountService::add_certificate(name, account, request.into_inner(), &user, state)
+        .map(Json)
+}
+
+/// Remove a client certificate of a service account, admins only
+#[utoipa::path(
+    tag = "organizations",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/organizations/<name>/service-accounts/<account>/certificates/<id>")]
+pub async fn remove_service_account_certificate(
+    name: &str,
+    account: &str,
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    ServiceAccountService::remove_certificate(name, account, id, &user, state)?;
+
+    Ok(Json(serde_json::json!({
+        "message": format!("Certificate {id} removed from service account '{account}'")
+    })))
+}
@@ -1,15 +1,20 @@
 use crate::error::ApiError;
-use crate::models::OptionalAuthenticatedUser;
-use crate::services::RegistryService;
+use crate::models::RegistryReader;
+use crate::services::{
+    DownloadStatsService, FeedService, PackageFilesService, PolicyService, QuarantineService,
+    RegistryService, ScopePolicyService,
+};
 use crate::state::AppState;
 use log;
-use rocket::http::{ContentType, Status};
+use rocket::http::uri::Segments;
+use rocket::http::{ContentType, HeaderMap, Status};
 use rocket::serde::json::Value;
 use rocket::{
     Response, State, get, head,
     request::{FromParam, FromRequest, Outcome, Request},
     response::Responder,
 };
+use sha2::{Digest, Sha256};
 use std::io::Cursor;
 
 // Custom request guard to extract URI path
@@ -25,39 +30,118 @@ impl<'r> FromRequest<'r> for UriPath {
     }
 }
 
+// Custom request guard to extract the If-Modified-Since header
+pub struct IfModifiedSince(pub Option<String>);
+
+impl IfModifiedSince {
+    /// Whether the client's copy is at least as recent as `last_modified`. Dates that
+    /// can't be parsed never match, the tarball is sent then.
+    fn is_fresh(&self, last_modified: &str) -> bool {
+        let parse = |date: &str| chrono::DateTime::parse_from_rfc2822(date.trim()).ok();
+        match (self.0.as_deref().and_then(parse), parse(last_modified)) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfModifiedSince {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let since = request.headers().get_one("If-Modified-Since");
+        Outcome::Success(IfModifiedSince(since.map(str::to_string)))
+    }
+}
+
 // Custom request guard to extract Host header and scheme
 pub struct RequestInfo {
     pub host: Option<String>,
     pub scheme: String,
 }
 
+impl RequestInfo {
+    /// Origin the client used. Behind a trusted reverse proxy this comes from the `Forwarded`
+    /// or `X-Forwarded-*` headers, otherwise from `Host` and the configured scheme.
+    fn of_headers(
+        headers: &HeaderMap<'_>,
+        trust_proxy_headers: bool,
+        default_scheme: &str,
+    ) -> Self {
+        let mut host = headers.get_one("Host").map(str::to_string);
+        let mut scheme = default_scheme.to_string();
+
+        if !trust_proxy_headers {
+            return RequestInfo { host, scheme };
+        }
+
+        // Only the first entry matters, it was added by the proxy closest to the client
+        let first = |value: &str| {
+            value
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+
+        if let Some(forwarded) = headers.get_one("Forwarded") {
+            for pair in first(forwarded).split(';') {
+                let Some((key, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim().trim_matches('"');
+                match key.trim().to_lowercase().as_str() {
+                    "proto" if !value.is_empty() => scheme = value.to_lowercase(),
+                    "host" if !value.is_empty() => host = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            return RequestInfo { host, scheme };
+        }
+
+        if let Some(proto) = headers.get_one("X-Forwarded-Proto") {
+            scheme = first(proto).to_lowercase();
+        } else if let Some(ssl) = headers.get_one("X-Forwarded-SSL")
+            && ssl.eq_ignore_ascii_case("on")
+        {
+            scheme = "https".to_string();
+        }
+
+        if let Some(forwarded_host) = headers.get_one("X-Forwarded-Host") {
+            let mut forwarded_host = first(forwarded_host);
+            let default_port = if scheme == "https" { "443" } else { "80" };
+            if let Some(port) = headers.get_one("X-Forwarded-Port").map(first)
+                && !forwarded_host.contains(':')
+                && port != default_port
+            {
+                forwarded_host = format!("{forwarded_host}:{port}");
+            }
+            host = Some(forwarded_host);
+        }
+
+        RequestInfo { host, scheme }
+    }
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for RequestInfo {
     type Error = ();
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let host = request.headers().get_one("Host").map(|s| s.to_string());
-
-        // Determine scheme from various sources
-        let scheme = if let Some(forwarded_proto) = request.headers().get_one("X-Forwarded-Proto") {
-            // Check X-Forwarded-Proto header (common with reverse proxies)
-            forwarded_proto.to_string()
-        } else if let Some(forwarded_ssl) = request.headers().get_one("X-Forwarded-SSL") {
-            // Check X-Forwarded-SSL header
-            if forwarded_ssl.to_lowercase() == "on" {
-                "https".to_string()
-            } else {
-                "http".to_string()
-            }
-        } else {
-            // Fall back to checking if we're behind a proxy or default to http
-            match request.headers().get_one("X-Forwarded-For") {
-                Some(_) => "https".to_string(), // Assume HTTPS if behind a proxy
-                None => "http".to_string(),     // Default to HTTP
-            }
-        };
-
-        Outcome::Success(RequestInfo { host, scheme })
+        let config = request
+            .rocket()
+            .state::<AppState>()
+            .map(|state| &state.config);
+        let trust_proxy_headers = config.is_some_and(|config| config.trust_proxy_headers);
+        let default_scheme = config.map_or("http", |config| config.get_scheme());
+
+        Outcome::Success(RequestInfo::of_headers(
+            request.headers(),
+            trust_proxy_headers,
+            default_scheme,
+        ))
     }
 }
 
@@ -65,22 +149,77 @@ impl<'r> FromRequest<'r> for RequestInfo {
 #[derive(Debug)]
 pub enum PackageResponse {
     Json(Value),
-    Binary(Vec<u8>),
-    Empty,
+    /// Tarball with the upstream `Last-Modified` recorded in the cache
+    Binary(Vec<u8>, Option<String>),
+    /// Answer to a HEAD request for a tarball, with its size when known
+    TarballHead(Option<u64>, Option<String>),
+    /// The client's copy of a tarball is still current, carries its `Last-Modified`
+    NotModified(String),
+}
+
+impl PackageResponse {
+    /// Strong validator of a JSON document, derived from the bytes sent
+    fn etag(body: &str) -> String {
+        let digest = Sha256::digest(body.as_bytes());
+        let hex: String = digest[..16]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        format!("\"{hex}\"")
+    }
+
+    /// `time.modified` of a package document as an HTTP date
+    fn last_modified(json: &Value) -> Option<String> {
+        let modified = json.get("time")?.get("modified")?.as_str()?;
+        let modified = chrono::DateTime::parse_from_rfc3339(modified).ok()?;
+        Some(
+            modified
+                .with_timezone(&chrono::Utc)
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        )
+    }
 }
 
+// HEAD requests get the same headers as GET requests, Rocket strips the body and keeps its size
 impl<'r> Responder<'r, 'static> for PackageResponse {
     fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
         match self {
-            PackageResponse::Json(json) => Response::build()
-                .header(ContentType::JSON)
-                .sized_body(json.to_string().len(), Cursor::new(json.to_string()))
-                .ok(),
-            PackageResponse::Binary(data) => Response::build()
-                .header(ContentType::Binary)
-                .sized_body(data.len(), Cursor::new(data))
+            PackageResponse::Json(json) => {
+                let body = json.to_string();
+                let mut response = Response::build();
+                response
+                    .header(ContentType::JSON)
+                    .raw_header("ETag", Self::etag(&body));
+                if let Some(last_modified) = Self::last_modified(&json) {
+                    response.raw_header("Last-Modified", last_modified);
+                }
+                response.sized_body(body.len(), Cursor::new(body)).ok()
+            }
+            PackageResponse::Binary(data, last_modified) => {
+                let mut response = Response::build();
+                response.header(ContentType::Binary);
+                if let Some(last_modified) = last_modified {
+                    response.raw_header("Last-Modified", last_modified);
+                }
+                response.sized_body(data.len(), Cursor::new(data)).ok()
+            }
+            PackageResponse::TarballHead(size, last_modified) => {
+                let mut response = Response::build();
+                response.status(Status::Ok).header(ContentType::Binary);
+                if let Some(last_modified) = last_modified {
+                    response.raw_header("Last-Modified", last_modified);
+                }
+                // The body is never sent, it only carries the size for Content-Length
+                if let Some(size) = size {
+                    response.sized_body(size as usize, Cursor::new(Vec::new()));
+                }
+                response.ok()
+            }
+            PackageResponse::NotModified(last_modified) => Response::build()
+                .status(Status::NotModified)
+                .raw_header("Last-Modified", last_modified)
                 .ok(),
-            PackageResponse::Empty => Response::build().status(Status::Ok).ok(),
         }
     }
 }
@@ -98,6 +237,17 @@ fn decode_package_name(encoded: &str) -> String {
         .replace("%2b", "+") // lowercase variant
 }
 
+/// Name of the package a registry metadata or tarball path is about
+pub(crate) fn package_of_path(path: &str) -> Option<String> {
+    let rest = path.strip_prefix("/registry/")?;
+    if rest.is_empty() || rest.starts_with('-') {
+        return None;
+    }
+    parse_package_path(path)
+        .map(|(name, _)| name)
+        .filter(|name| !name.is_empty())
+}
+
 // Parse package request from URI path
 fn parse_package_path(path: &str) -> Option<(String, PackageRequestType)> {
     // First decode the entire path to handle URL-encoded characters
@@ -162,6 +312,28 @@ enum PackageRequestType {
     Tarball(String),
 }
 
+/// Read access check shared by the registry routes. Private packages look like they don't
+/// exist, scopes closed to anonymous installs ask for a login instead.
+pub(crate) fn ensure_read_access(
+    package: &str,
+    user: &RegistryReader,
+    state: &AppState,
+) -> Result<(), ApiError> {
+    ScopePolicyService::check_anonymous_read(package, user.0.as_ref(), state)?;
+
+    let user_id = user.0.as_ref().map(|u| u.user_id);
+    let has_access = state
+        .database
+        .has_read_permission(package, user_id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+    if !has_access {
+        return Err(ApiError::NotFound(format!("Package '{package}' not found")));
+    }
+
+    QuarantineService::check_package(package, user.0.as_ref(), state)
+}
+
 // Specific routes for scoped packages (higher priority)
 // Route for scoped package metadata: /registry/@scope/package
 #[get("/registry/<scope>/<package>", rank = 1)]
@@ -169,32 +341,22 @@ pub async fn handle_scoped_package_metadata(
     scope: ScopedPackageName,
     package: &str,
     request_info: RequestInfo,
-    user: OptionalAuthenticatedUser,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     let full_package_name = format!("{}/{}", scope.0, package);
     log::info!("Scoped package metadata request: {full_package_name}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(&full_package_name, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
-
-    if !has_access {
-        return Err(ApiError::NotFound(format!(
-            "Package '{full_package_name}' not found"
-        )));
-    }
+    ensure_read_access(&full_package_name, &user, state)?;
 
-    let result = RegistryService::get_package_metadata(
+    let mut result = RegistryService::get_package_metadata(
         &full_package_name,
         state,
         request_info.host.as_deref(),
         &request_info.scheme,
     )
     .await?;
+    QuarantineService::filter_metadata(&full_package_name, &mut result, user.0.as_ref(), state)?;
     Ok(PackageResponse::Json(result))
 }
 
@@ -220,27 +382,22 @@ pub async fn handle_scoped_package_version(
     scope: ScopedPackageName,
     package: &str,
     version: &str,
-    user: OptionalAuthenticatedUser,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     let full_package_name = format!("{}/{}", scope.0, package);
     log::info!("Scoped package version request: {full_package_name} version {version}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(&full_package_name, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
-
-    if !has_access {
-        return Err(ApiError::NotFound(format!(
-            "Package '{full_package_name}' not found"
-        )));
-    }
+    ensure_read_access(&full_package_name, &user, state)?;
 
     let result =
         RegistryService::get_package_version_metadata(&full_package_name, version, state).await?;
+    QuarantineService::check_version(
+        &full_package_name,
+        result["version"].as_str().unwrap_or(version),
+        user.0.as_ref(),
+        state,
+    )?;
     Ok(PackageResponse::Json(result))
 }
 
@@ -250,27 +407,19 @@ pub async fn handle_scoped_package_tarball(
     scope: ScopedPackageName,
     package: &str,
     filename: &str,
-    user: OptionalAuthenticatedUser,
+    since: IfModifiedSince,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     let full_package_name = format!("{}/{}", scope.0, package);
     log::info!("Scoped package tarball request: {full_package_name} file {filename}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(&full_package_name, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+    ensure_read_access(&full_package_name, &user, state)?;
 
-    if !has_access {
-        return Err(ApiError::NotFound(format!(
-            "Package '{full_package_name}' not found"
-        )));
-    }
+    QuarantineService::check_tarball(&full_package_name, filename, user.0.as_ref(), state)?;
 
-    let result = RegistryService::get_package_tarball(&full_package_name, filename, state).await?;
-    Ok(PackageResponse::Binary(result))
+    PolicyService::check_tarball(&full_package_name, filename, user.0.as_ref(), state).await?;
+    tarball(&full_package_name, filename, &since, state).await
 }
 
 // HEAD request for scoped package tarballs
@@ -279,27 +428,19 @@ pub async fn handle_scoped_package_tarball_head(
     scope: ScopedPackageName,
     package: &str,
     filename: &str,
-    user: OptionalAuthenticatedUser,
+    since: IfModifiedSince,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     let full_package_name = format!("{}/{}", scope.0, package);
     log::info!("Scoped package tarball HEAD request: {full_package_name} file {filename}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(&full_package_name, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+    ensure_read_access(&full_package_name, &user, state)?;
 
-    if !has_access {
-        return Err(ApiError::NotFound(format!(
-            "Package '{full_package_name}' not found"
-        )));
-    }
+    QuarantineService::check_tarball(&full_package_name, filename, user.0.as_ref(), state)?;
 
-    RegistryService::head_package_tarball(&full_package_name, filename, state).await?;
-    Ok(PackageResponse::Empty)
+    PolicyService::check_tarball(&full_package_name, filename, user.0.as_ref(), state).await?;
+    tarball_head(&full_package_name, filename, &since, state).await
 }
 
 // Regular package routes (lower priority)
@@ -308,7 +449,7 @@ pub async fn handle_scoped_package_tarball_head(
 pub async fn handle_regular_package_metadata(
     package: &str,
     request_info: RequestInfo,
-    user: OptionalAuthenticatedUser,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     log::info!("Regular package metadata handler received: '{package}'");
@@ -318,24 +459,16 @@ pub async fn handle_regular_package_metadata(
     if package.starts_with('@') && package.contains('/') {
         log::info!("Decoded scoped package metadata request: {package}");
 
-        // Check if user has read permission for this scoped package
-        let user_id = user.0.as_ref().map(|u| u.user_id);
-        let has_access = state
-            .database
-            .has_read_permission(package, user_id)
-            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
-
-        if !has_access {
-            return Err(ApiError::NotFound(format!("Package '{package}' not found")));
-        }
+        ensure_read_access(package, &user, state)?;
 
-        let result = RegistryService::get_package_metadata(
+        let mut result = RegistryService::get_package_metadata(
             package,
             state,
             request_info.host.as_deref(),
             &request_info.scheme,
         )
         .await?;
+        QuarantineService::filter_metadata(package, &mut result, user.0.as_ref(), state)?;
         return Ok(PackageResponse::Json(result));
     }
     // Skip if this looks like a regular scoped package (starts with @ but no /)
@@ -347,24 +480,16 @@ pub async fn handle_regular_package_metadata(
     }
     log::info!("Regular package metadata request: {package}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(package, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
-
-    if !has_access {
-        return Err(ApiError::NotFound(format!("Package '{package}' not found")));
-    }
+    ensure_read_access(package, &user, state)?;
 
-    let result = RegistryService::get_package_metadata(
+    let mut result = RegistryService::get_package_metadata(
         package,
         state,
         request_info.host.as_deref(),
         &request_info.scheme,
     )
     .await?;
+    QuarantineService::filter_metadata(package, &mut result, user.0.as_ref(), state)?;
     Ok(PackageResponse::Json(result))
 }
 
@@ -373,7 +498,7 @@ pub async fn handle_regular_package_metadata(
 pub async fn handle_regular_package_version(
     package: &str,
     version: &str,
-    user: OptionalAuthenticatedUser,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     // Skip if this looks like a scoped package (starts with @)
@@ -382,18 +507,15 @@ pub async fn handle_regular_package_version(
     }
     log::info!("Regular package version request: {package} version {version}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(package, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
-
-    if !has_access {
-        return Err(ApiError::NotFound(format!("Package '{package}' not found")));
-    }
+    ensure_read_access(package, &user, state)?;
 
     let result = RegistryService::get_package_version_metadata(package, version, state).await?;
+    QuarantineService::check_version(
+        package,
+        result["version"].as_str().unwrap_or(version),
+        user.0.as_ref(),
+        state,
+    )?;
     Ok(PackageResponse::Json(result))
 }
 
@@ -402,7 +524,8 @@ pub async fn handle_regular_package_version(
 pub async fn handle_regular_package_tarball(
     package: &str,
     filename: &str,
-    user: OptionalAuthenticatedUser,
+    since: IfModifiedSince,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     // Skip if this looks like a scoped package (starts with @)
@@ -411,19 +534,12 @@ pub async fn handle_regular_package_tarball(
     }
     log::info!("Regular package tarball request: {package} file {filename}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(package, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+    ensure_read_access(package, &user, state)?;
 
-    if !has_access {
-        return Err(ApiError::NotFound(format!("Package '{package}' not found")));
-    }
+    QuarantineService::check_tarball(package, filename, user.0.as_ref(), state)?;
 
-    let result = RegistryService::get_package_tarball(package, filename, state).await?;
-    Ok(PackageResponse::Binary(result))
+    PolicyService::check_tarball(package, filename, user.0.as_ref(), state).await?;
+    tarball(package, filename, &since, state).await
 }
 
 // HEAD request for regular package tarballs
@@ -431,7 +547,8 @@ pub async fn handle_regular_package_tarball(
 pub async fn handle_regular_package_tarball_head(
     package: &str,
     filename: &str,
-    user: OptionalAuthenticatedUser,
+    since: IfModifiedSince,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     // Skip if this looks like a scoped package (starts with @)
@@ -440,19 +557,12 @@ pub async fn handle_regular_package_tarball_head(
     }
     log::info!("Regular package tarball HEAD request: {package} file {filename}");
 
-    // Check if user has read permission for this package
-    let user_id = user.0.as_ref().map(|u| u.user_id);
-    let has_access = state
-        .database
-        .has_read_permission(package, user_id)
-        .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+    ensure_read_access(package, &user, state)?;
 
-    if !has_access {
-        return Err(ApiError::NotFound(format!("Package '{package}' not found")));
-    }
+    QuarantineService::check_tarball(package, filename, user.0.as_ref(), state)?;
 
-    RegistryService::head_package_tarball(package, filename, state).await?;
-    Ok(PackageResponse::Empty)
+    PolicyService::check_tarball(package, filename, user.0.as_ref(), state).await?;
+    tarball_head(package, filename, &since, state).await
 }
 
 // Catch-all route for any remaining requests (lowest priority)
@@ -461,7 +571,8 @@ pub async fn handle_package_request(
     path: std::path::PathBuf,
     uri_path: UriPath,
     request_info: RequestInfo,
-    user: OptionalAuthenticatedUser,
+    since: IfModifiedSince,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     log::info!(
@@ -473,40 +584,20 @@ pub async fn handle_package_request(
     if let Some((package_name, request_type)) = parse_package_path(&uri_path.0) {
         log::info!("Parsed package: {package_name} with request type: {request_type:?}");
 
-        // Check if user has read permission for this package
-        let user_id = user.0.as_ref().map(|u| u.user_id);
-        let has_access = state
-            .database
-            .has_read_permission(&package_name, user_id)
-            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
-
-        if !has_access {
-            return Err(ApiError::NotFound(format!(
-                "Package '{package_name}' not found"
-            )));
-        }
+        ensure_read_access(&package_name, &user, state)?;
 
         match request_type {
             PackageRequestType::Metadata => {
-                let result = RegistryService::get_package_metadata(
-                    &package_name,
-                    state,
-                    request_info.host.as_deref(),
-                    &request_info.scheme,
-                )
-                .await?;
-                Ok(PackageResponse::Json(result))
+                package_metadata(&package_name, &request_info, &user, state).await
             }
             PackageRequestType::Version(version) => {
-                let result =
-                    RegistryService::get_package_version_metadata(&package_name, &version, state)
-                        .await?;
-                Ok(PackageResponse::Json(result))
+                version_metadata(&package_name, &version, &user, state).await
             }
             PackageRequestType::Tarball(filename) => {
-                let result =
-                    RegistryService::get_package_tarball(&package_name, &filename, state).await?;
-                Ok(PackageResponse::Binary(result))
+                QuarantineService::check_tarball(&package_name, &filename, user.0.as_ref(), state)?;
+                PolicyService::check_tarball(&package_name, &filename, user.0.as_ref(), state)
+                    .await?;
+                tarball(&package_name, &filename, &since, state).await
             }
         }
     } else {
@@ -515,38 +606,401 @@ pub async fn handle_package_request(
     }
 }
 
-// HEAD request handler
+// HEAD request handler. Metadata is answered like a GET request so both carry the same
+// headers, tarballs are only checked for existence.
 #[head("/registry/<_path..>")]
 pub async fn handle_package_head_request(
     _path: std::path::PathBuf,
     uri_path: UriPath,
-    user: OptionalAuthenticatedUser,
+    request_info: RequestInfo,
+    since: IfModifiedSince,
+    user: RegistryReader,
     state: &State<AppState>,
 ) -> Result<PackageResponse, ApiError> {
     if let Some((package_name, request_type)) = parse_package_path(&uri_path.0) {
-        // Check if user has read permission for this package
-        let user_id = user.0.as_ref().map(|u| u.user_id);
-        let has_access = state
-            .database
-            .has_read_permission(&package_name, user_id)
-            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
-
-        if !has_access {
-            return Err(ApiError::NotFound(format!(
-                "Package '{package_name}' not found"
-            )));
-        }
+        ensure_read_access(&package_name, &user, state)?;
 
         match request_type {
+            PackageRequestType::Metadata => {
+                package_metadata(&package_name, &request_info, &user, state).await
+            }
+            PackageRequestType::Version(version) => {
+                version_metadata(&package_name, &version, &user, state).await
+            }
             PackageRequestType::Tarball(filename) => {
-                RegistryService::head_package_tarball(&package_name, &filename, state).await?;
-                Ok(PackageResponse::Empty)
+                QuarantineService::check_tarball(&package_name, &filename, user.0.as_ref(), state)?;
+                PolicyService::check_tarball(&package_name, &filename, user.0.as_ref(), state)
+                    .await?;
+                tarball_head(&package_name, &filename, &since, state).await
             }
-            _ => Err(ApiError::BadRequest(
-                "HEAD only supported for tarballs".to_string(),
-            )),
         }
     } else {
         Err(ApiError::BadRequest("Invalid package path".to_string()))
     }
 }
+
+/// Tarball for a GET request. Clients whose copy is as recent as the upstream
+/// `Last-Modified` recorded with the cached tarball get a 304 without the tarball being read.
+async fn tarball(
+    package: &str,
+    filename: &str,
+    since: &IfModifiedSince,
+    state: &AppState,
+) -> Result<PackageResponse, ApiError> {
+    if let Some(last_modified) = state.cache.tarball_last_modified(package, filename)
+        && since.is_fresh(&last_modified)
+    {
+        return Ok(PackageResponse::NotModified(last_modified));
+    }
+
+    let data = RegistryService::get_package_tarball(package, filename, state).await?;
+    DownloadStatsService::record(package, filename, state);
+    Ok(PackageResponse::Binary(
+        data,
+        state.cache.tarball_last_modified(package, filename),
+    ))
+}
+
+/// Tarball for a HEAD request, with the status and headers a GET request would get
+async fn tarball_head(
+    package: &str,
+    filename: &str,
+    since: &IfModifiedSince,
+    state: &AppState,
+) -> Result<PackageResponse, ApiError> {
+    if let Some(last_modified) = state.cache.tarball_last_modified(package, filename)
+        && since.is_fresh(&last_modified)
+    {
+        return Ok(PackageResponse::NotModified(last_modified));
+    }
+
+    let size = RegistryService::head_package_tarball(package, filename, state).await?;
+    Ok(PackageResponse::TarballHead(
+        size,
+        state.cache.tarball_last_modified(package, filename),
+    ))
+}
+
+/// Package document as the reader may see it
+async fn package_metadata(
+    package_name: &str,
+    request_info: &RequestInfo,
+    user: &RegistryReader,
+    state: &AppState,
+) -> Result<PackageResponse, ApiError> {
+    let mut result = RegistryService::get_package_metadata(
+        package_name,
+        state,
+        request_info.host.as_deref(),
+        &request_info.scheme,
+    )
+    .await?;
+    QuarantineService::filter_metadata(package_name, &mut result, user.0.as_ref(), state)?;
+    Ok(PackageResponse::Json(result))
+}
+
+/// Version document, refused when the version is quarantined for the reader
+async fn version_metadata(
+    package_name: &str,
+    version: &str,
+    user: &RegistryReader,
+    state: &AppState,
+) -> Result<PackageResponse, ApiError> {
+    let result =
+        RegistryService::get_package_version_metadata(package_name, version, state).await?;
+    QuarantineService::check_version(
+        package_name,
+        result["version"].as_str().unwrap_or(version),
+        user.0.as_ref(),
+        state,
+    )?;
+    Ok(PackageResponse::Json(result))
+}
+
+// Response of the file serving route, a file out of a tarball or a redirect to the exact
+// version when the URL names a dist-tag or range
+#[derive(Debug)]
+pub enum PackageFileResponse {
+    File {
+        data: Vec<u8>,
+        content_type: &'static str,
+        cache_control: &'static str,
+    },
+    Redirect {
+        location: String,
+        cache_control: &'static str,
+    },
+}
+
+impl<'r> Responder<'r, 'static> for PackageFileResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+        match self {
+            PackageFileResponse::File {
+                data,
+                content_type,
+                cache_control,
+            } => {
+                let content_type = if content_type.starts_with("text/")
+                    || content_type.ends_with("javascript")
+                    || content_type.ends_with("typescript")
+                    || content_type.ends_with("json")
+                {
+                    format!("{content_type}; charset=utf-8")
+                } else {
+                    content_type.to_string()
+                };
+                // Package files are served from the registry origin, a sandbox keeps scripts
+                // in HTML files from running with access to it
+                Response::build()
+                    .raw_header("Content-Type", content_type)
+                    .raw_header("Cache-Control", cache_control)
+                    .raw_header("X-Content-Type-Options", "nosniff")
+                    .raw_header("Content-Security-Policy", "sandbox")
+                    .raw_header("Access-Control-Allow-Origin", "*")
+                    .sized_body(data.len(), Cursor::new(data))
+                    .ok()
+            }
+            PackageFileResponse::Redirect {
+                location,
+                cache_control,
+            } => Response::build()
+                .status(Status::Found)
+                .raw_header("Location", location)
+                .raw_header("Cache-Control", cache_control)
+                .ok(),
+        }
+    }
+}
+
+// Splits `@scope/name@1.0.0/dist/index.js` into the package, version spec and file path.
+// Without a spec the latest version is served, without a path the package's main file.
+fn parse_file_path(segments: &[&str]) -> Option<(String, String, String)> {
+    let (name, spec, rest) = match segments {
+        [scope, package, rest @ ..] if scope.starts_with('@') => {
+            let (package, spec) = package.split_once('@').unwrap_or((package, "latest"));
+            (format!("{scope}/{package}"), spec, rest)
+        }
+        [package, rest @ ..] if !package.starts_with('@') => {
+            let (package, spec) = package.split_once('@').unwrap_or((package, "latest"));
+            (package.to_string(), spec, rest)
+        }
+        _ => return None,
+    };
+    if name.ends_with('/') || name.is_empty() || spec.is_empty() {
+        return None;
+    }
+
+    Some((name, spec.to_string(), rest.join("/")))
+}
+
+/// Serves a file straight out of a package tarball, e.g. `/files/react@18.3.1/umd/react.production.min.js`.
+/// Exact versions are immutable and cached for a year, dist-tags and ranges redirect to the
+/// version they resolve to.
+#[get("/files/<path..>", rank = 1)]
+pub async fn handle_package_file(
+    path: Segments<'_, rocket::http::uri::fmt::Path>,
+    user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<PackageFileResponse, ApiError> {
+    let segments: Vec<&str> = path.collect();
+    let (package, spec, file) = parse_file_path(&segments).ok_or_else(|| {
+        ApiError::BadRequest("Expected /files/<package>@<version>/<path>".to_string())
+    })?;
+    log::info!("Package file request: {package}@{spec} file {file}");
+
+    ensure_read_access(&package, &user, state)?;
+
+    let metadata = RegistryService::get_package_version_metadata(&package, &spec, state).await?;
+    let version = metadata["version"].as_str().unwrap_or(&spec).to_string();
+    QuarantineService::check_version(&package, &version, user.0.as_ref(), state)?;
+
+    // Responses for logged in users may include private packages, shared caches mustn't keep them
+    let anonymous = user.0.is_none();
+    if version != spec {
+        return Ok(PackageFileResponse::Redirect {
+            location: format!("/files/{package}@{version}/{file}"),
+            cache_control: if anonymous {
+                "public, max-age=300"
+            } else {
+                "private, max-age=300"
+            },
+        });
+    }
+
+    let file = if file.is_empty() {
+        metadata["main"]
+            .as_str()
+            .unwrap_or("index.js")
+            .trim_start_matches("./")
+            .to_string()
+    } else {
+        file
+    };
+    let filename = metadata["dist"]["tarball"]
+        .as_str()
+        .and_then(|url| url.rsplit('/').next())
+        .map(str::to_string)
+        .ok_or_else(|| ApiError::NotFound(format!("{package}@{version} has no tarball")))?;
+    QuarantineService::check_tarball(&package, &filename, user.0.as_ref(), state)?;
+    PolicyService::check_tarball(&package, &filename, user.0.as_ref(), state).await?;
+    let tarball = RegistryService::get_package_tarball(&package, &filename, state).await?;
+
+    let data = PackageFilesService::read_file(&tarball, &file)?
+        .ok_or_else(|| ApiError::NotFound(format!("{package}@{version} has no file '{file}'")))?;
+    Ok(PackageFileResponse::File {
+        data,
+        content_type: PackageFilesService::content_type(&file),
+        cache_control: if anonymous {
+            "public, max-age=31536000, immutable"
+        } else {
+            "private, max-age=31536000, immutable"
+        },
+    })
+}
+
+/// Atom feed of versions recently published to or proxied through the registry
+#[get("/feed/packages.atom?<limit>&<source>")]
+pub async fn packages_feed(
+    limit: Option<i64>,
+    source: Option<&str>,
+    _user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<(ContentType, String), ApiError> {
+    let versions = FeedService::recent(limit, source, state)?;
+    let feed = FeedService::atom(&versions, &state.config.get_public_url());
+
+    Ok((ContentType::new("application", "atom+xml"), feed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Header;
+
+    fn request_info(headers: &[(&'static str, &'static str)], trusted: bool) -> RequestInfo {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.add(Header::new(*name, *value));
+        }
+        RequestInfo::of_headers(&map, trusted, "http")
+    }
+
+    #[test]
+    fn test_request_info_ignores_untrusted_headers() {
+        let info = request_info(
+            &[
+                ("Host", "localhost:8000"),
+                ("X-Forwarded-Proto", "https"),
+                ("X-Forwarded-Host", "evil.example.com"),
+            ],
+            false,
+        );
+        assert_eq!(info.host.as_deref(), Some("localhost:8000"));
+        assert_eq!(info.scheme, "http");
+    }
+
+    #[test]
+    fn test_request_info_x_forwarded() {
+        let info = request_info(
+            &[
+                ("Host", "clef:8000"),
+                ("X-Forwarded-Proto", "https, http"),
+                ("X-Forwarded-Host", "npm.example.com"),
+                ("X-Forwarded-Port", "443"),
+            ],
+            true,
+        );
+        assert_eq!(info.host.as_deref(), Some("npm.example.com"));
+        assert_eq!(info.scheme, "https");
+
+        let info = request_info(
+            &[
+                ("X-Forwarded-Proto", "https"),
+                ("X-Forwarded-Host", "npm.example.com"),
+                ("X-Forwarded-Port", "8443"),
+            ],
+            true,
+        );
+        assert_eq!(info.host.as_deref(), Some("npm.example.com:8443"));
+    }
+
+    #[test]
+    fn test_request_info_forwarded() {
+        let info = request_info(
+            &[
+                ("Host", "clef:8000"),
+                ("X-Forwarded-Host", "ignored.example.com"),
+                (
+                    "Forwarded",
+                    "for=192.0.2.60;proto=https;host=\"npm.example.com\", for=10.0.0.1;proto=http",
+                ),
+            ],
+            true,
+        );
+        assert_eq!(info.host.as_deref(), Some("npm.example.com"));
+        assert_eq!(info.scheme, "https");
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let since = |date: Option<&str>| IfModifiedSince(date.map(str::to_string));
+
+        assert!(since(Some(modified)).is_fresh(modified));
+        assert!(since(Some("Thu, 22 Oct 2015 00:00:00 GMT")).is_fresh(modified));
+        assert!(!since(Some("Wed, 21 Oct 2015 07:27:59 GMT")).is_fresh(modified));
+        assert!(!since(Some("yesterday")).is_fresh(modified));
+        assert!(!since(None).is_fresh(modified));
+        assert!(!since(Some(modified)).is_fresh("not a date"));
+    }
+
+    #[test]
+    fn test_package_of_path() {
+        let package = |path| package_of_path(path);
+
+        assert_eq!(package("/registry/lodash").as_deref(), Some("lodash"));
+        assert_eq!(
+            package("/registry/lodash/4.17.21").as_deref(),
+            Some("lodash")
+        );
+        assert_eq!(
+            package("/registry/@types/node/-/node-20.0.0.tgz").as_deref(),
+            Some("@types/node")
+        );
+        assert_eq!(
+            package("/registry/@types%2fnode").as_deref(),
+            Some("@types/node")
+        );
+        assert_eq!(package("/registry/-/v1/search"), None);
+        assert_eq!(package("/registry/"), None);
+        assert_eq!(package("/api/v1/packages/lodash"), None);
+    }
+
+    #[test]
+    fn test_parse_file_path() {
+        let parse = |path: &str| parse_file_path(&path.split('/').collect::<Vec<_>>());
+
+        assert_eq!(
+            parse("react@18.3.1/umd/react.production.min.js"),
+            Some((
+                "react".to_string(),
+                "18.3.1".to_string(),
+                "umd/react.production.min.js".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("@jkuri/ui@^2.0.0/dist/app.css"),
+            Some((
+                "@jkuri/ui".to_string(),
+                "^2.0.0".to_string(),
+                "dist/app.css".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("lodash"),
+            Some(("lodash".to_string(), "latest".to_string(), String::new()))
+        );
+        assert_eq!(parse("@jkuri"), None);
+        assert_eq!(parse("lodash@"), None);
+    }
+}
@@ -1,46 +1,522 @@
 use crate::error::ApiError;
-use crate::models::{AuthenticatedUser, NpmPublishRequest, NpmPublishResponse};
+use crate::models::{
+    AuthenticatedUser, NewAuditLogEntry, NewStagedPublish, NpmPublishRequest, NpmPublishResponse,
+    PUBLISH_ACTION, RegistryEvent, StagedPublish, StagedPublishResponse, StagedPublishesResponse,
+};
 use crate::routes::packages::ScopedPackageName;
+use crate::services::{
+    NameBlocklistService, PolicyService, ProvenanceService, QuotaService, ScopePolicyService,
+    SigningService, TyposquatService, UnpublishService,
+};
 use crate::state::AppState;
-use log::{debug, warn};
-use rocket::serde::json::Json;
-use rocket::{State, put};
+use crate::versions;
+use log::{debug, info, warn};
+use rocket::data::ToByteUnit;
+use rocket::serde::json::{Json, json};
+use rocket::{Data, State, delete, get, post, put};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-/// npm publish endpoint for scoped packages - PUT /registry/@scope/package
-#[put("/registry/<scope>/<package>", data = "<publish_request>", rank = 1)]
+/// npm publish endpoint for scoped packages - PUT /registry/@scope/package. With
+/// `?staged=true` the publish is checked and kept aside until it's promoted.
+#[put("/registry/<scope>/<package>?<staged>", data = "<data>", rank = 1)]
 pub async fn npm_publish_scoped(
     scope: ScopedPackageName,
     package: &str,
-    publish_request: Json<NpmPublishRequest>,
+    staged: Option<bool>,
+    data: Data<'_>,
     user: AuthenticatedUser,
     state: &State<AppState>,
 ) -> Result<Json<NpmPublishResponse>, ApiError> {
     let full_package_name = format!("{}/{}", scope.0, package);
-    npm_publish_impl(&full_package_name, publish_request, user, state).await
+    receive_publish(
+        &full_package_name,
+        staged.unwrap_or(false),
+        data,
+        user,
+        state,
+    )
+    .await
 }
 
 /// npm publish endpoint for regular packages - PUT /registry/:package
-#[put("/registry/<package>", data = "<publish_request>", rank = 2)]
+#[put("/registry/<package>?<staged>", data = "<data>", rank = 2)]
 pub async fn npm_publish(
     package: &str,
-    publish_request: Json<NpmPublishRequest>,
+    staged: Option<bool>,
+    data: Data<'_>,
     user: AuthenticatedUser,
     state: &State<AppState>,
 ) -> Result<Json<NpmPublishResponse>, ApiError> {
-    npm_publish_impl(package, publish_request, user, state).await
+    receive_publish(package, staged.unwrap_or(false), data, user, state).await
+}
+
+/// Publishes or stages a publish request. The body is spooled to a file in the cache
+/// directory and parsed from there rather than buffered whole. Attachments aren't streamed:
+/// the parsed request holds their base64 data until each tarball is decoded and stored. A
+/// staged publish keeps the file.
+async fn receive_publish(
+    package: &str,
+    staged: bool,
+    data: Data<'_>,
+    user: AuthenticatedUser,
+    state: &AppState,
+) -> Result<Json<NpmPublishResponse>, ApiError> {
+    let path = spool_publish_request(data, state).await?;
+    let result = match parse_publish_request(&path).await {
+        Ok(publish_request) => match check_not_staged(package, &publish_request, state) {
+            Ok(()) if staged => stage_publish(package, publish_request, &path, user, state).await,
+            Ok(()) => npm_publish_impl(package, publish_request, user, state).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    remove_publish_file(&path).await;
+    result
+}
+
+/// A staged version can't be published or staged again until it's promoted or discarded
+fn check_not_staged(
+    package: &str,
+    publish_request: &NpmPublishRequest,
+    state: &AppState,
+) -> Result<(), ApiError> {
+    for (version, _) in published_versions(publish_request)? {
+        let staged = state
+            .database
+            .find_staged_publish(package, &version)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        if let Some(staged) = staged {
+            return Err(ApiError::Conflict(format!(
+                "{package}@{version} is staged as {}, promote or discard it first",
+                staged.id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Writes the request body to a file of its own, refusing bodies over the configured limit
+async fn spool_publish_request(data: Data<'_>, state: &AppState) -> Result<PathBuf, ApiError> {
+    let limit = state.config.publish_body_limit();
+    let spool_dir = Path::new(&state.config.cache_dir).join("tmp");
+    tokio::fs::create_dir_all(&spool_dir).await.map_err(|e| {
+        ApiError::InternalServerError(format!("Failed to create spool directory: {e}"))
+    })?;
+    let path = spool_dir.join(format!("publish-{}.json", uuid::Uuid::new_v4().simple()));
+
+    let error = match data.open(limit.bytes()).into_file(&path).await {
+        Ok(file) if file.is_complete() => {
+            debug!("Spooled {} byte publish request", file.n.written);
+            return Ok(path);
+        }
+        Ok(_) => ApiError::PayloadTooLarge(format!(
+            "The publish request exceeds the maximum body size of {limit} bytes"
+        )),
+        Err(e) => ApiError::BadRequest(format!("Failed to read request body: {e}")),
+    };
+
+    remove_publish_file(&path).await;
+    Err(error)
+}
+
+async fn parse_publish_request(path: &Path) -> Result<NpmPublishRequest, ApiError> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)?;
+        serde_json::from_reader::<_, NpmPublishRequest>(std::io::BufReader::new(file))
+            .map_err(std::io::Error::from)
+    })
+    .await
+    .map_err(|e| ApiError::InternalServerError(format!("Failed to read publish: {e}")))?
+    .map_err(|e| ApiError::BadRequest(format!("Invalid publish request: {e}")))
+}
+
+async fn remove_publish_file(path: &Path) {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to remove publish request {}: {e}", path.display()),
+    }
+}
+
+/// Checks a publish and keeps its request in the staging area instead of storing it
+async fn stage_publish(
+    package: &str,
+    publish_request: NpmPublishRequest,
+    spooled: &Path,
+    user: AuthenticatedUser,
+    state: &AppState,
+) -> Result<Json<NpmPublishResponse>, ApiError> {
+    let version = single_published_version(&publish_request)?;
+    let checked = check_publish(package, &publish_request, &user, state).await?;
+    let size_bytes: u64 = publish_request
+        ._attachments
+        .iter()
+        .filter(|(filename, attachment)| {
+            !ProvenanceService::is_bundle_attachment(filename, attachment)
+        })
+        .map(|(_, attachment)| attachment.length)
+        .sum();
+
+    let staged_dir = Path::new(&state.config.cache_dir).join("staged");
+    std::fs::create_dir_all(&staged_dir).map_err(|e| {
+        ApiError::InternalServerError(format!("Failed to create staging directory: {e}"))
+    })?;
+    let request_path = staged_dir.join(format!("{}.json", uuid::Uuid::new_v4().simple()));
+    std::fs::rename(spooled, &request_path).map_err(|e| {
+        ApiError::InternalServerError(format!("Failed to stage publish request: {e}"))
+    })?;
+
+    let new_staged = NewStagedPublish {
+        package_name: package.to_string(),
+        version: version.clone(),
+        publisher_id: user.user_id,
+        request_path: request_path.to_string_lossy().to_string(),
+        size_bytes: size_bytes as i64,
+    };
+    let staged = match state.database.create_staged_publish(&new_staged) {
+        Ok(staged) => staged,
+        Err(e) => {
+            let _ = std::fs::remove_file(&request_path);
+            return Err(ApiError::InternalServerError(format!(
+                "Database error: {e}"
+            )));
+        }
+    };
+
+    info!(
+        "{} staged {package}@{version} as staged publish {}",
+        user.username, staged.id
+    );
+    audit(
+        "package.stage",
+        &staged,
+        &user,
+        checked.organization_id,
+        None,
+        state,
+    );
+
+    Ok(Json(NpmPublishResponse {
+        ok: true,
+        id: package.to_string(),
+        rev: "1-0".to_string(),
+        staged_id: Some(staged.id),
+    }))
+}
+
+/// Staged publishes of a package, waiting to be promoted
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = StagedPublishesResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/packages/<name>/staged")]
+pub async fn list_staged_publishes(
+    name: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<StagedPublishesResponse>, ApiError> {
+    check_staged_access(name, &user, state)?;
+    let staged = state
+        .database
+        .list_staged_publishes(name)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .into_iter()
+        .map(|(staged, publisher)| StagedPublishResponse::new(staged, publisher))
+        .collect();
+
+    Ok(Json(StagedPublishesResponse { staged }))
+}
+
+/// Promote a staged publish, publishing it on behalf of its publisher. With
+/// `CLEF_STAGED_PUBLISH_APPROVAL` it must be promoted by someone else.
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = StagedPublishResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/packages/<name>/staged/<id>/promote")]
+pub async fn promote_staged_publish(
+    name: &str,
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<StagedPublishResponse>, ApiError> {
+    let (staged, publisher_name) = get_staged_publish(name, id, &user, state)?;
+    if state.config.staged_publish_approval && staged.publisher_id == user.user_id {
+        return Err(ApiError::Forbidden(
+            "Staged publishes must be promoted by someone other than their publisher".to_string(),
+        ));
+    }
+
+    // The publish is checked again as its publisher, permissions or the package may have
+    // changed since it was staged
+    let publisher = state
+        .database
+        .get_user_by_id(staged.publisher_id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .ok_or_else(|| ApiError::NotFound(format!("User {} not found", staged.publisher_id)))?;
+    let publisher = AuthenticatedUser::new(
+        publisher.username.clone(),
+        publisher.id,
+        publisher.is_admin(),
+        None,
+    );
+    let request_path = PathBuf::from(&staged.request_path);
+    let publish_request = parse_publish_request(&request_path).await?;
+    if publish_request.name != staged.package_name
+        || single_published_version(&publish_request)? != staged.version
+    {
+        return Err(ApiError::InternalServerError(format!(
+            "Staged publish {id} doesn't match its stored request"
+        )));
+    }
+    let checked = check_publish(name, &publish_request, &publisher, state).await?;
+    let organization_id = checked.organization_id;
+
+    // The staged publish is only let go of once it's stored, a failed store leaves it to be
+    // promoted again or discarded
+    store_publish(name, publish_request, publisher, checked, state).await?;
+    match state.database.delete_staged_publish(staged.id) {
+        Ok(_) => remove_publish_file(&request_path).await,
+        Err(e) => warn!("Failed to remove staged publish {id} after promoting it: {e}"),
+    }
+
+    info!(
+        "{} promoted {}@{} staged by {publisher_name}",
+        user.username, staged.package_name, staged.version
+    );
+    audit(
+        "package.promote",
+        &staged,
+        &user,
+        organization_id,
+        Some(&publisher_name),
+        state,
+    );
+
+    Ok(Json(StagedPublishResponse::new(staged, publisher_name)))
+}
+
+/// Discard a staged publish
+#[utoipa::path(
+    tag = "packages",
+    responses((status = 200, body = StagedPublishResponse)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/packages/<name>/staged/<id>")]
+pub async fn discard_staged_publish(
+    name: &str,
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<StagedPublishResponse>, ApiError> {
+    let (staged, publisher_name) = get_staged_publish(name, id, &user, state)?;
+    state
+        .database
+        .delete_staged_publish(staged.id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+    remove_publish_file(Path::new(&staged.request_path)).await;
+
+    let organization_id = crate::database::DatabaseService::extract_organization_name(name)
+        .and_then(|org_name| state.database.get_organization_by_name(&org_name).ok())
+        .flatten()
+        .map(|org| org.id);
+    audit(
+        "package.discard_staged",
+        &staged,
+        &user,
+        organization_id,
+        Some(&publisher_name),
+        state,
+    );
+
+    Ok(Json(StagedPublishResponse::new(staged, publisher_name)))
+}
+
+/// A staged publish of `package` the user may promote or discard
+fn get_staged_publish(
+    package: &str,
+    id: i32,
+    user: &AuthenticatedUser,
+    state: &AppState,
+) -> Result<(StagedPublish, String), ApiError> {
+    check_staged_access(package, user, state)?;
+    state
+        .database
+        .get_staged_publish(id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .filter(|(staged, _)| staged.package_name == package)
+        .ok_or_else(|| ApiError::NotFound(format!("Staged publish {id} of '{package}' not found")))
+}
+
+/// Staged publishes are managed by admins and whoever may publish the package: its owners,
+/// or for scoped packages the members of the organization. Anyone may publish a new
+/// unscoped package, so only admins manage those.
+fn check_staged_access(
+    package: &str,
+    user: &AuthenticatedUser,
+    state: &AppState,
+) -> Result<(), ApiError> {
+    if user.is_admin {
+        return Ok(());
+    }
+
+    let forbidden = || {
+        ApiError::Forbidden(format!(
+            "You don't have permission to manage staged publishes of '{package}'"
+        ))
+    };
+    let exists = state
+        .database
+        .package_exists(package)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+    let can_publish = state
+        .database
+        .can_publish_package(package, user.user_id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+    if !can_publish {
+        return Err(forbidden());
+    }
+
+    match crate::database::DatabaseService::extract_organization_name(package) {
+        Some(org_name) => {
+            let organization = state
+                .database
+                .get_organization_by_name(&org_name)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+                .ok_or_else(forbidden)?;
+            let is_member = state
+                .database
+                .check_organization_permission(
+                    organization.id,
+                    user.user_id,
+                    crate::models::organization::OrganizationRole::Member,
+                )
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+            if is_member { Ok(()) } else { Err(forbidden()) }
+        }
+        None if exists => Ok(()),
+        None => Err(forbidden()),
+    }
+}
+
+fn audit(
+    action: &str,
+    staged: &StagedPublish,
+    user: &AuthenticatedUser,
+    organization_id: Option<i32>,
+    publisher: Option<&str>,
+    state: &AppState,
+) {
+    let entry = NewAuditLogEntry {
+        action: action.to_string(),
+        actor_id: Some(user.user_id),
+        organization_id,
+        package_name: Some(staged.package_name.clone()),
+        version: Some(staged.version.clone()),
+        details: Some(json!({ "staged_id": staged.id, "publisher": publisher }).to_string()),
+    };
+    if let Err(e) = state.database.create_audit_log_entry(&entry) {
+        warn!("Failed to write audit log entry {action}: {e}");
+    }
+}
+
+/// The versions a publish adds, oldest first, with the name of their tarball attachment.
+/// A version is published when its `dist.tarball` names one of the attachments, other
+/// versions of a full package document are left as they are. Every tarball must belong to
+/// a version.
+fn published_versions(
+    publish_request: &NpmPublishRequest,
+) -> Result<Vec<(String, String)>, ApiError> {
+    let mut published: Vec<(String, String)> = Vec::new();
+    for (filename, attachment) in &publish_request._attachments {
+        if ProvenanceService::is_bundle_attachment(filename, attachment) {
+            continue;
+        }
+        let version = publish_request
+            .versions
+            .iter()
+            .find(|(_, version_data)| names_attachment(&version_data.dist.tarball, filename))
+            .map(|(version, _)| version)
+            .ok_or_else(|| {
+                ApiError::BadRequest(format!(
+                    "Attachment {filename} isn't the tarball of any version in the request"
+                ))
+            })?;
+        if published.iter().any(|(published, _)| published == version) {
+            return Err(ApiError::BadRequest(format!(
+                "Version {version} has more than one tarball attached"
+            )));
+        }
+        published.push((version.clone(), filename.clone()));
+    }
+
+    if published.is_empty() {
+        return Err(ApiError::BadRequest(
+            "No tarball provided in publish request".to_string(),
+        ));
+    }
+    published.sort_by(|(a, _), (b, _)| versions::compare(a, b));
+    Ok(published)
+}
+
+/// Whether a `dist.tarball` URL points at the attachment. npm names attachments
+/// `<name>-<version>.tgz`, scoped ones including the scope, some clients without it.
+fn names_attachment(tarball_url: &str, filename: &str) -> bool {
+    let tarball_url = tarball_url
+        .replace("%2f", "/")
+        .replace("%2F", "/")
+        .replace("%40", "@");
+    tarball_url == filename
+        || tarball_url
+            .strip_suffix(filename)
+            .is_some_and(|rest| rest.ends_with('/'))
+}
+
+/// The only version a publish adds, for staging one version at a time
+fn single_published_version(publish_request: &NpmPublishRequest) -> Result<String, ApiError> {
+    let mut published = published_versions(publish_request)?;
+    if published.len() > 1 {
+        return Err(ApiError::BadRequest(format!(
+            "Stage one version at a time, the request publishes {}",
+            published
+                .iter()
+                .map(|(version, _)| version.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    Ok(published.remove(0).0)
+}
+
+/// What checking a publish found out, for storing it
+struct CheckedPublish {
+    organization_id: Option<i32>,
+    is_new_package: bool,
 }
 
 /// Common implementation for both scoped and regular package publishing
 async fn npm_publish_impl(
     package: &str,
-    publish_request: Json<NpmPublishRequest>,
+    publish_request: NpmPublishRequest,
     user: AuthenticatedUser,
-    state: &State<AppState>,
+    state: &AppState,
 ) -> Result<Json<NpmPublishResponse>, ApiError> {
-    use base64::prelude::*;
-    use std::fs;
-    use std::path::Path;
+    let checked = check_publish(package, &publish_request, &user, state).await?;
+    store_publish(package, publish_request, user, checked, state).await
+}
 
+/// Validates a publish and the publisher's permissions before anything is stored
+async fn check_publish(
+    package: &str,
+    publish_request: &NpmPublishRequest,
+    user: &AuthenticatedUser,
+    state: &AppState,
+) -> Result<CheckedPublish, ApiError> {
     debug!(
         "Publishing package: {} (URL parameter: {})",
         publish_request.name, package
@@ -72,6 +548,15 @@ async fn npm_publish_impl(
         ));
     }
 
+    let max_size = state.config.max_publish_size_bytes;
+    if let Some((filename, attachment)) = publish_request
+        ._attachments
+        .iter()
+        .find(|(_, attachment)| attachment.length > max_size)
+    {
+        return Err(too_large(filename, attachment.length, max_size));
+    }
+
     // Check if user has permission to publish this package
     // Check if user can publish to this package
     let can_publish = state
@@ -86,20 +571,42 @@ async fn npm_publish_impl(
         )));
     }
 
+    ScopePolicyService::check_publish(package, user, state)?;
+    NameBlocklistService::check_publish(package, state)?;
+
     // Check if this is a new package (no existing owners)
     let is_new_package = !state
         .database
         .package_exists(package)
         .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
 
-    // Get the first version from the request (npm publish sends one version at a time)
-    let (version, version_data) = publish_request
-        .versions
-        .iter()
-        .next()
-        .ok_or_else(|| ApiError::BadRequest("No version data provided".to_string()))?;
+    if is_new_package {
+        TyposquatService::check_new_package(package, user, state)?;
+    }
 
-    debug!("Publishing version: {version}");
+    let published = published_versions(publish_request)?;
+    debug!(
+        "Publishing versions: {}",
+        published
+            .iter()
+            .map(|(version, _)| version.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    // A provenance bundle attests one tarball
+    if published.len() > 1
+        && publish_request
+            ._attachments
+            .iter()
+            .any(|(filename, attachment)| {
+                ProvenanceService::is_bundle_attachment(filename, attachment)
+            })
+    {
+        return Err(ApiError::BadRequest(
+            "Provenance can only be attached when publishing a single version".to_string(),
+        ));
+    }
 
     // Check if this is a scoped package and handle organization
     let organization_id = if let Some(org_name) =
@@ -141,11 +648,51 @@ async fn npm_publish_impl(
         None
     };
 
-    // Use package-level description if available, otherwise fall back to version description
+    let mut incoming = 0;
+    for (version, filename) in &published {
+        let version_data = &publish_request.versions[version];
+        let size = publish_request._attachments[filename].length;
+        PolicyService::check_publish(package, version_data, size, user, state)?;
+        state
+            .plugins
+            .validate_publish(package, version_data, size, user)
+            .await?;
+        UnpublishService::check_publish(package, version, state)?;
+        incoming += size;
+    }
+    QuotaService::check_publish(user.user_id, organization_id, incoming, state)?;
+
+    Ok(CheckedPublish {
+        organization_id,
+        is_new_package,
+    })
+}
+
+/// Stores a checked publish: the version, its tarball and dist-tags
+async fn store_publish(
+    package: &str,
+    mut publish_request: NpmPublishRequest,
+    user: AuthenticatedUser,
+    checked: CheckedPublish,
+    state: &AppState,
+) -> Result<Json<NpmPublishResponse>, ApiError> {
+    use base64::prelude::*;
+    use std::fs;
+
+    let CheckedPublish {
+        organization_id,
+        is_new_package,
+    } = checked;
+    let max_size = state.config.max_publish_size_bytes;
+    let published = published_versions(&publish_request)?;
+    let (newest, _) = published.last().expect("published versions aren't empty");
+
+    // Use package-level description if available, otherwise fall back to the description of
+    // the newest published version
     let package_description = publish_request
         .description
         .clone()
-        .or_else(|| version_data.description.clone());
+        .or_else(|| publish_request.versions[newest].description.clone());
 
     // Create or get the package in the database with organization link
     let pkg = if let Some(org_id) = organization_id {
@@ -170,47 +717,76 @@ async fn npm_publish_impl(
             .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
     };
 
-    // Update package metadata (license, etc.) from version data
-    if version_data.license.is_some() {
-        state
-            .database
-            .update_package_metadata(
-                pkg.id,
-                None, // homepage
-                None, // repository_url
-                version_data.license.clone(),
-                None, // keywords
-            )
-            .map_err(|e| {
-                ApiError::InternalServerError(format!("Failed to update package metadata: {e}"))
-            })?;
-    }
-
     debug!("Package ID: {}", pkg.id);
 
-    // Create or get the package version
-    let version_json = serde_json::to_value(version_data).map_err(|e| {
-        ApiError::InternalServerError(format!("Failed to serialize version data: {e}"))
-    })?;
+    // Provenance bundles are attached next to the tarball. The attachments are taken out of
+    // the request so each tarball's base64 data is freed as soon as it's decoded.
+    let (bundles, mut tarballs): (HashMap<_, _>, HashMap<_, _>) =
+        std::mem::take(&mut publish_request._attachments)
+            .into_iter()
+            .partition(|(filename, attachment)| {
+                ProvenanceService::is_bundle_attachment(filename, attachment)
+            });
 
-    let pkg_version = state
-        .database
-        .create_or_get_package_version_with_metadata(pkg.id, version, &version_json)
-        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+    for (version, filename) in &published {
+        let version_data = &publish_request.versions[version];
 
-    debug!("Package version ID: {}", pkg_version.id);
+        // Update package metadata (license, etc.) from version data
+        if version_data.license.is_some() {
+            state
+                .database
+                .update_package_metadata(
+                    pkg.id,
+                    None, // homepage
+                    None, // repository_url
+                    version_data.license.clone(),
+                    None, // keywords
+                )
+                .map_err(|e| {
+                    ApiError::InternalServerError(format!("Failed to update package metadata: {e}"))
+                })?;
+        }
 
-    // Process attachments (tarballs)
-    for (filename, attachment) in &publish_request._attachments {
+        // Create or get the package version
+        let version_json = serde_json::to_value(version_data).map_err(|e| {
+            ApiError::InternalServerError(format!("Failed to serialize version data: {e}"))
+        })?;
+
+        let pkg_version = state
+            .database
+            .create_or_get_package_version_with_metadata(pkg.id, version, &version_json)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        debug!("Package version ID: {}", pkg_version.id);
         debug!("Processing attachment: {filename}");
 
+        let attachment = tarballs.remove(filename).ok_or_else(|| {
+            ApiError::InternalServerError(format!("Attachment {filename} went missing"))
+        })?;
+
         // Decode the base64 data
         let tarball_data = BASE64_STANDARD
-            .decode(&attachment.data)
+            .decode(attachment.data)
             .map_err(|e| ApiError::BadRequest(format!("Invalid base64 data: {e}")))?;
 
         debug!("Decoded tarball size: {} bytes", tarball_data.len());
 
+        // The declared length is checked up front, the decoded size is what ends up on disk
+        if tarball_data.len() as u64 > max_size {
+            return Err(too_large(filename, tarball_data.len() as u64, max_size));
+        }
+
+        for bundle in bundles.values() {
+            ProvenanceService::store_bundle(
+                package,
+                version,
+                pkg_version.id,
+                bundle,
+                &tarball_data,
+                state,
+            )?;
+        }
+
         // Create packages directory structure
         // Scoped packages like @jkuri/test-scoped-package are stored as @jkuri/test-scoped-package/
         let cache_dir = Path::new(&state.config.cache_dir);
@@ -265,7 +841,9 @@ async fn npm_publish_impl(
         // Store file information in database
         let upstream_url = format!(
             "{}/{}/-/{}",
-            state.config.upstream_registry, package, tarball_filename
+            state.upstream_registry(),
+            package,
+            tarball_filename
         );
 
         state
@@ -282,6 +860,35 @@ async fn npm_publish_impl(
             .map_err(|e| {
                 ApiError::InternalServerError(format!("Failed to create package file: {e}"))
             })?;
+
+        // Checksums are computed from the received tarball, the client supplied ones aren't trusted
+        let shasum = SigningService::shasum_for(&tarball_data);
+        if shasum != version_data.dist.shasum {
+            debug!(
+                "Client sent shasum {} for {package}@{version}, actual is {shasum}",
+                version_data.dist.shasum
+            );
+        }
+        state
+            .database
+            .set_version_checksums(
+                pkg_version.id,
+                &shasum,
+                &SigningService::integrity_for(&tarball_data),
+            )
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        // Sign the dist with the organization's keys, if it has registered any
+        if let Some(org_id) = organization_id {
+            SigningService::sign_package_version(
+                org_id,
+                package,
+                version,
+                pkg_version.id,
+                &tarball_data,
+                state,
+            )?;
+        }
     }
 
     // If this is a new package, create ownership record
@@ -294,9 +901,41 @@ async fn npm_publish_impl(
             })?;
     }
 
+    for (version, _) in &published {
+        let entry = NewAuditLogEntry {
+            action: PUBLISH_ACTION.to_string(),
+            actor_id: Some(user.user_id),
+            organization_id,
+            package_name: Some(package.to_string()),
+            version: Some(version.clone()),
+            details: None,
+        };
+        if let Err(e) = state.database.create_audit_log_entry(&entry) {
+            warn!("Failed to write audit log entry {PUBLISH_ACTION}: {e}");
+        }
+    }
+
+    let current_tags = state
+        .database
+        .get_package_tags_map(package)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+    let mut current_latest = current_tags.get("latest").cloned();
+    let prerelease_may_be_latest =
+        prerelease_may_be_latest(current_latest.as_deref(), state.config.prerelease_latest);
+
     // Handle dist-tags if provided
     if let Some(dist_tags) = &publish_request.dist_tags {
         for (tag_name, tag_version) in dist_tags {
+            // npm sends `latest` unless --tag names another one
+            if tag_name == "latest"
+                && versions::is_prerelease(tag_version)
+                && !prerelease_may_be_latest
+            {
+                info!(
+                    "Not moving latest of {package} to prerelease {tag_version}, publish it with --tag"
+                );
+                continue;
+            }
             if let Err(e) =
                 state
                     .database
@@ -305,17 +944,46 @@ async fn npm_publish_impl(
                 warn!("Failed to create/update tag {tag_name} for package {package}: {e}");
             } else {
                 debug!("Created/updated tag {tag_name} -> {tag_version} for package {package}");
+                record_tag_change(
+                    package,
+                    tag_name,
+                    current_tags.get(tag_name).map(String::as_str),
+                    tag_version,
+                    &user,
+                    organization_id,
+                    state,
+                );
             }
         }
     } else {
-        // If no explicit tags provided, set 'latest' tag to the published version
-        if let Err(e) = state
-            .database
-            .create_or_update_package_tag(package, "latest", version)
-        {
-            warn!("Failed to create/update latest tag for package {package}: {e}");
-        } else {
-            debug!("Set latest tag to {version} for package {package}");
+        // Without explicit tags 'latest' only moves forward, to a published version when it
+        // takes precedence over the current one
+        for (version, _) in &published {
+            if !moves_latest(
+                current_latest.as_deref(),
+                version,
+                state.config.prerelease_latest,
+            ) {
+                continue;
+            }
+            if let Err(e) = state
+                .database
+                .create_or_update_package_tag(package, "latest", version)
+            {
+                warn!("Failed to create/update latest tag for package {package}: {e}");
+            } else {
+                debug!("Set latest tag to {version} for package {package}");
+                record_tag_change(
+                    package,
+                    "latest",
+                    current_latest.as_deref(),
+                    version,
+                    &user,
+                    organization_id,
+                    state,
+                );
+                current_latest = Some(version.clone());
+            }
         }
     }
 
@@ -324,9 +992,147 @@ async fn npm_publish_impl(
         warn!("Failed to invalidate metadata cache for package {package}: {e}");
     }
 
+    for (version, _) in published {
+        state.events.emit(RegistryEvent::VersionPublished {
+            package: package.to_string(),
+            version,
+            actor: user.username.clone(),
+        });
+    }
+
     Ok(Json(NpmPublishResponse {
         ok: true,
         id: package.to_string(),
         rev: "1-0".to_string(),
+        staged_id: None,
     }))
 }
+
+/// Writes a moved dist-tag to the audit log, unless it already pointed at the version
+fn record_tag_change(
+    package: &str,
+    tag: &str,
+    from: Option<&str>,
+    to: &str,
+    user: &AuthenticatedUser,
+    organization_id: Option<i32>,
+    state: &AppState,
+) {
+    if from == Some(to) {
+        return;
+    }
+    let entry = NewAuditLogEntry {
+        action: "package.dist_tag".to_string(),
+        actor_id: Some(user.user_id),
+        organization_id,
+        package_name: Some(package.to_string()),
+        version: Some(to.to_string()),
+        details: Some(json!({ "tag": tag, "from": from }).to_string()),
+    };
+    if let Err(e) = state.database.create_audit_log_entry(&entry) {
+        warn!("Failed to write audit log entry package.dist_tag: {e}");
+    }
+}
+
+/// Whether a publish may move `latest` to a prerelease: always when configured, otherwise
+/// only while the package has no stable latest version. `npm dist-tag add` can still point
+/// `latest` anywhere.
+fn prerelease_may_be_latest(current_latest: Option<&str>, allowed: bool) -> bool {
+    allowed || current_latest.is_none_or(versions::is_prerelease)
+}
+
+/// Whether publishing `version` without dist-tags should point `latest` at it. Stable
+/// versions take precedence over prereleases unless prereleases may be latest.
+fn moves_latest(current: Option<&str>, version: &str, prerelease_latest: bool) -> bool {
+    let Some(current) = current.filter(|current| *current != version) else {
+        return true;
+    };
+    if prerelease_latest {
+        return versions::compare(version, current).is_gt();
+    }
+    versions::latest([current, version]).as_deref() == Some(version)
+}
+
+fn too_large(filename: &str, size: u64, max_size: u64) -> ApiError {
+    ApiError::PayloadTooLarge(format!(
+        "{filename} is {size} bytes, which exceeds the maximum publish size of {max_size} bytes"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prerelease_may_be_latest() {
+        assert!(prerelease_may_be_latest(None, false));
+        assert!(prerelease_may_be_latest(Some("2.0.0-beta.1"), false));
+        assert!(!prerelease_may_be_latest(Some("1.4.0"), false));
+        assert!(prerelease_may_be_latest(Some("1.4.0"), true));
+    }
+
+    #[test]
+    fn test_moves_latest() {
+        assert!(moves_latest(None, "1.0.0", false));
+        assert!(moves_latest(Some("1.9.0"), "1.10.0", false));
+        assert!(moves_latest(Some("1.0.0"), "1.0.0", false));
+        assert!(!moves_latest(Some("1.10.0"), "1.9.0", false));
+        assert!(!moves_latest(Some("1.4.0"), "2.0.0-beta.1", false));
+        assert!(moves_latest(Some("2.0.0-beta.1"), "2.0.0-beta.2", false));
+        assert!(moves_latest(Some("2.0.0-beta.2"), "2.0.0", false));
+
+        assert!(moves_latest(Some("1.4.0"), "2.0.0-beta.1", true));
+        assert!(!moves_latest(Some("2.0.0"), "2.0.0-beta.1", true));
+    }
+
+    #[test]
+    fn test_published_versions() {
+        let request = |attachments: serde_json::Value| -> NpmPublishRequest {
+            let version = |version: &str, tarball: &str| {
+                json!({
+                    "name": "@scope/pkg",
+                    "version": version,
+                    "dist": { "shasum": "", "tarball": tarball }
+                })
+            };
+            serde_json::from_value(json!({
+                "_id": "@scope/pkg",
+                "name": "@scope/pkg",
+                "versions": {
+                    "1.0.0": version("1.0.0", "http://registry/@scope/pkg/-/@scope/pkg-1.0.0.tgz"),
+                    "1.1.0": version("1.1.0", "http://registry/@scope%2fpkg/-/pkg-1.1.0.tgz"),
+                    "0.9.0": version("0.9.0", "http://registry/@scope/pkg/-/pkg-0.9.0.tgz"),
+                },
+                "_attachments": attachments,
+            }))
+            .unwrap()
+        };
+        let attachment =
+            |content_type: &str| json!({ "content_type": content_type, "data": "", "length": 0 });
+
+        let published = published_versions(&request(json!({
+            "pkg-1.1.0.tgz": attachment("application/octet-stream"),
+            "@scope/pkg-1.0.0.tgz": attachment("application/octet-stream"),
+            "pkg-1.0.0.sigstore": attachment("application/vnd.dev.sigstore.bundle.v0.3+json"),
+        })))
+        .unwrap();
+        assert_eq!(
+            published,
+            vec![
+                ("1.0.0".to_string(), "@scope/pkg-1.0.0.tgz".to_string()),
+                ("1.1.0".to_string(), "pkg-1.1.0.tgz".to_string()),
+            ]
+        );
+
+        for attachments in [
+            json!({ "pkg-2.0.0.tgz": attachment("application/octet-stream") }),
+            json!({ "kg-0.9.0.tgz": attachment("application/octet-stream") }),
+            json!({}),
+        ] {
+            assert!(matches!(
+                published_versions(&request(attachments)),
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+    }
+}
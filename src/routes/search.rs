@@ -0,0 +1,32 @@
+use crate::error::ApiError;
+use crate::models::{RegistryReader, SearchResponse};
+use crate::services::SearchService;
+use crate::services::search::SearchWeights;
+use crate::state::AppState;
+use rocket::serde::json::Json;
+use rocket::{State, get};
+
+// npm search API used by `npm search`
+
+#[get("/registry/-/v1/search?<text>&<size>&<from>&<quality>&<popularity>&<maintenance>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn search(
+    text: Option<&str>,
+    size: Option<usize>,
+    from: Option<usize>,
+    quality: Option<f64>,
+    popularity: Option<f64>,
+    maintenance: Option<f64>,
+    user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    SearchService::search(
+        text.unwrap_or_default(),
+        size,
+        from,
+        SearchWeights::new(quality, popularity, maintenance),
+        user.0.as_ref(),
+        state,
+    )
+    .map(Json)
+}
@@ -0,0 +1,187 @@
+use crate::error::ApiError;
+use crate::models::auth::AuthenticatedUser;
+use crate::models::organization::{Organization, OrganizationRole};
+use crate::models::{
+    NpmAttestationsResponse, NpmRegistryKeysResponse, RegistryReader,
+    SignatureVerificationResponse, SigningKey, SigningKeyListResponse,
+};
+use crate::routes::packages::ensure_read_access;
+use crate::services::{ProvenanceService, SigningService};
+use crate::state::AppState;
+use log::{info, warn};
+use rocket::serde::json::{Json, Value};
+use rocket::{State, delete, get, post};
+
+fn find_organization(name: &str, state: &AppState) -> Result<Organization, ApiError> {
+    state
+        .database
+        .get_organization_by_name(name)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        .ok_or_else(|| ApiError::NotFound(format!("Organization '{name}' not found")))
+}
+
+fn require_organization_admin(
+    organization: &Organization,
+    user: &AuthenticatedUser,
+    state: &AppState,
+) -> Result<(), ApiError> {
+    let has_permission = state
+        .database
+        .check_organization_permission(organization.id, user.user_id, OrganizationRole::Admin)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    if !has_permission {
+        return Err(ApiError::Forbidden(
+            "You don't have permission to manage signing keys of this organization".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Generate a new signing key for an organization
+#[utoipa::path(
+    tag = "signing",
+    responses((status = 200, body = SigningKey)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/organizations/<name>/signing-keys")]
+pub async fn create_signing_key(
+    name: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<SigningKey>, ApiError> {
+    let organization = find_organization(name, state)?;
+    require_organization_admin(&organization, &user, state)?;
+
+    let key = SigningService::generate_key(organization.id, Some(user.user_id), state)?;
+    info!(
+        "User {} created signing key {} for organization {name}",
+        user.username, key.key_id
+    );
+
+    Ok(Json(key))
+}
+
+/// List an organization's public signing keys (public, for consumers verifying signatures)
+#[utoipa::path(
+    tag = "signing",
+    responses((status = 200, body = SigningKeyListResponse))
+)]
+#[get("/api/v1/organizations/<name>/signing-keys")]
+pub async fn list_signing_keys(
+    name: &str,
+    state: &State<AppState>,
+) -> Result<Json<SigningKeyListResponse>, ApiError> {
+    let organization = find_organization(name, state)?;
+
+    let keys = state
+        .database
+        .list_signing_keys(organization.id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    Ok(Json(SigningKeyListResponse {
+        organization: organization.name,
+        keys,
+    }))
+}
+
+/// Revoke a signing key. Signatures made with it are no longer served or accepted.
+#[utoipa::path(
+    tag = "signing",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/organizations/<name>/signing-keys/<key_id>")]
+pub async fn revoke_signing_key(
+    name: &str,
+    key_id: &str,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let organization = find_organization(name, state)?;
+    require_organization_admin(&organization, &user, state)?;
+
+    let revoked = state
+        .database
+        .revoke_signing_key(organization.id, key_id)
+        .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+    if !revoked {
+        return Err(ApiError::NotFound(format!(
+            "Active signing key '{key_id}' not found"
+        )));
+    }
+
+    // Served metadata embeds signatures, so drop cached documents of the organization's packages
+    if let Ok(packages) = state.database.get_packages_by_organization(organization.id) {
+        for package in packages {
+            if let Err(e) = state.cache.invalidate_metadata(&package.name).await {
+                warn!(
+                    "Failed to invalidate metadata cache for package {}: {e}",
+                    package.name
+                );
+            }
+        }
+    }
+
+    info!(
+        "User {} revoked signing key {key_id} of organization {name}",
+        user.username
+    );
+
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
+
+/// Verify the signatures of a published package version
+#[utoipa::path(
+    tag = "signing",
+    responses((status = 200, body = SignatureVerificationResponse))
+)]
+#[get("/api/v1/signatures/verify?<package>&<version>&<integrity>")]
+pub async fn verify_signatures(
+    package: &str,
+    version: &str,
+    integrity: Option<&str>,
+    state: &State<AppState>,
+) -> Result<Json<SignatureVerificationResponse>, ApiError> {
+    let response = SigningService::verify_package_version(package, version, integrity, state)?;
+    Ok(Json(response))
+}
+
+/// Registry public keys, used by `npm audit signatures` to verify `dist.signatures`
+#[get("/registry/-/npm/v1/keys")]
+pub async fn npm_registry_keys(
+    state: &State<AppState>,
+) -> Result<Json<NpmRegistryKeysResponse>, ApiError> {
+    Ok(Json(SigningService::npm_registry_keys(state).await?))
+}
+
+/// Provenance attestations of a version, `<spec>` is `name@version`
+#[get("/registry/-/npm/v1/attestations/<spec>")]
+pub async fn npm_attestations(
+    spec: &str,
+    user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<NpmAttestationsResponse>, ApiError> {
+    // Scoped names start with '@', the version follows the last one
+    let (package, version) = spec
+        .rsplit_once('@')
+        .filter(|(package, _)| !package.is_empty())
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid package spec '{spec}'")))?;
+
+    ensure_read_access(package, &user, state)?;
+
+    let attestations = ProvenanceService::attestations(package, version, state).await?;
+    Ok(Json(attestations))
+}
+
+/// Same as `npm_attestations`, at the URL advertised in `dist.attestations`
+#[get("/-/npm/v1/attestations/<spec>")]
+pub async fn npm_attestations_root(
+    spec: &str,
+    user: RegistryReader,
+    state: &State<AppState>,
+) -> Result<Json<NpmAttestationsResponse>, ApiError> {
+    npm_attestations(spec, user, state).await
+}
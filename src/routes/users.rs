@@ -0,0 +1,102 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, NotificationSubscriptionRequest, NotificationSubscriptionResponse,
+    NotificationSubscriptionsResponse, OptionalAuthenticatedUser, UpdateProfileRequest,
+    UserPackagesResponse, UserProfile,
+};
+use crate::services::{NotificationService, ProfileService};
+use crate::state::AppState;
+use rocket::serde::json::Json;
+use rocket::{State, delete, get, post, put};
+
+/// Public profile of a user
+#[utoipa::path(
+    tag = "users",
+    responses((status = 200, body = UserProfile)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/users/<username>")]
+pub async fn get_user_profile(
+    username: &str,
+    state: &State<AppState>,
+) -> Result<Json<UserProfile>, ApiError> {
+    ProfileService::get(username, state).map(Json)
+}
+
+/// Packages a user owns, private ones only if the caller can read them
+#[utoipa::path(
+    tag = "users",
+    responses((status = 200, body = UserPackagesResponse)),
+    security((), ("bearer" = []))
+)]
+#[get("/api/v1/users/<username>/packages")]
+pub async fn get_user_packages(
+    username: &str,
+    user: OptionalAuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<UserPackagesResponse>, ApiError> {
+    ProfileService::packages(username, user.0.as_ref(), state).map(Json)
+}
+
+/// Replace the display name, avatar URL and links of the current user
+#[utoipa::path(
+    tag = "users",
+    request_body = UpdateProfileRequest,
+    responses((status = 200, body = UserProfile)),
+    security(("bearer" = []))
+)]
+#[put("/api/v1/user/profile", data = "<request>")]
+pub async fn update_profile(
+    request: Json<UpdateProfileRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<UserProfile>, ApiError> {
+    ProfileService::update(&user, request.into_inner(), state).map(Json)
+}
+
+/// Notification subscriptions of the current user
+#[utoipa::path(
+    tag = "users",
+    responses((status = 200, body = NotificationSubscriptionsResponse)),
+    security(("bearer" = []))
+)]
+#[get("/api/v1/user/notifications")]
+pub async fn list_notification_subscriptions(
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<NotificationSubscriptionsResponse>, ApiError> {
+    NotificationService::list(&user, state).map(Json)
+}
+
+/// Subscribe to new versions, deprecations and advisories of a package or `@scope`, sent by
+/// email or to a Slack, Discord or webhook URL, right away or as a daily or weekly digest
+#[utoipa::path(
+    tag = "users",
+    request_body = NotificationSubscriptionRequest,
+    responses((status = 200, body = NotificationSubscriptionResponse)),
+    security(("bearer" = []))
+)]
+#[post("/api/v1/user/notifications", data = "<request>")]
+pub async fn create_notification_subscription(
+    request: Json<NotificationSubscriptionRequest>,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<NotificationSubscriptionResponse>, ApiError> {
+    NotificationService::subscribe(request.into_inner(), &user, state).map(Json)
+}
+
+/// Remove a notification subscription of the current user
+#[utoipa::path(
+    tag = "users",
+    responses((status = 200, body = Object)),
+    security(("bearer" = []))
+)]
+#[delete("/api/v1/user/notifications/<id>")]
+pub async fn delete_notification_subscription(
+    id: i32,
+    user: AuthenticatedUser,
+    state: &State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    NotificationService::unsubscribe(id, &user, state)?;
+    Ok(Json(serde_json::json!({ "ok": true })))
+}
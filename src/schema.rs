@@ -1,5 +1,74 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    advisories (id) {
+        id -> Integer,
+        source -> Text,
+        advisory_id -> Text,
+        package_name -> Text,
+        title -> Text,
+        overview -> Nullable<Text>,
+        url -> Text,
+        severity -> Text,
+        vulnerable_versions -> Text,
+        patched_versions -> Nullable<Text>,
+        cwe -> Nullable<Text>,
+        cves -> Nullable<Text>,
+        cvss_vector -> Nullable<Text>,
+        published_at -> Nullable<Timestamp>,
+        modified_at -> Nullable<Timestamp>,
+        synced_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    allowed_packages (id) {
+        id -> Integer,
+        pattern -> Text,
+        is_regex -> Bool,
+        reason -> Nullable<Text>,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        action -> Text,
+        actor_id -> Nullable<Integer>,
+        organization_id -> Nullable<Integer>,
+        package_name -> Nullable<Text>,
+        version -> Nullable<Text>,
+        details -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    bandwidth_usage (id) {
+        id -> Integer,
+        day -> Date,
+        package_name -> Text,
+        user_id -> Integer,
+        token_id -> Integer,
+        bytes -> BigInt,
+        requests -> BigInt,
+    }
+}
+
+diesel::table! {
+    blocked_names (id) {
+        id -> Integer,
+        pattern -> Text,
+        is_regex -> Bool,
+        block_upstream -> Bool,
+        reason -> Nullable<Text>,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     cache_stats (id) {
         id -> Integer,
@@ -10,6 +79,71 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    client_certificates (id) {
+        id -> Integer,
+        user_id -> Integer,
+        fingerprint -> Nullable<Text>,
+        subject -> Nullable<Text>,
+        description -> Nullable<Text>,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    download_rollups (id) {
+        id -> Integer,
+        package_name -> Text,
+        version -> Text,
+        period -> Text,
+        period_start -> Date,
+        downloads -> BigInt,
+    }
+}
+
+diesel::table! {
+    flagged_names (id) {
+        id -> Integer,
+        package_name -> Text,
+        similar_to -> Text,
+        reason -> Text,
+        status -> Text,
+        published_by -> Nullable<Integer>,
+        reviewed_by -> Nullable<Integer>,
+        reviewed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    hooks (id) {
+        id -> Integer,
+        user_id -> Integer,
+        hook_type -> Text,
+        name -> Text,
+        endpoint -> Text,
+        secret -> Text,
+        last_delivery -> Nullable<Timestamp>,
+        response_code -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    invitations (id) {
+        id -> Integer,
+        token -> Text,
+        email -> Nullable<Text>,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        used_by -> Nullable<Integer>,
+    }
+}
+
 diesel::table! {
     metadata_cache (id) {
         id -> Integer,
@@ -24,6 +158,33 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    notification_subscriptions (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        events -> Text,
+        channel -> Text,
+        endpoint -> Nullable<Text>,
+        frequency -> Text,
+        last_sent_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Integer,
+        subscription_id -> Integer,
+        event -> Text,
+        package_name -> Text,
+        version -> Nullable<Text>,
+        message -> Text,
+        created_at -> Timestamp,
+        sent_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     organization_members (id) {
         id -> Integer,
@@ -45,6 +206,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    package_cache_stats (id) {
+        id -> Integer,
+        package_name -> Text,
+        day -> Date,
+        hits -> BigInt,
+        misses -> BigInt,
+        cache_bytes -> BigInt,
+        upstream_bytes -> BigInt,
+    }
+}
+
 diesel::table! {
     package_files (id) {
         id -> Integer,
@@ -61,6 +234,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    package_attestations (id) {
+        id -> Integer,
+        package_version_id -> Integer,
+        predicate_type -> Text,
+        bundle -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     package_owners (id) {
         id -> Integer,
@@ -71,6 +254,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    package_signatures (id) {
+        id -> Integer,
+        package_version_id -> Integer,
+        signing_key_id -> Integer,
+        integrity -> Text,
+        signature -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     package_tags (id) {
         id -> Integer,
@@ -82,6 +276,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    package_visibility_changes (id) {
+        id -> Integer,
+        package_id -> Integer,
+        package_name -> Text,
+        old_visibility -> Text,
+        new_visibility -> Text,
+        changed_by -> Nullable<Integer>,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     package_versions (id) {
         id -> Integer,
@@ -98,6 +305,11 @@ diesel::table! {
         readme -> Nullable<Text>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        integrity -> Nullable<Text>,
+        yanked_at -> Nullable<Timestamp>,
+        yank_reason -> Nullable<Text>,
+        registry_key_id -> Nullable<Text>,
+        registry_signature -> Nullable<Text>,
     }
 }
 
@@ -114,6 +326,133 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         organization_id -> Nullable<Integer>,
+        visibility -> Text,
+    }
+}
+
+diesel::table! {
+    pinned_packages (id) {
+        id -> Integer,
+        name -> Text,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    quarantined_packages (id) {
+        id -> Integer,
+        name -> Text,
+        major -> Nullable<Integer>,
+        status -> Text,
+        requested_by -> Nullable<Integer>,
+        reviewed_by -> Nullable<Integer>,
+        reviewed_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    registry_keys (id) {
+        id -> Integer,
+        key_id -> Text,
+        source -> Text,
+        keytype -> Text,
+        scheme -> Text,
+        public_key -> Text,
+        private_key -> Nullable<Text>,
+        expires_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    retention_policies (id) {
+        id -> Integer,
+        organization_id -> Integer,
+        name -> Text,
+        keep_last -> Nullable<Integer>,
+        max_age_days -> Nullable<Integer>,
+        prerelease_only -> Bool,
+        dry_run -> Bool,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    scope_policies (id) {
+        id -> Integer,
+        scope -> Text,
+        publish_access -> Text,
+        allow_upstream -> Bool,
+        allow_anonymous -> Bool,
+        updated_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    service_accounts (id) {
+        id -> Integer,
+        user_id -> Integer,
+        organization_id -> Integer,
+        description -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    signing_keys (id) {
+        id -> Integer,
+        organization_id -> Integer,
+        key_id -> Text,
+        algorithm -> Text,
+        public_key -> Text,
+        private_key -> Text,
+        is_active -> Bool,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    staged_publishes (id) {
+        id -> Integer,
+        package_name -> Text,
+        version -> Text,
+        publisher_id -> Integer,
+        request_path -> Text,
+        size_bytes -> BigInt,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    tasks (id) {
+        id -> Integer,
+        kind -> Text,
+        status -> Text,
+        input -> Nullable<Text>,
+        result -> Nullable<Text>,
+        error -> Nullable<Text>,
+        created_by -> Nullable<Integer>,
+        created_at -> Timestamp,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    tarball_entries (id) {
+        id -> Integer,
+        package_version_id -> Integer,
+        path -> Text,
+        size_bytes -> BigInt,
+        content_type -> Text,
     }
 }
 
@@ -126,6 +465,10 @@ diesel::table! {
         created_at -> Timestamp,
         expires_at -> Nullable<Timestamp>,
         is_active -> Bool,
+        last_used_at -> Nullable<Timestamp>,
+        user_agent -> Nullable<Text>,
+        ip_address -> Nullable<Text>,
+        token_preview -> Text,
     }
 }
 
@@ -138,28 +481,107 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         is_active -> Bool,
+        role -> Text,
+        email_verified -> Bool,
+        display_name -> Nullable<Text>,
+        avatar_url -> Nullable<Text>,
+        links -> Nullable<Text>,
+        auth_plugin -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    version_downloads (id) {
+        id -> Integer,
+        package_name -> Text,
+        version -> Text,
+        day -> Date,
+        downloads -> BigInt,
+    }
+}
+
+diesel::table! {
+    version_tombstones (id) {
+        id -> Integer,
+        package_name -> Text,
+        version -> Text,
+        reason -> Text,
+        deleted_by -> Nullable<Integer>,
+        created_at -> Timestamp,
     }
 }
 
+diesel::joinable!(allowed_packages -> users (created_by));
+diesel::joinable!(audit_log -> organizations (organization_id));
+diesel::joinable!(audit_log -> users (actor_id));
+diesel::joinable!(blocked_names -> users (created_by));
+diesel::joinable!(client_certificates -> users (user_id));
+diesel::joinable!(hooks -> users (user_id));
+diesel::joinable!(notification_subscriptions -> users (user_id));
+diesel::joinable!(notifications -> notification_subscriptions (subscription_id));
 diesel::joinable!(organization_members -> organizations (organization_id));
 diesel::joinable!(organization_members -> users (user_id));
 diesel::joinable!(package_files -> package_versions (package_version_id));
 diesel::joinable!(package_owners -> users (user_id));
+diesel::joinable!(package_attestations -> package_versions (package_version_id));
+diesel::joinable!(package_signatures -> package_versions (package_version_id));
+diesel::joinable!(package_signatures -> signing_keys (signing_key_id));
 diesel::joinable!(package_versions -> packages (package_id));
+diesel::joinable!(package_visibility_changes -> packages (package_id));
+diesel::joinable!(package_visibility_changes -> users (changed_by));
 diesel::joinable!(packages -> organizations (organization_id));
 diesel::joinable!(packages -> users (author_id));
+diesel::joinable!(pinned_packages -> users (created_by));
+diesel::joinable!(retention_policies -> organizations (organization_id));
+diesel::joinable!(retention_policies -> users (created_by));
+diesel::joinable!(scope_policies -> users (updated_by));
+diesel::joinable!(service_accounts -> organizations (organization_id));
+diesel::joinable!(service_accounts -> users (user_id));
+diesel::joinable!(signing_keys -> organizations (organization_id));
+diesel::joinable!(staged_publishes -> users (publisher_id));
+diesel::joinable!(tarball_entries -> package_versions (package_version_id));
+diesel::joinable!(tasks -> users (created_by));
 diesel::joinable!(user_tokens -> users (user_id));
+diesel::joinable!(version_tombstones -> users (deleted_by));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    advisories,
+    allowed_packages,
+    audit_log,
+    bandwidth_usage,
+    blocked_names,
     cache_stats,
+    client_certificates,
+    download_rollups,
+    flagged_names,
+    hooks,
+    invitations,
     metadata_cache,
+    notification_subscriptions,
+    notifications,
     organization_members,
     organizations,
+    package_attestations,
+    package_cache_stats,
     package_files,
     package_owners,
+    package_signatures,
     package_tags,
     package_versions,
+    package_visibility_changes,
     packages,
+    pinned_packages,
+    quarantined_packages,
+    registry_keys,
+    retention_policies,
+    scope_policies,
+    service_accounts,
+    signing_keys,
+    staged_publishes,
+    tarball_entries,
+    tasks,
     user_tokens,
     users,
+    version_downloads,
+    version_tombstones,
 );
@@ -0,0 +1,420 @@
+//! Secrets read from files and HashiCorp Vault instead of plain environment variables
+
+use crate::config::{AppConfig, ConfigFile, TokenKey};
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Settings that can be read from the file named by `<NAME>_FILE`, and except for the Vault
+/// token itself, from Vault
+pub const SECRET_ENV_VARS: &[&str] = &[
+    "CLEF_UPSTREAM_REGISTRY",
+    "CLEF_HTTP_PROXY_PASSWORD",
+    "CLEF_DATABASE_URL",
+    "CLEF_ADMIN_PASSWORD",
+    "CLEF_SMTP_USERNAME",
+    "CLEF_SMTP_PASSWORD",
+    "CLEF_AUTH_TOKEN_SECRET",
+    "CLEF_TOKEN_KEYS",
+    "CLEF_VAULT_TOKEN",
+];
+
+const VAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where secrets are read from. Fixed at startup, the files and the Vault path are read
+/// again on every refresh.
+#[derive(Debug, Clone, Default)]
+pub struct SecretSources {
+    /// Files named by `<NAME>_FILE`, keyed by the variable they stand in for
+    pub files: HashMap<&'static str, PathBuf>,
+    pub vault: Option<VaultSource>,
+}
+
+#[derive(Clone)]
+pub struct VaultSource {
+    /// e.g. `https://vault.example.com:8200`
+    pub addr: String,
+    /// Secret path below `/v1/`, e.g. `secret/data/clef` for a KV v2 engine
+    pub path: String,
+    /// Token set in `CLEF_VAULT_TOKEN`, unless it's read from `CLEF_VAULT_TOKEN_FILE`
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for VaultSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultSource")
+            .field("addr", &self.addr)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecretSources {
+    /// Reads the `<NAME>_FILE` variables and the Vault settings. Secrets set directly in the
+    /// environment aren't looked up anywhere else.
+    pub fn from_env_and_file(file: &ConfigFile) -> Self {
+        let var = |name: &str| {
+            env::var(name)
+                .ok()
+                .or_else(|| file.get(name).cloned())
+                .filter(|value| !value.is_empty())
+        };
+
+        let files = SECRET_ENV_VARS
+            .iter()
+            .filter(|name| env::var_os(name).is_none())
+            .filter_map(|name| {
+                env::var_os(format!("{name}_FILE")).map(|path| (*name, PathBuf::from(path)))
+            })
+            .collect();
+
+        let vault = var("CLEF_VAULT_ADDR").map(|addr| VaultSource {
+            addr: addr.trim_end_matches('/').to_string(),
+            path: var("CLEF_VAULT_PATH")
+                .unwrap_or_else(|| "secret/data/clef".to_string())
+                .trim_matches('/')
+                .to_string(),
+            token: var("CLEF_VAULT_TOKEN"),
+        });
+
+        Self { files, vault }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.vault.is_none()
+    }
+}
+
+impl VaultSource {
+    async fn read(&self, token: &str) -> Result<Vec<(&'static str, String)>, String> {
+        let url = format!("{}/v1/{}", self.addr, self.path);
+        let client = reqwest::Client::builder()
+            .timeout(VAULT_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create Vault client: {e}"))?;
+
+        let response = client
+            .get(&url)
+            .header("X-Vault-Token", token)
+            .send()
+            .await
+            .map_err(|e| format!("Vault request to {url} failed: {e}"))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Vault returned {status} for {url}"));
+        }
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Vault response from {url}: {e}"))?;
+
+        Ok(Self::secrets(&body))
+    }
+
+    /// Secrets in a KV v2 (`data.data`) or KV v1 (`data`) response. Keys are variable or
+    /// setting names, `CLEF_SMTP_PASSWORD` or `smtp_password`.
+    fn secrets(body: &Value) -> Vec<(&'static str, String)> {
+        let data = &body["data"];
+        let data = if data["data"].is_object() {
+            &data["data"]
+        } else {
+            data
+        };
+        let Some(data) = data.as_object() else {
+            return Vec::new();
+        };
+
+        SECRET_ENV_VARS
+            .iter()
+            .filter(|name| **name != "CLEF_VAULT_TOKEN")
+            .filter_map(|name| {
+                let key = name.trim_start_matches("CLEF_").to_lowercase();
+                let value = data.get(*name).or_else(|| data.get(&key))?;
+                value.as_str().map(|value| (*name, value.to_string()))
+            })
+            .collect()
+    }
+}
+
+/// Secret values read from their sources, keyed by environment variable
+#[derive(Clone, Default)]
+pub struct Secrets {
+    pub sources: SecretSources,
+    values: HashMap<&'static str, String>,
+}
+
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Secrets")
+            .field("sources", &self.sources)
+            .field("names", &self.values.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Secrets {
+    /// Reads the secrets at startup. Vault is queried on a thread of its own, this runs
+    /// before and inside the server's runtime.
+    pub fn load(sources: SecretSources) -> Result<Self, String> {
+        if sources.is_empty() {
+            return Ok(Self {
+                sources,
+                values: HashMap::new(),
+            });
+        }
+
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| format!("Failed to start runtime: {e}"))?
+                .block_on(Self::fetch(sources))
+        })
+        .join()
+        .map_err(|_| "Loading secrets panicked".to_string())?
+    }
+
+    /// Reads the files, then Vault. A secret in a file wins over the same secret in Vault.
+    pub async fn fetch(sources: SecretSources) -> Result<Self, String> {
+        let mut values = HashMap::new();
+        for (name, path) in &sources.files {
+            let value = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("Failed to read {name}_FILE {}: {e}", path.display()))?;
+            values.insert(*name, value.trim_end_matches(['\r', '\n']).to_string());
+        }
+
+        if let Some(vault) = &sources.vault {
+            let token = values
+                .get("CLEF_VAULT_TOKEN")
+                .or(vault.token.as_ref())
+                .ok_or("CLEF_VAULT_ADDR is set without CLEF_VAULT_TOKEN")?
+                .clone();
+            for (name, value) in vault.read(&token).await? {
+                if env::var_os(name).is_none() {
+                    values.entry(name).or_insert(value);
+                }
+            }
+        }
+
+        Ok(Self { sources, values })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.values.get(name)
+    }
+}
+
+/// The current secrets, re-read periodically so rotated credentials are picked up without
+/// a restart
+#[derive(Debug, Default)]
+pub struct SecretStore {
+    secrets: RwLock<Secrets>,
+}
+
+impl SecretStore {
+    pub fn new(secrets: Secrets) -> Self {
+        Self {
+            secrets: RwLock::new(secrets),
+        }
+    }
+
+    /// Current value of a secret read from a file or Vault, `None` when it's set otherwise
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.secrets
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+    }
+
+    /// Keys tokens are hashed with, `CLEF_TOKEN_KEYS` as last read from a file or Vault
+    pub fn token_keys(&self, config: &AppConfig) -> Vec<TokenKey> {
+        match self.get("CLEF_TOKEN_KEYS") {
+            Some(keys) => TokenKey::parse_list(&keys),
+            None => config.token_keys.clone(),
+        }
+    }
+
+    /// Re-reads the secrets every `interval_secs`. A failed refresh keeps the current values.
+    pub fn spawn_refresh(store: Arc<Self>, interval_secs: u64) {
+        let sources = store
+            .secrets
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .sources
+            .clone();
+        if interval_secs == 0 || sources.is_empty() {
+            return;
+        }
+        let interval = Duration::from_secs(interval_secs);
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match Secrets::fetch(sources.clone()).await {
+                    Ok(secrets) => {
+                        let mut current = store.secrets.write().unwrap_or_else(|e| e.into_inner());
+                        for name in SECRET_ENV_VARS {
+                            if current.get(name) != secrets.get(name) {
+                                info!("Secret {name} changed, using the new value");
+                            }
+                        }
+                        *current = secrets;
+                    }
+                    Err(e) => warn!("Refreshing secrets failed, keeping the current ones: {e}"),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_vault_secrets() {
+        let kv2 = json!({
+            "data": {
+                "data": {
+                    "CLEF_SMTP_PASSWORD": "hunter2",
+                    "auth_token_secret": "signing-key",
+                    "vault_token": "ignored",
+                    "unrelated": "value",
+                },
+                "metadata": { "version": 3 },
+            }
+        });
+        let mut secrets = VaultSource::secrets(&kv2);
+        secrets.sort();
+        assert_eq!(
+            secrets,
+            vec![
+                ("CLEF_AUTH_TOKEN_SECRET", "signing-key".to_string()),
+                ("CLEF_SMTP_PASSWORD", "hunter2".to_string()),
+            ]
+        );
+
+        let kv1 = json!({ "data": { "database_url": "/var/lib/clef/clef.db" } });
+        assert_eq!(
+            VaultSource::secrets(&kv1),
+            vec![("CLEF_DATABASE_URL", "/var/lib/clef/clef.db".to_string())]
+        );
+        assert!(VaultSource::secrets(&json!({ "errors": [] })).is_empty());
+    }
+
+    #[test]
+    fn test_load_secret_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("smtp_password");
+        std::fs::write(&path, "hunter2\n").unwrap();
+
+        let sources = SecretSources {
+            files: HashMap::from([("CLEF_SMTP_PASSWORD", path)]),
+            vault: None,
+        };
+        let secrets = Secrets::load(sources.clone()).unwrap();
+        assert_eq!(secrets.get("CLEF_SMTP_PASSWORD").unwrap(), "hunter2");
+        assert!(secrets.get("CLEF_AUTH_TOKEN_SECRET").is_none());
+
+        std::fs::remove_file(dir.path().join("smtp_password")).unwrap();
+        assert!(
+            Secrets::load(sources)
+                .unwrap_err()
+                .contains("CLEF_SMTP_PASSWORD_FILE")
+        );
+    }
+
+    /// Accepts one proxied request and returns its `Proxy-Authorization` header
+    async fn proxy_authorization(listener: &tokio::net::TcpListener) -> Option<String> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = socket.read(&mut buf).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..read]);
+        }
+        socket
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        String::from_utf8_lossy(&request).lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("proxy-authorization")
+                .then(|| value.trim().to_string())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_refreshed_secrets_are_used() {
+        use base64::prelude::*;
+
+        let dir = tempfile::tempdir().unwrap();
+        let password_path = dir.path().join("proxy_password");
+        let keys_path = dir.path().join("token_keys");
+        std::fs::write(&password_path, "old password\n").unwrap();
+        std::fs::write(&keys_path, "2025-01:old secret\n").unwrap();
+
+        let sources = SecretSources {
+            files: HashMap::from([
+                ("CLEF_HTTP_PROXY_PASSWORD", password_path.clone()),
+                ("CLEF_TOKEN_KEYS", keys_path.clone()),
+            ]),
+            vault: None,
+        };
+        let store = Arc::new(SecretStore::new(Secrets::fetch(sources).await.unwrap()));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = AppConfig {
+            http_proxy: Some(format!("http://{}", listener.local_addr().unwrap())),
+            http_proxy_username: Some("clef".to_string()),
+            ..AppConfig::default()
+        };
+        let client = crate::create_http_client(&config, &store).unwrap();
+        let basic = |password: &str| {
+            format!(
+                "Basic {}",
+                BASE64_STANDARD.encode(format!("clef:{password}"))
+            )
+        };
+
+        let request = tokio::spawn(client.get("http://upstream.test/pkg").send());
+        assert_eq!(
+            proxy_authorization(&listener).await,
+            Some(basic("old password"))
+        );
+        request.await.unwrap().unwrap();
+        assert_eq!(store.token_keys(&config)[0].id, "2025-01");
+
+        std::fs::write(&password_path, "new password\n").unwrap();
+        std::fs::write(&keys_path, "2025-07:new secret,2025-01:old secret\n").unwrap();
+        SecretStore::spawn_refresh(store.clone(), 1);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        // The client built at startup authenticates with the rotated password
+        let request = tokio::spawn(client.get("http://upstream.test/pkg").send());
+        assert_eq!(
+            proxy_authorization(&listener).await,
+            Some(basic("new password"))
+        );
+        request.await.unwrap().unwrap();
+        let keys = store.token_keys(&config);
+        assert_eq!(
+            keys.iter().map(|key| key.id.as_str()).collect::<Vec<_>>(),
+            vec!["2025-07", "2025-01"]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use crate::models::{UpdateUser, User};
+use crate::schema::users;
+use crate::services::{AuthService, DatabaseService, MailerService};
+use crate::state::AppState;
+use base64::prelude::*;
+use diesel::prelude::*;
+use hmac::{Hmac, Mac};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const EMAIL_VERIFICATION_PURPOSE: &str = "verify-email";
+const PASSWORD_RESET_PURPOSE: &str = "reset-password";
+const EMAIL_VERIFICATION_TTL_HOURS: i64 = 48;
+const PASSWORD_RESET_TTL_HOURS: i64 = 1;
+
+/// Payload of a signed account token
+#[derive(Serialize, Deserialize, Debug)]
+struct AccountTokenClaims {
+    sub: i32,
+    purpose: String,
+    exp: i64,
+    /// Binds the token to the account state it was issued for (email or password hash),
+    /// so it stops working once that changes
+    fp: String,
+}
+
+pub struct AccountService;
+
+impl AccountService {
+    /// Emails the user a link to confirm their address. Does nothing without SMTP.
+    pub async fn send_verification_email(state: &AppState, user: &User) -> Result<(), ApiError> {
+        if !MailerService::is_configured(&state.config) {
+            debug!(
+                "SMTP is not configured, skipping verification email for {}",
+                user.username
+            );
+            return Ok(());
+        }
+
+        let token = Self::issue_token(
+            &state.config,
+            user,
+            EMAIL_VERIFICATION_PURPOSE,
+            chrono::Duration::hours(EMAIL_VERIFICATION_TTL_HOURS),
+        );
+        let link = format!(
+            "{}/api/v1/auth/verify-email?token={token}",
+            state.config.get_public_url()
+        );
+
+        let body = format!(
+            "Hi {},\n\nPlease confirm your email address for clef by opening the link below:\n\n{link}\n\nThe link expires in {EMAIL_VERIFICATION_TTL_HOURS} hours.\n",
+            user.username
+        );
+
+        MailerService::send(state, &user.email, "Verify your email address", body).await
+    }
+
+    /// Marks the user's email as verified if the token is valid
+    pub fn verify_email(
+        db: &DatabaseService,
+        config: &AppConfig,
+        token: &str,
+    ) -> Result<User, ApiError> {
+        let claims = Self::decode_token(config, token, EMAIL_VERIFICATION_PURPOSE)?;
+
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = Self::find_token_user(&mut conn, &claims)?;
+
+        let user = diesel::update(users::table.find(user.id))
+            .set(&UpdateUser {
+                email: None,
+                password_hash: None,
+                updated_at: Some(chrono::Utc::now().naive_utc()),
+                is_active: None,
+                role: None,
+                email_verified: Some(true),
+            })
+            .get_result::<User>(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to update user: {e}")))?;
+
+        info!("Email verified for user {}", user.username);
+        Ok(user)
+    }
+
+    /// Emails a password reset link to the account with this address. Unknown addresses are
+    /// ignored so the endpoint can't be used to discover accounts.
+    pub async fn request_password_reset(state: &AppState, email: &str) -> Result<(), ApiError> {
+        if !MailerService::is_configured(&state.config) {
+            return Err(ApiError::BadRequest(
+                "Password reset by email is not available, SMTP is not configured".to_string(),
+            ));
+        }
+
+        let mut conn = state.database.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = users::table
+            .filter(users::email.eq(email))
+            .filter(users::is_active.eq(true))
+            .first::<User>(&mut conn)
+            .optional()
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+        let Some(user) = user else {
+            debug!("Password reset requested for unknown email");
+            return Ok(());
+        };
+
+        let token = Self::issue_token(
+            &state.config,
+            &user,
+            PASSWORD_RESET_PURPOSE,
+            chrono::Duration::hours(PASSWORD_RESET_TTL_HOURS),
+        );
+
+        let body = format!(
+            "Hi {},\n\nA password reset was requested for your clef account. Use the token below with POST {}/api/v1/auth/password-reset to choose a new password:\n\n{token}\n\nThe token expires in {PASSWORD_RESET_TTL_HOURS} hour(s). If you did not request this, you can ignore this email.\n",
+            user.username,
+            state.config.get_public_url()
+        );
+
+        if let Err(e) = MailerService::send(state, &user.email, "Reset your password", body).await {
+            warn!(
+                "Failed to send password reset email to {}: {e:?}",
+                user.username
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Sets a new password using a reset token. All existing sessions are revoked.
+    pub fn reset_password(
+        db: &DatabaseService,
+        config: &AppConfig,
+        token: &str,
+        password: &str,
+    ) -> Result<User, ApiError> {
+        if password.is_empty() {
+            return Err(ApiError::BadRequest("Password cannot be empty".to_string()));
+        }
+
+        let claims = Self::decode_token(config, token, PASSWORD_RESET_PURPOSE)?;
+
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = Self::find_token_user(&mut conn, &claims)?;
+        drop(conn);
+
+        AuthService::reset_password(db, &user.username, password)?;
+
+        info!("Password reset via email token for user {}", user.username);
+        Ok(user)
+    }
+
+    fn find_token_user(
+        conn: &mut crate::database::DbConnection,
+        claims: &AccountTokenClaims,
+    ) -> Result<User, ApiError> {
+        let user = users::table
+            .find(claims.sub)
+            .filter(users::is_active.eq(true))
+            .first::<User>(conn)
+            .optional()
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?
+            .ok_or_else(|| ApiError::BadRequest("Invalid or expired token".to_string()))?;
+
+        // The account changed since the token was issued
+        if Self::fingerprint(&claims.purpose, &user) != claims.fp {
+            return Err(ApiError::BadRequest("Invalid or expired token".to_string()));
+        }
+
+        Ok(user)
+    }
+
+    fn fingerprint(purpose: &str, user: &User) -> String {
+        let bound_value = match purpose {
+            PASSWORD_RESET_PURPOSE => &user.password_hash,
+            _ => &user.email,
+        };
+        let digest = Sha256::digest(bound_value.as_bytes());
+        BASE64_URL_SAFE_NO_PAD.encode(&digest[..12])
+    }
+
+    fn sign(config: &AppConfig, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(config.auth_token_secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn issue_token(
+        config: &AppConfig,
+        user: &User,
+        purpose: &str,
+        ttl: chrono::Duration,
+    ) -> String {
+        let claims = AccountTokenClaims {
+            sub: user.id,
+            purpose: purpose.to_string(),
+            exp: (chrono::Utc::now() + ttl).timestamp(),
+            fp: Self::fingerprint(purpose, user),
+        };
+
+        let payload = BASE64_URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&claims).expect("claims serialize to JSON"));
+        let signature =
+            BASE64_URL_SAFE_NO_PAD.encode(Self::sign(config, &payload).finalize().into_bytes());
+
+        format!("{payload}.{signature}")
+    }
+
+    fn decode_token(
+        config: &AppConfig,
+        token: &str,
+        purpose: &str,
+    ) -> Result<AccountTokenClaims, ApiError> {
+        let invalid = || ApiError::BadRequest("Invalid or expired token".to_string());
+
+        let (payload, signature) = token.split_once('.').ok_or_else(invalid)?;
+        let signature = BASE64_URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| invalid())?;
+        Self::sign(config, payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+
+        let claims: AccountTokenClaims = BASE64_URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(invalid)?;
+
+        if claims.purpose != purpose || claims.exp < chrono::Utc::now().timestamp() {
+            return Err(invalid());
+        }
+
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_user() -> User {
+        let now = chrono::Utc::now().naive_utc();
+        User {
+            id: 7,
+            username: "alice".to_string(),
+            email: "alice@example.com".to_string(),
+            password_hash: "hash".to_string(),
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            role: "user".to_string(),
+            email_verified: false,
+            display_name: None,
+            avatar_url: None,
+            links: None,
+            auth_plugin: None,
+        }
+    }
+
+    #[test]
+    fn test_account_token_round_trip() {
+        let config = AppConfig::default();
+        let user = test_user();
+        let token = AccountService::issue_token(
+            &config,
+            &user,
+            PASSWORD_RESET_PURPOSE,
+            chrono::Duration::hours(1),
+        );
+
+        let claims = AccountService::decode_token(&config, &token, PASSWORD_RESET_PURPOSE).unwrap();
+        assert_eq!(claims.sub, user.id);
+        assert_eq!(
+            claims.fp,
+            AccountService::fingerprint(PASSWORD_RESET_PURPOSE, &user)
+        );
+
+        // Wrong purpose, tampered payload, other secret and expired tokens are rejected
+        assert!(AccountService::decode_token(&config, &token, EMAIL_VERIFICATION_PURPOSE).is_err());
+        let tampered = format!("x{token}");
+        assert!(AccountService::decode_token(&config, &tampered, PASSWORD_RESET_PURPOSE).is_err());
+        assert!(
+            AccountService::decode_token(&AppConfig::default(), &token, PASSWORD_RESET_PURPOSE)
+                .is_err()
+        );
+        let expired = AccountService::issue_token(
+            &config,
+            &user,
+            PASSWORD_RESET_PURPOSE,
+            chrono::Duration::hours(-1),
+        );
+        assert!(AccountService::decode_token(&config, &expired, PASSWORD_RESET_PURPOSE).is_err());
+    }
+}
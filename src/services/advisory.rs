@@ -0,0 +1,879 @@
+use crate::error::ApiError;
+use crate::models::{
+    Advisory, AdvisoryStatusResponse, AdvisorySyncReport, AuthenticatedUser,
+    InternalAdvisoryRequest, NewAdvisory, RegistryEvent,
+};
+use crate::state::AppState;
+use chrono::NaiveDateTime;
+use flate2::read::GzDecoder;
+use log::{debug, error, info};
+use semver::{Version, VersionReq};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::time::Instant;
+
+/// Source name of advisories synced from OSV.dev
+pub const OSV_SOURCE: &str = "osv";
+
+/// Source name of advisories defined by registry admins
+pub const INTERNAL_SOURCE: &str = "internal";
+
+/// Packages per OSV batch query, the API maximum
+const QUERY_BATCH_SIZE: usize = 1000;
+
+/// Advisory documents fetched from OSV.dev in parallel
+const FETCH_CONCURRENCY: usize = 8;
+
+const SEVERITIES: [&str; 5] = ["info", "low", "moderate", "high", "critical"];
+
+/// A dependency found while walking an audit request's dependency tree
+struct AuditedDependency {
+    version: String,
+    path: String,
+    dev: bool,
+    optional: bool,
+}
+
+pub struct AdvisoryService;
+
+impl AdvisoryService {
+    /// Pulls advisories for every package proxied from upstream from OSV.dev. Only new and
+    /// modified advisories are downloaded, withdrawn ones are removed.
+    pub async fn sync(state: &AppState) -> Result<AdvisorySyncReport, ApiError> {
+        let started = Instant::now();
+        let mut report = AdvisorySyncReport::default();
+
+        let packages = state
+            .database
+            .get_upstream_package_names()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        let known = state
+            .database
+            .get_advisory_versions(OSV_SOURCE)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        report.packages_checked = packages.len();
+
+        // Advisory ids with their modification time, for every package we serve
+        let mut current: HashMap<String, Option<NaiveDateTime>> = HashMap::new();
+        for chunk in packages.chunks(QUERY_BATCH_SIZE) {
+            current.extend(Self::query_batch(chunk, state).await?);
+        }
+
+        let changed: Vec<String> = current
+            .iter()
+            .filter(|(id, modified)| known.get(*id) != Some(*modified))
+            .map(|(id, _)| id.clone())
+            .collect();
+        debug!(
+            "{} advisories affect {} packages, {} are new or modified",
+            current.len(),
+            packages.len(),
+            changed.len()
+        );
+
+        for ids in changed.chunks(FETCH_CONCURRENCY) {
+            let mut tasks = tokio::task::JoinSet::new();
+            for id in ids {
+                let url = format!("{}/v1/vulns/{id}", state.config.osv_url);
+                let client = state.client.clone();
+                tasks.spawn(async move {
+                    let response = client.get(&url).send().await?.error_for_status()?;
+                    response.json::<Value>().await
+                });
+            }
+
+            while let Some(result) = tasks.join_next().await {
+                let vuln = result
+                    .map_err(|e| ApiError::InternalServerError(format!("Sync task failed: {e}")))?
+                    .map_err(|e| ApiError::UpstreamError(format!("OSV request failed: {e}")))?;
+                let Some(id) = vuln.get("id").and_then(Value::as_str) else {
+                    continue;
+                };
+
+                if vuln.get("withdrawn").is_some_and(|w| !w.is_null()) {
+                    report.advisories_removed += Self::delete(id, state)?;
+                    continue;
+                }
+
+                let rows = Self::advisories_from_osv(&vuln, chrono::Utc::now().naive_utc());
+                state
+                    .database
+                    .replace_advisory(OSV_SOURCE, id, &rows)
+                    .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+                report.advisories_updated += 1;
+
+                // The first sync finds every existing advisory, only announce later ones
+                if !known.is_empty() && !known.contains_key(id) {
+                    rows.iter().for_each(|row| Self::announce(row, state));
+                }
+            }
+        }
+
+        // Advisories that no longer affect any package we serve
+        for id in known.keys().filter(|id| !current.contains_key(*id)) {
+            report.advisories_removed += Self::delete(id, state)?;
+        }
+
+        report.duration_ms = started.elapsed().as_millis();
+        info!(
+            "Advisory sync checked {} packages: {} advisories updated, {} removed in {} ms",
+            report.packages_checked,
+            report.advisories_updated,
+            report.advisories_removed,
+            report.duration_ms
+        );
+
+        Ok(report)
+    }
+
+    /// When advisories were last synced from OSV.dev
+    pub fn last_synced_at(state: &AppState) -> Option<NaiveDateTime> {
+        state
+            .database
+            .get_advisory_stats(OSV_SOURCE)
+            .ok()
+            .and_then(|(_, _, last)| last)
+    }
+
+    pub fn status(state: &AppState) -> Result<AdvisoryStatusResponse, ApiError> {
+        let (advisories, packages, last_synced_at) = state
+            .database
+            .get_advisory_stats(OSV_SOURCE)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let (internal_advisories, _, _) = state
+            .database
+            .get_advisory_stats(INTERNAL_SOURCE)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(AdvisoryStatusResponse {
+            advisories,
+            packages,
+            last_synced_at,
+            internal_advisories,
+            osv_url: state.config.osv_url.clone(),
+            sync_interval_hours: state.config.advisory_sync_hours,
+        })
+    }
+
+    pub fn list_internal(state: &AppState) -> Result<Vec<Advisory>, ApiError> {
+        state
+            .database
+            .list_advisories(INTERNAL_SOURCE)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))
+    }
+
+    /// Adds an internal advisory. It is reported by `npm audit` like any synced advisory.
+    pub fn create_internal(
+        request: InternalAdvisoryRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<Advisory, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+        let advisory_id = format!(
+            "CLEF-{}",
+            uuid::Uuid::new_v4().simple().to_string()[..12].to_uppercase()
+        );
+        let row = Self::internal_row(request, advisory_id, now, now)?;
+
+        let advisory = state
+            .database
+            .create_advisory(&row)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Self::announce(&row, state);
+
+        info!(
+            "User {} added internal advisory {} for {} {}",
+            actor.username,
+            advisory.advisory_id,
+            advisory.package_name,
+            advisory.vulnerable_versions
+        );
+
+        Ok(advisory)
+    }
+
+    /// Replaces an internal advisory, keeping its identifier and publication time
+    pub fn update_internal(
+        id: i32,
+        request: InternalAdvisoryRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<Advisory, ApiError> {
+        let not_found = || ApiError::NotFound(format!("Internal advisory {id} not found"));
+        let existing = Self::list_internal(state)?
+            .into_iter()
+            .find(|advisory| advisory.id == id)
+            .ok_or_else(not_found)?;
+
+        let published_at = existing.published_at.unwrap_or(existing.synced_at);
+        let row = Self::internal_row(
+            request,
+            existing.advisory_id,
+            published_at,
+            chrono::Utc::now().naive_utc(),
+        )?;
+
+        let advisory = state
+            .database
+            .update_advisory(id, INTERNAL_SOURCE, &row)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(not_found)?;
+
+        info!(
+            "User {} updated internal advisory {}",
+            actor.username, advisory.advisory_id
+        );
+
+        Ok(advisory)
+    }
+
+    /// Lets subscribers know about a new advisory
+    fn announce(row: &NewAdvisory, state: &AppState) {
+        state.events.emit(RegistryEvent::AdvisoryPublished {
+            package: row.package_name.clone(),
+            advisory_id: row.advisory_id.clone(),
+            title: row.title.clone(),
+            severity: row.severity.clone(),
+        });
+    }
+
+    pub fn delete_internal(id: i32, state: &AppState) -> Result<(), ApiError> {
+        let deleted = state
+            .database
+            .delete_advisory_by_id(id, INTERNAL_SOURCE)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if deleted == 0 {
+            return Err(ApiError::NotFound(format!(
+                "Internal advisory {id} not found"
+            )));
+        }
+
+        info!("Deleted internal advisory {id}");
+        Ok(())
+    }
+
+    /// Parses an audit request body, which npm sends gzip compressed
+    pub fn decode_body(body: &[u8]) -> Result<Value, ApiError> {
+        let mut decoded = Vec::new();
+        let body = if body.starts_with(&[0x1f, 0x8b]) {
+            GzDecoder::new(body)
+                .read_to_end(&mut decoded)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid gzip body: {e}")))?;
+            &decoded[..]
+        } else {
+            body
+        };
+
+        serde_json::from_slice(body)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid audit request: {e}")))
+    }
+
+    /// Response of `/-/npm/v1/security/advisories/bulk`: advisories affecting any of the
+    /// requested versions, keyed by package name
+    pub fn bulk_report(request: &Value, state: &AppState) -> Result<Value, ApiError> {
+        let Some(requested) = request.as_object() else {
+            return Err(ApiError::BadRequest(
+                "Expected an object of package names to versions".to_string(),
+            ));
+        };
+
+        let names: Vec<&str> = requested.keys().map(String::as_str).collect();
+        let advisories = Self::load(&names, state)?;
+
+        let mut report = serde_json::Map::new();
+        for advisory in advisories {
+            let versions = requested
+                .get(&advisory.package_name)
+                .and_then(Value::as_array)
+                .map(|versions| {
+                    versions
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            if !versions
+                .iter()
+                .any(|version| Self::range_matches(&advisory.vulnerable_versions, version))
+            {
+                continue;
+            }
+
+            let entry = report
+                .entry(advisory.package_name.clone())
+                .or_insert_with(|| json!([]));
+            if let Some(entries) = entry.as_array_mut() {
+                entries.push(json!({
+                    "id": advisory.id,
+                    "url": advisory.url,
+                    "title": advisory.title,
+                    "severity": advisory.severity,
+                    "vulnerable_versions": advisory.vulnerable_versions,
+                    "cwe": Self::json_list(&advisory.cwe),
+                    "cvss": { "score": 0, "vectorString": advisory.cvss_vector },
+                }));
+            }
+        }
+
+        Ok(Value::Object(report))
+    }
+
+    /// Response of `/-/npm/v1/security/audits(/quick)`, the npm 6 and pnpm audit format
+    /// built from the dependency tree in the request
+    pub fn audit_report(request: &Value, state: &AppState) -> Result<Value, ApiError> {
+        let mut found: BTreeMap<String, Vec<AuditedDependency>> = BTreeMap::new();
+        if let Some(dependencies) = request.get("dependencies") {
+            Self::collect_dependencies(dependencies, "", false, false, &mut found);
+        }
+
+        let all = found.values().flatten();
+        let dev_count = all.clone().filter(|dep| dep.dev).count();
+        let optional_count = all.clone().filter(|dep| dep.optional).count();
+        let total_count = all.count();
+
+        let names: Vec<&str> = found.keys().map(String::as_str).collect();
+        let advisories = Self::load(&names, state)?;
+
+        let mut vulnerabilities: BTreeMap<&str, usize> =
+            SEVERITIES.iter().map(|severity| (*severity, 0)).collect();
+        let mut report_advisories = serde_json::Map::new();
+
+        for advisory in advisories {
+            let Some(dependencies) = found.get(&advisory.package_name) else {
+                continue;
+            };
+
+            let mut findings: BTreeMap<&str, Vec<&AuditedDependency>> = BTreeMap::new();
+            for dep in dependencies
+                .iter()
+                .filter(|dep| Self::range_matches(&advisory.vulnerable_versions, &dep.version))
+            {
+                findings.entry(&dep.version).or_default().push(dep);
+            }
+            if findings.is_empty() {
+                continue;
+            }
+
+            let path_count: usize = findings.values().map(Vec::len).sum();
+            if let Some(count) = vulnerabilities.get_mut(advisory.severity.as_str()) {
+                *count += path_count;
+            }
+
+            let findings: Vec<Value> = findings
+                .into_iter()
+                .map(|(version, deps)| {
+                    json!({
+                        "version": version,
+                        "paths": deps.iter().map(|dep| &dep.path).collect::<Vec<_>>(),
+                        "dev": deps.iter().all(|dep| dep.dev),
+                        "optional": deps.iter().all(|dep| dep.optional),
+                        "bundled": false,
+                    })
+                })
+                .collect();
+
+            let recommendation = match &advisory.patched_versions {
+                Some(patched) => format!("Upgrade to version {patched}"),
+                None => "None".to_string(),
+            };
+
+            report_advisories.insert(
+                advisory.id.to_string(),
+                json!({
+                    "id": advisory.id,
+                    "title": advisory.title,
+                    "module_name": advisory.package_name,
+                    "findings": findings,
+                    "vulnerable_versions": advisory.vulnerable_versions,
+                    "patched_versions": advisory.patched_versions.as_deref().unwrap_or("<0.0.0"),
+                    "severity": advisory.severity,
+                    "overview": advisory.overview.as_deref().unwrap_or(&advisory.title),
+                    "recommendation": recommendation,
+                    "references": advisory.url,
+                    "url": advisory.url,
+                    "cwe": Self::json_list(&advisory.cwe),
+                    "cves": Self::json_list(&advisory.cves),
+                    "access": "public",
+                    "github_advisory_id": advisory.advisory_id,
+                    "created": advisory.published_at,
+                    "updated": advisory.modified_at,
+                    "deleted": null,
+                }),
+            );
+        }
+
+        Ok(json!({
+            "actions": [],
+            "advisories": report_advisories,
+            "muted": [],
+            "metadata": {
+                "vulnerabilities": vulnerabilities,
+                "dependencies": total_count - dev_count,
+                "devDependencies": dev_count,
+                "optionalDependencies": optional_count,
+                "totalDependencies": total_count,
+            },
+        }))
+    }
+
+    fn internal_row(
+        request: InternalAdvisoryRequest,
+        advisory_id: String,
+        published_at: NaiveDateTime,
+        modified_at: NaiveDateTime,
+    ) -> Result<NewAdvisory, ApiError> {
+        let package_name = request.package_name.trim().to_string();
+        let title = request.title.trim().to_string();
+        if package_name.is_empty() || title.is_empty() {
+            return Err(ApiError::BadRequest(
+                "Package name and title must not be empty".to_string(),
+            ));
+        }
+
+        let severity = request
+            .severity
+            .map(|severity| severity.to_lowercase())
+            .unwrap_or_else(|| "moderate".to_string());
+        if !SEVERITIES.contains(&severity.as_str()) {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid severity '{severity}', expected info, low, moderate, high or critical"
+            )));
+        }
+
+        let vulnerable_versions = request.vulnerable_versions.trim().to_string();
+        for range in std::iter::once(&vulnerable_versions).chain(&request.patched_versions) {
+            if let Some(e) = range
+                .split("||")
+                .find_map(|set| Self::comparator_set(set).err())
+            {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid version range '{range}': {e}"
+                )));
+            }
+        }
+
+        Ok(NewAdvisory {
+            source: INTERNAL_SOURCE.to_string(),
+            advisory_id,
+            package_name,
+            title,
+            overview: request.overview,
+            url: request.url.unwrap_or_default(),
+            severity,
+            vulnerable_versions,
+            patched_versions: request.patched_versions,
+            cwe: request.cwe.map(|cwe| json!(cwe).to_string()),
+            cves: request.cves.map(|cves| json!(cves).to_string()),
+            cvss_vector: None,
+            published_at: Some(published_at),
+            modified_at: Some(modified_at),
+            synced_at: modified_at,
+        })
+    }
+
+    async fn query_batch(
+        packages: &[String],
+        state: &AppState,
+    ) -> Result<HashMap<String, Option<NaiveDateTime>>, ApiError> {
+        let queries: Vec<Value> = packages
+            .iter()
+            .map(|name| json!({ "package": { "name": name, "ecosystem": "npm" } }))
+            .collect();
+
+        let url = format!("{}/v1/querybatch", state.config.osv_url);
+        let response = state
+            .client
+            .post(&url)
+            .json(&json!({ "queries": queries }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                error!("OSV batch query failed: {e}");
+                ApiError::UpstreamError(format!("OSV request failed: {e}"))
+            })?;
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| ApiError::ParseError(format!("Invalid OSV response: {e}")))?;
+
+        let mut found = HashMap::new();
+        for result in body
+            .get("results")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            for vuln in result
+                .get("vulns")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if let Some(id) = vuln.get("id").and_then(Value::as_str) {
+                    found.insert(id.to_string(), Self::parse_time(vuln.get("modified")));
+                }
+            }
+        }
+
+        Ok(found)
+    }
+
+    fn delete(id: &str, state: &AppState) -> Result<usize, ApiError> {
+        let deleted = state
+            .database
+            .delete_advisory(OSV_SOURCE, id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        Ok(usize::from(deleted > 0))
+    }
+
+    fn load(names: &[&str], state: &AppState) -> Result<Vec<Advisory>, ApiError> {
+        state
+            .database
+            .get_advisories_for_packages(names)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))
+    }
+
+    fn collect_dependencies(
+        dependencies: &Value,
+        parent_path: &str,
+        parent_dev: bool,
+        parent_optional: bool,
+        found: &mut BTreeMap<String, Vec<AuditedDependency>>,
+    ) {
+        let Some(dependencies) = dependencies.as_object() else {
+            return;
+        };
+
+        for (name, dep) in dependencies {
+            let Some(version) = dep.get("version").and_then(Value::as_str) else {
+                continue;
+            };
+            let path = if parent_path.is_empty() {
+                name.clone()
+            } else {
+                format!("{parent_path}>{name}")
+            };
+            let dev = parent_dev || dep.get("dev").and_then(Value::as_bool).unwrap_or(false);
+            let optional = parent_optional
+                || dep
+                    .get("optional")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+
+            if let Some(nested) = dep.get("dependencies") {
+                Self::collect_dependencies(nested, &path, dev, optional, found);
+            }
+
+            found
+                .entry(name.clone())
+                .or_default()
+                .push(AuditedDependency {
+                    version: version.to_string(),
+                    path,
+                    dev,
+                    optional,
+                });
+        }
+    }
+
+    /// One row per npm package affected by an OSV vulnerability
+    fn advisories_from_osv(vuln: &Value, synced_at: NaiveDateTime) -> Vec<NewAdvisory> {
+        let Some(id) = vuln.get("id").and_then(Value::as_str) else {
+            return Vec::new();
+        };
+        let str_field = |key: &str| vuln.get(key).and_then(Value::as_str).map(str::to_string);
+
+        let url = ["ADVISORY", "WEB"]
+            .iter()
+            .find_map(|kind| {
+                vuln.get("references")?
+                    .as_array()?
+                    .iter()
+                    .find(|reference| reference.get("type").and_then(Value::as_str) == Some(kind))?
+                    .get("url")?
+                    .as_str()
+            })
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("https://osv.dev/vulnerability/{id}"));
+
+        let severity = match vuln
+            .pointer("/database_specific/severity")
+            .and_then(Value::as_str)
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("low") => "low",
+            Some("high") => "high",
+            Some("critical") => "critical",
+            _ => "moderate",
+        };
+
+        let cwe = vuln
+            .pointer("/database_specific/cwe_ids")
+            .filter(|ids| ids.is_array())
+            .map(Value::to_string);
+        let cves: Vec<&str> = vuln
+            .get("aliases")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter(|alias| alias.starts_with("CVE-"))
+            .collect();
+        let cvss_vector = vuln
+            .get("severity")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .find(|s| {
+                s.get("type")
+                    .and_then(Value::as_str)
+                    .is_some_and(|t| t.starts_with("CVSS"))
+            })
+            .and_then(|s| s.get("score"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        vuln.get("affected")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|affected| {
+                affected
+                    .pointer("/package/ecosystem")
+                    .and_then(Value::as_str)
+                    == Some("npm")
+            })
+            .filter_map(|affected| {
+                let package_name = affected.pointer("/package/name")?.as_str()?;
+                let (vulnerable_versions, patched_versions) = Self::osv_ranges(affected)?;
+
+                Some(NewAdvisory {
+                    source: OSV_SOURCE.to_string(),
+                    advisory_id: id.to_string(),
+                    package_name: package_name.to_string(),
+                    title: str_field("summary").unwrap_or_else(|| id.to_string()),
+                    overview: str_field("details"),
+                    url: url.clone(),
+                    severity: severity.to_string(),
+                    vulnerable_versions,
+                    patched_versions,
+                    cwe: cwe.clone(),
+                    cves: (!cves.is_empty()).then(|| json!(cves).to_string()),
+                    cvss_vector: cvss_vector.clone(),
+                    published_at: Self::parse_time(vuln.get("published")),
+                    modified_at: Self::parse_time(vuln.get("modified")),
+                    synced_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Converts OSV range events to npm style vulnerable and patched ranges
+    fn osv_ranges(affected: &Value) -> Option<(String, Option<String>)> {
+        let mut vulnerable = Vec::new();
+        let mut patched = Vec::new();
+
+        for range in affected
+            .get("ranges")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|range| range.get("type").and_then(Value::as_str) != Some("GIT"))
+        {
+            let mut introduced: Option<&str> = None;
+            let mut open = false;
+            for event in range
+                .get("events")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let lower = |introduced: Option<&str>| match introduced {
+                    Some(version) if version != "0" => format!(">={version} "),
+                    _ => String::new(),
+                };
+
+                if let Some(version) = event.get("introduced").and_then(Value::as_str) {
+                    introduced = Some(version);
+                    open = true;
+                } else if let Some(version) = event.get("fixed").and_then(Value::as_str) {
+                    vulnerable.push(format!("{}<{version}", lower(introduced)));
+                    patched.push(format!(">={version}"));
+                    open = false;
+                } else if let Some(version) = event.get("last_affected").and_then(Value::as_str) {
+                    vulnerable.push(format!("{}<={version}", lower(introduced)));
+                    open = false;
+                } else if let Some(version) = event.get("limit").and_then(Value::as_str) {
+                    vulnerable.push(format!("{}<{version}", lower(introduced)));
+                    open = false;
+                }
+            }
+
+            if open {
+                vulnerable.push(match introduced {
+                    Some(version) if version != "0" => format!(">={version}"),
+                    _ => ">=0.0.0".to_string(),
+                });
+            }
+        }
+
+        // Some records only list the affected versions
+        if vulnerable.is_empty() {
+            vulnerable.extend(
+                affected
+                    .get("versions")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .map(|version| format!("={version}")),
+            );
+        }
+
+        if vulnerable.is_empty() {
+            return None;
+        }
+
+        Some((
+            vulnerable.join(" || "),
+            (!patched.is_empty()).then(|| patched.join(" || ")),
+        ))
+    }
+
+    /// Whether a version satisfies an npm style range (`||` separated comparator sets)
+    fn range_matches(range: &str, version: &str) -> bool {
+        let Ok(version) = Version::parse(version.trim_start_matches('v')) else {
+            return false;
+        };
+
+        range
+            .split("||")
+            .any(|set| match Self::comparator_set(set) {
+                Ok(Some(req)) => req.matches(&version),
+                Ok(None) => true,
+                Err(e) => {
+                    debug!("Ignoring unsupported range '{set}': {e}");
+                    false
+                }
+            })
+    }
+
+    /// Parses one set of space separated comparators, `None` when it matches any version
+    fn comparator_set(set: &str) -> Result<Option<VersionReq>, semver::Error> {
+        let comparators: Vec<&str> = set.split_whitespace().collect();
+        if comparators.is_empty() || comparators == ["*"] {
+            return Ok(None);
+        }
+
+        VersionReq::parse(&comparators.join(", ")).map(Some)
+    }
+
+    fn parse_time(value: Option<&Value>) -> Option<NaiveDateTime> {
+        value
+            .and_then(Value::as_str)
+            .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.naive_utc())
+    }
+
+    fn json_list(value: &Option<String>) -> Value {
+        value
+            .as_deref()
+            .and_then(|list| serde_json::from_str(list).ok())
+            .unwrap_or_else(|| json!([]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osv_ranges() {
+        let affected = json!({
+            "package": { "ecosystem": "npm", "name": "lodash" },
+            "ranges": [{
+                "type": "SEMVER",
+                "events": [
+                    { "introduced": "0" }, { "fixed": "4.17.12" },
+                    { "introduced": "5.0.0" }, { "last_affected": "5.1.0" },
+                    { "introduced": "6.0.0" }
+                ]
+            }]
+        });
+
+        let (vulnerable, patched) = AdvisoryService::osv_ranges(&affected).unwrap();
+        assert_eq!(vulnerable, "<4.17.12 || >=5.0.0 <=5.1.0 || >=6.0.0");
+        assert_eq!(patched.as_deref(), Some(">=4.17.12"));
+    }
+
+    #[test]
+    fn test_range_matches() {
+        let range = "<4.17.12 || >=5.0.0 <=5.1.0";
+        assert!(AdvisoryService::range_matches(range, "4.17.11"));
+        assert!(AdvisoryService::range_matches(range, "5.1.0"));
+        assert!(!AdvisoryService::range_matches(range, "4.17.12"));
+        assert!(!AdvisoryService::range_matches(range, "not-a-version"));
+        assert!(AdvisoryService::range_matches("=1.0.0", "1.0.0"));
+    }
+
+    #[test]
+    fn test_advisories_from_osv() {
+        let vuln = json!({
+            "id": "GHSA-xxxx-yyyy-zzzz",
+            "summary": "Prototype pollution",
+            "aliases": ["CVE-2020-0001"],
+            "modified": "2024-01-02T03:04:05Z",
+            "database_specific": { "severity": "HIGH", "cwe_ids": ["CWE-1321"] },
+            "references": [{ "type": "ADVISORY", "url": "https://github.com/advisories/GHSA-xxxx-yyyy-zzzz" }],
+            "affected": [
+                {
+                    "package": { "ecosystem": "npm", "name": "lodash" },
+                    "ranges": [{ "type": "SEMVER", "events": [{ "introduced": "0" }, { "fixed": "4.17.12" }] }]
+                },
+                {
+                    "package": { "ecosystem": "PyPI", "name": "lodash" },
+                    "versions": ["1.0"]
+                }
+            ]
+        });
+
+        let rows = AdvisoryService::advisories_from_osv(&vuln, chrono::Utc::now().naive_utc());
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].package_name, "lodash");
+        assert_eq!(rows[0].severity, "high");
+        assert_eq!(rows[0].vulnerable_versions, "<4.17.12");
+        assert_eq!(rows[0].cves.as_deref(), Some("[\"CVE-2020-0001\"]"));
+        assert!(rows[0].url.starts_with("https://github.com/advisories/"));
+    }
+
+    #[test]
+    fn test_internal_row_validation() {
+        let request = |severity: &str, range: &str| InternalAdvisoryRequest {
+            package_name: "foo".to_string(),
+            title: "Don't use foo 1.x internally".to_string(),
+            overview: None,
+            url: None,
+            severity: Some(severity.to_string()),
+            vulnerable_versions: range.to_string(),
+            patched_versions: Some(">=2.0.0".to_string()),
+            cwe: None,
+            cves: None,
+        };
+        let row = |severity, range| {
+            let now = chrono::Utc::now().naive_utc();
+            AdvisoryService::internal_row(request(severity, range), "CLEF-1".to_string(), now, now)
+        };
+
+        let valid = row("High", "<2.0.0 || =3.0.0").unwrap();
+        assert_eq!(valid.source, INTERNAL_SOURCE);
+        assert_eq!(valid.severity, "high");
+        assert!(row("urgent", "<2.0.0").is_err());
+        assert!(row("low", "not a range").is_err());
+    }
+}
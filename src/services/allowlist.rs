@@ -0,0 +1,114 @@
+use crate::error::ApiError;
+use crate::models::{
+    AllowedPackage, AllowedPackageRequest, AllowlistResponse, AuthenticatedUser, NewAllowedPackage,
+};
+use crate::services::NameBlocklistService;
+use crate::state::AppState;
+use log::info;
+use regex::Regex;
+
+pub struct AllowlistService;
+
+impl AllowlistService {
+    /// In allowlist only mode, rejects proxying a package from upstream unless an allowlist
+    /// entry matches it
+    pub fn check_upstream(package: &str, state: &AppState) -> Result<(), ApiError> {
+        if !state.config.upstream_allowlist_only {
+            return Ok(());
+        }
+
+        let entries = state
+            .database
+            .list_allowed_packages()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if entries
+            .iter()
+            .any(|entry| NameBlocklistService::matches(&entry.pattern, entry.is_regex, package))
+        {
+            return Ok(());
+        }
+
+        info!("Not proxying {package} from upstream, it isn't on the allowlist");
+        Err(ApiError::Forbidden(format!(
+            "Policy violation: '{package}' is not on the allowlist of this registry, ask an admin to allow it"
+        )))
+    }
+
+    pub fn list(state: &AppState) -> Result<AllowlistResponse, ApiError> {
+        let entries = state
+            .database
+            .list_allowed_packages()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(AllowlistResponse {
+            enabled: state.config.upstream_allowlist_only,
+            entries,
+        })
+    }
+
+    /// Adds an allowlist entry. Exact names are stored lowercase, regular expressions are
+    /// validated before they are saved.
+    pub fn add_entry(
+        request: AllowedPackageRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<AllowedPackage, ApiError> {
+        let is_regex = request.is_regex.unwrap_or(false);
+        let pattern = request.pattern.trim();
+        if pattern.is_empty() {
+            return Err(ApiError::BadRequest(
+                "Pattern must not be empty".to_string(),
+            ));
+        }
+
+        let pattern = if is_regex {
+            Regex::new(pattern)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid regular expression: {e}")))?;
+            pattern.to_string()
+        } else {
+            pattern.to_lowercase()
+        };
+
+        let entry = state
+            .database
+            .create_allowed_package(&NewAllowedPackage {
+                pattern: pattern.clone(),
+                is_regex,
+                reason: request.reason,
+                created_by: Some(actor.user_id),
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::UniqueViolation,
+                    _,
+                ) => ApiError::Conflict(format!("'{pattern}' is already allowed")),
+                _ => ApiError::InternalServerError(format!("Database error: {e}")),
+            })?;
+
+        info!(
+            "User {} allowed {} '{}' from upstream",
+            actor.username,
+            if entry.is_regex { "pattern" } else { "name" },
+            entry.pattern
+        );
+
+        Ok(entry)
+    }
+
+    pub fn delete_entry(id: i32, state: &AppState) -> Result<(), ApiError> {
+        let deleted = state
+            .database
+            .delete_allowed_package(id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if deleted == 0 {
+            return Err(ApiError::NotFound(format!(
+                "Allowlist entry {id} not found"
+            )));
+        }
+
+        info!("Deleted allowlist entry {id}");
+        Ok(())
+    }
+}
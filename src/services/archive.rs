@@ -0,0 +1,563 @@
+use crate::error::ApiError;
+use crate::models::{
+    ArchivedFile, ArchivedVersion, PACKAGE_ARCHIVE_FORMAT, PACKAGE_ARCHIVE_VERSION, PackageArchive,
+    PackageImportResponse, validate_package_name,
+};
+use crate::services::{RegistryService, SigningService};
+use crate::state::AppState;
+use base64::prelude::*;
+use log::{debug, info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub struct ArchiveService;
+
+impl ArchiveService {
+    /// Bundles a locally stored package (metadata, versions, dist-tags and tarballs) into an archive
+    pub async fn export_package(
+        package: &str,
+        state: &AppState,
+    ) -> Result<PackageArchive, ApiError> {
+        let package_with_versions = state
+            .database
+            .get_package_with_versions(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Package '{package}' not found")))?;
+
+        let pkg = &package_with_versions.package;
+        let mut versions = Vec::new();
+
+        for version_with_files in &package_with_versions.versions {
+            let pkg_version = &version_with_files.version;
+
+            let manifest = match RegistryService::load_package_json_from_filesystem(
+                &pkg.name,
+                &pkg_version.version,
+                state,
+            )? {
+                Some(manifest) => manifest,
+                None => {
+                    RegistryService::construct_version_metadata_from_db_fields(
+                        pkg,
+                        pkg_version,
+                        state,
+                    )
+                    .await?
+                }
+            };
+
+            let mut files = Vec::new();
+            for file in &version_with_files.files {
+                match std::fs::read(&file.file_path) {
+                    Ok(data) => files.push(ArchivedFile {
+                        filename: file.filename.clone(),
+                        content_type: file.content_type.clone(),
+                        size_bytes: data.len() as i64,
+                        data: BASE64_STANDARD.encode(&data),
+                    }),
+                    Err(e) => {
+                        warn!(
+                            "Skipping file {} of {}@{} in export: {e}",
+                            file.filename, pkg.name, pkg_version.version
+                        );
+                    }
+                }
+            }
+
+            versions.push(ArchivedVersion {
+                version: pkg_version.version.clone(),
+                created_at: pkg_version.created_at,
+                manifest,
+                files,
+            });
+        }
+
+        let dist_tags = state
+            .database
+            .get_package_tags_map(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!(
+            "Exported package {} with {} versions",
+            pkg.name,
+            versions.len()
+        );
+
+        Ok(PackageArchive {
+            format: PACKAGE_ARCHIVE_FORMAT.to_string(),
+            format_version: PACKAGE_ARCHIVE_VERSION,
+            exported_at: chrono::Utc::now().naive_utc(),
+            name: pkg.name.clone(),
+            description: pkg.description.clone(),
+            homepage: pkg.homepage.clone(),
+            repository_url: pkg.repository_url.clone(),
+            license: pkg.license.clone(),
+            keywords: pkg.keywords.clone(),
+            dist_tags,
+            versions,
+        })
+    }
+
+    /// Name of a version's tarball, from its `dist.tarball` URL
+    pub fn tarball_filename(package: &str, version: &str, manifest: &Value) -> String {
+        manifest["dist"]["tarball"]
+            .as_str()
+            .and_then(|url| url.rsplit('/').next())
+            .filter(|filename| !filename.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| Self::archived_tarball_filename(package, version))
+    }
+
+    /// Name a version's tarball has in an archive, the name it's stored under here
+    fn archived_tarball_filename(package: &str, version: &str) -> String {
+        let base_name = package.split('/').next_back().unwrap_or(package);
+        format!("{base_name}-{version}.tgz")
+    }
+
+    /// Builds an archive from a package document as npm registries serve it, with the
+    /// tarballs found for its versions keyed by version. Versions without a tarball are left
+    /// out, and so are dist-tags pointing at them.
+    pub fn archive_from_document(
+        document: &Value,
+        mut tarballs: HashMap<String, Vec<u8>>,
+    ) -> Result<PackageArchive, String> {
+        let name = document["name"]
+            .as_str()
+            .ok_or("The package document has no name")?
+            .to_string();
+
+        let mut versions = Vec::new();
+        for (version, manifest) in document["versions"].as_object().into_iter().flatten() {
+            let Some(data) = tarballs.remove(version) else {
+                continue;
+            };
+            let created_at = document["time"][version]
+                .as_str()
+                .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+                .map(|time| time.naive_utc())
+                .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+            versions.push(ArchivedVersion {
+                version: version.clone(),
+                created_at,
+                manifest: manifest.clone(),
+                files: vec![ArchivedFile {
+                    filename: Self::archived_tarball_filename(&name, version),
+                    content_type: Some("application/octet-stream".to_string()),
+                    size_bytes: data.len() as i64,
+                    data: BASE64_STANDARD.encode(&data),
+                }],
+            });
+        }
+        versions.sort_by_key(|v| v.created_at);
+
+        let dist_tags: HashMap<String, String> = document["dist-tags"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(tag, version)| Some((tag.clone(), version.as_str()?.to_string())))
+            .filter(|(_, version)| versions.iter().any(|v| v.version == *version))
+            .collect();
+
+        let latest = dist_tags
+            .get("latest")
+            .and_then(|latest| versions.iter().find(|v| v.version == *latest))
+            .or_else(|| versions.last())
+            .map(|v| v.manifest.clone())
+            .unwrap_or(Value::Null);
+        let text = |value: &Value| value.as_str().map(str::to_string);
+        let keywords = latest["keywords"].as_array().map(|keywords| {
+            keywords
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+
+        Ok(PackageArchive {
+            format: PACKAGE_ARCHIVE_FORMAT.to_string(),
+            format_version: PACKAGE_ARCHIVE_VERSION,
+            exported_at: chrono::Utc::now().naive_utc(),
+            description: text(&document["description"]).or_else(|| text(&latest["description"])),
+            homepage: text(&latest["homepage"]),
+            repository_url: text(&latest["repository"]["url"])
+                .or_else(|| text(&latest["repository"])),
+            license: text(&latest["license"]),
+            keywords,
+            dist_tags,
+            versions,
+            name,
+        })
+    }
+
+    /// Restores a package archive into this registry on behalf of the given admin user.
+    /// Versions that already exist locally are left untouched.
+    pub async fn import_package(
+        archive: PackageArchive,
+        user_id: i32,
+        state: &AppState,
+    ) -> Result<PackageImportResponse, ApiError> {
+        if archive.format != PACKAGE_ARCHIVE_FORMAT {
+            return Err(ApiError::BadRequest(format!(
+                "Unsupported archive format '{}'",
+                archive.format
+            )));
+        }
+
+        if archive.format_version > PACKAGE_ARCHIVE_VERSION {
+            return Err(ApiError::BadRequest(format!(
+                "Unsupported archive format version {}",
+                archive.format_version
+            )));
+        }
+
+        let package = archive.name.as_str();
+        validate_package_name(package)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid package name '{package}': {e}")))?;
+
+        // Versions and file names end up on disk and in the database, so nothing is written
+        // for an archive carrying a version that isn't valid semver, or a file that isn't
+        // the version's tarball
+        for archived in &archive.versions {
+            semver::Version::parse(&archived.version).map_err(|e| {
+                ApiError::BadRequest(format!(
+                    "Invalid version '{}' in archive: {e}",
+                    archived.version
+                ))
+            })?;
+
+            let expected = Self::archived_tarball_filename(package, &archived.version);
+            if let Some(file) = archived.files.iter().find(|f| f.filename != expected) {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid file name '{}' in archive, expected '{expected}'",
+                    file.filename
+                )));
+            }
+            if archived.files.len() > 1 {
+                return Err(ApiError::BadRequest(format!(
+                    "Version '{}' has more than one tarball in archive",
+                    archived.version
+                )));
+            }
+        }
+
+        let is_new_package = !state
+            .database
+            .package_exists(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+        // Scoped packages are linked to their organization, same as on publish
+        let organization_id = state
+            .database
+            .get_or_create_organization_for_package(package, Some(user_id))
+            .map_err(|e| ApiError::InternalServerError(format!("Organization error: {e}")))?;
+
+        let pkg = state
+            .database
+            .create_or_get_package_with_organization(
+                package,
+                archive.description.clone(),
+                Some(user_id),
+                organization_id,
+            )
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        state
+            .database
+            .update_package_metadata(
+                pkg.id,
+                archive.homepage.clone(),
+                archive.repository_url.clone(),
+                archive.license.clone(),
+                archive.keywords.clone(),
+            )
+            .map_err(|e| {
+                ApiError::InternalServerError(format!("Failed to update package metadata: {e}"))
+            })?;
+
+        let existing_versions: Vec<String> = state
+            .database
+            .get_package_versions(pkg.id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .into_iter()
+            .map(|v| v.version)
+            .collect();
+
+        let package_dir = Path::new(&state.config.cache_dir)
+            .join("packages")
+            .join(package);
+        std::fs::create_dir_all(&package_dir).map_err(|e| {
+            ApiError::InternalServerError(format!("Failed to create package directory: {e}"))
+        })?;
+
+        let base_name = package.split('/').next_back().unwrap_or(package);
+        let mut imported_versions = Vec::new();
+        let mut skipped_versions = Vec::new();
+
+        for archived in archive.versions {
+            if existing_versions.contains(&archived.version) {
+                debug!("Skipping existing version {package}@{}", archived.version);
+                skipped_versions.push(archived.version);
+                continue;
+            }
+
+            // Decode the tarball up front so a bad one doesn't leave a half-imported version
+            let tarball = archived
+                .files
+                .first()
+                .map(|file| {
+                    BASE64_STANDARD
+                        .decode(&file.data)
+                        .map(|data| (file, data))
+                        .map_err(|e| {
+                            ApiError::BadRequest(format!(
+                                "Invalid base64 data for {}: {e}",
+                                file.filename
+                            ))
+                        })
+                })
+                .transpose()?;
+
+            let package_json_path =
+                package_dir.join(format!("{base_name}-{}.json", archived.version));
+            let package_json = serde_json::to_string(&archived.manifest).map_err(|e| {
+                ApiError::InternalServerError(format!("Failed to serialize package.json: {e}"))
+            })?;
+            std::fs::write(&package_json_path, package_json).map_err(|e| {
+                ApiError::InternalServerError(format!("Failed to write package.json: {e}"))
+            })?;
+
+            // Preserve the original publish time
+            let mut manifest = archived.manifest.clone();
+            if let Some(obj) = manifest.as_object_mut() {
+                obj.insert(
+                    "_published_time".to_string(),
+                    serde_json::json!(archived.created_at.and_utc().to_rfc3339()),
+                );
+            }
+
+            let pkg_version = state
+                .database
+                .create_or_get_package_version_with_metadata(pkg.id, &archived.version, &manifest)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+            if let Some((file, data)) = tarball {
+                let file_path = package_dir.join(&file.filename);
+                std::fs::write(&file_path, &data).map_err(|e| {
+                    ApiError::InternalServerError(format!("Failed to write tarball: {e}"))
+                })?;
+
+                let upstream_url = format!(
+                    "{}/{}/-/{}",
+                    state.upstream_registry(),
+                    package,
+                    file.filename
+                );
+
+                state
+                    .database
+                    .create_or_update_package_file(
+                        pkg_version.id,
+                        &file.filename,
+                        data.len() as i64,
+                        &upstream_url,
+                        &file_path.to_string_lossy(),
+                        None,
+                        file.content_type
+                            .clone()
+                            .or_else(|| Some("application/octet-stream".to_string())),
+                    )
+                    .map_err(|e| {
+                        ApiError::InternalServerError(format!("Failed to create package file: {e}"))
+                    })?;
+
+                state
+                    .database
+                    .set_version_checksums(
+                        pkg_version.id,
+                        &SigningService::shasum_for(&data),
+                        &SigningService::integrity_for(&data),
+                    )
+                    .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+            }
+
+            imported_versions.push(archived.version);
+        }
+
+        if is_new_package {
+            state
+                .database
+                .create_package_owner(package, user_id, "admin")
+                .map_err(|e| {
+                    ApiError::InternalServerError(format!("Failed to create ownership: {e}"))
+                })?;
+        }
+
+        for (tag_name, tag_version) in &archive.dist_tags {
+            if let Err(e) =
+                state
+                    .database
+                    .create_or_update_package_tag(package, tag_name, tag_version)
+            {
+                warn!("Failed to import tag {tag_name} for package {package}: {e}");
+            }
+        }
+
+        if let Err(e) = state.cache.invalidate_metadata(package).await {
+            warn!("Failed to invalidate metadata cache for package {package}: {e}");
+        }
+
+        let dist_tags = state
+            .database
+            .get_package_tags_map(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!(
+            "Imported package {package}: {} versions imported, {} skipped",
+            imported_versions.len(),
+            skipped_versions.len()
+        );
+
+        Ok(PackageImportResponse {
+            ok: true,
+            package: package.to_string(),
+            imported_versions,
+            skipped_versions,
+            dist_tags,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::models::RegisterRequest;
+    use crate::services::AuthService;
+
+    fn state(dir: &tempfile::TempDir) -> AppState {
+        crate::create_state(AppConfig {
+            cache_dir: dir.path().to_str().unwrap().to_string(),
+            database_url: dir.path().join("clef.db").to_str().unwrap().to_string(),
+            ..AppConfig::default()
+        })
+    }
+
+    fn admin(state: &AppState) -> i32 {
+        AuthService::register_user(
+            &state.database,
+            RegisterRequest {
+                name: "admin".to_string(),
+                email: "admin@example.com".to_string(),
+                password: "password123".to_string(),
+                invite_token: None,
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    fn archive(name: &str, versions: &[(&str, &[u8])]) -> PackageArchive {
+        let base_name = name.split('/').next_back().unwrap();
+        PackageArchive {
+            format: PACKAGE_ARCHIVE_FORMAT.to_string(),
+            format_version: PACKAGE_ARCHIVE_VERSION,
+            exported_at: chrono::Utc::now().naive_utc(),
+            name: name.to_string(),
+            description: None,
+            homepage: None,
+            repository_url: None,
+            license: None,
+            keywords: None,
+            dist_tags: HashMap::new(),
+            versions: versions
+                .iter()
+                .map(|(version, data)| ArchivedVersion {
+                    version: version.to_string(),
+                    created_at: chrono::Utc::now().naive_utc(),
+                    manifest: serde_json::json!({ "name": name, "version": version }),
+                    files: vec![ArchivedFile {
+                        filename: format!("{base_name}-{version}.tgz"),
+                        content_type: None,
+                        size_bytes: data.len() as i64,
+                        data: BASE64_STANDARD.encode(data),
+                    }],
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_package_checksums_each_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let user_id = admin(&state);
+
+        let response = ArchiveService::import_package(
+            archive("@acme/lib", &[("1.0.0", b"first"), ("1.1.0", b"second")]),
+            user_id,
+            &state,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.imported_versions, vec!["1.0.0", "1.1.0"]);
+
+        let package = state
+            .database
+            .get_package_with_versions("@acme/lib")
+            .unwrap()
+            .unwrap();
+        for (version, data) in [("1.0.0", &b"first"[..]), ("1.1.0", &b"second"[..])] {
+            let stored = package
+                .versions
+                .iter()
+                .find(|v| v.version.version == version)
+                .unwrap();
+            assert_eq!(
+                stored.version.integrity.as_deref(),
+                Some(SigningService::integrity_for(data).as_str())
+            );
+            let path = dir
+                .path()
+                .join(format!("packages/@acme/lib/lib-{version}.tgz"));
+            assert_eq!(std::fs::read(path).unwrap(), data);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_package_rejects_invalid_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let user_id = admin(&state);
+
+        // An unscoped name with a slash would nest directories under the cache
+        let result = ArchiveService::import_package(
+            archive("evil/lib", &[("1.0.0", b"x")]),
+            user_id,
+            &state,
+        )
+        .await;
+        assert!(matches!(result, Err(ApiError::BadRequest(_))));
+        assert!(!dir.path().join("packages/evil").exists());
+
+        let mut renamed = archive("lib", &[("1.0.0", b"x")]);
+        renamed.versions[0].files[0].filename = "other-1.0.0.tgz".to_string();
+        let result = ArchiveService::import_package(renamed, user_id, &state).await;
+        assert!(matches!(result, Err(ApiError::BadRequest(_))));
+
+        let mut duplicated = archive("lib", &[("1.0.0", b"x")]);
+        let extra = ArchivedFile {
+            filename: "lib-1.0.0.tgz".to_string(),
+            content_type: None,
+            size_bytes: 1,
+            data: BASE64_STANDARD.encode(b"y"),
+        };
+        duplicated.versions[0].files.push(extra);
+        let result = ArchiveService::import_package(duplicated, user_id, &state).await;
+        assert!(matches!(result, Err(ApiError::BadRequest(_))));
+
+        for name in ["evil/lib", "lib"] {
+            assert!(!state.database.package_exists(name).unwrap());
+        }
+    }
+}
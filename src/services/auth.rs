@@ -1,9 +1,24 @@
+use crate::config::{AppConfig, TokenKey};
+use crate::database::DbConnection;
 use crate::error::ApiError;
-use crate::models::{LoginRequest, NewUser, NewUserToken, RegisterRequest, User, UserToken};
+use crate::models::{
+    ClientInfo, Invitation, LoginRequest, NewUser, NewUserToken, RegisterRequest, TokenSession,
+    UpdateUser, User, UserRole, UserToken,
+};
 use crate::schema::{user_tokens, users};
-use crate::services::DatabaseService;
+use crate::services::plugins::{PluginHost, PluginIdentity};
+use crate::services::{DatabaseService, RateLimiter};
 use diesel::prelude::*;
-use log::debug;
+use hmac::{Hmac, Mac};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+
+/// Token usage is recorded at most this often to keep writes off the hot path
+const TOKEN_USAGE_RESOLUTION_SECS: i64 = 60;
+
+/// Prefixes of stored token digests, `sha256:<hex>` and `hmac-sha256:<key id>:<hex>`
+const SHA256_DIGEST_PREFIX: &str = "sha256:";
+const HMAC_DIGEST_PREFIX: &str = "hmac-sha256:";
 
 pub struct AuthService;
 
@@ -56,55 +71,304 @@ impl AuthService {
         Ok(user)
     }
 
-    pub fn authenticate_user(
+    /// Creates an account migrated from another registry, keeping its bcrypt password hash.
+    /// Without a hash the account gets a random password that has to be reset. `Ok(None)`
+    /// when the username is taken.
+    pub fn import_user(
         db: &DatabaseService,
-        request: LoginRequest,
-    ) -> Result<(User, String), ApiError> {
+        username: &str,
+        email: &str,
+        password_hash: Option<String>,
+    ) -> Result<Option<User>, ApiError> {
         let mut conn = db.get_connection().map_err(|e| {
             ApiError::InternalServerError(format!("Database connection error: {e}"))
         })?;
 
-        // Find user by username
-        let user = users::table
-            .filter(users::username.eq(&request.name))
-            .filter(users::is_active.eq(true))
+        let existing_user = users::table
+            .filter(users::username.eq(username))
             .first::<User>(&mut conn)
             .optional()
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+        if existing_user.is_some() {
+            return Ok(None);
+        }
+
+        let mut new_user = NewUser::new(
+            username.to_string(),
+            email.to_string(),
+            uuid::Uuid::new_v4().to_string(),
+        )
+        .map_err(|e| ApiError::InternalServerError(format!("Password hashing error: {e}")))?;
+        if let Some(password_hash) = password_hash {
+            new_user.password_hash = password_hash;
+        }
+
+        let user = diesel::insert_into(users::table)
+            .values(&new_user)
+            .get_result::<User>(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to create user: {e}")))?;
+
+        debug!("User imported: {}", user.username);
+        Ok(Some(user))
+    }
+
+    /// Registers a new account through a public endpoint. When self-registration is disabled
+    /// a valid invitation is required; a supplied invitation is redeemed either way.
+    pub fn register_new_user(
+        db: &DatabaseService,
+        config: &AppConfig,
+        request: RegisterRequest,
+    ) -> Result<User, ApiError> {
+        let invitation = match &request.invite_token {
+            Some(token) => Some(Self::validate_invitation(db, token, &request.email)?),
+            None if !config.registration_enabled => {
+                return Err(ApiError::Forbidden(
+                    "Registration is disabled, an invitation is required".to_string(),
+                ));
+            }
+            None => None,
+        };
+
+        let user = Self::register_user(db, request)?;
+
+        if let Some(invitation) = invitation {
+            let redeemed = db.redeem_invitation(invitation.id, user.id).map_err(|e| {
+                ApiError::InternalServerError(format!("Failed to redeem invitation: {e}"))
+            })?;
+
+            // Someone else redeemed it in the meantime, undo the registration
+            if !redeemed {
+                Self::delete_user(db, &user.username)?;
+                return Err(ApiError::Conflict(
+                    "Invitation has already been used".to_string(),
+                ));
+            }
+
+            debug!("Invitation {} redeemed by {}", invitation.id, user.username);
+        }
+
+        Ok(user)
+    }
+
+    fn validate_invitation(
+        db: &DatabaseService,
+        token: &str,
+        email: &str,
+    ) -> Result<Invitation, ApiError> {
+        let invitation = db
+            .get_invitation_by_token(token)
             .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?
-            .ok_or_else(|| ApiError::Unauthorized("Invalid username or password".to_string()))?;
+            .ok_or_else(|| ApiError::Forbidden("Invalid invitation".to_string()))?;
 
-        // Verify password
-        let password_valid = user.verify_password(&request.password).map_err(|e| {
-            ApiError::InternalServerError(format!("Password verification error: {e}"))
+        if !invitation.is_usable() {
+            return Err(ApiError::Forbidden(
+                "Invitation has expired or has already been used".to_string(),
+            ));
+        }
+
+        if let Some(invited_email) = &invitation.email
+            && !invited_email.eq_ignore_ascii_case(email)
+        {
+            return Err(ApiError::Forbidden(
+                "Invitation was issued for a different email address".to_string(),
+            ));
+        }
+
+        Ok(invitation)
+    }
+
+    /// Checks login credentials with the auth provider plugins, then against the user's
+    /// password, and issues a token. After `login_max_failures` failed attempts for a
+    /// username from one address, logins are refused until the lockout window ends.
+    pub async fn authenticate_user(
+        db: &DatabaseService,
+        config: &AppConfig,
+        plugins: &PluginHost,
+        limiter: &RateLimiter,
+        token_keys: &[TokenKey],
+        request: LoginRequest,
+        client: &ClientInfo,
+    ) -> Result<(User, String), ApiError> {
+        if config.login_max_failures == 0 {
+            return Self::login(db, plugins, token_keys, request, client).await;
+        }
+
+        let window_secs = config.login_lockout_minutes * 60;
+        let key = format!(
+            "login:{}:{}",
+            client.ip_address.as_deref().unwrap_or("unknown"),
+            request.name
+        );
+        if limiter.count(&key, window_secs).await >= config.login_max_failures {
+            warn!(
+                "Refusing login of {} from {}, too many failed attempts",
+                request.name,
+                client.ip_address.as_deref().unwrap_or("an unknown address")
+            );
+            return Err(ApiError::TooManyRequests(format!(
+                "Too many failed logins, try again in {} seconds",
+                RateLimiter::retry_after(window_secs)
+            )));
+        }
+
+        let result = Self::login(db, plugins, token_keys, request, client).await;
+        match &result {
+            Ok(_) => limiter.reset(&key, window_secs).await,
+            Err(ApiError::Unauthorized(_)) => {
+                limiter.hit(&key, window_secs).await;
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    async fn login(
+        db: &DatabaseService,
+        plugins: &PluginHost,
+        token_keys: &[TokenKey],
+        request: LoginRequest,
+        client: &ClientInfo,
+    ) -> Result<(User, String), ApiError> {
+        let identity = plugins
+            .authenticate(&request.name, &request.password)
+            .await?;
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
         })?;
 
-        if !password_valid {
-            return Err(ApiError::Unauthorized(
-                "Invalid username or password".to_string(),
+        let user = match identity {
+            Some(identity) => Self::plugin_user(&mut conn, &request.name, identity)?,
+            None => {
+                // Find user by username
+                let user = users::table
+                    .filter(users::username.eq(&request.name))
+                    .filter(users::is_active.eq(true))
+                    .first::<User>(&mut conn)
+                    .optional()
+                    .map_err(|e| {
+                        ApiError::InternalServerError(format!("Database query error: {e}"))
+                    })?
+                    .ok_or_else(|| {
+                        ApiError::Unauthorized("Invalid username or password".to_string())
+                    })?;
+
+                // Verify password
+                let password_valid = user.verify_password(&request.password).map_err(|e| {
+                    ApiError::InternalServerError(format!("Password verification error: {e}"))
+                })?;
+
+                if !password_valid {
+                    return Err(ApiError::Unauthorized(
+                        "Invalid username or password".to_string(),
+                    ));
+                }
+                user
+            }
+        };
+
+        if user.is_service_account() {
+            return Err(ApiError::Forbidden(
+                "Service accounts can't log in, use a token issued by the organization".to_string(),
             ));
         }
 
         // Create authentication token
-        let new_token = NewUserToken::new_auth_token(user.id);
-        let token_value = new_token.token.clone();
-
-        diesel::insert_into(user_tokens::table)
-            .values(&new_token)
-            .execute(&mut conn)
-            .map_err(|e| ApiError::InternalServerError(format!("Failed to create token: {e}")))?;
+        let new_token = NewUserToken {
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.clone(),
+            ..NewUserToken::new_auth_token(user.id)
+        };
+        let token_value = Self::insert_token(&mut conn, token_keys, new_token)?;
 
         debug!("User authenticated successfully: {}", user.username);
         Ok((user, token_value))
     }
 
-    pub fn validate_token(db: &DatabaseService, token: &str) -> Result<User, ApiError> {
+    /// The account of a user an auth provider plugin vouched for, created on their first
+    /// login with a random password, so they can't log in without the plugin. A plugin only
+    /// logs into accounts it created: a local account of the same name, like the bootstrap
+    /// admin or a name someone registered first, isn't taken over.
+    fn plugin_user(
+        conn: &mut DbConnection,
+        username: &str,
+        identity: PluginIdentity,
+    ) -> Result<User, ApiError> {
+        let existing = users::table
+            .filter(users::username.eq(username))
+            .first::<User>(conn)
+            .optional()
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+        if let Some(user) = existing {
+            if user.auth_plugin.as_deref() != Some(identity.plugin.as_str()) {
+                warn!(
+                    "Plugin {} accepted {username}, but the account isn't one of its own, an admin has to rename or remove it",
+                    identity.plugin
+                );
+                return Err(ApiError::Conflict(format!(
+                    "User {username} already exists and isn't managed by {}",
+                    identity.plugin
+                )));
+            }
+            if !user.is_active {
+                return Err(ApiError::Unauthorized(
+                    "Invalid username or password".to_string(),
+                ));
+            }
+            return Ok(user);
+        }
+
+        let email_taken = match &identity.email {
+            Some(email) => {
+                users::table
+                    .filter(users::email.eq(email))
+                    .count()
+                    .get_result::<i64>(conn)
+                    .map_err(|e| {
+                        ApiError::InternalServerError(format!("Database query error: {e}"))
+                    })?
+                    > 0
+            }
+            None => true,
+        };
+        let email = match identity.email {
+            Some(email) if !email_taken => email,
+            _ => format!("{username}@{}.invalid", identity.plugin),
+        };
+        let new_user = NewUser {
+            auth_plugin: Some(identity.plugin.clone()),
+            ..NewUser::new(
+                username.to_string(),
+                email,
+                uuid::Uuid::new_v4().to_string(),
+            )
+            .map_err(|e| ApiError::InternalServerError(format!("Password hashing error: {e}")))?
+        };
+
+        let user = diesel::insert_into(users::table)
+            .values(&new_user)
+            .get_result::<User>(conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to create user: {e}")))?;
+        info!(
+            "Created user {username} authenticated by plugin {}",
+            identity.plugin
+        );
+        Ok(user)
+    }
+
+    /// Resolves a bearer token to its user and records the client using it
+    pub fn validate_token(
+        db: &DatabaseService,
+        token_keys: &[TokenKey],
+        token: &str,
+        client: &ClientInfo,
+    ) -> Result<(User, UserToken), ApiError> {
         let mut conn = db.get_connection().map_err(|e| {
             ApiError::InternalServerError(format!("Database connection error: {e}"))
         })?;
 
         // Find active token
         let user_token = user_tokens::table
-            .filter(user_tokens::token.eq(token))
+            .filter(user_tokens::token.eq_any(Self::token_digests(token_keys, token)))
             .filter(user_tokens::is_active.eq(true))
             .first::<UserToken>(&mut conn)
             .optional()
@@ -126,23 +390,121 @@ impl AuthService {
             .first::<User>(&mut conn)
             .map_err(|e| ApiError::InternalServerError(format!("Failed to retrieve user: {e}")))?;
 
-        Ok(user)
+        let now = chrono::Utc::now().naive_utc();
+        let stale_before = now - chrono::Duration::seconds(TOKEN_USAGE_RESOLUTION_SECS);
+        let recorded = diesel::update(
+            user_tokens::table.find(user_token.id).filter(
+                user_tokens::last_used_at
+                    .is_null()
+                    .or(user_tokens::last_used_at.lt(stale_before)),
+            ),
+        )
+        .set((
+            user_tokens::last_used_at.eq(now),
+            user_tokens::user_agent.eq(&client.user_agent),
+            user_tokens::ip_address.eq(&client.ip_address),
+        ))
+        .execute(&mut conn);
+
+        if let Err(e) = recorded {
+            debug!("Failed to record token usage: {e}");
+        }
+
+        Ok((user, user_token))
     }
 
-    pub fn revoke_token(db: &DatabaseService, token: &str) -> Result<(), ApiError> {
+    /// Active, unexpired tokens of a user, most recently used first. `current_token_id` is
+    /// the token of the request when users list their own tokens.
+    pub fn list_sessions(
+        db: &DatabaseService,
+        user_id: i32,
+        current_token_id: Option<i32>,
+    ) -> Result<Vec<TokenSession>, ApiError> {
         let mut conn = db.get_connection().map_err(|e| {
             ApiError::InternalServerError(format!("Database connection error: {e}"))
         })?;
 
-        diesel::update(user_tokens::table.filter(user_tokens::token.eq(token)))
-            .set(user_tokens::is_active.eq(false))
-            .execute(&mut conn)
-            .map_err(|e| ApiError::InternalServerError(format!("Failed to revoke token: {e}")))?;
+        let now = chrono::Utc::now().naive_utc();
+        let mut tokens = user_tokens::table
+            .filter(user_tokens::user_id.eq(user_id))
+            .filter(user_tokens::is_active.eq(true))
+            .filter(
+                user_tokens::expires_at
+                    .is_null()
+                    .or(user_tokens::expires_at.gt(now)),
+            )
+            .load::<UserToken>(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+        tokens.sort_by_key(|t| std::cmp::Reverse(t.last_used_at.unwrap_or(t.created_at)));
+
+        Ok(tokens
+            .into_iter()
+            .map(|t| TokenSession::from_token(t, current_token_id))
+            .collect())
+    }
+
+    /// Revokes one of the user's own tokens by id
+    pub fn revoke_session(
+        db: &DatabaseService,
+        user_id: i32,
+        token_id: i32,
+    ) -> Result<(), ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let revoked = diesel::update(
+            user_tokens::table
+                .find(token_id)
+                .filter(user_tokens::user_id.eq(user_id))
+                .filter(user_tokens::is_active.eq(true)),
+        )
+        .set(user_tokens::is_active.eq(false))
+        .execute(&mut conn)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to revoke token: {e}")))?;
+
+        if revoked == 0 {
+            return Err(ApiError::NotFound(format!("Session {token_id} not found")));
+        }
+
+        info!("Revoked session {token_id} of user {user_id}");
+        Ok(())
+    }
+
+    pub fn revoke_token(
+        db: &DatabaseService,
+        token_keys: &[TokenKey],
+        token: &str,
+    ) -> Result<(), ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        diesel::update(
+            user_tokens::table
+                .filter(user_tokens::token.eq_any(Self::token_digests(token_keys, token))),
+        )
+        .set(user_tokens::is_active.eq(false))
+        .execute(&mut conn)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to revoke token: {e}")))?;
 
         debug!("Token revoked successfully");
         Ok(())
     }
 
+    /// Revokes every token of a user
+    pub fn revoke_all_tokens(db: &DatabaseService, user_id: i32) -> Result<(), ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        Self::revoke_user_tokens(&mut conn, user_id)?;
+
+        debug!("Revoked all tokens of user {user_id}");
+        Ok(())
+    }
+
     pub fn get_user_by_username(
         db: &DatabaseService,
         username: &str,
@@ -160,4 +522,538 @@ impl AuthService {
 
         Ok(user)
     }
+
+    /// Creates the admin account configured via environment variables if it doesn't exist yet.
+    /// An existing account of that name without the admin role is never promoted, anyone could
+    /// have registered it before the admin was configured.
+    pub fn bootstrap_admin(db: &DatabaseService, config: &AppConfig) -> Result<(), ApiError> {
+        let (Some(username), Some(password)) = (&config.admin_username, &config.admin_password)
+        else {
+            return Ok(());
+        };
+
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let existing_user = users::table
+            .filter(users::username.eq(username))
+            .first::<User>(&mut conn)
+            .optional()
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+        match existing_user {
+            Some(user) if user.is_admin() => {
+                debug!("Admin user '{username}' already exists, skipping bootstrap");
+            }
+            Some(_) => {
+                return Err(ApiError::Conflict(format!(
+                    "User '{username}' already exists and is not an admin, refusing to make it \
+                     the bootstrap admin. Choose another CLEF_ADMIN_USERNAME, or have an admin \
+                     grant the role through /api/v1/admin/users/{username}/role."
+                )));
+            }
+            None => {
+                let email = config
+                    .admin_email
+                    .clone()
+                    .unwrap_or_else(|| format!("{username}@localhost"));
+
+                Self::register_user(
+                    db,
+                    RegisterRequest {
+                        name: username.clone(),
+                        email,
+                        password: password.clone(),
+                        invite_token: None,
+                    },
+                )?;
+                Self::set_user_role(db, username, UserRole::Admin)?;
+                info!("Created bootstrap admin user '{username}'");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lists all users, including disabled ones
+    pub fn list_users(db: &DatabaseService) -> Result<Vec<User>, ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        users::table
+            .order(users::username.asc())
+            .load::<User>(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))
+    }
+
+    /// Enables or disables a user. Disabling also revokes all of the user's tokens.
+    pub fn set_user_active(
+        db: &DatabaseService,
+        username: &str,
+        active: bool,
+    ) -> Result<User, ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = Self::find_user(&mut conn, username)?;
+
+        diesel::update(users::table.find(user.id))
+            .set(&UpdateUser {
+                email: None,
+                password_hash: None,
+                updated_at: Some(chrono::Utc::now().naive_utc()),
+                is_active: Some(active),
+                role: None,
+                email_verified: None,
+            })
+            .execute(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to update user: {e}")))?;
+
+        if !active {
+            Self::revoke_user_tokens(&mut conn, user.id)?;
+        }
+
+        let user = Self::find_user(&mut conn, username)?;
+        debug!("User {} is_active set to {active}", user.username);
+        Ok(user)
+    }
+
+    /// Changes a user's registry-wide role
+    pub fn set_user_role(
+        db: &DatabaseService,
+        username: &str,
+        role: UserRole,
+    ) -> Result<User, ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = Self::find_user(&mut conn, username)?;
+        if user.is_service_account() || role == UserRole::Service {
+            return Err(ApiError::BadRequest(
+                "Service accounts are managed by their organization".to_string(),
+            ));
+        }
+
+        diesel::update(users::table.find(user.id))
+            .set(&UpdateUser {
+                email: None,
+                password_hash: None,
+                updated_at: Some(chrono::Utc::now().naive_utc()),
+                is_active: None,
+                role: Some(role.to_string()),
+                email_verified: None,
+            })
+            .execute(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to update user: {e}")))?;
+
+        let user = Self::find_user(&mut conn, username)?;
+        debug!("User {} role set to {}", user.username, user.role);
+        Ok(user)
+    }
+
+    /// Deletes a user; tokens, ownerships and memberships are removed by cascade
+    pub fn delete_user(db: &DatabaseService, username: &str) -> Result<(), ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = Self::find_user(&mut conn, username)?;
+
+        diesel::delete(users::table.find(user.id))
+            .execute(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to delete user: {e}")))?;
+
+        debug!("User deleted: {username}");
+        Ok(())
+    }
+
+    /// Sets a new password for a user and revokes all existing tokens
+    pub fn reset_password(
+        db: &DatabaseService,
+        username: &str,
+        password: &str,
+    ) -> Result<(), ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = Self::find_user(&mut conn, username)?;
+
+        let password_hash = bcrypt::hash(password, bcrypt::DEFAULT_COST)
+            .map_err(|e| ApiError::InternalServerError(format!("Password hashing error: {e}")))?;
+
+        diesel::update(users::table.find(user.id))
+            .set(&UpdateUser {
+                email: None,
+                password_hash: Some(password_hash),
+                updated_at: Some(chrono::Utc::now().naive_utc()),
+                is_active: None,
+                role: None,
+                email_verified: None,
+            })
+            .execute(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to update user: {e}")))?;
+
+        Self::revoke_user_tokens(&mut conn, user.id)?;
+
+        debug!("Password reset for user: {username}");
+        Ok(())
+    }
+
+    /// Issues a token for a user without a password, for operators. Publish tokens don't
+    /// expire, auth tokens expire like login sessions.
+    pub fn create_token(
+        db: &DatabaseService,
+        token_keys: &[TokenKey],
+        username: &str,
+        publish: bool,
+    ) -> Result<String, ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let user = Self::find_user(&mut conn, username)?;
+        if !user.is_active {
+            return Err(ApiError::BadRequest(format!(
+                "User '{username}' is disabled"
+            )));
+        }
+
+        let new_token = if publish {
+            NewUserToken::new_publish_token(user.id)
+        } else {
+            NewUserToken::new_auth_token(user.id)
+        };
+
+        let token_type = new_token.token_type.clone();
+        let token = Self::insert_token(&mut conn, token_keys, new_token)?;
+
+        info!("Created {token_type} token for user {username}");
+        Ok(token)
+    }
+
+    /// Replaces tokens stored before tokens were hashed with their digests, returning how
+    /// many were converted. Runs at startup.
+    pub fn hash_stored_tokens(db: &DatabaseService, config: &AppConfig) -> Result<usize, ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let plain = user_tokens::table
+            .filter(user_tokens::token.not_like(format!("{SHA256_DIGEST_PREFIX}%")))
+            .filter(user_tokens::token.not_like(format!("{HMAC_DIGEST_PREFIX}%")))
+            .select((user_tokens::id, user_tokens::token))
+            .load::<(i32, String)>(&mut conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?;
+
+        conn.transaction(|conn| {
+            for (id, token) in &plain {
+                diesel::update(user_tokens::table.find(id))
+                    .set((
+                        user_tokens::token.eq(Self::token_digest(&config.token_keys, token)),
+                        user_tokens::token_preview.eq(NewUserToken::preview(token)),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to hash tokens: {e}")))?;
+
+        if !plain.is_empty() {
+            info!("Hashed {} tokens stored in plain text", plain.len());
+        }
+        Ok(plain.len())
+    }
+
+    /// Stores a new token as its digest, returning the token itself
+    fn insert_token(
+        conn: &mut DbConnection,
+        token_keys: &[TokenKey],
+        mut new_token: NewUserToken,
+    ) -> Result<String, ApiError> {
+        let digest = Self::token_digest(token_keys, &new_token.token);
+        let token = std::mem::replace(&mut new_token.token, digest);
+
+        diesel::insert_into(user_tokens::table)
+            .values(&new_token)
+            .execute(conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to create token: {e}")))?;
+
+        Ok(token)
+    }
+
+    /// Digest a token is stored as, keyed with the current token key if there is one
+    fn token_digest(token_keys: &[TokenKey], token: &str) -> String {
+        match token_keys.first() {
+            Some(key) => Self::keyed_token_digest(key, token),
+            None => format!("{SHA256_DIGEST_PREFIX}{}", hex(&Sha256::digest(token))),
+        }
+    }
+
+    /// Every digest a token may be stored as. Tokens hashed with a previous key keep
+    /// validating while the key is listed.
+    fn token_digests(token_keys: &[TokenKey], token: &str) -> Vec<String> {
+        std::iter::once(format!(
+            "{SHA256_DIGEST_PREFIX}{}",
+            hex(&Sha256::digest(token))
+        ))
+        .chain(
+            token_keys
+                .iter()
+                .map(|key| Self::keyed_token_digest(key, token)),
+        )
+        .collect()
+    }
+
+    fn keyed_token_digest(key: &TokenKey, token: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(token.as_bytes());
+        format!(
+            "{HMAC_DIGEST_PREFIX}{}:{}",
+            key.id,
+            hex(&mac.finalize().into_bytes())
+        )
+    }
+
+    /// Revokes tokens that weren't used for `days` days, or since they were created if they
+    /// were never used. Returns how many were revoked.
+    pub fn revoke_idle_tokens(db: &DatabaseService, days: u64) -> Result<usize, ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let idle_since = chrono::Utc::now().naive_utc()
+            - chrono::Duration::days(i64::try_from(days).unwrap_or(i64::MAX).min(36500));
+        let revoked = diesel::update(
+            user_tokens::table
+                .filter(user_tokens::is_active.eq(true))
+                .filter(
+                    user_tokens::last_used_at
+                        .lt(idle_since)
+                        .or(user_tokens::last_used_at
+                            .is_null()
+                            .and(user_tokens::created_at.lt(idle_since))),
+                ),
+        )
+        .set(user_tokens::is_active.eq(false))
+        .execute(&mut conn)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to revoke tokens: {e}")))?;
+
+        Ok(revoked)
+    }
+
+    /// Deletes revoked and expired tokens, returning how many were removed
+    pub fn prune_tokens(db: &DatabaseService) -> Result<usize, ApiError> {
+        let mut conn = db.get_connection().map_err(|e| {
+            ApiError::InternalServerError(format!("Database connection error: {e}"))
+        })?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let pruned = diesel::delete(
+            user_tokens::table.filter(
+                user_tokens::is_active
+                    .eq(false)
+                    .or(user_tokens::expires_at.lt(now)),
+            ),
+        )
+        .execute(&mut conn)
+        .map_err(|e| ApiError::InternalServerError(format!("Failed to prune tokens: {e}")))?;
+
+        debug!("Pruned {pruned} revoked or expired tokens");
+        Ok(pruned)
+    }
+
+    fn find_user(conn: &mut DbConnection, username: &str) -> Result<User, ApiError> {
+        users::table
+            .filter(users::username.eq(username))
+            .first::<User>(conn)
+            .optional()
+            .map_err(|e| ApiError::InternalServerError(format!("Database query error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("User '{username}' not found")))
+    }
+
+    fn revoke_user_tokens(conn: &mut DbConnection, user_id: i32) -> Result<(), ApiError> {
+        diesel::update(user_tokens::table.filter(user_tokens::user_id.eq(user_id)))
+            .set(user_tokens::is_active.eq(false))
+            .execute(conn)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to revoke tokens: {e}")))?;
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_digests_survive_key_rotation() {
+        let key = |id: &str, secret: &str| TokenKey {
+            id: id.to_string(),
+            secret: secret.to_string(),
+        };
+        let unkeyed = [];
+        let before = [key("2025-01", "old secret")];
+        let after = [key("2025-07", "new secret"), key("2025-01", "old secret")];
+        let token = "0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0";
+
+        let plain = AuthService::token_digest(&unkeyed, token);
+        assert!(plain.starts_with("sha256:"));
+        let old = AuthService::token_digest(&before, token);
+        assert!(old.starts_with("hmac-sha256:2025-01:"));
+        let new = AuthService::token_digest(&after, token);
+        assert!(new.starts_with("hmac-sha256:2025-07:"));
+
+        let accepted = AuthService::token_digests(&after, token);
+        assert!(accepted.contains(&plain));
+        assert!(accepted.contains(&old));
+        assert!(accepted.contains(&new));
+        assert!(!AuthService::token_digests(&unkeyed, token).contains(&old));
+        assert!(!accepted.contains(&AuthService::token_digest(&after, "another token")));
+    }
+
+    #[test]
+    fn test_bootstrap_admin() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+        let config = |username: &str| AppConfig {
+            admin_username: Some(username.to_string()),
+            admin_password: Some("correct horse battery".to_string()),
+            ..AppConfig::default()
+        };
+
+        AuthService::bootstrap_admin(&database, &config("root")).unwrap();
+        let admin = AuthService::get_user_by_username(&database, "root").unwrap();
+        assert!(admin.is_some_and(|user| user.is_admin()));
+        // Booting again leaves the admin alone
+        AuthService::bootstrap_admin(&database, &config("root")).unwrap();
+
+        // Someone registered the name before the operator configured it
+        AuthService::register_user(
+            &database,
+            RegisterRequest {
+                name: "squatter".to_string(),
+                email: "squatter@example.com".to_string(),
+                password: "hunter22".to_string(),
+                invite_token: None,
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            AuthService::bootstrap_admin(&database, &config("squatter")),
+            Err(ApiError::Conflict(_))
+        ));
+        let squatter = AuthService::get_user_by_username(&database, "squatter").unwrap();
+        assert!(squatter.is_some_and(|user| !user.is_admin()));
+    }
+
+    #[test]
+    fn test_plugin_user_only_logs_into_its_own_accounts() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+        let mut conn = database.get_connection().unwrap();
+        let identity = |plugin: &str| PluginIdentity {
+            plugin: plugin.to_string(),
+            email: Some("alice@example.com".to_string()),
+        };
+
+        let created = AuthService::plugin_user(&mut conn, "alice", identity("ldap")).unwrap();
+        assert_eq!(created.auth_plugin.as_deref(), Some("ldap"));
+        let again = AuthService::plugin_user(&mut conn, "alice", identity("ldap")).unwrap();
+        assert_eq!(again.id, created.id);
+        assert!(matches!(
+            AuthService::plugin_user(&mut conn, "alice", identity("sso")),
+            Err(ApiError::Conflict(_))
+        ));
+
+        // Local accounts, the bootstrap admin among them, aren't taken over
+        let config = AppConfig {
+            admin_username: Some("root".to_string()),
+            admin_password: Some("correct horse battery".to_string()),
+            ..AppConfig::default()
+        };
+        AuthService::bootstrap_admin(&database, &config).unwrap();
+        AuthService::register_user(
+            &database,
+            RegisterRequest {
+                name: "bob".to_string(),
+                email: "bob@example.com".to_string(),
+                password: "hunter22".to_string(),
+                invite_token: None,
+            },
+        )
+        .unwrap();
+        for username in ["root", "bob"] {
+            assert!(matches!(
+                AuthService::plugin_user(&mut conn, username, identity("ldap")),
+                Err(ApiError::Conflict(_))
+            ));
+            let user = AuthService::get_user_by_username(&database, username)
+                .unwrap()
+                .unwrap();
+            assert_eq!(user.auth_plugin, None);
+        }
+    }
+
+    #[test]
+    fn test_revoke_idle_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+        AuthService::register_user(
+            &database,
+            RegisterRequest {
+                name: "alice".to_string(),
+                email: "alice@example.com".to_string(),
+                password: "hunter22".to_string(),
+                invite_token: None,
+            },
+        )
+        .unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        let days_ago = |days: i64| now - chrono::Duration::days(days);
+        let token = |created_at, last_used_at| {
+            let token = AuthService::create_token(&database, &[], "alice", true).unwrap();
+            let mut conn = database.get_connection().unwrap();
+            diesel::update(
+                user_tokens::table
+                    .filter(user_tokens::token.eq(AuthService::token_digest(&[], &token))),
+            )
+            .set((
+                user_tokens::created_at.eq(created_at),
+                user_tokens::last_used_at.eq(last_used_at),
+            ))
+            .execute(&mut conn)
+            .unwrap();
+            token
+        };
+        let active = |token: &str| {
+            let mut conn = database.get_connection().unwrap();
+            user_tokens::table
+                .filter(user_tokens::token.eq(AuthService::token_digest(&[], token)))
+                .select(user_tokens::is_active)
+                .first::<bool>(&mut conn)
+                .unwrap()
+        };
+
+        let idle = token(days_ago(60), Some(days_ago(45)));
+        let never_used = token(days_ago(40), None);
+        let recently_used = token(days_ago(60), Some(days_ago(2)));
+        let new = token(now, None);
+
+        assert_eq!(AuthService::revoke_idle_tokens(&database, 30).unwrap(), 2);
+        assert!(!active(&idle));
+        assert!(!active(&never_used));
+        assert!(active(&recently_used));
+        assert!(active(&new));
+        // Revoked tokens aren't counted again
+        assert_eq!(AuthService::revoke_idle_tokens(&database, 30).unwrap(), 0);
+    }
 }
@@ -0,0 +1,170 @@
+use crate::error::ApiError;
+use crate::models::{BandwidthEntry, BandwidthGrouping, BandwidthReport, BandwidthUsageRow};
+use crate::services::CacheAnalyticsService;
+use crate::state::AppState;
+use chrono::Duration;
+use std::collections::HashMap;
+
+/// Period used when none is requested
+const DEFAULT_PERIOD: &str = "30d";
+/// Entries returned when no limit is requested
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+pub struct BandwidthService;
+
+impl BandwidthService {
+    /// Bytes of metadata and tarballs served over the last `period` days, today included,
+    /// added up per package, user, token or organization
+    pub fn report(
+        period: Option<&str>,
+        group_by: Option<&str>,
+        limit: Option<usize>,
+        state: &AppState,
+    ) -> Result<BandwidthReport, ApiError> {
+        let group_by = match group_by {
+            Some(value) => BandwidthGrouping::from_grouping_str(value).ok_or_else(|| {
+                ApiError::BadRequest(format!(
+                    "Invalid group_by '{value}', expected package, user, token or org"
+                ))
+            })?,
+            None => BandwidthGrouping::Package,
+        };
+        let period = period.unwrap_or(DEFAULT_PERIOD);
+        let days = CacheAnalyticsService::parse_period(period)?;
+        let to = chrono::Utc::now().date_naive();
+        let from = to - Duration::days(days - 1);
+
+        let rows = state
+            .database
+            .get_bandwidth_usage(from, to)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let total_bytes = rows.iter().map(|row| row.bytes).sum();
+        let total_requests = rows.iter().map(|row| row.requests).sum();
+        let mut entries = Self::group(rows, group_by);
+        entries.truncate(limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+
+        Ok(BandwidthReport {
+            period: period.to_string(),
+            from,
+            to,
+            group_by,
+            total_bytes,
+            total_requests,
+            entries,
+        })
+    }
+
+    /// Adds up usage rows by the grouping, largest consumers first
+    fn group(rows: Vec<BandwidthUsageRow>, group_by: BandwidthGrouping) -> Vec<BandwidthEntry> {
+        let mut entries: HashMap<Option<String>, BandwidthEntry> = HashMap::new();
+        for row in rows {
+            let (key, username) = match group_by {
+                BandwidthGrouping::Package => (Some(row.package_name), None),
+                BandwidthGrouping::User => (Self::username(&row), None),
+                BandwidthGrouping::Token => {
+                    let token = match row.token_id {
+                        0 => None,
+                        id => Some(row.token_preview.clone().unwrap_or(format!("token #{id}"))),
+                    };
+                    (token.clone(), token.and(Self::username(&row)))
+                }
+                BandwidthGrouping::Org => (row.organization, None),
+            };
+
+            let entry = entries.entry(key.clone()).or_insert(BandwidthEntry {
+                key,
+                username,
+                bytes: 0,
+                requests: 0,
+            });
+            entry.bytes += row.bytes;
+            entry.requests += row.requests;
+        }
+
+        let mut entries: Vec<BandwidthEntry> = entries.into_values().collect();
+        entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.key.cmp(&b.key)));
+        entries
+    }
+
+    /// Name of the user of a row, anonymous requests have none
+    fn username(row: &BandwidthUsageRow) -> Option<String> {
+        match row.user_id {
+            0 => None,
+            id => Some(row.username.clone().unwrap_or(format!("user #{id}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(
+        package: &str,
+        user: Option<(i32, &str)>,
+        token: Option<i32>,
+        bytes: i64,
+    ) -> BandwidthUsageRow {
+        BandwidthUsageRow {
+            package_name: package.to_string(),
+            user_id: user.map_or(0, |(id, _)| id),
+            token_id: token.unwrap_or(0),
+            bytes,
+            requests: 1,
+            username: user.map(|(_, name)| name.to_string()),
+            token_preview: token.map(|id| format!("npm_{id}…")),
+            organization: package
+                .strip_prefix('@')
+                .and_then(|name| name.split('/').next())
+                .map(str::to_string),
+        }
+    }
+
+    fn rows() -> Vec<BandwidthUsageRow> {
+        vec![
+            row("@acme/ui", Some((1, "alice")), Some(10), 300),
+            row("@acme/ui", None, None, 50),
+            row("lodash", Some((1, "alice")), Some(11), 100),
+            row("lodash", Some((2, "bob")), None, 200),
+        ]
+    }
+
+    fn summary(entries: &[BandwidthEntry]) -> Vec<(Option<&str>, i64)> {
+        entries
+            .iter()
+            .map(|entry| (entry.key.as_deref(), entry.bytes))
+            .collect()
+    }
+
+    #[test]
+    fn test_group_by_package_and_org() {
+        let entries = BandwidthService::group(rows(), BandwidthGrouping::Package);
+        assert_eq!(
+            summary(&entries),
+            [(Some("@acme/ui"), 350), (Some("lodash"), 300)]
+        );
+        assert_eq!(entries[0].requests, 2);
+
+        let entries = BandwidthService::group(rows(), BandwidthGrouping::Org);
+        assert_eq!(summary(&entries), [(Some("acme"), 350), (None, 300)]);
+    }
+
+    #[test]
+    fn test_group_by_user_and_token() {
+        let entries = BandwidthService::group(rows(), BandwidthGrouping::User);
+        assert_eq!(
+            summary(&entries),
+            [(Some("alice"), 400), (Some("bob"), 200), (None, 50)]
+        );
+
+        let entries = BandwidthService::group(rows(), BandwidthGrouping::Token);
+        assert_eq!(
+            summary(&entries),
+            [(Some("npm_10…"), 300), (None, 250), (Some("npm_11…"), 100)]
+        );
+        assert_eq!(entries[0].username.as_deref(), Some("alice"));
+        assert_eq!(entries[1].username, None);
+    }
+}
@@ -1,11 +1,21 @@
 use crate::config::AppConfig;
 use crate::database::files::CompletePackageParams;
-use crate::models::{CacheEntry, CacheStats};
+use crate::models::{
+    CacheEntry, CacheEntryInfo, CacheEntryKind, CacheGcReport, CacheInvalidationReport, CacheStats,
+    HotCacheStats, MissingCacheFile,
+};
 use crate::services::DatabaseService;
+use crate::services::disk_watermark::EvictionCounters;
+use crate::services::hot_cache::{HotCache, HotEntry};
+use crate::services::shared_redis::SharedRedis;
 use log::{debug, info, warn};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Files younger than this are never treated as orphans, their record may still be written
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(600);
 // Arc removed - database passed as parameter
 
 #[derive(Debug)]
@@ -13,6 +23,12 @@ pub struct CacheService {
     config: AppConfig,
     hit_count: std::sync::atomic::AtomicU64,
     miss_count: std::sync::atomic::AtomicU64,
+    /// Package metadata documents served without touching the disk or the database
+    hot: HotCache,
+    /// When the dist-tags of cached package documents were last checked upstream
+    dist_tags_checked: std::sync::Mutex<HashMap<String, SystemTime>>,
+    /// Tarballs removed to keep the cache volume below the high watermark
+    disk_evictions: EvictionCounters,
 }
 
 impl CacheService {
@@ -24,9 +40,12 @@ impl CacheService {
         }
 
         Ok(Self {
+            hot: Self::hot_cache(&config),
             config,
             hit_count: std::sync::atomic::AtomicU64::new(0),
             miss_count: std::sync::atomic::AtomicU64::new(0),
+            dist_tags_checked: Default::default(),
+            disk_evictions: Default::default(),
         })
     }
 
@@ -65,9 +84,12 @@ impl CacheService {
         };
 
         Ok(Self {
+            hot: Self::hot_cache(&config),
             config,
             hit_count: std::sync::atomic::AtomicU64::new(initial_hit_count),
             miss_count: std::sync::atomic::AtomicU64::new(initial_miss_count),
+            dist_tags_checked: Default::default(),
+            disk_evictions: Default::default(),
         })
     }
 
@@ -75,6 +97,54 @@ impl CacheService {
         self.config.cache_enabled
     }
 
+    fn hot_cache(config: &AppConfig) -> HotCache {
+        let hot = HotCache::new(
+            config.hot_cache_max_mb.saturating_mul(1024 * 1024),
+            config.hot_cache_max_entry_kb.saturating_mul(1024),
+        )
+        .with_shared(SharedRedis::from_config(config));
+        hot.set_pinned(config.pinned_packages.iter().cloned().collect());
+        hot
+    }
+
+    pub fn hot_cache_stats(&self) -> HotCacheStats {
+        self.hot.stats()
+    }
+
+    pub fn disk_evictions(&self) -> &EvictionCounters {
+        &self.disk_evictions
+    }
+
+    /// Packages whose metadata stays in memory when room is needed
+    pub fn set_pinned(&self, pinned: BTreeSet<String>) {
+        self.hot.set_pinned(pinned);
+    }
+
+    async fn remember_metadata(
+        &self,
+        package: &str,
+        data: &[u8],
+        etag: Option<String>,
+        fetched_at: SystemTime,
+    ) {
+        if !self.hot.is_enabled() {
+            return;
+        }
+        let published = serde_json::from_slice::<serde_json::Value>(data)
+            .is_ok_and(|json| self.has_published_versions(&json));
+        self.hot
+            .insert(
+                package,
+                HotEntry {
+                    data: data.to_vec(),
+                    etag,
+                    fetched_at,
+                    published,
+                },
+            )
+            .await;
+    }
+
     // Database is now passed as parameter to methods that need it
 
     fn extract_version_from_filename(&self, package: &str, filename: &str) -> Option<String> {
@@ -123,6 +193,38 @@ impl CacheService {
         package_dir.join(meta_filename)
     }
 
+    /// Contents of the metadata file of a tarball: the ETag on the first line and the
+    /// upstream `Last-Modified` on the second, either may be empty
+    fn tarball_meta(etag: Option<&str>, last_modified: Option<&str>) -> String {
+        match last_modified {
+            Some(last_modified) => format!("{}\n{last_modified}", etag.unwrap_or_default()),
+            None => etag.unwrap_or_default().to_string(),
+        }
+    }
+
+    /// ETag and `Last-Modified` of a tarball metadata file. Files written before the
+    /// `Last-Modified` was recorded only hold the ETag.
+    fn parse_tarball_meta(meta: &str) -> (Option<String>, Option<String>) {
+        let mut lines = meta.lines().map(str::trim);
+        let mut next = || {
+            lines
+                .next()
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let etag = next();
+        (etag, next())
+    }
+
+    /// Upstream `Last-Modified` recorded with a cached tarball
+    pub fn tarball_last_modified(&self, package: &str, filename: &str) -> Option<String> {
+        if !self.config.cache_enabled {
+            return None;
+        }
+        let meta = fs::read_to_string(self.get_metadata_path(package, filename)).ok()?;
+        Self::parse_tarball_meta(&meta).1
+    }
+
     pub fn get_metadata_cache_path(&self, package: &str) -> PathBuf {
         // Metadata cache files are stored as {package}.metadata.json
         let packages_dir = Path::new(&self.config.cache_dir).join("packages");
@@ -202,6 +304,12 @@ impl CacheService {
             self.miss_count
                 .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             debug!("Cache miss for key: {cache_key} - file not found at {file_path:?}");
+
+            // Persist miss count to database if available
+            if let Some(database) = database {
+                database.queue_cache_miss(package);
+            }
+
             return None;
         }
 
@@ -216,7 +324,9 @@ impl CacheService {
 
                 // Try to read metadata (etag, etc.)
                 let meta_path = self.get_metadata_path(package, filename);
-                let etag = fs::read_to_string(&meta_path).ok();
+                let (etag, last_modified) = fs::read_to_string(&meta_path)
+                    .map(|meta| Self::parse_tarball_meta(&meta))
+                    .unwrap_or_default();
 
                 self.hit_count
                     .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
@@ -224,7 +334,7 @@ impl CacheService {
 
                 // Persist hit count to database if available
                 if let Some(database) = database {
-                    let _ = database.increment_cache_hit_count();
+                    database.queue_cache_hit(package, size);
                 }
 
                 // Update access info in database if available
@@ -232,7 +342,7 @@ impl CacheService {
                     if let Ok(Some((_package, _version, file))) =
                         database.get_package_file(package, filename)
                     {
-                        let _ = database.update_file_access_info(file.id);
+                        database.queue_file_access(file.id);
                     }
                 }
 
@@ -241,6 +351,7 @@ impl CacheService {
                     created_at,
                     size,
                     etag,
+                    last_modified,
                 })
             }
             Err(e) => {
@@ -250,7 +361,7 @@ impl CacheService {
 
                 // Persist miss count to database if available
                 if let Some(database) = database {
-                    let _ = database.increment_cache_miss_count();
+                    database.queue_cache_miss(package);
                 }
 
                 None
@@ -258,6 +369,30 @@ impl CacheService {
         }
     }
 
+    /// Reads cached package metadata ignoring the TTL, used to serve stale data when upstream is slow
+    pub fn get_stale_metadata(&self, package: &str) -> Option<serde_json::Value> {
+        if !self.config.cache_enabled {
+            return None;
+        }
+
+        let data = fs::read(self.get_metadata_cache_path(package)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// Reads cached version metadata ignoring the TTL
+    pub fn get_stale_version_metadata(
+        &self,
+        package: &str,
+        version: &str,
+    ) -> Option<serde_json::Value> {
+        if !self.config.cache_enabled {
+            return None;
+        }
+
+        let data = fs::read(self.get_version_metadata_cache_path(package, version)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
     pub async fn get_metadata(&self, package: &str) -> Option<CacheEntry> {
         self.get_metadata_with_database(package, None).await
     }
@@ -284,7 +419,7 @@ impl CacheService {
 
             // Persist miss count to database if available
             if let Some(database) = database {
-                let _ = database.increment_cache_miss_count();
+                database.queue_cache_miss(package);
             }
 
             return None;
@@ -296,10 +431,13 @@ impl CacheService {
                 let age = SystemTime::now()
                     .duration_since(created)
                     .unwrap_or_default();
-                let ttl_seconds = self.config.cache_ttl_hours * 3600;
+                let expired = self
+                    .config
+                    .metadata_ttl_secs(package)
+                    .is_some_and(|ttl_seconds| age.as_secs() > ttl_seconds);
 
                 // Only apply TTL to upstream packages (check if this is a published package by looking for author_id in cached metadata)
-                if age.as_secs() > ttl_seconds {
+                if expired {
                     if let Ok(data) = fs::read_to_string(&cache_path) {
                         if let Ok(json) = serde_json::from_str::<serde_json::Value>(&data) {
                             // For version-specific metadata, check if it's from our server by looking at dist.tarball
@@ -326,7 +464,7 @@ impl CacheService {
 
                                 // Persist miss count to database if available
                                 if let Some(database) = database {
-                                    let _ = database.increment_cache_miss_count();
+                                    database.queue_cache_miss(package);
                                 }
 
                                 return None;
@@ -351,7 +489,7 @@ impl CacheService {
 
                 // Persist hit count and update access info in database if available
                 if let Some(database) = database {
-                    let _ = database.increment_cache_hit_count();
+                    database.queue_cache_hit(package, size);
                     // Note: We don't have version-specific access tracking in the database yet
                 }
 
@@ -364,6 +502,7 @@ impl CacheService {
                     created_at,
                     size,
                     etag,
+                    last_modified: None,
                 })
             }
             Err(e) => {
@@ -373,7 +512,7 @@ impl CacheService {
 
                 // Persist miss count to database if available
                 if let Some(database) = database {
-                    let _ = database.increment_cache_miss_count();
+                    database.queue_cache_miss(package);
                 }
 
                 None
@@ -390,6 +529,33 @@ impl CacheService {
             return None;
         }
 
+        if let Some(entry) = self
+            .hot
+            .get(
+                package,
+                self.config.metadata_ttl_secs(package).unwrap_or(u64::MAX),
+            )
+            .await
+        {
+            self.hit_count
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            debug!("In-memory metadata cache hit for package: {package}");
+            let size = entry.data.len() as u64;
+            if let Some(database) = database {
+                database.queue_cache_hit(package, size);
+            }
+            return Some(CacheEntry {
+                size,
+                created_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                data: entry.data,
+                etag: entry.etag,
+                last_modified: None,
+            });
+        }
+
         let cache_key = format!("{package}.metadata");
         let cache_path = self.get_metadata_cache_path(package);
 
@@ -402,7 +568,7 @@ impl CacheService {
 
             // Persist miss count to database if available
             if let Some(database) = database {
-                let _ = database.increment_cache_miss_count();
+                database.queue_cache_miss(package);
             }
 
             return None;
@@ -414,10 +580,13 @@ impl CacheService {
                 let age = SystemTime::now()
                     .duration_since(modified)
                     .unwrap_or_default();
-                let ttl_seconds = self.config.cache_ttl_hours * 3600;
+                let expired = self
+                    .config
+                    .metadata_ttl_secs(package)
+                    .is_some_and(|ttl_seconds| age.as_secs() > ttl_seconds);
 
                 // Only apply TTL to upstream packages (check if this is a published package by looking for author_id in cached metadata)
-                if age.as_secs() > ttl_seconds {
+                if expired {
                     if let Ok(data) = fs::read_to_string(&cache_path) {
                         if let Ok(json) = serde_json::from_str::<serde_json::Value>(&data) {
                             // If it doesn't have published versions (no author_id), it's upstream and should expire
@@ -428,7 +597,7 @@ impl CacheService {
 
                                 // Persist miss count to database if available
                                 if let Some(database) = database {
-                                    let _ = database.increment_cache_miss_count();
+                                    database.queue_cache_miss(package);
                                 }
 
                                 return None;
@@ -453,7 +622,7 @@ impl CacheService {
 
                 // Persist hit count and update access info in database if available
                 if let Some(database) = database {
-                    let _ = database.increment_cache_hit_count();
+                    database.queue_cache_hit(package, size);
                     let _ = database.update_metadata_access_info(package);
                 }
 
@@ -461,11 +630,18 @@ impl CacheService {
                 let etag_path = self.get_metadata_etag_path(package);
                 let etag = fs::read_to_string(&etag_path).ok();
 
+                let fetched_at = fs::metadata(&cache_path)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or_else(|_| SystemTime::now());
+                self.remember_metadata(package, &data, etag.clone(), fetched_at)
+                    .await;
+
                 Some(CacheEntry {
                     data,
                     created_at,
                     size,
                     etag,
+                    last_modified: None,
                 })
             }
             Err(e) => {
@@ -475,7 +651,7 @@ impl CacheService {
 
                 // Persist miss count to database if available
                 if let Some(database) = database {
-                    let _ = database.increment_cache_miss_count();
+                    database.queue_cache_miss(package);
                 }
 
                 None
@@ -483,12 +659,14 @@ impl CacheService {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn put(
         &self,
         package: &str,
         filename: &str,
         data: &[u8],
         etag: Option<&str>,
+        last_modified: Option<&str>,
         _upstream_url: &str,
         database: Option<&DatabaseService>,
     ) -> Result<(), std::io::Error> {
@@ -515,8 +693,8 @@ impl CacheService {
         fs::write(&cache_path, data)?;
 
         // Write metadata if available
-        if let Some(etag_value) = etag {
-            fs::write(&meta_path, etag_value)?;
+        if etag.is_some() || last_modified.is_some() {
+            fs::write(&meta_path, Self::tarball_meta(etag, last_modified))?;
         }
 
         // Store metadata in database if available and version is known
@@ -683,6 +861,14 @@ impl CacheService {
             }
         }
 
+        self.remember_metadata(
+            package,
+            metadata_json.as_bytes(),
+            etag.map(str::to_string),
+            SystemTime::now(),
+        )
+        .await;
+
         info!(
             "Cached metadata for {package} (size: {} bytes)",
             metadata_json.len()
@@ -690,11 +876,89 @@ impl CacheService {
         Ok(())
     }
 
+    /// Time since the package metadata was last fetched
+    pub fn metadata_age(&self, package: &str) -> Option<Duration> {
+        let modified = fs::metadata(self.get_metadata_cache_path(package))
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        Some(
+            SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Sets when the package metadata counts as fetched. Backdating it past the TTL makes
+    /// the next read revalidate upstream while the stale copy stays available.
+    pub async fn set_metadata_fetched_at(
+        &self,
+        package: &str,
+        fetched_at: SystemTime,
+    ) -> Result<(), std::io::Error> {
+        self.hot.remove(package).await;
+        fs::File::options()
+            .write(true)
+            .open(self.get_metadata_cache_path(package))?
+            .set_modified(fetched_at)
+    }
+
+    /// Time since the dist-tags of the cached package document were fetched or checked
+    pub fn dist_tags_age(&self, package: &str) -> Option<Duration> {
+        let fetched_at = fs::metadata(self.get_metadata_cache_path(package))
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        let checked_at = self
+            .dist_tags_checked
+            .lock()
+            .unwrap()
+            .get(package)
+            .map_or(fetched_at, |checked_at| (*checked_at).max(fetched_at));
+        Some(
+            SystemTime::now()
+                .duration_since(checked_at)
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn mark_dist_tags_checked(&self, package: &str) {
+        self.dist_tags_checked
+            .lock()
+            .unwrap()
+            .insert(package.to_string(), SystemTime::now());
+    }
+
+    /// Stores a package document whose dist-tags were refreshed. The document keeps its
+    /// fetch time for the metadata TTL, its etag no longer matches and is dropped.
+    pub async fn update_dist_tags(
+        &self,
+        package: &str,
+        metadata: &serde_json::Value,
+    ) -> Result<(), std::io::Error> {
+        let cache_path = self.get_metadata_cache_path(package);
+        let fetched_at = fs::metadata(&cache_path)?.modified()?;
+
+        self.hot.remove(package).await;
+        fs::write(&cache_path, serde_json::to_vec(metadata)?)?;
+        fs::File::options()
+            .write(true)
+            .open(&cache_path)?
+            .set_modified(fetched_at)?;
+        let etag_path = self.get_metadata_etag_path(package);
+        if etag_path.exists() {
+            fs::remove_file(etag_path)?;
+        }
+
+        self.mark_dist_tags_checked(package);
+        Ok(())
+    }
+
     pub async fn invalidate_metadata(&self, package: &str) -> Result<(), std::io::Error> {
         if !self.config.cache_enabled {
             return Ok(());
         }
 
+        self.hot.remove(package).await;
+
         let cache_path = self.get_metadata_cache_path(package);
         let etag_path = self.get_metadata_etag_path(package);
 
@@ -717,6 +981,188 @@ impl CacheService {
         Ok(())
     }
 
+    /// The cached files of `package`, or of every package, ordered by package and filename.
+    /// Etag files are reported with the file they belong to.
+    pub fn list_entries(
+        &self,
+        package: Option<&str>,
+    ) -> Result<Vec<CacheEntryInfo>, std::io::Error> {
+        let packages_dir = Path::new(&self.config.cache_dir).join("packages");
+        let packages = match package {
+            Some(package) => vec![package.to_string()],
+            None => Self::cached_packages(&packages_dir)?,
+        };
+
+        let now = SystemTime::now();
+        let mut entries = Vec::new();
+        for package in packages {
+            let package_dir = packages_dir.join(&package);
+            if !package_dir.is_dir() {
+                continue;
+            }
+
+            let mut files: Vec<PathBuf> = fs::read_dir(&package_dir)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .collect();
+            files.sort();
+
+            for path in files {
+                let filename = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let Some((kind, etag_file)) = Self::entry_kind(&filename) else {
+                    continue;
+                };
+                let metadata = fs::metadata(&path)?;
+                let modified = metadata.modified().ok();
+                entries.push(CacheEntryInfo {
+                    package: package.clone(),
+                    kind,
+                    size_bytes: metadata.len(),
+                    cached_at: modified.map(|modified| {
+                        chrono::DateTime::<chrono::Utc>::from(modified).naive_utc()
+                    }),
+                    age_secs: modified
+                        .and_then(|modified| now.duration_since(modified).ok())
+                        .map(|age| age.as_secs()),
+                    etag: etag_file
+                        .and_then(|etag_file| fs::read_to_string(package_dir.join(etag_file)).ok())
+                        .and_then(|meta| Self::parse_tarball_meta(&meta).0),
+                    filename,
+                });
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Names of the packages with a cache directory, scoped ones as `@scope/name`
+    fn cached_packages(packages_dir: &Path) -> Result<Vec<String>, std::io::Error> {
+        let mut names = Vec::new();
+        if !packages_dir.exists() {
+            return Ok(names);
+        }
+
+        let file_name = |path: &Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+        for entry in fs::read_dir(packages_dir)? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let name = file_name(&path);
+            if name.starts_with('@') {
+                for scoped in fs::read_dir(&path)? {
+                    let scoped = scoped?.path();
+                    if scoped.is_dir() {
+                        names.push(format!("{name}/{}", file_name(&scoped)));
+                    }
+                }
+            } else {
+                names.push(name);
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    /// What a file in a package directory holds and the file next to it with its etag.
+    /// `None` for the etag files themselves.
+    fn entry_kind(filename: &str) -> Option<(CacheEntryKind, Option<String>)> {
+        if filename.ends_with(".meta") || filename.ends_with(".etag") {
+            return None;
+        }
+        if filename == "metadata.json" {
+            return Some((CacheEntryKind::Metadata, Some("metadata.etag".to_string())));
+        }
+        if let Some(version) = filename
+            .strip_prefix("version-")
+            .and_then(|rest| rest.strip_suffix(".json"))
+        {
+            return Some((
+                CacheEntryKind::VersionMetadata,
+                Some(format!("version-{version}.etag")),
+            ));
+        }
+        if filename.ends_with(".tgz") {
+            return Some((CacheEntryKind::Tarball, Some(format!("{filename}.meta"))));
+        }
+        if filename.ends_with(".json") {
+            return Some((CacheEntryKind::Manifest, None));
+        }
+        Some((CacheEntryKind::Other, None))
+    }
+
+    fn is_metadata_file(filename: &str) -> bool {
+        matches!(filename, "metadata.json" | "metadata.etag")
+            || (filename.starts_with("version-")
+                && (filename.ends_with(".json") || filename.ends_with(".etag")))
+    }
+
+    /// Drops the cached metadata and tarballs of one package along with their database
+    /// records, so the next request fetches them from upstream again. Tarballs of locally
+    /// published packages are the only copy and are kept.
+    pub async fn invalidate_package(
+        &self,
+        package: &str,
+        database: &DatabaseService,
+    ) -> Result<CacheInvalidationReport, Box<dyn std::error::Error>> {
+        self.hot.remove(package).await;
+
+        let published = database
+            .get_package_by_name(package)?
+            .is_some_and(|pkg| pkg.author_id.is_some());
+        let mut report = CacheInvalidationReport {
+            package: package.to_string(),
+            kept_published_files: published,
+            ..Default::default()
+        };
+
+        let package_dir = Path::new(&self.config.cache_dir)
+            .join("packages")
+            .join(package);
+        if package_dir.is_dir() {
+            for entry in fs::read_dir(&package_dir)? {
+                let path = entry?.path();
+                let filename = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if !path.is_file() || (published && !Self::is_metadata_file(&filename)) {
+                    continue;
+                }
+                report.bytes_freed += fs::metadata(&path)?.len();
+                fs::remove_file(&path)?;
+                report.files_removed += 1;
+            }
+            if !published {
+                let _ = fs::remove_dir(&package_dir);
+            }
+        }
+
+        report.records_removed += database.delete_metadata_cache_entry(package)?;
+        if !published {
+            for (pkg, _, file) in database.list_package_files()? {
+                if pkg.name == package {
+                    report.records_removed += database.delete_package_file(file.id)?;
+                }
+            }
+        }
+
+        info!(
+            "Invalidated cache of {package}: {} files ({} bytes), {} records",
+            report.files_removed, report.bytes_freed, report.records_removed
+        );
+        Ok(report)
+    }
+
     // PERMANENT STORAGE: Packages are never deleted from cache
     // This ensures fast access to all previously downloaded packages
     pub async fn get_cache_info(&self) -> Result<String, std::io::Error> {
@@ -882,6 +1328,172 @@ impl CacheService {
         Ok(())
     }
 
+    /// Finds cache files without a database record and records whose file is gone. With
+    /// `delete`, removes the orphaned files and the records of missing proxied files.
+    pub async fn collect_garbage(
+        &self,
+        database: &DatabaseService,
+        delete: bool,
+    ) -> Result<CacheGcReport, Box<dyn std::error::Error>> {
+        let started = Instant::now();
+        let mut report = CacheGcReport {
+            deleted: delete,
+            ..Default::default()
+        };
+
+        let mut tracked = HashSet::new();
+        for (package, version, file) in database.list_package_files()? {
+            let path = Path::new(&file.file_path);
+            if path.exists() {
+                tracked.insert(Self::normalize_path(path));
+                continue;
+            }
+
+            // A kept record still owns the files next to it, like the package.json
+            let removable = package.author_id.is_none();
+            if !removable {
+                tracked.insert(Self::normalize_path(path));
+            }
+            if delete && removable {
+                report.records_removed += database.delete_package_file(file.id)?;
+            }
+            debug!(
+                "Missing cached file for {}@{}: {}",
+                package.name, version.version, file.file_path
+            );
+            report.missing_files.push(MissingCacheFile {
+                name: package.name,
+                filename: file.filename,
+                path: file.file_path,
+                removable,
+            });
+        }
+
+        for entry in database.list_metadata_cache_entries()? {
+            let path = Path::new(&entry.file_path);
+            if path.exists() {
+                tracked.insert(Self::normalize_path(path));
+                continue;
+            }
+
+            if delete {
+                report.records_removed +=
+                    database.delete_metadata_cache_entry(&entry.package_name)?;
+            }
+            report.missing_files.push(MissingCacheFile {
+                name: entry.package_name,
+                filename: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: entry.file_path,
+                removable: true,
+            });
+        }
+
+        let cache_dir = Path::new(&self.config.cache_dir);
+        let packages_dir = cache_dir.join("packages");
+        let mut entries = Vec::new();
+        if packages_dir.exists() {
+            Self::collect_files(&packages_dir, &mut entries)?;
+        }
+
+        let now = SystemTime::now();
+        for (path, modified, size) in entries {
+            report.scanned_files += 1;
+
+            let recent = now
+                .duration_since(modified)
+                .is_ok_and(|age| age < GC_GRACE_PERIOD);
+            let Some(record_path) = Self::record_path(&path) else {
+                continue;
+            };
+            if recent || tracked.contains(&Self::normalize_path(&record_path)) {
+                continue;
+            }
+
+            if delete {
+                fs::remove_file(&path)?;
+                report.files_removed += 1;
+                // Drops the package and scope directories once they are empty
+                if let Some(parent) = path.parent()
+                    && fs::remove_dir(parent).is_ok()
+                    && parent.parent() != Some(&packages_dir)
+                {
+                    let _ = parent.parent().map(fs::remove_dir);
+                }
+            }
+            report.orphaned_bytes += size;
+            report.orphaned_files.push(
+                path.strip_prefix(cache_dir)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+
+        // Removed files may include metadata documents held in memory
+        if report.files_removed > 0 {
+            self.hot.clear().await;
+        }
+
+        report.duration_ms = started.elapsed().as_millis();
+        info!(
+            "Cache garbage collection: {} files scanned, {} orphaned ({} bytes), {} records without a file, {} files and {} records removed",
+            report.scanned_files,
+            report.orphaned_files.len(),
+            report.orphaned_bytes,
+            report.missing_files.len(),
+            report.files_removed,
+            report.records_removed
+        );
+        Ok(report)
+    }
+
+    /// The file whose database record keeps a cache file: tarballs and metadata are recorded
+    /// themselves, the package.json of a published version and ETag files belong to the file
+    /// next to them. Unknown files are left alone.
+    fn record_path(path: &Path) -> Option<PathBuf> {
+        let filename = path.file_name()?.to_str()?;
+
+        if filename.ends_with(".tgz")
+            || filename == "metadata.json"
+            || (filename.starts_with("version-") && filename.ends_with(".json"))
+        {
+            Some(path.to_path_buf())
+        } else if filename.ends_with(".json") {
+            Some(path.with_extension("tgz"))
+        } else if let Some(tarball) = filename.strip_suffix(".meta") {
+            Some(path.with_file_name(tarball))
+        } else {
+            filename
+                .strip_suffix(".etag")
+                .map(|metadata| path.with_file_name(format!("{metadata}.json")))
+        }
+    }
+
+    fn normalize_path(path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    fn collect_files(
+        dir: &Path,
+        entries: &mut Vec<(PathBuf, SystemTime, u64)>,
+    ) -> Result<(), std::io::Error> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+
+            if metadata.is_dir() {
+                Self::collect_files(&path, entries)?;
+            } else {
+                entries.push((path, metadata.modified()?, metadata.len()));
+            }
+        }
+        Ok(())
+    }
+
     fn extract_package_name_from_path(&self, path: &Path) -> Option<String> {
         // Extract package name from cache path structure
         // Expected structure: cache_dir/packages/package_name/file.tgz or cache_dir/packages/@scope/package_name/file.tgz
@@ -955,28 +1567,85 @@ impl CacheService {
         }
     }
 
-    pub async fn clear(&self) -> Result<(), std::io::Error> {
+    /// Removes everything from the cache except the directories of pinned packages
+    pub async fn clear(&self, pinned: &BTreeSet<String>) -> Result<(), std::io::Error> {
         let cache_dir = Path::new(&self.config.cache_dir);
 
+        self.hot.set_pinned(pinned.clone());
+        self.hot.clear().await;
+
         if !cache_dir.exists() {
             return Ok(());
         }
 
         warn!("CLEARING PERMANENT CACHE - This will remove all cached packages!");
 
+        let packages_dir = cache_dir.join("packages");
+
         // Remove all package directories and their contents
         for entry in fs::read_dir(cache_dir)? {
             let entry = entry?;
             let path = entry.path();
 
-            if path.is_dir() {
+            if path == packages_dir && !pinned.is_empty() {
+                Self::clear_packages(&packages_dir, pinned)?;
+            } else if path.is_dir() {
                 fs::remove_dir_all(path)?;
             } else if path.is_file() {
                 fs::remove_file(path)?;
             }
         }
 
-        info!("Permanent cache cleared - all packages removed");
+        if pinned.is_empty() {
+            info!("Permanent cache cleared - all packages removed");
+        } else {
+            info!(
+                "Permanent cache cleared - kept {} pinned packages",
+                pinned.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn clear_packages(
+        packages_dir: &Path,
+        pinned: &BTreeSet<String>,
+    ) -> Result<(), std::io::Error> {
+        for entry in fs::read_dir(packages_dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if name.starts_with('@') && path.is_dir() {
+                let mut kept = false;
+                for scoped in fs::read_dir(&path)? {
+                    let scoped = scoped?.path();
+                    let scoped_name = scoped
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    if pinned.contains(&format!("{name}/{scoped_name}")) {
+                        kept = true;
+                    } else if scoped.is_dir() {
+                        fs::remove_dir_all(scoped)?;
+                    } else {
+                        fs::remove_file(scoped)?;
+                    }
+                }
+                if !kept {
+                    fs::remove_dir_all(path)?;
+                }
+            } else if pinned.contains(&name) {
+                continue;
+            } else if path.is_dir() {
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -1026,6 +1695,181 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_clear_keeps_pinned_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            cache_dir: dir.path().to_string_lossy().to_string(),
+            ..AppConfig::default()
+        };
+        let cache = CacheService::new(config).unwrap();
+
+        for (package, filename) in [
+            ("lodash", "lodash-4.17.21.tgz"),
+            ("react", "react-18.2.0.tgz"),
+            ("@types/node", "node-20.5.0.tgz"),
+            ("@types/react", "react-18.2.0.tgz"),
+            ("@acme/utils", "utils-1.0.0.tgz"),
+        ] {
+            let path = cache.get_cache_path(package, filename);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"tarball").unwrap();
+        }
+
+        let pinned = BTreeSet::from(["react".to_string(), "@types/node".to_string()]);
+        cache.clear(&pinned).await.unwrap();
+
+        assert!(cache.get_cache_path("react", "react-18.2.0.tgz").exists());
+        assert!(
+            cache
+                .get_cache_path("@types/node", "node-20.5.0.tgz")
+                .exists()
+        );
+        assert!(
+            !cache
+                .get_cache_path("lodash", "lodash-4.17.21.tgz")
+                .exists()
+        );
+        assert!(
+            !cache
+                .get_cache_path("@types/react", "react-18.2.0.tgz")
+                .exists()
+        );
+        assert!(!dir.path().join("packages/@acme").exists());
+
+        cache.clear(&BTreeSet::new()).await.unwrap();
+        assert!(!dir.path().join("packages").exists());
+    }
+
+    #[test]
+    fn test_list_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            cache_dir: dir.path().to_string_lossy().to_string(),
+            ..AppConfig::default()
+        };
+        let cache = CacheService::new(config).unwrap();
+
+        for (package, filename, content) in [
+            ("lodash", "lodash-4.17.21.tgz", "tarball"),
+            ("lodash", "lodash-4.17.21.tgz.meta", "\"abc\""),
+            ("lodash", "metadata.json", "{}"),
+            ("lodash", "metadata.etag", "\"def\""),
+            ("@types/node", "version-20.5.0.json", "{}"),
+        ] {
+            let path = cache.get_cache_path(package, filename);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let entries = cache.list_entries(None).unwrap();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.package.as_str(),
+                    entry.filename.as_str(),
+                    entry.kind,
+                    entry.etag.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "@types/node",
+                    "version-20.5.0.json",
+                    CacheEntryKind::VersionMetadata,
+                    None
+                ),
+                (
+                    "lodash",
+                    "lodash-4.17.21.tgz",
+                    CacheEntryKind::Tarball,
+                    Some("\"abc\"")
+                ),
+                (
+                    "lodash",
+                    "metadata.json",
+                    CacheEntryKind::Metadata,
+                    Some("\"def\"")
+                ),
+            ]
+        );
+        assert_eq!(entries[1].size_bytes, 7);
+
+        assert_eq!(cache.list_entries(Some("lodash")).unwrap().len(), 2);
+        assert!(cache.list_entries(Some("react")).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tarball_last_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            cache_dir: dir.path().to_string_lossy().to_string(),
+            ..AppConfig::default()
+        };
+        let cache = CacheService::new(config).unwrap();
+        let last_modified = "Sat, 26 Oct 1985 08:15:00 GMT";
+
+        cache
+            .put(
+                "lodash",
+                "lodash-4.17.21.tgz",
+                b"tarball",
+                Some("\"abc\""),
+                Some(last_modified),
+                "https://registry.npmjs.org/lodash/-/lodash-4.17.21.tgz",
+                None,
+            )
+            .await
+            .unwrap();
+        let entry = cache
+            .get("lodash", "lodash-4.17.21.tgz", None)
+            .await
+            .unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(entry.last_modified.as_deref(), Some(last_modified));
+        assert_eq!(
+            cache.tarball_last_modified("lodash", "lodash-4.17.21.tgz"),
+            Some(last_modified.to_string())
+        );
+
+        // Upstreams may send only one of the two, older metadata files only have the ETag
+        cache
+            .put(
+                "ms",
+                "ms-2.1.3.tgz",
+                b"tarball",
+                None,
+                Some(last_modified),
+                "",
+                None,
+            )
+            .await
+            .unwrap();
+        let entry = cache.get("ms", "ms-2.1.3.tgz", None).await.unwrap();
+        assert_eq!(entry.etag, None);
+        assert_eq!(entry.last_modified.as_deref(), Some(last_modified));
+
+        fs::write(
+            cache.get_metadata_path("lodash", "lodash-4.17.21.tgz"),
+            "\"def\"",
+        )
+        .unwrap();
+        let entry = cache
+            .get("lodash", "lodash-4.17.21.tgz", None)
+            .await
+            .unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"def\""));
+        assert_eq!(entry.last_modified, None);
+        assert_eq!(
+            cache.tarball_last_modified("left-pad", "left-pad-1.3.0.tgz"),
+            None
+        );
+    }
+
     #[test]
     fn test_extract_package_name_from_path() {
         let mut config = AppConfig::default();
@@ -1059,4 +1903,27 @@ mod tests {
         let path = Path::new("data/packages/file.tgz");
         assert_eq!(cache.extract_package_name_from_path(path), None);
     }
+
+    #[test]
+    fn test_gc_record_path() {
+        let dir = Path::new("data/packages/@acme/utils");
+        let record = |filename: &str| CacheService::record_path(&dir.join(filename));
+
+        assert_eq!(record("utils-1.0.0.tgz"), Some(dir.join("utils-1.0.0.tgz")));
+        assert_eq!(
+            record("utils-1.0.0.json"),
+            Some(dir.join("utils-1.0.0.tgz"))
+        );
+        assert_eq!(
+            record("utils-1.0.0.tgz.meta"),
+            Some(dir.join("utils-1.0.0.tgz"))
+        );
+        assert_eq!(record("metadata.json"), Some(dir.join("metadata.json")));
+        assert_eq!(record("metadata.etag"), Some(dir.join("metadata.json")));
+        assert_eq!(
+            record("version-1.0.0.etag"),
+            Some(dir.join("version-1.0.0.json"))
+        );
+        assert_eq!(record("notes.txt"), None);
+    }
 }
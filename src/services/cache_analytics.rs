@@ -0,0 +1,158 @@
+use crate::error::ApiError;
+use crate::models::{PackageCacheAnalytics, PackageCacheStat, PackageCacheUsage};
+use crate::state::AppState;
+use chrono::Duration;
+use std::collections::BTreeMap;
+
+/// Period used when none is requested
+const DEFAULT_PERIOD: &str = "7d";
+/// Longest period that can be requested, in days
+const MAX_PERIOD_DAYS: i64 = 366;
+/// Packages listed when no package is requested
+const MAX_PACKAGES: usize = 100;
+
+pub struct CacheAnalyticsService;
+
+impl CacheAnalyticsService {
+    /// Hits, misses and bytes served from the cache and fetched upstream per package over
+    /// the last `period` days, today included. Without `package` the packages served most
+    /// from the cache are listed.
+    pub fn package_usage(
+        package: Option<&str>,
+        period: Option<&str>,
+        state: &AppState,
+    ) -> Result<PackageCacheAnalytics, ApiError> {
+        let period = period.unwrap_or(DEFAULT_PERIOD);
+        let days = Self::parse_period(period)?;
+        let to = chrono::Utc::now().date_naive();
+        let from = to - Duration::days(days - 1);
+
+        let stats = state
+            .database
+            .get_package_cache_stats(package, from, to)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let mut packages = Self::summarize(stats);
+        match package {
+            Some(package) if packages.is_empty() => packages.push(PackageCacheUsage {
+                package: package.to_string(),
+                ..Default::default()
+            }),
+            _ => packages.truncate(MAX_PACKAGES),
+        }
+
+        Ok(PackageCacheAnalytics {
+            period: period.to_string(),
+            from,
+            to,
+            packages,
+        })
+    }
+
+    /// Days of a period like `7d` or `4w`
+    pub fn parse_period(period: &str) -> Result<i64, ApiError> {
+        let invalid = || {
+            ApiError::BadRequest(format!(
+                "Invalid period '{period}', expected days like '7d' or weeks like '4w', up to {MAX_PERIOD_DAYS} days"
+            ))
+        };
+
+        let days = if let Some(count) = period.strip_suffix('d') {
+            count.parse::<i64>().map_err(|_| invalid())?
+        } else if let Some(count) = period.strip_suffix('w') {
+            count
+                .parse::<i64>()
+                .map_err(|_| invalid())?
+                .saturating_mul(7)
+        } else {
+            return Err(invalid());
+        };
+
+        if (1..=MAX_PERIOD_DAYS).contains(&days) {
+            Ok(days)
+        } else {
+            Err(invalid())
+        }
+    }
+
+    /// Adds up daily stats per package, packages served most from the cache first
+    fn summarize(stats: Vec<PackageCacheStat>) -> Vec<PackageCacheUsage> {
+        let mut totals: BTreeMap<String, PackageCacheUsage> = BTreeMap::new();
+        for stat in stats {
+            let usage =
+                totals
+                    .entry(stat.package_name.clone())
+                    .or_insert_with(|| PackageCacheUsage {
+                        package: stat.package_name,
+                        ..Default::default()
+                    });
+            usage.hits += stat.hits;
+            usage.misses += stat.misses;
+            usage.cache_bytes += stat.cache_bytes;
+            usage.upstream_bytes += stat.upstream_bytes;
+        }
+
+        let mut packages: Vec<PackageCacheUsage> = totals
+            .into_values()
+            .map(|mut usage| {
+                let lookups = usage.hits + usage.misses;
+                if lookups > 0 {
+                    usage.hit_rate = usage.hits as f64 / lookups as f64 * 100.0;
+                }
+                usage.bytes_saved = usage.cache_bytes;
+                usage
+            })
+            .collect();
+        packages.sort_by_key(|usage| std::cmp::Reverse(usage.cache_bytes));
+        packages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn stat(package: &str, day: u32, hits: i64, misses: i64, cache_bytes: i64) -> PackageCacheStat {
+        PackageCacheStat {
+            id: 0,
+            package_name: package.to_string(),
+            day: NaiveDate::from_ymd_opt(2025, 8, day).unwrap(),
+            hits,
+            misses,
+            cache_bytes,
+            upstream_bytes: misses * 10,
+        }
+    }
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(CacheAnalyticsService::parse_period("7d").unwrap(), 7);
+        assert_eq!(CacheAnalyticsService::parse_period("4w").unwrap(), 28);
+        for invalid in ["", "d", "0d", "7", "7h", "-1d", "400d", "7é"] {
+            assert!(
+                CacheAnalyticsService::parse_period(invalid).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let packages = CacheAnalyticsService::summarize(vec![
+            stat("left-pad", 1, 1, 1, 10),
+            stat("lodash", 1, 2, 1, 200),
+            stat("lodash", 2, 1, 0, 100),
+        ]);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].package, "lodash");
+        assert_eq!((packages[0].hits, packages[0].misses), (3, 1));
+        assert_eq!(packages[0].hit_rate, 75.0);
+        assert_eq!(packages[0].cache_bytes, 300);
+        assert_eq!(packages[0].upstream_bytes, 10);
+        assert_eq!(packages[0].bytes_saved, 300);
+        assert_eq!(packages[1].package, "left-pad");
+        assert_eq!(packages[1].hit_rate, 50.0);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::error::ApiError;
+use crate::fairings::RequestId;
+use crate::services::RegistryService;
+use crate::state::AppState;
+use log::{debug, info, warn};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Changes read from the feed at once, a full batch is followed by the next one right away
+const CHANGES_BATCH: usize = 1000;
+
+/// Package names and the sequence to continue from in a page of a `_changes` feed
+#[derive(Debug, Default, PartialEq)]
+struct ChangesPage {
+    packages: BTreeSet<String>,
+    changes: usize,
+    last_seq: Option<String>,
+}
+
+/// Where the upstream changes feed is followed from, kept between runs of the job
+#[derive(Debug, Default)]
+pub struct ChangesFeedCursor {
+    since: Mutex<Option<String>>,
+}
+
+impl ChangesFeedCursor {
+    fn since(&self) -> Option<String> {
+        self.since.lock().unwrap().clone()
+    }
+
+    fn set(&self, seq: String) {
+        *self.since.lock().unwrap() = Some(seq);
+    }
+}
+
+pub struct ChangesFeedService;
+
+impl ChangesFeedService {
+    /// Reads the upstream changes feed from where the last run stopped, expiring or
+    /// refreshing the cached packages that changed. The first run after the server starts
+    /// only finds where the feed ends, packages that changed while it was down expire with
+    /// their TTL.
+    pub async fn follow(state: &AppState) -> Result<String, ApiError> {
+        let Some(url) = state.config.upstream_changes_url.as_deref() else {
+            return Ok("Upstream changes feed is disabled".to_string());
+        };
+
+        let Some(mut since) = state.changes_feed.since() else {
+            let seq = Self::current_seq(url, state).await?;
+            info!("Following upstream changes feed from sequence {seq}");
+            state.changes_feed.set(seq.clone());
+            return Ok(format!("Following the changes feed from sequence {seq}"));
+        };
+
+        // Full batches are followed by the next one right away, until the feed is caught up
+        let (mut changes, mut updated) = (0, 0);
+        loop {
+            let (page, page_updated) = Self::sync(url, &since, state).await?;
+            changes += page.changes;
+            updated += page_updated;
+            if let Some(seq) = page.last_seq {
+                state.changes_feed.set(seq.clone());
+                since = seq;
+            }
+            if page.changes < CHANGES_BATCH {
+                break;
+            }
+        }
+
+        Ok(format!(
+            "Read {changes} changes, {} {updated} cached packages",
+            if state.config.upstream_changes_refresh {
+                "refreshed"
+            } else {
+                "expired"
+            }
+        ))
+    }
+
+    /// Sequence of the latest change
+    async fn current_seq(url: &str, state: &AppState) -> Result<String, ApiError> {
+        let page = Self::fetch(url, &[("descending", "true"), ("limit", "1")], state).await?;
+        page.last_seq.ok_or_else(|| {
+            ApiError::ParseError("Upstream changes feed has no last_seq".to_string())
+        })
+    }
+
+    /// Reads the changes after `since` and expires or refreshes the changed packages that
+    /// are cached, returning the page and how many packages were
+    async fn sync(
+        url: &str,
+        since: &str,
+        state: &AppState,
+    ) -> Result<(ChangesPage, usize), ApiError> {
+        let limit = CHANGES_BATCH.to_string();
+        let page = Self::fetch(url, &[("since", since), ("limit", &limit)], state).await?;
+
+        let mut updated = 0;
+        for package in &page.packages {
+            if Self::is_mirrored(package, state) {
+                Self::expire(package, state).await;
+                updated += 1;
+            }
+        }
+        if updated > 0 {
+            info!(
+                "Upstream changes feed: {updated} of {} changed packages are cached and were {}",
+                page.packages.len(),
+                if state.config.upstream_changes_refresh {
+                    "refreshed"
+                } else {
+                    "expired"
+                }
+            );
+        } else {
+            debug!(
+                "Upstream changes feed: {} changes, none cached",
+                page.changes
+            );
+        }
+        Ok((page, updated))
+    }
+
+    async fn fetch(
+        url: &str,
+        query: &[(&str, &str)],
+        state: &AppState,
+    ) -> Result<ChangesPage, ApiError> {
+        let _permit = state.upstream_limiter.acquire(url).await?;
+        let response = RequestId::forward(state.client.get(url).query(query))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(ApiError::UpstreamError(format!(
+                "Upstream changes feed returned {}",
+                response.status()
+            )));
+        }
+        let body: Value = response.json().await.map_err(|e| {
+            ApiError::ParseError(format!("Invalid upstream changes feed response: {e}"))
+        })?;
+        Ok(Self::parse_page(&body))
+    }
+
+    fn parse_page(body: &Value) -> ChangesPage {
+        let results = body
+            .get("results")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let packages = results
+            .iter()
+            .filter_map(|change| change.get("id").and_then(Value::as_str))
+            .filter(|id| !id.starts_with('_'))
+            .map(str::to_string)
+            .collect();
+
+        // CouchDB sequences are numbers or opaque strings
+        let last_seq = body.get("last_seq").and_then(|seq| match seq {
+            Value::String(seq) => Some(seq.clone()),
+            Value::Number(seq) => Some(seq.to_string()),
+            _ => None,
+        });
+
+        ChangesPage {
+            packages,
+            changes: results.len(),
+            last_seq,
+        }
+    }
+
+    /// Whether the package has an upstream document in the cache. Packages published here
+    /// are left alone even when a package of the same name changes upstream.
+    fn is_mirrored(package: &str, state: &AppState) -> bool {
+        state.cache.metadata_age(package).is_some()
+            && state
+                .database
+                .get_package_by_name(package)
+                .ok()
+                .flatten()
+                .is_none_or(|pkg| pkg.author_id.is_none())
+    }
+
+    /// Expires the cached document so the next read revalidates upstream, keeping it as a
+    /// stale copy. With refreshing enabled the new document is fetched right away.
+    async fn expire(package: &str, state: &AppState) {
+        if state.config.upstream_changes_refresh {
+            if let Err(e) = RegistryService::refresh_package_metadata(package, state).await {
+                warn!("Failed to refresh {package} after it changed upstream: {e}");
+            }
+        } else if let Err(e) = state
+            .cache
+            .set_metadata_fetched_at(package, UNIX_EPOCH)
+            .await
+        {
+            warn!("Failed to expire cached metadata of {package}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_page() {
+        let page = ChangesFeedService::parse_page(&json!({
+            "results": [
+                { "seq": 101, "id": "lodash", "changes": [{ "rev": "2-a" }] },
+                { "seq": 102, "id": "_design/app", "changes": [{ "rev": "1-b" }] },
+                { "seq": 103, "id": "@acme/ui", "changes": [{ "rev": "5-c" }], "deleted": true },
+                { "seq": 104, "id": "lodash", "changes": [{ "rev": "3-d" }] }
+            ],
+            "last_seq": 104
+        }));
+        assert_eq!(
+            page.packages.into_iter().collect::<Vec<_>>(),
+            ["@acme/ui", "lodash"]
+        );
+        assert_eq!(page.changes, 4);
+        assert_eq!(page.last_seq.as_deref(), Some("104"));
+
+        let page = ChangesFeedService::parse_page(&json!({
+            "results": [],
+            "last_seq": "42-g1AAAAB"
+        }));
+        assert!(page.packages.is_empty());
+        assert_eq!(page.last_seq.as_deref(), Some("42-g1AAAAB"));
+
+        assert_eq!(
+            ChangesFeedService::parse_page(&json!({})),
+            ChangesPage::default()
+        );
+    }
+}
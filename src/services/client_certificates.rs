@@ -0,0 +1,376 @@
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use crate::models::{ClientCertificate, ClientCertificateRequest, NewClientCertificate, User};
+use crate::services::DatabaseService;
+use base64::prelude::*;
+use log::debug;
+use rocket::Request;
+use rocket::http::{HeaderMap, RawStr};
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+
+/// Certificate use is recorded at most this often, like token use
+const USAGE_RESOLUTION_SECS: i64 = 60;
+
+/// A client certificate a trusted proxy verified and forwarded
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresentedCertificate {
+    pub fingerprint: Option<String>,
+    pub subject: Option<String>,
+    pub sans: Vec<String>,
+}
+
+/// Authentication by mutual TLS. TLS is terminated by a proxy in front of the registry,
+/// which verifies client certificates against its CA and forwards the certificate and its
+/// subject in headers. Those headers are only believed with `trust_proxy_headers` on and
+/// from `mtls_trusted_proxies`, which has to be configured explicitly. A certificate
+/// maps to a user or service account by its fingerprint, its subject or one of its SANs.
+pub struct ClientCertificateService;
+
+impl ClientCertificateService {
+    pub fn is_enabled(config: &AppConfig) -> bool {
+        config.mtls_mode != "off"
+    }
+
+    /// Whether bearer tokens are refused
+    pub fn is_required(config: &AppConfig) -> bool {
+        config.mtls_mode == "required"
+    }
+
+    /// The certificate forwarded with a request, if it came through a trusted proxy
+    pub fn presented(request: &Request<'_>, config: &AppConfig) -> Option<PresentedCertificate> {
+        Self::forwarded(request.remote()?.ip(), request.headers(), config)
+    }
+
+    fn forwarded(
+        peer: IpAddr,
+        headers: &HeaderMap<'_>,
+        config: &AppConfig,
+    ) -> Option<PresentedCertificate> {
+        if !Self::is_enabled(config) || !config.trust_proxy_headers {
+            return None;
+        }
+        let peer = peer.to_canonical();
+        if !config
+            .mtls_trusted_proxies
+            .iter()
+            .any(|net| net.contains(&peer))
+        {
+            return None;
+        }
+
+        let fingerprint = headers
+            .get_one(&config.mtls_cert_header)
+            .and_then(Self::fingerprint);
+        let subject = headers
+            .get_one(&config.mtls_subject_header)
+            .map(str::trim)
+            .filter(|subject| !subject.is_empty())
+            .map(str::to_string);
+        // Only a header the proxy is configured to overwrite, a client could add it otherwise
+        let sans = config
+            .mtls_san_header
+            .as_deref()
+            .and_then(|header| headers.get_one(header))
+            .map(Self::sans)
+            .unwrap_or_default();
+
+        (fingerprint.is_some() || subject.is_some() || !sans.is_empty()).then_some(
+            PresentedCertificate {
+                fingerprint,
+                subject,
+                sans,
+            },
+        )
+    }
+
+    /// The active user a certificate belongs to
+    pub fn authenticate(
+        db: &DatabaseService,
+        certificate: &PresentedCertificate,
+    ) -> Result<User, ApiError> {
+        let unknown = || ApiError::Unauthorized("Unknown client certificate".to_string());
+
+        let database_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        let mut registered = db
+            .find_client_certificate(
+                certificate.fingerprint.as_deref(),
+                certificate.subject.as_deref(),
+            )
+            .map_err(database_error)?;
+        for san in &certificate.sans {
+            if registered.is_some() {
+                break;
+            }
+            registered = db
+                .find_client_certificate(None, Some(san))
+                .map_err(database_error)?;
+        }
+        let registered = registered.ok_or_else(unknown)?;
+        let user = db
+            .get_user_by_id(registered.user_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(unknown)?;
+
+        let now = chrono::Utc::now().naive_utc();
+        let stale_before = now - chrono::Duration::seconds(USAGE_RESOLUTION_SECS);
+        if let Err(e) = db.touch_client_certificate(registered.id, now, stale_before) {
+            debug!("Failed to record client certificate usage: {e}");
+        }
+
+        Ok(user)
+    }
+
+    pub fn list(db: &DatabaseService, user_id: i32) -> Result<Vec<ClientCertificate>, ApiError> {
+        db.list_client_certificates(user_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))
+    }
+
+    pub fn add(
+        db: &DatabaseService,
+        user_id: i32,
+        request: ClientCertificateRequest,
+    ) -> Result<ClientCertificate, ApiError> {
+        let subject = request
+            .subject
+            .map(|subject| subject.trim().to_string())
+            .filter(|subject| !subject.is_empty());
+        let fingerprint = match request.fingerprint {
+            Some(fingerprint) => {
+                Some(Self::normalize_fingerprint(&fingerprint).ok_or_else(|| {
+                    ApiError::BadRequest(format!(
+                        "Invalid fingerprint '{fingerprint}', expected a SHA-256 fingerprint in hex"
+                    ))
+                })?)
+            }
+            None => None,
+        };
+        if fingerprint.is_some() == subject.is_some() {
+            return Err(ApiError::BadRequest(
+                "Exactly one of fingerprint and subject is required".to_string(),
+            ));
+        }
+
+        db.create_client_certificate(&NewClientCertificate {
+            user_id,
+            fingerprint,
+            subject,
+            description: request.description.filter(|d| !d.trim().is_empty()),
+        })
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => ApiError::Conflict("Certificate is already registered".to_string()),
+            _ => ApiError::InternalServerError(format!("Database error: {e}")),
+        })
+    }
+
+    pub fn remove(db: &DatabaseService, user_id: i32, id: i32) -> Result<(), ApiError> {
+        let deleted = db
+            .delete_client_certificate(user_id, id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if deleted == 0 {
+            return Err(ApiError::NotFound(format!("Certificate {id} not found")));
+        }
+        Ok(())
+    }
+
+    /// Subject alternative names of a forwarded certificate, listed separated by commas
+    fn sans(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|san| !san.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Fingerprint of a forwarded certificate. Proxies send either the PEM, usually URL
+    /// encoded as in nginx's `$ssl_client_escaped_cert`, or the fingerprint itself.
+    fn fingerprint(value: &str) -> Option<String> {
+        let value = RawStr::new(value).percent_decode_lossy();
+
+        let Some((_, rest)) = value.split_once("-----BEGIN CERTIFICATE-----") else {
+            return Self::normalize_fingerprint(&value);
+        };
+        let (body, _) = rest.split_once("-----END CERTIFICATE-----")?;
+        let body: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+        let der = BASE64_STANDARD.decode(body).ok()?;
+
+        Some(
+            Sha256::digest(der)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        )
+    }
+
+    /// Lowercase hex without separators, `None` unless it's a SHA-256 fingerprint
+    fn normalize_fingerprint(fingerprint: &str) -> Option<String> {
+        let hex: String = fingerprint
+            .trim()
+            .chars()
+            .filter(|c| *c != ':')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        (hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit())).then_some(hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RegisterRequest;
+    use crate::services::AuthService;
+
+    #[test]
+    fn test_authenticate_by_san() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+        let user = AuthService::register_user(
+            &database,
+            RegisterRequest {
+                name: "ci".to_string(),
+                email: "ci@example.com".to_string(),
+                password: "runner-password".to_string(),
+                invite_token: None,
+            },
+        )
+        .unwrap();
+        ClientCertificateService::add(
+            &database,
+            user.id,
+            ClientCertificateRequest {
+                fingerprint: None,
+                subject: Some("spiffe://example.org/ci/runner".to_string()),
+                description: None,
+            },
+        )
+        .unwrap();
+
+        let presented = |sans: &str| PresentedCertificate {
+            fingerprint: None,
+            subject: Some("CN=runner,O=Example".to_string()),
+            sans: ClientCertificateService::sans(sans),
+        };
+        let authenticated = ClientCertificateService::authenticate(
+            &database,
+            &presented("DNS:runner.example.org, spiffe://example.org/ci/runner"),
+        )
+        .unwrap();
+        assert_eq!(authenticated.id, user.id);
+
+        assert!(matches!(
+            ClientCertificateService::authenticate(&database, &presented("DNS:other.example.org")),
+            Err(ApiError::Unauthorized(_))
+        ));
+        assert_eq!(ClientCertificateService::sans(" , "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_forwarded_headers_are_only_believed_from_configured_proxies() {
+        use crate::config::parse_networks;
+        use rocket::http::Header;
+
+        let mut headers = HeaderMap::new();
+        headers.add(Header::new("X-Client-Cert-Subject", "CN=runner"));
+        headers.add(Header::new(
+            "X-Client-Cert-SAN",
+            "spiffe://example.org/ci/runner",
+        ));
+        let proxy: IpAddr = "10.0.0.5".parse().unwrap();
+        let config = AppConfig {
+            mtls_mode: "optional".to_string(),
+            trust_proxy_headers: true,
+            mtls_trusted_proxies: parse_networks("10.0.0.5"),
+            ..AppConfig::default()
+        };
+
+        // Without a configured SAN header a SAN sent along isn't believed
+        assert_eq!(
+            ClientCertificateService::forwarded(proxy, &headers, &config),
+            Some(PresentedCertificate {
+                fingerprint: None,
+                subject: Some("CN=runner".to_string()),
+                sans: Vec::new(),
+            })
+        );
+        let san_header = AppConfig {
+            mtls_san_header: Some("X-Client-Cert-SAN".to_string()),
+            ..config.clone()
+        };
+        assert_eq!(
+            ClientCertificateService::forwarded(proxy, &headers, &san_header)
+                .unwrap()
+                .sans,
+            vec!["spiffe://example.org/ci/runner".to_string()]
+        );
+
+        // Clients connecting directly, from the LAN or not, can't set any of the headers
+        let lan_client: IpAddr = "192.168.1.20".parse().unwrap();
+        assert_eq!(
+            ClientCertificateService::forwarded(lan_client, &headers, &san_header),
+            None
+        );
+        let default_proxies = AppConfig {
+            mtls_trusted_proxies: Vec::new(),
+            ..san_header.clone()
+        };
+        assert_eq!(
+            ClientCertificateService::forwarded(proxy, &headers, &default_proxies),
+            None
+        );
+        let untrusted = AppConfig {
+            trust_proxy_headers: false,
+            ..san_header
+        };
+        assert_eq!(
+            ClientCertificateService::forwarded(proxy, &headers, &untrusted),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let der = b"not really a certificate";
+        let expected: String = Sha256::digest(der)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let pem = format!(
+            "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+            BASE64_STANDARD.encode(der)
+        );
+
+        assert_eq!(
+            ClientCertificateService::fingerprint(&pem),
+            Some(expected.clone())
+        );
+        let escaped = pem.replace('\n', "%0A").replace(' ', "%20");
+        assert_eq!(
+            ClientCertificateService::fingerprint(&escaped),
+            Some(expected.clone())
+        );
+
+        let colons = expected
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| std::str::from_utf8(pair).unwrap().to_uppercase())
+            .collect::<Vec<_>>()
+            .join(":");
+        assert_eq!(
+            ClientCertificateService::fingerprint(&colons),
+            Some(expected)
+        );
+
+        assert_eq!(ClientCertificateService::fingerprint("abc123"), None);
+        assert_eq!(
+            ClientCertificateService::fingerprint("-----BEGIN CERTIFICATE-----\n!!!"),
+            None
+        );
+    }
+}
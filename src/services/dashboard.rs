@@ -0,0 +1,108 @@
+use crate::config::redact_url_credentials;
+use crate::error::ApiError;
+use crate::models::{CacheSummary, DashboardSummary, UpstreamStatus};
+use crate::services::FeedService;
+use crate::state::AppState;
+use chrono::Utc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// A summary is served this long before it is put together again
+pub const DASHBOARD_CACHE_SECS: u64 = 30;
+
+/// The upstream registry counts as unreachable when its ping takes longer
+const UPSTREAM_PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+const TOP_PACKAGES: i64 = 5;
+const RECENT_PUBLISHES: i64 = 10;
+
+/// The last dashboard summary. Requests arriving while it is put together wait for it
+/// instead of running the same queries.
+#[derive(Debug, Default)]
+pub struct DashboardCache {
+    summary: Mutex<Option<(Instant, DashboardSummary)>>,
+}
+
+pub struct DashboardService;
+
+impl DashboardService {
+    /// Totals, cache usage, top packages, recent publishes and upstream status in one
+    /// response, cached for `DASHBOARD_CACHE_SECS`
+    pub async fn summary(state: &AppState) -> Result<DashboardSummary, ApiError> {
+        let mut cached = state.dashboard.summary.lock().await;
+        if let Some((at, summary)) = cached.as_ref()
+            && at.elapsed() < Duration::from_secs(DASHBOARD_CACHE_SECS)
+        {
+            return Ok(summary.clone());
+        }
+
+        let summary = Self::build(state).await?;
+        *cached = Some((Instant::now(), summary.clone()));
+        Ok(summary)
+    }
+
+    async fn build(state: &AppState) -> Result<DashboardSummary, ApiError> {
+        let database_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        let totals = state
+            .database
+            .get_registry_totals()
+            .map_err(database_error)?;
+        let (_, size_bytes) = state.database.get_cache_stats().map_err(database_error)?;
+        let metadata = state
+            .database
+            .get_metadata_cache_stats()
+            .map_err(database_error)?;
+        // The summary is shared by every reader, anonymous ones included
+        let top_packages = state
+            .database
+            .get_public_popular_packages(TOP_PACKAGES)
+            .map_err(database_error)?;
+        let recent_publishes =
+            FeedService::recent(Some(RECENT_PUBLISHES), Some("published"), state)?;
+
+        Ok(DashboardSummary {
+            totals,
+            cache: CacheSummary {
+                enabled: state.config.cache_enabled,
+                size_bytes,
+                hit_rate: state.cache.get_hit_rate(),
+                metadata_entries: metadata.total_entries,
+                metadata_size_bytes: metadata.total_size_bytes,
+            },
+            top_packages,
+            recent_publishes,
+            upstream: Self::ping_upstream(state).await,
+            generated_at: Utc::now().naive_utc(),
+        })
+    }
+
+    /// Pings the upstream registry with npm's `/-/ping`
+    async fn ping_upstream(state: &AppState) -> UpstreamStatus {
+        let url = format!("{}/-/ping", state.upstream_registry().trim_end_matches('/'));
+        let started = Instant::now();
+        let response = state
+            .client
+            .get(&url)
+            .timeout(UPSTREAM_PING_TIMEOUT)
+            .send()
+            .await;
+
+        let mut status = UpstreamStatus {
+            url: redact_url_credentials(&state.upstream_registry()),
+            reachable: false,
+            status: None,
+            latency_ms: None,
+            error: None,
+        };
+        match response {
+            Ok(response) => {
+                status.reachable = response.status().is_success();
+                status.status = Some(response.status().as_u16());
+                status.latency_ms = Some(started.elapsed().as_millis() as u64);
+            }
+            Err(e) => status.error = Some(e.to_string()),
+        }
+        status
+    }
+}
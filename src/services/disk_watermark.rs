@@ -0,0 +1,275 @@
+use crate::error::ApiError;
+use crate::models::{DiskEvictionReport, DiskEvictionStats, DiskUsage, RegistryEvent};
+use crate::services::PinnedPackageService;
+use crate::state::AppState;
+use chrono::{NaiveDateTime, Utc};
+use log::{info, warn};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// A cached upstream tarball that may be removed to free space
+#[derive(Debug, Clone)]
+struct EvictionCandidate {
+    file_id: i32,
+    package: String,
+    path: String,
+    size_bytes: u64,
+    access_count: i32,
+    last_accessed: NaiveDateTime,
+}
+
+impl EvictionCandidate {
+    /// Higher scores are evicted first: large files that haven't been read for a long time
+    /// and were rarely downloaded at all
+    fn score(&self, now: NaiveDateTime) -> f64 {
+        let idle_hours = (now - self.last_accessed).num_seconds().max(0) as f64 / 3600.0;
+        self.size_bytes as f64 * (idle_hours + 1.0) / (self.access_count.max(0) as f64 + 1.0)
+    }
+}
+
+/// Watermark evictions since startup
+#[derive(Debug, Default)]
+pub struct EvictionCounters {
+    runs: AtomicU64,
+    files: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl EvictionCounters {
+    pub fn record(&self, report: &DiskEvictionReport) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        self.files
+            .fetch_add(report.files_removed as u64, Ordering::Relaxed);
+        self.bytes.fetch_add(report.bytes_freed, Ordering::Relaxed);
+    }
+}
+
+/// Keeps the cache volume below the high watermark by removing cached upstream tarballs.
+/// Tarballs of published and pinned packages are never removed.
+pub struct DiskWatermarkService;
+
+impl DiskWatermarkService {
+    /// Space on the filesystem holding `path`
+    #[cfg(unix)]
+    pub fn usage(path: &Path) -> Option<DiskUsage> {
+        let stat = rustix::fs::statvfs(path).ok()?;
+        Some(DiskUsage {
+            total_bytes: stat.f_blocks * stat.f_frsize,
+            free_bytes: stat.f_bfree * stat.f_frsize,
+            available_bytes: stat.f_bavail * stat.f_frsize,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn usage(_path: &Path) -> Option<DiskUsage> {
+        None
+    }
+
+    pub fn stats(state: &AppState) -> DiskEvictionStats {
+        let counters = state.cache.disk_evictions();
+        DiskEvictionStats {
+            enabled: state.config.cache_enabled && state.config.cache_high_watermark_percent > 0,
+            high_watermark_percent: state.config.cache_high_watermark_percent,
+            low_watermark_percent: state.config.cache_low_watermark_percent,
+            usage: Self::usage(Path::new(&state.config.cache_dir)),
+            runs: counters.runs.load(Ordering::Relaxed),
+            files_evicted: counters.files.load(Ordering::Relaxed),
+            bytes_evicted: counters.bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Evicts tarballs when the cache volume is at or above the high watermark
+    pub fn check(state: &AppState) -> Result<Option<DiskEvictionReport>, ApiError> {
+        let high = state.config.cache_high_watermark_percent;
+        if high == 0 {
+            return Ok(None);
+        }
+        let Some(usage) = Self::usage(Path::new(&state.config.cache_dir)) else {
+            return Ok(None);
+        };
+        if usage.used_percent() < high as f64 {
+            return Ok(None);
+        }
+
+        warn!(
+            "Cache volume is {:.1}% full, evicting cached tarballs down to {}%",
+            usage.used_percent(),
+            state.config.cache_low_watermark_percent
+        );
+        Self::evict(state, usage).map(Some)
+    }
+
+    fn evict(state: &AppState, usage: DiskUsage) -> Result<DiskEvictionReport, ApiError> {
+        let started = Instant::now();
+        let bytes_to_free = usage.bytes_above(state.config.cache_low_watermark_percent);
+        let mut report = DiskEvictionReport {
+            used_percent_before: usage.used_percent(),
+            ..Default::default()
+        };
+
+        // Reads are counted in memory first, recent ones should make a tarball more valuable
+        if let Err(e) = state.database.flush_counters() {
+            warn!("Failed to write queued counters before eviction: {e}");
+        }
+
+        let pinned = PinnedPackageService::names(state)?;
+        let candidates = state
+            .database
+            .list_package_files()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .into_iter()
+            .filter(|(package, _, _)| {
+                package.author_id.is_none() && !pinned.contains(&package.name)
+            })
+            .map(|(package, _, file)| EvictionCandidate {
+                file_id: file.id,
+                package: package.name,
+                path: file.file_path,
+                size_bytes: file.size_bytes.max(0) as u64,
+                access_count: file.access_count,
+                last_accessed: file.last_accessed,
+            })
+            .collect();
+
+        let mut packages = BTreeSet::new();
+        for candidate in Self::select(candidates, bytes_to_free, Utc::now().naive_utc()) {
+            let path = Path::new(&candidate.path);
+            if let Err(e) = fs::remove_file(path)
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                warn!("Failed to evict {}: {e}", candidate.path);
+                continue;
+            }
+            let _ = fs::remove_file(format!("{}.meta", candidate.path));
+            state
+                .database
+                .delete_package_file(candidate.file_id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+            report.files_removed += 1;
+            report.bytes_freed += candidate.size_bytes;
+            packages.insert(candidate.package);
+        }
+        report.packages = packages.into_iter().collect();
+        report.used_percent_after = Self::usage(Path::new(&state.config.cache_dir))
+            .map_or(report.used_percent_before, |usage| usage.used_percent());
+        report.duration_ms = started.elapsed().as_millis();
+
+        state.cache.disk_evictions().record(&report);
+        // Nothing left to evict is reported by the metrics, not on every check
+        if report.files_removed > 0 {
+            state.events.emit(RegistryEvent::CacheEvicted {
+                files: report.files_removed,
+                bytes_freed: report.bytes_freed,
+                used_percent: report.used_percent_after,
+                high_watermark_percent: state.config.cache_high_watermark_percent,
+                low_watermark_percent: state.config.cache_low_watermark_percent,
+            });
+        }
+        info!(
+            "Evicted {} cached tarballs ({} bytes) of {} packages, cache volume at {:.1}% in {} ms",
+            report.files_removed,
+            report.bytes_freed,
+            report.packages.len(),
+            report.used_percent_after,
+            report.duration_ms
+        );
+        Ok(report)
+    }
+
+    /// The least valuable candidates that together free at least `bytes_to_free`
+    fn select(
+        mut candidates: Vec<EvictionCandidate>,
+        bytes_to_free: u64,
+        now: NaiveDateTime,
+    ) -> Vec<EvictionCandidate> {
+        candidates.sort_by(|a, b| b.score(now).total_cmp(&a.score(now)));
+
+        let mut selected_bytes = 0;
+        candidates
+            .into_iter()
+            .take_while(|candidate| {
+                let needed = selected_bytes < bytes_to_free;
+                selected_bytes += candidate.size_bytes;
+                needed
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(
+        file_id: i32,
+        size_bytes: u64,
+        access_count: i32,
+        idle_hours: i64,
+        now: NaiveDateTime,
+    ) -> EvictionCandidate {
+        EvictionCandidate {
+            file_id,
+            package: format!("pkg-{file_id}"),
+            path: format!("/cache/packages/pkg-{file_id}/pkg-{file_id}-1.0.0.tgz"),
+            size_bytes,
+            access_count,
+            last_accessed: now - chrono::Duration::hours(idle_hours),
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let now = Utc::now().naive_utc();
+        let candidates = vec![
+            // Large, but downloaded all the time and read just now
+            candidate(1, 50_000, 1000, 0, now),
+            // Small and read a week ago
+            candidate(2, 1_000, 1, 24 * 7, now),
+            // Large, read once a month ago
+            candidate(3, 40_000, 1, 24 * 30, now),
+            // Medium, read once a week ago
+            candidate(4, 10_000, 0, 24 * 7, now),
+        ];
+
+        let ids = |selected: Vec<EvictionCandidate>| -> Vec<i32> {
+            selected.iter().map(|c| c.file_id).collect()
+        };
+        assert_eq!(
+            ids(DiskWatermarkService::select(candidates.clone(), 0, now)),
+            Vec::<i32>::new()
+        );
+        assert_eq!(
+            ids(DiskWatermarkService::select(candidates.clone(), 1, now)),
+            vec![3]
+        );
+        assert_eq!(
+            ids(DiskWatermarkService::select(
+                candidates.clone(),
+                45_000,
+                now
+            )),
+            vec![3, 4]
+        );
+        assert_eq!(
+            ids(DiskWatermarkService::select(candidates, 1_000_000, now)),
+            vec![3, 4, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        let usage = DiskUsage {
+            total_bytes: 1000,
+            free_bytes: 150,
+            available_bytes: 100,
+        };
+        assert_eq!(usage.used_bytes(), 850);
+        assert!((usage.used_percent() - 850.0 / 950.0 * 100.0).abs() < 1e-9);
+        assert_eq!(usage.bytes_above(80), 90);
+        assert_eq!(usage.bytes_above(90), 0);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::error::ApiError;
+use crate::models::{DanglingRow, DoctorReport};
+use crate::services::StorageService;
+use crate::state::AppState;
+use log::info;
+use std::time::Instant;
+
+pub struct DoctorService;
+
+impl DoctorService {
+    /// Runs every consistency check without changing anything: stored files against their
+    /// records and checksums, cache files against the database, and rows left without the
+    /// rows they belong to
+    pub async fn run(state: &AppState) -> Result<DoctorReport, ApiError> {
+        let started = Instant::now();
+        let checked_at = chrono::Utc::now().naive_utc();
+
+        let storage = StorageService::verify(&state.database)?;
+        let cache = state
+            .cache
+            .collect_garbage(&state.database, false)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Cache check failed: {e}")))?;
+        let dangling_rows = Self::dangling_rows(state)?;
+
+        let ok = storage.problems.is_empty()
+            && cache.orphaned_files.is_empty()
+            && cache.missing_files.is_empty()
+            && dangling_rows.is_empty();
+
+        let report = DoctorReport {
+            ok,
+            checked_at,
+            storage,
+            cache,
+            dangling_rows,
+            duration_ms: started.elapsed().as_millis(),
+        };
+
+        info!(
+            "Doctor: {} storage problems, {} orphaned cache files, {} records without a file, {} dangling rows",
+            report.storage.problems.len(),
+            report.cache.orphaned_files.len(),
+            report.cache.missing_files.len(),
+            report.dangling_rows.len()
+        );
+        Ok(report)
+    }
+
+    fn dangling_rows(state: &AppState) -> Result<Vec<DanglingRow>, ApiError> {
+        let db_error = |e: diesel::result::Error| {
+            ApiError::InternalServerError(format!("Database error: {e}"))
+        };
+        let mut rows = Vec::new();
+
+        for (package, version) in state
+            .database
+            .list_published_versions_without_files()
+            .map_err(db_error)?
+        {
+            rows.push(DanglingRow {
+                table: "package_versions".to_string(),
+                id: version.id,
+                name: Some(format!("{}@{}", package.name, version.version)),
+                problem: "published version has no tarball".to_string(),
+            });
+        }
+
+        for version in state
+            .database
+            .list_versions_without_package()
+            .map_err(db_error)?
+        {
+            rows.push(DanglingRow {
+                table: "package_versions".to_string(),
+                id: version.id,
+                name: Some(version.version),
+                problem: format!("package {} does not exist", version.package_id),
+            });
+        }
+
+        for file in state
+            .database
+            .list_files_without_version()
+            .map_err(db_error)?
+        {
+            rows.push(DanglingRow {
+                table: "package_files".to_string(),
+                id: file.id,
+                name: Some(file.filename),
+                problem: format!("package version {} does not exist", file.package_version_id),
+            });
+        }
+
+        Ok(rows)
+    }
+}
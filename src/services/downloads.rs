@@ -0,0 +1,378 @@
+use crate::error::ApiError;
+use crate::models::{
+    DownloadGranularity, DownloadPoint, DownloadRollupReport, NewDownloadRollup,
+    PackageDownloadsResponse,
+};
+use crate::services::QuarantineService;
+use crate::state::AppState;
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use log::warn;
+use std::collections::BTreeMap;
+
+/// Days shown when a time series is requested without `from`
+const DEFAULT_RANGE_DAYS: i64 = 30;
+/// Longest time series that can be requested
+const MAX_RANGE_DAYS: i64 = 5 * 366;
+/// Caps configured ages so that subtracting them from today can't overflow
+const MAX_AGE_DAYS: i64 = 100 * 366;
+
+pub struct DownloadStatsService;
+
+impl DownloadStatsService {
+    /// Counts a served tarball towards the daily downloads of its version. The count is
+    /// queued and written with the next counter flush, off the request path.
+    pub fn record(package: &str, filename: &str, state: &AppState) {
+        let Some(version) = QuarantineService::tarball_version(package, filename) else {
+            return;
+        };
+
+        let today = chrono::Utc::now().date_naive();
+        state
+            .database
+            .queue_version_download(package, version, today);
+    }
+
+    /// Writes queued download, file access and cache counters every
+    /// `CLEF_COUNTER_FLUSH_SECS`. Failed writes are retried with the next flush.
+    pub fn spawn_counter_flush(state: AppState) {
+        let interval = std::time::Duration::from_secs(state.config.counter_flush_secs.max(1));
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if let Err(e) = state.database.flush_counters() {
+                    warn!("Writing download and cache counters failed: {e}");
+                }
+            }
+        });
+    }
+
+    /// Downloads of a package between two days (inclusive) in buckets of a day, week or
+    /// month. Defaults to the last 30 days per day.
+    pub fn series(
+        package: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+        granularity: Option<&str>,
+        state: &AppState,
+    ) -> Result<PackageDownloadsResponse, ApiError> {
+        let granularity = match granularity {
+            Some(value) => DownloadGranularity::from_granularity_str(value).ok_or_else(|| {
+                ApiError::BadRequest(format!(
+                    "Invalid granularity '{value}', expected day, week or month"
+                ))
+            })?,
+            None => DownloadGranularity::Day,
+        };
+        let to = match to {
+            Some(value) => Self::parse_day("to", value)?,
+            None => chrono::Utc::now().date_naive(),
+        };
+        let from = match from {
+            Some(value) => Self::parse_day("from", value)?,
+            None => to - Duration::days(DEFAULT_RANGE_DAYS - 1),
+        };
+
+        if from > to {
+            return Err(ApiError::BadRequest(
+                "'from' must not be after 'to'".to_string(),
+            ));
+        }
+        if (to - from).num_days() >= MAX_RANGE_DAYS {
+            return Err(ApiError::BadRequest(format!(
+                "Time range must not be longer than {MAX_RANGE_DAYS} days"
+            )));
+        }
+
+        let daily = state
+            .database
+            .get_version_downloads(package, from, to)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        // Rolled up weeks and months count on their first day
+        let rollups = state
+            .database
+            .get_download_rollups(package, from, to)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let rows = daily
+            .iter()
+            .map(|row| (row.version.as_str(), row.day, row.downloads))
+            .chain(
+                rollups
+                    .iter()
+                    .map(|row| (row.version.as_str(), row.period_start, row.downloads)),
+            );
+        let points = Self::build_points(rows, from, to, granularity);
+        Ok(PackageDownloadsResponse {
+            package: package.to_string(),
+            from,
+            to,
+            granularity,
+            total: points.iter().map(|point| point.downloads).sum(),
+            points,
+        })
+    }
+
+    fn parse_day(name: &str, value: &str) -> Result<NaiveDate, ApiError> {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+            ApiError::BadRequest(format!(
+                "Invalid '{name}' date '{value}', expected YYYY-MM-DD"
+            ))
+        })
+    }
+
+    /// First day of the bucket a day falls into. Weeks start on Monday.
+    fn bucket_start(day: NaiveDate, granularity: DownloadGranularity) -> NaiveDate {
+        match granularity {
+            DownloadGranularity::Day => day,
+            DownloadGranularity::Week => {
+                day - Duration::days(i64::from(day.weekday().num_days_from_monday()))
+            }
+            DownloadGranularity::Month => day.with_day(1).unwrap_or(day),
+        }
+    }
+
+    fn next_bucket(start: NaiveDate, granularity: DownloadGranularity) -> NaiveDate {
+        match granularity {
+            DownloadGranularity::Day => start + Duration::days(1),
+            DownloadGranularity::Week => start + Duration::weeks(1),
+            DownloadGranularity::Month => start + Months::new(1),
+        }
+    }
+
+    /// Sums daily rows into one point per bucket between `from` and `to`, including the
+    /// buckets without downloads
+    fn build_points<'a>(
+        rows: impl IntoIterator<Item = (&'a str, NaiveDate, i64)>,
+        from: NaiveDate,
+        to: NaiveDate,
+        granularity: DownloadGranularity,
+    ) -> Vec<DownloadPoint> {
+        let mut buckets = BTreeMap::new();
+        let mut start = Self::bucket_start(from, granularity);
+        while start <= to {
+            buckets.insert(
+                start,
+                DownloadPoint {
+                    start,
+                    downloads: 0,
+                    versions: BTreeMap::new(),
+                },
+            );
+            start = Self::next_bucket(start, granularity);
+        }
+
+        for (version, day, downloads) in rows {
+            if let Some(point) = buckets.get_mut(&Self::bucket_start(day, granularity)) {
+                point.downloads += downloads;
+                *point.versions.entry(version.to_string()).or_insert(0) += downloads;
+            }
+        }
+
+        buckets.into_values().collect()
+    }
+
+    /// Rolls daily download stats of complete weeks older than `download_stats_daily_days`
+    /// into weekly aggregates, weekly aggregates older than `download_stats_weekly_days`
+    /// into monthly ones, and deletes stats older than `download_stats_retention_days`
+    pub fn roll_up(state: &AppState) -> Result<DownloadRollupReport, ApiError> {
+        let config = &state.config;
+        let today = chrono::Utc::now().date_naive();
+        let mut report = DownloadRollupReport::default();
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        if config.download_stats_daily_days > 0 {
+            let before = Self::bucket_start(
+                Self::days_ago(today, config.download_stats_daily_days),
+                DownloadGranularity::Week,
+            );
+            let rows = state
+                .database
+                .get_version_downloads_before(before)
+                .map_err(db_error)?;
+            let rollups = Self::aggregate(
+                rows.iter().map(|row| {
+                    (
+                        row.package_name.as_str(),
+                        row.version.as_str(),
+                        row.day,
+                        row.downloads,
+                    )
+                }),
+                DownloadGranularity::Week,
+            );
+            report.daily_rows_rolled_up = state
+                .database
+                .roll_up_downloads(None, before, &rollups)
+                .map_err(db_error)?;
+        }
+
+        if config.download_stats_weekly_days > 0 {
+            let before = Self::bucket_start(
+                Self::days_ago(today, config.download_stats_weekly_days),
+                DownloadGranularity::Month,
+            );
+            let week = DownloadGranularity::Week.as_str();
+            let rows = state
+                .database
+                .get_download_rollups_before(week, before)
+                .map_err(db_error)?;
+            // Weeks count towards the month they start in
+            let rollups = Self::aggregate(
+                rows.iter().map(|row| {
+                    (
+                        row.package_name.as_str(),
+                        row.version.as_str(),
+                        row.period_start,
+                        row.downloads,
+                    )
+                }),
+                DownloadGranularity::Month,
+            );
+            report.weekly_rows_rolled_up = state
+                .database
+                .roll_up_downloads(Some(week), before, &rollups)
+                .map_err(db_error)?;
+        }
+
+        if config.download_stats_retention_days > 0 {
+            report.rows_expired = state
+                .database
+                .delete_download_stats_before(Self::days_ago(
+                    today,
+                    config.download_stats_retention_days,
+                ))
+                .map_err(db_error)?;
+        }
+
+        Ok(report)
+    }
+
+    fn days_ago(today: NaiveDate, days: u64) -> NaiveDate {
+        today - Duration::days(i64::try_from(days).unwrap_or(i64::MAX).min(MAX_AGE_DAYS))
+    }
+
+    /// Sums rows of (package, version, day, downloads) into one aggregate per version and
+    /// week or month
+    fn aggregate<'a>(
+        rows: impl IntoIterator<Item = (&'a str, &'a str, NaiveDate, i64)>,
+        granularity: DownloadGranularity,
+    ) -> Vec<NewDownloadRollup> {
+        let mut sums = BTreeMap::new();
+        for (package, version, day, downloads) in rows {
+            *sums
+                .entry((package, version, Self::bucket_start(day, granularity)))
+                .or_insert(0) += downloads;
+        }
+
+        sums.into_iter()
+            .map(
+                |((package, version, period_start), downloads)| NewDownloadRollup {
+                    package_name: package.to_string(),
+                    version: version.to_string(),
+                    period: granularity.as_str().to_string(),
+                    period_start,
+                    downloads,
+                },
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_build_points() {
+        let rows = vec![
+            ("1.0.0", day("2025-01-30"), 2),
+            ("1.0.0", day("2025-02-03"), 1),
+            ("2.0.0", day("2025-02-03"), 4),
+        ];
+
+        let daily = DownloadStatsService::build_points(
+            rows.clone(),
+            day("2025-01-30"),
+            day("2025-02-03"),
+            DownloadGranularity::Day,
+        );
+        assert_eq!(daily.len(), 5);
+        assert_eq!(daily[0].downloads, 2);
+        assert_eq!(daily[1].downloads, 0);
+        assert_eq!(daily[4].downloads, 5);
+        assert_eq!(daily[4].versions["2.0.0"], 4);
+
+        // 2025-01-30 is a Thursday, 2025-02-03 a Monday
+        let weekly = DownloadStatsService::build_points(
+            rows.clone(),
+            day("2025-01-30"),
+            day("2025-02-03"),
+            DownloadGranularity::Week,
+        );
+        assert_eq!(
+            weekly
+                .iter()
+                .map(|p| (p.start, p.downloads))
+                .collect::<Vec<_>>(),
+            vec![(day("2025-01-27"), 2), (day("2025-02-03"), 5)]
+        );
+
+        let monthly = DownloadStatsService::build_points(
+            rows.clone(),
+            day("2025-01-30"),
+            day("2025-02-03"),
+            DownloadGranularity::Month,
+        );
+        assert_eq!(
+            monthly
+                .iter()
+                .map(|p| (p.start, p.downloads))
+                .collect::<Vec<_>>(),
+            vec![(day("2025-01-01"), 2), (day("2025-02-01"), 5)]
+        );
+        assert_eq!(monthly[1].versions["1.0.0"], 1);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let rows = vec![
+            ("lodash", "1.0.0", day("2025-01-27"), 2),
+            ("lodash", "1.0.0", day("2025-02-02"), 3),
+            ("lodash", "1.0.0", day("2025-02-03"), 1),
+            ("lodash", "2.0.0", day("2025-01-28"), 4),
+            ("react", "1.0.0", day("2025-01-28"), 5),
+        ];
+
+        let weekly = DownloadStatsService::aggregate(rows.clone(), DownloadGranularity::Week);
+        assert_eq!(
+            weekly
+                .iter()
+                .map(|r| (
+                    r.package_name.as_str(),
+                    r.version.as_str(),
+                    r.period_start,
+                    r.downloads
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("lodash", "1.0.0", day("2025-01-27"), 5),
+                ("lodash", "1.0.0", day("2025-02-03"), 1),
+                ("lodash", "2.0.0", day("2025-01-27"), 4),
+                ("react", "1.0.0", day("2025-01-27"), 5),
+            ]
+        );
+        assert!(weekly.iter().all(|r| r.period == "week"));
+
+        let monthly = DownloadStatsService::aggregate(rows, DownloadGranularity::Month);
+        assert_eq!(monthly.len(), 4);
+        assert_eq!(monthly[0].period_start, day("2025-01-01"));
+        assert_eq!(monthly[0].downloads, 2);
+        assert_eq!(monthly[1].period_start, day("2025-02-01"));
+        assert_eq!(monthly[1].downloads, 4);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::models::RegistryEvent;
+use log::debug;
+use tokio::sync::broadcast;
+
+/// Number of events a slow subscriber may lag behind before missing some
+const EVENT_BUS_CAPACITY: usize = 256;
+
+/// In-process broadcast channel for registry events
+#[derive(Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<RegistryEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+
+    /// Publishes an event to all current subscribers
+    pub fn emit(&self, event: RegistryEvent) {
+        debug!("Emitting event: {event:?}");
+        // Having no subscribers is not an error
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RegistryEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,149 @@
+use crate::error::ApiError;
+use crate::models::RecentVersion;
+use crate::state::AppState;
+use chrono::{NaiveDateTime, SecondsFormat, Utc};
+
+/// Versions listed when the client doesn't ask for a number
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+/// Feed of versions entering the registry, published here or proxied from upstream, so
+/// teams can follow what their installs may pull in. Only public packages are listed, the
+/// feed is the same for every reader.
+pub struct FeedService;
+
+impl FeedService {
+    pub fn recent(
+        limit: Option<i64>,
+        source: Option<&str>,
+        state: &AppState,
+    ) -> Result<Vec<RecentVersion>, ApiError> {
+        let published = match source {
+            None => None,
+            Some("published") => Some(true),
+            Some("proxied") => Some(false),
+            Some(other) => {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid source '{other}', expected 'published' or 'proxied'"
+                )));
+            }
+        };
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        let versions = state
+            .database
+            .get_recent_versions(limit, published)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(versions
+            .into_iter()
+            .map(|(package, version)| RecentVersion {
+                source: if package.author_id.is_some() {
+                    "published"
+                } else {
+                    "proxied"
+                }
+                .to_string(),
+                description: version.description.or(package.description),
+                name: package.name,
+                version: version.version,
+                created_at: version.created_at,
+            })
+            .collect())
+    }
+
+    /// Atom document of the versions, linking to their pages in the web UI at `base_url`
+    pub fn atom(versions: &[RecentVersion], base_url: &str) -> String {
+        let updated = versions
+            .first()
+            .map(|v| v.created_at)
+            .unwrap_or_else(|| Utc::now().naive_utc());
+
+        let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        feed.push_str("  <title>Clef registry packages</title>\n");
+        feed.push_str(&format!("  <id>{base_url}/feed/packages.atom</id>\n"));
+        feed.push_str(&format!(
+            "  <link rel=\"self\" href=\"{base_url}/feed/packages.atom\"/>\n"
+        ));
+        feed.push_str(&format!("  <link href=\"{base_url}/packages\"/>\n"));
+        feed.push_str(&format!(
+            "  <updated>{}</updated>\n",
+            Self::timestamp(updated)
+        ));
+        feed.push_str("  <author><name>Clef</name></author>\n");
+
+        for version in versions {
+            let name = Self::escape(&version.name);
+            let number = Self::escape(&version.version);
+            feed.push_str("  <entry>\n");
+            feed.push_str(&format!("    <title>{name}@{number}</title>\n"));
+            feed.push_str(&format!("    <id>urn:clef:package:{name}@{number}</id>\n"));
+            feed.push_str(&format!(
+                "    <link href=\"{base_url}/packages/{name}\"/>\n"
+            ));
+            feed.push_str(&format!(
+                "    <updated>{}</updated>\n",
+                Self::timestamp(version.created_at)
+            ));
+            feed.push_str(&format!("    <category term=\"{}\"/>\n", version.source));
+            if let Some(description) = &version.description {
+                feed.push_str(&format!(
+                    "    <summary>{}</summary>\n",
+                    Self::escape(description)
+                ));
+            }
+            feed.push_str("  </entry>\n");
+        }
+
+        feed.push_str("</feed>\n");
+        feed
+    }
+
+    fn timestamp(time: NaiveDateTime) -> String {
+        time.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    fn escape(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&apos;"),
+                // Control characters aren't allowed in XML 1.0
+                c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atom() {
+        let created_at =
+            NaiveDateTime::parse_from_str("2025-08-01 12:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let versions = vec![RecentVersion {
+            name: "@acme/ui".to_string(),
+            version: "1.0.0".to_string(),
+            description: Some("Buttons & <inputs>\u{1}".to_string()),
+            source: "published".to_string(),
+            created_at,
+        }];
+
+        let feed = FeedService::atom(&versions, "https://npm.example.com");
+        assert!(feed.contains("<updated>2025-08-01T12:30:00Z</updated>"));
+        assert!(feed.contains("<title>@acme/ui@1.0.0</title>"));
+        assert!(feed.contains("<link href=\"https://npm.example.com/packages/@acme/ui\"/>"));
+        assert!(feed.contains("<summary>Buttons &amp; &lt;inputs&gt;</summary>"));
+        assert!(feed.contains("<category term=\"published\"/>"));
+        assert_eq!(feed.matches("<entry>").count(), 1);
+    }
+}
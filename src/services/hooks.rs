@@ -0,0 +1,434 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, Hook, HookListResponse, HookRequest, HookResponse, HookUpdateRequest,
+    NewHook, RegistryEvent,
+};
+use crate::services::RegistryService;
+use crate::state::AppState;
+use hmac::{Hmac, Mac};
+use log::{debug, info, warn};
+use serde_json::json;
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Attempts made to deliver an event to a hook endpoint
+const HOOK_DELIVERY_ATTEMPTS: u64 = 3;
+
+/// How long a hook endpoint has to answer a delivery
+const HOOK_DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct HookService;
+
+impl HookService {
+    /// Registers a hook of the requesting user. Names are `@scope` for scope hooks and a
+    /// username for owner hooks, `npm hook add` sends them that way.
+    pub fn create(
+        request: HookRequest,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<HookResponse, ApiError> {
+        Self::validate_endpoint(&request.endpoint)?;
+        let name = request.name.trim().to_string();
+
+        match request.hook_type.as_str() {
+            "package" => {
+                let can_read = state
+                    .database
+                    .has_read_permission(&name, Some(user.user_id))
+                    .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+                if !can_read {
+                    return Err(ApiError::NotFound(format!("Package '{name}' not found")));
+                }
+            }
+            "scope" => {
+                if !name.starts_with('@') || name.contains('/') || name.len() < 2 {
+                    return Err(ApiError::BadRequest(format!(
+                        "Invalid scope '{name}', expected @scope"
+                    )));
+                }
+            }
+            "owner" => {
+                let exists = state
+                    .database
+                    .get_user_by_username(&name)
+                    .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+                    .is_some();
+                if !exists {
+                    return Err(ApiError::NotFound(format!("User '{name}' not found")));
+                }
+            }
+            other => {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid hook type '{other}', expected package, scope or owner"
+                )));
+            }
+        }
+
+        let hook = state
+            .database
+            .create_hook(&NewHook {
+                user_id: user.user_id,
+                hook_type: request.hook_type,
+                name,
+                endpoint: request.endpoint,
+                secret: request.secret,
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!(
+            "User {} added a {} hook for {} to {}",
+            user.username, hook.hook_type, hook.name, hook.endpoint
+        );
+        Ok(HookResponse::from_hook(hook, &user.username, false))
+    }
+
+    /// Hooks of the requesting user, optionally only those for one package or scope
+    pub fn list(
+        package: Option<&str>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<HookListResponse, ApiError> {
+        let hooks: Vec<Hook> = state
+            .database
+            .list_hooks(Some(user.user_id))
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .into_iter()
+            .map(|(hook, _)| hook)
+            .filter(|hook| package.is_none_or(|package| hook.name == package))
+            .collect();
+
+        let total = hooks.len();
+        let objects = hooks
+            .into_iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|hook| HookResponse::from_hook(hook, &user.username, false))
+            .collect();
+
+        Ok(HookListResponse {
+            objects,
+            total,
+            urls: json!({}),
+        })
+    }
+
+    pub fn get(
+        id: i32,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<HookResponse, ApiError> {
+        let hook = Self::own_hook(id, user, state)?;
+        Ok(HookResponse::from_hook(hook, &user.username, false))
+    }
+
+    pub fn update(
+        id: i32,
+        request: HookUpdateRequest,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<HookResponse, ApiError> {
+        Self::own_hook(id, user, state)?;
+        Self::validate_endpoint(&request.endpoint)?;
+
+        let hook = state
+            .database
+            .update_hook(id, &request.endpoint, request.secret.as_deref())
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!("User {} updated hook {id}", user.username);
+        Ok(HookResponse::from_hook(hook, &user.username, false))
+    }
+
+    pub fn delete(
+        id: i32,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<HookResponse, ApiError> {
+        let hook = Self::own_hook(id, user, state)?;
+        state
+            .database
+            .delete_hook(id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!("User {} removed hook {id}", user.username);
+        Ok(HookResponse::from_hook(hook, &user.username, true))
+    }
+
+    /// Delivers registry events to the matching hooks as they are emitted
+    pub fn spawn_dispatcher(state: AppState) {
+        let mut events = state.events.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        let state = state.clone();
+                        tokio::spawn(async move { Self::dispatch(event, &state).await });
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Hook dispatcher fell behind, {missed} events were not delivered");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    async fn dispatch(event: RegistryEvent, state: &AppState) {
+        let Some((package, hook_event, version)) = Self::hook_event(&event) else {
+            return;
+        };
+
+        let hooks = match state.database.list_hooks(None) {
+            Ok(hooks) => hooks,
+            Err(e) => {
+                warn!("Failed to load hooks for {hook_event} of {package}: {e}");
+                return;
+            }
+        };
+        // Usernames of owner hooks that own the package
+        let owner_ids: Vec<i32> = state
+            .database
+            .get_package_owners(package)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|owner| owner.user_id)
+            .collect();
+        let owners: Vec<String> = hooks
+            .iter()
+            .filter(|(hook, _)| hook.hook_type == "owner")
+            .filter(|(hook, _)| {
+                matches!(
+                    state.database.get_user_by_username(&hook.name),
+                    Ok(Some(user)) if owner_ids.contains(&user.id)
+                )
+            })
+            .map(|(hook, _)| hook.name.clone())
+            .collect();
+
+        let matching: Vec<(Hook, String)> = hooks
+            .into_iter()
+            .filter(|(hook, _)| Self::matches(hook, package, &owners))
+            // Hooks only see packages their owner can read
+            .filter(|(hook, _)| {
+                state
+                    .database
+                    .has_read_permission(package, Some(hook.user_id))
+                    .unwrap_or(false)
+            })
+            .collect();
+        if matching.is_empty() {
+            return;
+        }
+
+        let document = RegistryService::warm_package_metadata(package, state)
+            .await
+            .unwrap_or_else(|_| json!({ "name": package }));
+        let time = chrono::Utc::now().timestamp_millis();
+
+        for (hook, username) in matching {
+            let body = json!({
+                "event": hook_event,
+                "name": package,
+                "type": hook.hook_type,
+                "hookOwner": { "username": username },
+                "payload": document,
+                "change": { "version": version },
+                "time": time,
+            })
+            .to_string();
+
+            let response_code = Self::deliver(&hook, &body, state).await;
+            if let Err(e) = state.database.record_hook_delivery(hook.id, response_code) {
+                warn!("Failed to record delivery of hook {}: {e}", hook.id);
+            }
+        }
+    }
+
+    /// Posts a payload to the hook endpoint, retrying failed deliveries. Returns the last
+    /// response status, 0 when the endpoint couldn't be reached.
+    async fn deliver(hook: &Hook, body: &str, state: &AppState) -> i32 {
+        let signature = Self::signature(&hook.secret, body);
+        let mut response_code = 0;
+
+        for attempt in 1..=HOOK_DELIVERY_ATTEMPTS {
+            let result = state
+                .client
+                .post(&hook.endpoint)
+                .header("content-type", "application/json")
+                .header("x-npm-signature", &signature)
+                .timeout(HOOK_DELIVERY_TIMEOUT)
+                .body(body.to_string())
+                .send()
+                .await;
+
+            match result {
+                Ok(response) => {
+                    response_code = i32::from(response.status().as_u16());
+                    if response.status().is_success() {
+                        debug!("Delivered hook {} to {}", hook.id, hook.endpoint);
+                        return response_code;
+                    }
+                    warn!(
+                        "Hook {} endpoint {} answered {} (attempt {attempt})",
+                        hook.id,
+                        hook.endpoint,
+                        response.status()
+                    );
+                }
+                Err(e) => {
+                    response_code = 0;
+                    warn!(
+                        "Failed to deliver hook {} to {} (attempt {attempt}): {e}",
+                        hook.id, hook.endpoint
+                    );
+                }
+            }
+
+            if attempt < HOOK_DELIVERY_ATTEMPTS {
+                tokio::time::sleep(Duration::from_secs(attempt * 2)).await;
+            }
+        }
+
+        response_code
+    }
+
+    fn own_hook(id: i32, user: &AuthenticatedUser, state: &AppState) -> Result<Hook, ApiError> {
+        state
+            .database
+            .get_hook(id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .filter(|hook| hook.user_id == user.user_id)
+            .ok_or_else(|| ApiError::NotFound(format!("Hook {id} not found")))
+    }
+
+    fn validate_endpoint(endpoint: &str) -> Result<(), ApiError> {
+        match reqwest::Url::parse(endpoint) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+            _ => Err(ApiError::BadRequest(format!(
+                "Invalid hook endpoint '{endpoint}', expected an http(s) URL"
+            ))),
+        }
+    }
+
+    /// Package, npm event name and version of the events hooks are notified about
+    fn hook_event(event: &RegistryEvent) -> Option<(&str, &'static str, &str)> {
+        match event {
+            RegistryEvent::VersionPublished {
+                package, version, ..
+            } => Some((package, "package:publish", version)),
+            RegistryEvent::VersionUnpublished {
+                package, version, ..
+            } => Some((package, "package:unpublish", version)),
+            RegistryEvent::VersionYanked {
+                package,
+                version,
+                yanked,
+                ..
+            } => {
+                let hook_event = if *yanked {
+                    "package:deprecate"
+                } else {
+                    "package:undeprecate"
+                };
+                Some((package, hook_event, version))
+            }
+            RegistryEvent::PackageVisibilityChanged { .. }
+            | RegistryEvent::AdvisoryPublished { .. }
+            | RegistryEvent::CacheEvicted { .. } => None,
+        }
+    }
+
+    fn matches(hook: &Hook, package: &str, owners: &[String]) -> bool {
+        match hook.hook_type.as_str() {
+            "package" => hook.name == package,
+            "scope" => package
+                .strip_prefix(hook.name.as_str())
+                .is_some_and(|rest| rest.starts_with('/')),
+            "owner" => owners.contains(&hook.name),
+            _ => false,
+        }
+    }
+
+    /// `x-npm-signature` header value, a SHA-256 HMAC of the body keyed with the hook secret
+    fn signature(secret: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body.as_bytes());
+        let digest: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        format!("sha256={digest}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(hook_type: &str, name: &str) -> Hook {
+        let now = chrono::Utc::now().naive_utc();
+        Hook {
+            id: 1,
+            user_id: 1,
+            hook_type: hook_type.to_string(),
+            name: name.to_string(),
+            endpoint: "https://example.com/hook".to_string(),
+            secret: "secret".to_string(),
+            last_delivery: None,
+            response_code: 0,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_hook_matches() {
+        let owners = vec!["alice".to_string()];
+
+        assert!(HookService::matches(
+            &hook("package", "@acme/lib"),
+            "@acme/lib",
+            &owners
+        ));
+        assert!(!HookService::matches(
+            &hook("package", "@acme/lib"),
+            "@acme/cli",
+            &owners
+        ));
+        assert!(HookService::matches(
+            &hook("scope", "@acme"),
+            "@acme/cli",
+            &owners
+        ));
+        assert!(!HookService::matches(
+            &hook("scope", "@acme"),
+            "@acmecorp/cli",
+            &owners
+        ));
+        assert!(HookService::matches(
+            &hook("owner", "alice"),
+            "left-pad",
+            &owners
+        ));
+        assert!(!HookService::matches(
+            &hook("owner", "bob"),
+            "left-pad",
+            &owners
+        ));
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            HookService::signature("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use crate::models::HotCacheStats;
+use crate::services::shared_redis::SharedRedis;
+use redis::AsyncCommands;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Documents in Redis are dropped when no instance stored them for this long
+const SHARED_EXPIRY_SECS: i64 = 24 * 3600;
+
+/// A metadata document kept in memory
+#[derive(Debug, Clone)]
+pub struct HotEntry {
+    pub data: Vec<u8>,
+    pub etag: Option<String>,
+    /// When the document was fetched, for the TTL of upstream packages
+    pub fetched_at: SystemTime,
+    /// Documents of locally published packages never expire
+    pub published: bool,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Entry and its position in `order`
+    entries: HashMap<String, (HotEntry, u64)>,
+    /// Least recently used first
+    order: BTreeMap<u64, String>,
+    next_tick: u64,
+    size_bytes: u64,
+    /// Packages that are never evicted to make room
+    pinned: BTreeSet<String>,
+}
+
+impl Entries {
+    fn touch(&mut self, key: &str) {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some((_, position)) = self.entries.get_mut(key) {
+            self.order.remove(position);
+            *position = tick;
+            self.order.insert(tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<HotEntry> {
+        let (entry, position) = self.entries.remove(key)?;
+        self.order.remove(&position);
+        self.size_bytes -= entry.data.len() as u64;
+        Some(entry)
+    }
+}
+
+/// Bounded least recently used cache of package metadata documents, in front of the
+/// metadata files and their database records. With Redis configured documents are shared
+/// with the other instances: a local miss is looked up in Redis, and a local hit is only
+/// served while Redis holds the same version of the document, so an update made by one
+/// instance is seen by all of them.
+#[derive(Debug)]
+pub struct HotCache {
+    max_bytes: u64,
+    max_entry_bytes: u64,
+    entries: Mutex<Entries>,
+    shared: Option<SharedRedis>,
+    hits: AtomicU64,
+    shared_hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl HotCache {
+    pub fn new(max_bytes: u64, max_entry_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            max_entry_bytes: max_entry_bytes.min(max_bytes),
+            entries: Mutex::new(Entries::default()),
+            shared: None,
+            hits: AtomicU64::new(0),
+            shared_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Shares documents with the other instances through Redis
+    pub fn with_shared(mut self, shared: Option<SharedRedis>) -> Self {
+        self.shared = shared;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    /// The document of `package` unless it expired
+    pub async fn get(&self, package: &str, ttl_secs: u64) -> Option<HotEntry> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let local = self.get_local(package, ttl_secs);
+        let Some(shared) = &self.shared else {
+            return self.count(local, false);
+        };
+
+        if let Some(entry) = local {
+            let key = Self::shared_key(package);
+            let fetched_at = shared
+                .run(|mut connection| async move {
+                    connection
+                        .hget::<_, _, Option<u64>>(key, "fetched_at")
+                        .await
+                })
+                .await;
+            match fetched_at {
+                // Redis can't be reached, the local document is the best there is
+                None => return self.count(Some(entry), false),
+                Some(Some(fetched_at)) if fetched_at == Self::millis(entry.fetched_at) => {
+                    return self.count(Some(entry), false);
+                }
+                // Another instance stored a newer document, or it was removed
+                Some(_) => self.entries.lock().unwrap().remove(package),
+            };
+        }
+
+        let key = Self::shared_key(package);
+        let entry = shared
+            .run(|mut connection| async move {
+                connection.hgetall::<_, HashMap<String, Vec<u8>>>(key).await
+            })
+            .await
+            .and_then(Self::from_shared)
+            .filter(|entry| !Self::is_expired(entry, ttl_secs));
+        if let Some(entry) = &entry {
+            self.insert_local(package, entry.clone());
+        }
+        self.count(entry, true)
+    }
+
+    fn get_local(&self, package: &str, ttl_secs: u64) -> Option<HotEntry> {
+        let mut entries = self.entries.lock().unwrap();
+        let expired = Self::is_expired(&entries.entries.get(package)?.0, ttl_secs);
+        if expired {
+            entries.remove(package);
+            return None;
+        }
+
+        entries.touch(package);
+        entries.entries.get(package).map(|(entry, _)| entry.clone())
+    }
+
+    fn count(&self, entry: Option<HotEntry>, shared: bool) -> Option<HotEntry> {
+        match &entry {
+            Some(_) if shared => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                self.shared_hits.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(_) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        entry
+    }
+
+    fn is_expired(entry: &HotEntry, ttl_secs: u64) -> bool {
+        !entry.published
+            && entry
+                .fetched_at
+                .elapsed()
+                .is_ok_and(|age| age.as_secs() > ttl_secs)
+    }
+
+    /// Stores a document, evicting the least recently used unpinned ones to make room.
+    /// Documents over the size limit for a single entry aren't kept.
+    pub async fn insert(&self, package: &str, entry: HotEntry) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        if let Some(shared) = &self.shared
+            && entry.data.len() as u64 <= self.max_entry_bytes
+        {
+            let key = Self::shared_key(package);
+            let mut pipeline = redis::pipe();
+            pipeline
+                .atomic()
+                .del(&key)
+                .hset(&key, "data", &entry.data)
+                .hset(&key, "fetched_at", Self::millis(entry.fetched_at))
+                .hset(&key, "published", entry.published)
+                .hset(&key, "etag", entry.etag.as_deref().unwrap_or_default())
+                .expire(&key, SHARED_EXPIRY_SECS);
+            shared
+                .run(|mut connection| async move { pipeline.exec_async(&mut connection).await })
+                .await;
+        }
+        self.insert_local(package, entry);
+    }
+
+    fn insert_local(&self, package: &str, entry: HotEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(package);
+        let size = entry.data.len() as u64;
+        if size > self.max_entry_bytes {
+            return;
+        }
+
+        let mut skipped = Vec::new();
+        while entries.size_bytes + size > self.max_bytes {
+            let Some((position, key)) = entries.order.pop_first() else {
+                break;
+            };
+            if entries.pinned.contains(&key) {
+                skipped.push((position, key));
+                continue;
+            }
+            if let Some((evicted, _)) = entries.entries.remove(&key) {
+                entries.size_bytes -= evicted.data.len() as u64;
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        entries.order.extend(skipped);
+        // Pinned documents alone may fill the cache
+        if entries.size_bytes + size > self.max_bytes {
+            return;
+        }
+
+        let tick = entries.next_tick;
+        entries.next_tick += 1;
+        entries.order.insert(tick, package.to_string());
+        entries.size_bytes += size;
+        entries.entries.insert(package.to_string(), (entry, tick));
+    }
+
+    pub async fn remove(&self, package: &str) {
+        if let Some(shared) = &self.shared {
+            let key = Self::shared_key(package);
+            shared
+                .run(|mut connection| async move { connection.del::<_, ()>(key).await })
+                .await;
+        }
+        self.entries.lock().unwrap().remove(package);
+    }
+
+    /// Removes every document except those of pinned packages
+    pub async fn clear(&self) {
+        let pinned = {
+            let mut entries = self.entries.lock().unwrap();
+            let keys: Vec<String> = entries
+                .entries
+                .keys()
+                .filter(|key| !entries.pinned.contains(*key))
+                .cloned()
+                .collect();
+            for key in keys {
+                entries.remove(&key);
+            }
+            entries.pinned.clone()
+        };
+
+        if let Some(shared) = &self.shared {
+            let prefix = Self::shared_key("");
+            shared
+                .run(|mut connection| async move {
+                    let mut keys: Vec<String> = Vec::new();
+                    let mut scan = connection
+                        .scan_match::<_, String>(format!("{prefix}*"))
+                        .await?;
+                    while let Some(key) = scan.next_item().await {
+                        if !pinned.contains(&key[prefix.len()..]) {
+                            keys.push(key);
+                        }
+                    }
+                    drop(scan);
+                    if keys.is_empty() {
+                        return Ok(());
+                    }
+                    connection.del::<_, ()>(keys).await
+                })
+                .await;
+        }
+    }
+
+    pub fn set_pinned(&self, pinned: BTreeSet<String>) {
+        self.entries.lock().unwrap().pinned = pinned;
+    }
+
+    pub fn stats(&self) -> HotCacheStats {
+        let entries = self.entries.lock().unwrap();
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        HotCacheStats {
+            enabled: self.is_enabled(),
+            entries: entries.entries.len() as u64,
+            size_bytes: entries.size_bytes,
+            max_bytes: self.max_bytes,
+            hits,
+            misses,
+            evictions: self.evictions.load(Ordering::Relaxed),
+            hit_rate: if hits + misses > 0 {
+                hits as f64 / (hits + misses) as f64 * 100.0
+            } else {
+                0.0
+            },
+            shared: self.shared.is_some(),
+            shared_hits: self.shared_hits.load(Ordering::Relaxed),
+        }
+    }
+
+    fn shared_key(package: &str) -> String {
+        format!("clef:metadata:{package}")
+    }
+
+    fn millis(at: SystemTime) -> u64 {
+        at.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
+    /// A document read from Redis, `None` when a field is missing or malformed
+    fn from_shared(mut fields: HashMap<String, Vec<u8>>) -> Option<HotEntry> {
+        let mut text = |field: &str| String::from_utf8(fields.remove(field)?).ok();
+        let fetched_at: u64 = text("fetched_at")?.parse().ok()?;
+        let published = text("published")? == "1";
+        let etag = text("etag").filter(|etag| !etag.is_empty());
+        Some(HotEntry {
+            data: fields.remove("data")?,
+            etag,
+            fetched_at: UNIX_EPOCH + Duration::from_millis(fetched_at),
+            published,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn entry(size: usize) -> HotEntry {
+        HotEntry {
+            data: vec![b'x'; size],
+            etag: None,
+            fetched_at: SystemTime::now(),
+            published: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let cache = HotCache::new(300, 200);
+        cache.insert("a", entry(100)).await;
+        cache.insert("b", entry(100)).await;
+        cache.insert("c", entry(100)).await;
+        assert!(cache.get("a", 3600).await.is_some());
+
+        // "b" is the least recently used now
+        cache.insert("d", entry(100)).await;
+        assert!(cache.get("b", 3600).await.is_none());
+        assert!(cache.get("a", 3600).await.is_some());
+        assert!(cache.get("c", 3600).await.is_some());
+
+        // Too large for a single entry
+        cache.insert("e", entry(201)).await;
+        assert!(cache.get("e", 3600).await.is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.size_bytes, 300);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.misses, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_and_expired_entries() {
+        let cache = HotCache::new(200, 200);
+        cache.set_pinned(BTreeSet::from(["a".to_string()]));
+        cache.insert("a", entry(100)).await;
+        cache.insert("b", entry(100)).await;
+        cache.insert("c", entry(100)).await;
+        assert!(cache.get("a", 3600).await.is_some());
+        assert!(cache.get("b", 3600).await.is_none());
+
+        cache.clear().await;
+        assert!(cache.get("a", 3600).await.is_some());
+        assert!(cache.get("c", 3600).await.is_none());
+
+        let old = SystemTime::now() - Duration::from_secs(7200);
+        cache
+            .insert(
+                "upstream",
+                HotEntry {
+                    fetched_at: old,
+                    ..entry(10)
+                },
+            )
+            .await;
+        cache
+            .insert(
+                "published",
+                HotEntry {
+                    fetched_at: old,
+                    published: true,
+                    ..entry(10)
+                },
+            )
+            .await;
+        assert!(cache.get("upstream", 3600).await.is_none());
+        assert!(cache.get("published", 3600).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_serves_local_entries_without_redis() {
+        let cache = HotCache::new(300, 200).with_shared(SharedRedis::open("redis://127.0.0.1:1"));
+        cache.insert("a", entry(100)).await;
+        assert!(cache.get("a", 3600).await.is_some());
+        assert!(cache.get("b", 3600).await.is_none());
+
+        let stats = cache.stats();
+        assert!(stats.shared);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.shared_hits, 0);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::error::ApiError;
+use crate::models::{AuthenticatedUser, PackageSizeResponse};
+use crate::services::{PackageFilesService, RegistryService};
+use crate::state::AppState;
+use crate::versions;
+use log::debug;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Most dependency versions walked for one report
+const MAX_DEPENDENCIES: usize = 2000;
+
+/// Reports how much disk space a version takes once installed, similar to packagephobia.
+/// Sizes come from the `dist.unpackedSize` and `dist.fileCount` fields npm adds to version
+/// metadata, or from the indexed tarball when the metadata has none.
+pub struct InstallSizeService;
+
+impl InstallSizeService {
+    /// Size report of a version, callers check read access to it first. Dependencies `user`
+    /// can't read count as unresolved.
+    pub async fn report(
+        name: &str,
+        version: &str,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<PackageSizeResponse, ApiError> {
+        let user_id = user.map(|u| u.user_id);
+        let readable = |package: &str| {
+            state
+                .database
+                .has_read_permission(package, user_id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))
+        };
+        let metadata = RegistryService::get_package_version_metadata(name, version, state).await?;
+        let version = metadata["version"].as_str().unwrap_or(version).to_string();
+        let (unpacked_size_bytes, file_count) = Self::size(name, &version, &metadata, state)?
+            .ok_or_else(|| {
+                ApiError::NotFound(format!(
+                    "The size of {name}@{version} is unknown, its tarball is not cached"
+                ))
+            })?;
+
+        let mut install_size_bytes = unpacked_size_bytes;
+        let mut install_file_count = file_count;
+        let mut unresolved = Vec::new();
+        let mut truncated = false;
+        let mut documents: HashMap<String, Option<Value>> = HashMap::new();
+        let mut installed = HashSet::from([format!("{name}@{version}")]);
+        let mut queue: VecDeque<(String, String)> = Self::dependencies(&metadata).collect();
+        let mut seen: HashSet<(String, String)> = queue.iter().cloned().collect();
+
+        while let Some((dependency, range)) = queue.pop_front() {
+            if installed.len() > MAX_DEPENDENCIES {
+                truncated = true;
+                break;
+            }
+
+            if !documents.contains_key(&dependency) {
+                let document = if readable(&dependency)? {
+                    RegistryService::get_package_metadata(
+                        &dependency,
+                        state,
+                        None,
+                        state.config.get_scheme(),
+                    )
+                    .await
+                    .inspect_err(|e| debug!("No metadata for dependency {dependency}: {e}"))
+                    .ok()
+                } else {
+                    None
+                };
+                documents.insert(dependency.clone(), document);
+            }
+            let resolved = documents[&dependency]
+                .as_ref()
+                .and_then(|document| Self::resolve(document, &range).map(|v| (document, v)));
+            let Some((document, resolved)) = resolved else {
+                unresolved.push(format!("{dependency}@{range}"));
+                continue;
+            };
+            if !installed.insert(format!("{dependency}@{resolved}")) {
+                continue;
+            }
+
+            let version_metadata = &document["versions"][&resolved];
+            match Self::size(&dependency, &resolved, version_metadata, state)? {
+                Some((size, files)) => {
+                    install_size_bytes += size;
+                    install_file_count += files;
+                }
+                None => unresolved.push(format!("{dependency}@{resolved}")),
+            }
+            for next in Self::dependencies(version_metadata) {
+                if seen.insert(next.clone()) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Ok(PackageSizeResponse {
+            name: name.to_string(),
+            version,
+            unpacked_size_bytes,
+            file_count,
+            install_size_bytes,
+            install_file_count,
+            dependency_count: installed.len() as i64 - 1,
+            unresolved,
+            truncated,
+        })
+    }
+
+    /// Dependencies an install pulls in, optional ones included
+    fn dependencies(metadata: &Value) -> impl Iterator<Item = (String, String)> + '_ {
+        ["dependencies", "optionalDependencies"]
+            .into_iter()
+            .filter_map(|field| metadata[field].as_object())
+            .flatten()
+            .map(|(name, range)| (name.clone(), range.as_str().unwrap_or("*").to_string()))
+    }
+
+    /// The version npm would pick for `range`: a dist-tag, `latest` when it satisfies the
+    /// range, otherwise the highest matching version
+    fn resolve(document: &Value, range: &str) -> Option<String> {
+        let tags = &document["dist-tags"];
+        if let Some(tagged) = tags[range].as_str() {
+            return Some(tagged.to_string());
+        }
+        if let Some(latest) = tags["latest"].as_str()
+            && versions::satisfies(latest, range)
+        {
+            return Some(latest.to_string());
+        }
+        let available = document["versions"].as_object()?;
+        versions::max_satisfying(available.keys().map(String::as_str), range)
+    }
+
+    /// Unpacked size and file count of a version
+    fn size(
+        name: &str,
+        version: &str,
+        metadata: &Value,
+        state: &AppState,
+    ) -> Result<Option<(i64, i64)>, ApiError> {
+        let dist = &metadata["dist"];
+        if let (Some(size), Some(files)) =
+            (dist["unpackedSize"].as_i64(), dist["fileCount"].as_i64())
+        {
+            return Ok(Some((size, files)));
+        }
+
+        let package = state
+            .database
+            .get_package_with_versions(name)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        let Some(entry) = package
+            .as_ref()
+            .and_then(|p| p.versions.iter().find(|v| v.version.version == version))
+        else {
+            return Ok(None);
+        };
+
+        Ok(PackageFilesService::entries(name, entry, state)?
+            .map(|files| (files.iter().map(|f| f.size_bytes).sum(), files.len() as i64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve() {
+        let document = json!({
+            "dist-tags": { "latest": "1.2.0", "next": "2.0.0-beta.1" },
+            "versions": { "1.0.0": {}, "1.2.0": {}, "1.3.0": {}, "2.0.0-beta.1": {} }
+        });
+
+        assert_eq!(
+            InstallSizeService::resolve(&document, "^1.0.0").as_deref(),
+            Some("1.2.0")
+        );
+        assert_eq!(
+            InstallSizeService::resolve(&document, "~1.3.0").as_deref(),
+            Some("1.3.0")
+        );
+        assert_eq!(
+            InstallSizeService::resolve(&document, "next").as_deref(),
+            Some("2.0.0-beta.1")
+        );
+        assert_eq!(InstallSizeService::resolve(&document, "^3.0.0"), None);
+        assert_eq!(
+            InstallSizeService::resolve(&document, "github:user/repo"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let metadata = json!({
+            "dependencies": { "a": "^1.0.0" },
+            "optionalDependencies": { "b": "2.x" },
+            "devDependencies": { "c": "*" }
+        });
+
+        let dependencies: Vec<_> = InstallSizeService::dependencies(&metadata).collect();
+        assert_eq!(
+            dependencies,
+            vec![
+                ("a".to_string(), "^1.0.0".to_string()),
+                ("b".to_string(), "2.x".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use crate::config::AppConfig;
+use ipnet::IpNet;
+use rocket::Request;
+use rocket::http::Method;
+use std::net::IpAddr;
+
+/// Requests that have their own allow and deny rules in addition to the global ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteGroup {
+    /// Reading packages and tarballs from the registry
+    Install,
+    /// Publishing, unpublishing and tagging packages
+    Publish,
+    /// The admin API and the cache management routes only admins may use
+    Admin,
+    Other,
+}
+
+impl RouteGroup {
+    /// The group of a request by the decoded, non-empty segments of its path, which is what
+    /// Rocket routes on. `//` and percent-encoded characters don't change the group.
+    pub fn of<'a>(method: Method, segments: impl IntoIterator<Item = &'a str>) -> Self {
+        let segments: Vec<&str> = segments.into_iter().collect();
+        match segments.as_slice() {
+            ["api", "v1", "admin", ..]
+            | ["api", "v1", "cache", "packages", ..]
+            // Cache management routes outside the admin API that require an admin
+            | ["api", "v1", "cache"]
+            | ["api", "v1", "cache", "entries" | "reprocess" | "gc"] => Self::Admin,
+            ["registry", ..] if matches!(method, Method::Get | Method::Head) => Self::Install,
+            // Logging in and npm audit are writes that don't change packages
+            ["registry", "-", "user", ..] | ["registry", "-", "npm", "v1", "security", ..] => {
+                Self::Other
+            }
+            ["registry", ..] => Self::Publish,
+            _ => Self::Other,
+        }
+    }
+}
+
+pub struct IpFilter;
+
+impl IpFilter {
+    /// The address of the client. Behind trusted proxies this is the last `X-Forwarded-For`
+    /// entry that wasn't added by one of them, entries further left could be made up by the
+    /// client.
+    pub fn client_ip(request: &Request<'_>, config: &AppConfig) -> Option<IpAddr> {
+        let peer = request.remote()?.ip();
+        if !config.trust_proxy_headers {
+            return Some(peer);
+        }
+
+        let forwarded: Vec<&str> = request.headers().get("X-Forwarded-For").collect();
+        Some(Self::forwarded_client(
+            peer,
+            &forwarded,
+            &config.trusted_proxies,
+        ))
+    }
+
+    fn forwarded_client(peer: IpAddr, forwarded_for: &[&str], trusted: &[IpNet]) -> IpAddr {
+        let is_trusted = |ip: &IpAddr| {
+            let ip = ip.to_canonical();
+            trusted.iter().any(|network| network.contains(&ip))
+        };
+
+        let mut client = peer;
+        let hops = forwarded_for
+            .iter()
+            .flat_map(|value| value.split(','))
+            .rev()
+            .map(str::trim);
+        for hop in hops {
+            if !is_trusted(&client) {
+                break;
+            }
+            match Self::parse_hop(hop) {
+                Some(ip) => client = ip,
+                None => break,
+            }
+        }
+        client
+    }
+
+    /// Parses an `X-Forwarded-For` entry, which may carry a port
+    fn parse_hop(hop: &str) -> Option<IpAddr> {
+        if let Ok(ip) = hop.parse() {
+            return Some(ip);
+        }
+        hop.parse::<std::net::SocketAddr>()
+            .map(|addr| addr.ip())
+            .ok()
+    }
+
+    /// Whether the global rules and the rules of the route group let `ip` through. Deny
+    /// rules win, and a non-empty allow list has to contain the address.
+    pub fn allows(ip: IpAddr, group: RouteGroup, config: &AppConfig) -> bool {
+        let (allow, deny): (&[IpNet], &[IpNet]) = match group {
+            RouteGroup::Install => (&config.ip_allow_install, &config.ip_deny_install),
+            RouteGroup::Publish => (&config.ip_allow_publish, &config.ip_deny_publish),
+            RouteGroup::Admin => (&config.ip_allow_admin, &config.ip_deny_admin),
+            RouteGroup::Other => (&[], &[]),
+        };
+
+        Self::rules_allow(ip, &config.ip_allow, &config.ip_deny)
+            && Self::rules_allow(ip, allow, deny)
+    }
+
+    /// Whether any address rule is configured at all
+    pub fn is_enabled(config: &AppConfig) -> bool {
+        [
+            &config.ip_allow,
+            &config.ip_deny,
+            &config.ip_allow_install,
+            &config.ip_deny_install,
+            &config.ip_allow_publish,
+            &config.ip_deny_publish,
+            &config.ip_allow_admin,
+            &config.ip_deny_admin,
+        ]
+        .iter()
+        .any(|rules| !rules.is_empty())
+    }
+
+    fn rules_allow(ip: IpAddr, allow: &[IpNet], deny: &[IpNet]) -> bool {
+        // IPv4 clients of a dual stack listener show up as ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        if deny.iter().any(|network| network.contains(&ip)) {
+            return false;
+        }
+        allow.is_empty() || allow.iter().any(|network| network.contains(&ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_networks;
+    use rocket::http::uri::Origin;
+
+    fn group(method: Method, uri: &str) -> RouteGroup {
+        RouteGroup::of(method, Origin::parse(uri).unwrap().path().segments())
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_route_group() {
+        assert_eq!(group(Method::Get, "/registry/lodash"), RouteGroup::Install);
+        assert_eq!(
+            group(Method::Put, "/registry/@acme%2fui"),
+            RouteGroup::Publish
+        );
+        assert_eq!(
+            group(Method::Put, "/registry/-/package/lodash/dist-tags/beta"),
+            RouteGroup::Publish
+        );
+        assert_eq!(
+            group(Method::Put, "/registry/-/user/org.couchdb.user:alice"),
+            RouteGroup::Other
+        );
+        assert_eq!(
+            group(Method::Post, "/registry/-/npm/v1/security/audits/quick"),
+            RouteGroup::Other
+        );
+        assert_eq!(
+            group(Method::Delete, "/api/v1/admin/users/alice"),
+            RouteGroup::Admin
+        );
+        assert_eq!(
+            group(Method::Get, "/api/v1/administrators"),
+            RouteGroup::Other
+        );
+        for (method, path) in [
+            (Method::Delete, "/api/v1/cache"),
+            (Method::Get, "/api/v1/cache/entries"),
+            (Method::Delete, "/api/v1/cache/packages/lodash"),
+            (Method::Delete, "/api/v1/cache/packages/@acme/ui"),
+            (Method::Post, "/api/v1/cache/reprocess"),
+            (Method::Post, "/api/v1/cache/gc"),
+        ] {
+            assert_eq!(group(method, path), RouteGroup::Admin, "{path}");
+        }
+        for path in [
+            "/api/v1/cache/stats",
+            "/api/v1/cache/health",
+            "/api/v1/cache/analytics",
+        ] {
+            assert_eq!(group(Method::Get, path), RouteGroup::Other, "{path}");
+        }
+
+        // Rocket routes these to the same handlers as the plain paths
+        for (method, path) in [
+            (Method::Get, "/api/v1//admin/users"),
+            (Method::Get, "//api/v1/admin/users/"),
+            (Method::Get, "/api/v1/%61dmin/users"),
+            (Method::Delete, "/api/%761/cache"),
+            (Method::Post, "/api/v1/cache//gc"),
+        ] {
+            assert_eq!(group(method, path), RouteGroup::Admin, "{path}");
+        }
+        assert_eq!(
+            group(Method::Put, "/registry//-/package/lodash/dist-tags/beta"),
+            RouteGroup::Publish
+        );
+        assert_eq!(
+            group(Method::Put, "/registry/%2d/user/org.couchdb.user:alice"),
+            RouteGroup::Other
+        );
+        // A package named like a login route is still a publish
+        assert_eq!(
+            group(Method::Put, "/registry/-%2Fuser%2Forg.couchdb.user:alice"),
+            RouteGroup::Publish
+        );
+    }
+
+    #[test]
+    fn test_forwarded_client() {
+        let trusted = parse_networks("10.0.0.0/8,127.0.0.1");
+
+        // Not behind a trusted proxy, the header is ignored
+        assert_eq!(
+            IpFilter::forwarded_client(ip("203.0.113.7"), &["1.2.3.4"], &trusted),
+            ip("203.0.113.7")
+        );
+        // The client prepended a made up address, the proxies appended the real one
+        assert_eq!(
+            IpFilter::forwarded_client(
+                ip("127.0.0.1"),
+                &["1.2.3.4, 198.51.100.9", "10.0.0.5"],
+                &trusted
+            ),
+            ip("198.51.100.9")
+        );
+        assert_eq!(
+            IpFilter::forwarded_client(ip("10.0.0.1"), &["198.51.100.9:51234"], &trusted),
+            ip("198.51.100.9")
+        );
+        assert_eq!(
+            IpFilter::forwarded_client(ip("10.0.0.1"), &["unknown"], &trusted),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn test_allows() {
+        let config = AppConfig {
+            ip_allow: parse_networks("10.0.0.0/8, 2001:db8::/32"),
+            ip_deny: parse_networks("10.6.6.6"),
+            ip_allow_publish: parse_networks("10.1.0.0/16"),
+            ip_deny_admin: parse_networks("10.2.0.0/16"),
+            ..Default::default()
+        };
+
+        assert!(IpFilter::allows(
+            ip("10.3.0.1"),
+            RouteGroup::Install,
+            &config
+        ));
+        assert!(IpFilter::allows(
+            ip("::ffff:10.3.0.1"),
+            RouteGroup::Install,
+            &config
+        ));
+        assert!(IpFilter::allows(
+            ip("2001:db8::1"),
+            RouteGroup::Other,
+            &config
+        ));
+        assert!(!IpFilter::allows(
+            ip("192.0.2.1"),
+            RouteGroup::Install,
+            &config
+        ));
+        assert!(!IpFilter::allows(
+            ip("10.6.6.6"),
+            RouteGroup::Install,
+            &config
+        ));
+        assert!(IpFilter::allows(
+            ip("10.1.2.3"),
+            RouteGroup::Publish,
+            &config
+        ));
+        assert!(!IpFilter::allows(
+            ip("10.3.0.1"),
+            RouteGroup::Publish,
+            &config
+        ));
+        assert!(IpFilter::allows(ip("10.3.0.1"), RouteGroup::Admin, &config));
+        assert!(!IpFilter::allows(
+            ip("10.2.0.1"),
+            RouteGroup::Admin,
+            &config
+        ));
+        assert!(IpFilter::is_enabled(&config));
+        assert!(!IpFilter::is_enabled(&AppConfig::default()));
+    }
+}
@@ -0,0 +1,672 @@
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use crate::models::JobStatus;
+use crate::services::{
+    AdvisoryService, AuthService, ChangesFeedService, DiskWatermarkService, DownloadStatsService,
+    NotificationService, PinnedPackageService, PopularRefreshService, RetentionService,
+    TaskService,
+};
+use crate::state::AppState;
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDateTime, Timelike, Utc};
+use log::{info, warn};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// Minutes a cron schedule is searched ahead for its next run, a bit over four years so
+/// February 29th is found
+const CRON_SEARCH_LIMIT: usize = 4 * 366 * 24 * 60;
+
+/// When a job runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    /// At a fixed interval, starting at startup
+    Every(Duration),
+    Cron(CronSchedule),
+    Disabled,
+}
+
+impl Schedule {
+    /// Parses a five field cron expression, `every <n>[s|m|h|d]`, or `off`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value == "off" || value == "disabled" {
+            return Ok(Self::Disabled);
+        }
+        if let Some(interval) = value.strip_prefix("every ") {
+            return match Self::parse_interval(interval.trim()) {
+                Some(0) | None => Err(format!("Invalid interval '{interval}'")),
+                Some(secs) => Ok(Self::Every(Duration::from_secs(secs))),
+            };
+        }
+        CronSchedule::parse(value).map(Self::Cron)
+    }
+
+    fn parse_interval(interval: &str) -> Option<u64> {
+        let (amount, unit) = match interval.char_indices().last()? {
+            (i, unit) if unit.is_ascii_alphabetic() => (&interval[..i], unit),
+            _ => (interval, 's'),
+        };
+        let multiplier = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return None,
+        };
+        Some(amount.parse::<u64>().ok()?.saturating_mul(multiplier))
+    }
+
+    /// The first run after `after`, `None` when the job never runs
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Self::Every(interval) => Some(after + ChronoDuration::from_std(*interval).ok()?),
+            Self::Cron(cron) => cron.next_after(after),
+            Self::Disabled => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Every(interval) => {
+                let secs = interval.as_secs();
+                let (amount, unit) = [(86400, "d"), (3600, "h"), (60, "m")]
+                    .into_iter()
+                    .find(|(unit, _)| secs % unit == 0)
+                    .map(|(unit, name)| (secs / unit, name))
+                    .unwrap_or((secs, "s"));
+                format!("every {amount}{unit}")
+            }
+            Self::Cron(cron) => cron.expression.clone(),
+            Self::Disabled => "disabled".to_string(),
+        }
+    }
+}
+
+/// A `minute hour day-of-month month day-of-week` expression. Fields take `*`, values,
+/// ranges, lists and steps like `*/15` or `1-5`. Sunday is 0 or 7, like in crontab, and a
+/// run is due when either day field matches if both are restricted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields.as_slice() else {
+            return Err(format!(
+                "Invalid cron expression '{expression}', expected 5 fields"
+            ));
+        };
+
+        let mut weekday_bits = Self::field(weekdays, 0, 7)?;
+        // 7 is another name for Sunday
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes: Self::field(minutes, 0, 59)?,
+            hours: Self::field(hours, 0, 23)?,
+            days: Self::field(days, 1, 31)?,
+            months: Self::field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+
+    /// Bits of the values a field matches
+    fn field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+        let invalid = || format!("Invalid cron field '{field}'");
+        let mut bits = 0;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+                None => (part, 1),
+            };
+            let (start, end) = match range {
+                "*" => (min, max),
+                range => match range.split_once('-') {
+                    Some((start, end)) => (
+                        start.parse().map_err(|_| invalid())?,
+                        end.parse().map_err(|_| invalid())?,
+                    ),
+                    // `5/10` runs from 5 to the end of the field
+                    None if part.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+                    None => {
+                        let value = range.parse().map_err(|_| invalid())?;
+                        (value, value)
+                    }
+                },
+            };
+            if step == 0 || start < min || end > max || start > end {
+                return Err(invalid());
+            }
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(bits)
+    }
+
+    fn matches_day(&self, time: NaiveDateTime) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first matching minute after `after`
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+
+        for _ in 0..CRON_SEARCH_LIMIT {
+            if self.months & (1 << time.month()) == 0 || !self.matches_day(time) {
+                time = (time.date() + ChronoDuration::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += ChronoDuration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+/// The background jobs the scheduler runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    CacheSweep,
+    DownloadRollup,
+    AdvisorySync,
+    Retention,
+    TaskCleanup,
+    ChangesFeed,
+    PopularRefresh,
+    PinnedRefresh,
+    NotificationDigests,
+    IdleTokenRevocation,
+}
+
+impl Job {
+    pub const ALL: [Self; 10] = [
+        Self::CacheSweep,
+        Self::DownloadRollup,
+        Self::AdvisorySync,
+        Self::Retention,
+        Self::TaskCleanup,
+        Self::ChangesFeed,
+        Self::PopularRefresh,
+        Self::PinnedRefresh,
+        Self::NotificationDigests,
+        Self::IdleTokenRevocation,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CacheSweep => "cache-sweep",
+            Self::DownloadRollup => "download-rollup",
+            Self::AdvisorySync => "advisory-sync",
+            Self::Retention => "retention",
+            Self::TaskCleanup => "task-cleanup",
+            Self::ChangesFeed => "changes-feed",
+            Self::PopularRefresh => "popular-refresh",
+            Self::PinnedRefresh => "pinned-refresh",
+            Self::NotificationDigests => "notification-digests",
+            Self::IdleTokenRevocation => "idle-token-revocation",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|job| job.name() == name)
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::CacheSweep => {
+                "Evicts cached tarballs when the cache volume is above the high watermark"
+            }
+            Self::DownloadRollup => "Rolls daily download stats up into weeks and months",
+            Self::AdvisorySync => "Syncs security advisories from OSV.dev",
+            Self::Retention => "Applies organization retention policies",
+            Self::TaskCleanup => "Deletes finished tasks and export archives after a week",
+            Self::ChangesFeed => "Expires cached packages that changed upstream",
+            Self::PopularRefresh => {
+                "Refreshes the most downloaded upstream packages before their metadata expires"
+            }
+            Self::PinnedRefresh => "Refreshes pinned packages and fetches their new tarballs",
+            Self::NotificationDigests => {
+                "Sends notification digests that are due and retries failed deliveries"
+            }
+            Self::IdleTokenRevocation => "Revokes tokens that haven't been used for a while",
+        }
+    }
+
+    /// The schedule from the interval settings that predate `CLEF_JOB_SCHEDULES`
+    fn default_schedule(&self, config: &AppConfig) -> Schedule {
+        let every = |secs: u64| match secs {
+            0 => Schedule::Disabled,
+            secs => Schedule::Every(Duration::from_secs(secs)),
+        };
+        match self {
+            Self::CacheSweep => every(config.cache_watermark_check_secs),
+            Self::DownloadRollup => every(24 * 3600),
+            Self::AdvisorySync => every(config.advisory_sync_hours * 3600),
+            Self::Retention => every(config.retention_interval_hours * 3600),
+            Self::TaskCleanup => every(3600),
+            Self::ChangesFeed => every(config.upstream_changes_poll_secs),
+            Self::PopularRefresh => every(config.popular_refresh_minutes * 60),
+            Self::PinnedRefresh => every(config.pinned_refresh_minutes * 60),
+            Self::NotificationDigests => every(15 * 60),
+            Self::IdleTokenRevocation => every(3600),
+        }
+    }
+
+    /// Whether the job has anything to do with this configuration, whatever its schedule
+    fn available(&self, config: &AppConfig) -> bool {
+        match self {
+            Self::CacheSweep => config.cache_enabled && config.cache_high_watermark_percent > 0,
+            Self::ChangesFeed => config.cache_enabled && config.upstream_changes_url.is_some(),
+            Self::PopularRefresh => config.cache_enabled && config.popular_refresh_count > 0,
+            Self::PinnedRefresh => config.cache_enabled,
+            Self::IdleTokenRevocation => config.token_idle_revoke_days > 0,
+            _ => true,
+        }
+    }
+
+    /// Jobs that write to packages or the cache wait for maintenance to end
+    fn pauses_during_maintenance(&self) -> bool {
+        matches!(
+            self,
+            Self::DownloadRollup
+                | Self::Retention
+                | Self::ChangesFeed
+                | Self::PopularRefresh
+                | Self::PinnedRefresh
+        )
+    }
+
+    /// Delay before the first run of an interval schedule. Advisories are synced once the
+    /// previous sync is due, so restarts don't hit OSV.dev each time.
+    fn first_delay(&self, interval: Duration, state: &AppState) -> Duration {
+        match self {
+            Self::AdvisorySync => AdvisoryService::last_synced_at(state)
+                .and_then(|last| (Utc::now().naive_utc() - last).to_std().ok())
+                .map(|elapsed| interval.saturating_sub(elapsed))
+                .unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Runs the job, returning a summary of what it did
+    async fn run(&self, state: &AppState) -> Result<String, ApiError> {
+        match self {
+            Self::CacheSweep => Ok(match DiskWatermarkService::check(state)? {
+                Some(report) => format!(
+                    "Evicted {} files, freed {} bytes",
+                    report.files_removed, report.bytes_freed
+                ),
+                None => "Cache volume is below the high watermark".to_string(),
+            }),
+            Self::DownloadRollup => {
+                let report = DownloadStatsService::roll_up(state)?;
+                Ok(format!(
+                    "Rolled up {} daily and {} weekly download stats, expired {}",
+                    report.daily_rows_rolled_up, report.weekly_rows_rolled_up, report.rows_expired
+                ))
+            }
+            Self::AdvisorySync => {
+                let report = AdvisoryService::sync(state).await?;
+                Ok(format!(
+                    "Checked {} packages, updated {} advisories, removed {}",
+                    report.packages_checked, report.advisories_updated, report.advisories_removed
+                ))
+            }
+            Self::Retention => {
+                let report = RetentionService::apply(None, false, None, state).await?;
+                Ok(format!(
+                    "Applied {} policies to {} packages, deleted {} versions",
+                    report.policies, report.packages, report.deleted
+                ))
+            }
+            Self::TaskCleanup => {
+                let deleted = TaskService::prune(state)?;
+                Ok(format!("Deleted {deleted} finished tasks"))
+            }
+            Self::ChangesFeed => ChangesFeedService::follow(state).await,
+            Self::PopularRefresh => {
+                let report = PopularRefreshService::refresh(state).await?;
+                Ok(format!(
+                    "Refreshed {} of {} popular packages, {} failed",
+                    report.refreshed, report.checked, report.failed
+                ))
+            }
+            Self::PinnedRefresh => {
+                let report = PinnedPackageService::refresh(state, false).await?;
+                Ok(format!(
+                    "Refreshed {} of {} pinned packages, fetched {} tarballs, {} failed",
+                    report.metadata_refreshed,
+                    report.packages,
+                    report.tarballs_fetched,
+                    report.failed.len()
+                ))
+            }
+            Self::NotificationDigests => {
+                let sent = NotificationService::send_due(state).await?;
+                Ok(format!("Sent notifications to {sent} subscriptions"))
+            }
+            Self::IdleTokenRevocation => {
+                let days = state.config.token_idle_revoke_days;
+                let revoked = AuthService::revoke_idle_tokens(&state.database, days)?;
+                Ok(format!("Revoked {revoked} tokens unused for {days} days"))
+            }
+        }
+    }
+}
+
+/// Runs the background jobs on their schedules and keeps track of their runs
+#[derive(Debug, Default)]
+pub struct JobScheduler {
+    jobs: Vec<(Job, Schedule)>,
+    statuses: RwLock<Vec<JobStatus>>,
+}
+
+impl JobScheduler {
+    /// Schedules every job, `CLEF_JOB_SCHEDULES` overrides the default intervals
+    pub fn new(config: &AppConfig) -> Self {
+        for (name, _) in &config.job_schedules {
+            if Job::from_name(name).is_none() {
+                warn!("Ignoring the schedule of unknown job '{name}'");
+            }
+        }
+
+        let jobs: Vec<(Job, Schedule)> = Job::ALL
+            .into_iter()
+            .map(|job| {
+                let configured = config
+                    .job_schedules
+                    .iter()
+                    .find(|(name, _)| name == job.name())
+                    .and_then(|(_, value)| match Schedule::parse(value) {
+                        Ok(schedule) => Some(schedule),
+                        Err(e) => {
+                            warn!("Ignoring the schedule of job {}: {e}", job.name());
+                            None
+                        }
+                    });
+                let schedule = match configured {
+                    _ if !job.available(config) => Schedule::Disabled,
+                    Some(schedule) => schedule,
+                    None => job.default_schedule(config),
+                };
+                (job, schedule)
+            })
+            .collect();
+
+        let statuses = jobs
+            .iter()
+            .map(|(job, schedule)| JobStatus {
+                name: job.name().to_string(),
+                description: job.description().to_string(),
+                schedule: schedule.describe(),
+                enabled: *schedule != Schedule::Disabled,
+                ..Default::default()
+            })
+            .collect();
+
+        Self {
+            jobs,
+            statuses: RwLock::new(statuses),
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.statuses.read().unwrap().clone()
+    }
+
+    /// Starts a task per enabled job
+    pub fn spawn(state: AppState) {
+        for (index, (job, schedule)) in state.jobs.jobs.iter().cloned().enumerate() {
+            if schedule == Schedule::Disabled {
+                info!("Background job {} is disabled", job.name());
+                continue;
+            }
+            info!(
+                "Scheduled background job {} ({})",
+                job.name(),
+                schedule.describe()
+            );
+
+            let state = state.clone();
+            tokio::spawn(async move {
+                let now = Utc::now().naive_utc();
+                let mut next = match &schedule {
+                    Schedule::Every(interval) => {
+                        ChronoDuration::from_std(job.first_delay(*interval, &state))
+                            .ok()
+                            .map(|delay| now + delay)
+                    }
+                    schedule => schedule.next_after(now),
+                };
+
+                while let Some(at) = next {
+                    state
+                        .jobs
+                        .update(index, |status| status.next_run_at = Some(at));
+                    let delay = (at - Utc::now().naive_utc()).to_std().unwrap_or_default();
+                    tokio::time::sleep(delay).await;
+
+                    Self::run(job, index, &state).await;
+                    next = schedule.next_after(Utc::now().naive_utc());
+                }
+            });
+        }
+    }
+
+    async fn run(job: Job, index: usize, state: &AppState) {
+        let started_at = Utc::now().naive_utc();
+        let started = Instant::now();
+        state.jobs.update(index, |status| {
+            status.running = true;
+            status.last_started_at = Some(started_at);
+        });
+
+        let (outcome, message) =
+            if job.pauses_during_maintenance() && state.maintenance.is_enabled() {
+                info!("Skipping background job {} during maintenance", job.name());
+                ("skipped", "Skipped during maintenance".to_string())
+            } else {
+                match job.run(state).await {
+                    Ok(summary) => {
+                        info!("Background job {} finished: {summary}", job.name());
+                        ("succeeded", summary)
+                    }
+                    Err(e) => {
+                        warn!("Background job {} failed: {e:?}", job.name());
+                        ("failed", e.to_string())
+                    }
+                }
+            };
+
+        state.jobs.update(index, |status| {
+            status.running = false;
+            status.runs += 1;
+            if outcome == "failed" {
+                status.failures += 1;
+            }
+            status.last_finished_at = Some(Utc::now().naive_utc());
+            status.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+            status.last_outcome = Some(outcome.to_string());
+            status.last_message = Some(message);
+            status.next_run_at = None;
+        });
+    }
+
+    fn update(&self, index: usize, update: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self.statuses.write().unwrap().get_mut(index) {
+            update(status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2025-03-01 is a Saturday
+        NaiveDate::from_ymd_opt(2025, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cron_next_run() {
+        let next = |expression: &str, after| {
+            CronSchedule::parse(expression)
+                .unwrap()
+                .next_after(after)
+                .unwrap()
+        };
+
+        assert_eq!(next("*/15 * * * *", at(1, 10, 7)), at(1, 10, 15));
+        assert_eq!(next("0 3 * * *", at(1, 3, 0)), at(2, 3, 0));
+        assert_eq!(next("30 */6 * * *", at(1, 7, 0)), at(1, 12, 30));
+        // Weekdays only, from Saturday
+        assert_eq!(next("0 9 * * 1-5", at(1, 12, 0)), at(3, 9, 0));
+        assert_eq!(next("0 0 * * 7", at(1, 12, 0)), at(2, 0, 0));
+        // Either day field matches when both are restricted
+        assert_eq!(next("0 0 10 * 1", at(1, 12, 0)), at(3, 0, 0));
+        assert_eq!(
+            next("0 0 29 2 *", at(1, 0, 0)),
+            NaiveDate::from_ymd_opt(2028, 2, 29)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+        assert_eq!(
+            CronSchedule::parse("0 0 31 2 *")
+                .unwrap()
+                .next_after(at(1, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_schedule_parsing() {
+        assert_eq!(
+            Schedule::parse("every 6h").unwrap(),
+            Schedule::Every(Duration::from_secs(6 * 3600))
+        );
+        assert_eq!(Schedule::parse("off").unwrap(), Schedule::Disabled);
+        assert_eq!(
+            Schedule::parse("every 90s").unwrap().describe(),
+            "every 90s"
+        );
+        assert_eq!(Schedule::parse("every 120").unwrap().describe(), "every 2m");
+        assert_eq!(
+            Schedule::parse(" 0  3 * * 1,3,5 ").unwrap().describe(),
+            "0 3 * * 1,3,5"
+        );
+        assert!(Schedule::parse("every 0m").is_err());
+        assert!(Schedule::parse("0 3 * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+    }
+
+    #[test]
+    fn test_scheduler_configuration() {
+        let config = AppConfig {
+            cache_high_watermark_percent: 0,
+            advisory_sync_hours: 0,
+            job_schedules: vec![
+                ("retention".to_string(), "0 3 * * *".to_string()),
+                ("advisory-sync".to_string(), "every 12h".to_string()),
+                ("download-rollup".to_string(), "sometimes".to_string()),
+            ],
+            ..Default::default()
+        };
+        let statuses = JobScheduler::new(&config).statuses();
+        let schedule = |name: &str| {
+            statuses
+                .iter()
+                .find(|status| status.name == name)
+                .map(|status| (status.schedule.as_str(), status.enabled))
+                .unwrap()
+        };
+
+        assert_eq!(schedule("cache-sweep"), ("disabled", false));
+        assert_eq!(schedule("retention"), ("0 3 * * *", true));
+        assert_eq!(schedule("advisory-sync"), ("every 12h", true));
+        // An invalid schedule falls back to the default
+        assert_eq!(schedule("download-rollup"), ("every 1d", true));
+        // Without an upstream changes feed there's nothing to follow
+        assert_eq!(schedule("changes-feed"), ("disabled", false));
+        assert_eq!(schedule("popular-refresh"), ("every 5m", true));
+        assert_eq!(schedule("pinned-refresh"), ("every 1h", true));
+        assert_eq!(schedule("notification-digests"), ("every 15m", true));
+        // Idle tokens are kept unless CLEF_TOKEN_IDLE_REVOKE_DAYS is set
+        assert_eq!(schedule("idle-token-revocation"), ("disabled", false));
+
+        let statuses = JobScheduler::new(&AppConfig {
+            popular_refresh_count: 0,
+            ..Default::default()
+        })
+        .statuses();
+        assert!(
+            statuses
+                .iter()
+                .any(|status| status.name == "popular-refresh" && !status.enabled)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_changes_feed_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = crate::create_state(AppConfig {
+            cache_dir: dir.path().to_str().unwrap().to_string(),
+            database_url: dir.path().join("clef.db").to_str().unwrap().to_string(),
+            upstream_changes_url: Some("http://127.0.0.1:9/_changes".to_string()),
+            upstream_changes_poll_secs: 30,
+            maintenance_mode: true,
+            ..AppConfig::default()
+        });
+        let index = Job::ALL
+            .iter()
+            .position(|job| *job == Job::ChangesFeed)
+            .unwrap();
+        let status = |state: &AppState| state.jobs.statuses()[index].clone();
+
+        assert_eq!(status(&state).name, "changes-feed");
+        assert_eq!(status(&state).schedule, "every 30s");
+        assert!(status(&state).enabled);
+
+        JobScheduler::run(Job::ChangesFeed, index, &state).await;
+        assert_eq!(status(&state).last_outcome.as_deref(), Some("skipped"));
+
+        // Once maintenance ends the feed is read, and an unreachable one fails the run
+        let admin = crate::models::AuthenticatedUser::new("admin".to_string(), 1, true, None);
+        crate::services::MaintenanceMode::disable(&admin, &state);
+        JobScheduler::run(Job::ChangesFeed, index, &state).await;
+        assert_eq!(status(&state).last_outcome.as_deref(), Some("failed"));
+        assert_eq!(status(&state).runs, 2);
+    }
+}
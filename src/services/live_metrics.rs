@@ -0,0 +1,242 @@
+use crate::models::LiveMetricsSample;
+use crate::state::AppState;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use chrono::Utc;
+use log::debug;
+use rocket::Shutdown;
+use rocket::data::{IoHandler, IoStream};
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::{self, io};
+use sha1::{Digest, Sha1};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::Role;
+
+/// Interval between two samples sent to a live dashboard
+pub const LIVE_METRICS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counters behind the live dashboard, kept current by the `LiveMetricsRecorder` fairing
+#[derive(Debug, Default)]
+pub struct LiveMetrics {
+    requests: AtomicU64,
+    active_downloads: AtomicU64,
+}
+
+impl LiveMetrics {
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a tarball download as active until the returned guard is dropped
+    pub fn start_download(self: &Arc<Self>) -> ActiveDownload {
+        self.active_downloads.fetch_add(1, Ordering::Relaxed);
+        ActiveDownload(self.clone())
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn active_downloads(&self) -> u64 {
+        self.active_downloads.load(Ordering::Relaxed)
+    }
+}
+
+/// A tarball download in flight
+#[derive(Debug)]
+pub struct ActiveDownload(Arc<LiveMetrics>);
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        self.0.active_downloads.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Totals a sample is taken from
+#[derive(Debug, Clone, Copy)]
+struct Totals {
+    requests: u64,
+    cache_hits: u64,
+    cache_misses: u64,
+    at: Instant,
+}
+
+impl Totals {
+    fn of(state: &AppState) -> Self {
+        Self {
+            requests: state.live_metrics.requests(),
+            cache_hits: state.cache.get_hit_count(),
+            cache_misses: state.cache.get_miss_count(),
+            at: Instant::now(),
+        }
+    }
+}
+
+/// Turns the registry's running totals into rates over the time since the previous sample
+#[derive(Debug)]
+pub struct LiveMetricsSampler {
+    previous: Totals,
+}
+
+impl LiveMetricsSampler {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            previous: Totals::of(state),
+        }
+    }
+
+    pub fn sample(&mut self, state: &AppState) -> LiveMetricsSample {
+        self.next(Totals::of(state), state.live_metrics.active_downloads())
+    }
+
+    fn next(&mut self, totals: Totals, active_downloads: u64) -> LiveMetricsSample {
+        let previous = std::mem::replace(&mut self.previous, totals);
+        let elapsed = totals.at.duration_since(previous.at).as_secs_f64();
+        let requests = totals.requests.saturating_sub(previous.requests);
+        let hits = totals.cache_hits.saturating_sub(previous.cache_hits);
+        let lookups = hits + totals.cache_misses.saturating_sub(previous.cache_misses);
+
+        LiveMetricsSample {
+            requests_per_sec: if elapsed > 0.0 {
+                requests as f64 / elapsed
+            } else {
+                0.0
+            },
+            cache_hit_rate: (lookups > 0).then(|| hits as f64 / lookups as f64),
+            active_downloads,
+            sampled_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// Appended to the client's key to build the `Sec-WebSocket-Accept` header (RFC 6455)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+pub struct LiveMetricsService;
+
+impl LiveMetricsService {
+    /// `Sec-WebSocket-Accept` answering a client's `Sec-WebSocket-Key`
+    pub fn accept_key(key: &str) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(key.trim().as_bytes());
+        hasher.update(WEBSOCKET_GUID.as_bytes());
+        BASE64_STANDARD.encode(hasher.finalize())
+    }
+
+    /// Samples as server-sent events, for clients that can't open a WebSocket
+    pub fn events(state: &AppState, mut shutdown: Shutdown) -> EventStream![Event + '_] {
+        EventStream! {
+            let mut sampler = LiveMetricsSampler::new(state);
+            let mut interval = tokio::time::interval(LIVE_METRICS_INTERVAL);
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => yield Event::json(&sampler.sample(state)),
+                    _ = &mut shutdown => break,
+                }
+            }
+        }
+    }
+}
+
+/// Sends a sample as a JSON text message every `LIVE_METRICS_INTERVAL` until the client
+/// closes the WebSocket or the server shuts down
+pub struct LiveMetricsSocket<'r> {
+    pub state: &'r AppState,
+    pub shutdown: Shutdown,
+}
+
+#[rocket::async_trait]
+impl IoHandler for LiveMetricsSocket<'_> {
+    async fn io(self: Pin<Box<Self>>, io: IoStream) -> io::Result<()> {
+        let LiveMetricsSocket {
+            state,
+            mut shutdown,
+        } = *Pin::into_inner(self);
+        let mut socket = WebSocketStream::from_raw_socket(io, Role::Server, None).await;
+        let mut sampler = LiveMetricsSampler::new(state);
+        let mut interval = tokio::time::interval(LIVE_METRICS_INTERVAL);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let sample = serde_json::to_string(&sampler.sample(state))?;
+                    if let Err(e) = socket.send(Message::Text(sample)).await {
+                        debug!("Live metrics socket closed: {e}");
+                        break;
+                    }
+                }
+                // Pings are answered while reading, anything else from the client is ignored
+                message = socket.next() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+                _ = &mut shutdown => {
+                    let _ = socket.close(None).await;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(requests: u64, cache_hits: u64, cache_misses: u64, at: Instant) -> Totals {
+        Totals {
+            requests,
+            cache_hits,
+            cache_misses,
+            at,
+        }
+    }
+
+    #[test]
+    fn test_samples_rates_since_previous_sample() {
+        let start = Instant::now();
+        let mut sampler = LiveMetricsSampler {
+            previous: totals(100, 10, 10, start),
+        };
+
+        let sample = sampler.next(totals(120, 13, 11, start + Duration::from_secs(2)), 4);
+        assert_eq!(sample.requests_per_sec, 10.0);
+        assert_eq!(sample.cache_hit_rate, Some(0.75));
+        assert_eq!(sample.active_downloads, 4);
+
+        let sample = sampler.next(totals(120, 13, 11, start + Duration::from_secs(3)), 0);
+        assert_eq!(sample.requests_per_sec, 0.0);
+        assert_eq!(sample.cache_hit_rate, None);
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // Example handshake of RFC 6455, section 1.3
+        assert_eq!(
+            LiveMetricsService::accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_counts_active_downloads_until_dropped() {
+        let metrics = Arc::new(LiveMetrics::default());
+        let first = metrics.start_download();
+        let second = metrics.start_download();
+        assert_eq!(metrics.active_downloads(), 2);
+
+        drop(first);
+        assert_eq!(metrics.active_downloads(), 1);
+        drop(second);
+        assert_eq!(metrics.active_downloads(), 0);
+    }
+}
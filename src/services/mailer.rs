@@ -0,0 +1,84 @@
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use crate::state::AppState;
+use lettre::message::{Mailbox, header::ContentType};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{debug, info};
+
+pub struct MailerService;
+
+impl MailerService {
+    pub fn is_configured(config: &AppConfig) -> bool {
+        config.smtp_host.is_some()
+    }
+
+    /// Sends a plain text email through the configured SMTP server
+    pub async fn send(
+        state: &AppState,
+        to: &str,
+        subject: &str,
+        body: String,
+    ) -> Result<(), ApiError> {
+        let config = &state.config;
+        let Some(host) = &config.smtp_host else {
+            return Err(ApiError::InternalServerError(
+                "SMTP is not configured".to_string(),
+            ));
+        };
+
+        let from: Mailbox = config.smtp_from.parse().map_err(|e| {
+            ApiError::InternalServerError(format!(
+                "Invalid sender address '{}': {e}",
+                config.smtp_from
+            ))
+        })?;
+        let to: Mailbox = to
+            .parse()
+            .map_err(|e| ApiError::BadRequest(format!("Invalid email address '{to}': {e}")))?;
+
+        let message = Message::builder()
+            .from(from)
+            .to(to.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to build email: {e}")))?;
+
+        let builder = match config.smtp_tls.to_lowercase().as_str() {
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+        }
+        .map_err(|e| ApiError::InternalServerError(format!("Invalid SMTP configuration: {e}")))?;
+
+        let mut builder = builder.port(config.smtp_port);
+        // Credentials read from files or Vault may have been rotated since startup
+        let username = state
+            .secrets
+            .get("CLEF_SMTP_USERNAME")
+            .or_else(|| config.smtp_username.clone());
+        let password = state
+            .secrets
+            .get("CLEF_SMTP_PASSWORD")
+            .or_else(|| config.smtp_password.clone());
+        if let (Some(username), Some(password)) = (username, password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        debug!(
+            "Sending email '{subject}' to {to} via {host}:{}",
+            config.smtp_port
+        );
+        builder
+            .build()
+            .send(message)
+            .await
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to send email: {e}")))?;
+
+        info!("Sent email '{subject}' to {to}");
+        Ok(())
+    }
+}
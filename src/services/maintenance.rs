@@ -0,0 +1,150 @@
+use crate::models::{AuthenticatedUser, MaintenanceRequest, MaintenanceStatus, NewAuditLogEntry};
+use crate::state::AppState;
+use log::{info, warn};
+use rocket::http::Method;
+use std::sync::RwLock;
+
+/// Write requests that keep working during maintenance: logging in, switching maintenance
+/// off again, optimizing the database and npm audit, which only reads despite being a POST
+const MAINTENANCE_EXEMPT_PATHS: &[&str] = &[
+    "/api/v1/login",
+    "/api/v1/admin/maintenance",
+    "/api/v1/admin/database/optimize",
+    "/registry/-/npm/v1/security/",
+];
+
+/// Runtime maintenance toggle. While it is enabled write requests are answered with 503.
+#[derive(Debug)]
+pub struct MaintenanceMode {
+    status: RwLock<MaintenanceStatus>,
+}
+
+impl MaintenanceMode {
+    pub fn new(enabled: bool, retry_after_secs: u64) -> Self {
+        Self {
+            status: RwLock::new(MaintenanceStatus {
+                enabled,
+                retry_after_secs,
+                enabled_at: enabled.then(|| chrono::Utc::now().naive_utc()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.status.read().unwrap().enabled
+    }
+
+    /// Whether maintenance mode turns a request away
+    pub fn blocks(method: Method, path: &str) -> bool {
+        if matches!(method, Method::Get | Method::Head | Method::Options) {
+            return false;
+        }
+
+        !MAINTENANCE_EXEMPT_PATHS
+            .iter()
+            .any(|exempt| match exempt.strip_suffix('/') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == *exempt,
+            })
+    }
+
+    /// Enables maintenance mode, or updates its message while enabled
+    pub fn enable(
+        request: MaintenanceRequest,
+        admin: &AuthenticatedUser,
+        state: &AppState,
+    ) -> MaintenanceStatus {
+        let status = {
+            let mut status = state.maintenance.status.write().unwrap();
+            if !status.enabled {
+                status.enabled_at = Some(chrono::Utc::now().naive_utc());
+            }
+            status.enabled = true;
+            status.message = request.message;
+            status.retry_after_secs = request
+                .retry_after_secs
+                .unwrap_or(state.config.maintenance_retry_after_secs);
+            status.enabled_by = Some(admin.username.clone());
+            status.clone()
+        };
+
+        info!("Admin {} enabled maintenance mode", admin.username);
+        Self::audit("maintenance.enable", status.message.clone(), admin, state);
+        status
+    }
+
+    pub fn disable(admin: &AuthenticatedUser, state: &AppState) -> MaintenanceStatus {
+        let status = {
+            let mut status = state.maintenance.status.write().unwrap();
+            status.enabled = false;
+            status.message = None;
+            status.enabled_at = None;
+            status.enabled_by = None;
+            status.clone()
+        };
+
+        info!("Admin {} disabled maintenance mode", admin.username);
+        Self::audit("maintenance.disable", None, admin, state);
+        status
+    }
+
+    fn audit(action: &str, details: Option<String>, admin: &AuthenticatedUser, state: &AppState) {
+        let entry = NewAuditLogEntry {
+            action: action.to_string(),
+            actor_id: Some(admin.user_id),
+            details,
+            ..Default::default()
+        };
+        if let Err(e) = state.database.create_audit_log_entry(&entry) {
+            warn!("Failed to write audit log entry {action}: {e}");
+        }
+    }
+}
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        Self::new(false, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_writes() {
+        assert!(!MaintenanceMode::blocks(Method::Get, "/registry/lodash"));
+        assert!(!MaintenanceMode::blocks(
+            Method::Head,
+            "/registry/lodash/-/lodash-4.17.21.tgz"
+        ));
+        assert!(MaintenanceMode::blocks(Method::Put, "/registry/lodash"));
+        assert!(MaintenanceMode::blocks(Method::Post, "/api/v1/register"));
+        assert!(MaintenanceMode::blocks(
+            Method::Delete,
+            "/api/v1/organizations/acme"
+        ));
+        assert!(!MaintenanceMode::blocks(Method::Post, "/api/v1/login"));
+        assert!(!MaintenanceMode::blocks(
+            Method::Delete,
+            "/api/v1/admin/maintenance"
+        ));
+        assert!(!MaintenanceMode::blocks(
+            Method::Post,
+            "/registry/-/npm/v1/security/audits/quick"
+        ));
+        assert!(!MaintenanceMode::blocks(
+            Method::Post,
+            "/api/v1/admin/database/optimize"
+        ));
+        assert!(MaintenanceMode::blocks(
+            Method::Post,
+            "/api/v1/admin/maintenance/other"
+        ));
+    }
+}
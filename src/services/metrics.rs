@@ -0,0 +1,172 @@
+use crate::models::{DatabasePoolStats, DiskEvictionStats};
+use crate::services::DiskWatermarkService;
+use crate::state::AppState;
+use std::fmt::Write;
+
+/// Renders registry metrics in the Prometheus text exposition format
+pub struct MetricsService;
+
+impl MetricsService {
+    pub fn render(state: &AppState) -> String {
+        let mut out = String::new();
+        Self::database(&mut out, &state.database.pool_stats());
+        Self::disk(&mut out, &DiskWatermarkService::stats(state));
+        out
+    }
+
+    fn database(out: &mut String, stats: &DatabasePoolStats) {
+        Self::metric(
+            out,
+            "clef_db_pool_max_connections",
+            "gauge",
+            "Maximum number of database connections",
+            &[("", stats.max_size as f64)],
+        );
+        Self::metric(
+            out,
+            "clef_db_pool_connections",
+            "gauge",
+            "Open database connections",
+            &[
+                ("state=\"in_use\"", stats.in_use as f64),
+                ("state=\"idle\"", stats.idle as f64),
+            ],
+        );
+        Self::metric(
+            out,
+            "clef_db_pool_checkouts_total",
+            "counter",
+            "Connections handed out by the pool",
+            &[("", stats.checkouts as f64)],
+        );
+        Self::metric(
+            out,
+            "clef_db_pool_checkout_timeouts_total",
+            "counter",
+            "Requests that gave up waiting for a connection",
+            &[("", stats.checkout_timeouts as f64)],
+        );
+        Self::metric(
+            out,
+            "clef_db_pool_wait_seconds_total",
+            "counter",
+            "Time spent waiting for a connection",
+            &[("", stats.avg_wait_ms * stats.checkouts as f64 / 1000.0)],
+        );
+        Self::metric(
+            out,
+            "clef_db_pool_wait_seconds_max",
+            "gauge",
+            "Longest wait for a connection",
+            &[("", stats.max_wait_ms / 1000.0)],
+        );
+        Self::metric(
+            out,
+            "clef_db_queries_total",
+            "counter",
+            "Database queries run",
+            &[("", stats.queries as f64)],
+        );
+        Self::metric(
+            out,
+            "clef_db_slow_queries_total",
+            "counter",
+            "Database queries slower than the slow query threshold",
+            &[("", stats.slow_queries as f64)],
+        );
+    }
+
+    fn disk(out: &mut String, stats: &DiskEvictionStats) {
+        if let Some(usage) = &stats.usage {
+            Self::metric(
+                out,
+                "clef_cache_disk_bytes",
+                "gauge",
+                "Size and free space of the cache volume",
+                &[
+                    ("state=\"total\"", usage.total_bytes as f64),
+                    ("state=\"available\"", usage.available_bytes as f64),
+                ],
+            );
+            Self::metric(
+                out,
+                "clef_cache_disk_used_ratio",
+                "gauge",
+                "Used share of the cache volume",
+                &[("", usage.used_percent() / 100.0)],
+            );
+        }
+        Self::metric(
+            out,
+            "clef_cache_disk_high_watermark_ratio",
+            "gauge",
+            "Used share of the cache volume that starts eviction, 0 when disabled",
+            &[("", stats.high_watermark_percent as f64 / 100.0)],
+        );
+        Self::metric(
+            out,
+            "clef_cache_watermark_evictions_total",
+            "counter",
+            "Times the cache volume crossed the high watermark",
+            &[("", stats.runs as f64)],
+        );
+        Self::metric(
+            out,
+            "clef_cache_evicted_files_total",
+            "counter",
+            "Cached tarballs removed to free space",
+            &[("", stats.files_evicted as f64)],
+        );
+        Self::metric(
+            out,
+            "clef_cache_evicted_bytes_total",
+            "counter",
+            "Bytes freed by removing cached tarballs",
+            &[("", stats.bytes_evicted as f64)],
+        );
+    }
+
+    fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, f64)]) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+        for (labels, value) in samples {
+            if labels.is_empty() {
+                let _ = writeln!(out, "{name} {value}");
+            } else {
+                let _ = writeln!(out, "{name}{{{labels}}} {value}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_database_metrics() {
+        let mut out = String::new();
+        MetricsService::database(
+            &mut out,
+            &DatabasePoolStats {
+                max_size: 20,
+                connections: 3,
+                in_use: 1,
+                idle: 2,
+                checkouts: 4,
+                checkout_timeouts: 0,
+                avg_wait_ms: 2.5,
+                max_wait_ms: 8.0,
+                queries: 42,
+                slow_queries: 1,
+            },
+        );
+
+        assert!(out.contains("# TYPE clef_db_pool_connections gauge\n"));
+        assert!(out.contains("clef_db_pool_connections{state=\"in_use\"} 1\n"));
+        assert!(out.contains("clef_db_pool_connections{state=\"idle\"} 2\n"));
+        assert!(out.contains("clef_db_pool_wait_seconds_total 0.01\n"));
+        assert!(out.contains("clef_db_pool_wait_seconds_max 0.008\n"));
+        assert!(out.contains("clef_db_slow_queries_total 1\n"));
+    }
+}
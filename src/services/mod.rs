@@ -1,8 +1,119 @@
+pub mod access_log;
+pub mod account;
+pub mod advisory;
+pub mod allowlist;
+pub mod archive;
 pub mod auth;
+pub mod bandwidth;
 pub mod cache;
+pub mod cache_analytics;
+pub mod changes_feed;
+pub mod client_certificates;
+pub mod dashboard;
+pub mod disk_watermark;
+pub mod doctor;
+pub mod downloads;
+pub mod events;
+pub mod feed;
+pub mod hooks;
+pub mod hot_cache;
+pub mod install_size;
+pub mod ip_filter;
+pub mod jobs;
+pub mod live_metrics;
+pub mod mailer;
+pub mod maintenance;
+pub mod metrics;
+pub mod name_blocklist;
+pub mod notifications;
+pub mod organization;
+pub mod package_files;
+pub mod pinned;
+pub mod plugins;
+pub mod policy;
+pub mod popular_refresh;
+pub mod prefetch;
+pub mod profile;
+pub mod provenance;
+pub mod quarantine;
+pub mod quota;
+pub mod rate_limit;
+pub mod readme;
 pub mod registry;
+pub mod repository_import;
+pub mod retention;
+pub mod scope_policy;
+pub mod search;
+pub mod seed;
+pub mod service_accounts;
+pub mod shared_redis;
+pub mod signing;
+pub mod storage;
+pub mod tasks;
+pub mod transfer;
+pub mod typosquat;
+pub mod unpublish;
+pub mod upstream_limit;
+pub mod verdaccio;
+pub mod visibility;
+pub mod yank;
 
 pub use crate::database::DatabaseService;
+pub use access_log::AccessLog;
+pub use account::AccountService;
+pub use advisory::AdvisoryService;
+pub use allowlist::AllowlistService;
+pub use archive::ArchiveService;
 pub use auth::AuthService;
+pub use bandwidth::BandwidthService;
 pub use cache::CacheService;
+pub use cache_analytics::CacheAnalyticsService;
+pub use changes_feed::{ChangesFeedCursor, ChangesFeedService};
+pub use client_certificates::ClientCertificateService;
+pub use dashboard::{DashboardCache, DashboardService};
+pub use disk_watermark::DiskWatermarkService;
+pub use doctor::DoctorService;
+pub use downloads::DownloadStatsService;
+pub use events::EventBus;
+pub use feed::FeedService;
+pub use hooks::HookService;
+pub use install_size::InstallSizeService;
+pub use ip_filter::IpFilter;
+pub use jobs::JobScheduler;
+pub use live_metrics::{LiveMetrics, LiveMetricsService, LiveMetricsSocket};
+pub use mailer::MailerService;
+pub use maintenance::MaintenanceMode;
+pub use metrics::MetricsService;
+pub use name_blocklist::NameBlocklistService;
+pub use notifications::NotificationService;
+pub use organization::OrganizationService;
+pub use package_files::PackageFilesService;
+pub use pinned::PinnedPackageService;
+pub use plugins::PluginHost;
+pub use policy::{PolicyEngine, PolicyService};
+pub use popular_refresh::PopularRefreshService;
+pub use prefetch::PrefetchService;
+pub use profile::ProfileService;
+pub use provenance::ProvenanceService;
+pub use quarantine::QuarantineService;
+pub use quota::QuotaService;
+pub use rate_limit::RateLimiter;
+pub use readme::ReadmeService;
 pub use registry::RegistryService;
+pub use repository_import::RepositoryImportService;
+pub use retention::RetentionService;
+pub use scope_policy::ScopePolicyService;
+pub use search::SearchService;
+pub use seed::SeedService;
+pub use service_accounts::ServiceAccountService;
+pub use shared_redis::SharedRedis;
+pub use signing::SigningService;
+pub use storage::StorageService;
+pub use tasks::TaskService;
+pub use transfer::TransferService;
+pub use typosquat::TyposquatService;
+pub use unpublish::UnpublishService;
+pub use upstream_limit::{UpstreamLimiter, UpstreamPermit};
+pub use verdaccio::VerdaccioImportService;
+pub use visibility::VisibilityService;
+pub use yank::YankService;
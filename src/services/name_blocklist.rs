@@ -0,0 +1,290 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, BlockedName, BlockedNameListResponse, BlockedNameMatch, BlockedNameRequest,
+    BlockedNameSource, NewBlockedName,
+};
+use crate::state::AppState;
+use log::{info, warn};
+use regex::Regex;
+
+/// Built-in Node.js modules. Publishing these would shadow the core module for anyone who
+/// `require`s the name, so they are reserved like on the public registry.
+const NODE_CORE_MODULES: &[&str] = &[
+    "assert",
+    "async_hooks",
+    "buffer",
+    "child_process",
+    "cluster",
+    "console",
+    "constants",
+    "crypto",
+    "dgram",
+    "diagnostics_channel",
+    "dns",
+    "domain",
+    "events",
+    "fs",
+    "http",
+    "http2",
+    "https",
+    "inspector",
+    "module",
+    "net",
+    "os",
+    "path",
+    "perf_hooks",
+    "process",
+    "punycode",
+    "querystring",
+    "readline",
+    "repl",
+    "stream",
+    "string_decoder",
+    "sys",
+    "timers",
+    "tls",
+    "trace_events",
+    "tty",
+    "url",
+    "util",
+    "v8",
+    "vm",
+    "wasi",
+    "worker_threads",
+    "zlib",
+];
+
+pub struct NameBlocklistService;
+
+impl NameBlocklistService {
+    /// First blocklist entry matching a package name, checking admin entries, configured
+    /// entries and reserved core module names in that order
+    pub fn find_match(
+        package: &str,
+        state: &AppState,
+    ) -> Result<Option<BlockedNameMatch>, ApiError> {
+        let entries = state
+            .database
+            .list_blocked_names()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if let Some(entry) = entries
+            .into_iter()
+            .find(|entry| Self::matches(&entry.pattern, entry.is_regex, package))
+        {
+            return Ok(Some(BlockedNameMatch {
+                pattern: entry.pattern,
+                source: BlockedNameSource::Admin,
+                block_upstream: entry.block_upstream,
+                reason: entry.reason,
+            }));
+        }
+
+        if let Some(entry) = state.config.blocked_names.iter().find(|entry| {
+            let (pattern, is_regex) = Self::parse_configured(entry);
+            Self::matches(pattern, is_regex, package)
+        }) {
+            return Ok(Some(BlockedNameMatch {
+                pattern: entry.clone(),
+                source: BlockedNameSource::Config,
+                block_upstream: state.config.blocked_names_upstream,
+                reason: None,
+            }));
+        }
+
+        if state.config.reserve_node_core_names && Self::is_node_core_module(package) {
+            return Ok(Some(BlockedNameMatch {
+                pattern: package.to_lowercase(),
+                source: BlockedNameSource::NodeCore,
+                // The public registry serves userland polyfills under some of these names
+                block_upstream: false,
+                reason: Some("name of a Node.js core module".to_string()),
+            }));
+        }
+
+        Ok(None)
+    }
+
+    /// Rejects publishing a blocked name
+    pub fn check_publish(package: &str, state: &AppState) -> Result<(), ApiError> {
+        match Self::find_match(package, state)? {
+            Some(blocked) => {
+                info!(
+                    "Rejected publish of {package}, blocked by {:?} entry '{}'",
+                    blocked.source, blocked.pattern
+                );
+                Err(Self::blocked_error(package, &blocked))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Rejects proxying a blocked name from upstream, for entries that ask for it
+    pub fn check_upstream(package: &str, state: &AppState) -> Result<(), ApiError> {
+        match Self::find_match(package, state)? {
+            Some(blocked) if blocked.block_upstream => {
+                info!(
+                    "Not proxying {package} from upstream, blocked by '{}'",
+                    blocked.pattern
+                );
+                Err(Self::blocked_error(package, &blocked))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn list(state: &AppState) -> Result<BlockedNameListResponse, ApiError> {
+        let entries = state
+            .database
+            .list_blocked_names()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(BlockedNameListResponse {
+            entries,
+            configured: state.config.blocked_names.clone(),
+            configured_block_upstream: state.config.blocked_names_upstream,
+            node_core_reserved: state.config.reserve_node_core_names,
+        })
+    }
+
+    /// Adds a blocklist entry. Exact names are stored lowercase, regular expressions are
+    /// validated before they are saved.
+    pub fn add_entry(
+        request: BlockedNameRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<BlockedName, ApiError> {
+        let is_regex = request.is_regex.unwrap_or(false);
+        let pattern = request.pattern.trim();
+        if pattern.is_empty() {
+            return Err(ApiError::BadRequest(
+                "Pattern must not be empty".to_string(),
+            ));
+        }
+
+        let pattern = if is_regex {
+            Regex::new(pattern)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid regular expression: {e}")))?;
+            pattern.to_string()
+        } else {
+            pattern.to_lowercase()
+        };
+
+        let entry = state
+            .database
+            .create_blocked_name(&NewBlockedName {
+                pattern: pattern.clone(),
+                is_regex,
+                block_upstream: request.block_upstream.unwrap_or(false),
+                reason: request.reason,
+                created_by: Some(actor.user_id),
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::UniqueViolation,
+                    _,
+                ) => ApiError::Conflict(format!("'{pattern}' is already blocked")),
+                _ => ApiError::InternalServerError(format!("Database error: {e}")),
+            })?;
+
+        info!(
+            "User {} blocked {} '{}' (upstream: {})",
+            actor.username,
+            if entry.is_regex { "pattern" } else { "name" },
+            entry.pattern,
+            entry.block_upstream
+        );
+
+        Ok(entry)
+    }
+
+    pub fn delete_entry(id: i32, state: &AppState) -> Result<(), ApiError> {
+        let deleted = state
+            .database
+            .delete_blocked_name(id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if deleted == 0 {
+            return Err(ApiError::NotFound(format!(
+                "Blocklist entry {id} not found"
+            )));
+        }
+
+        info!("Deleted blocklist entry {id}");
+        Ok(())
+    }
+
+    fn blocked_error(package: &str, blocked: &BlockedNameMatch) -> ApiError {
+        match &blocked.reason {
+            Some(reason) => {
+                ApiError::Forbidden(format!("Package name '{package}' is blocked: {reason}"))
+            }
+            None => ApiError::Forbidden(format!("Package name '{package}' is blocked")),
+        }
+    }
+
+    /// Configured entries wrapped in slashes are regular expressions
+    fn parse_configured(entry: &str) -> (&str, bool) {
+        match entry
+            .strip_prefix('/')
+            .and_then(|entry| entry.strip_suffix('/'))
+        {
+            Some(pattern) if !pattern.is_empty() => (pattern, true),
+            _ => (entry, false),
+        }
+    }
+
+    pub(crate) fn matches(pattern: &str, is_regex: bool, package: &str) -> bool {
+        if !is_regex {
+            return pattern.eq_ignore_ascii_case(package);
+        }
+
+        match Regex::new(pattern) {
+            Ok(regex) => regex.is_match(package),
+            Err(e) => {
+                warn!("Ignoring invalid name pattern '{pattern}': {e}");
+                false
+            }
+        }
+    }
+
+    fn is_node_core_module(package: &str) -> bool {
+        NODE_CORE_MODULES.contains(&package.to_lowercase().as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_configured() {
+        assert_eq!(
+            NameBlocklistService::parse_configured("/^malware-/"),
+            ("^malware-", true)
+        );
+        assert_eq!(
+            NameBlocklistService::parse_configured("evil-pkg"),
+            ("evil-pkg", false)
+        );
+        assert_eq!(NameBlocklistService::parse_configured("/"), ("/", false));
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(NameBlocklistService::matches("evil-pkg", false, "Evil-Pkg"));
+        assert!(!NameBlocklistService::matches(
+            "evil-pkg",
+            false,
+            "evil-pkg2"
+        ));
+        assert!(NameBlocklistService::matches(
+            "^malware-",
+            true,
+            "malware-x"
+        ));
+        assert!(!NameBlocklistService::matches("(", true, "anything"));
+        assert!(NameBlocklistService::is_node_core_module("fs"));
+        assert!(!NameBlocklistService::is_node_core_module("fs-extra"));
+    }
+}
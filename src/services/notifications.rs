@@ -0,0 +1,506 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, NOTIFICATION_EVENTS, NewNotification, NewNotificationSubscription,
+    Notification, NotificationSubscription, NotificationSubscriptionRequest,
+    NotificationSubscriptionResponse, NotificationSubscriptionsResponse, RegistryEvent,
+};
+use crate::services::MailerService;
+use crate::state::AppState;
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use log::{debug, info, warn};
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Immediate notifications still pending after this long are retried by the scheduler
+const RETRY_AFTER_MINUTES: i64 = 10;
+
+/// Notifications are dropped after this many days, sent or not
+const NOTIFICATION_RETENTION_DAYS: i64 = 30;
+
+/// How long a Slack, Discord or webhook endpoint has to answer
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Discord rejects messages longer than this
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+const CHANNELS: [&str; 4] = ["email", "slack", "discord", "webhook"];
+const FREQUENCIES: [&str; 3] = ["immediate", "daily", "weekly"];
+
+/// Notifies users about new versions, deprecations and security advisories of the packages
+/// and scopes they subscribed to. Events are queued per subscription and sent right away or
+/// collected into daily or weekly digests, by email or to a Slack, Discord or plain webhook.
+pub struct NotificationService;
+
+impl NotificationService {
+    pub fn subscribe(
+        request: NotificationSubscriptionRequest,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<NotificationSubscriptionResponse, ApiError> {
+        let name = request.name.trim().to_string();
+        if name.starts_with('@') && !name.contains('/') {
+            if name.len() < 2 {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid scope '{name}', expected @scope"
+                )));
+            }
+        } else {
+            let can_read = state
+                .database
+                .has_read_permission(&name, Some(user.user_id))
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+            if name.is_empty() || !can_read {
+                return Err(ApiError::NotFound(format!("Package '{name}' not found")));
+            }
+        }
+
+        let events = match request.events {
+            Some(events) if events.is_empty() => {
+                return Err(ApiError::BadRequest(
+                    "Subscribe to at least one event".to_string(),
+                ));
+            }
+            Some(events) => {
+                if let Some(invalid) = events
+                    .iter()
+                    .find(|event| !NOTIFICATION_EVENTS.contains(&event.as_str()))
+                {
+                    return Err(ApiError::BadRequest(format!(
+                        "Invalid event '{invalid}', expected one of {}",
+                        NOTIFICATION_EVENTS.join(", ")
+                    )));
+                }
+                NOTIFICATION_EVENTS
+                    .iter()
+                    .filter(|event| events.iter().any(|e| e == *event))
+                    .copied()
+                    .collect::<Vec<_>>()
+            }
+            None => NOTIFICATION_EVENTS.to_vec(),
+        };
+
+        let channel = request.channel.to_lowercase();
+        let endpoint = match channel.as_str() {
+            "email" if !MailerService::is_configured(&state.config) => {
+                return Err(ApiError::BadRequest(
+                    "Email notifications need SMTP to be configured".to_string(),
+                ));
+            }
+            "email" => None,
+            _ if CHANNELS.contains(&channel.as_str()) => {
+                let endpoint = request.endpoint.unwrap_or_default();
+                match reqwest::Url::parse(&endpoint) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") => Some(endpoint),
+                    _ => {
+                        return Err(ApiError::BadRequest(format!(
+                            "Invalid {channel} endpoint '{endpoint}', expected an http(s) URL"
+                        )));
+                    }
+                }
+            }
+            _ => {
+                return Err(ApiError::BadRequest(format!(
+                    "Invalid channel '{channel}', expected one of {}",
+                    CHANNELS.join(", ")
+                )));
+            }
+        };
+
+        let frequency = request
+            .frequency
+            .map(|f| f.to_lowercase())
+            .unwrap_or_else(|| "immediate".to_string());
+        if !FREQUENCIES.contains(&frequency.as_str()) {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid frequency '{frequency}', expected one of {}",
+                FREQUENCIES.join(", ")
+            )));
+        }
+
+        let subscription = state
+            .database
+            .create_notification_subscription(&NewNotificationSubscription {
+                user_id: user.user_id,
+                name,
+                events: events.join(","),
+                channel,
+                endpoint,
+                frequency,
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!(
+            "User {} subscribed to {} notifications for {} ({})",
+            user.username, subscription.channel, subscription.name, subscription.frequency
+        );
+        Ok(subscription.into())
+    }
+
+    pub fn list(
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<NotificationSubscriptionsResponse, ApiError> {
+        let subscriptions = state
+            .database
+            .list_notification_subscriptions(Some(user.user_id))
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(NotificationSubscriptionsResponse {
+            subscriptions: subscriptions.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    pub fn unsubscribe(
+        id: i32,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        state
+            .database
+            .get_notification_subscription(id)
+            .map_err(db_error)?
+            .filter(|subscription| subscription.user_id == user.user_id)
+            .ok_or_else(|| ApiError::NotFound(format!("Subscription {id} not found")))?;
+        state
+            .database
+            .delete_notification_subscription(id)
+            .map_err(db_error)?;
+
+        info!(
+            "User {} removed notification subscription {id}",
+            user.username
+        );
+        Ok(())
+    }
+
+    /// Queues notifications for registry events as they are emitted and sends those of
+    /// immediate subscriptions
+    pub fn spawn_dispatcher(state: AppState) {
+        let mut events = state.events.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        let state = state.clone();
+                        tokio::spawn(async move { Self::dispatch(event, &state).await });
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Notification dispatcher fell behind, {missed} events were missed");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    async fn dispatch(event: RegistryEvent, state: &AppState) {
+        let Some((kind, package, version, message)) = Self::notification(&event) else {
+            return;
+        };
+
+        let subscriptions = match state.database.list_notification_subscriptions(None) {
+            Ok(subscriptions) => subscriptions,
+            Err(e) => {
+                warn!("Failed to load notification subscriptions for {package}: {e}");
+                return;
+            }
+        };
+        let matching: Vec<NotificationSubscription> = subscriptions
+            .into_iter()
+            .filter(|s| s.matches(package) && s.events().any(|e| e == kind))
+            // Subscribers only hear about packages they can read
+            .filter(|s| {
+                state
+                    .database
+                    .has_read_permission(package, Some(s.user_id))
+                    .unwrap_or(false)
+            })
+            .collect();
+        if matching.is_empty() {
+            return;
+        }
+
+        let entries: Vec<NewNotification> = matching
+            .iter()
+            .map(|subscription| NewNotification {
+                subscription_id: subscription.id,
+                event: kind.to_string(),
+                package_name: package.to_string(),
+                version: version.map(str::to_string),
+                message: message.clone(),
+            })
+            .collect();
+        if let Err(e) = state.database.create_notifications(&entries) {
+            warn!("Failed to queue {kind} notifications for {package}: {e}");
+            return;
+        }
+        debug!(
+            "Queued {kind} notifications for {package} to {} subscriptions",
+            entries.len()
+        );
+
+        for subscription in matching.iter().filter(|s| s.frequency == "immediate") {
+            if let Err(e) = Self::send(subscription, state).await {
+                warn!("Failed to send notification {}: {e:?}", subscription.id);
+            }
+        }
+    }
+
+    /// Sends digests that are due and retries failed deliveries, returning how many
+    /// subscriptions were sent to
+    pub async fn send_due(state: &AppState) -> Result<usize, ApiError> {
+        let now = Utc::now().naive_utc();
+        let cutoff = now - ChronoDuration::days(NOTIFICATION_RETENTION_DAYS);
+        match state.database.delete_notifications_before(cutoff) {
+            Ok(0) => {}
+            Ok(deleted) => debug!("Deleted {deleted} old notifications"),
+            Err(e) => warn!("Failed to delete old notifications: {e}"),
+        }
+
+        let subscriptions = state
+            .database
+            .get_subscriptions_with_pending_notifications()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let mut sent = 0;
+        for subscription in subscriptions {
+            let oldest = match state.database.get_pending_notifications(subscription.id) {
+                Ok(pending) => pending.first().map(|n| n.created_at),
+                Err(e) => {
+                    warn!(
+                        "Failed to load notifications of subscription {}: {e}",
+                        subscription.id
+                    );
+                    continue;
+                }
+            };
+            if !oldest.is_some_and(|oldest| Self::is_due(&subscription, oldest, now)) {
+                continue;
+            }
+            match Self::send(&subscription, state).await {
+                Ok(()) => sent += 1,
+                Err(e) => warn!(
+                    "Failed to send notifications of subscription {}: {e:?}",
+                    subscription.id
+                ),
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Sends the pending notifications of a subscription, as one message
+    async fn send(
+        subscription: &NotificationSubscription,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let pending = state
+            .database
+            .get_pending_notifications(subscription.id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let (subject, body) = Self::compose(&subscription.name, &pending);
+        match (subscription.channel.as_str(), &subscription.endpoint) {
+            ("email", _) => {
+                let user = state
+                    .database
+                    .get_user_by_id(subscription.user_id)
+                    .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+                    .ok_or_else(|| {
+                        ApiError::NotFound(format!("User {} not found", subscription.user_id))
+                    })?;
+                MailerService::send(state, &user.email, &subject, body).await?;
+            }
+            ("slack", Some(endpoint)) => {
+                Self::post(endpoint, json!({ "text": body }), state).await?;
+            }
+            ("discord", Some(endpoint)) => {
+                let content: String = body.chars().take(DISCORD_MESSAGE_LIMIT).collect();
+                Self::post(endpoint, json!({ "content": content }), state).await?;
+            }
+            ("webhook", Some(endpoint)) => {
+                let payload = json!({
+                    "subscription": subscription.name,
+                    "notifications": pending,
+                });
+                Self::post(endpoint, payload, state).await?;
+            }
+            (channel, _) => {
+                return Err(ApiError::InternalServerError(format!(
+                    "Subscription {} has no endpoint for channel {channel}",
+                    subscription.id
+                )));
+            }
+        }
+
+        let ids: Vec<i32> = pending.iter().map(|n| n.id).collect();
+        state
+            .database
+            .mark_notifications_sent(subscription.id, &ids, Utc::now().naive_utc())
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!(
+            "Sent {} notifications of subscription {} by {}",
+            ids.len(),
+            subscription.id,
+            subscription.channel
+        );
+        Ok(())
+    }
+
+    async fn post(
+        endpoint: &str,
+        payload: serde_json::Value,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let response = state
+            .client
+            .post(endpoint)
+            .json(&payload)
+            .timeout(DELIVERY_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| ApiError::UpstreamError(format!("Notification delivery failed: {e}")))?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::UpstreamError(format!(
+                "Notification endpoint answered {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Kind, package, version and text of the events subscribers are notified about.
+    /// Yanked versions are deprecated in the package document, so yanks count as
+    /// deprecations.
+    fn notification(event: &RegistryEvent) -> Option<(&'static str, &str, Option<&str>, String)> {
+        match event {
+            RegistryEvent::VersionPublished {
+                package, version, ..
+            } => Some((
+                "version",
+                package,
+                Some(version),
+                format!("{package}@{version} was published"),
+            )),
+            RegistryEvent::VersionYanked {
+                package,
+                version,
+                yanked: true,
+                ..
+            } => Some((
+                "deprecation",
+                package,
+                Some(version),
+                format!("{package}@{version} was deprecated"),
+            )),
+            RegistryEvent::AdvisoryPublished {
+                package,
+                advisory_id,
+                title,
+                severity,
+            } => Some((
+                "advisory",
+                package,
+                None,
+                format!("{package} has a new {severity} severity advisory {advisory_id}: {title}"),
+            )),
+            RegistryEvent::VersionYanked { .. }
+            | RegistryEvent::VersionUnpublished { .. }
+            | RegistryEvent::PackageVisibilityChanged { .. }
+            | RegistryEvent::CacheEvicted { .. } => None,
+        }
+    }
+
+    /// Whether a subscription with pending notifications, the oldest queued at `oldest`,
+    /// should be sent now
+    fn is_due(
+        subscription: &NotificationSubscription,
+        oldest: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> bool {
+        let since = subscription.last_sent_at.unwrap_or(subscription.created_at);
+        match subscription.frequency.as_str() {
+            "daily" => now - since >= ChronoDuration::days(1),
+            "weekly" => now - since >= ChronoDuration::weeks(1),
+            _ => now - oldest >= ChronoDuration::minutes(RETRY_AFTER_MINUTES),
+        }
+    }
+
+    /// Subject and plain text body of a message with one or more notifications
+    fn compose(name: &str, notifications: &[Notification]) -> (String, String) {
+        match notifications {
+            [notification] => (
+                notification.message.clone(),
+                format!("{}\n", notification.message),
+            ),
+            _ => {
+                let subject = format!("{} updates for {name}", notifications.len());
+                let body = notifications
+                    .iter()
+                    .map(|n| format!("- {}\n", n.message))
+                    .collect();
+                (subject, body)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(name: &str, frequency: &str) -> NotificationSubscription {
+        NotificationSubscription {
+            id: 1,
+            user_id: 1,
+            name: name.to_string(),
+            events: "version,advisory".to_string(),
+            channel: "slack".to_string(),
+            endpoint: Some("https://hooks.slack.com/services/T0/B0/X".to_string()),
+            frequency: frequency.to_string(),
+            last_sent_at: None,
+            created_at: NaiveDateTime::parse_from_str("2025-08-01 00:00:00", "%Y-%m-%d %H:%M:%S")
+                .unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_subscription_matches() {
+        assert!(subscription("@acme", "immediate").matches("@acme/ui"));
+        assert!(!subscription("@acme", "immediate").matches("@acmecorp/ui"));
+        assert!(!subscription("@acme", "immediate").matches("acme"));
+        assert!(subscription("left-pad", "immediate").matches("left-pad"));
+        assert!(!subscription("left-pad", "immediate").matches("left-pad-2"));
+        assert_eq!(
+            subscription("@acme", "daily").events().collect::<Vec<_>>(),
+            vec!["version", "advisory"]
+        );
+    }
+
+    #[test]
+    fn test_is_due() {
+        let daily = subscription("@acme", "daily");
+        let created = daily.created_at;
+        let hours = |h| created + ChronoDuration::hours(h);
+
+        assert!(!NotificationService::is_due(&daily, created, hours(23)));
+        assert!(NotificationService::is_due(&daily, created, hours(24)));
+        assert!(!NotificationService::is_due(
+            &subscription("@acme", "weekly"),
+            created,
+            hours(24)
+        ));
+
+        let immediate = subscription("@acme", "immediate");
+        assert!(!NotificationService::is_due(&immediate, hours(5), hours(5)));
+        assert!(NotificationService::is_due(&immediate, hours(5), hours(6)));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::ApiError;
+use crate::models::{
+    MemberPublishActivity, Organization, OrganizationUsageResponse, PackageListResponse,
+};
+use crate::services::QuotaService;
+use crate::state::AppState;
+use chrono::{Duration, NaiveDate, Utc};
+
+/// Views over everything published under an organization's scope
+pub struct OrganizationService;
+
+impl OrganizationService {
+    /// A page of the organization's packages sorted by name, private ones included
+    pub fn packages(
+        organization: &Organization,
+        limit: Option<i64>,
+        page: Option<i64>,
+        search: Option<&str>,
+        state: &AppState,
+    ) -> Result<PackageListResponse, ApiError> {
+        let limit = limit.unwrap_or(20).clamp(1, 100);
+        let page = page.unwrap_or(1).max(1);
+
+        let (packages, total_count) = state
+            .database
+            .get_packages_paginated(
+                limit,
+                (page - 1) * limit,
+                search,
+                Some("name"),
+                Some("asc"),
+                true,
+                Some(organization.id),
+            )
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(PackageListResponse::new(packages, total_count, page, limit))
+    }
+
+    /// Storage against the quota, downloads of all packages and publishes per member.
+    /// Publishes are counted from the audit log.
+    pub fn usage(
+        organization: &Organization,
+        state: &AppState,
+    ) -> Result<OrganizationUsageResponse, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        let storage = QuotaService::organization_usage(organization.id, &organization.name, state)?;
+
+        let names: Vec<String> = state
+            .database
+            .get_packages_by_organization(organization.id)
+            .map_err(db_error)?
+            .into_iter()
+            .map(|package| package.name)
+            .collect();
+        let count_since = |since: NaiveDate| {
+            state
+                .database
+                .get_downloads_since(&names, since)
+                .map(|downloads| downloads.values().sum::<i64>())
+                .map_err(db_error)
+        };
+        let downloads = count_since(NaiveDate::default())?;
+        let downloads_last_30_days = count_since(Utc::now().date_naive() - Duration::days(30))?;
+
+        let activity = state
+            .database
+            .get_organization_publish_activity(organization.id)
+            .map_err(db_error)?;
+        let mut members: Vec<MemberPublishActivity> = state
+            .database
+            .get_organization_members(organization.id)
+            .map_err(db_error)?
+            .into_iter()
+            .map(|member| {
+                let published = activity
+                    .iter()
+                    .find(|(user_id, _, _)| *user_id == member.member.user_id);
+                MemberPublishActivity {
+                    username: member.username,
+                    role: member.member.role,
+                    publishes: published.map_or(0, |(_, count, _)| *count),
+                    last_published_at: published.map(|(_, _, latest)| *latest),
+                }
+            })
+            .collect();
+        members.sort_by(|a, b| {
+            b.publishes
+                .cmp(&a.publishes)
+                .then_with(|| a.username.cmp(&b.username))
+        });
+
+        Ok(OrganizationUsageResponse {
+            storage,
+            downloads,
+            downloads_last_30_days,
+            members,
+        })
+    }
+}
@@ -0,0 +1,269 @@
+use crate::error::ApiError;
+use crate::models::{NewTarballEntry, PackageFilesResponse, PackageVersionWithFiles, TarballEntry};
+use crate::state::AppState;
+use flate2::read::GzDecoder;
+use log::info;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Lists the files inside version tarballs. A tarball is read once, the first time its files
+/// are requested, and the listing is stored in the database.
+pub struct PackageFilesService;
+
+impl PackageFilesService {
+    /// Files of a version, callers check read access first
+    pub fn list(
+        name: &str,
+        version: &str,
+        state: &AppState,
+    ) -> Result<PackageFilesResponse, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+        let not_found = || ApiError::NotFound(format!("Package '{name}' not found"));
+
+        let package = state
+            .database
+            .get_package_with_versions(name)
+            .map_err(db_error)?
+            .ok_or_else(not_found)?;
+        let entry = package
+            .versions
+            .iter()
+            .find(|v| v.version.version == version)
+            .ok_or_else(|| {
+                ApiError::NotFound(format!("Package '{name}' version '{version}' not found"))
+            })?;
+
+        let files = Self::entries(name, entry, state)?.ok_or_else(|| {
+            ApiError::NotFound(format!("The tarball of {name}@{version} is not cached"))
+        })?;
+
+        Ok(PackageFilesResponse {
+            name: name.to_string(),
+            version: version.to_string(),
+            total_size_bytes: files.iter().map(|f| f.size_bytes).sum(),
+            files,
+        })
+    }
+
+    /// Files of a version, indexing its tarball if that wasn't done yet. `None` when the
+    /// tarball isn't on disk.
+    pub fn entries(
+        name: &str,
+        entry: &PackageVersionWithFiles,
+        state: &AppState,
+    ) -> Result<Option<Vec<TarballEntry>>, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+        let version = &entry.version.version;
+
+        let files = state
+            .database
+            .get_tarball_entries(entry.version.id)
+            .map_err(db_error)?;
+        if !files.is_empty() {
+            return Ok(Some(files));
+        }
+
+        let Some(tarball) = entry
+            .files
+            .iter()
+            .find(|f| f.filename.ends_with(".tgz"))
+            .and_then(|f| fs::read(&f.file_path).ok())
+        else {
+            return Ok(None);
+        };
+
+        let entries = Self::index(&tarball)?
+            .into_iter()
+            .map(|(path, size_bytes)| NewTarballEntry {
+                package_version_id: entry.version.id,
+                content_type: Self::content_type(&path).to_string(),
+                path,
+                size_bytes,
+            })
+            .collect::<Vec<_>>();
+        state
+            .database
+            .create_tarball_entries(&entries)
+            .map_err(db_error)?;
+        info!("Indexed {} files of {name}@{version}", entries.len());
+
+        state
+            .database
+            .get_tarball_entries(entry.version.id)
+            .map(Some)
+            .map_err(db_error)
+    }
+
+    /// Paths and sizes of the regular files in a gzipped tarball, relative to the package
+    /// root. npm packs everything under `package/`, but some tarballs use another top level
+    /// directory, so the first component is dropped whatever it is.
+    pub fn index(tarball: &[u8]) -> Result<Vec<(String, i64)>, ApiError> {
+        let invalid = |e: std::io::Error| ApiError::ParseError(format!("Invalid tarball: {e}"));
+
+        let mut archive = tar::Archive::new(GzDecoder::new(tarball));
+        let mut files = Vec::new();
+        for entry in archive.entries().map_err(invalid)? {
+            let mut entry = entry.map_err(invalid)?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let relative = Self::relative_path(&entry.path().map_err(invalid)?);
+            if relative.is_empty() {
+                continue;
+            }
+            let size = entry.size() as i64;
+            files.push((relative, size));
+            // Drain the contents so the next header can be read
+            std::io::copy(&mut entry.by_ref(), &mut std::io::sink()).map_err(invalid)?;
+        }
+
+        Ok(files)
+    }
+
+    /// Contents of one file of a gzipped tarball, `path` relative to the package root
+    pub fn read_file(tarball: &[u8], path: &str) -> Result<Option<Vec<u8>>, ApiError> {
+        let invalid = |e: std::io::Error| ApiError::ParseError(format!("Invalid tarball: {e}"));
+
+        let mut archive = tar::Archive::new(GzDecoder::new(tarball));
+        for entry in archive.entries().map_err(invalid)? {
+            let mut entry = entry.map_err(invalid)?;
+            if !entry.header().entry_type().is_file()
+                || Self::relative_path(&entry.path().map_err(invalid)?) != path
+            {
+                continue;
+            }
+            let mut contents = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut contents).map_err(invalid)?;
+            return Ok(Some(contents));
+        }
+
+        Ok(None)
+    }
+
+    fn relative_path(path: &Path) -> String {
+        path.components()
+            .skip(1)
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Media type of a file from its extension, for the file browser to decide how to show it
+    pub fn content_type(path: &str) -> &'static str {
+        let filename = path.rsplit('/').next().unwrap_or(path);
+        let extension = filename
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "js" | "mjs" | "cjs" => "application/javascript",
+            "ts" | "mts" | "cts" | "tsx" => "application/typescript",
+            "jsx" => "text/jsx",
+            "json" | "map" => "application/json",
+            "md" | "markdown" => "text/markdown",
+            "html" | "htm" => "text/html",
+            "css" => "text/css",
+            "scss" | "sass" | "less" => "text/x-scss",
+            "xml" => "application/xml",
+            "yml" | "yaml" => "application/yaml",
+            "txt" => "text/plain",
+            "svg" => "image/svg+xml",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "ico" => "image/x-icon",
+            "woff" => "font/woff",
+            "woff2" => "font/woff2",
+            "ttf" => "font/ttf",
+            "wasm" => "application/wasm",
+            "node" => "application/octet-stream",
+            _ if matches!(
+                filename.to_ascii_uppercase().as_str(),
+                "LICENSE" | "LICENCE" | "README" | "CHANGELOG" | "AUTHORS" | "NOTICE"
+            ) =>
+            {
+                "text/plain"
+            }
+            _ => "application/octet-stream",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    fn tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_index() {
+        let data = tarball(&[
+            ("package/package.json", b"{}"),
+            ("package/lib/index.js", b"module.exports = 1;\n"),
+            ("node/LICENSE", b"MIT"),
+        ]);
+        let files = PackageFilesService::index(&data).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                ("package.json".to_string(), 2),
+                ("lib/index.js".to_string(), 20),
+                ("LICENSE".to_string(), 3),
+            ]
+        );
+
+        assert!(PackageFilesService::index(b"not a tarball").is_err());
+    }
+
+    #[test]
+    fn test_read_file() {
+        let data = tarball(&[
+            ("package/package.json", b"{}"),
+            ("package/dist/app.css", b"body {}"),
+        ]);
+        assert_eq!(
+            PackageFilesService::read_file(&data, "dist/app.css").unwrap(),
+            Some(b"body {}".to_vec())
+        );
+        assert_eq!(PackageFilesService::read_file(&data, "dist").unwrap(), None);
+        assert_eq!(
+            PackageFilesService::read_file(&data, "package/package.json").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            PackageFilesService::content_type("dist/index.mjs"),
+            "application/javascript"
+        );
+        assert_eq!(
+            PackageFilesService::content_type("types/index.d.ts"),
+            "application/typescript"
+        );
+        assert_eq!(
+            PackageFilesService::content_type("README.MD"),
+            "text/markdown"
+        );
+        assert_eq!(PackageFilesService::content_type("LICENSE"), "text/plain");
+        assert_eq!(
+            PackageFilesService::content_type("bin/addon"),
+            "application/octet-stream"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, NewPinnedPackage, PinnedPackage, PinnedPackageListResponse,
+    PinnedPackageRequest, PinnedRefreshReport, PrefetchFailure,
+};
+use crate::services::RegistryService;
+use crate::state::AppState;
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub struct PinnedPackageService;
+
+impl PinnedPackageService {
+    /// Names of all pinned packages, configured and admin managed
+    pub fn names(state: &AppState) -> Result<BTreeSet<String>, ApiError> {
+        let entries = state
+            .database
+            .list_pinned_packages()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(state
+            .config
+            .pinned_packages
+            .iter()
+            .cloned()
+            .chain(entries.into_iter().map(|entry| entry.name))
+            .collect())
+    }
+
+    /// Keeps the metadata of pinned packages in the in-memory cache
+    fn keep_in_memory(state: &AppState) {
+        match Self::names(state) {
+            Ok(names) => state.cache.set_pinned(names),
+            Err(e) => warn!("Failed to update pinned packages of the metadata cache: {e:?}"),
+        }
+    }
+
+    pub fn list(state: &AppState) -> Result<PinnedPackageListResponse, ApiError> {
+        let entries = state
+            .database
+            .list_pinned_packages()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(PinnedPackageListResponse {
+            entries,
+            configured: state.config.pinned_packages.clone(),
+            refresh_minutes: state.config.pinned_refresh_minutes,
+        })
+    }
+
+    /// Pins a package and warms it in the background
+    pub fn add(
+        request: PinnedPackageRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<PinnedPackage, ApiError> {
+        let name = request.name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid package name '{name}'"
+            )));
+        }
+
+        let entry = state
+            .database
+            .create_pinned_package(&NewPinnedPackage {
+                name: name.to_string(),
+                created_by: Some(actor.user_id),
+            })
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::UniqueViolation,
+                    _,
+                ) => ApiError::Conflict(format!("'{name}' is already pinned")),
+                _ => ApiError::InternalServerError(format!("Database error: {e}")),
+            })?;
+
+        info!("User {} pinned package {}", actor.username, entry.name);
+        Self::keep_in_memory(state);
+
+        let state = state.clone();
+        let name = entry.name.clone();
+        tokio::spawn(async move {
+            let mut report = PinnedRefreshReport::default();
+            Self::refresh_package(&name, false, &state, &mut report).await;
+            for failure in report.failed {
+                warn!("Failed to warm pinned package {name}: {}", failure.error);
+            }
+        });
+
+        Ok(entry)
+    }
+
+    pub fn delete(id: i32, state: &AppState) -> Result<(), ApiError> {
+        let deleted = state
+            .database
+            .delete_pinned_package(id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if deleted == 0 {
+            return Err(ApiError::NotFound(format!("Pinned package {id} not found")));
+        }
+
+        info!("Unpinned package {id}");
+        Self::keep_in_memory(state);
+        Ok(())
+    }
+
+    /// Refreshes metadata of pinned packages that would expire before the next run, or all
+    /// of them when forced, and caches the tarballs of their dist-tags
+    pub async fn refresh(state: &AppState, force: bool) -> Result<PinnedRefreshReport, ApiError> {
+        let started = Instant::now();
+        let names = Self::names(state)?;
+        state.cache.set_pinned(names.clone());
+        let mut report = PinnedRefreshReport {
+            packages: names.len(),
+            ..Default::default()
+        };
+
+        for name in &names {
+            Self::refresh_package(name, force, state, &mut report).await;
+        }
+
+        report.duration_ms = started.elapsed().as_millis();
+        info!(
+            "Refreshed pinned packages: {} metadata, {} tarballs fetched, {} failed in {} ms",
+            report.metadata_refreshed,
+            report.tarballs_fetched,
+            report.failed.len(),
+            report.duration_ms
+        );
+
+        Ok(report)
+    }
+
+    async fn refresh_package(
+        name: &str,
+        force: bool,
+        state: &AppState,
+        report: &mut PinnedRefreshReport,
+    ) {
+        let fail = |version: Option<&str>, error: String| PrefetchFailure {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            error,
+        };
+
+        let age = state.cache.metadata_age(name);
+        let interval = Duration::from_secs(state.config.pinned_refresh_minutes * 60);
+        let ttl = Duration::from_secs(state.config.metadata_ttl_secs(name).unwrap_or(u64::MAX));
+        let refresh = force || Self::refresh_due(age, interval, ttl);
+
+        // Expire the cached copy so the next read goes upstream, and put it back if that fails
+        let fetched_at = age.map(|age| SystemTime::now() - age);
+        if refresh
+            && fetched_at.is_some()
+            && let Err(e) = state.cache.set_metadata_fetched_at(name, UNIX_EPOCH).await
+        {
+            warn!("Failed to expire cached metadata of {name}: {e}");
+        }
+
+        let metadata = match RegistryService::warm_package_metadata(name, state).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                if let Some(fetched_at) = fetched_at {
+                    let _ = state.cache.set_metadata_fetched_at(name, fetched_at).await;
+                }
+                report.failed.push(fail(None, e.to_string()));
+                return;
+            }
+        };
+        if refresh {
+            report.metadata_refreshed += 1;
+        }
+
+        let basename = name.rsplit('/').next().unwrap_or(name);
+        for version in Self::dist_tag_versions(&metadata) {
+            let filename = format!("{basename}-{version}.tgz");
+            if state.cache.get_cache_path(name, &filename).exists() {
+                continue;
+            }
+
+            match RegistryService::get_package_tarball(name, &filename, state).await {
+                Ok(_) => report.tarballs_fetched += 1,
+                Err(e) => report.failed.push(fail(Some(&version), e.to_string())),
+            }
+        }
+    }
+
+    /// Whether metadata of the given age expires before the next refresh
+    pub(crate) fn refresh_due(age: Option<Duration>, interval: Duration, ttl: Duration) -> bool {
+        age.is_none_or(|age| age + interval >= ttl)
+    }
+
+    fn dist_tag_versions(metadata: &Value) -> BTreeSet<String> {
+        metadata
+            .get("dist-tags")
+            .and_then(Value::as_object)
+            .map(|tags| {
+                tags.values()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_refresh_due() {
+        let hour = Duration::from_secs(3600);
+        let ttl = hour * 24;
+
+        assert!(PinnedPackageService::refresh_due(None, hour, ttl));
+        assert!(!PinnedPackageService::refresh_due(Some(hour), hour, ttl));
+        assert!(PinnedPackageService::refresh_due(
+            Some(hour * 23),
+            hour,
+            ttl
+        ));
+        assert!(PinnedPackageService::refresh_due(
+            Some(hour * 30),
+            hour,
+            ttl
+        ));
+    }
+
+    #[test]
+    fn test_dist_tag_versions() {
+        let metadata = json!({
+            "dist-tags": { "latest": "18.2.0", "next": "19.0.0-rc.1", "stable": "18.2.0" },
+        });
+        assert_eq!(
+            PinnedPackageService::dist_tag_versions(&metadata),
+            BTreeSet::from(["18.2.0".to_string(), "19.0.0-rc.1".to_string()])
+        );
+        assert!(PinnedPackageService::dist_tag_versions(&json!({})).is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+use crate::error::ApiError;
+use crate::models::{AuthenticatedUser, NpmPackageVersion};
+use log::{info, warn};
+use serde_json::{Value, json};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use wasmi::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// Instructions a plugin may run per hook call, so a runaway plugin can't stall requests
+const PLUGIN_FUEL: u64 = 10_000_000;
+
+/// Linear memory a plugin instance may grow to, growing past it traps
+const PLUGIN_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Largest JSON document a plugin may return
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Checks `npm login` credentials, before the registry's own accounts
+pub const AUTHENTICATE_HOOK: &str = "clef_authenticate";
+/// Accepts or rejects a publish after the registry's own checks
+pub const VALIDATE_PUBLISH_HOOK: &str = "clef_validate_publish";
+
+const HOOKS: &[&str] = &[AUTHENTICATE_HOOK, VALIDATE_PUBLISH_HOOK];
+
+/// A user vouched for by an auth provider plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginIdentity {
+    pub plugin: String,
+    pub email: Option<String>,
+}
+
+/// A WASM module without imports. It exports its `memory`, `clef_alloc(len) -> ptr` for
+/// the host to place input in, and any of the hooks. Hooks take a JSON document as
+/// `(ptr, len)` and return another as `ptr << 32 | len`, or 0 when they have nothing to say.
+struct Plugin {
+    name: String,
+    module: Arc<Module>,
+    hooks: Vec<&'static str>,
+}
+
+/// Plugins loaded at startup. Every hook call runs in a fresh instance on the blocking
+/// thread pool, plugins keep no state between calls.
+pub struct PluginHost {
+    engine: Engine,
+    plugins: Vec<Plugin>,
+}
+
+impl Default for PluginHost {
+    fn default() -> Self {
+        Self {
+            engine: Self::engine(),
+            plugins: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for PluginHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginHost")
+            .field(
+                "plugins",
+                &self.plugins.iter().map(|p| &p.name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl PluginHost {
+    /// Loads the plugins in order, which is the order their hooks are called in
+    pub fn load(paths: &[String]) -> Result<Self, String> {
+        let mut host = Self::default();
+        for path in paths {
+            let wasm =
+                std::fs::read(path).map_err(|e| format!("Failed to read plugin {path}: {e}"))?;
+            let name = Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.clone());
+            host.add(name, &wasm)
+                .map_err(|e| format!("Failed to load plugin {path}: {e}"))?;
+        }
+        Ok(host)
+    }
+
+    /// Compiles a plugin and checks it can be instantiated and exports what the host needs
+    pub fn add(&mut self, name: String, wasm: &[u8]) -> Result<(), String> {
+        let module = Module::new(&self.engine, wasm).map_err(|e| e.to_string())?;
+        let hooks: Vec<&'static str> = HOOKS
+            .iter()
+            .copied()
+            .filter(|hook| module.exports().any(|export| export.name() == *hook))
+            .collect();
+        if hooks.is_empty() {
+            return Err(format!("exports none of {}", HOOKS.join(", ")));
+        }
+
+        let plugin = Plugin {
+            name,
+            module: Arc::new(module),
+            hooks,
+        };
+        let mut store = Self::store(&self.engine)?;
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &plugin.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| format!("can't be instantiated: {e}"))?;
+        if instance.get_memory(&store, "memory").is_none() {
+            return Err("doesn't export its memory".to_string());
+        }
+        instance
+            .get_typed_func::<i32, i32>(&store, "clef_alloc")
+            .map_err(|e| format!("clef_alloc: {e}"))?;
+
+        info!(
+            "Loaded plugin {} with {}",
+            plugin.name,
+            plugin.hooks.join(", ")
+        );
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Asks the auth provider plugins about login credentials. The first one that answers
+    /// decides, `Ok(None)` leaves the login to the registry's own accounts.
+    pub async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<PluginIdentity>, ApiError> {
+        let input = json!({ "username": username, "password": password });
+        for plugin in self.with_hook(AUTHENTICATE_HOOK) {
+            let output = match self.call(plugin, AUTHENTICATE_HOOK, &input).await {
+                Ok(Some(output)) => output,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(
+                        "Plugin {} failed to authenticate {username}: {e}",
+                        plugin.name
+                    );
+                    continue;
+                }
+            };
+
+            if output["ok"].as_bool() == Some(true) {
+                return Ok(Some(PluginIdentity {
+                    plugin: plugin.name.clone(),
+                    email: output["email"].as_str().map(str::to_string),
+                }));
+            }
+            return Err(ApiError::Unauthorized(
+                output["message"]
+                    .as_str()
+                    .unwrap_or("Invalid username or password")
+                    .to_string(),
+            ));
+        }
+        Ok(None)
+    }
+
+    /// Runs the publish validator plugins. A validator that fails rejects the publish.
+    pub async fn validate_publish(
+        &self,
+        package: &str,
+        version: &NpmPackageVersion,
+        size: u64,
+        user: &AuthenticatedUser,
+    ) -> Result<(), ApiError> {
+        let input = json!({
+            "package": package,
+            "version": version.version,
+            "license": version.license,
+            "author": version.author,
+            "size": size,
+            "user": user.username,
+            "manifest": version,
+        });
+        for plugin in self.with_hook(VALIDATE_PUBLISH_HOOK) {
+            let output = self
+                .call(plugin, VALIDATE_PUBLISH_HOOK, &input)
+                .await
+                .map_err(|e| {
+                    warn!(
+                        "Plugin {} failed to validate {package}@{}: {e}",
+                        plugin.name, version.version
+                    );
+                    ApiError::Forbidden(format!(
+                        "The publish could not be validated by plugin {}",
+                        plugin.name
+                    ))
+                })?;
+
+            if let Some(output) = output
+                && output["allow"].as_bool() != Some(true)
+            {
+                let message = output["message"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Rejected by plugin {}", plugin.name));
+                info!(
+                    "Plugin {} rejected {package}@{}: {message}",
+                    plugin.name, version.version
+                );
+                return Err(ApiError::Forbidden(message));
+            }
+        }
+        Ok(())
+    }
+
+    fn engine() -> Engine {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Engine::new(&config)
+    }
+
+    fn with_hook(&self, hook: &'static str) -> impl Iterator<Item = &Plugin> {
+        self.plugins
+            .iter()
+            .filter(move |plugin| plugin.hooks.contains(&hook))
+    }
+
+    /// A store with the fuel and memory a plugin instance may use
+    fn store(engine: &Engine) -> Result<Store<StoreLimits>, String> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(PLUGIN_MEMORY_BYTES)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(PLUGIN_FUEL).map_err(|e| e.to_string())?;
+        Ok(store)
+    }
+
+    /// Calls a hook in a fresh instance of the plugin, off the async runtime since a call
+    /// runs until it returns or its fuel is spent
+    async fn call(
+        &self,
+        plugin: &Plugin,
+        hook: &'static str,
+        input: &Value,
+    ) -> Result<Option<Value>, String> {
+        let engine = self.engine.clone();
+        let module = plugin.module.clone();
+        let input = serde_json::to_vec(input).map_err(|e| e.to_string())?;
+        tokio::task::spawn_blocking(move || Self::call_blocking(&engine, &module, hook, &input))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    fn call_blocking(
+        engine: &Engine,
+        module: &Module,
+        hook: &str,
+        input: &[u8],
+    ) -> Result<Option<Value>, String> {
+        let mut store = Self::store(engine)?;
+        let instance = Linker::<StoreLimits>::new(engine)
+            .instantiate(&mut store, module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| e.to_string())?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("no memory export")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "clef_alloc")
+            .map_err(|e| e.to_string())?;
+        let func = instance
+            .get_typed_func::<(i32, i32), i64>(&store, hook)
+            .map_err(|e| e.to_string())?;
+
+        let len = i32::try_from(input.len()).map_err(|_| "input too large")?;
+        let ptr = alloc.call(&mut store, len).map_err(|e| e.to_string())?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| e.to_string())?;
+
+        let result = func
+            .call(&mut store, (ptr, len))
+            .map_err(|e| e.to_string())? as u64;
+        if result == 0 {
+            return Ok(None);
+        }
+        let (ptr, len) = ((result >> 32) as usize, (result & 0xffff_ffff) as usize);
+        if len > MAX_OUTPUT_BYTES {
+            return Err(format!("output of {len} bytes is too large"));
+        }
+        let mut output = vec![0; len];
+        memory
+            .read(&store, ptr, &mut output)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice(&output)
+            .map(Some)
+            .map_err(|e| format!("invalid output: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plugin whose hooks return `output`, which is placed at offset 1024 of its memory
+    fn plugin(hook: &str, output: &str) -> Vec<u8> {
+        let result = if output.is_empty() {
+            "(i64.const 0)".to_string()
+        } else {
+            format!("(i64.const {})", (1024u64 << 32) | output.len() as u64)
+        };
+        let output = output.replace('"', "\\\"");
+        wat::parse_str(format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 1024) "{output}")
+                (func (export "clef_alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "{hook}") (param i32 i32) (result i64) {result}))"#
+        ))
+        .unwrap()
+    }
+
+    fn version() -> NpmPackageVersion {
+        serde_json::from_value(json!({
+            "name": "left-pad",
+            "version": "1.0.0",
+            "license": "MIT",
+            "dist": { "shasum": "", "tarball": "" },
+        }))
+        .unwrap()
+    }
+
+    fn user() -> AuthenticatedUser {
+        AuthenticatedUser::new("alice".to_string(), 1, false, None)
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let mut host = PluginHost::default();
+        assert_eq!(host.authenticate("alice", "secret").await.unwrap(), None);
+
+        host.add("pass".to_string(), &plugin(AUTHENTICATE_HOOK, ""))
+            .unwrap();
+        host.add(
+            "ldap".to_string(),
+            &plugin(
+                AUTHENTICATE_HOOK,
+                r#"{"ok":true,"email":"alice@example.com"}"#,
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            host.authenticate("alice", "secret").await.unwrap(),
+            Some(PluginIdentity {
+                plugin: "ldap".to_string(),
+                email: Some("alice@example.com".to_string()),
+            })
+        );
+
+        let mut host = PluginHost::default();
+        host.add(
+            "ldap".to_string(),
+            &plugin(AUTHENTICATE_HOOK, r#"{"ok":false,"message":"Locked out"}"#),
+        )
+        .unwrap();
+        assert!(matches!(
+            host.authenticate("alice", "secret").await,
+            Err(ApiError::Unauthorized(message)) if message == "Locked out"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_publish() {
+        let mut host = PluginHost::default();
+        host.add(
+            "allow".to_string(),
+            &plugin(VALIDATE_PUBLISH_HOOK, r#"{"allow":true}"#),
+        )
+        .unwrap();
+        host.validate_publish("left-pad", &version(), 10, &user())
+            .await
+            .unwrap();
+
+        host.add(
+            "deny".to_string(),
+            &plugin(
+                VALIDATE_PUBLISH_HOOK,
+                r#"{"allow":false,"message":"No MIT"}"#,
+            ),
+        )
+        .unwrap();
+        assert!(matches!(
+            host.validate_publish("left-pad", &version(), 10, &user())
+                .await,
+            Err(ApiError::Forbidden(message)) if message == "No MIT"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_plugins() {
+        let mut host = PluginHost::default();
+        let no_hooks = wat::parse_str(r#"(module (memory (export "memory") 1))"#).unwrap();
+        assert!(host.add("none".to_string(), &no_hooks).is_err());
+
+        let imports = wat::parse_str(
+            r#"(module
+                (import "env" "log" (func))
+                (memory (export "memory") 1)
+                (func (export "clef_alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "clef_authenticate") (param i32 i32) (result i64) (i64.const 0)))"#,
+        )
+        .unwrap();
+        assert!(host.add("imports".to_string(), &imports).is_err());
+        assert!(host.add("garbage".to_string(), b"not wasm").is_err());
+        assert!(host.is_empty());
+
+        let runaway = wat::parse_str(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "clef_alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "clef_validate_publish") (param i32 i32) (result i64)
+                    (loop (br 0)) (i64.const 0)))"#,
+        )
+        .unwrap();
+        host.add("runaway".to_string(), &runaway).unwrap();
+        assert!(
+            host.validate_publish("left-pad", &version(), 10, &user())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_memory_is_limited() {
+        let mut host = PluginHost::default();
+        let pages = PLUGIN_MEMORY_BYTES / 65536;
+        let oversized = wat::parse_str(format!(
+            r#"(module
+                (memory (export "memory") {})
+                (func (export "clef_alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "clef_authenticate") (param i32 i32) (result i64) (i64.const 0)))"#,
+            pages + 1
+        ))
+        .unwrap();
+        assert!(host.add("oversized".to_string(), &oversized).is_err());
+
+        // Growing within the limit works, past it the call traps
+        let growing = |pages: usize| {
+            wat::parse_str(format!(
+                r#"(module
+                    (memory (export "memory") 1)
+                    (func (export "clef_alloc") (param i32) (result i32) (i32.const 0))
+                    (func (export "clef_validate_publish") (param i32 i32) (result i64)
+                        (drop (memory.grow (i32.const {pages}))) (i64.const 0)))"#
+            ))
+            .unwrap()
+        };
+        host.add("small".to_string(), &growing(pages - 1)).unwrap();
+        host.validate_publish("left-pad", &version(), 10, &user())
+            .await
+            .unwrap();
+
+        host.add("greedy".to_string(), &growing(pages)).unwrap();
+        assert!(matches!(
+            host.validate_publish("left-pad", &version(), 10, &user())
+                .await,
+            Err(ApiError::Forbidden(_))
+        ));
+    }
+}
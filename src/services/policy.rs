@@ -0,0 +1,337 @@
+use crate::error::ApiError;
+use crate::models::{AuthenticatedUser, NpmPackageVersion};
+use crate::services::{QuarantineService, RegistryService};
+use crate::state::AppState;
+use log::{info, warn};
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use serde_json::Value;
+use std::fmt;
+
+/// Operations a policy script may run per decision, so a runaway script can't stall requests
+const MAX_OPERATIONS: u64 = 100_000;
+
+const DEFAULT_DENY_MESSAGE: &str = "Denied by the registry policy";
+
+/// What a policy decides on: a publish, or a tarball download for an install
+#[derive(Debug, Clone, Default)]
+pub struct PolicyInput {
+    /// `publish` or `install`
+    pub action: &'static str,
+    pub package: String,
+    pub version: String,
+    pub license: Option<String>,
+    pub author: Option<String>,
+    /// Tarball size for publishes, unpacked size for installs when upstream reports it
+    pub size: Option<u64>,
+    /// Publisher, or the user downloading. Missing for anonymous installs.
+    pub user: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    Allow,
+    Deny(String),
+}
+
+/// Compiled policy script. The script sees the decision's input as `request` and allows
+/// with `true` or `()`, denies with `false`, a message, or `#{ allow: false, message: "..." }`.
+pub struct PolicyEngine {
+    engine: Engine,
+    ast: Option<AST>,
+}
+
+impl Default for PolicyEngine {
+    fn default() -> Self {
+        Self {
+            engine: Self::engine(),
+            ast: None,
+        }
+    }
+}
+
+impl fmt::Debug for PolicyEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PolicyEngine")
+            .field("enabled", &self.ast.is_some())
+            .finish()
+    }
+}
+
+impl PolicyEngine {
+    /// Compiles the policy script, every request is allowed without one
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let script = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read policy script {path}: {e}"))?;
+        let policy = Self::compile(&script)
+            .map_err(|e| format!("Failed to compile policy script {path}: {e}"))?;
+        info!("Loaded policy script {path}");
+        Ok(policy)
+    }
+
+    pub fn compile(script: &str) -> Result<Self, String> {
+        let engine = Self::engine();
+        let ast = engine.compile(script).map_err(|e| e.to_string())?;
+        Ok(Self {
+            engine,
+            ast: Some(ast),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.ast.is_some()
+    }
+
+    /// Runs the script. A script that fails denies, rather than letting everything through.
+    pub fn evaluate(&self, input: &PolicyInput) -> PolicyDecision {
+        let Some(ast) = &self.ast else {
+            return PolicyDecision::Allow;
+        };
+
+        let mut scope = Scope::new();
+        scope.push_constant("request", Self::request(input));
+        match self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, ast) {
+            Ok(result) => Self::decision(result),
+            Err(e) => {
+                warn!(
+                    "Policy script failed for {} of {}@{}: {e}",
+                    input.action, input.package, input.version
+                );
+                PolicyDecision::Deny("The registry policy could not be evaluated".to_string())
+            }
+        }
+    }
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine
+    }
+
+    fn request(input: &PolicyInput) -> Map {
+        let optional =
+            |value: &Option<String>| value.clone().map(Dynamic::from).unwrap_or(Dynamic::UNIT);
+
+        let mut request = Map::new();
+        request.insert("action".into(), input.action.into());
+        request.insert("package".into(), input.package.clone().into());
+        request.insert("version".into(), input.version.clone().into());
+        request.insert("license".into(), optional(&input.license));
+        request.insert("author".into(), optional(&input.author));
+        request.insert(
+            "size".into(),
+            input
+                .size
+                .map(|size| Dynamic::from_int(size as rhai::INT))
+                .unwrap_or(Dynamic::UNIT),
+        );
+        request.insert("user".into(), optional(&input.user));
+        request
+    }
+
+    fn decision(result: Dynamic) -> PolicyDecision {
+        if result.is_unit() {
+            return PolicyDecision::Allow;
+        }
+        if let Ok(allow) = result.as_bool() {
+            return if allow {
+                PolicyDecision::Allow
+            } else {
+                PolicyDecision::Deny(DEFAULT_DENY_MESSAGE.to_string())
+            };
+        }
+        if result.is_string() {
+            return PolicyDecision::Deny(result.to_string());
+        }
+        if let Some(map) = result.try_cast::<Map>() {
+            let allow = map
+                .get("allow")
+                .and_then(|allow| allow.as_bool().ok())
+                .unwrap_or(false);
+            if allow {
+                return PolicyDecision::Allow;
+            }
+            let message = map
+                .get("message")
+                .filter(|message| message.is_string())
+                .map(|message| message.to_string())
+                .unwrap_or_else(|| DEFAULT_DENY_MESSAGE.to_string());
+            return PolicyDecision::Deny(message);
+        }
+
+        warn!("Policy script returned an unexpected value, denying");
+        PolicyDecision::Deny(DEFAULT_DENY_MESSAGE.to_string())
+    }
+}
+
+pub struct PolicyService;
+
+impl PolicyService {
+    /// Asks the policy whether a version may be published
+    pub fn check_publish(
+        package: &str,
+        version: &NpmPackageVersion,
+        size: u64,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        if !state.policy.is_enabled() {
+            return Ok(());
+        }
+
+        let input = PolicyInput {
+            action: "publish",
+            package: package.to_string(),
+            version: version.version.clone(),
+            license: version.license.clone(),
+            author: version.author.as_ref().and_then(Self::author),
+            size: Some(size),
+            user: Some(user.username.clone()),
+        };
+        Self::enforce(&input, state)
+    }
+
+    /// Asks the policy whether a tarball may be downloaded. The version's metadata is looked
+    /// up for its license and author, it's usually cached from the install's metadata request.
+    pub async fn check_tarball(
+        package: &str,
+        filename: &str,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        if !state.policy.is_enabled() {
+            return Ok(());
+        }
+
+        let version = QuarantineService::tarball_version(package, filename).ok_or_else(|| {
+            ApiError::NotFound(format!("'{filename}' isn't a tarball of '{package}'"))
+        })?;
+        let metadata =
+            RegistryService::get_package_version_metadata(package, version, state).await?;
+        let input = PolicyInput {
+            action: "install",
+            package: package.to_string(),
+            version: version.to_string(),
+            license: Self::license(&metadata["license"]),
+            author: Self::author(&metadata["author"]),
+            size: metadata["dist"]["unpackedSize"].as_u64(),
+            user: user.map(|user| user.username.clone()),
+        };
+        Self::enforce(&input, state)
+    }
+
+    fn enforce(input: &PolicyInput, state: &AppState) -> Result<(), ApiError> {
+        match state.policy.evaluate(input) {
+            PolicyDecision::Allow => Ok(()),
+            PolicyDecision::Deny(message) => {
+                info!(
+                    "Policy denied {} of {}@{}: {message}",
+                    input.action, input.package, input.version
+                );
+                Err(ApiError::Forbidden(message))
+            }
+        }
+    }
+
+    /// `license` is an SPDX expression, older packages have `{ "type": "MIT" }`
+    fn license(license: &Value) -> Option<String> {
+        license
+            .as_str()
+            .or_else(|| license["type"].as_str())
+            .map(str::to_string)
+    }
+
+    /// `author` is a string like `Jane Doe <jane@example.com>` or an object with a name
+    fn author(author: &Value) -> Option<String> {
+        author
+            .as_str()
+            .or_else(|| author["name"].as_str())
+            .map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn input(license: Option<&str>, size: u64) -> PolicyInput {
+        PolicyInput {
+            action: "publish",
+            package: "left-pad".to_string(),
+            version: "1.0.0".to_string(),
+            license: license.map(str::to_string),
+            size: Some(size),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_policy_decisions() {
+        let policy = PolicyEngine::compile(
+            r#"
+            if request.license == () {
+                return "A license is required";
+            }
+            if request.license == "GPL-3.0" {
+                return #{ allow: false, message: "GPL packages aren't allowed" };
+            }
+            if request.size > 1000 {
+                return false;
+            }
+            true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            policy.evaluate(&input(Some("MIT"), 10)),
+            PolicyDecision::Allow
+        );
+        assert_eq!(
+            policy.evaluate(&input(None, 10)),
+            PolicyDecision::Deny("A license is required".to_string())
+        );
+        assert_eq!(
+            policy.evaluate(&input(Some("GPL-3.0"), 10)),
+            PolicyDecision::Deny("GPL packages aren't allowed".to_string())
+        );
+        assert_eq!(
+            policy.evaluate(&input(Some("MIT"), 5000)),
+            PolicyDecision::Deny(DEFAULT_DENY_MESSAGE.to_string())
+        );
+    }
+
+    #[test]
+    fn test_failing_policy_denies() {
+        let policy = PolicyEngine::compile("loop { }").unwrap();
+        assert!(matches!(
+            policy.evaluate(&input(Some("MIT"), 10)),
+            PolicyDecision::Deny(_)
+        ));
+        assert!(PolicyEngine::compile("if {").is_err());
+        assert_eq!(
+            PolicyEngine::default().evaluate(&input(None, 10)),
+            PolicyDecision::Allow
+        );
+    }
+
+    #[test]
+    fn test_author_and_license() {
+        assert_eq!(
+            PolicyService::author(&json!({ "name": "Jane Doe", "email": "jane@example.com" })),
+            Some("Jane Doe".to_string())
+        );
+        assert_eq!(
+            PolicyService::author(&json!("Jane Doe <jane@example.com>")),
+            Some("Jane Doe <jane@example.com>".to_string())
+        );
+        assert_eq!(
+            PolicyService::license(&json!({ "type": "MIT" })),
+            Some("MIT".to_string())
+        );
+        assert_eq!(PolicyService::license(&Value::Null), None);
+    }
+}
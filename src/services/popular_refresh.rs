@@ -0,0 +1,79 @@
+use crate::error::ApiError;
+use crate::services::{PinnedPackageService, RegistryService, ScopePolicyService};
+use crate::state::AppState;
+use log::{debug, info};
+use std::time::Duration;
+
+/// Days of downloads the most downloaded packages are picked from
+const POPULAR_WINDOW_DAYS: i64 = 7;
+
+/// Packages refreshed and failed in one pass
+#[derive(Debug, Default, PartialEq)]
+pub struct PopularRefreshReport {
+    pub checked: usize,
+    pub refreshed: usize,
+    pub failed: usize,
+}
+
+pub struct PopularRefreshService;
+
+impl PopularRefreshService {
+    /// Refreshes the metadata of the most downloaded upstream packages of the last week
+    /// before it expires, so installs of them are served from the cache without waiting on
+    /// the upstream
+    pub async fn refresh(state: &AppState) -> Result<PopularRefreshReport, ApiError> {
+        let since = chrono::Utc::now().date_naive() - chrono::Duration::days(POPULAR_WINDOW_DAYS);
+        let popular = state
+            .database
+            .get_most_downloaded_since(since, state.config.popular_refresh_count as i64)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        let interval = Duration::from_secs(state.config.popular_refresh_minutes * 60);
+
+        let mut report = PopularRefreshReport::default();
+        for (name, _) in popular {
+            report.checked += 1;
+            if !Self::refresh_due(&name, interval, state) {
+                continue;
+            }
+            match RegistryService::refresh_package_metadata(&name, state).await {
+                Ok(_) => report.refreshed += 1,
+                Err(e) => {
+                    debug!("Failed to refresh popular package {name}: {e}");
+                    report.failed += 1;
+                }
+            }
+        }
+
+        if report.refreshed > 0 || report.failed > 0 {
+            info!(
+                "Refreshed popular packages: {} of {} refreshed, {} failed",
+                report.refreshed, report.checked, report.failed
+            );
+        }
+        Ok(report)
+    }
+
+    /// Whether the metadata of an upstream package expires before the next check. Packages
+    /// published here and documents that never expire are left alone.
+    fn refresh_due(name: &str, interval: Duration, state: &AppState) -> bool {
+        if !ScopePolicyService::upstream_allowed(name, state).unwrap_or(false) {
+            return false;
+        }
+        let published = state
+            .database
+            .get_package_by_name(name)
+            .ok()
+            .flatten()
+            .is_some_and(|pkg| pkg.author_id.is_some());
+        let Some(ttl) = state.config.metadata_ttl_secs(name) else {
+            return false;
+        };
+
+        !published
+            && PinnedPackageService::refresh_due(
+                state.cache.metadata_age(name),
+                interval,
+                Duration::from_secs(ttl),
+            )
+    }
+}
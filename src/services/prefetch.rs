@@ -0,0 +1,279 @@
+use crate::error::ApiError;
+use crate::models::{AuthenticatedUser, PrefetchFailure, PrefetchReport};
+use crate::services::RegistryService;
+use crate::state::AppState;
+use log::{debug, info};
+use semver::Version;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+
+/// Packages warmed in parallel
+const PREFETCH_CONCURRENCY: usize = 8;
+
+/// Outcome of warming one package name
+#[derive(Default)]
+struct PackageOutcome {
+    already_cached: usize,
+    fetched: usize,
+    failed: Vec<PrefetchFailure>,
+}
+
+pub struct PrefetchService;
+
+impl PrefetchService {
+    /// Package names and versions from a `package-lock.json` (any lockfile version) or a
+    /// `pnpm-lock.yaml`, keyed by name
+    pub fn parse_lockfile(lockfile: &str) -> Result<BTreeMap<String, BTreeSet<String>>, ApiError> {
+        let mut packages: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut add = |name: &str, version: &str| {
+            // Git, file and alias specs aren't registry versions
+            if !name.is_empty() && Version::parse(version).is_ok() {
+                packages
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(version.to_string());
+            }
+        };
+
+        if lockfile.trim_start().starts_with('{') {
+            let lock: Value = serde_json::from_str(lockfile)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid package-lock.json: {e}")))?;
+
+            if let Some(entries) = lock.get("packages").and_then(Value::as_object) {
+                for (path, entry) in entries {
+                    if path.is_empty() || entry.get("link").and_then(Value::as_bool) == Some(true) {
+                        continue;
+                    }
+                    let name = entry
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .or_else(|| path.rsplit_once("node_modules/").map(|(_, name)| name));
+                    let version = entry.get("version").and_then(Value::as_str);
+                    if let (Some(name), Some(version)) = (name, version) {
+                        add(name, version);
+                    }
+                }
+            } else if let Some(dependencies) = lock.get("dependencies") {
+                Self::collect_v1_dependencies(dependencies, &mut add);
+            }
+        } else {
+            for (name, version) in Self::pnpm_packages(lockfile) {
+                add(name, version);
+            }
+        }
+
+        if packages.is_empty() {
+            return Err(ApiError::BadRequest(
+                "No registry packages found in the lockfile".to_string(),
+            ));
+        }
+
+        Ok(packages)
+    }
+
+    /// Fetches metadata and tarballs of every package the user can read into the cache
+    pub async fn warm(
+        packages: BTreeMap<String, BTreeSet<String>>,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<PrefetchReport, ApiError> {
+        let started = Instant::now();
+        let mut report = PrefetchReport {
+            packages: packages.len(),
+            versions: packages.values().map(BTreeSet::len).sum(),
+            ..Default::default()
+        };
+
+        let semaphore = Arc::new(Semaphore::new(PREFETCH_CONCURRENCY));
+        let mut tasks = tokio::task::JoinSet::new();
+        for (name, versions) in packages {
+            let semaphore = semaphore.clone();
+            let state = state.clone();
+            let user_id = user.user_id;
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                Self::warm_package(name, versions, user_id, &state).await
+            });
+        }
+
+        while let Some(outcome) = tasks.join_next().await {
+            let outcome = outcome
+                .map_err(|e| ApiError::InternalServerError(format!("Prefetch task failed: {e}")))?;
+            report.already_cached += outcome.already_cached;
+            report.fetched += outcome.fetched;
+            report.failed.extend(outcome.failed);
+        }
+
+        report.failed.sort_by(|a, b| a.name.cmp(&b.name));
+        report.duration_ms = started.elapsed().as_millis();
+        info!(
+            "User {} prefetched {} versions of {} packages: {} fetched, {} already cached, {} failed in {} ms",
+            user.username,
+            report.versions,
+            report.packages,
+            report.fetched,
+            report.already_cached,
+            report.failed.len(),
+            report.duration_ms
+        );
+
+        Ok(report)
+    }
+
+    async fn warm_package(
+        name: String,
+        versions: BTreeSet<String>,
+        user_id: i32,
+        state: &AppState,
+    ) -> PackageOutcome {
+        let mut outcome = PackageOutcome::default();
+        let fail = |version: Option<&String>, error: String| PrefetchFailure {
+            name: name.clone(),
+            version: version.cloned(),
+            error,
+        };
+
+        // Private packages are reported like unknown ones, as on reads
+        match state.database.has_read_permission(&name, Some(user_id)) {
+            Ok(true) => {}
+            Ok(false) => {
+                outcome
+                    .failed
+                    .push(fail(None, "Package not found".to_string()));
+                return outcome;
+            }
+            Err(e) => {
+                outcome
+                    .failed
+                    .push(fail(None, format!("Database error: {e}")));
+                return outcome;
+            }
+        }
+
+        if let Err(e) = RegistryService::warm_package_metadata(&name, state).await {
+            outcome.failed.push(fail(None, e.to_string()));
+            return outcome;
+        }
+
+        let basename = name.rsplit('/').next().unwrap_or(&name);
+        for version in &versions {
+            let filename = format!("{basename}-{version}.tgz");
+            if state.cache.get_cache_path(&name, &filename).exists() {
+                outcome.already_cached += 1;
+                continue;
+            }
+
+            match RegistryService::get_package_tarball(&name, &filename, state).await {
+                Ok(_) => outcome.fetched += 1,
+                Err(e) => {
+                    debug!("Failed to prefetch {name}@{version}: {e}");
+                    outcome.failed.push(fail(Some(version), e.to_string()));
+                }
+            }
+        }
+
+        outcome
+    }
+
+    fn collect_v1_dependencies(dependencies: &Value, add: &mut impl FnMut(&str, &str)) {
+        let Some(dependencies) = dependencies.as_object() else {
+            return;
+        };
+
+        for (name, entry) in dependencies {
+            if let Some(version) = entry.get("version").and_then(Value::as_str) {
+                add(name, version);
+            }
+            if let Some(nested) = entry.get("dependencies") {
+                Self::collect_v1_dependencies(nested, add);
+            }
+        }
+    }
+
+    /// Keys of the `packages:` section of a pnpm lockfile: `/name/1.0.0` (v5),
+    /// `/name@1.0.0` (v6) or `name@1.0.0` (v9), optionally with a peer dependency suffix
+    fn pnpm_packages(lockfile: &str) -> Vec<(&str, &str)> {
+        let mut found = Vec::new();
+        let mut in_packages = false;
+
+        for line in lockfile.lines() {
+            if !line.starts_with(' ') && !line.trim().is_empty() {
+                in_packages = line.trim_end() == "packages:";
+                continue;
+            }
+            if !in_packages || line.starts_with("   ") || !line.starts_with("  ") {
+                continue;
+            }
+
+            let key = line
+                .trim()
+                .trim_end_matches(':')
+                .trim_matches(|c| c == '\'' || c == '"');
+            let key = key.strip_prefix('/').unwrap_or(key);
+            let key = key.split('(').next().unwrap_or(key);
+
+            // The name ends at the first `@` (v6, v9) or `/` (v5) after the scope
+            let scope_len = if key.starts_with('@') {
+                key.find('/').map_or(key.len(), |slash| slash + 1)
+            } else {
+                0
+            };
+            if let Some(end) = key[scope_len..].find(['@', '/']) {
+                let (name, version) = (&key[..scope_len + end], &key[scope_len + end + 1..]);
+                found.push((name, version.split('_').next().unwrap_or(version)));
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(packages: &BTreeMap<String, BTreeSet<String>>, name: &str) -> Vec<String> {
+        packages[name].iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_parse_package_lock() {
+        let lock = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "name": "app" },
+                "node_modules/lodash": { "version": "4.17.21" },
+                "node_modules/a/node_modules/lodash": { "version": "4.17.15" },
+                "node_modules/@acme/utils": { "version": "1.0.0" },
+                "node_modules/aliased": { "name": "ms", "version": "2.1.3" },
+                "node_modules/local": { "resolved": "packages/local", "link": true },
+                "node_modules/from-git": { "version": "git+ssh://git@github.com/a/b.git" }
+            }
+        }"#;
+
+        let packages = PrefetchService::parse_lockfile(lock).unwrap();
+        assert_eq!(versions(&packages, "lodash"), ["4.17.15", "4.17.21"]);
+        assert_eq!(versions(&packages, "@acme/utils"), ["1.0.0"]);
+        assert_eq!(versions(&packages, "ms"), ["2.1.3"]);
+        assert_eq!(packages.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_pnpm_lock() {
+        let v5 = "lockfileVersion: 5.4\n\npackages:\n\n  /lodash/4.17.21:\n    resolution: {integrity: sha512-x}\n  /@acme/utils/1.0.0_react@18.2.0:\n    dev: false\n";
+        let packages = PrefetchService::parse_lockfile(v5).unwrap();
+        assert_eq!(versions(&packages, "lodash"), ["4.17.21"]);
+        assert_eq!(versions(&packages, "@acme/utils"), ["1.0.0"]);
+
+        let v9 = "lockfileVersion: '9.0'\n\nimporters:\n\n  .:\n    dependencies:\n      lodash:\n        specifier: ^4\n\npackages:\n\n  '@acme/utils@1.0.0':\n    resolution: {integrity: sha512-x}\n\n  lodash@4.17.21:\n    resolution: {integrity: sha512-x}\n\nsnapshots:\n\n  '@acme/utils@1.0.0(react@18.2.0)': {}\n";
+        let packages = PrefetchService::parse_lockfile(v9).unwrap();
+        assert_eq!(versions(&packages, "lodash"), ["4.17.21"]);
+        assert_eq!(versions(&packages, "@acme/utils"), ["1.0.0"]);
+        assert_eq!(packages.len(), 2);
+
+        assert!(PrefetchService::parse_lockfile("lockfileVersion: '9.0'\n").is_err());
+    }
+}
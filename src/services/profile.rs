@@ -0,0 +1,188 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, UpdateProfileRequest, UpdateUserProfile, User, UserPackage,
+    UserPackagesResponse, UserProfile,
+};
+use crate::state::AppState;
+use log::info;
+
+const MAX_DISPLAY_NAME_LENGTH: usize = 100;
+const MAX_URL_LENGTH: usize = 2048;
+const MAX_LINKS: usize = 10;
+const MAX_LINK_LABEL_LENGTH: usize = 32;
+
+/// Public user profiles and the packages each user owns
+pub struct ProfileService;
+
+impl ProfileService {
+    pub fn get(username: &str, state: &AppState) -> Result<UserProfile, ApiError> {
+        Self::find_user(username, state).map(|user| UserProfile::from(&user))
+    }
+
+    /// Packages the user owns. Private ones are only listed when the viewer can read them.
+    pub fn packages(
+        username: &str,
+        viewer: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<UserPackagesResponse, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+        let user = Self::find_user(username, state)?;
+
+        let mut packages = Vec::new();
+        for (package, permission_level) in state
+            .database
+            .get_user_packages(user.id)
+            .map_err(db_error)?
+        {
+            let readable = state
+                .database
+                .has_read_permission(&package.name, viewer.map(|v| v.user_id))
+                .map_err(db_error)?;
+            if readable {
+                packages.push(UserPackage {
+                    package,
+                    permission_level,
+                });
+            }
+        }
+
+        Ok(UserPackagesResponse {
+            username: user.username,
+            packages,
+        })
+    }
+
+    pub fn update(
+        user: &AuthenticatedUser,
+        request: UpdateProfileRequest,
+        state: &AppState,
+    ) -> Result<UserProfile, ApiError> {
+        let profile = Self::validate(request)?;
+        let updated = state
+            .database
+            .update_user_profile(user.user_id, &profile)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to update profile: {e}")))?;
+
+        info!("User {} updated their profile", user.username);
+        Ok(UserProfile::from(&updated))
+    }
+
+    fn find_user(username: &str, state: &AppState) -> Result<User, ApiError> {
+        state
+            .database
+            .get_user_by_username(username)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("User '{username}' not found")))
+    }
+
+    /// Trims the fields, treating blank ones as cleared, and checks lengths and that URLs
+    /// are http or https, as they are rendered as links in the web UI
+    fn validate(request: UpdateProfileRequest) -> Result<UpdateUserProfile, ApiError> {
+        let trimmed = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+
+        let display_name = trimmed(request.display_name);
+        if display_name
+            .as_ref()
+            .is_some_and(|name| name.chars().count() > MAX_DISPLAY_NAME_LENGTH)
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Display name can't be longer than {MAX_DISPLAY_NAME_LENGTH} characters"
+            )));
+        }
+
+        let avatar_url = trimmed(request.avatar_url);
+        if let Some(url) = &avatar_url {
+            Self::check_url("Avatar URL", url)?;
+        }
+
+        if request.links.len() > MAX_LINKS {
+            return Err(ApiError::BadRequest(format!(
+                "A profile can have at most {MAX_LINKS} links"
+            )));
+        }
+        let mut links = std::collections::BTreeMap::new();
+        for (label, url) in request.links {
+            let label = label.trim().to_string();
+            if label.is_empty() || label.chars().count() > MAX_LINK_LABEL_LENGTH {
+                return Err(ApiError::BadRequest(format!(
+                    "Link labels must be 1 to {MAX_LINK_LABEL_LENGTH} characters"
+                )));
+            }
+            let url = url.trim().to_string();
+            Self::check_url(&format!("Link '{label}'"), &url)?;
+            links.insert(label, url);
+        }
+
+        Ok(UpdateUserProfile {
+            display_name,
+            avatar_url,
+            links: (!links.is_empty())
+                .then(|| serde_json::to_string(&links))
+                .transpose()
+                .map_err(|e| ApiError::InternalServerError(format!("Invalid links: {e}")))?,
+            updated_at: chrono::Utc::now().naive_utc(),
+        })
+    }
+
+    fn check_url(field: &str, url: &str) -> Result<(), ApiError> {
+        let lowercase = url.to_ascii_lowercase();
+        let valid = (lowercase.starts_with("https://") || lowercase.starts_with("http://"))
+            && url.len() <= MAX_URL_LENGTH
+            && !url.chars().any(|c| c.is_whitespace() || c.is_control());
+        if valid {
+            Ok(())
+        } else {
+            Err(ApiError::BadRequest(format!(
+                "{field} must be an http or https URL of at most {MAX_URL_LENGTH} characters"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn request(avatar_url: Option<&str>, links: &[(&str, &str)]) -> UpdateProfileRequest {
+        UpdateProfileRequest {
+            display_name: Some("  Jan Kuri ".to_string()),
+            avatar_url: avatar_url.map(str::to_string),
+            links: links
+                .iter()
+                .map(|(label, url)| (label.to_string(), url.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let profile = ProfileService::validate(request(
+            Some("https://example.com/me.png"),
+            &[("github", "https://github.com/jkuri")],
+        ))
+        .unwrap();
+        assert_eq!(profile.display_name.as_deref(), Some("Jan Kuri"));
+        assert_eq!(
+            profile.links.as_deref(),
+            Some(r#"{"github":"https://github.com/jkuri"}"#)
+        );
+
+        let cleared = ProfileService::validate(UpdateProfileRequest {
+            display_name: Some("   ".to_string()),
+            avatar_url: None,
+            links: BTreeMap::new(),
+        })
+        .unwrap();
+        assert_eq!(cleared.display_name, None);
+        assert_eq!(cleared.links, None);
+
+        assert!(ProfileService::validate(request(Some("javascript:alert(1)"), &[])).is_err());
+        assert!(ProfileService::validate(request(None, &[("site", "ftp://example.com")])).is_err());
+        assert!(ProfileService::validate(request(None, &[(" ", "https://example.com")])).is_err());
+    }
+}
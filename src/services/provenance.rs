@@ -0,0 +1,286 @@
+use crate::error::ApiError;
+use crate::models::{
+    NewPackageAttestation, NpmAttachment, NpmAttestation, NpmAttestationsResponse,
+};
+use crate::services::RegistryService;
+use crate::state::AppState;
+use base64::prelude::*;
+use log::{debug, info, warn};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha512};
+
+/// Media type prefix of Sigstore bundles attached by `npm publish --provenance`
+const SIGSTORE_BUNDLE_MEDIA_TYPE: &str = "application/vnd.dev.sigstore.bundle";
+
+/// Predicate type prefix of SLSA provenance statements
+const SLSA_PROVENANCE_PREFIX: &str = "https://slsa.dev/provenance/";
+
+pub struct ProvenanceService;
+
+impl ProvenanceService {
+    /// Whether a publish attachment is a Sigstore bundle rather than the tarball
+    pub fn is_bundle_attachment(filename: &str, attachment: &NpmAttachment) -> bool {
+        filename.ends_with(".sigstore")
+            || attachment
+                .content_type
+                .starts_with(SIGSTORE_BUNDLE_MEDIA_TYPE)
+    }
+
+    /// Checks that a Sigstore bundle attests the published tarball and stores it. The
+    /// signature itself is verified by clients against the Sigstore infrastructure.
+    pub fn store_bundle(
+        package: &str,
+        version: &str,
+        package_version_id: i32,
+        attachment: &NpmAttachment,
+        tarball: &[u8],
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let bundle = BASE64_STANDARD
+            .decode(&attachment.data)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid base64 data: {e}")))?;
+        let bundle: Value = serde_json::from_slice(&bundle)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid Sigstore bundle: {e}")))?;
+
+        let predicate_type = Self::check_bundle(package, version, &bundle, tarball)?;
+
+        state
+            .database
+            .create_package_attestation(&NewPackageAttestation {
+                package_version_id,
+                predicate_type: predicate_type.clone(),
+                bundle: bundle.to_string(),
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!("Stored {predicate_type} attestation for {package}@{version}");
+        Ok(())
+    }
+
+    /// Attestations of a version, stored ones for published packages and the upstream
+    /// registry's for proxied packages
+    pub async fn attestations(
+        package: &str,
+        version: &str,
+        state: &AppState,
+    ) -> Result<NpmAttestationsResponse, ApiError> {
+        let not_found =
+            || ApiError::NotFound(format!("No attestations found for {package}@{version}"));
+
+        let local = state
+            .database
+            .get_package_by_name(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if let Some(pkg) = local.filter(|pkg| pkg.author_id.is_some()) {
+            let pkg_version = state
+                .database
+                .get_package_versions(pkg.id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+                .into_iter()
+                .find(|v| v.version == version)
+                .ok_or_else(not_found)?;
+
+            let attestations = state
+                .database
+                .get_package_attestations(pkg_version.id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+            if attestations.is_empty() {
+                return Err(not_found());
+            }
+
+            return Ok(NpmAttestationsResponse {
+                attestations: attestations
+                    .into_iter()
+                    .filter_map(|attestation| {
+                        Some(NpmAttestation {
+                            bundle: serde_json::from_str(&attestation.bundle).ok()?,
+                            predicate_type: attestation.predicate_type,
+                        })
+                    })
+                    .collect(),
+            });
+        }
+
+        RegistryService::ensure_upstream_allowed(package, state)?;
+
+        let url = format!(
+            "{}/-/npm/v1/attestations/{}@{version}",
+            state.upstream_registry(),
+            package.replace('/', "%2f")
+        );
+        debug!("Fetching attestations from {url}");
+
+        let response = state
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| ApiError::NetworkError(format!("Failed to contact upstream: {e}")))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(not_found());
+        }
+
+        response
+            .error_for_status()
+            .map_err(|e| ApiError::UpstreamError(format!("Upstream attestations failed: {e}")))?
+            .json()
+            .await
+            .map_err(|e| ApiError::ParseError(format!("Invalid attestations response: {e}")))
+    }
+
+    /// Adds `dist.attestations` to a published version that has attestations
+    pub fn attach_attestations(
+        version_data: &mut Value,
+        package_version_id: i32,
+        state: &AppState,
+    ) {
+        let attestations = match state.database.get_package_attestations(package_version_id) {
+            Ok(attestations) => attestations,
+            Err(e) => {
+                warn!("Failed to load attestations for package version {package_version_id}: {e}");
+                return;
+            }
+        };
+        if attestations.is_empty() {
+            return;
+        }
+
+        let (Some(name), Some(version)) = (
+            version_data.get("name").and_then(Value::as_str),
+            version_data.get("version").and_then(Value::as_str),
+        ) else {
+            return;
+        };
+
+        // npm resolves the path of this URL against the registry it installs from
+        let mut entry = json!({
+            "url": format!(
+                "{}/-/npm/v1/attestations/{}@{version}",
+                state.config.get_public_url(),
+                name.replace('/', "%2f")
+            ),
+        });
+        if let Some(provenance) = attestations
+            .iter()
+            .find(|a| a.predicate_type.starts_with(SLSA_PROVENANCE_PREFIX))
+        {
+            entry["provenance"] = json!({ "predicateType": provenance.predicate_type });
+        }
+
+        if let Some(dist) = version_data.get_mut("dist").and_then(|d| d.as_object_mut()) {
+            dist.insert("attestations".to_string(), entry);
+        }
+    }
+
+    /// Validates a bundle's in-toto statement against the package and tarball and returns
+    /// its predicate type
+    fn check_bundle(
+        package: &str,
+        version: &str,
+        bundle: &Value,
+        tarball: &[u8],
+    ) -> Result<String, ApiError> {
+        let invalid = |reason: &str| ApiError::BadRequest(format!("Invalid provenance: {reason}"));
+
+        if !bundle
+            .get("mediaType")
+            .and_then(Value::as_str)
+            .is_some_and(|media_type| media_type.starts_with(SIGSTORE_BUNDLE_MEDIA_TYPE))
+        {
+            return Err(invalid("not a Sigstore bundle"));
+        }
+
+        let statement: Value = bundle
+            .pointer("/dsseEnvelope/payload")
+            .and_then(Value::as_str)
+            .and_then(|payload| BASE64_STANDARD.decode(payload).ok())
+            .and_then(|payload| serde_json::from_slice(&payload).ok())
+            .ok_or_else(|| invalid("missing in-toto statement"))?;
+
+        let subject_name = statement.pointer("/subject/0/name").and_then(Value::as_str);
+        if subject_name != Some(Self::purl(package, version).as_str()) {
+            return Err(invalid(&format!(
+                "statement subject {} does not match {package}@{version}",
+                subject_name.unwrap_or("(none)")
+            )));
+        }
+
+        let digest = format!("{:x}", Sha512::digest(tarball));
+        if statement
+            .pointer("/subject/0/digest/sha512")
+            .and_then(Value::as_str)
+            != Some(digest.as_str())
+        {
+            return Err(invalid("statement digest does not match the tarball"));
+        }
+
+        statement
+            .get("predicateType")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| invalid("missing predicate type"))
+    }
+
+    /// Package URL of a version, the subject name npm attests
+    fn purl(package: &str, version: &str) -> String {
+        format!("pkg:npm/{}@{version}", package.replace('@', "%40"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(subject: &str, digest: &str) -> Value {
+        let statement = json!({
+            "_type": "https://in-toto.io/Statement/v1",
+            "subject": [{ "name": subject, "digest": { "sha512": digest } }],
+            "predicateType": "https://slsa.dev/provenance/v1",
+        });
+
+        json!({
+            "mediaType": "application/vnd.dev.sigstore.bundle+json;version=0.2",
+            "dsseEnvelope": {
+                "payload": BASE64_STANDARD.encode(statement.to_string()),
+                "payloadType": "application/vnd.in-toto+json",
+            },
+        })
+    }
+
+    #[test]
+    fn test_purl() {
+        assert_eq!(
+            ProvenanceService::purl("@acme/utils", "1.0.0"),
+            "pkg:npm/%40acme/utils@1.0.0"
+        );
+        assert_eq!(
+            ProvenanceService::purl("left-pad", "1.3.0"),
+            "pkg:npm/left-pad@1.3.0"
+        );
+    }
+
+    #[test]
+    fn test_check_bundle() {
+        let tarball = b"tarball";
+        let digest = format!("{:x}", Sha512::digest(tarball));
+
+        let valid = bundle("pkg:npm/%40acme/utils@1.0.0", &digest);
+        assert_eq!(
+            ProvenanceService::check_bundle("@acme/utils", "1.0.0", &valid, tarball).unwrap(),
+            "https://slsa.dev/provenance/v1"
+        );
+
+        let other_version = bundle("pkg:npm/%40acme/utils@0.9.0", &digest);
+        assert!(
+            ProvenanceService::check_bundle("@acme/utils", "1.0.0", &other_version, tarball)
+                .is_err()
+        );
+
+        let other_tarball = bundle("pkg:npm/%40acme/utils@1.0.0", "00");
+        assert!(
+            ProvenanceService::check_bundle("@acme/utils", "1.0.0", &other_tarball, tarball)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, NewAuditLogEntry, NewQuarantinedPackage, QuarantineListResponse,
+    QuarantineStatus, QuarantinedPackage,
+};
+use crate::services::{RegistryService, YankService};
+use crate::state::AppState;
+use log::{info, warn};
+use semver::Version;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+/// Which upstream packages wait for admin approval, from `CLEF_QUARANTINE_MODE`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuarantineMode {
+    Off,
+    /// New packages are held until approved
+    Packages,
+    /// New packages and every new major version of an approved package are held
+    Majors,
+}
+
+impl QuarantineMode {
+    pub fn from_mode_str(mode: &str) -> Self {
+        match mode {
+            "packages" => Self::Packages,
+            "majors" => Self::Majors,
+            _ => Self::Off,
+        }
+    }
+}
+
+pub struct QuarantineService;
+
+impl QuarantineService {
+    /// Holds back upstream packages that weren't approved. The first request of a package
+    /// records it for review. Admins can always read packages so they can inspect them.
+    pub fn check_package(
+        package: &str,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        if !Self::applies(package, user, state)? {
+            return Ok(());
+        }
+
+        let entries = state
+            .database
+            .get_quarantine_entries(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        match entries.iter().find(|entry| entry.major.is_none()) {
+            Some(entry) => match entry.status() {
+                QuarantineStatus::Approved => Ok(()),
+                _ => Err(Self::quarantined_error(entry)),
+            },
+            None => {
+                Self::record(package, &[None], user, state)?;
+                info!("Quarantined upstream package {package} until an admin approves it");
+                Err(ApiError::Forbidden(format!(
+                    "Package '{package}' is quarantined and awaits admin approval"
+                )))
+            }
+        }
+    }
+
+    /// With quarantined majors, removes the versions of majors that weren't approved from
+    /// package metadata and records new majors for review
+    pub fn filter_metadata(
+        package: &str,
+        metadata: &mut Value,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        if QuarantineMode::from_mode_str(&state.config.quarantine_mode) != QuarantineMode::Majors
+            || !Self::applies(package, user, state)?
+        {
+            return Ok(());
+        }
+
+        let entries = state
+            .database
+            .get_quarantine_entries(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        let approved = Self::approved_majors(&entries);
+
+        let new_majors: Vec<Option<i32>> = Self::filter_versions(metadata, &approved)
+            .into_iter()
+            .filter(|major| !entries.iter().any(|entry| entry.major == Some(*major)))
+            .map(Some)
+            .collect();
+        if !new_majors.is_empty() {
+            Self::record(package, &new_majors, user, state)?;
+            info!(
+                "Quarantined {} new major version(s) of {package}",
+                new_majors.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// With quarantined majors, rejects version documents and tarballs of majors that
+    /// weren't approved
+    pub fn check_version(
+        package: &str,
+        version: &str,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        if QuarantineMode::from_mode_str(&state.config.quarantine_mode) != QuarantineMode::Majors
+            || !Self::applies(package, user, state)?
+        {
+            return Ok(());
+        }
+
+        let entries = state
+            .database
+            .get_quarantine_entries(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        match Self::major_of(version) {
+            Some(major) if Self::approved_majors(&entries).contains(&major) => Ok(()),
+            _ => Err(ApiError::Forbidden(format!(
+                "Version {version} of '{package}' is quarantined and awaits admin approval"
+            ))),
+        }
+    }
+
+    /// Tarball variant of `check_version`, with the version taken from the file name
+    pub fn check_tarball(
+        package: &str,
+        filename: &str,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let version = Self::tarball_version(package, filename).unwrap_or(filename);
+        Self::check_version(package, version, user, state)
+    }
+
+    pub fn list(
+        status: Option<&str>,
+        state: &AppState,
+    ) -> Result<QuarantineListResponse, ApiError> {
+        if let Some(status) = status
+            && QuarantineStatus::from_status_str(status).is_none()
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid status '{status}', expected pending, approved or rejected"
+            )));
+        }
+
+        let entries = state
+            .database
+            .list_quarantined_packages(status.map(str::to_lowercase).as_deref())
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(QuarantineListResponse {
+            mode: state.config.quarantine_mode.clone(),
+            entries,
+        })
+    }
+
+    /// Approves or rejects a quarantined package or major. With quarantined majors,
+    /// approving a package approves the majors it has now, later majors are held again.
+    pub async fn review(
+        id: i32,
+        status: QuarantineStatus,
+        admin: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<QuarantinedPackage, ApiError> {
+        let entry = state
+            .database
+            .review_quarantined_package(id, status.as_str(), admin.user_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Quarantine entry {id} not found")))?;
+
+        let majors_mode =
+            QuarantineMode::from_mode_str(&state.config.quarantine_mode) == QuarantineMode::Majors;
+        if majors_mode && status == QuarantineStatus::Approved && entry.major.is_none() {
+            Self::approve_current_majors(&entry.name, admin, state).await?;
+        }
+
+        let action = match status {
+            QuarantineStatus::Approved => "quarantine.approve",
+            QuarantineStatus::Rejected => "quarantine.reject",
+            QuarantineStatus::Pending => "quarantine.reset",
+        };
+        let audit = NewAuditLogEntry {
+            action: action.to_string(),
+            actor_id: Some(admin.user_id),
+            organization_id: None,
+            package_name: Some(entry.name.clone()),
+            version: None,
+            details: entry.major.map(|major| format!("major {major}")),
+        };
+        if let Err(e) = state.database.create_audit_log_entry(&audit) {
+            warn!("Failed to write audit log entry {action}: {e}");
+        }
+
+        info!(
+            "Admin {} marked quarantined {} as {status}",
+            admin.username,
+            entry.label()
+        );
+
+        Ok(entry)
+    }
+
+    async fn approve_current_majors(
+        package: &str,
+        admin: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let mut metadata = RegistryService::warm_package_metadata(package, state).await?;
+        let majors = Self::filter_versions(&mut metadata, &BTreeSet::new());
+
+        let now = chrono::Utc::now().naive_utc();
+        let entries: Vec<NewQuarantinedPackage> = majors
+            .into_iter()
+            .map(|major| NewQuarantinedPackage {
+                name: package.to_string(),
+                major: Some(major),
+                status: QuarantineStatus::Approved.to_string(),
+                requested_by: None,
+                reviewed_by: Some(admin.user_id),
+                reviewed_at: Some(now),
+            })
+            .collect();
+
+        state
+            .database
+            .create_quarantined_packages(&entries)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        Ok(())
+    }
+
+    /// Whether quarantine is enabled and covers the package. Published packages and
+    /// admins are never held.
+    fn applies(
+        package: &str,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<bool, ApiError> {
+        let mode = QuarantineMode::from_mode_str(&state.config.quarantine_mode);
+        if mode == QuarantineMode::Off || user.is_some_and(|user| user.is_admin) {
+            return Ok(false);
+        }
+
+        let published = state
+            .database
+            .get_package_by_name(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .is_some_and(|pkg| pkg.author_id.is_some());
+
+        Ok(!published)
+    }
+
+    fn record(
+        package: &str,
+        majors: &[Option<i32>],
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let entries: Vec<NewQuarantinedPackage> = majors
+            .iter()
+            .map(|major| NewQuarantinedPackage {
+                name: package.to_string(),
+                major: *major,
+                status: QuarantineStatus::Pending.to_string(),
+                requested_by: user.map(|user| user.user_id),
+                reviewed_by: None,
+                reviewed_at: None,
+            })
+            .collect();
+
+        state
+            .database
+            .create_quarantined_packages(&entries)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        Ok(())
+    }
+
+    fn quarantined_error(entry: &QuarantinedPackage) -> ApiError {
+        match entry.status() {
+            QuarantineStatus::Rejected => ApiError::Forbidden(format!(
+                "Package '{}' was rejected by an admin and isn't served",
+                entry.label()
+            )),
+            _ => ApiError::Forbidden(format!(
+                "Package '{}' is quarantined and awaits admin approval",
+                entry.label()
+            )),
+        }
+    }
+
+    fn approved_majors(entries: &[QuarantinedPackage]) -> BTreeSet<i32> {
+        entries
+            .iter()
+            .filter(|entry| entry.status() == QuarantineStatus::Approved)
+            .filter_map(|entry| entry.major)
+            .collect()
+    }
+
+    fn major_of(version: &str) -> Option<i32> {
+        Version::parse(version)
+            .ok()
+            .and_then(|version| i32::try_from(version.major).ok())
+    }
+
+    /// Version in a tarball name of the form `<name>-<version>.tgz`
+    pub fn tarball_version<'a>(package: &str, filename: &'a str) -> Option<&'a str> {
+        let basename = package.rsplit('/').next().unwrap_or(package);
+        filename
+            .strip_prefix(basename)?
+            .strip_prefix('-')?
+            .strip_suffix(".tgz")
+    }
+
+    /// Removes the versions of majors that aren't approved from a package document and
+    /// repairs its dist-tags. Returns all majors the document had.
+    fn filter_versions(metadata: &mut Value, approved: &BTreeSet<i32>) -> BTreeSet<i32> {
+        let mut majors = BTreeSet::new();
+
+        let Some(versions) = metadata.get_mut("versions").and_then(Value::as_object_mut) else {
+            return majors;
+        };
+        let mut removed = Vec::new();
+        versions.retain(|version, _| {
+            let major = Self::major_of(version);
+            majors.extend(major);
+            let keep = major.is_some_and(|major| approved.contains(&major));
+            if !keep {
+                removed.push(version.clone());
+            }
+            keep
+        });
+        let remaining: Vec<String> = versions.keys().cloned().collect();
+
+        if let Some(time) = metadata.get_mut("time").and_then(Value::as_object_mut) {
+            for version in &removed {
+                time.remove(version);
+            }
+        }
+
+        if let Some(tags) = metadata.get_mut("dist-tags") {
+            let mut dist_tags: HashMap<String, String> =
+                serde_json::from_value(tags.clone()).unwrap_or_default();
+            YankService::repair_dist_tags(&mut dist_tags, remaining.iter());
+            *tags = serde_json::to_value(dist_tags).unwrap_or_default();
+        }
+
+        majors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_versions() {
+        let mut metadata = json!({
+            "name": "left-pad",
+            "dist-tags": { "latest": "2.0.0", "legacy": "1.1.0" },
+            "versions": { "1.0.0": {}, "1.1.0": {}, "2.0.0": {}, "next": {} },
+            "time": { "created": "2020-01-01", "1.0.0": "2020-01-01", "2.0.0": "2021-01-01" }
+        });
+
+        let majors = QuarantineService::filter_versions(&mut metadata, &BTreeSet::from([1]));
+        assert_eq!(majors, BTreeSet::from([1, 2]));
+        assert_eq!(metadata["versions"], json!({ "1.0.0": {}, "1.1.0": {} }));
+        assert_eq!(
+            metadata["dist-tags"],
+            json!({ "latest": "1.1.0", "legacy": "1.1.0" })
+        );
+        assert_eq!(
+            metadata["time"],
+            json!({ "created": "2020-01-01", "1.0.0": "2020-01-01" })
+        );
+    }
+
+    #[test]
+    fn test_tarball_version() {
+        assert_eq!(
+            QuarantineService::tarball_version("@acme/lib", "lib-1.2.3.tgz"),
+            Some("1.2.3")
+        );
+        assert_eq!(
+            QuarantineService::tarball_version("lodash", "lodash-4.0.0-rc.1.tgz"),
+            Some("4.0.0-rc.1")
+        );
+        assert_eq!(
+            QuarantineService::tarball_version("lodash", "other-1.0.0.tgz"),
+            None
+        );
+        assert_eq!(QuarantineService::major_of("12.0.1"), Some(12));
+        assert_eq!(QuarantineService::major_of("latest"), None);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::ApiError;
+use crate::models::{StorageUsage, StorageUsageResponse};
+use crate::state::AppState;
+use log::{debug, warn};
+
+pub struct QuotaService;
+
+impl QuotaService {
+    /// Usage of the packages a user published outside of organizations
+    pub fn user_usage(
+        user_id: i32,
+        username: &str,
+        state: &AppState,
+    ) -> Result<StorageUsageResponse, ApiError> {
+        let usage = state
+            .database
+            .get_user_storage_usage(user_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(Self::response(
+            username,
+            usage,
+            state.config.user_quota_bytes,
+        ))
+    }
+
+    /// Usage of the packages of an organization
+    pub fn organization_usage(
+        organization_id: i32,
+        name: &str,
+        state: &AppState,
+    ) -> Result<StorageUsageResponse, ApiError> {
+        let usage = state
+            .database
+            .get_organization_storage_usage(organization_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(Self::response(name, usage, state.config.org_quota_bytes))
+    }
+
+    /// Rejects a publish of `incoming` bytes that would take the owner of the package over
+    /// its quota. Organization packages count against the organization, others against the
+    /// publishing user.
+    pub fn check_publish(
+        user_id: i32,
+        organization_id: Option<i32>,
+        incoming: u64,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        Self::check(user_id, organization_id, incoming, "publish", state)
+    }
+
+    /// Rejects transferring a package of `incoming` bytes to a user or organization it would
+    /// take over its quota
+    pub fn check_transfer(
+        user_id: i32,
+        organization_id: Option<i32>,
+        incoming: u64,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        Self::check(user_id, organization_id, incoming, "transfer", state)
+    }
+
+    fn check(
+        user_id: i32,
+        organization_id: Option<i32>,
+        incoming: u64,
+        action: &str,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let (owner, usage, quota) = match organization_id {
+            Some(org_id) => (
+                "organization",
+                state.database.get_organization_storage_usage(org_id),
+                state.config.org_quota_bytes,
+            ),
+            None => (
+                "user",
+                state.database.get_user_storage_usage(user_id),
+                state.config.user_quota_bytes,
+            ),
+        };
+
+        if quota == 0 {
+            return Ok(());
+        }
+
+        let used = usage
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .bytes;
+        debug!("Storage quota of {owner}: {used} of {quota} bytes used, {action} adds {incoming}");
+
+        if Self::exceeds(used, incoming, quota) {
+            warn!("Rejected {action} of {incoming} bytes over the {owner} storage quota");
+            return Err(ApiError::PayloadTooLarge(format!(
+                "Storage quota exceeded: the {owner} uses {used} of {quota} bytes and this {action} adds {incoming}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn exceeds(used: u64, incoming: u64, quota: u64) -> bool {
+        quota > 0 && used.saturating_add(incoming) > quota
+    }
+
+    fn response(name: &str, usage: StorageUsage, quota: u64) -> StorageUsageResponse {
+        let quota_bytes = (quota > 0).then_some(quota);
+
+        StorageUsageResponse {
+            name: name.to_string(),
+            usage,
+            quota_bytes,
+            remaining_bytes: quota_bytes.map(|quota| quota.saturating_sub(usage.bytes)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_exceeded() {
+        assert!(!QuotaService::exceeds(100, 50, 0));
+        assert!(!QuotaService::exceeds(100, 50, 150));
+        assert!(QuotaService::exceeds(100, 51, 150));
+        assert!(QuotaService::exceeds(u64::MAX, 1, 150));
+
+        let usage = StorageUsage::from_files(&[(1, 1, 40), (1, 2, 40), (2, 3, 30)]);
+        assert_eq!((usage.packages, usage.versions, usage.bytes), (2, 3, 110));
+
+        let response = QuotaService::response("acme", usage, 100);
+        assert_eq!(response.remaining_bytes, Some(0));
+        assert_eq!(QuotaService::response("acme", usage, 0).quota_bytes, None);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::config::AppConfig;
+use crate::services::shared_redis::SharedRedis;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// In-process counters are pruned of past windows once there are this many
+const MAX_LOCAL_COUNTERS: usize = 10_000;
+
+/// Fixed window counters for request rate limits and login throttling. With Redis configured
+/// the counters are shared by every instance, otherwise, or while Redis can't be reached,
+/// each instance counts on its own.
+#[derive(Debug)]
+pub struct RateLimiter {
+    redis: Option<SharedRedis>,
+    /// Window and hits within it, by key
+    counters: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::in_process()
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            redis: SharedRedis::from_config(config),
+            ..Self::in_process()
+        }
+    }
+
+    pub fn in_process() -> Self {
+        Self {
+            redis: None,
+            counters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a hit on `key`, returning the hits in the current window including this one
+    pub async fn hit(&self, key: &str, window_secs: u64) -> u64 {
+        let window = Self::window(window_secs);
+        if let Some(redis) = &self.redis {
+            let redis_key = Self::redis_key(key, window);
+            let shared = redis
+                .run(|mut connection| async move {
+                    let (hits,): (u64,) = redis::pipe()
+                        .atomic()
+                        .incr(&redis_key, 1)
+                        .expire(&redis_key, window_secs as i64)
+                        .ignore()
+                        .query_async(&mut connection)
+                        .await?;
+                    Ok(hits)
+                })
+                .await;
+            if let Some(hits) = shared {
+                return hits;
+            }
+        }
+
+        let mut counters = self.counters.lock().unwrap();
+        if counters.len() >= MAX_LOCAL_COUNTERS {
+            let now = SystemTime::now();
+            counters.retain(|_, (window, _)| *window >= Self::window_at(now, window_secs));
+        }
+        let counter = counters.entry(key.to_string()).or_insert((window, 0));
+        if counter.0 != window {
+            *counter = (window, 0);
+        }
+        counter.1 += 1;
+        counter.1
+    }
+
+    /// Hits on `key` in the current window
+    pub async fn count(&self, key: &str, window_secs: u64) -> u64 {
+        let window = Self::window(window_secs);
+        if let Some(redis) = &self.redis {
+            let redis_key = Self::redis_key(key, window);
+            let shared = redis
+                .run(|mut connection| async move {
+                    redis::cmd("GET")
+                        .arg(&redis_key)
+                        .query_async::<Option<u64>>(&mut connection)
+                        .await
+                })
+                .await;
+            if let Some(hits) = shared {
+                return hits.unwrap_or(0);
+            }
+        }
+
+        match self.counters.lock().unwrap().get(key) {
+            Some((counted, hits)) if *counted == window => *hits,
+            _ => 0,
+        }
+    }
+
+    /// Forgets the hits on `key` in the current window
+    pub async fn reset(&self, key: &str, window_secs: u64) {
+        let window = Self::window(window_secs);
+        if let Some(redis) = &self.redis {
+            let redis_key = Self::redis_key(key, window);
+            redis
+                .run(|mut connection| async move {
+                    redis::cmd("DEL")
+                        .arg(&redis_key)
+                        .exec_async(&mut connection)
+                        .await
+                })
+                .await;
+        }
+        self.counters.lock().unwrap().remove(key);
+    }
+
+    /// Seconds until the current window ends
+    pub fn retry_after(window_secs: u64) -> u64 {
+        let window_secs = window_secs.max(1);
+        window_secs - Self::now_secs(SystemTime::now()) % window_secs
+    }
+
+    fn window(window_secs: u64) -> u64 {
+        Self::window_at(SystemTime::now(), window_secs)
+    }
+
+    fn window_at(at: SystemTime, window_secs: u64) -> u64 {
+        Self::now_secs(at) / window_secs.max(1)
+    }
+
+    fn now_secs(at: SystemTime) -> u64 {
+        at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+
+    fn redis_key(key: &str, window: u64) -> String {
+        format!("clef:rate:{key}:{window}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_counts_hits_per_key() {
+        let limiter = RateLimiter::in_process();
+        assert_eq!(limiter.hit("a", 3600).await, 1);
+        assert_eq!(limiter.hit("a", 3600).await, 2);
+        assert_eq!(limiter.hit("b", 3600).await, 1);
+        assert_eq!(limiter.count("a", 3600).await, 2);
+
+        limiter.reset("a", 3600).await;
+        assert_eq!(limiter.count("a", 3600).await, 0);
+        assert_eq!(limiter.count("b", 3600).await, 1);
+        assert!((1..=3600).contains(&RateLimiter::retry_after(3600)));
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_without_redis() {
+        let config = AppConfig {
+            redis_url: Some("redis://127.0.0.1:1".to_string()),
+            ..AppConfig::default()
+        };
+        let limiter = RateLimiter::new(&config);
+        assert!(limiter.redis.is_some());
+        assert_eq!(limiter.hit("a", 3600).await, 1);
+        assert_eq!(limiter.hit("a", 3600).await, 2);
+        assert_eq!(limiter.count("a", 3600).await, 2);
+    }
+}
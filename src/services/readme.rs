@@ -0,0 +1,550 @@
+use crate::error::ApiError;
+use crate::models::PackageReadme;
+use crate::state::AppState;
+use crate::versions;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd, html};
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+/// HTML elements kept from READMEs, with the attributes they may carry. Classes are limited
+/// to code highlighting and styles to table cell alignment.
+const ALLOWED_TAGS: &[(&str, &[&str])] = &[
+    ("a", &["href", "title", "name"]),
+    ("abbr", &["title"]),
+    ("b", &[]),
+    ("blockquote", &[]),
+    ("br", &[]),
+    ("code", &["class"]),
+    ("dd", &[]),
+    ("del", &[]),
+    ("details", &["open"]),
+    ("div", &["align"]),
+    ("dl", &[]),
+    ("dt", &[]),
+    ("em", &[]),
+    ("h1", &["align", "id"]),
+    ("h2", &["align", "id"]),
+    ("h3", &["align", "id"]),
+    ("h4", &["align", "id"]),
+    ("h5", &["align", "id"]),
+    ("h6", &["align", "id"]),
+    ("hr", &[]),
+    ("i", &[]),
+    ("img", &["src", "alt", "title", "width", "height", "align"]),
+    ("kbd", &[]),
+    ("li", &[]),
+    ("ol", &["start"]),
+    ("p", &["align"]),
+    ("picture", &[]),
+    ("pre", &[]),
+    ("s", &[]),
+    ("source", &["srcset", "media", "type"]),
+    ("span", &["class"]),
+    ("strong", &[]),
+    ("sub", &[]),
+    ("summary", &[]),
+    ("sup", &[]),
+    ("table", &[]),
+    ("tbody", &[]),
+    ("td", &["align", "colspan", "rowspan", "style"]),
+    ("th", &["align", "colspan", "rowspan", "style"]),
+    ("thead", &[]),
+    ("tr", &[]),
+    ("ul", &[]),
+];
+
+/// Elements removed together with their content
+const DROPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "noscript", "template", "textarea", "svg",
+    "math",
+];
+
+/// Cleans the rendered HTML, raw HTML from the markdown included, down to the allowlist
+static SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut builder = ammonia::Builder::empty();
+    builder
+        .tags(ALLOWED_TAGS.iter().map(|(tag, _)| *tag).collect())
+        .tag_attributes(
+            ALLOWED_TAGS
+                .iter()
+                .map(|(tag, attributes)| (*tag, attributes.iter().copied().collect()))
+                .collect(),
+        )
+        .generic_attributes(HashSet::new())
+        .clean_content_tags(DROPPED_ELEMENTS.iter().copied().collect())
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(Some("nofollow noopener"))
+        .filter_style_properties(HashSet::from(["text-align"]))
+        .attribute_filter(|_, attribute, value| {
+            // Only the classes of highlighted code blocks
+            let highlighting =
+                |class: &str| class.starts_with("language-") || class.starts_with("hl-");
+            (attribute != "class" || value.split_whitespace().all(highlighting))
+                .then_some(value.into())
+        });
+    builder
+});
+
+/// How a language is split into highlighted tokens
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    keywords: &'static [&'static str],
+}
+
+const JAVASCRIPT: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    keywords: &[
+        "as",
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "implements",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "null",
+        "of",
+        "private",
+        "protected",
+        "public",
+        "readonly",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "type",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ],
+};
+
+const JSON: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    keywords: &["true", "false", "null"],
+};
+
+const SHELL: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+        "in", "local", "return", "then", "until", "while",
+    ],
+};
+
+const CSS: Syntax = Syntax {
+    line_comments: &[],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    keywords: &[],
+};
+
+const YAML: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    keywords: &["true", "false", "null", "yes", "no"],
+};
+
+/// Renders package READMEs from markdown to HTML. Raw HTML in the markdown is reduced to
+/// an allowlist of elements and attributes, and links may only use safe schemes.
+pub struct ReadmeService;
+
+impl ReadmeService {
+    /// The rendered README of a version, the one tagged `latest` by default. `version` may
+    /// also be a dist-tag or a range. Callers check read access first.
+    pub fn get(
+        name: &str,
+        version: Option<&str>,
+        state: &AppState,
+    ) -> Result<PackageReadme, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+        let not_found = || ApiError::NotFound(format!("Package '{name}' not found"));
+
+        let package = state
+            .database
+            .get_package_with_versions(name)
+            .map_err(db_error)?
+            .ok_or_else(not_found)?;
+        let tags = state
+            .database
+            .get_package_tags_map(name)
+            .map_err(db_error)?;
+
+        let spec = version.unwrap_or("latest");
+        let available: Vec<&str> = package
+            .versions
+            .iter()
+            .filter(|v| v.version.yanked_at.is_none())
+            .map(|v| v.version.version.as_str())
+            .collect();
+        let resolved = if package.versions.iter().any(|v| v.version.version == spec) {
+            Some(spec.to_string())
+        } else if let Some(tagged) = tags.get(spec) {
+            Some(tagged.clone())
+        } else if version.is_none() {
+            versions::latest(available.iter().copied())
+        } else {
+            versions::max_satisfying(available.iter().copied(), spec)
+        };
+        let resolved = resolved
+            .ok_or_else(|| ApiError::NotFound(format!("No version of {name} matches {spec}")))?;
+
+        let markdown = package
+            .versions
+            .iter()
+            .find(|v| v.version.version == resolved)
+            .and_then(|v| v.version.readme.as_deref())
+            .filter(|readme| !readme.trim().is_empty())
+            .ok_or_else(|| ApiError::NotFound(format!("{name}@{resolved} has no README")))?;
+
+        Ok(PackageReadme {
+            name: name.to_string(),
+            version: resolved,
+            html: Self::render(markdown),
+        })
+    }
+
+    pub fn render(markdown: &str) -> String {
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+        let mut events = Vec::new();
+        let mut code: Option<(String, String)> = None;
+        for event in Parser::new_ext(markdown, options) {
+            match event {
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => {
+                            info.split_whitespace().next().unwrap_or("").to_string()
+                        }
+                        CodeBlockKind::Indented => String::new(),
+                    };
+                    code = Some((language, String::new()));
+                }
+                Event::Text(text) if code.is_some() => {
+                    if let Some((_, body)) = &mut code {
+                        body.push_str(&text);
+                    }
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    let (language, body) = code.take().unwrap_or_default();
+                    events.push(Event::Html(Self::code_block(&language, &body).into()));
+                }
+                event => events.push(event),
+            }
+        }
+
+        // GitHub style heading anchors, numbered when repeated
+        let mut slugs: HashMap<String, usize> = HashMap::new();
+        for i in 0..events.len() {
+            if !matches!(events[i], Event::Start(Tag::Heading { .. })) {
+                continue;
+            }
+            let text: String = events[i + 1..]
+                .iter()
+                .take_while(|event| !matches!(event, Event::End(TagEnd::Heading(_))))
+                .filter_map(|event| match event {
+                    Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                    _ => None,
+                })
+                .collect();
+            let slug = slug(&text);
+            let count = slugs.entry(slug.clone()).or_insert(0);
+            let anchor = if *count == 0 {
+                slug
+            } else {
+                format!("{slug}-{count}")
+            };
+            *count += 1;
+            if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+                *id = Some(anchor.into());
+            }
+        }
+
+        let mut out = String::new();
+        html::push_html(&mut out, events.into_iter());
+        SANITIZER.clean(&out).to_string()
+    }
+
+    fn code_block(language: &str, code: &str) -> String {
+        if language.is_empty() {
+            format!("<pre><code>{}</code></pre>\n", escape_code(code))
+        } else {
+            format!(
+                "<pre><code class=\"language-{}\">{}</code></pre>\n",
+                escape_code(language),
+                Self::highlight(code, language)
+            )
+        }
+    }
+    /// Wraps comments, strings, numbers and keywords of known languages in spans
+    fn highlight(code: &str, language: &str) -> String {
+        let syntax = match language.to_lowercase().as_str() {
+            "js" | "javascript" | "jsx" | "mjs" | "cjs" | "ts" | "typescript" | "tsx" => {
+                &JAVASCRIPT
+            }
+            "json" | "jsonc" | "json5" => &JSON,
+            "sh" | "bash" | "shell" | "zsh" | "console" | "shellsession" => &SHELL,
+            "css" | "scss" | "less" => &CSS,
+            "yaml" | "yml" => &YAML,
+            "diff" | "patch" => return Self::highlight_diff(code),
+            _ => return escape_code(code),
+        };
+
+        let mut out = String::new();
+        let mut rest = code;
+        let mut previous: Option<char> = None;
+        while let Some(c) = rest.chars().next() {
+            let after_space = previous.is_none_or(char::is_whitespace);
+            let token_end = if let Some((open, close)) = syntax
+                .block_comment
+                .filter(|(open, _)| rest.starts_with(open))
+            {
+                Some((
+                    rest[open.len()..]
+                        .find(close)
+                        .map_or(rest.len(), |end| open.len() + end + close.len()),
+                    "comment",
+                ))
+            } else if syntax
+                .line_comments
+                .iter()
+                .any(|start| rest.starts_with(start) && (*start != "#" || after_space))
+            {
+                Some((rest.find('\n').unwrap_or(rest.len()), "comment"))
+            } else if syntax.quotes.contains(&c) {
+                Some((string_end(rest, c), "string"))
+            } else if c.is_ascii_digit() && !previous.is_some_and(is_identifier) {
+                Some((
+                    rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                        .unwrap_or(rest.len()),
+                    "number",
+                ))
+            } else if is_identifier(c) {
+                let end = rest.find(|c| !is_identifier(c)).unwrap_or(rest.len());
+                Some((
+                    end,
+                    if syntax.keywords.contains(&&rest[..end]) {
+                        "keyword"
+                    } else {
+                        ""
+                    },
+                ))
+            } else {
+                None
+            };
+
+            let end = match token_end {
+                Some((end, class)) => {
+                    let end = end.max(c.len_utf8());
+                    if class.is_empty() {
+                        out.push_str(&escape_code(&rest[..end]));
+                    } else {
+                        out.push_str(&format!(
+                            "<span class=\"hl-{class}\">{}</span>",
+                            escape_code(&rest[..end])
+                        ));
+                    }
+                    end
+                }
+                None => {
+                    out.push_str(&escape_code(&rest[..c.len_utf8()]));
+                    c.len_utf8()
+                }
+            };
+            previous = rest[..end].chars().last();
+            rest = &rest[end..];
+        }
+        out
+    }
+
+    fn highlight_diff(code: &str) -> String {
+        code.split_inclusive('\n')
+            .map(|line| {
+                let class = match line.chars().next() {
+                    Some('+') => "addition",
+                    Some('-') => "deletion",
+                    Some('@') => "meta",
+                    _ => return escape_code(line),
+                };
+                format!("<span class=\"hl-{class}\">{}</span>", escape_code(line))
+            })
+            .collect()
+    }
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Length of the string literal at the start of `text`, up to its unescaped closing quote
+fn string_end(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\n' if quote != '`' => return i,
+            c if c == quote => return i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+fn escape_code(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Lowercase words joined by dashes
+fn slug(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let html = ReadmeService::render(
+            "# My Package\n\n\
+             [![npm][npm-badge]][npm-url] A **fast** _and_ `tiny` lib &copy; 2025.\n\n\
+             ## Install\n\n\
+             ```bash\nnpm install my-package # or yarn\n```\n\n\
+             - one\n- two\n  - nested\n\n\
+             1. first\n2. second\n\n\
+             | Option | Default |\n|:--|--:|\n| `size` | 10 |\n\n\
+             > Note: see <https://example.com>\n\n\
+             [npm-badge]: https://img.shields.io/npm/v/my-package.svg\n\
+             [npm-url]: https://npmjs.com/package/my-package\n",
+        );
+
+        assert!(html.contains("<h1 id=\"my-package\">My Package</h1>"));
+        assert!(html.contains(
+            "<a href=\"https://npmjs.com/package/my-package\" rel=\"nofollow noopener\"><img src=\"https://img.shields.io/npm/v/my-package.svg\" alt=\"npm\"></a>"
+        ));
+        assert!(
+            html.contains("A <strong>fast</strong> <em>and</em> <code>tiny</code> lib © 2025.")
+        );
+        assert!(html.contains("<h2 id=\"install\">Install</h2>"));
+        assert!(html.contains(
+            "<pre><code class=\"language-bash\">npm install my-package <span class=\"hl-comment\"># or yarn</span>\n</code></pre>"
+        ));
+        assert!(
+            html.contains(
+                "<ul>\n<li>one</li>\n<li>two\n<ul>\n<li>nested</li>\n</ul>\n</li>\n</ul>"
+            )
+        );
+        assert!(html.contains("<ol>\n<li>first</li>\n<li>second</li>\n</ol>"));
+        assert!(html.contains(
+            "<th style=\"text-align:left\">Option</th><th style=\"text-align:right\">Default</th>"
+        ));
+        assert!(html.contains("<td style=\"text-align:left\"><code>size</code></td>"));
+        assert!(html.contains(
+            "<blockquote>\n<p>Note: see <a href=\"https://example.com\" rel=\"nofollow noopener\">https://example.com</a></p>\n</blockquote>"
+        ));
+        assert!(!html.contains("[npm-url]:"));
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let html = ReadmeService::render(
+            "<p align=\"center\" onclick=\"steal()\"><img src=\"logo.png\" onerror=\"alert(1)\" width=\"100\"></p>\n\n\
+             <script>alert(document.cookie)</script>\n\n\
+             [click](javascript:alert(1)) and <a href=\"JavaScript:alert(1)\">this</a> \
+             <iframe src=\"https://evil.example\"></iframe>\n",
+        );
+
+        assert!(html.contains("<p align=\"center\"><img src=\"logo.png\" width=\"100\"></p>"));
+        assert!(html.contains("<a rel=\"nofollow noopener\">click</a>"));
+        assert!(html.contains("<a rel=\"nofollow noopener\">this</a>"));
+        assert_eq!(
+            ReadmeService::render("Hi <style>p{}</style>there"),
+            "<p>Hi there</p>\n"
+        );
+        for unsafe_html in [
+            "onclick",
+            "onerror",
+            "script",
+            "cookie",
+            "iframe",
+            "javascript",
+        ] {
+            assert!(
+                !html.to_lowercase().contains(unsafe_html),
+                "{unsafe_html}: {html}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sanitize_unclosed_tags() {
+        let html = ReadmeService::render(
+            "<div>\n<img src=x onerror=alert(1) <b>\n</div>\n\n\
+             Inline <img src=x onerror=alert(1) <b> html\n",
+        );
+
+        assert!(html.contains("<img src=\"x\">"), "{html}");
+        assert!(!html.contains("<img src=x onerror"), "{html}");
+        assert!(!html.contains("onerror=\""), "{html}");
+    }
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            ReadmeService::highlight("const a = \"<b>\"; // 42", "js"),
+            "<span class=\"hl-keyword\">const</span> a = <span class=\"hl-string\">&quot;&lt;b&gt;&quot;</span>; <span class=\"hl-comment\">// 42</span>"
+        );
+        assert_eq!(
+            ReadmeService::highlight("{\"port\": 8000}", "json"),
+            "{<span class=\"hl-string\">&quot;port&quot;</span>: <span class=\"hl-number\">8000</span>}"
+        );
+        assert_eq!(ReadmeService::highlight("a < b", "brainfuck"), "a &lt; b");
+    }
+}
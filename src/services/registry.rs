@@ -1,11 +1,20 @@
-use crate::config::AppConfig;
+use crate::config::{AppConfig, UrlRewriteRule};
 use crate::error::ApiError;
-use crate::models::{Package, PackageVersion};
+use crate::fairings::{ClientHeaders, RequestId};
+use crate::models::{Package, PackageFile, PackageVersion};
+use crate::services::{
+    AllowlistService, NameBlocklistService, ProvenanceService, ScopePolicyService, SigningService,
+    YankService,
+};
 use crate::state::AppState;
+use crate::versions;
 use diesel::prelude::*;
 use log::{debug, error, info, warn};
 use rocket::serde::json::Value;
 
+/// Times an interrupted upstream tarball download is resumed before giving up
+const TARBALL_RESUME_ATTEMPTS: usize = 3;
+
 /// Clean repository URL to make it browser-accessible
 /// Removes git+ prefix and .git suffix, converts SSH URLs to HTTPS
 fn clean_repository_url(url: &str) -> String {
@@ -33,6 +42,19 @@ fn clean_repository_url(url: &str) -> String {
     cleaned
 }
 
+/// Apply the first matching URL rewrite rule, keeping a `git+` prefix intact
+fn apply_url_rewrite_rules(url: &str, rules: &[UrlRewriteRule]) -> Option<String> {
+    let (prefix, rest) = match url.strip_prefix("git+") {
+        Some(rest) => ("git+", rest),
+        None => ("", url),
+    };
+
+    rules.iter().find_map(|rule| {
+        rest.strip_prefix(&rule.from)
+            .map(|path| format!("{prefix}{}{path}", rule.to))
+    })
+}
+
 pub struct RegistryService;
 
 impl RegistryService {
@@ -57,12 +79,12 @@ impl RegistryService {
                             if let Some(path_part) =
                                 tarball_url.strip_prefix(&format!("{}/", config.upstream_registry))
                             {
-                                // Use request host if available, otherwise fall back to config host
-                                let host_to_use = request_host.unwrap_or(&config.host);
-
-                                // Rewrite to our proxy server URL using the same scheme as the request
-                                let new_url =
-                                    format!("{scheme}://{host_to_use}/registry/{path_part}");
+                                // Rewrite to our proxy server URL as seen by the client, or the
+                                // public URL outside of a request
+                                let new_url = format!(
+                                    "{}/registry/{path_part}",
+                                    config.registry_base_url(scheme, request_host)
+                                );
 
                                 dist.insert("tarball".to_string(), Value::String(new_url.clone()));
                                 debug!(
@@ -77,6 +99,45 @@ impl RegistryService {
         Ok(())
     }
 
+    fn rewrite_metadata_urls(json: &mut Value, config: &AppConfig) {
+        if config.url_rewrite_rules.is_empty() {
+            return;
+        }
+
+        // Rewrite homepage/repository on the document itself and on every version it contains
+        Self::rewrite_document_urls(json, &config.url_rewrite_rules);
+        if let Some(versions) = json.get_mut("versions").and_then(|v| v.as_object_mut()) {
+            for version_data in versions.values_mut() {
+                Self::rewrite_document_urls(version_data, &config.url_rewrite_rules);
+            }
+        }
+    }
+
+    fn rewrite_document_urls(document: &mut Value, rules: &[UrlRewriteRule]) {
+        if let Some(Value::String(homepage)) = document.get_mut("homepage")
+            && let Some(new_url) = apply_url_rewrite_rules(homepage, rules)
+        {
+            debug!("Rewrote homepage URL: {homepage} -> {new_url}");
+            *homepage = new_url;
+        }
+
+        let repository_url = match document.get_mut("repository") {
+            Some(Value::String(url)) => Some(url),
+            Some(Value::Object(repository)) => match repository.get_mut("url") {
+                Some(Value::String(url)) => Some(url),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(url) = repository_url
+            && let Some(new_url) = apply_url_rewrite_rules(url, rules)
+        {
+            debug!("Rewrote repository URL: {url} -> {new_url}");
+            *url = new_url;
+        }
+    }
+
     pub async fn store_package_metadata_in_database(
         package: &str,
         json: &Value,
@@ -336,8 +397,10 @@ impl RegistryService {
         };
 
         let tarball_url = format!(
-            "http://{}/registry/{}/-/{}",
-            state.config.host, pkg.name, tarball_filename
+            "{}/registry/{}/-/{}",
+            state.config.get_public_url(),
+            pkg.name,
+            tarball_filename
         );
 
         if let Some(dist) = package_json.get_mut("dist") {
@@ -350,10 +413,67 @@ impl RegistryService {
             });
         }
 
+        Self::insert_dist_checksums(&mut package_json, pkg_version);
+        SigningService::attach_signatures(
+            &mut package_json,
+            &pkg.name,
+            pkg_version,
+            &state.database,
+        );
+        ProvenanceService::attach_attestations(&mut package_json, pkg_version.id, state);
+
         Ok(package_json)
     }
 
-    async fn construct_version_metadata_from_db_fields(
+    /// Overrides `dist.shasum` and `dist.integrity` with the checksums the registry computed
+    fn insert_dist_checksums(version_data: &mut Value, pkg_version: &PackageVersion) {
+        use serde_json::json;
+
+        let Some(integrity) = &pkg_version.integrity else {
+            return;
+        };
+        if let Some(dist) = version_data.get_mut("dist").and_then(Value::as_object_mut) {
+            dist.insert("integrity".to_string(), json!(integrity));
+            if let Some(shasum) = &pkg_version.shasum {
+                dist.insert("shasum".to_string(), json!(shasum));
+            }
+        }
+    }
+
+    /// Computes and stores the checksums of a version published before the registry kept them
+    fn backfill_dist_checksums(
+        pkg_version: &PackageVersion,
+        file: &PackageFile,
+        state: &AppState,
+    ) -> PackageVersion {
+        let mut pkg_version = pkg_version.clone();
+        if pkg_version.integrity.is_some() {
+            return pkg_version;
+        }
+
+        match std::fs::read(&file.file_path) {
+            Ok(data) => {
+                let shasum = SigningService::shasum_for(&data);
+                let integrity = SigningService::integrity_for(&data);
+                if let Err(e) =
+                    state
+                        .database
+                        .set_version_checksums(pkg_version.id, &shasum, &integrity)
+                {
+                    warn!("Failed to store checksums of {}: {e}", file.filename);
+                }
+                pkg_version.shasum = Some(shasum);
+                pkg_version.integrity = Some(integrity);
+                pkg_version.registry_key_id = None;
+                pkg_version.registry_signature = None;
+            }
+            Err(e) => warn!("Failed to read {} for checksums: {e}", file.file_path),
+        }
+
+        pkg_version
+    }
+
+    pub(crate) async fn construct_version_metadata_from_db_fields(
         pkg: &Package,
         pkg_version: &PackageVersion,
         state: &AppState,
@@ -418,8 +538,10 @@ impl RegistryService {
         };
 
         let tarball_url = format!(
-            "http://{}/registry/{}/-/{}",
-            state.config.host, pkg.name, tarball_filename
+            "{}/registry/{}/-/{}",
+            state.config.get_public_url(),
+            pkg.name,
+            tarball_filename
         );
 
         let mut dist = json!({
@@ -429,6 +551,9 @@ impl RegistryService {
         if let Some(shasum) = &pkg_version.shasum {
             dist["shasum"] = json!(shasum);
         }
+        if let Some(integrity) = &pkg_version.integrity {
+            dist["integrity"] = json!(integrity);
+        }
 
         version_data["dist"] = dist;
 
@@ -452,10 +577,12 @@ impl RegistryService {
         }
 
         // If not cached, fetch from upstream
-        let url = format!("{}/{package}", state.config.upstream_registry);
-        let client = reqwest::Client::new();
-
-        match client.get(&url).send().await {
+        if Self::ensure_upstream_allowed(package, state).is_err() {
+            return None;
+        }
+        let url = format!("{}/{package}", state.upstream_registry());
+        let _permit = state.upstream_limiter.acquire(&url).await.ok()?;
+        match RequestId::forward(state.client.get(&url)).send().await {
             Ok(response) if response.status().is_success() => {
                 match response.json::<Value>().await {
                     Ok(package_metadata) => {
@@ -484,11 +611,184 @@ impl RegistryService {
         None
     }
 
+    /// Runs upstream work within the configured per-request deadline.
+    /// Returns `None` when the budget ran out before the work completed.
+    async fn within_upstream_deadline<T>(
+        state: &AppState,
+        work: impl std::future::Future<Output = Result<T, ApiError>>,
+    ) -> Option<Result<T, ApiError>> {
+        if state.config.upstream_deadline_ms == 0 {
+            return Some(work.await);
+        }
+
+        let deadline = std::time::Duration::from_millis(state.config.upstream_deadline_ms);
+        tokio::time::timeout(deadline, work).await.ok()
+    }
+
+    /// Packages in scopes with upstream lookups disabled only exist locally, and blocked
+    /// names or names missing from the allowlist may be refused outright
+    pub(crate) fn ensure_upstream_allowed(package: &str, state: &AppState) -> Result<(), ApiError> {
+        if !ScopePolicyService::upstream_allowed(package, state)? {
+            info!("Not looking up {package} upstream, it is internal or its scope disallows it");
+            return Err(ApiError::NotFound(format!("Package '{package}' not found")));
+        }
+
+        NameBlocklistService::check_upstream(package, state)?;
+        AllowlistService::check_upstream(package, state)
+    }
+
+    /// Refreshes the dist-tags of a cached upstream package document once they are older
+    /// than their TTL, without fetching the whole document. `None` when the tags point at
+    /// versions the document doesn't have yet and it has to be fetched again.
+    async fn refresh_dist_tags(
+        package: &str,
+        mut metadata: Value,
+        state: &AppState,
+    ) -> Option<Value> {
+        let Some(ttl) = state.config.dist_tags_ttl_secs(package) else {
+            return Some(metadata);
+        };
+        if state
+            .cache
+            .dist_tags_age(package)
+            .is_none_or(|age| age.as_secs() <= ttl)
+        {
+            return Some(metadata);
+        }
+        let published = state
+            .database
+            .get_package_by_name(package)
+            .ok()
+            .flatten()
+            .is_some_and(|pkg| pkg.author_id.is_some());
+        if published || Self::ensure_upstream_allowed(package, state).is_err() {
+            return Some(metadata);
+        }
+
+        let url = format!(
+            "{}/-/package/{package}/dist-tags",
+            state.upstream_registry()
+        );
+        let Ok(_permit) = state.upstream_limiter.acquire(&url).await else {
+            return Some(metadata);
+        };
+        let tags = match RequestId::forward(state.client.get(&url)).send().await {
+            Ok(response) if response.status().is_success() => {
+                response.json::<Value>().await.ok().filter(Value::is_object)
+            }
+            Ok(response) => {
+                warn!(
+                    "Fetching dist-tags of {package} returned {}",
+                    response.status()
+                );
+                None
+            }
+            Err(e) => {
+                warn!("Fetching dist-tags of {package} failed: {e}");
+                None
+            }
+        };
+        // Keep serving the cached tags and try again after the TTL
+        let Some(tags) = tags.filter(|tags| tags != &metadata["dist-tags"]) else {
+            state.cache.mark_dist_tags_checked(package);
+            return Some(metadata);
+        };
+
+        let versions = metadata.get("versions").and_then(Value::as_object);
+        let known = tags.as_object().into_iter().flatten().all(|(_, version)| {
+            version.as_str().is_some_and(|version| {
+                versions.is_some_and(|versions| versions.contains_key(version))
+            })
+        });
+        if !known {
+            info!("dist-tags of {package} point at new versions, fetching the package again");
+            if let Err(e) = state
+                .cache
+                .set_metadata_fetched_at(package, std::time::UNIX_EPOCH)
+                .await
+            {
+                warn!("Failed to expire cached metadata of {package}: {e}");
+            }
+            return None;
+        }
+
+        info!("Refreshed dist-tags of {package}");
+        metadata["dist-tags"] = tags;
+        if let Err(e) = state.cache.update_dist_tags(package, &metadata).await {
+            warn!("Failed to store refreshed dist-tags of {package}: {e}");
+        }
+        Some(metadata)
+    }
+
+    fn deadline_exceeded(what: &str, state: &AppState) -> ApiError {
+        ApiError::GatewayTimeout(format!(
+            "Upstream did not respond within {} ms for {what}",
+            state.config.upstream_deadline_ms
+        ))
+    }
+
     pub async fn get_package_metadata(
         package: &str,
         state: &AppState,
         request_host: Option<&str>,
         request_scheme: &str,
+    ) -> Result<Value, ApiError> {
+        let work = Self::fetch_package_metadata(package, state, request_host, request_scheme);
+        match Self::within_upstream_deadline(state, work).await {
+            Some(result) => result,
+            None => match state.cache.get_stale_metadata(package) {
+                Some(metadata) => {
+                    warn!("Upstream deadline exceeded for {package}, serving stale metadata");
+                    Ok(metadata)
+                }
+                None => Err(Self::deadline_exceeded(package, state)),
+            },
+        }
+    }
+
+    /// Package metadata fetched outside of a request, e.g. to warm the cache. Cached documents
+    /// keep the tarball host they were generated for, so the public URL is used.
+    pub(crate) async fn warm_package_metadata(
+        package: &str,
+        state: &AppState,
+    ) -> Result<Value, ApiError> {
+        Self::get_package_metadata(package, state, None, state.config.get_scheme()).await
+    }
+
+    /// Fetches the package metadata again ahead of its TTL. The cached copy is expired so the
+    /// read revalidates upstream, and put back when that fails.
+    pub(crate) async fn refresh_package_metadata(
+        package: &str,
+        state: &AppState,
+    ) -> Result<Value, ApiError> {
+        let fetched_at = state
+            .cache
+            .metadata_age(package)
+            .map(|age| std::time::SystemTime::now() - age);
+        if fetched_at.is_some()
+            && let Err(e) = state
+                .cache
+                .set_metadata_fetched_at(package, std::time::UNIX_EPOCH)
+                .await
+        {
+            warn!("Failed to expire cached metadata of {package}: {e}");
+        }
+
+        let result = Self::warm_package_metadata(package, state).await;
+        if let (Err(_), Some(fetched_at)) = (&result, fetched_at) {
+            let _ = state
+                .cache
+                .set_metadata_fetched_at(package, fetched_at)
+                .await;
+        }
+        result
+    }
+
+    async fn fetch_package_metadata(
+        package: &str,
+        state: &AppState,
+        request_host: Option<&str>,
+        request_scheme: &str,
     ) -> Result<Value, ApiError> {
         info!("Fetching metadata for package: {package}");
 
@@ -509,7 +809,9 @@ impl RegistryService {
             // Validate that the cached metadata is complete and useful
             if Self::is_metadata_valid(&metadata) {
                 info!("Metadata cache hit for package: {package} (size: {data_size} bytes)");
-                return Ok(metadata);
+                if let Some(metadata) = Self::refresh_dist_tags(package, metadata, state).await {
+                    return Ok(metadata);
+                }
             } else {
                 warn!(
                     "Cached metadata for package {package} is invalid/incomplete, revalidating from upstream"
@@ -565,8 +867,11 @@ impl RegistryService {
                 // Note: Cache will be overwritten with correct data from upstream
 
                 // Fetch from upstream
-                let url = format!("{}/{package}", state.config.upstream_registry);
-                let response = state.client.get(&url).send().await?;
+                Self::ensure_upstream_allowed(package, state)?;
+                let url = format!("{}/{package}", state.upstream_registry());
+                let _permit = state.upstream_limiter.acquire(&url).await?;
+                let response = RequestId::forward(state.client.get(&url)).send().await?;
+                ClientHeaders::relay(response.headers());
 
                 if response.status().is_success() {
                     // Extract ETag from response headers
@@ -585,6 +890,7 @@ impl RegistryService {
                                 request_scheme,
                                 request_host,
                             )?;
+                            Self::rewrite_metadata_urls(&mut json, &state.config);
 
                             info!("Successfully proxied metadata for package: {package}");
 
@@ -602,6 +908,9 @@ impl RegistryService {
                                     "Failed to serialize metadata for caching: {e}"
                                 ))
                             })?;
+                            state
+                                .database
+                                .queue_upstream_transfer(package, metadata_str.len() as u64);
 
                             if let Err(e) = state
                                 .cache
@@ -641,10 +950,13 @@ impl RegistryService {
             }
         } else {
             // No published versions found, proxy to upstream
-            let url = format!("{}/{package}", state.config.upstream_registry);
+            Self::ensure_upstream_allowed(package, state)?;
+            let url = format!("{}/{package}", state.upstream_registry());
+
+            let _permit = state.upstream_limiter.acquire(&url).await?;
 
             // Check if we have cached metadata with ETag for conditional request
-            let mut request = state.client.get(&url);
+            let mut request = RequestId::forward(state.client.get(&url));
 
             // Add If-None-Match header if we have cached ETag
             if let Some(cache_entry) = state
@@ -659,6 +971,7 @@ impl RegistryService {
             }
 
             let response = request.send().await?;
+            ClientHeaders::relay(response.headers());
 
             if response.status() == 304 {
                 // Not Modified - use cached version
@@ -705,6 +1018,7 @@ impl RegistryService {
                             request_scheme,
                             request_host,
                         )?;
+                        Self::rewrite_metadata_urls(&mut json, &state.config);
 
                         info!("Successfully proxied metadata for package: {package}");
 
@@ -721,6 +1035,9 @@ impl RegistryService {
                                 "Failed to serialize metadata for caching: {e}"
                             ))
                         })?;
+                        state
+                            .database
+                            .queue_upstream_transfer(package, metadata_str.len() as u64);
 
                         if let Err(e) = state
                             .cache
@@ -784,6 +1101,69 @@ impl RegistryService {
         package: &str,
         version: &str,
         state: &AppState,
+    ) -> Result<Value, ApiError> {
+        let version = &Self::resolve_published_version(package, version, state)?;
+        let work = Self::fetch_package_version_metadata(package, version, state);
+        match Self::within_upstream_deadline(state, work).await {
+            Some(result) => result,
+            None => match state.cache.get_stale_version_metadata(package, version) {
+                Some(metadata) => {
+                    warn!(
+                        "Upstream deadline exceeded for {package}@{version}, serving stale metadata"
+                    );
+                    Ok(metadata)
+                }
+                None => Err(Self::deadline_exceeded(
+                    &format!("{package}@{version}"),
+                    state,
+                )),
+            },
+        }
+    }
+
+    /// Resolves a dist-tag or npm range to a version of a locally published package, yanked
+    /// versions excluded. Exact versions and upstream packages are left to the lookup, the
+    /// upstream registry resolves its own ranges.
+    fn resolve_published_version(
+        package: &str,
+        spec: &str,
+        state: &AppState,
+    ) -> Result<String, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+        if semver::Version::parse(spec).is_ok() {
+            return Ok(spec.to_string());
+        }
+        let published = state
+            .database
+            .get_package_with_versions(package)
+            .map_err(db_error)?
+            .filter(|pkg| pkg.package.author_id.is_some());
+        let Some(published) = published else {
+            return Ok(spec.to_string());
+        };
+
+        if let Some(tagged) = state
+            .database
+            .get_package_tags_map(package)
+            .map_err(db_error)?
+            .remove(spec)
+        {
+            return Ok(tagged);
+        }
+
+        let available = published
+            .versions
+            .iter()
+            .filter(|v| v.version.yanked_at.is_none())
+            .map(|v| v.version.version.as_str());
+        versions::max_satisfying(available, spec)
+            .ok_or_else(|| ApiError::NotFound(format!("No version of {package} matches {spec}")))
+    }
+
+    async fn fetch_package_version_metadata(
+        package: &str,
+        version: &str,
+        state: &AppState,
     ) -> Result<Value, ApiError> {
         info!("Fetching metadata for package: {package} version: {version}");
 
@@ -825,17 +1205,23 @@ impl RegistryService {
 
         if let Some((pkg, pkg_version)) = local_version {
             info!("Found locally published version: {package}@{version}");
-            return Self::generate_version_metadata_from_database(&pkg, &pkg_version, state).await;
+            let mut metadata =
+                Self::generate_version_metadata_from_database(&pkg, &pkg_version, state).await?;
+            YankService::mark_version_document(&mut metadata, &pkg_version);
+            return Ok(metadata);
         }
 
         info!(
             "Version metadata cache miss for package: {package}@{version}, fetching from upstream"
         );
 
-        let url = format!("{}/{package}/{version}", state.config.upstream_registry);
+        Self::ensure_upstream_allowed(package, state)?;
+        let url = format!("{}/{package}/{version}", state.upstream_registry());
+
+        let _permit = state.upstream_limiter.acquire(&url).await?;
 
         // Check if we have cached metadata with ETag for conditional request
-        let mut request = state.client.get(&url);
+        let mut request = RequestId::forward(state.client.get(&url));
 
         // Add If-None-Match header if we have cached ETag
         if let Some(cache_entry) = state
@@ -850,6 +1236,7 @@ impl RegistryService {
         }
 
         let response = request.send().await?;
+        ClientHeaders::relay(response.headers());
 
         if response.status().is_success() {
             // Extract ETag from response headers
@@ -865,6 +1252,8 @@ impl RegistryService {
                         "Successfully proxied metadata for package: {package} version: {version}"
                     );
 
+                    Self::rewrite_metadata_urls(&mut json, &state.config);
+
                     // Add README from package-level metadata if not present
                     if json.get("readme").is_none() {
                         if let Some(readme) =
@@ -888,6 +1277,9 @@ impl RegistryService {
                             "Failed to serialize version metadata for caching: {e}"
                         ))
                     })?;
+                    state
+                        .database
+                        .queue_upstream_transfer(package, metadata_str.len() as u64);
 
                     if let Err(e) = state
                         .cache
@@ -962,6 +1354,17 @@ impl RegistryService {
         package: &str,
         filename: &str,
         state: &AppState,
+    ) -> Result<Vec<u8>, ApiError> {
+        let work = Self::fetch_package_tarball(package, filename, state);
+        Self::within_upstream_deadline(state, work)
+            .await
+            .unwrap_or_else(|| Err(Self::deadline_exceeded(filename, state)))
+    }
+
+    async fn fetch_package_tarball(
+        package: &str,
+        filename: &str,
+        state: &AppState,
     ) -> Result<Vec<u8>, ApiError> {
         info!("Fetching tarball for package: {package} filename: {filename}");
 
@@ -979,24 +1382,30 @@ impl RegistryService {
         }
 
         // Cache miss, fetch from upstream
-        let url = format!(
-            "{}/{}/-/{filename}",
-            state.config.upstream_registry, package
-        );
+        Self::ensure_upstream_allowed(package, state)?;
+        let url = format!("{}/{}/-/{filename}", state.upstream_registry(), package);
 
-        let response = state.client.get(&url).send().await?;
+        let _permit = state.upstream_limiter.acquire(&url).await?;
+        let response = RequestId::forward(state.client.get(&url)).send().await?;
+        ClientHeaders::relay(response.headers());
 
         if response.status().is_success() {
-            // Extract ETag for cache validation
-            let etag = response
-                .headers()
-                .get("etag")
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.to_string());
+            // Extract ETag and Last-Modified for cache validation
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|s| s.to_string())
+            };
+            let etag = header(reqwest::header::ETAG);
+            let last_modified = header(reqwest::header::LAST_MODIFIED);
 
-            match response.bytes().await {
-                Ok(bytes) => {
-                    let data = bytes.to_vec();
+            match Self::read_tarball(&state.client, response, &url).await {
+                Ok(data) => {
+                    state
+                        .database
+                        .queue_upstream_transfer(package, data.len() as u64);
 
                     // Store in cache
                     if let Err(e) = state
@@ -1006,6 +1415,7 @@ impl RegistryService {
                             filename,
                             &data,
                             etag.as_deref(),
+                            last_modified.as_deref(),
                             &url,
                             Some(&*state.database),
                         )
@@ -1046,35 +1456,116 @@ impl RegistryService {
         }
     }
 
+    /// Reads a tarball response to the end. When the connection drops mid-stream and the
+    /// upstream accepts ranges, the download continues from the last received byte instead of
+    /// starting over. Only complete tarballs are returned, the bytes read so far are never
+    /// handed out.
+    async fn read_tarball(
+        client: &reqwest::Client,
+        mut response: reqwest::Response,
+        url: &str,
+    ) -> Result<Vec<u8>, reqwest::Error> {
+        let headers = response.headers();
+        let resumable = headers
+            .get(reqwest::header::ACCEPT_RANGES)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ranges| ranges.eq_ignore_ascii_case("bytes"));
+        // Strong ETag, or else Last-Modified, making sure the rest comes from the same tarball
+        let validator = headers
+            .get(reqwest::header::ETAG)
+            .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+            .or_else(|| headers.get(reqwest::header::LAST_MODIFIED))
+            .cloned();
+        let mut data = Vec::with_capacity(response.content_length().unwrap_or(0) as usize);
+        let mut attempts = 0;
+
+        loop {
+            let interrupted = loop {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => data.extend_from_slice(&chunk),
+                    Ok(None) => return Ok(data),
+                    Err(e) => break e,
+                }
+            };
+            if !resumable || attempts == TARBALL_RESUME_ATTEMPTS {
+                return Err(interrupted);
+            }
+            attempts += 1;
+            warn!(
+                "Tarball download from {url} interrupted after {} bytes, resuming: {interrupted}",
+                data.len()
+            );
+
+            let mut request = RequestId::forward(client.get(url))
+                .header(reqwest::header::RANGE, format!("bytes={}-", data.len()));
+            if let Some(validator) = &validator {
+                request = request.header(reqwest::header::IF_RANGE, validator.clone());
+            }
+            response = request.send().await?;
+
+            let resumed_at = response
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|range| range.strip_prefix("bytes "))
+                .and_then(|range| range.split('-').next())
+                .and_then(|start| start.parse::<usize>().ok());
+            match response.status() {
+                reqwest::StatusCode::PARTIAL_CONTENT if resumed_at == Some(data.len()) => {}
+                // The tarball changed or the range was ignored, it is sent from the start
+                reqwest::StatusCode::OK => data.clear(),
+                status => {
+                    warn!("Upstream answered {status} to resuming the download from {url}");
+                    return Err(interrupted);
+                }
+            }
+        }
+    }
+
     pub async fn head_package_tarball(
         package: &str,
         filename: &str,
         state: &AppState,
-    ) -> Result<(), ApiError> {
+    ) -> Result<Option<u64>, ApiError> {
+        let work = Self::fetch_package_tarball_head(package, filename, state);
+        Self::within_upstream_deadline(state, work)
+            .await
+            .unwrap_or_else(|| Err(Self::deadline_exceeded(filename, state)))
+    }
+
+    async fn fetch_package_tarball_head(
+        package: &str,
+        filename: &str,
+        state: &AppState,
+    ) -> Result<Option<u64>, ApiError> {
         info!("HEAD request for tarball: {package} filename: {filename}");
 
         // Check cache first
-        if state
+        if let Some(cache_entry) = state
             .cache
             .get(package, filename, Some(&*state.database))
             .await
-            .is_some()
         {
             info!("Cache hit for HEAD tarball: {package} filename: {filename}");
-            return Ok(());
+            return Ok(Some(cache_entry.data.len() as u64));
         }
 
         // Cache miss, check upstream
-        let url = format!(
-            "{}/{}/-/{}",
-            state.config.upstream_registry, package, filename
-        );
+        Self::ensure_upstream_allowed(package, state)?;
+        let url = format!("{}/{}/-/{}", state.upstream_registry(), package, filename);
 
-        let response = state.client.head(&url).send().await?;
+        let _permit = state.upstream_limiter.acquire(&url).await?;
+        let response = RequestId::forward(state.client.head(&url)).send().await?;
+        ClientHeaders::relay(response.headers());
 
         if response.status().is_success() {
             info!("Successfully checked tarball for package: {package} filename: {filename}");
-            Ok(())
+            // Bodies of HEAD responses are empty, the size is only in the header
+            Ok(response
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok()))
         } else if response.status() == 404 {
             info!("Package tarball not found upstream (HEAD): {package} filename: {filename}");
             Err(ApiError::NotFound(format!(
@@ -1092,7 +1583,7 @@ impl RegistryService {
         }
     }
 
-    fn load_package_json_from_filesystem(
+    pub(crate) fn load_package_json_from_filesystem(
         package_name: &str,
         version: &str,
         state: &AppState,
@@ -1142,7 +1633,6 @@ impl RegistryService {
 
         let mut versions = HashMap::new();
         let mut dist_tags = HashMap::new();
-        let mut latest_version = "0.0.0".to_string();
         let mut package_description: Option<String> = None;
         let mut package_license: Option<String> = None;
         let mut package_homepage: Option<String> = None;
@@ -1181,17 +1671,17 @@ impl RegistryService {
             {
                 // Process each version
                 for version_with_files in pkg_with_versions.versions {
+                    // Yanked versions stay downloadable by exact version, but new installs
+                    // must not resolve to them
+                    if version_with_files.version.yanked_at.is_some() {
+                        continue;
+                    }
                     let version = version_with_files.version.version.clone();
 
                     // Load package.json from filesystem
                     if let Some(package_json) =
                         Self::load_package_json_from_filesystem(package_name, &version, state)?
                     {
-                        // Update latest version (simple string comparison for now)
-                        if version > latest_version {
-                            latest_version = version.clone();
-                        }
-
                         // Set description from package.json only as fallback if not set from database
                         if package_description.is_none() {
                             package_description = package_json
@@ -1203,11 +1693,11 @@ impl RegistryService {
                         // Get the first file for the tarball URL
                         if let Some(file) = version_with_files.files.first() {
                             // Create version metadata
-                            // Use request host if available, otherwise fall back to config host
-                            let host_to_use = request_host.unwrap_or(&state.config.host);
                             let tarball_url = format!(
-                                "{}://{}/registry/{}/-/{}",
-                                request_scheme, host_to_use, package_name, file.filename
+                                "{}/registry/{}/-/{}",
+                                state.config.registry_base_url(request_scheme, request_host),
+                                package_name,
+                                file.filename
                             );
 
                             let mut version_data = package_json.clone();
@@ -1223,6 +1713,25 @@ impl RegistryService {
                                 });
                             }
 
+                            let pkg_version = Self::backfill_dist_checksums(
+                                &version_with_files.version,
+                                file,
+                                state,
+                            );
+                            Self::insert_dist_checksums(&mut version_data, &pkg_version);
+
+                            SigningService::attach_signatures(
+                                &mut version_data,
+                                package_name,
+                                &pkg_version,
+                                &state.database,
+                            );
+                            ProvenanceService::attach_attestations(
+                                &mut version_data,
+                                version_with_files.version.id,
+                                state,
+                            );
+
                             versions.insert(version, version_data);
                         }
                     }
@@ -1230,6 +1739,9 @@ impl RegistryService {
             }
         }
 
+        let latest_version = versions::latest(versions.keys().map(String::as_str))
+            .unwrap_or_else(|| "0.0.0".to_string());
+
         // Get dist-tags from database
         match state.database.get_package_tags_map(package_name) {
             Ok(db_tags) => {
@@ -1248,6 +1760,8 @@ impl RegistryService {
             }
         }
 
+        YankService::repair_dist_tags(&mut dist_tags, versions.keys());
+
         // Create the complete package metadata
         let mut metadata = json!({
             "name": package_name,
@@ -1272,6 +1786,8 @@ impl RegistryService {
             metadata["keywords"] = json!(keywords);
         }
 
+        Self::rewrite_metadata_urls(&mut metadata, &state.config);
+
         Ok(metadata)
     }
 }
@@ -1324,4 +1840,117 @@ mod tests {
             "https://github.com/facebook/react"
         );
     }
+
+    #[test]
+    fn test_rewrite_metadata_urls() {
+        let config = AppConfig {
+            url_rewrite_rules: vec![UrlRewriteRule {
+                from: "https://github.com/".to_string(),
+                to: "https://git.internal/github/".to_string(),
+            }],
+            ..AppConfig::default()
+        };
+
+        let mut json = serde_json::json!({
+            "homepage": "https://github.com/facebook/react#readme",
+            "repository": { "type": "git", "url": "git+https://github.com/facebook/react.git" },
+            "versions": {
+                "1.0.0": {
+                    "homepage": "https://react.dev",
+                    "repository": "https://github.com/facebook/react"
+                }
+            }
+        });
+
+        RegistryService::rewrite_metadata_urls(&mut json, &config);
+
+        assert_eq!(
+            json["homepage"],
+            "https://git.internal/github/facebook/react#readme"
+        );
+        assert_eq!(
+            json["repository"]["url"],
+            "git+https://git.internal/github/facebook/react.git"
+        );
+        assert_eq!(json["versions"]["1.0.0"]["homepage"], "https://react.dev");
+        assert_eq!(
+            json["versions"]["1.0.0"]["repository"],
+            "https://git.internal/github/facebook/react"
+        );
+    }
+
+    /// Serves the canned responses to consecutive connections, sending the requests it
+    /// received back once all were served
+    async fn serve_responses(
+        responses: Vec<&'static str>,
+    ) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/left-pad/-/left-pad-1.0.0.tgz",
+            listener.local_addr().unwrap()
+        );
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let read = socket.read(&mut request).await.unwrap();
+                requests.push(String::from_utf8_lossy(&request[..read]).to_lowercase());
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_resumes_interrupted_tarball_download() {
+        let (url, server) = serve_responses(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nAccept-Ranges: bytes\r\nETag: \"abc\"\r\n\r\n0123",
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\nContent-Range: bytes 4-9/10\r\n\r\n456789",
+        ])
+        .await;
+        let client = reqwest::Client::new();
+        let response = client.get(&url).send().await.unwrap();
+
+        let data = RegistryService::read_tarball(&client, response, &url)
+            .await
+            .unwrap();
+        assert_eq!(data, b"0123456789");
+
+        let requests = server.await.unwrap();
+        assert!(requests[1].contains("range: bytes=4-"));
+        assert!(requests[1].contains("if-range: \"abc\""));
+    }
+
+    #[tokio::test]
+    async fn test_never_returns_partial_tarballs() {
+        // Without range support the download can't be resumed
+        let (url, server) =
+            serve_responses(vec!["HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123"]).await;
+        let client = reqwest::Client::new();
+        let response = client.get(&url).send().await.unwrap();
+        assert!(
+            RegistryService::read_tarball(&client, response, &url)
+                .await
+                .is_err()
+        );
+        server.await.unwrap();
+
+        // A resumed download has to continue where the first one stopped
+        let (url, server) = serve_responses(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nAccept-Ranges: bytes\r\n\r\n0123",
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: 8\r\nContent-Range: bytes 2-9/10\r\n\r\n23456789",
+        ])
+        .await;
+        let response = client.get(&url).send().await.unwrap();
+        assert!(
+            RegistryService::read_tarball(&client, response, &url)
+                .await
+                .is_err()
+        );
+        server.await.unwrap();
+    }
 }
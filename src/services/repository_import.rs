@@ -0,0 +1,362 @@
+use crate::error::ApiError;
+use crate::models::PackageArchive;
+use crate::services::{ArchiveService, AuthService};
+use crate::state::AppState;
+use clap::Parser;
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+
+/// Repository managers with an npm repository API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepositoryKind {
+    #[default]
+    Nexus,
+    Artifactory,
+}
+
+impl RepositoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Nexus => "nexus",
+            Self::Artifactory => "artifactory",
+        }
+    }
+}
+
+/// Options of the `clef import nexus` and `clef import artifactory` commands
+#[derive(Parser, Debug, Clone, PartialEq, Default)]
+pub struct RepositoryImportOptions {
+    /// Set from the command, `nexus` or `artifactory`
+    #[arg(skip)]
+    pub kind: RepositoryKind,
+    /// Base URL of the server, e.g. https://nexus.example.com
+    #[arg(long, value_name = "URL", value_parser = server_url)]
+    pub url: String,
+    /// Name of the npm repository
+    #[arg(long, value_name = "REPO")]
+    pub repository: String,
+    /// Authenticate as this user with the password read from stdin
+    #[arg(long, value_name = "USER", requires = "password_stdin")]
+    pub username: Option<String>,
+    /// Read the password, or without --username an access token, from stdin
+    #[arg(long)]
+    pub password_stdin: bool,
+    /// Password, or a token without a username, set once read
+    #[arg(skip)]
+    pub secret: Option<String>,
+    /// Import only this package, may be repeated
+    #[arg(long = "package", value_name = "NAME")]
+    pub packages: Vec<String>,
+    /// Owner of the imported packages (default: CLEF_ADMIN_USERNAME)
+    #[arg(long, value_name = "USER")]
+    pub owner: Option<String>,
+}
+
+fn server_url(url: &str) -> Result<String, String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!(
+            "invalid URL '{url}', expected an http:// or https:// URL"
+        ));
+    }
+
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+#[derive(Debug, Default)]
+pub struct RepositoryImportReport {
+    pub packages: usize,
+    pub imported_versions: usize,
+    pub skipped_versions: usize,
+    /// `name@version` of versions whose tarball couldn't be downloaded
+    pub missing_tarballs: Vec<String>,
+    /// Packages that failed to import, with the reason
+    pub failed_packages: Vec<(String, String)>,
+}
+
+pub struct RepositoryImportService;
+
+impl RepositoryImportService {
+    /// Imports the repository's packages one at a time
+    pub async fn import(
+        options: &RepositoryImportOptions,
+        state: &AppState,
+    ) -> Result<RepositoryImportReport, ApiError> {
+        let owner_name = options
+            .owner
+            .clone()
+            .or_else(|| state.config.admin_username.clone())
+            .ok_or_else(|| {
+                ApiError::BadRequest(
+                    "No package owner, pass --owner or set CLEF_ADMIN_USERNAME".to_string(),
+                )
+            })?;
+        let owner = AuthService::get_user_by_username(&state.database, &owner_name)?
+            .ok_or_else(|| ApiError::NotFound(format!("User '{owner_name}' not found")))?;
+
+        let packages = if options.packages.is_empty() {
+            Self::package_names(options, state).await?
+        } else {
+            options.packages.clone()
+        };
+        info!(
+            "Importing {} packages from {} repository {}",
+            packages.len(),
+            options.kind.as_str(),
+            options.repository
+        );
+
+        let mut report = RepositoryImportReport::default();
+        for name in packages {
+            let archive = match Self::fetch_package(&name, options, state, &mut report).await {
+                Ok(archive) => archive,
+                Err(e) => {
+                    warn!("Failed to fetch {name}: {e}");
+                    report.failed_packages.push((name, e.to_string()));
+                    continue;
+                }
+            };
+
+            match ArchiveService::import_package(archive, owner.id, state).await {
+                Ok(imported) => {
+                    report.packages += 1;
+                    report.imported_versions += imported.imported_versions.len();
+                    report.skipped_versions += imported.skipped_versions.len();
+                }
+                Err(e) => {
+                    warn!("Failed to import {name}: {e}");
+                    report.failed_packages.push((name, e.to_string()));
+                }
+            }
+        }
+
+        info!(
+            "Imported {} packages ({} versions, {} skipped) from {} repository {}",
+            report.packages,
+            report.imported_versions,
+            report.skipped_versions,
+            options.kind.as_str(),
+            options.repository
+        );
+        Ok(report)
+    }
+
+    /// Fetches a package document and its tarballs into an archive
+    async fn fetch_package(
+        name: &str,
+        options: &RepositoryImportOptions,
+        state: &AppState,
+        report: &mut RepositoryImportReport,
+    ) -> Result<PackageArchive, ApiError> {
+        let document = Self::get(&Self::package_url(options, name), options, state)
+            .await?
+            .json::<Value>()
+            .await?;
+
+        let mut tarballs = HashMap::new();
+        for (version, manifest) in document["versions"].as_object().into_iter().flatten() {
+            let Some(url) = manifest["dist"]["tarball"].as_str() else {
+                report.missing_tarballs.push(format!("{name}@{version}"));
+                continue;
+            };
+            let data = match Self::get(url, options, state).await {
+                Ok(response) => response.bytes().await.map_err(ApiError::from),
+                Err(e) => Err(e),
+            };
+            match data {
+                Ok(data) => {
+                    tarballs.insert(version.clone(), data.to_vec());
+                }
+                Err(e) => {
+                    warn!("Failed to download {name}@{version} from {url}: {e}");
+                    report.missing_tarballs.push(format!("{name}@{version}"));
+                }
+            }
+        }
+
+        ArchiveService::archive_from_document(&document, tarballs).map_err(ApiError::ParseError)
+    }
+
+    /// Names of the packages in the repository
+    async fn package_names(
+        options: &RepositoryImportOptions,
+        state: &AppState,
+    ) -> Result<Vec<String>, ApiError> {
+        let mut names = BTreeSet::new();
+        match options.kind {
+            RepositoryKind::Nexus => {
+                // Components are listed a page at a time
+                let mut continuation: Option<String> = None;
+                loop {
+                    let mut url = format!(
+                        "{}/service/rest/v1/components?repository={}",
+                        options.url, options.repository
+                    );
+                    if let Some(token) = &continuation {
+                        url.push_str(&format!("&continuationToken={token}"));
+                    }
+                    let page: Value = Self::get(&url, options, state).await?.json().await?;
+                    names.extend(
+                        page["items"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(Self::nexus_package_name),
+                    );
+                    continuation = page["continuationToken"].as_str().map(str::to_string);
+                    if continuation.is_none() {
+                        break;
+                    }
+                }
+            }
+            RepositoryKind::Artifactory => {
+                let url = format!(
+                    "{}/api/storage/{}?list&deep=1&listFolders=0",
+                    options.url, options.repository
+                );
+                let listing: Value = Self::get(&url, options, state).await?.json().await?;
+                names.extend(
+                    listing["files"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|file| file["uri"].as_str())
+                        .filter_map(Self::artifactory_package_name),
+                );
+            }
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    /// Package document URL of the repository's npm API
+    fn package_url(options: &RepositoryImportOptions, name: &str) -> String {
+        let name = name.replace('/', "%2f");
+        match options.kind {
+            RepositoryKind::Nexus => {
+                format!("{}/repository/{}/{name}", options.url, options.repository)
+            }
+            RepositoryKind::Artifactory => {
+                format!("{}/api/npm/{}/{name}", options.url, options.repository)
+            }
+        }
+    }
+
+    /// GET with the credentials, which are only sent to the repository's own server
+    async fn get(
+        url: &str,
+        options: &RepositoryImportOptions,
+        state: &AppState,
+    ) -> Result<reqwest::Response, ApiError> {
+        let mut request = state.client.get(url);
+        if url.starts_with(&format!("{}/", options.url)) {
+            request = match (&options.username, &options.secret) {
+                (Some(username), password) => request.basic_auth(username, password.as_ref()),
+                (None, Some(token)) => request.bearer_auth(token),
+                (None, None) => request,
+            };
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(ApiError::UpstreamError(format!(
+                "{} returned {}",
+                url,
+                response.status()
+            )));
+        }
+        Ok(response)
+    }
+
+    /// Nexus lists npm scopes as the component's group, without the `@`
+    fn nexus_package_name(item: &Value) -> Option<String> {
+        let name = item["name"].as_str()?;
+        match item["group"].as_str().filter(|group| !group.is_empty()) {
+            Some(group) => Some(format!("@{}/{name}", group.trim_start_matches('@'))),
+            None => Some(name.to_string()),
+        }
+    }
+
+    /// Artifactory stores tarballs as `/<name>/-/<file>.tgz`
+    fn artifactory_package_name(uri: &str) -> Option<String> {
+        let (name, file) = uri.split_once("/-/")?;
+        let name = name.trim_start_matches('/');
+        (file.ends_with(".tgz") && !name.is_empty()).then(|| name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(args: &[&str]) -> Result<RepositoryImportOptions, clap::Error> {
+        RepositoryImportOptions::try_parse_from(
+            std::iter::once("import").chain(args.iter().copied()),
+        )
+    }
+
+    #[test]
+    fn test_import_options_parsing() {
+        let options = RepositoryImportOptions {
+            kind: RepositoryKind::Artifactory,
+            ..parse(&[
+                "--url",
+                "https://artifactory.example.com/artifactory/",
+                "--repository",
+                "npm-local",
+                "--username",
+                "ci",
+                "--password-stdin",
+                "--package",
+                "@acme/widget",
+            ])
+            .unwrap()
+        };
+        assert_eq!(options.url, "https://artifactory.example.com/artifactory");
+        assert_eq!(options.repository, "npm-local");
+        assert_eq!(options.username.as_deref(), Some("ci"));
+        assert_eq!(options.packages, vec!["@acme/widget"]);
+        assert_eq!(
+            RepositoryImportService::package_url(&options, "@acme/widget"),
+            "https://artifactory.example.com/artifactory/api/npm/npm-local/@acme%2fwidget"
+        );
+
+        let nexus = |extra: &[&str]| {
+            let mut all = vec!["--url", "https://nexus", "--repository", "npm"];
+            all.extend(extra);
+            parse(&all)
+        };
+        assert!(nexus(&[]).is_ok());
+        assert!(nexus(&["--username", "ci"]).is_err());
+        assert!(nexus(&["--bogus"]).is_err());
+        assert!(parse(&["--url", "nexus", "--repository", "npm"]).is_err());
+    }
+
+    #[test]
+    fn test_package_names() {
+        assert_eq!(
+            RepositoryImportService::nexus_package_name(
+                &json!({ "name": "widget", "group": "acme", "version": "1.0.0" })
+            ),
+            Some("@acme/widget".to_string())
+        );
+        assert_eq!(
+            RepositoryImportService::nexus_package_name(&json!({ "name": "left-pad" })),
+            Some("left-pad".to_string())
+        );
+        assert_eq!(
+            RepositoryImportService::artifactory_package_name(
+                "/@acme/widget/-/@acme/widget-1.0.0.tgz"
+            ),
+            Some("@acme/widget".to_string())
+        );
+        assert_eq!(
+            RepositoryImportService::artifactory_package_name("/left-pad/-/left-pad-1.3.0.tgz"),
+            Some("left-pad".to_string())
+        );
+        assert_eq!(
+            RepositoryImportService::artifactory_package_name("/.npm/left-pad/package.json"),
+            None
+        );
+    }
+}
@@ -0,0 +1,555 @@
+use crate::error::ApiError;
+use crate::models::organization::{Organization, OrganizationRole};
+use crate::models::{
+    AuthenticatedUser, NewAuditLogEntry, NewRetentionPolicy, PackageVersionWithFiles,
+    RetentionCandidate, RetentionPolicy, RetentionPolicyListResponse, RetentionPolicyRequest,
+    RetentionReport,
+};
+use crate::services::UnpublishService;
+use crate::state::AppState;
+use chrono::NaiveDateTime;
+use log::{info, warn};
+use semver::Version;
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Instant;
+
+/// A version as seen by retention rules
+#[derive(Debug, Clone)]
+struct RetainedVersion {
+    version: String,
+    /// When the version was last published or downloaded
+    last_used: NaiveDateTime,
+}
+
+pub struct RetentionService;
+
+impl RetentionService {
+    pub fn list(
+        organization: &str,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<RetentionPolicyListResponse, ApiError> {
+        let org = Self::organization(organization, user, OrganizationRole::Member, state)?;
+
+        let policies = state
+            .database
+            .list_retention_policies(Some(org.id))
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(RetentionPolicyListResponse {
+            policies,
+            interval_hours: state.config.retention_interval_hours,
+        })
+    }
+
+    /// Creates or replaces a named policy of an organization. New policies are dry runs
+    /// unless the request says otherwise.
+    pub fn set_policy(
+        organization: &str,
+        name: &str,
+        request: RetentionPolicyRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<RetentionPolicy, ApiError> {
+        let org = Self::organization(organization, actor, OrganizationRole::Owner, state)?;
+        Self::validate(name, &request)?;
+
+        let policy = state
+            .database
+            .upsert_retention_policy(&NewRetentionPolicy {
+                organization_id: org.id,
+                name: name.to_string(),
+                keep_last: request.keep_last,
+                max_age_days: request.max_age_days,
+                prerelease_only: request.prerelease_only.unwrap_or(false),
+                dry_run: request.dry_run.unwrap_or(true),
+                created_by: Some(actor.user_id),
+                updated_at: chrono::Utc::now().naive_utc(),
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Self::audit(
+            state,
+            NewAuditLogEntry {
+                action: "retention.set_policy".to_string(),
+                actor_id: Some(actor.user_id),
+                organization_id: Some(org.id),
+                details: Some(
+                    json!({
+                        "policy": policy.name,
+                        "keep_last": policy.keep_last,
+                        "max_age_days": policy.max_age_days,
+                        "prerelease_only": policy.prerelease_only,
+                        "dry_run": policy.dry_run,
+                    })
+                    .to_string(),
+                ),
+                ..Default::default()
+            },
+        );
+
+        info!(
+            "User {} set retention policy {} of organization {}",
+            actor.username, policy.name, org.name
+        );
+        Ok(policy)
+    }
+
+    pub fn delete_policy(
+        organization: &str,
+        name: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let org = Self::organization(organization, actor, OrganizationRole::Owner, state)?;
+
+        let deleted = state
+            .database
+            .delete_retention_policy(org.id, name)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if deleted == 0 {
+            return Err(ApiError::NotFound(format!(
+                "Retention policy '{name}' not found"
+            )));
+        }
+
+        Self::audit(
+            state,
+            NewAuditLogEntry {
+                action: "retention.delete_policy".to_string(),
+                actor_id: Some(actor.user_id),
+                organization_id: Some(org.id),
+                details: Some(json!({ "policy": name }).to_string()),
+                ..Default::default()
+            },
+        );
+
+        info!(
+            "User {} deleted retention policy {name} of organization {}",
+            actor.username, org.name
+        );
+        Ok(())
+    }
+
+    /// Applies the policies of one organization now, as a dry run when asked to
+    pub async fn apply_organization(
+        organization: &str,
+        dry_run: bool,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<RetentionReport, ApiError> {
+        let org = Self::organization(organization, actor, OrganizationRole::Owner, state)?;
+        Self::apply(Some(org.id), dry_run, Some(actor), state).await
+    }
+
+    /// Applies retention policies to locally published packages of their organization,
+    /// every organization when none is given. Versions referenced by a dist-tag and the
+    /// highest version of a package are always kept.
+    pub async fn apply(
+        organization_id: Option<i32>,
+        dry_run: bool,
+        actor: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<RetentionReport, ApiError> {
+        let started = Instant::now();
+        let policies = state
+            .database
+            .list_retention_policies(organization_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let mut report = RetentionReport {
+            policies: policies.len(),
+            ..Default::default()
+        };
+
+        let mut by_organization: BTreeMap<i32, Vec<RetentionPolicy>> = BTreeMap::new();
+        for policy in policies {
+            by_organization
+                .entry(policy.organization_id)
+                .or_default()
+                .push(policy);
+        }
+
+        let now = chrono::Utc::now().naive_utc();
+        for (org_id, policies) in by_organization {
+            let packages = state
+                .database
+                .get_packages_by_organization(org_id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+            for package in packages.into_iter().filter(|pkg| pkg.author_id.is_some()) {
+                report.packages += 1;
+                let deleted = Self::apply_package(
+                    &package.name,
+                    org_id,
+                    &policies,
+                    dry_run,
+                    actor,
+                    now,
+                    state,
+                    &mut report,
+                )?;
+
+                if deleted > 0
+                    && let Err(e) = state.cache.invalidate_metadata(&package.name).await
+                {
+                    warn!(
+                        "Failed to invalidate metadata cache for package {}: {e}",
+                        package.name
+                    );
+                }
+            }
+        }
+
+        report.duration_ms = started.elapsed().as_millis();
+        info!(
+            "Applied {} retention policies to {} packages: {} versions matched, {} deleted in {} ms",
+            report.policies,
+            report.packages,
+            report.versions.len(),
+            report.deleted,
+            report.duration_ms
+        );
+
+        Ok(report)
+    }
+
+    /// Applies the policies of an organization to one package. Returns the number of
+    /// deleted versions.
+    #[allow(clippy::too_many_arguments)]
+    fn apply_package(
+        package: &str,
+        organization_id: i32,
+        policies: &[RetentionPolicy],
+        dry_run: bool,
+        actor: Option<&AuthenticatedUser>,
+        now: NaiveDateTime,
+        state: &AppState,
+        report: &mut RetentionReport,
+    ) -> Result<usize, ApiError> {
+        let Some(package_with_versions) = state
+            .database
+            .get_package_with_versions(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        else {
+            return Ok(0);
+        };
+
+        let mut versions = package_with_versions.versions;
+        let tags = state
+            .database
+            .get_package_tags_map(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        let protected = Self::protected_versions(&versions, tags.into_values());
+
+        let mut deleted = 0;
+        for policy in policies {
+            let retained: Vec<RetainedVersion> = versions.iter().map(Self::retained).collect();
+
+            for (version, reason) in Self::select(policy, &retained, &protected, now) {
+                let Some(index) = versions.iter().position(|v| v.version.version == version) else {
+                    continue;
+                };
+                let delete = !dry_run && !policy.dry_run;
+
+                if delete {
+                    let entry = versions.remove(index);
+                    report.bytes_freed += UnpublishService::delete_version(
+                        package,
+                        &entry,
+                        actor.map(|actor| actor.user_id),
+                        &format!("retention policy {}", policy.name),
+                        state,
+                    )?;
+                    deleted += 1;
+
+                    Self::audit(
+                        state,
+                        NewAuditLogEntry {
+                            action: "retention.delete_version".to_string(),
+                            actor_id: actor.map(|actor| actor.user_id),
+                            organization_id: Some(organization_id),
+                            package_name: Some(package.to_string()),
+                            version: Some(version.clone()),
+                            details: Some(
+                                json!({ "policy": policy.name, "reason": reason }).to_string(),
+                            ),
+                        },
+                    );
+                    info!(
+                        "Retention policy {} deleted {package}@{version}: {reason}",
+                        policy.name
+                    );
+                } else {
+                    info!(
+                        "Retention policy {} would delete {package}@{version}: {reason}",
+                        policy.name
+                    );
+                }
+
+                report.versions.push(RetentionCandidate {
+                    policy: policy.name.clone(),
+                    package: package.to_string(),
+                    version,
+                    reason,
+                    deleted: delete,
+                });
+            }
+        }
+
+        report.deleted += deleted;
+        Ok(deleted)
+    }
+
+    /// Versions a policy deletes, with the reason, newest first
+    fn select(
+        policy: &RetentionPolicy,
+        versions: &[RetainedVersion],
+        protected: &HashSet<String>,
+        now: NaiveDateTime,
+    ) -> Vec<(String, String)> {
+        let kind = if policy.prerelease_only {
+            "prerelease versions"
+        } else {
+            "versions"
+        };
+
+        // Versions that aren't valid semver are never touched
+        let mut candidates: Vec<(Version, &RetainedVersion)> = versions
+            .iter()
+            .filter_map(|v| Version::parse(&v.version).ok().map(|parsed| (parsed, v)))
+            .filter(|(parsed, _)| !policy.prerelease_only || !parsed.pre.is_empty())
+            .collect();
+        candidates.sort_by(|a, b| b.0.cmp(&a.0));
+
+        let keep_last = policy.keep_last.map(|keep| keep.max(0) as usize);
+        let max_age = policy
+            .max_age_days
+            .map(|days| chrono::Duration::days(days.into()));
+
+        candidates
+            .into_iter()
+            .enumerate()
+            .filter(|(rank, _)| keep_last.is_none_or(|keep| *rank >= keep))
+            .filter(|(_, (_, v))| max_age.is_none_or(|age| now - v.last_used > age))
+            .filter(|(_, (_, v))| !protected.contains(&v.version))
+            .map(|(_, (_, v))| {
+                let mut reasons = Vec::new();
+                if let Some(keep) = keep_last {
+                    reasons.push(format!("not among the newest {keep} {kind}"));
+                }
+                if max_age.is_some() {
+                    let days = (now - v.last_used).num_days();
+                    reasons.push(format!("untouched for {days} days"));
+                }
+                (v.version.clone(), reasons.join(", "))
+            })
+            .collect()
+    }
+
+    /// Dist-tag targets and the highest version, which installs resolve to
+    fn protected_versions(
+        versions: &[PackageVersionWithFiles],
+        tags: impl Iterator<Item = String>,
+    ) -> HashSet<String> {
+        let highest = versions
+            .iter()
+            .filter_map(|v| Version::parse(&v.version.version).ok())
+            .max()
+            .map(|version| version.to_string());
+
+        tags.chain(highest).collect()
+    }
+
+    fn retained(entry: &PackageVersionWithFiles) -> RetainedVersion {
+        let last_accessed = entry.files.iter().map(|file| file.last_accessed).max();
+
+        RetainedVersion {
+            version: entry.version.version.clone(),
+            last_used: last_accessed.map_or(entry.version.updated_at, |accessed| {
+                accessed.max(entry.version.updated_at)
+            }),
+        }
+    }
+
+    fn validate(name: &str, request: &RetentionPolicyRequest) -> Result<(), ApiError> {
+        let valid_name = !name.is_empty()
+            && name.len() <= 64
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_name {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid policy name '{name}', use letters, digits, '-', '_' and '.'"
+            )));
+        }
+
+        if request.keep_last.is_none() && request.max_age_days.is_none() {
+            return Err(ApiError::BadRequest(
+                "A retention policy needs keep_last, max_age_days or both".to_string(),
+            ));
+        }
+        if request.keep_last.is_some_and(|keep| keep < 0) {
+            return Err(ApiError::BadRequest(
+                "keep_last can't be negative".to_string(),
+            ));
+        }
+        if request.max_age_days.is_some_and(|days| days < 1) {
+            return Err(ApiError::BadRequest(
+                "max_age_days must be at least 1".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Registry admins may manage the policies of every organization
+    fn organization(
+        name: &str,
+        user: &AuthenticatedUser,
+        role: OrganizationRole,
+        state: &AppState,
+    ) -> Result<Organization, ApiError> {
+        let org = state
+            .database
+            .get_organization_by_name(name)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Organization '{name}' not found")))?;
+
+        let permitted = user.is_admin
+            || state
+                .database
+                .check_organization_permission(org.id, user.user_id, role)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if !permitted {
+            return Err(ApiError::Forbidden(format!(
+                "You don't have permission to manage retention policies of '{name}'"
+            )));
+        }
+
+        Ok(org)
+    }
+
+    /// Failing to write the audit log doesn't undo the change it records
+    fn audit(state: &AppState, entry: NewAuditLogEntry) {
+        if let Err(e) = state.database.create_audit_log_entry(&entry) {
+            warn!("Failed to write audit log entry {}: {e}", entry.action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(
+        keep_last: Option<i32>,
+        max_age_days: Option<i32>,
+        prerelease_only: bool,
+    ) -> RetentionPolicy {
+        let now = chrono::Utc::now().naive_utc();
+        RetentionPolicy {
+            id: 1,
+            organization_id: 1,
+            name: "cleanup".to_string(),
+            keep_last,
+            max_age_days,
+            prerelease_only,
+            dry_run: false,
+            created_by: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn versions(now: NaiveDateTime, entries: &[(&str, i64)]) -> Vec<RetainedVersion> {
+        entries
+            .iter()
+            .map(|(version, age_days)| RetainedVersion {
+                version: version.to_string(),
+                last_used: now - chrono::Duration::days(*age_days),
+            })
+            .collect()
+    }
+
+    fn selected(
+        policy: &RetentionPolicy,
+        versions: &[RetainedVersion],
+        protected: &[&str],
+        now: NaiveDateTime,
+    ) -> Vec<String> {
+        let protected = protected.iter().map(|v| v.to_string()).collect();
+        RetentionService::select(policy, versions, &protected, now)
+            .into_iter()
+            .map(|(version, _)| version)
+            .collect()
+    }
+
+    #[test]
+    fn test_select_keep_last_prereleases() {
+        let now = chrono::Utc::now().naive_utc();
+        let versions = versions(
+            now,
+            &[
+                ("1.0.0", 9),
+                ("2.0.0-beta.1", 8),
+                ("2.0.0-beta.2", 7),
+                ("2.0.0-beta.10", 6),
+                ("not-semver", 5),
+            ],
+        );
+
+        assert_eq!(
+            selected(&policy(Some(1), None, true), &versions, &[], now),
+            vec!["2.0.0-beta.2", "2.0.0-beta.1"]
+        );
+        assert_eq!(
+            selected(
+                &policy(Some(1), None, true),
+                &versions,
+                &["2.0.0-beta.1"],
+                now
+            ),
+            vec!["2.0.0-beta.2"]
+        );
+        assert_eq!(
+            selected(
+                &policy(Some(2), None, false),
+                &versions,
+                &["2.0.0-beta.10"],
+                now
+            ),
+            vec!["2.0.0-beta.1", "1.0.0"]
+        );
+    }
+
+    #[test]
+    fn test_select_max_age() {
+        let now = chrono::Utc::now().naive_utc();
+        let versions = versions(now, &[("1.0.0", 400), ("1.1.0", 200), ("1.2.0", 500)]);
+
+        assert_eq!(
+            selected(&policy(None, Some(365), false), &versions, &["1.2.0"], now),
+            vec!["1.0.0"]
+        );
+        // Both rules have to match: 1.1.0 is outside the newest one but recently used
+        assert_eq!(
+            selected(&policy(Some(1), Some(365), false), &versions, &[], now),
+            vec!["1.0.0"]
+        );
+        assert!(selected(&policy(Some(3), Some(365), false), &versions, &[], now).is_empty());
+        assert_eq!(
+            RetentionService::select(
+                &policy(Some(1), Some(300), false),
+                &versions,
+                &HashSet::new(),
+                now
+            )[0]
+            .1,
+            "not among the newest 1 versions, untouched for 400 days"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use crate::error::ApiError;
+use crate::models::organization::OrganizationRole;
+use crate::models::{
+    AuthenticatedUser, NewScopePolicy, ScopePolicy, ScopePolicyRequest, ScopePublishAccess,
+};
+use crate::state::AppState;
+use log::{debug, info};
+
+pub struct ScopePolicyService;
+
+impl ScopePolicyService {
+    /// Scope of a package name, e.g. `@internal` for `@internal/utils`
+    pub fn scope_of(package: &str) -> Option<&str> {
+        package
+            .split_once('/')
+            .map(|(scope, _)| scope)
+            .filter(|scope| scope.starts_with('@'))
+    }
+
+    /// Policy that applies to a package, if its scope has one
+    pub fn policy_for(package: &str, state: &AppState) -> Result<Option<ScopePolicy>, ApiError> {
+        let Some(scope) = Self::scope_of(package) else {
+            return Ok(None);
+        };
+
+        state
+            .database
+            .get_scope_policy(&scope.to_lowercase())
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))
+    }
+
+    /// Creates or updates a scope policy. Fields missing from the request keep their current
+    /// value, or the permissive default for a new policy.
+    pub fn set_policy(
+        scope: &str,
+        request: ScopePolicyRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<ScopePolicy, ApiError> {
+        let scope = Self::normalize_scope(scope)?;
+
+        let publish_access = request
+            .publish_access
+            .as_deref()
+            .map(|access| {
+                ScopePublishAccess::from_access_str(access).ok_or_else(|| {
+                    ApiError::BadRequest(format!(
+                        "Invalid publish access '{access}', expected members, admins or registry-admins"
+                    ))
+                })
+            })
+            .transpose()?;
+
+        let existing = state
+            .database
+            .get_scope_policy(&scope)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let policy = NewScopePolicy {
+            publish_access: publish_access
+                .or(existing.as_ref().map(|p| p.publish_access()))
+                .unwrap_or(ScopePublishAccess::Members)
+                .to_string(),
+            allow_upstream: request
+                .allow_upstream
+                .or(existing.as_ref().map(|p| p.allow_upstream))
+                .unwrap_or(true),
+            allow_anonymous: request
+                .allow_anonymous
+                .or(existing.as_ref().map(|p| p.allow_anonymous))
+                .unwrap_or(true),
+            updated_by: Some(actor.user_id),
+            updated_at: chrono::Utc::now().naive_utc(),
+            scope,
+        };
+
+        let policy = state
+            .database
+            .upsert_scope_policy(&policy)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!(
+            "User {} set policy for {}: publish={}, upstream={}, anonymous={}",
+            actor.username,
+            policy.scope,
+            policy.publish_access,
+            policy.allow_upstream,
+            policy.allow_anonymous
+        );
+
+        Ok(policy)
+    }
+
+    pub fn delete_policy(scope: &str, state: &AppState) -> Result<(), ApiError> {
+        let scope = Self::normalize_scope(scope)?;
+
+        let deleted = state
+            .database
+            .delete_scope_policy(&scope)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if deleted == 0 {
+            return Err(ApiError::NotFound(format!("No policy for scope '{scope}'")));
+        }
+
+        info!("Deleted policy for scope {scope}");
+        Ok(())
+    }
+
+    /// Checks the scope's publish rule. Organization membership is still checked on publish,
+    /// this only narrows who may publish.
+    pub fn check_publish(
+        package: &str,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let Some(policy) = Self::policy_for(package, state)? else {
+            return Ok(());
+        };
+
+        if user.is_admin {
+            return Ok(());
+        }
+
+        let allowed = match policy.publish_access() {
+            ScopePublishAccess::Members => true,
+            ScopePublishAccess::Admins => {
+                let org_name = policy.scope.trim_start_matches('@');
+                match state
+                    .database
+                    .get_organization_by_name(org_name)
+                    .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+                {
+                    Some(org) => state
+                        .database
+                        .check_organization_permission(
+                            org.id,
+                            user.user_id,
+                            OrganizationRole::Admin,
+                        )
+                        .map_err(|e| {
+                            ApiError::InternalServerError(format!("Database error: {e}"))
+                        })?,
+                    None => false,
+                }
+            }
+            ScopePublishAccess::RegistryAdmins => false,
+        };
+
+        if !allowed {
+            return Err(ApiError::Forbidden(format!(
+                "Publishing to {} is restricted to {}",
+                policy.scope,
+                policy.publish_access()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Rejects anonymous requests for packages in scopes that require a login
+    pub fn check_anonymous_read(
+        package: &str,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        if user.is_some() {
+            return Ok(());
+        }
+
+        match Self::policy_for(package, state)? {
+            Some(policy) if !policy.allow_anonymous => Err(ApiError::Unauthorized(format!(
+                "Packages in {} require authentication, run `npm login`",
+                policy.scope
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a package may be looked up on the upstream registry. Configured internal
+    /// scopes are never proxied, regardless of their policy.
+    pub fn upstream_allowed(package: &str, state: &AppState) -> Result<bool, ApiError> {
+        if state.config.is_internal_package(package) {
+            debug!("{package} is internal, not looking it up upstream");
+            return Ok(false);
+        }
+
+        let allowed = Self::policy_for(package, state)?.is_none_or(|policy| policy.allow_upstream);
+        if !allowed {
+            debug!("Upstream lookups are disabled for the scope of {package}");
+        }
+        Ok(allowed)
+    }
+
+    fn normalize_scope(scope: &str) -> Result<String, ApiError> {
+        let scope = scope.trim().to_lowercase();
+        let name = scope.strip_prefix('@').unwrap_or(&scope);
+
+        if name.is_empty()
+            || name.starts_with('.')
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(ApiError::BadRequest(format!("Invalid scope '{scope}'")));
+        }
+
+        Ok(format!("@{name}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_of() {
+        assert_eq!(
+            ScopePolicyService::scope_of("@internal/utils"),
+            Some("@internal")
+        );
+        assert_eq!(ScopePolicyService::scope_of("lodash"), None);
+        assert_eq!(ScopePolicyService::scope_of("lodash/extra"), None);
+    }
+
+    #[test]
+    fn test_normalize_scope() {
+        assert_eq!(
+            ScopePolicyService::normalize_scope("@Internal").unwrap(),
+            "@internal"
+        );
+        assert_eq!(
+            ScopePolicyService::normalize_scope("internal").unwrap(),
+            "@internal"
+        );
+        assert!(ScopePolicyService::normalize_scope("@").is_err());
+        assert!(ScopePolicyService::normalize_scope("@a/b").is_err());
+    }
+}
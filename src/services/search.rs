@@ -0,0 +1,356 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, Package, PackageVersion, SearchLinks, SearchObject, SearchPackage,
+    SearchResponse, SearchScore, SearchScoreDetail, SearchUser,
+};
+use crate::state::AppState;
+use chrono::{NaiveDateTime, SecondsFormat};
+use semver::Version;
+
+/// Default weights of the score details, the same as the public npm registry uses
+const DEFAULT_QUALITY_WEIGHT: f64 = 0.65;
+const DEFAULT_POPULARITY_WEIGHT: f64 = 0.98;
+const DEFAULT_MAINTENANCE_WEIGHT: f64 = 0.5;
+/// Monthly downloads at which popularity reaches 1
+const POPULARITY_SATURATION: f64 = 100_000.0;
+/// Largest page of results
+const MAX_SEARCH_SIZE: usize = 250;
+
+/// Weights of the score details in the final score
+#[derive(Debug, Clone, Copy)]
+pub struct SearchWeights {
+    pub quality: f64,
+    pub popularity: f64,
+    pub maintenance: f64,
+}
+
+impl SearchWeights {
+    /// Weights given as query parameters, the defaults for any left out
+    pub fn new(quality: Option<f64>, popularity: Option<f64>, maintenance: Option<f64>) -> Self {
+        let weight = |value: Option<f64>, default| {
+            value
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .unwrap_or(default)
+        };
+        Self {
+            quality: weight(quality, DEFAULT_QUALITY_WEIGHT),
+            popularity: weight(popularity, DEFAULT_POPULARITY_WEIGHT),
+            maintenance: weight(maintenance, DEFAULT_MAINTENANCE_WEIGHT),
+        }
+    }
+}
+
+impl Default for SearchWeights {
+    fn default() -> Self {
+        Self::new(None, None, None)
+    }
+}
+
+pub struct SearchService;
+
+impl SearchService {
+    /// Searches the packages known to the registry that the user can read, best matches
+    /// first
+    pub fn search(
+        text: &str,
+        size: Option<usize>,
+        from: Option<usize>,
+        weights: SearchWeights,
+        user: Option<&AuthenticatedUser>,
+        state: &AppState,
+    ) -> Result<SearchResponse, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+        let text = text.trim().to_lowercase();
+        let terms: Vec<&str> = text.split_whitespace().collect();
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        let mut packages = Vec::new();
+        for package in state.database.search_packages(&terms).map_err(db_error)? {
+            let readable = state
+                .database
+                .has_read_permission(&package.name, user.map(|u| u.user_id))
+                .map_err(db_error)?;
+            if readable {
+                packages.push(package);
+            }
+        }
+
+        let names: Vec<String> = packages.iter().map(|pkg| pkg.name.clone()).collect();
+        let since = now.date() - chrono::Duration::days(30);
+        let downloads = state
+            .database
+            .get_downloads_since(&names, since)
+            .map_err(db_error)?;
+
+        let mut objects = Vec::new();
+        for package in packages {
+            let versions = state
+                .database
+                .get_package_versions(package.id)
+                .map_err(db_error)?;
+            let tags = state
+                .database
+                .get_package_tags_map(&package.name)
+                .map_err(db_error)?;
+            let Some(latest) = Self::latest_version(&versions, tags.get("latest")) else {
+                continue;
+            };
+
+            let detail = SearchScoreDetail {
+                quality: Self::quality(&package, latest),
+                popularity: Self::popularity(downloads.get(&package.name).copied().unwrap_or(0)),
+                maintenance: Self::maintenance(&versions, now),
+            };
+            let score = Self::score(detail, weights);
+            let maintainers: Vec<SearchUser> = state
+                .database
+                .get_package_maintainers(&package.name)
+                .map_err(db_error)?
+                .into_iter()
+                .map(|(username, email)| SearchUser { username, email })
+                .collect();
+
+            objects.push(SearchObject {
+                search_score: Self::relevance(&package.name, &terms) + score.final_score,
+                score,
+                package: SearchPackage {
+                    scope: package
+                        .name
+                        .strip_prefix('@')
+                        .and_then(|name| name.split('/').next())
+                        .map(str::to_string),
+                    version: latest.version.clone(),
+                    description: package.description.clone(),
+                    keywords: package
+                        .keywords
+                        .as_deref()
+                        .and_then(|keywords| serde_json::from_str(keywords).ok())
+                        .unwrap_or_default(),
+                    date: latest
+                        .created_at
+                        .and_utc()
+                        .to_rfc3339_opts(SecondsFormat::Millis, true),
+                    links: SearchLinks {
+                        homepage: package.homepage.clone(),
+                        repository: package.repository_url.clone(),
+                    },
+                    publisher: maintainers.first().cloned(),
+                    maintainers,
+                    name: package.name,
+                },
+            });
+        }
+
+        objects.sort_by(|a, b| {
+            b.search_score
+                .total_cmp(&a.search_score)
+                .then_with(|| a.package.name.cmp(&b.package.name))
+        });
+
+        let total = objects.len();
+        let objects = objects
+            .into_iter()
+            .skip(from.unwrap_or(0))
+            .take(size.unwrap_or(20).clamp(1, MAX_SEARCH_SIZE))
+            .collect();
+
+        Ok(SearchResponse {
+            objects,
+            total,
+            time: chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        })
+    }
+
+    /// The version tagged latest, otherwise the highest stable version that isn't yanked
+    fn latest_version<'a>(
+        versions: &'a [PackageVersion],
+        latest_tag: Option<&String>,
+    ) -> Option<&'a PackageVersion> {
+        if let Some(tagged) = latest_tag.and_then(|tag| versions.iter().find(|v| &v.version == tag))
+        {
+            return Some(tagged);
+        }
+
+        let available = versions.iter().filter(|v| v.yanked_at.is_none());
+        available
+            .clone()
+            .filter_map(|v| Version::parse(&v.version).ok().map(|parsed| (parsed, v)))
+            .filter(|(parsed, _)| parsed.pre.is_empty())
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v)
+            .or_else(|| available.max_by_key(|v| v.created_at))
+    }
+
+    /// Share of the metadata a well kept package has: description, readme, license,
+    /// repository, homepage, keywords and a stable (1.0.0 or later) latest version
+    fn quality(package: &Package, latest: &PackageVersion) -> f64 {
+        let present = |value: &Option<String>| {
+            value
+                .as_deref()
+                .is_some_and(|value| !value.trim().is_empty() && value != "[]")
+        };
+        let stable = Version::parse(&latest.version)
+            .is_ok_and(|version| version.major >= 1 && version.pre.is_empty());
+
+        let checks = [
+            present(&package.description),
+            present(&latest.readme),
+            present(&package.license),
+            present(&package.repository_url),
+            present(&package.homepage),
+            present(&package.keywords),
+            stable,
+        ];
+        checks.iter().filter(|check| **check).count() as f64 / checks.len() as f64
+    }
+
+    /// Downloads in the last 30 days on a log scale
+    fn popularity(downloads: i64) -> f64 {
+        ((downloads.max(0) as f64).ln_1p() / POPULARITY_SATURATION.ln_1p()).min(1.0)
+    }
+
+    /// Mostly how recently a version was published, fading out over two years, and partly
+    /// how many versions were published in the last year
+    fn maintenance(versions: &[PackageVersion], now: NaiveDateTime) -> f64 {
+        let Some(last_release) = versions.iter().map(|v| v.created_at).max() else {
+            return 0.0;
+        };
+
+        let age_days = (now - last_release).num_days().max(0) as f64;
+        let recency = if age_days <= 30.0 {
+            1.0
+        } else {
+            (1.0 - (age_days - 30.0) / 700.0).max(0.0)
+        };
+
+        let releases_last_year = versions
+            .iter()
+            .filter(|v| (now - v.created_at).num_days() <= 365)
+            .count();
+        let frequency = (releases_last_year as f64 / 12.0).min(1.0);
+
+        0.8 * recency + 0.2 * frequency
+    }
+
+    fn score(detail: SearchScoreDetail, weights: SearchWeights) -> SearchScore {
+        let total = weights.quality + weights.popularity + weights.maintenance;
+        let final_score = if total > 0.0 {
+            (detail.quality * weights.quality
+                + detail.popularity * weights.popularity
+                + detail.maintenance * weights.maintenance)
+                / total
+        } else {
+            0.0
+        };
+
+        SearchScore {
+            final_score,
+            detail,
+        }
+    }
+
+    /// How well the name matches the search text: exact name, name prefix, name contains
+    /// it, or only the description or keywords match
+    fn relevance(name: &str, terms: &[&str]) -> f64 {
+        let text = terms.join(" ");
+        let bare_name = name.rsplit('/').next().unwrap_or(name);
+        if text.is_empty() {
+            0.0
+        } else if name == text || bare_name == text {
+            3.0
+        } else if name.starts_with(&text) || bare_name.starts_with(&text) {
+            2.0
+        } else if terms.iter().all(|term| name.contains(term)) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(number: &str, days_ago: i64, now: NaiveDateTime) -> PackageVersion {
+        let created_at = now - chrono::Duration::days(days_ago);
+        PackageVersion {
+            id: 0,
+            package_id: 0,
+            version: number.to_string(),
+            description: None,
+            main_file: None,
+            scripts: None,
+            dependencies: None,
+            dev_dependencies: None,
+            peer_dependencies: None,
+            engines: None,
+            shasum: None,
+            readme: None,
+            created_at,
+            updated_at: created_at,
+            integrity: None,
+            yanked_at: None,
+            yank_reason: None,
+            registry_key_id: None,
+            registry_signature: None,
+        }
+    }
+
+    #[test]
+    fn test_latest_version() {
+        let now = chrono::Utc::now().naive_utc();
+        let mut versions = vec![
+            version("1.0.0", 30, now),
+            version("1.2.0", 20, now),
+            version("2.0.0-beta.1", 10, now),
+            version("1.3.0", 5, now),
+        ];
+        versions[3].yanked_at = Some(now);
+
+        let latest = |tag: Option<&str>| {
+            let tag = tag.map(str::to_string);
+            SearchService::latest_version(&versions, tag.as_ref()).map(|v| v.version.as_str())
+        };
+        assert_eq!(latest(None), Some("1.2.0"));
+        assert_eq!(latest(Some("1.0.0")), Some("1.0.0"));
+        assert_eq!(latest(Some("9.9.9")), Some("1.2.0"));
+    }
+
+    #[test]
+    fn test_scores() {
+        let now = chrono::Utc::now().naive_utc();
+
+        assert_eq!(SearchService::popularity(0), 0.0);
+        assert_eq!(SearchService::popularity(1_000_000), 1.0);
+        assert!(SearchService::popularity(100) < SearchService::popularity(1000));
+
+        assert_eq!(SearchService::maintenance(&[], now), 0.0);
+        let fresh: Vec<_> = (0..12).map(|i| version("1.0.0", i * 10, now)).collect();
+        assert_eq!(SearchService::maintenance(&fresh, now), 1.0);
+        let stale = [version("1.0.0", 1000, now)];
+        assert_eq!(SearchService::maintenance(&stale, now), 0.0);
+
+        let detail = SearchScoreDetail {
+            quality: 1.0,
+            popularity: 0.0,
+            maintenance: 1.0,
+        };
+        let only_quality = SearchWeights::new(Some(1.0), Some(0.0), Some(0.0));
+        assert_eq!(SearchService::score(detail, only_quality).final_score, 1.0);
+        let only_popularity = SearchWeights::new(Some(0.0), Some(1.0), Some(0.0));
+        assert_eq!(
+            SearchService::score(detail, only_popularity).final_score,
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_relevance() {
+        assert_eq!(SearchService::relevance("lodash", &["lodash"]), 3.0);
+        assert_eq!(SearchService::relevance("@acme/lodash", &["lodash"]), 3.0);
+        assert_eq!(SearchService::relevance("lodash.merge", &["lodash"]), 2.0);
+        assert_eq!(SearchService::relevance("my-lodash", &["lodash"]), 1.0);
+        assert_eq!(SearchService::relevance("underscore", &["lodash"]), 0.0);
+    }
+}
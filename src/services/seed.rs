@@ -0,0 +1,374 @@
+use crate::error::ApiError;
+use crate::models::{
+    ArchivedFile, ArchivedVersion, PACKAGE_ARCHIVE_FORMAT, PACKAGE_ARCHIVE_VERSION, PackageArchive,
+};
+use crate::services::{ArchiveService, AuthService, RegistryService, SigningService};
+use crate::state::AppState;
+use base64::prelude::*;
+use clap::{ArgAction, Parser};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Options of the `clef seed` command
+#[derive(Parser, Debug, Clone, PartialEq)]
+pub struct SeedOptions {
+    /// Number of packages to create
+    #[arg(long, value_name = "N", default_value_t = 100, value_parser = positive)]
+    pub packages: usize,
+    /// Versions per package
+    #[arg(long, value_name = "M", default_value_t = 5, value_parser = positive)]
+    pub versions: usize,
+    /// Package name prefix, may be scoped like @bench/pkg
+    #[arg(long, value_name = "NAME", default_value = "clef-seed", value_parser = package_prefix)]
+    pub prefix: String,
+    /// Existing user that owns the packages (default: CLEF_ADMIN_USERNAME)
+    #[arg(long, value_name = "USER")]
+    pub owner: Option<String>,
+    /// Where to write the loadgen fixture file (default: <cache dir>/seed-fixtures.json)
+    #[arg(long, value_name = "PATH")]
+    pub fixtures: Option<PathBuf>,
+    /// Don't pre-generate cached metadata after seeding
+    #[arg(long = "no-warm", action = ArgAction::SetFalse)]
+    pub warm_cache: bool,
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        Self {
+            packages: 100,
+            versions: 5,
+            prefix: "clef-seed".to_string(),
+            owner: None,
+            fixtures: None,
+            warm_cache: true,
+        }
+    }
+}
+
+fn positive(value: &str) -> Result<usize, String> {
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| format!("expects a positive number, got '{value}'"))
+}
+
+fn package_prefix(prefix: &str) -> Result<String, String> {
+    let valid = !prefix.is_empty()
+        && prefix == prefix.to_lowercase()
+        && !prefix.contains("..")
+        && prefix.matches('/').count() == usize::from(prefix.starts_with('@'));
+    if !valid {
+        return Err(format!(
+            "invalid package prefix '{prefix}', use a lowercase name such as 'bench' or '@bench/pkg'"
+        ));
+    }
+
+    Ok(prefix.to_string())
+}
+
+/// Entry of the fixture file consumed by load generators
+#[derive(Serialize, Debug)]
+pub struct SeedFixturePackage {
+    pub name: String,
+    pub latest: String,
+    pub versions: Vec<String>,
+    pub metadata_url: String,
+    pub tarball_urls: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SeedFixtures {
+    pub registry: String,
+    pub generated_at: chrono::NaiveDateTime,
+    pub packages: Vec<SeedFixturePackage>,
+}
+
+#[derive(Debug)]
+pub struct SeedReport {
+    pub packages: usize,
+    pub imported_versions: usize,
+    pub skipped_versions: usize,
+    pub fixtures_path: PathBuf,
+    pub seed_time: Duration,
+    /// Time spent generating and caching metadata for every seeded package
+    pub warm_time: Option<Duration>,
+}
+
+pub struct SeedService;
+
+impl SeedService {
+    /// Publishes synthetic packages through the regular import path and writes a fixture
+    /// file listing their metadata and tarball URLs. Output is deterministic for the same
+    /// options, so runs against different builds can be compared. Existing versions are kept.
+    pub async fn seed(options: &SeedOptions, state: &AppState) -> Result<SeedReport, ApiError> {
+        let owner_name = options
+            .owner
+            .clone()
+            .or_else(|| state.config.admin_username.clone())
+            .ok_or_else(|| {
+                ApiError::BadRequest(
+                    "No package owner, pass --owner or set CLEF_ADMIN_USERNAME".to_string(),
+                )
+            })?;
+        let owner = AuthService::get_user_by_username(&state.database, &owner_name)?
+            .ok_or_else(|| ApiError::NotFound(format!("User '{owner_name}' not found")))?;
+
+        let public_url = state.config.get_public_url();
+        let registry = format!("{public_url}/registry");
+        let mut report = SeedReport {
+            packages: options.packages,
+            imported_versions: 0,
+            skipped_versions: 0,
+            fixtures_path: options.fixtures.clone().unwrap_or_else(|| {
+                PathBuf::from(&state.config.cache_dir).join("seed-fixtures.json")
+            }),
+            seed_time: Duration::ZERO,
+            warm_time: None,
+        };
+        let mut fixtures = SeedFixtures {
+            registry: registry.clone(),
+            generated_at: chrono::Utc::now().naive_utc(),
+            packages: Vec::with_capacity(options.packages),
+        };
+
+        let started = Instant::now();
+        for index in 0..options.packages {
+            let archive = Self::synthetic_archive(options, index)?;
+            let name = archive.name.clone();
+            let versions: Vec<String> =
+                archive.versions.iter().map(|v| v.version.clone()).collect();
+            let base_name = name.split('/').next_back().unwrap_or(&name);
+
+            let imported = ArchiveService::import_package(archive, owner.id, state).await?;
+            report.imported_versions += imported.imported_versions.len();
+            report.skipped_versions += imported.skipped_versions.len();
+
+            fixtures.packages.push(SeedFixturePackage {
+                metadata_url: format!("{registry}/{name}"),
+                tarball_urls: versions
+                    .iter()
+                    .map(|v| format!("{registry}/{name}/-/{base_name}-{v}.tgz"))
+                    .collect(),
+                latest: versions.last().cloned().unwrap_or_default(),
+                versions,
+                name,
+            });
+
+            if (index + 1) % 100 == 0 {
+                info!("Seeded {}/{} packages", index + 1, options.packages);
+            }
+        }
+        report.seed_time = started.elapsed();
+
+        if options.warm_cache {
+            // Cached documents keep the tarball host they were generated for, so use the
+            // same one the fixtures point at
+            let (scheme, host) = public_url
+                .split_once("://")
+                .unwrap_or((state.config.get_scheme(), &public_url));
+
+            let started = Instant::now();
+            for package in &fixtures.packages {
+                if let Err(e) =
+                    RegistryService::get_package_metadata(&package.name, state, Some(host), scheme)
+                        .await
+                {
+                    warn!("Failed to warm metadata cache for {}: {e:?}", package.name);
+                }
+            }
+            report.warm_time = Some(started.elapsed());
+        }
+
+        if let Some(parent) = report.fixtures_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                ApiError::InternalServerError(format!("Failed to create fixtures directory: {e}"))
+            })?;
+        }
+        let fixtures_json = serde_json::to_string_pretty(&fixtures).map_err(|e| {
+            ApiError::InternalServerError(format!("Failed to serialize fixtures: {e}"))
+        })?;
+        std::fs::write(&report.fixtures_path, fixtures_json)
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to write fixtures: {e}")))?;
+
+        info!(
+            "Seeded {} packages ({} versions imported, {} skipped) in {:?}",
+            report.packages, report.imported_versions, report.skipped_versions, report.seed_time
+        );
+
+        Ok(report)
+    }
+
+    fn package_name(prefix: &str, index: usize) -> String {
+        format!("{prefix}-{index:05}")
+    }
+
+    /// Builds the archive of the `index`th synthetic package. Every package after the first
+    /// depends on an earlier one, so the seeded set forms a dependency tree.
+    fn synthetic_archive(options: &SeedOptions, index: usize) -> Result<PackageArchive, ApiError> {
+        let name = Self::package_name(&options.prefix, index);
+        let description = format!("Synthetic package #{index} generated by clef seed");
+        let keywords = ["clef", "seed", "benchmark"];
+        // Fixed timestamps keep the generated metadata identical between runs
+        let epoch = chrono::DateTime::from_timestamp(1_700_000_000, 0)
+            .expect("valid timestamp")
+            .naive_utc();
+
+        let mut versions = Vec::with_capacity(options.versions);
+        for minor in 0..options.versions {
+            let version = format!("1.{minor}.0");
+
+            let mut dependencies = serde_json::Map::new();
+            if index > 0 {
+                dependencies.insert(
+                    Self::package_name(&options.prefix, (index - 1) / 2),
+                    json!("^1.0.0"),
+                );
+            }
+
+            let mut manifest = json!({
+                "name": name,
+                "version": version,
+                "description": description,
+                "main": "index.js",
+                "license": "MIT",
+                "keywords": keywords,
+                "dependencies": dependencies,
+            });
+
+            let tarball = Self::synthetic_tarball(&manifest, &description).map_err(|e| {
+                ApiError::InternalServerError(format!("Failed to build tarball for {name}: {e}"))
+            })?;
+            manifest["dist"] = json!({ "integrity": SigningService::integrity_for(&tarball) });
+
+            let base_name = name.split('/').next_back().unwrap_or(&name);
+            versions.push(ArchivedVersion {
+                created_at: epoch + chrono::Duration::days(minor as i64),
+                manifest,
+                files: vec![ArchivedFile {
+                    filename: format!("{base_name}-{version}.tgz"),
+                    content_type: Some("application/octet-stream".to_string()),
+                    size_bytes: tarball.len() as i64,
+                    data: BASE64_STANDARD.encode(&tarball),
+                }],
+                version,
+            });
+        }
+
+        let latest = versions
+            .last()
+            .map(|v| v.version.clone())
+            .unwrap_or_default();
+
+        Ok(PackageArchive {
+            format: PACKAGE_ARCHIVE_FORMAT.to_string(),
+            format_version: PACKAGE_ARCHIVE_VERSION,
+            exported_at: epoch,
+            description: Some(description),
+            homepage: None,
+            repository_url: None,
+            license: Some("MIT".to_string()),
+            keywords: Some(keywords.join(",")),
+            dist_tags: HashMap::from([("latest".to_string(), latest)]),
+            versions,
+            name,
+        })
+    }
+
+    /// Minimal installable npm tarball with zeroed mtimes, so identical input gives identical bytes
+    fn synthetic_tarball(manifest: &Value, description: &str) -> std::io::Result<Vec<u8>> {
+        let files = [
+            ("package/package.json", serde_json::to_vec_pretty(manifest)?),
+            (
+                "package/README.md",
+                format!(
+                    "# {}\n\n{description}\n",
+                    manifest["name"].as_str().unwrap_or("")
+                )
+                .into_bytes(),
+            ),
+            (
+                "package/index.js",
+                b"module.exports = function () {\n  return true;\n};\n".to_vec(),
+            ),
+        ];
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(0);
+            builder.append_data(&mut header, path, contents.as_slice())?;
+        }
+
+        builder.into_inner()?.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<SeedOptions, clap::Error> {
+        SeedOptions::try_parse_from(std::iter::once("seed").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_seed_options_parsing() {
+        assert_eq!(parse(&[]).unwrap(), SeedOptions::default());
+
+        let options = parse(&[
+            "--packages",
+            "20",
+            "--versions",
+            "3",
+            "--prefix",
+            "@bench/pkg",
+            "--no-warm",
+        ])
+        .unwrap();
+        assert_eq!(options.packages, 20);
+        assert_eq!(options.versions, 3);
+        assert_eq!(options.prefix, "@bench/pkg");
+        assert!(!options.warm_cache);
+
+        assert!(parse(&["--packages", "0"]).is_err());
+        assert!(parse(&["--versions"]).is_err());
+        assert!(parse(&["--prefix", "Bench"]).is_err());
+        assert!(parse(&["--prefix", "a/b"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_synthetic_archive_is_deterministic() {
+        let options = SeedOptions {
+            versions: 2,
+            ..SeedOptions::default()
+        };
+
+        let first = SeedService::synthetic_archive(&options, 3).unwrap();
+        let second = SeedService::synthetic_archive(&options, 3).unwrap();
+
+        assert_eq!(first.name, "clef-seed-00003");
+        assert_eq!(first.dist_tags["latest"], "1.1.0");
+        assert_eq!(
+            first.versions[0].manifest["dependencies"]["clef-seed-00001"],
+            "^1.0.0"
+        );
+        assert_eq!(
+            first.versions[1].files[0].filename,
+            "clef-seed-00003-1.1.0.tgz"
+        );
+        assert_eq!(
+            first.versions[1].files[0].data,
+            second.versions[1].files[0].data
+        );
+    }
+}
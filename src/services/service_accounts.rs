@@ -0,0 +1,362 @@
+use crate::error::ApiError;
+use crate::models::organization::{Organization, OrganizationRole};
+use crate::models::{
+    AuthenticatedUser, ClientCertificate, ClientCertificateRequest, CreateServiceAccountRequest,
+    NewAuditLogEntry, NewUser, ServiceAccount, ServiceAccountResponse, ServiceAccountTokenResponse,
+    ServiceAccountsResponse, TokenSession, User,
+};
+use crate::services::{AuthService, ClientCertificateService};
+use crate::state::AppState;
+use log::{info, warn};
+use rocket::http::Method;
+use serde_json::json;
+
+const MAX_NAME_LENGTH: usize = 64;
+
+/// Non-interactive accounts owned by an organization, the identity CI pipelines publish
+/// with instead of personal accounts. They are members of their organization, can't log
+/// in, and their tokens only read and publish packages. Organization admins manage them.
+pub struct ServiceAccountService;
+
+impl ServiceAccountService {
+    pub fn create(
+        organization: &str,
+        request: CreateServiceAccountRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<ServiceAccountResponse, ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+        Self::validate_name(&request.name)?;
+
+        let description = request
+            .description
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty());
+        let user = NewUser::new_service_account(request.name.clone())
+            .map_err(|e| ApiError::InternalServerError(format!("Password hashing error: {e}")))?;
+
+        let (user, account) = state
+            .database
+            .create_service_account(&user, org.id, description)
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::UniqueViolation,
+                    _,
+                ) => ApiError::Conflict(format!("User '{}' already exists", request.name)),
+                _ => ApiError::InternalServerError(format!("Database error: {e}")),
+            })?;
+
+        Self::audit(state, "service_account.create", actor, &org, &user.username);
+        info!(
+            "User {} created service account {} in organization {}",
+            actor.username, user.username, org.name
+        );
+
+        Ok(Self::response(&org, user, account))
+    }
+
+    pub fn list(
+        organization: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<ServiceAccountsResponse, ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+
+        let service_accounts = state
+            .database
+            .list_service_accounts(org.id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .into_iter()
+            .map(|(user, account)| Self::response(&org, user, account))
+            .collect();
+
+        Ok(ServiceAccountsResponse { service_accounts })
+    }
+
+    /// Deletes the account, its tokens and its membership. Versions it published stay.
+    pub fn delete(
+        organization: &str,
+        name: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+        let (user, _) = Self::account(&org, name, state)?;
+
+        AuthService::delete_user(&state.database, &user.username)?;
+
+        Self::audit(state, "service_account.delete", actor, &org, name);
+        info!(
+            "User {} deleted service account {name} of organization {}",
+            actor.username, org.name
+        );
+        Ok(())
+    }
+
+    /// Issues a publish token. Tokens don't expire, they're revoked with [`Self::revoke_tokens`].
+    pub fn issue_token(
+        organization: &str,
+        name: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<ServiceAccountTokenResponse, ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+        let (user, _) = Self::account(&org, name, state)?;
+
+        let token = AuthService::create_token(
+            &state.database,
+            &state.secrets.token_keys(&state.config),
+            &user.username,
+            true,
+        )?;
+
+        Self::audit(state, "service_account.issue_token", actor, &org, name);
+        Ok(ServiceAccountTokenResponse {
+            name: user.username,
+            token,
+        })
+    }
+
+    /// Active tokens of the account with when and from where they were last used
+    pub fn tokens(
+        organization: &str,
+        name: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<Vec<TokenSession>, ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+        let (user, _) = Self::account(&org, name, state)?;
+
+        AuthService::list_sessions(&state.database, user.id, None)
+    }
+
+    pub fn revoke_tokens(
+        organization: &str,
+        name: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+        let (user, _) = Self::account(&org, name, state)?;
+
+        AuthService::revoke_all_tokens(&state.database, user.id)?;
+
+        Self::audit(state, "service_account.revoke_tokens", actor, &org, name);
+        Ok(())
+    }
+
+    pub fn certificates(
+        organization: &str,
+        name: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<Vec<ClientCertificate>, ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+        let (user, _) = Self::account(&org, name, state)?;
+
+        ClientCertificateService::list(&state.database, user.id)
+    }
+
+    /// Lets the account authenticate with a client certificate, e.g. one of a CI runner.
+    /// Only registry admins register certificates, a certificate identity belongs to a single
+    /// account and organization admins shouldn't be able to claim one of another's.
+    pub fn add_certificate(
+        organization: &str,
+        name: &str,
+        request: ClientCertificateRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<ClientCertificate, ApiError> {
+        if !actor.is_admin {
+            return Err(ApiError::Forbidden(
+                "Only registry admins can register client certificates".to_string(),
+            ));
+        }
+        let org = Self::organization(organization, actor, state)?;
+        let (user, _) = Self::account(&org, name, state)?;
+
+        let certificate = ClientCertificateService::add(&state.database, user.id, request)?;
+
+        Self::audit(state, "service_account.add_certificate", actor, &org, name);
+        Ok(certificate)
+    }
+
+    pub fn remove_certificate(
+        organization: &str,
+        name: &str,
+        id: i32,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let org = Self::organization(organization, actor, state)?;
+        let (user, _) = Self::account(&org, name, state)?;
+
+        ClientCertificateService::remove(&state.database, user.id, id)?;
+
+        Self::audit(
+            state,
+            "service_account.remove_certificate",
+            actor,
+            &org,
+            name,
+        );
+        Ok(())
+    }
+
+    /// Whether a service account may make a request, by the decoded, non-empty segments of
+    /// its path. Anything reading the registry is allowed, of the writes only publishing and
+    /// audits.
+    pub fn allows<'a>(method: Method, segments: impl IntoIterator<Item = &'a str>) -> bool {
+        let segments: Vec<&str> = segments.into_iter().collect();
+        match method {
+            Method::Get | Method::Head => true,
+            Method::Put => matches!(segments.as_slice(), ["registry", first, ..] if *first != "-"),
+            Method::Post => matches!(
+                segments.as_slice(),
+                ["registry", "-", "npm", "v1", "security", ..]
+            ),
+            _ => false,
+        }
+    }
+
+    fn validate_name(name: &str) -> Result<(), ApiError> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAME_LENGTH
+            && name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(ApiError::BadRequest(format!(
+                "Invalid service account name '{name}', use up to {MAX_NAME_LENGTH} lowercase \
+                 letters, digits, hyphens and underscores, starting with a letter"
+            )))
+        }
+    }
+
+    /// The organization, if the actor may manage its service accounts
+    fn organization(
+        name: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<Organization, ApiError> {
+        let org = state
+            .database
+            .get_organization_by_name(name)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Organization '{name}' not found")))?;
+
+        let permitted = actor.is_admin
+            || state
+                .database
+                .check_organization_permission(org.id, actor.user_id, OrganizationRole::Admin)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if !permitted {
+            return Err(ApiError::Forbidden(format!(
+                "You don't have permission to manage service accounts of '{name}'"
+            )));
+        }
+
+        Ok(org)
+    }
+
+    fn account(
+        org: &Organization,
+        name: &str,
+        state: &AppState,
+    ) -> Result<(User, ServiceAccount), ApiError> {
+        state
+            .database
+            .get_service_account(org.id, name)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| {
+                ApiError::NotFound(format!(
+                    "Service account '{name}' not found in organization '{}'",
+                    org.name
+                ))
+            })
+    }
+
+    fn response(org: &Organization, user: User, account: ServiceAccount) -> ServiceAccountResponse {
+        ServiceAccountResponse {
+            name: user.username,
+            organization: org.name.clone(),
+            description: account.description,
+            is_active: user.is_active,
+            created_at: account.created_at,
+        }
+    }
+
+    /// Failing to write the audit log doesn't undo the change it records
+    fn audit(
+        state: &AppState,
+        action: &str,
+        actor: &AuthenticatedUser,
+        org: &Organization,
+        name: &str,
+    ) {
+        let entry = NewAuditLogEntry {
+            action: action.to_string(),
+            actor_id: Some(actor.user_id),
+            organization_id: Some(org.id),
+            details: Some(json!({ "service_account": name }).to_string()),
+            ..Default::default()
+        };
+        if let Err(e) = state.database.create_audit_log_entry(&entry) {
+            warn!("Failed to write audit log entry {action}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::uri::Origin;
+
+    fn allows(method: Method, uri: &str) -> bool {
+        ServiceAccountService::allows(method, Origin::parse(uri).unwrap().path().segments())
+    }
+
+    #[test]
+    fn test_allows() {
+        assert!(allows(Method::Get, "/registry/react"));
+        assert!(allows(Method::Get, "/api/v1/packages/react"));
+        assert!(allows(Method::Put, "/registry/@acme/ui"));
+        assert!(allows(Method::Put, "/registry/left-pad"));
+        assert!(allows(
+            Method::Post,
+            "/registry/-/npm/v1/security/audits/quick"
+        ));
+
+        assert!(!allows(Method::Put, "/registry/-/user/org.couchdb.user:ci"));
+        assert!(!allows(Method::Post, "/registry/-/npm/v1/hooks/hook"));
+        assert!(!allows(Method::Post, "/api/v1/packages/react/transfer"));
+        assert!(!allows(Method::Delete, "/registry/-/user/token/abc"));
+
+        // Rocket routes these to the same handlers as the plain paths
+        assert!(!allows(
+            Method::Put,
+            "/registry//-/user/org.couchdb.user:ci"
+        ));
+        assert!(!allows(
+            Method::Put,
+            "/registry/%2D/package/react/dist-tags/latest"
+        ));
+        assert!(!allows(Method::Post, "/registry/-//npm/v1/hooks/hook"));
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(ServiceAccountService::validate_name("ci-release").is_ok());
+        assert!(ServiceAccountService::validate_name("ci_2").is_ok());
+        assert!(ServiceAccountService::validate_name("").is_err());
+        assert!(ServiceAccountService::validate_name("2ci").is_err());
+        assert!(ServiceAccountService::validate_name("CI").is_err());
+        assert!(ServiceAccountService::validate_name("ci bot").is_err());
+        assert!(ServiceAccountService::validate_name(&"a".repeat(65)).is_err());
+    }
+}
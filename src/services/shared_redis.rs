@@ -0,0 +1,141 @@
+use crate::config::{AppConfig, redact_url_credentials};
+use log::{info, warn};
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Commands answer well within this, a slower Redis is treated as unavailable
+const REDIS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long Redis is skipped after a failed command, so an unreachable server doesn't slow
+/// down every request
+const REDIS_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Connection {
+    connection: Option<ConnectionManager>,
+    retry_at: Option<Instant>,
+}
+
+/// Connection to the Redis shared by every instance of the registry. It's a multiplexed
+/// async connection: requests pipeline their commands on it concurrently, without holding
+/// a lock or a worker thread while waiting for Redis. Commands that fail return `None` so
+/// callers fall back to their in-process state, and the connection is opened again once
+/// `REDIS_RETRY_INTERVAL` passed.
+pub struct SharedRedis {
+    client: redis::Client,
+    connection: Mutex<Connection>,
+    /// Held while connecting, so requests arriving meanwhile don't open connections too
+    connecting: tokio::sync::Mutex<()>,
+}
+
+impl std::fmt::Debug for SharedRedis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedRedis")
+            .field("addr", &self.client.get_connection_info().addr.to_string())
+            .finish()
+    }
+}
+
+impl SharedRedis {
+    pub fn open(url: &str) -> Option<Self> {
+        match redis::Client::open(url) {
+            Ok(client) => Some(Self {
+                client,
+                connection: Mutex::new(Connection::default()),
+                connecting: tokio::sync::Mutex::new(()),
+            }),
+            Err(e) => {
+                warn!("Invalid Redis URL {}: {e}", redact_url_credentials(url));
+                None
+            }
+        }
+    }
+
+    /// The Redis of `CLEF_REDIS_URL`, when one is configured
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        config.redis_url.as_deref().and_then(Self::open)
+    }
+
+    /// Runs commands on the shared connection, `None` when Redis can't be reached or a
+    /// command failed. The connection handle is cheap to clone, each call gets its own.
+    pub async fn run<T, F>(&self, commands: impl FnOnce(ConnectionManager) -> F) -> Option<T>
+    where
+        F: Future<Output = redis::RedisResult<T>>,
+    {
+        let connection = self.connection().await?;
+
+        match tokio::time::timeout(REDIS_TIMEOUT, commands(connection)).await {
+            Ok(Ok(value)) => Some(value),
+            Ok(Err(e)) => {
+                self.failed(&format!(
+                    "Redis command failed, using in-process state: {e}"
+                ));
+                None
+            }
+            Err(_) => {
+                self.failed("Redis command timed out, using in-process state");
+                None
+            }
+        }
+    }
+
+    async fn connection(&self) -> Option<ConnectionManager> {
+        if let Some(connection) = self.current()? {
+            return Some(connection);
+        }
+
+        let _connecting = self.connecting.lock().await;
+        if let Some(connection) = self.current()? {
+            return Some(connection);
+        }
+
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(REDIS_TIMEOUT)
+            .set_response_timeout(REDIS_TIMEOUT)
+            .set_number_of_retries(0);
+        let connected = tokio::time::timeout(
+            REDIS_TIMEOUT,
+            ConnectionManager::new_with_config(self.client.clone(), config),
+        )
+        .await;
+        match connected {
+            Ok(Ok(connection)) => {
+                let mut state = self.connection.lock().unwrap();
+                if state.retry_at.take().is_some() {
+                    info!("Connected to Redis again");
+                }
+                state.connection = Some(connection.clone());
+                Some(connection)
+            }
+            Ok(Err(e)) => {
+                self.failed(&format!(
+                    "Failed to connect to Redis, using in-process state: {e}"
+                ));
+                None
+            }
+            Err(_) => {
+                self.failed("Timed out connecting to Redis, using in-process state");
+                None
+            }
+        }
+    }
+
+    /// The open connection, `Some(None)` when one has to be opened and `None` while Redis
+    /// is skipped after a failure
+    fn current(&self) -> Option<Option<ConnectionManager>> {
+        let state = self.connection.lock().unwrap();
+        if state.retry_at.is_some_and(|at| Instant::now() < at) {
+            return None;
+        }
+        Some(state.connection.clone())
+    }
+
+    fn failed(&self, message: &str) {
+        warn!("{message}");
+        let mut state = self.connection.lock().unwrap();
+        state.connection = None;
+        state.retry_at = Some(Instant::now() + REDIS_RETRY_INTERVAL);
+    }
+}
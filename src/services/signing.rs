@@ -0,0 +1,682 @@
+use crate::database::DatabaseService;
+use crate::error::ApiError;
+use crate::fairings::RequestId;
+use crate::models::{
+    NewPackageSignature, NewRegistryKey, NewSigningKey, NpmRegistryKey, NpmRegistryKeysResponse,
+    PackageVersion, REGISTRY_KEY_TYPE, RegistryKey, SIGNING_KEY_ALGORITHM, SignatureCheck,
+    SignatureVerificationResponse, SigningKey,
+};
+use crate::state::AppState;
+use base64::prelude::*;
+use chrono::SecondsFormat;
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
+use log::{debug, info, warn};
+use p256::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey};
+use serde_json::{Value, json};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo; the raw 32 byte key follows it
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+/// How long cached upstream registry keys are used before they are fetched again
+const UPSTREAM_KEYS_MAX_AGE_HOURS: i64 = 24;
+
+pub struct SigningService;
+
+impl SigningService {
+    /// Generates a new Ed25519 keypair for an organization. The private half never leaves clef.
+    pub fn generate_key(
+        organization_id: i32,
+        created_by: Option<i32>,
+        state: &AppState,
+    ) -> Result<SigningKey, ApiError> {
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut rand_core::OsRng);
+
+        let mut public_key_der = ED25519_SPKI_PREFIX.to_vec();
+        public_key_der.extend_from_slice(signing_key.verifying_key().as_bytes());
+
+        let new_key = NewSigningKey {
+            organization_id,
+            key_id: format!(
+                "SHA256:{}",
+                BASE64_STANDARD_NO_PAD.encode(Sha256::digest(&public_key_der))
+            ),
+            algorithm: SIGNING_KEY_ALGORITHM.to_string(),
+            public_key: BASE64_STANDARD.encode(&public_key_der),
+            private_key: BASE64_STANDARD.encode(signing_key.to_bytes()),
+            is_active: true,
+            created_by,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+
+        state
+            .database
+            .create_signing_key(&new_key)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))
+    }
+
+    /// Creates the registry key on first start. npm clients only verify ECDSA P-256
+    /// signatures, so unlike organization keys it isn't an Ed25519 key.
+    pub fn ensure_registry_key(db: &DatabaseService) -> Result<RegistryKey, ApiError> {
+        if let Some(key) = db
+            .get_local_registry_key()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        {
+            return Ok(key);
+        }
+
+        let signing_key = p256::ecdsa::SigningKey::random(&mut rand_core::OsRng);
+        let public_key_der = signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to encode key: {e}")))?;
+        let private_key_der = signing_key
+            .to_pkcs8_der()
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to encode key: {e}")))?;
+
+        let key = db
+            .create_registry_key(&NewRegistryKey {
+                key_id: format!(
+                    "SHA256:{}",
+                    BASE64_STANDARD_NO_PAD.encode(Sha256::digest(public_key_der.as_bytes()))
+                ),
+                source: "local".to_string(),
+                keytype: REGISTRY_KEY_TYPE.to_string(),
+                scheme: REGISTRY_KEY_TYPE.to_string(),
+                public_key: BASE64_STANDARD.encode(public_key_der.as_bytes()),
+                private_key: Some(BASE64_STANDARD.encode(private_key_der.as_bytes())),
+                expires_at: None,
+                created_at: chrono::Utc::now().naive_utc(),
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        info!("Created registry signing key {}", key.key_id);
+        Ok(key)
+    }
+
+    /// Keys for `/-/npm/v1/keys`: the registry's own key and the upstream registry's keys,
+    /// which verify the signatures of proxied packages. Upstream keys are refreshed daily
+    /// and served from the database when upstream is unreachable.
+    pub async fn npm_registry_keys(state: &AppState) -> Result<NpmRegistryKeysResponse, ApiError> {
+        let mut keys = state
+            .database
+            .list_registry_keys()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let refreshed_at = keys
+            .iter()
+            .filter(|key| key.source == "upstream")
+            .map(|key| key.created_at)
+            .max();
+        let stale = refreshed_at.is_none_or(|at| {
+            chrono::Utc::now().naive_utc() - at
+                > chrono::Duration::hours(UPSTREAM_KEYS_MAX_AGE_HOURS)
+        });
+
+        if stale {
+            match Self::fetch_upstream_keys(state).await {
+                Ok(upstream) => {
+                    state
+                        .database
+                        .replace_upstream_registry_keys(&upstream)
+                        .map_err(|e| {
+                            ApiError::InternalServerError(format!("Database error: {e}"))
+                        })?;
+                    keys = state.database.list_registry_keys().map_err(|e| {
+                        ApiError::InternalServerError(format!("Database error: {e}"))
+                    })?;
+                }
+                Err(e) => warn!("Failed to refresh upstream registry keys: {e:?}"),
+            }
+        }
+
+        Ok(NpmRegistryKeysResponse {
+            keys: keys
+                .into_iter()
+                .map(|key| NpmRegistryKey {
+                    expires: key
+                        .expires_at
+                        .map(|at| at.and_utc().to_rfc3339_opts(SecondsFormat::Millis, true)),
+                    keyid: key.key_id,
+                    keytype: key.keytype,
+                    scheme: key.scheme,
+                    key: key.public_key,
+                })
+                .collect(),
+        })
+    }
+
+    async fn fetch_upstream_keys(state: &AppState) -> Result<Vec<NewRegistryKey>, ApiError> {
+        let url = format!("{}/-/npm/v1/keys", state.upstream_registry());
+        let mut request = RequestId::forward(state.client.get(&url));
+        if state.config.upstream_deadline_ms > 0 {
+            request = request.timeout(std::time::Duration::from_millis(
+                state.config.upstream_deadline_ms,
+            ));
+        }
+
+        let response: NpmRegistryKeysResponse = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ApiError::UpstreamError(format!("Failed to fetch {url}: {e}")))?
+            .json()
+            .await
+            .map_err(|e| ApiError::ParseError(format!("Invalid keys response: {e}")))?;
+
+        let now = chrono::Utc::now().naive_utc();
+        Ok(response
+            .keys
+            .into_iter()
+            .map(|key| NewRegistryKey {
+                expires_at: key
+                    .expires
+                    .as_deref()
+                    .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+                    .map(|at| at.naive_utc()),
+                key_id: key.keyid,
+                source: "upstream".to_string(),
+                keytype: key.keytype,
+                scheme: key.scheme,
+                public_key: key.key,
+                private_key: None,
+                created_at: now,
+            })
+            .collect())
+    }
+
+    /// Subresource integrity string (sha512) of a tarball
+    pub fn integrity_for(data: &[u8]) -> String {
+        format!("sha512-{}", BASE64_STANDARD.encode(Sha512::digest(data)))
+    }
+
+    /// Legacy sha1 checksum (hex) of a tarball, `dist.shasum`
+    pub fn shasum_for(data: &[u8]) -> String {
+        format!("{:x}", Sha1::digest(data))
+    }
+
+    /// Message that gets signed, same layout as npm registry signatures
+    fn signing_message(package: &str, version: &str, integrity: &str) -> String {
+        format!("{package}@{version}:{integrity}")
+    }
+
+    /// Signs a freshly published tarball with every active key of the organization.
+    /// Returns the number of signatures created.
+    pub fn sign_package_version(
+        organization_id: i32,
+        package: &str,
+        version: &str,
+        package_version_id: i32,
+        tarball: &[u8],
+        state: &AppState,
+    ) -> Result<usize, ApiError> {
+        let keys = state
+            .database
+            .get_active_signing_keys(organization_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if keys.is_empty() {
+            return Ok(0);
+        }
+
+        let integrity = Self::integrity_for(tarball);
+        let message = Self::signing_message(package, version, &integrity);
+
+        for key in &keys {
+            let seed: [u8; 32] = BASE64_STANDARD
+                .decode(&key.private_key)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| {
+                    ApiError::InternalServerError(format!(
+                        "Signing key {} is corrupted",
+                        key.key_id
+                    ))
+                })?;
+
+            let signature = ed25519_dalek::SigningKey::from_bytes(&seed).sign(message.as_bytes());
+
+            state
+                .database
+                .create_package_signature(&NewPackageSignature {
+                    package_version_id,
+                    signing_key_id: key.id,
+                    integrity: integrity.clone(),
+                    signature: BASE64_STANDARD.encode(signature.to_bytes()),
+                    created_at: chrono::Utc::now().naive_utc(),
+                })
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+            debug!("Signed {package}@{version} with key {}", key.key_id);
+        }
+
+        info!(
+            "Signed {package}@{version} with {} organization key(s)",
+            keys.len()
+        );
+
+        Ok(keys.len())
+    }
+
+    /// Adds the registry signature of a version as `dist.signatures`, where npm clients
+    /// verify it, and organization signatures as `dist.organizationSignatures`
+    pub fn attach_signatures(
+        version_data: &mut Value,
+        package: &str,
+        pkg_version: &PackageVersion,
+        db: &DatabaseService,
+    ) {
+        let signatures = match db.get_package_signatures(pkg_version.id) {
+            Ok(signatures) => signatures,
+            Err(e) => {
+                warn!(
+                    "Failed to load signatures for package version {}: {e}",
+                    pkg_version.id
+                );
+                Vec::new()
+            }
+        };
+
+        let active: Vec<_> = signatures.iter().filter(|(_, key)| key.is_active).collect();
+
+        if version_data.get("dist").is_none_or(|d| !d.is_object()) {
+            version_data["dist"] = json!({});
+        }
+
+        let registry_signature = Self::registry_signature(package, pkg_version, db);
+
+        if let Some(dist) = version_data.get_mut("dist").and_then(|d| d.as_object_mut()) {
+            if let Some(signature) = registry_signature {
+                dist.insert("signatures".to_string(), json!([signature]));
+            }
+
+            if active.is_empty() {
+                return;
+            }
+
+            if !dist.contains_key("integrity") {
+                dist.insert("integrity".to_string(), json!(active[0].0.integrity));
+            }
+
+            let entries: Vec<Value> = active
+                .iter()
+                .map(|(signature, key)| json!({ "keyid": key.key_id, "sig": signature.signature }))
+                .collect();
+            dist.insert("organizationSignatures".to_string(), json!(entries));
+        }
+    }
+
+    /// Registry signature over `name@version:integrity`, the message npm verifies. A version
+    /// is signed the first time it's served and the signature stored with it, it's signed
+    /// again only when its integrity changes.
+    fn registry_signature(
+        package: &str,
+        pkg_version: &PackageVersion,
+        db: &DatabaseService,
+    ) -> Option<Value> {
+        let integrity = pkg_version.integrity.as_deref()?;
+        if let (Some(key_id), Some(signature)) = (
+            &pkg_version.registry_key_id,
+            &pkg_version.registry_signature,
+        ) {
+            return Some(json!({ "keyid": key_id, "sig": signature }));
+        }
+
+        let key = match db.get_local_registry_key() {
+            Ok(key) => key?,
+            Err(e) => {
+                warn!("Failed to load the registry key: {e}");
+                return None;
+            }
+        };
+
+        let signing_key = key
+            .private_key
+            .as_deref()
+            .and_then(|der| BASE64_STANDARD.decode(der).ok())
+            .and_then(|der| p256::ecdsa::SigningKey::from_pkcs8_der(&der).ok());
+        let Some(signing_key) = signing_key else {
+            warn!("Registry key {} is corrupted", key.key_id);
+            return None;
+        };
+
+        let message = Self::signing_message(package, &pkg_version.version, integrity);
+        let signature: p256::ecdsa::Signature = signing_key.sign(message.as_bytes());
+        let signature = BASE64_STANDARD.encode(signature.to_der().as_bytes());
+
+        if let Err(e) = db.set_version_registry_signature(pkg_version.id, &key.key_id, &signature) {
+            warn!(
+                "Failed to store the registry signature of {package}@{}: {e}",
+                pkg_version.version
+            );
+        }
+        debug!(
+            "Signed {package}@{} with registry key {}",
+            pkg_version.version, key.key_id
+        );
+
+        Some(json!({ "keyid": key.key_id, "sig": signature }))
+    }
+
+    /// Checks a locally published version's tarball against its recorded signatures.
+    /// When `expected_integrity` is given it must also match the stored tarball.
+    pub fn verify_package_version(
+        package: &str,
+        version: &str,
+        expected_integrity: Option<&str>,
+        state: &AppState,
+    ) -> Result<SignatureVerificationResponse, ApiError> {
+        let package_with_versions = state
+            .database
+            .get_package_with_versions(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Package '{package}' not found")))?;
+
+        let version_with_files = package_with_versions
+            .versions
+            .iter()
+            .find(|v| v.version.version == version)
+            .ok_or_else(|| {
+                ApiError::NotFound(format!("Version '{version}' of '{package}' not found"))
+            })?;
+
+        let mut response = SignatureVerificationResponse {
+            package: package.to_string(),
+            version: version.to_string(),
+            integrity: None,
+            verified: false,
+            signatures: Vec::new(),
+            error: None,
+        };
+
+        let tarball = version_with_files
+            .files
+            .iter()
+            .find(|f| f.filename.ends_with(".tgz"))
+            .and_then(|f| std::fs::read(&f.file_path).ok());
+
+        let Some(tarball) = tarball else {
+            response.error = Some("Tarball is not available on this registry".to_string());
+            return Ok(response);
+        };
+
+        let integrity = Self::integrity_for(&tarball);
+        response.integrity = Some(integrity.clone());
+
+        let signatures = state
+            .database
+            .get_package_signatures(version_with_files.version.id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let message = Self::signing_message(package, version, &integrity);
+        for (signature, key) in signatures {
+            let valid = signature.integrity == integrity
+                && Self::verify_signature(&key, &message, &signature.signature);
+
+            response.signatures.push(SignatureCheck {
+                keyid: key.key_id,
+                sig: signature.signature,
+                key_active: key.is_active,
+                valid,
+            });
+        }
+
+        if let Some(expected) = expected_integrity
+            && expected != integrity
+        {
+            response.error = Some("Integrity does not match the published tarball".to_string());
+            return Ok(response);
+        }
+
+        response.verified = response.signatures.iter().any(|s| s.valid && s.key_active);
+        if !response.verified {
+            response.error = Some(if response.signatures.is_empty() {
+                "Package version is not signed".to_string()
+            } else {
+                "No valid signature from an active key".to_string()
+            });
+        }
+
+        Ok(response)
+    }
+
+    fn verify_signature(key: &SigningKey, message: &str, signature: &str) -> bool {
+        let verifying_key = BASE64_STANDARD
+            .decode(&key.public_key)
+            .ok()
+            .and_then(|der| {
+                der.strip_prefix(&ED25519_SPKI_PREFIX[..])
+                    .map(|raw| raw.to_vec())
+            })
+            .and_then(|raw| <[u8; 32]>::try_from(raw).ok())
+            .and_then(|raw| VerifyingKey::from_bytes(&raw).ok());
+
+        let signature = BASE64_STANDARD
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok());
+
+        match (verifying_key, signature) {
+            (Some(verifying_key), Some(signature)) => verifying_key
+                .verify_strict(message.as_bytes(), &signature)
+                .is_ok(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::models::RegisterRequest;
+    use crate::services::AuthService;
+    use p256::ecdsa::signature::Verifier;
+    use p256::pkcs8::DecodePublicKey;
+
+    fn database() -> (tempfile::TempDir, DatabaseService) {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+        (dir, database)
+    }
+
+    fn state(dir: &tempfile::TempDir) -> AppState {
+        crate::create_state(AppConfig {
+            cache_dir: dir.path().to_str().unwrap().to_string(),
+            database_url: dir.path().join("clef.db").to_str().unwrap().to_string(),
+            ..AppConfig::default()
+        })
+    }
+
+    /// An organization with a signing key and a published `@acme/lib@1.0.0` whose tarball
+    /// is on disk, returns the key, the version id and the tarball path
+    fn signed_fixture(state: &AppState, dir: &tempfile::TempDir) -> (SigningKey, i32, String) {
+        let user = AuthService::register_user(
+            &state.database,
+            RegisterRequest {
+                name: "owner".to_string(),
+                email: "owner@example.com".to_string(),
+                password: "password123".to_string(),
+                invite_token: None,
+            },
+        )
+        .unwrap();
+        let org = state
+            .database
+            .create_organization("acme", None, None, user.id)
+            .unwrap();
+        let key = SigningService::generate_key(org.id, Some(user.id), state).unwrap();
+
+        let package = state
+            .database
+            .create_or_get_package("@acme/lib", None, Some(user.id))
+            .unwrap();
+        let version = state
+            .database
+            .create_or_get_package_version(package.id, "1.0.0")
+            .unwrap();
+        let path = dir.path().join("lib-1.0.0.tgz");
+        std::fs::write(&path, b"tarball").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        state
+            .database
+            .create_or_update_package_file(version.id, "lib-1.0.0.tgz", 7, "", &path, None, None)
+            .unwrap();
+
+        let signed = SigningService::sign_package_version(
+            org.id,
+            "@acme/lib",
+            "1.0.0",
+            version.id,
+            b"tarball",
+            state,
+        )
+        .unwrap();
+        assert_eq!(signed, 1);
+
+        (key, version.id, path)
+    }
+
+    #[test]
+    fn test_organization_signature_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let (key, _, _) = signed_fixture(&state, &dir);
+        let integrity = SigningService::integrity_for(b"tarball");
+
+        let response =
+            SigningService::verify_package_version("@acme/lib", "1.0.0", Some(&integrity), &state)
+                .unwrap();
+        assert!(response.verified, "{:?}", response.error);
+        assert_eq!(response.integrity.as_deref(), Some(integrity.as_str()));
+        assert_eq!(response.signatures.len(), 1);
+        assert_eq!(response.signatures[0].keyid, key.key_id);
+        assert!(response.signatures[0].valid);
+    }
+
+    #[test]
+    fn test_tampered_tarball_fails_verification() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let (_, _, path) = signed_fixture(&state, &dir);
+        std::fs::write(&path, b"tampered").unwrap();
+
+        let response =
+            SigningService::verify_package_version("@acme/lib", "1.0.0", None, &state).unwrap();
+        assert!(!response.verified);
+        assert!(!response.signatures[0].valid);
+        assert_eq!(
+            response.error.as_deref(),
+            Some("No valid signature from an active key")
+        );
+
+        // The stored tarball no longer matches what the client expects either
+        let expected = SigningService::integrity_for(b"tarball");
+        let response =
+            SigningService::verify_package_version("@acme/lib", "1.0.0", Some(&expected), &state)
+                .unwrap();
+        assert!(!response.verified);
+        assert_eq!(
+            response.error.as_deref(),
+            Some("Integrity does not match the published tarball")
+        );
+    }
+
+    #[test]
+    fn test_signature_does_not_verify_with_another_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let (key, version_id, _) = signed_fixture(&state, &dir);
+
+        let other_org = state
+            .database
+            .create_organization("other", None, None, key.created_by.unwrap())
+            .unwrap();
+        let other_key = SigningService::generate_key(other_org.id, None, &state).unwrap();
+        assert_ne!(other_key.key_id, key.key_id);
+
+        let (signature, _) = state
+            .database
+            .get_package_signatures(version_id)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let message = SigningService::signing_message(
+            "@acme/lib",
+            "1.0.0",
+            &SigningService::integrity_for(b"tarball"),
+        );
+        assert!(SigningService::verify_signature(
+            &key,
+            &message,
+            &signature.signature
+        ));
+        assert!(!SigningService::verify_signature(
+            &other_key,
+            &message,
+            &signature.signature
+        ));
+    }
+
+    #[test]
+    fn test_registry_signature_is_stored() {
+        let (_dir, db) = database();
+        let key = SigningService::ensure_registry_key(&db).unwrap();
+        let package = db.create_or_get_package("signed-pkg", None, None).unwrap();
+        let version = db
+            .create_or_get_package_version(package.id, "1.0.0")
+            .unwrap();
+
+        // Without a registry computed integrity there's nothing to sign
+        let mut data = json!({ "dist": {} });
+        SigningService::attach_signatures(&mut data, "signed-pkg", &version, &db);
+        assert!(data["dist"].get("signatures").is_none());
+
+        let integrity = SigningService::integrity_for(b"tarball");
+        db.set_version_checksums(version.id, "shasum", &integrity)
+            .unwrap();
+        let version = db
+            .create_or_get_package_version(package.id, "1.0.0")
+            .unwrap();
+        SigningService::attach_signatures(&mut data, "signed-pkg", &version, &db);
+
+        let signature = &data["dist"]["signatures"][0];
+        assert_eq!(signature["keyid"], key.key_id);
+        let public_key = BASE64_STANDARD.decode(&key.public_key).unwrap();
+        let sig = BASE64_STANDARD
+            .decode(signature["sig"].as_str().unwrap())
+            .unwrap();
+        p256::ecdsa::VerifyingKey::from_public_key_der(&public_key)
+            .unwrap()
+            .verify(
+                format!("signed-pkg@1.0.0:{integrity}").as_bytes(),
+                &p256::ecdsa::Signature::from_der(&sig).unwrap(),
+            )
+            .unwrap();
+
+        let stored = db
+            .create_or_get_package_version(package.id, "1.0.0")
+            .unwrap();
+        assert_eq!(stored.registry_key_id.as_deref(), Some(key.key_id.as_str()));
+        assert_eq!(
+            stored.registry_signature.as_deref(),
+            signature["sig"].as_str()
+        );
+
+        // A stored signature is served as is, ECDSA signatures differ every time they're made
+        let mut again = json!({});
+        SigningService::attach_signatures(&mut again, "signed-pkg", &stored, &db);
+        assert_eq!(again["dist"]["signatures"][0], *signature);
+
+        db.set_version_checksums(version.id, "shasum", "sha512-other")
+            .unwrap();
+        let changed = db
+            .create_or_get_package_version(package.id, "1.0.0")
+            .unwrap();
+        assert!(changed.registry_key_id.is_none());
+        assert!(changed.registry_signature.is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::ApiError;
+use crate::models::{PackageVersion, StorageProblem, StorageVerifyReport};
+use crate::services::{DatabaseService, SigningService};
+use log::info;
+use std::path::Path;
+
+pub struct StorageService;
+
+impl StorageService {
+    /// Checks that every stored file exists with the recorded size, and that tarballs match
+    /// the shasum and integrity recorded for their version
+    pub fn verify(database: &DatabaseService) -> Result<StorageVerifyReport, ApiError> {
+        let files = database
+            .list_package_files()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        let mut report = StorageVerifyReport::default();
+        for (package, version, file) in files {
+            report.files += 1;
+            let path = Path::new(&file.file_path);
+
+            match Self::check_file(path, file.size_bytes, &version) {
+                Ok(size) => report.bytes += size,
+                Err(error) => report.problems.push(StorageProblem {
+                    name: package.name,
+                    version: version.version,
+                    filename: file.filename,
+                    path: file.file_path,
+                    error,
+                }),
+            }
+        }
+
+        info!(
+            "Verified {} stored files: {} problems",
+            report.files,
+            report.problems.len()
+        );
+        Ok(report)
+    }
+
+    /// Size of a stored file that matches its records
+    fn check_file(path: &Path, size_bytes: i64, version: &PackageVersion) -> Result<u64, String> {
+        let data = std::fs::read(path).map_err(|e| format!("unreadable: {e}"))?;
+
+        if data.len() as i64 != size_bytes {
+            return Err(format!(
+                "size is {} bytes, recorded {size_bytes}",
+                data.len()
+            ));
+        }
+
+        if path.extension().is_some_and(|ext| ext == "tgz") {
+            if let Some(shasum) = &version.shasum
+                && SigningService::shasum_for(&data) != *shasum
+            {
+                return Err(format!("shasum does not match {shasum}"));
+            }
+            if let Some(integrity) = &version.integrity
+                && SigningService::integrity_for(&data) != *integrity
+            {
+                return Err(format!("integrity does not match {integrity}"));
+            }
+        }
+
+        Ok(data.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(data: &[u8]) -> PackageVersion {
+        let now = chrono::Utc::now().naive_utc();
+        PackageVersion {
+            id: 1,
+            package_id: 1,
+            version: "1.0.0".to_string(),
+            description: None,
+            main_file: None,
+            scripts: None,
+            dependencies: None,
+            dev_dependencies: None,
+            peer_dependencies: None,
+            engines: None,
+            shasum: Some(SigningService::shasum_for(data)),
+            readme: None,
+            created_at: now,
+            updated_at: now,
+            integrity: Some(SigningService::integrity_for(data)),
+            yanked_at: None,
+            yank_reason: None,
+            registry_key_id: None,
+            registry_signature: None,
+        }
+    }
+
+    #[test]
+    fn test_check_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pkg-1.0.0.tgz");
+        std::fs::write(&path, b"tarball").unwrap();
+
+        assert_eq!(
+            StorageService::check_file(&path, 7, &version(b"tarball")),
+            Ok(7)
+        );
+        assert!(
+            StorageService::check_file(&path, 8, &version(b"tarball"))
+                .unwrap_err()
+                .starts_with("size is 7 bytes")
+        );
+        assert!(
+            StorageService::check_file(&path, 7, &version(b"another"))
+                .unwrap_err()
+                .starts_with("shasum does not match")
+        );
+        assert!(
+            StorageService::check_file(&dir.path().join("missing.tgz"), 7, &version(b""))
+                .unwrap_err()
+                .starts_with("unreadable")
+        );
+    }
+}
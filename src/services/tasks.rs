@@ -0,0 +1,387 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, DatabaseOptimizeRequest, NewTask, Task, TaskKind, TaskResponse, TaskStatus,
+};
+use crate::services::{ArchiveService, PrefetchService};
+use crate::state::AppState;
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{info, warn};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often the worker looks for queued tasks when the queue is empty
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Finished tasks, and the archives of exports, are kept this long for polling
+pub const TASK_RETENTION_DAYS: i64 = 7;
+
+pub struct TaskService;
+
+impl TaskService {
+    /// Cache reprocessing, run by the worker
+    pub fn enqueue_reprocess_cache(
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<TaskResponse, ApiError> {
+        if !state.config.cache_enabled {
+            return Err(ApiError::ParseError("Cache is disabled".to_string()));
+        }
+        Self::enqueue(TaskKind::ReprocessCache, None, user, state)
+    }
+
+    /// Warms the cache with the packages of a lockfile, parsed before it's queued
+    pub fn enqueue_prefetch(
+        lockfile: &str,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<TaskResponse, ApiError> {
+        if !state.config.cache_enabled {
+            return Err(ApiError::ParseError("Cache is disabled".to_string()));
+        }
+        let packages = PrefetchService::parse_lockfile(lockfile)?;
+        Self::enqueue(
+            TaskKind::Prefetch,
+            Some(json!({ "packages": packages })),
+            user,
+            state,
+        )
+    }
+
+    /// Exports a package into an archive downloaded from `/api/v1/tasks/<id>/download`
+    pub fn enqueue_export(
+        package: &str,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<TaskResponse, ApiError> {
+        state
+            .database
+            .get_package_by_name(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Package '{package}' not found")))?;
+        Self::enqueue(
+            TaskKind::Export,
+            Some(json!({ "package": package })),
+            user,
+            state,
+        )
+    }
+
+    /// `VACUUM` and `REINDEX` hold the write lock until they finish, so they only run while
+    /// maintenance mode is on. `ANALYZE` alone runs anytime.
+    pub fn enqueue_optimize_database(
+        request: DatabaseOptimizeRequest,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<TaskResponse, ApiError> {
+        Self::check_optimize_allowed(&request, state)?;
+        let input =
+            serde_json::to_value(request).map_err(|e| ApiError::ParseError(e.to_string()))?;
+        Self::enqueue(TaskKind::OptimizeDatabase, Some(input), user, state)
+    }
+
+    fn check_optimize_allowed(
+        request: &DatabaseOptimizeRequest,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        if (request.vacuum || request.reindex) && !state.maintenance.is_enabled() {
+            return Err(ApiError::Conflict(
+                "VACUUM and REINDEX block writes, enable maintenance mode first".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn enqueue(
+        kind: TaskKind,
+        input: Option<Value>,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<TaskResponse, ApiError> {
+        let task = state
+            .database
+            .create_task(&NewTask {
+                kind: kind.as_str().to_string(),
+                status: TaskStatus::Queued.as_str().to_string(),
+                input: input.map(|input| input.to_string()),
+                created_by: Some(user.user_id),
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Failed to queue task: {e}")))?;
+
+        info!(
+            "User {} queued task {} ({})",
+            user.username,
+            task.id,
+            kind.as_str()
+        );
+        Ok(task.into())
+    }
+
+    /// A task, for the user who queued it or an admin
+    pub fn get(id: i32, user: &AuthenticatedUser, state: &AppState) -> Result<Task, ApiError> {
+        state
+            .database
+            .get_task(id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .filter(|task| user.is_admin || task.created_by == Some(user.user_id))
+            .ok_or_else(|| ApiError::NotFound(format!("Task {id} not found")))
+    }
+
+    /// Archive written by a succeeded export
+    pub fn export_path(task: &Task, state: &AppState) -> Result<PathBuf, ApiError> {
+        if TaskKind::from_kind_str(&task.kind) != Some(TaskKind::Export) {
+            return Err(ApiError::BadRequest(format!(
+                "Task {} is not an export",
+                task.id
+            )));
+        }
+        if task.status() != TaskStatus::Succeeded {
+            return Err(ApiError::Conflict(format!(
+                "Task {} is {}",
+                task.id,
+                task.status()
+            )));
+        }
+        Ok(Self::archive_path(task.id, state))
+    }
+
+    fn archive_path(id: i32, state: &AppState) -> PathBuf {
+        PathBuf::from(&state.config.cache_dir)
+            .join("tasks")
+            .join(format!("{id}.json"))
+    }
+
+    /// Runs queued tasks one at a time. Tasks that were running when the server stopped are
+    /// queued again, every task kind is safe to repeat.
+    pub fn spawn_worker(state: AppState) {
+        match state.database.requeue_running_tasks() {
+            Ok(0) => {}
+            Ok(requeued) => info!("Queued {requeued} interrupted tasks again"),
+            Err(e) => warn!("Failed to queue interrupted tasks again: {e}"),
+        }
+
+        tokio::spawn(async move {
+            loop {
+                match state.database.claim_next_task(Utc::now().naive_utc()) {
+                    Ok(Some(task)) => Self::run(task, &state).await,
+                    Ok(None) => tokio::time::sleep(TASK_POLL_INTERVAL).await,
+                    Err(e) => {
+                        warn!("Failed to claim the next task: {e}");
+                        tokio::time::sleep(TASK_POLL_INTERVAL).await;
+                    }
+                }
+            }
+        });
+    }
+
+    async fn run(task: Task, state: &AppState) {
+        let (id, kind) = (task.id, task.kind.clone());
+        info!("Running task {id} ({kind})");
+
+        let (status, result, error) = match Self::execute(task, state).await {
+            Ok(result) => {
+                info!("Task {id} ({kind}) succeeded");
+                (TaskStatus::Succeeded, Some(result.to_string()), None)
+            }
+            Err(e) => {
+                warn!("Task {id} ({kind}) failed: {e:?}");
+                (TaskStatus::Failed, None, Some(e.to_string()))
+            }
+        };
+
+        if let Err(e) = state.database.finish_task(
+            id,
+            status,
+            result.as_deref(),
+            error.as_deref(),
+            Utc::now().naive_utc(),
+        ) {
+            warn!("Failed to record the outcome of task {id}: {e}");
+        }
+    }
+
+    async fn execute(task: Task, state: &AppState) -> Result<Value, ApiError> {
+        let input: Value = task
+            .input
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(|e| ApiError::ParseError(format!("Invalid task input: {e}")))?
+            .unwrap_or_default();
+
+        match TaskKind::from_kind_str(&task.kind) {
+            Some(TaskKind::ReprocessCache) => {
+                let processed_count = state
+                    .cache
+                    .reprocess_cached_files(&state.database)
+                    .await
+                    .map_err(|e| ApiError::ParseError(format!("Failed to reprocess cache: {e}")))?;
+                Ok(json!({
+                    "message": "Cache reprocessing completed",
+                    "processed_files": processed_count
+                }))
+            }
+            Some(TaskKind::Prefetch) => {
+                let packages: BTreeMap<String, BTreeSet<String>> =
+                    serde_json::from_value(input["packages"].clone()).map_err(|e| {
+                        ApiError::ParseError(format!("Invalid prefetch task input: {e}"))
+                    })?;
+                let user = Self::creator(&task, state)?;
+                let report = PrefetchService::warm(packages, &user, state).await?;
+                serde_json::to_value(report).map_err(|e| ApiError::ParseError(e.to_string()))
+            }
+            Some(TaskKind::Export) => {
+                let package = input["package"].as_str().ok_or_else(|| {
+                    ApiError::ParseError("Export task without a package".to_string())
+                })?;
+                let archive = ArchiveService::export_package(package, state).await?;
+                let versions = archive.versions.len();
+
+                let path = Self::archive_path(task.id, state);
+                let contents = serde_json::to_vec(&archive)
+                    .map_err(|e| ApiError::ParseError(format!("Failed to encode archive: {e}")))?;
+                let write_error =
+                    |e| ApiError::InternalServerError(format!("Failed to write archive: {e}"));
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent)
+                        .await
+                        .map_err(write_error)?;
+                }
+                tokio::fs::write(&path, contents)
+                    .await
+                    .map_err(write_error)?;
+
+                Ok(json!({
+                    "package": package,
+                    "versions": versions,
+                    "download": format!("/api/v1/tasks/{}/download", task.id)
+                }))
+            }
+            Some(TaskKind::OptimizeDatabase) => {
+                let request: DatabaseOptimizeRequest =
+                    serde_json::from_value(input).map_err(|e| {
+                        ApiError::ParseError(format!("Invalid optimize task input: {e}"))
+                    })?;
+                // Maintenance mode may have been switched off while the task was queued
+                Self::check_optimize_allowed(&request, state)?;
+
+                let database = state.database.clone();
+                let report = tokio::task::spawn_blocking(move || {
+                    database.optimize(request.vacuum, request.reindex)
+                })
+                .await
+                .map_err(|e| ApiError::InternalServerError(format!("Optimize task failed: {e}")))?
+                .map_err(|e| {
+                    ApiError::DatabaseError(format!("Failed to optimize database: {e}"))
+                })?;
+                info!(
+                    "Optimized the database, reclaimed {} bytes in {} ms",
+                    report.reclaimed_bytes, report.duration_ms
+                );
+                serde_json::to_value(report).map_err(|e| ApiError::ParseError(e.to_string()))
+            }
+            None => Err(ApiError::ParseError(format!(
+                "Unknown task kind '{}'",
+                task.kind
+            ))),
+        }
+    }
+
+    /// The user who queued a task, whose access it runs with
+    fn creator(task: &Task, state: &AppState) -> Result<AuthenticatedUser, ApiError> {
+        let user = task
+            .created_by
+            .map(|id| state.database.get_user_by_id(id))
+            .transpose()
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .flatten()
+            .ok_or_else(|| {
+                ApiError::NotFound("The user who queued the task no longer exists".to_string())
+            })?;
+        Ok(AuthenticatedUser::new(
+            user.username.clone(),
+            user.id,
+            user.is_admin(),
+            None,
+        ))
+    }
+
+    /// Deletes tasks finished more than `TASK_RETENTION_DAYS` ago with their archives
+    pub fn prune(state: &AppState) -> Result<usize, ApiError> {
+        let before = Utc::now().naive_utc() - ChronoDuration::days(TASK_RETENTION_DAYS);
+        let deleted = state
+            .database
+            .delete_finished_tasks(before)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        for task in &deleted {
+            if TaskKind::from_kind_str(&task.kind) == Some(TaskKind::Export) {
+                let path = Self::archive_path(task.id, state);
+                if let Err(e) = std::fs::remove_file(&path)
+                    && e.kind() != std::io::ErrorKind::NotFound
+                {
+                    warn!("Failed to remove {}: {e}", path.display());
+                }
+            }
+        }
+        Ok(deleted.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseService;
+
+    fn new_task(kind: TaskKind) -> NewTask {
+        NewTask {
+            kind: kind.as_str().to_string(),
+            status: TaskStatus::Queued.as_str().to_string(),
+            input: None,
+            created_by: None,
+        }
+    }
+
+    #[test]
+    fn test_task_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = DatabaseService::new(dir.path().join("clef.db").to_str().unwrap()).unwrap();
+        let now = Utc::now().naive_utc();
+
+        let first = database.create_task(&new_task(TaskKind::Export)).unwrap();
+        let second = database
+            .create_task(&new_task(TaskKind::ReprocessCache))
+            .unwrap();
+
+        // Oldest first, each task is claimed once
+        let claimed = database.claim_next_task(now).unwrap().unwrap();
+        assert_eq!(claimed.id, first.id);
+        assert_eq!(claimed.status(), TaskStatus::Running);
+        assert_eq!(
+            database.claim_next_task(now).unwrap().unwrap().id,
+            second.id
+        );
+        assert!(database.claim_next_task(now).unwrap().is_none());
+
+        // A restart queues running tasks again
+        database
+            .finish_task(first.id, TaskStatus::Succeeded, Some("{}"), None, now)
+            .unwrap();
+        assert_eq!(database.requeue_running_tasks().unwrap(), 1);
+        let requeued = database.claim_next_task(now).unwrap().unwrap();
+        assert_eq!(requeued.id, second.id);
+
+        let response = TaskResponse::from(database.get_task(first.id).unwrap().unwrap());
+        assert_eq!(response.status, "succeeded");
+        assert_eq!(response.result, Some(serde_json::json!({})));
+
+        // Only finished tasks are pruned
+        let pruned = database
+            .delete_finished_tasks(now + ChronoDuration::seconds(1))
+            .unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert!(database.get_task(first.id).unwrap().is_none());
+        assert!(database.get_task(second.id).unwrap().is_some());
+    }
+}
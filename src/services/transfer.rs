@@ -0,0 +1,385 @@
+use crate::error::ApiError;
+use crate::models::organization::{Organization, OrganizationRole};
+use crate::models::{
+    AuthenticatedUser, NewAuditLogEntry, TransferPackageRequest, TransferPackageResponse, User,
+};
+use crate::services::{QuotaService, VisibilityService};
+use crate::state::AppState;
+use log::{info, warn};
+use rocket::serde::json::json;
+
+/// New owner of a transferred package
+enum Target {
+    User(User),
+    Organization(Organization),
+}
+
+/// Moves packages between users and organizations. A package transferred to a user is owned
+/// by that user alone, one transferred to an organization by the organization's admins.
+/// Scoped packages always belong to the organization of their scope, so only their owners
+/// can change.
+pub struct TransferService;
+
+impl TransferService {
+    pub async fn transfer(
+        package: &str,
+        request: TransferPackageRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<TransferPackageResponse, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        let pkg = state
+            .database
+            .get_package_by_name(package)
+            .map_err(db_error)?
+            .ok_or_else(|| ApiError::NotFound(format!("Package '{package}' not found")))?;
+
+        if !VisibilityService::can_administer(&pkg, actor, state)? {
+            return Err(ApiError::Forbidden(
+                "You don't have permission to transfer this package".to_string(),
+            ));
+        }
+
+        let target = Self::target(request, actor, state)?;
+        let scoped = package.starts_with('@');
+        let previous_owners = state
+            .database
+            .get_package_maintainers(package)
+            .map_err(db_error)?;
+
+        let (author_id, organization_id, owner_ids) = match &target {
+            Target::User(user) => {
+                let organization_id = pkg.organization_id.filter(|_| scoped);
+                if organization_id == pkg.organization_id
+                    && previous_owners.len() == 1
+                    && previous_owners[0].0 == user.username
+                {
+                    return Err(ApiError::BadRequest(format!(
+                        "Package '{package}' is already owned by '{}'",
+                        user.username
+                    )));
+                }
+                (Some(user.id), organization_id, vec![user.id])
+            }
+            Target::Organization(org) => {
+                if pkg.organization_id == Some(org.id) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Package '{package}' already belongs to organization '{}'",
+                        org.name
+                    )));
+                }
+                if scoped {
+                    return Err(ApiError::BadRequest(
+                        "Scoped packages belong to the organization of their scope".to_string(),
+                    ));
+                }
+                let admins = state
+                    .database
+                    .get_organization_members(org.id)
+                    .map_err(db_error)?
+                    .into_iter()
+                    .filter(|m| {
+                        OrganizationRole::from_role_str(&m.member.role)
+                            .is_some_and(|role| role.can_manage_members())
+                    })
+                    .map(|m| m.member.user_id)
+                    .collect::<Vec<_>>();
+                if admins.is_empty() {
+                    return Err(ApiError::BadRequest(format!(
+                        "Organization '{}' has no admins to own the package",
+                        org.name
+                    )));
+                }
+                (pkg.author_id, Some(org.id), admins)
+            }
+        };
+
+        // Storage counts against the organization, or against the author of packages outside
+        // of organizations, so the quota is only checked when that changes
+        let quota_owner = match &target {
+            Target::User(user)
+                if organization_id.is_none()
+                    && (pkg.organization_id.is_some() || pkg.author_id != Some(user.id)) =>
+            {
+                Some((user.id, None))
+            }
+            Target::User(_) => None,
+            Target::Organization(org) => Some((actor.user_id, Some(org.id))),
+        };
+        if let Some((user_id, organization_id)) = quota_owner {
+            let size = Self::size(package, state)?;
+            QuotaService::check_transfer(user_id, organization_id, size, state)?;
+        }
+
+        let transferred = state
+            .database
+            .transfer_package(pkg.id, author_id, organization_id, &owner_ids)
+            .map_err(db_error)?;
+
+        if let Err(e) = state.cache.invalidate_metadata(package).await {
+            warn!("Failed to invalidate metadata cache for package {package}: {e}");
+        }
+
+        let (to_user, to_organization) = match &target {
+            Target::User(user) => (Some(user.username.clone()), None),
+            Target::Organization(org) => (None, Some(org.name.clone())),
+        };
+        let details = json!({
+            "from_owners": previous_owners.iter().map(|(username, _)| username).collect::<Vec<_>>(),
+            "from_organization_id": pkg.organization_id,
+            "to_user": to_user,
+            "to_organization": to_organization,
+        });
+        let entry = NewAuditLogEntry {
+            action: "package.transfer".to_string(),
+            actor_id: Some(actor.user_id),
+            organization_id: transferred.organization_id.or(pkg.organization_id),
+            package_name: Some(package.to_string()),
+            version: None,
+            details: Some(details.to_string()),
+        };
+        if let Err(e) = state.database.create_audit_log_entry(&entry) {
+            warn!("Failed to write audit log entry package.transfer: {e}");
+        }
+
+        info!(
+            "User {} transferred {package} to {}",
+            actor.username,
+            to_user
+                .as_deref()
+                .or(to_organization.as_deref())
+                .unwrap_or_default()
+        );
+
+        let organization = match transferred.organization_id {
+            Some(id) => state
+                .database
+                .get_organization_by_id(id)
+                .map_err(db_error)?
+                .map(|org| org.name),
+            None => None,
+        };
+        let owners = state
+            .database
+            .get_package_maintainers(package)
+            .map_err(db_error)?
+            .into_iter()
+            .map(|(username, _)| username)
+            .collect();
+
+        Ok(TransferPackageResponse {
+            package: package.to_string(),
+            organization,
+            owners,
+        })
+    }
+
+    /// Resolves the user or organization named in the request. Only admins of the receiving
+    /// organization may move packages into it.
+    fn target(
+        request: TransferPackageRequest,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<Target, ApiError> {
+        let db_error = |e| ApiError::InternalServerError(format!("Database error: {e}"));
+
+        match (request.to_user, request.to_organization) {
+            (Some(username), None) => state
+                .database
+                .get_user_by_username(&username)
+                .map_err(db_error)?
+                .filter(|user| user.is_active)
+                .map(Target::User)
+                .ok_or_else(|| ApiError::NotFound(format!("User '{username}' not found"))),
+            (None, Some(name)) => {
+                let org = state
+                    .database
+                    .get_organization_by_name(&name)
+                    .map_err(db_error)?
+                    .ok_or_else(|| {
+                        ApiError::NotFound(format!("Organization '{name}' not found"))
+                    })?;
+                let is_org_admin = actor.is_admin
+                    || state
+                        .database
+                        .check_organization_permission(
+                            org.id,
+                            actor.user_id,
+                            OrganizationRole::Admin,
+                        )
+                        .map_err(db_error)?;
+                if !is_org_admin {
+                    return Err(ApiError::Forbidden(format!(
+                        "Only admins of organization '{name}' can transfer packages to it"
+                    )));
+                }
+                Ok(Target::Organization(org))
+            }
+            _ => Err(ApiError::BadRequest(
+                "Exactly one of to_user and to_organization is required".to_string(),
+            )),
+        }
+    }
+
+    /// Bytes stored for all versions of a package
+    fn size(package: &str, state: &AppState) -> Result<u64, ApiError> {
+        let versions = state
+            .database
+            .get_package_with_versions(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .map(|p| p.versions)
+            .unwrap_or_default();
+
+        Ok(versions
+            .iter()
+            .flat_map(|v| &v.files)
+            .map(|f| f.size_bytes.max(0) as u64)
+            .sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::models::RegisterRequest;
+    use crate::services::AuthService;
+
+    fn state(dir: &tempfile::TempDir) -> AppState {
+        crate::create_state(AppConfig {
+            cache_dir: dir.path().to_str().unwrap().to_string(),
+            database_url: dir.path().join("clef.db").to_str().unwrap().to_string(),
+            ..AppConfig::default()
+        })
+    }
+
+    fn user(state: &AppState, name: &str) -> AuthenticatedUser {
+        let user = AuthService::register_user(
+            &state.database,
+            RegisterRequest {
+                name: name.to_string(),
+                email: format!("{name}@example.com"),
+                password: "password123".to_string(),
+                invite_token: None,
+            },
+        )
+        .unwrap();
+        AuthenticatedUser::new(user.username, user.id, false, None)
+    }
+
+    fn to_user(username: &str) -> TransferPackageRequest {
+        TransferPackageRequest {
+            to_user: Some(username.to_string()),
+            to_organization: None,
+        }
+    }
+
+    fn owners(package: &str, state: &AppState) -> Vec<String> {
+        state
+            .database
+            .get_package_maintainers(package)
+            .unwrap()
+            .into_iter()
+            .map(|(username, _)| username)
+            .collect()
+    }
+
+    fn transfers(state: &AppState) -> usize {
+        state
+            .database
+            .list_package_audit_log("left-pad", 100)
+            .unwrap()
+            .iter()
+            .filter(|(entry, _)| entry.action == "package.transfer")
+            .count()
+    }
+
+    /// `left-pad`, owned by a fresh user `alice`
+    fn fixture(state: &AppState) -> AuthenticatedUser {
+        let alice = user(state, "alice");
+        state
+            .database
+            .create_or_get_package("left-pad", None, Some(alice.user_id))
+            .unwrap();
+        state
+            .database
+            .create_package_owner("left-pad", alice.user_id, "admin")
+            .unwrap();
+        alice
+    }
+
+    #[tokio::test]
+    async fn test_transfer_to_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let alice = fixture(&state);
+        user(&state, "bob");
+
+        let response = TransferService::transfer("left-pad", to_user("bob"), &alice, &state)
+            .await
+            .unwrap();
+        assert_eq!(response.owners, vec!["bob".to_string()]);
+        assert_eq!(response.organization, None);
+
+        let bob = state.database.get_user_by_username("bob").unwrap().unwrap();
+        let pkg = state
+            .database
+            .get_package_by_name("left-pad")
+            .unwrap()
+            .unwrap();
+        assert_eq!(pkg.author_id, Some(bob.id));
+        assert_eq!(owners("left-pad", &state), vec!["bob".to_string()]);
+        assert_eq!(transfers(&state), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_refused_for_non_owner() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        fixture(&state);
+        let mallory = user(&state, "mallory");
+
+        let result =
+            TransferService::transfer("left-pad", to_user("mallory"), &mallory, &state).await;
+        assert!(matches!(result, Err(ApiError::Forbidden(_))));
+        assert_eq!(owners("left-pad", &state), vec!["alice".to_string()]);
+        assert_eq!(transfers(&state), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_transfer_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state(&dir);
+        let alice = fixture(&state);
+        let bob = user(&state, "bob");
+        state
+            .database
+            .create_organization("acme", None, None, bob.user_id)
+            .unwrap();
+
+        // Alice owns the package but isn't an admin of the organization
+        let request = TransferPackageRequest {
+            to_user: None,
+            to_organization: Some("acme".to_string()),
+        };
+        let result = TransferService::transfer("left-pad", request, &alice, &state).await;
+        assert!(matches!(result, Err(ApiError::Forbidden(_))));
+
+        // Nor can a package be given to its current owner, or to a missing user
+        let result = TransferService::transfer("left-pad", to_user("alice"), &alice, &state).await;
+        assert!(matches!(result, Err(ApiError::BadRequest(_))));
+        let result = TransferService::transfer("left-pad", to_user("nobody"), &alice, &state).await;
+        assert!(matches!(result, Err(ApiError::NotFound(_))));
+
+        let pkg = state
+            .database
+            .get_package_by_name("left-pad")
+            .unwrap()
+            .unwrap();
+        assert_eq!(pkg.organization_id, None);
+        assert_eq!(pkg.author_id, Some(alice.user_id));
+        assert_eq!(owners("left-pad", &state), vec!["alice".to_string()]);
+        assert_eq!(transfers(&state), 0);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, FlaggedName, FlaggedNameListResponse, FlaggedNameStatus, NewFlaggedName,
+};
+use crate::state::AppState;
+use log::{info, warn};
+
+/// Widely depended upon npm packages that typosquatters tend to imitate. Locally popular
+/// packages are checked in addition to these.
+const POPULAR_PACKAGES: &[&str] = &[
+    "@babel/core",
+    "@types/node",
+    "@types/react",
+    "async",
+    "axios",
+    "bluebird",
+    "body-parser",
+    "chalk",
+    "cheerio",
+    "classnames",
+    "colors",
+    "commander",
+    "cookie-parser",
+    "cors",
+    "cross-env",
+    "crypto-js",
+    "debug",
+    "dotenv",
+    "electron",
+    "eslint",
+    "express",
+    "fs-extra",
+    "glob",
+    "graphql",
+    "inquirer",
+    "jquery",
+    "jsonwebtoken",
+    "lodash",
+    "minimist",
+    "mkdirp",
+    "moment",
+    "mongodb",
+    "mongoose",
+    "morgan",
+    "mysql",
+    "next",
+    "node-fetch",
+    "nodemon",
+    "prettier",
+    "prop-types",
+    "react",
+    "react-dom",
+    "react-router",
+    "redux",
+    "request",
+    "rimraf",
+    "rxjs",
+    "semver",
+    "socket.io",
+    "styled-components",
+    "tslib",
+    "typescript",
+    "underscore",
+    "uuid",
+    "vue",
+    "webpack",
+    "ws",
+    "yargs",
+];
+
+/// Locally most downloaded packages that are compared as well
+const LOCAL_POPULAR_LIMIT: i64 = 200;
+
+/// How publishing a look-alike name is handled, from `CLEF_TYPOSQUAT_MODE`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TyposquatMode {
+    Off,
+    Warn,
+    Review,
+    Reject,
+}
+
+impl TyposquatMode {
+    pub fn from_mode_str(mode: &str) -> Self {
+        match mode {
+            "off" => Self::Off,
+            "review" => Self::Review,
+            "reject" => Self::Reject,
+            _ => Self::Warn,
+        }
+    }
+}
+
+pub struct TyposquatService;
+
+impl TyposquatService {
+    /// Checks the name of a package that is published for the first time. Depending on the
+    /// mode a look-alike name is logged, held for admin approval or rejected.
+    pub fn check_new_package(
+        package: &str,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<(), ApiError> {
+        let mode = TyposquatMode::from_mode_str(&state.config.typosquat_mode);
+        if mode == TyposquatMode::Off || user.is_admin {
+            return Ok(());
+        }
+
+        // An earlier review decision sticks to the name
+        if let Some(flagged) = state
+            .database
+            .get_flagged_name(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+        {
+            return match flagged.status() {
+                FlaggedNameStatus::Approved | FlaggedNameStatus::Warned => Ok(()),
+                FlaggedNameStatus::Pending | FlaggedNameStatus::Rejected => {
+                    Err(Self::flagged_error(&flagged))
+                }
+            };
+        }
+
+        let popular = Self::popular_names(state)?;
+        let Some((similar_to, reason)) =
+            Self::find_similar(package, popular.iter().map(String::as_str))
+        else {
+            return Ok(());
+        };
+
+        let status = match mode {
+            TyposquatMode::Review => FlaggedNameStatus::Pending,
+            TyposquatMode::Reject => FlaggedNameStatus::Rejected,
+            _ => FlaggedNameStatus::Warned,
+        };
+
+        let flagged = state
+            .database
+            .create_flagged_name(&NewFlaggedName {
+                package_name: package.to_string(),
+                similar_to,
+                reason,
+                status: status.to_string(),
+                published_by: Some(user.user_id),
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        warn!(
+            "User {} published {package}, which resembles {} ({}), status: {status}",
+            user.username, flagged.similar_to, flagged.reason
+        );
+
+        match status {
+            FlaggedNameStatus::Warned => Ok(()),
+            _ => Err(Self::flagged_error(&flagged)),
+        }
+    }
+
+    pub fn list(
+        status: Option<&str>,
+        state: &AppState,
+    ) -> Result<FlaggedNameListResponse, ApiError> {
+        if let Some(status) = status
+            && FlaggedNameStatus::from_status_str(status).is_none()
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid status '{status}', expected warned, pending, approved or rejected"
+            )));
+        }
+
+        let flagged = state
+            .database
+            .list_flagged_names(status.map(str::to_lowercase).as_deref())
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(FlaggedNameListResponse {
+            mode: state.config.typosquat_mode.clone(),
+            flagged,
+        })
+    }
+
+    /// Approves or rejects a flagged name. An approved name can then be published.
+    pub fn review(
+        id: i32,
+        status: FlaggedNameStatus,
+        admin: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<FlaggedName, ApiError> {
+        let flagged = state
+            .database
+            .review_flagged_name(id, status.as_str(), admin.user_id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Flagged name {id} not found")))?;
+
+        info!(
+            "Admin {} marked flagged name {} as {status}",
+            admin.username, flagged.package_name
+        );
+
+        Ok(flagged)
+    }
+
+    fn flagged_error(flagged: &FlaggedName) -> ApiError {
+        match flagged.status() {
+            FlaggedNameStatus::Rejected => ApiError::Forbidden(format!(
+                "Package name '{}' is too similar to '{}' and was rejected",
+                flagged.package_name, flagged.similar_to
+            )),
+            _ => ApiError::Forbidden(format!(
+                "Package name '{}' is similar to '{}' and needs admin approval before it can be published",
+                flagged.package_name, flagged.similar_to
+            )),
+        }
+    }
+
+    fn popular_names(state: &AppState) -> Result<Vec<String>, ApiError> {
+        let mut names: Vec<String> = POPULAR_PACKAGES.iter().map(|s| s.to_string()).collect();
+
+        let local = state
+            .database
+            .get_popular_packages(LOCAL_POPULAR_LIMIT)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        names.extend(
+            local
+                .into_iter()
+                .filter(|pkg| pkg.total_downloads > 0)
+                .map(|pkg| pkg.name),
+        );
+
+        Ok(names)
+    }
+
+    /// First popular name the package name could be a typo of, with the reason
+    fn find_similar<'a>(
+        package: &str,
+        popular: impl IntoIterator<Item = &'a str>,
+    ) -> Option<(String, String)> {
+        let name = package.to_lowercase();
+        let normalized = Self::normalize(&name);
+
+        for candidate in popular {
+            if candidate == name {
+                continue;
+            }
+
+            if Self::normalize(candidate) == normalized {
+                return Some((candidate.to_string(), "look-alike characters".to_string()));
+            }
+
+            let max_distance = match candidate.len() {
+                0..=3 => 0,
+                4..=9 => 1,
+                _ => 2,
+            };
+            if max_distance == 0 {
+                continue;
+            }
+
+            let distance = Self::edit_distance(&name, candidate);
+            if distance <= max_distance {
+                return Some((candidate.to_string(), format!("edit distance {distance}")));
+            }
+        }
+
+        None
+    }
+
+    /// Folds separators and characters that are easily confused with each other
+    fn normalize(name: &str) -> String {
+        name.replace("rn", "m")
+            .replace("vv", "w")
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | '.'))
+            .map(|c| match c {
+                '0' => 'o',
+                '1' | 'i' => 'l',
+                '3' => 'e',
+                '4' => 'a',
+                '5' => 's',
+                '7' => 't',
+                c => c,
+            })
+            .collect()
+    }
+
+    /// Levenshtein distance that counts swapping two adjacent characters as one edit
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, cell) in rows[0].iter_mut().enumerate() {
+            *cell = j;
+        }
+
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = usize::from(a[i - 1] != b[j - 1]);
+                let mut best = (rows[i - 1][j] + 1)
+                    .min(rows[i][j - 1] + 1)
+                    .min(rows[i - 1][j - 1] + cost);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    best = best.min(rows[i - 2][j - 2] + 1);
+                }
+                rows[i][j] = best;
+            }
+        }
+
+        rows[a.len()][b.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(TyposquatService::edit_distance("express", "expres"), 1);
+        assert_eq!(TyposquatService::edit_distance("react", "raect"), 1);
+        assert_eq!(TyposquatService::edit_distance("lodash", "lodash"), 0);
+        assert_eq!(TyposquatService::edit_distance("vue", "react"), 5);
+    }
+
+    #[test]
+    fn test_find_similar() {
+        let popular = ["lodash", "cross-env", "express", "ws"];
+        let similar = |name| TyposquatService::find_similar(name, popular);
+
+        assert_eq!(similar("l0dash").unwrap().0, "lodash");
+        assert_eq!(similar("crossenv").unwrap().0, "cross-env");
+        assert_eq!(similar("expresss").unwrap().0, "express");
+        assert!(similar("lodash").is_none());
+        assert!(similar("wss").is_none());
+        assert!(similar("my-internal-tool").is_none());
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::ApiError;
+use crate::models::{
+    AuthenticatedUser, NewAuditLogEntry, NewVersionTombstone, PackageVersionWithFiles,
+    RegistryEvent, UnpublishResponse,
+};
+use crate::state::AppState;
+use chrono::NaiveDateTime;
+use log::{info, warn};
+use std::path::Path;
+
+pub struct UnpublishService;
+
+impl UnpublishService {
+    /// With immutable versions, rejects publishing a version that exists or was deleted
+    pub fn check_publish(package: &str, version: &str, state: &AppState) -> Result<(), ApiError> {
+        if !state.config.immutable_versions {
+            return Ok(());
+        }
+
+        let tombstone = state
+            .database
+            .get_version_tombstone(package, version)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        if let Some(tombstone) = tombstone {
+            return Err(ApiError::Conflict(format!(
+                "{package}@{version} was deleted on {} ({}) and can't be published again",
+                tombstone.created_at.format("%Y-%m-%d"),
+                tombstone.reason
+            )));
+        }
+
+        let published = state
+            .database
+            .get_package_by_name(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .filter(|pkg| pkg.author_id.is_some());
+        if let Some(pkg) = published {
+            let exists = state
+                .database
+                .get_package_versions(pkg.id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+                .iter()
+                .any(|v| v.version == version);
+
+            if exists {
+                return Err(ApiError::Conflict(format!(
+                    "Cannot publish over the previously published version {version} of {package}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes a published version. With immutable versions this is only possible within the
+    /// grace period after publishing, and leaves a tombstone.
+    pub async fn unpublish(
+        package: &str,
+        version: &str,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<UnpublishResponse, ApiError> {
+        let pkg_with_versions = state
+            .database
+            .get_package_with_versions(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .filter(|pkg| pkg.package.author_id.is_some())
+            .ok_or_else(|| ApiError::NotFound(format!("Package '{package}' not found")))?;
+
+        let can_publish = actor.is_admin
+            || state
+                .database
+                .can_publish_package(package, actor.user_id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        if !can_publish {
+            return Err(ApiError::Forbidden(format!(
+                "You don't have permission to unpublish versions of '{package}'"
+            )));
+        }
+
+        let entry = pkg_with_versions
+            .versions
+            .iter()
+            .find(|v| v.version.version == version)
+            .ok_or_else(|| {
+                ApiError::NotFound(format!("Package '{package}' version '{version}' not found"))
+            })?;
+
+        let grace_hours = state.config.unpublish_grace_hours;
+        if state.config.immutable_versions
+            && !Self::within_grace(
+                entry.version.created_at,
+                chrono::Utc::now().naive_utc(),
+                grace_hours,
+            )
+        {
+            return Err(ApiError::Forbidden(format!(
+                "{package}@{version} can only be unpublished within {grace_hours} hours of publishing, yank it instead"
+            )));
+        }
+
+        let bytes_freed =
+            Self::delete_version(package, entry, Some(actor.user_id), "unpublished", state)?;
+
+        if let Err(e) = state.cache.invalidate_metadata(package).await {
+            warn!("Failed to invalidate metadata cache for package {package}: {e}");
+        }
+
+        let entry = NewAuditLogEntry {
+            action: "package.unpublish".to_string(),
+            actor_id: Some(actor.user_id),
+            organization_id: pkg_with_versions.package.organization_id,
+            package_name: Some(package.to_string()),
+            version: Some(version.to_string()),
+            details: None,
+        };
+        if let Err(e) = state.database.create_audit_log_entry(&entry) {
+            warn!("Failed to write audit log entry package.unpublish: {e}");
+        }
+
+        state.events.emit(RegistryEvent::VersionUnpublished {
+            package: package.to_string(),
+            version: version.to_string(),
+            actor: actor.username.clone(),
+        });
+
+        info!("User {} unpublished {package}@{version}", actor.username);
+        Ok(UnpublishResponse {
+            package: package.to_string(),
+            version: version.to_string(),
+            bytes_freed,
+            tombstone: state.config.immutable_versions,
+        })
+    }
+
+    /// Deletes the stored files and the row of a version, and leaves a tombstone when
+    /// versions are immutable. Returns the bytes freed.
+    pub fn delete_version(
+        package: &str,
+        entry: &PackageVersionWithFiles,
+        deleted_by: Option<i32>,
+        reason: &str,
+        state: &AppState,
+    ) -> Result<u64, ApiError> {
+        let mut freed = 0;
+
+        for file in &entry.files {
+            let path = Path::new(&file.file_path);
+            // Published tarballs keep their package.json next to them
+            let manifest = path.with_extension("json");
+
+            for path in [path, manifest.as_path()] {
+                match std::fs::remove_file(path) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => warn!("Failed to remove {}: {e}", path.display()),
+                }
+            }
+            freed += file.size_bytes.max(0) as u64;
+        }
+
+        if state.config.immutable_versions {
+            state
+                .database
+                .create_version_tombstone(&NewVersionTombstone {
+                    package_name: package.to_string(),
+                    version: entry.version.version.clone(),
+                    reason: reason.to_string(),
+                    deleted_by,
+                })
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        }
+
+        state
+            .database
+            .delete_package_version(entry.version.id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok(freed)
+    }
+
+    fn within_grace(published_at: NaiveDateTime, now: NaiveDateTime, grace_hours: u64) -> bool {
+        i128::from((now - published_at).num_seconds()) <= i128::from(grace_hours) * 3600
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_grace() {
+        let now = chrono::Utc::now().naive_utc();
+        let hours = |h| now - chrono::Duration::hours(h);
+
+        assert!(UnpublishService::within_grace(hours(1), now, 72));
+        assert!(UnpublishService::within_grace(hours(72), now, 72));
+        assert!(!UnpublishService::within_grace(hours(73), now, 72));
+        assert!(!UnpublishService::within_grace(hours(1), now, 0));
+        assert!(UnpublishService::within_grace(hours(1), now, u64::MAX));
+    }
+}
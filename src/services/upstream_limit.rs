@@ -0,0 +1,208 @@
+use crate::config::AppConfig;
+use crate::error::ApiError;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Caps the upstream requests in flight, in total and per upstream host, so a burst of cold
+/// installs queues for a free slot instead of opening a connection per request. Requests
+/// still waiting after the queue timeout are refused with a 503.
+#[derive(Debug)]
+pub struct UpstreamLimiter {
+    global: Option<Arc<Semaphore>>,
+    per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+    queue_timeout: Duration,
+}
+
+/// A slot for one upstream request, given back when dropped
+#[derive(Debug)]
+pub struct UpstreamPermit {
+    _host: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+impl Default for UpstreamLimiter {
+    fn default() -> Self {
+        Self::new(&AppConfig {
+            upstream_max_concurrency: 0,
+            upstream_max_concurrency_per_host: 0,
+            ..AppConfig::default()
+        })
+    }
+}
+
+impl UpstreamLimiter {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            global: (config.upstream_max_concurrency > 0)
+                .then(|| Arc::new(Semaphore::new(config.upstream_max_concurrency))),
+            per_host: config.upstream_max_concurrency_per_host,
+            hosts: Mutex::new(HashMap::new()),
+            queue_timeout: Duration::from_millis(config.upstream_queue_timeout_ms),
+        }
+    }
+
+    /// Waits for a slot to send a request to `url`. The slot of the host is taken first, so
+    /// requests queued for a busy host don't hold on to slots other hosts could use.
+    pub async fn acquire(&self, url: &str) -> Result<UpstreamPermit, ApiError> {
+        let host = Self::host_of(url);
+        let host_slots = self.host_slots(&host);
+        let global = self.global.clone();
+
+        let acquire = async {
+            let host = match host_slots {
+                Some(slots) => Some(slots.acquire_owned().await.ok()?),
+                None => None,
+            };
+            let global = match global {
+                Some(slots) => Some(slots.acquire_owned().await.ok()?),
+                None => None,
+            };
+            Some(UpstreamPermit {
+                _host: host,
+                _global: global,
+            })
+        };
+
+        let permit = if self.queue_timeout.is_zero() {
+            // Nothing is queued, a request either gets a slot right away or is refused
+            tokio::select! {
+                biased;
+                permit = acquire => permit,
+                _ = std::future::ready(()) => None,
+            }
+        } else {
+            tokio::time::timeout(self.queue_timeout, acquire)
+                .await
+                .ok()
+                .flatten()
+        };
+
+        permit.ok_or_else(|| {
+            warn!("Too many concurrent upstream requests to {host}, refusing one");
+            ApiError::ServiceUnavailable(format!(
+                "Too many concurrent requests to upstream {host}, try again later"
+            ))
+        })
+    }
+
+    fn host_slots(&self, host: &str) -> Option<Arc<Semaphore>> {
+        if self.per_host == 0 {
+            return None;
+        }
+        let mut hosts = self.hosts.lock().unwrap();
+        let slots = hosts
+            .entry(host.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)));
+        Some(slots.clone())
+    }
+
+    /// Host and port requests to `url` go to
+    fn host_of(url: &str) -> String {
+        match reqwest::Url::parse(url) {
+            Ok(url) => match (url.host_str(), url.port_or_known_default()) {
+                (Some(host), Some(port)) => format!("{host}:{port}"),
+                (Some(host), None) => host.to_string(),
+                _ => url.to_string(),
+            },
+            Err(_) => url.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(total: usize, per_host: usize, queue_timeout_ms: u64) -> UpstreamLimiter {
+        UpstreamLimiter::new(&AppConfig {
+            upstream_max_concurrency: total,
+            upstream_max_concurrency_per_host: per_host,
+            upstream_queue_timeout_ms: queue_timeout_ms,
+            ..AppConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_limits_requests_per_host() {
+        let limiter = limiter(0, 2, 0);
+        let _first = limiter
+            .acquire("https://registry.npmjs.org/a")
+            .await
+            .unwrap();
+        let second = limiter
+            .acquire("https://registry.npmjs.org/b")
+            .await
+            .unwrap();
+        let refused = limiter.acquire("https://registry.npmjs.org/c").await;
+        assert!(matches!(refused, Err(ApiError::ServiceUnavailable(_))));
+
+        // Other hosts have slots of their own
+        assert!(limiter.acquire("https://mirror.internal/a").await.is_ok());
+
+        drop(second);
+        assert!(
+            limiter
+                .acquire("https://registry.npmjs.org/c")
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limits_requests_in_total() {
+        let limiter = limiter(1, 0, 0);
+        let first = limiter
+            .acquire("https://registry.npmjs.org/a")
+            .await
+            .unwrap();
+        assert!(limiter.acquire("https://mirror.internal/a").await.is_err());
+        drop(first);
+        assert!(limiter.acquire("https://mirror.internal/a").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_queues_until_a_slot_frees_up() {
+        let limiter = Arc::new(limiter(1, 1, 5000));
+        let first = limiter
+            .acquire("https://registry.npmjs.org/a")
+            .await
+            .unwrap();
+
+        let waiting = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire("https://registry.npmjs.org/b").await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+
+        drop(first);
+        assert!(waiting.await.unwrap().is_ok());
+
+        let limiter = self::limiter(1, 1, 20);
+        let _first = limiter
+            .acquire("https://registry.npmjs.org/a")
+            .await
+            .unwrap();
+        assert!(
+            limiter
+                .acquire("https://registry.npmjs.org/b")
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let limiter = UpstreamLimiter::default();
+        assert!(limiter.global.is_none());
+        assert!(limiter.host_slots("registry.npmjs.org:443").is_none());
+        assert_eq!(
+            UpstreamLimiter::host_of("https://registry.npmjs.org/lodash"),
+            "registry.npmjs.org:443"
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use crate::error::ApiError;
+use crate::models::PackageArchive;
+use crate::services::{ArchiveService, AuthService};
+use crate::state::AppState;
+use clap::Parser;
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Options of the `clef import verdaccio` command
+#[derive(Parser, Debug, Clone, PartialEq, Default)]
+pub struct VerdaccioImportOptions {
+    /// Verdaccio storage directory
+    #[arg(long, value_name = "DIR")]
+    pub storage: PathBuf,
+    /// Verdaccio htpasswd file to import users from
+    #[arg(long, value_name = "FILE")]
+    pub htpasswd: Option<PathBuf>,
+    /// Owner of packages whose publisher isn't a user here (default: CLEF_ADMIN_USERNAME)
+    #[arg(long, value_name = "USER")]
+    pub owner: Option<String>,
+    /// Also import packages Verdaccio cached from its uplinks
+    #[arg(long)]
+    pub include_cached: bool,
+}
+
+/// An htpasswd entry. Only bcrypt hashes can be kept, Verdaccio's older `{SHA}` and crypt
+/// hashes can't be checked here.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtpasswdUser {
+    pub username: String,
+    pub password_hash: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct VerdaccioImportReport {
+    pub users_created: usize,
+    pub users_skipped: usize,
+    /// Users created with a random password, their hash couldn't be kept
+    pub users_without_password: Vec<String>,
+    pub packages: usize,
+    pub imported_versions: usize,
+    pub skipped_versions: usize,
+    /// `name@version` of versions whose tarball isn't in the storage directory
+    pub missing_tarballs: Vec<String>,
+    /// Packages cached from uplinks, left out without `--include-cached`
+    pub cached_packages: Vec<String>,
+    /// Packages that failed to import, with the reason
+    pub failed_packages: Vec<(String, String)>,
+}
+
+/// A package found in the storage directory
+struct StoredPackage {
+    archive: PackageArchive,
+    /// Publisher of the latest version
+    publisher: Option<String>,
+    cached: bool,
+    missing_tarballs: Vec<String>,
+}
+
+pub struct VerdaccioImportService;
+
+impl VerdaccioImportService {
+    /// Imports the htpasswd users, then every package in the storage directory
+    pub async fn import(
+        options: &VerdaccioImportOptions,
+        state: &AppState,
+    ) -> Result<VerdaccioImportReport, ApiError> {
+        let mut report = VerdaccioImportReport::default();
+
+        if let Some(htpasswd) = &options.htpasswd {
+            let contents = std::fs::read_to_string(htpasswd).map_err(|e| {
+                ApiError::BadRequest(format!("Failed to read {}: {e}", htpasswd.display()))
+            })?;
+            for user in Self::parse_htpasswd(&contents) {
+                let email = format!("{}@localhost", user.username);
+                let keeps_password = user.password_hash.is_some();
+                match AuthService::import_user(
+                    &state.database,
+                    &user.username,
+                    &email,
+                    user.password_hash,
+                )? {
+                    Some(_) => {
+                        report.users_created += 1;
+                        if !keeps_password {
+                            report.users_without_password.push(user.username);
+                        }
+                    }
+                    None => report.users_skipped += 1,
+                }
+            }
+        }
+
+        let fallback_owner = options
+            .owner
+            .clone()
+            .or_else(|| state.config.admin_username.clone());
+
+        for dir in Self::package_dirs(&options.storage)? {
+            let stored = match Self::read_package(&dir) {
+                Ok(Some(stored)) => stored,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Skipping {}: {e}", dir.display());
+                    report
+                        .failed_packages
+                        .push((dir.display().to_string(), e.to_string()));
+                    continue;
+                }
+            };
+            let name = stored.archive.name.clone();
+            if stored.cached && !options.include_cached {
+                report.cached_packages.push(name);
+                continue;
+            }
+            report.missing_tarballs.extend(stored.missing_tarballs);
+
+            let owner = Self::owner(
+                stored.publisher.as_deref(),
+                fallback_owner.as_deref(),
+                state,
+            )?;
+            let Some(owner) = owner else {
+                report.failed_packages.push((
+                    name,
+                    "no owner, pass --owner or set CLEF_ADMIN_USERNAME".to_string(),
+                ));
+                continue;
+            };
+
+            match ArchiveService::import_package(stored.archive, owner, state).await {
+                Ok(imported) => {
+                    report.packages += 1;
+                    report.imported_versions += imported.imported_versions.len();
+                    report.skipped_versions += imported.skipped_versions.len();
+                }
+                Err(e) => {
+                    warn!("Failed to import {name}: {e}");
+                    report.failed_packages.push((name, e.to_string()));
+                }
+            }
+        }
+
+        info!(
+            "Imported {} packages ({} versions, {} skipped) and {} users from Verdaccio",
+            report.packages,
+            report.imported_versions,
+            report.skipped_versions,
+            report.users_created
+        );
+        Ok(report)
+    }
+
+    /// Users of an htpasswd file, `name:hash` with Verdaccio's optional trailing fields
+    pub fn parse_htpasswd(contents: &str) -> Vec<HtpasswdUser> {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let username = fields.next()?.trim();
+                let hash = fields.next().unwrap_or("").trim();
+                if username.is_empty() {
+                    return None;
+                }
+                let is_bcrypt = ["$2a$", "$2b$", "$2y$"]
+                    .iter()
+                    .any(|prefix| hash.starts_with(prefix));
+                Some(HtpasswdUser {
+                    username: username.to_string(),
+                    password_hash: is_bcrypt.then(|| hash.to_string()),
+                })
+            })
+            .collect()
+    }
+
+    /// Package directories of a storage directory, `name/` and `@scope/name/`
+    fn package_dirs(storage: &Path) -> Result<Vec<PathBuf>, ApiError> {
+        let read_dir = |dir: &Path| {
+            std::fs::read_dir(dir)
+                .map_err(|e| ApiError::BadRequest(format!("Failed to read {}: {e}", dir.display())))
+                .map(|entries| {
+                    entries
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.path())
+                        .filter(|path| path.is_dir())
+                        .collect::<Vec<_>>()
+                })
+        };
+
+        let mut dirs = Vec::new();
+        for dir in read_dir(storage)? {
+            let name = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if name.starts_with('@') {
+                dirs.extend(read_dir(&dir)?);
+            } else if !name.starts_with('.') {
+                dirs.push(dir);
+            }
+        }
+        dirs.sort();
+        Ok(dirs)
+    }
+
+    /// Reads a package's `package.json` and the tarballs next to it. `Ok(None)` for
+    /// directories that aren't packages.
+    fn read_package(dir: &Path) -> Result<Option<StoredPackage>, String> {
+        let path = dir.join("package.json");
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let document: Value = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()))?;
+
+        let name = document["name"]
+            .as_str()
+            .ok_or("package.json has no name")?
+            .to_string();
+        let cached = document["_uplinks"]
+            .as_object()
+            .is_some_and(|uplinks| !uplinks.is_empty());
+
+        let mut tarballs = HashMap::new();
+        let mut missing_tarballs = Vec::new();
+        for (version, manifest) in document["versions"].as_object().into_iter().flatten() {
+            let filename = ArchiveService::tarball_filename(&name, version, manifest);
+            match std::fs::read(dir.join(&filename)) {
+                Ok(data) => {
+                    tarballs.insert(version.clone(), data);
+                }
+                Err(_) => missing_tarballs.push(format!("{name}@{version}")),
+            }
+        }
+
+        let archive = ArchiveService::archive_from_document(&document, tarballs)?;
+        let publisher = archive
+            .dist_tags
+            .get("latest")
+            .and_then(|latest| archive.versions.iter().find(|v| v.version == *latest))
+            .or_else(|| archive.versions.last())
+            .and_then(|latest| latest.manifest["_npmUser"]["name"].as_str())
+            .map(str::to_string);
+
+        Ok(Some(StoredPackage {
+            archive,
+            publisher,
+            cached,
+            missing_tarballs,
+        }))
+    }
+
+    /// The publisher when they're a user here, otherwise the fallback owner
+    fn owner(
+        publisher: Option<&str>,
+        fallback: Option<&str>,
+        state: &AppState,
+    ) -> Result<Option<i32>, ApiError> {
+        for username in [publisher, fallback].into_iter().flatten() {
+            if let Some(user) = AuthService::get_user_by_username(&state.database, username)? {
+                return Ok(Some(user.id));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(args: &[&str]) -> Result<VerdaccioImportOptions, clap::Error> {
+        VerdaccioImportOptions::try_parse_from(
+            std::iter::once("verdaccio").chain(args.iter().copied()),
+        )
+    }
+
+    #[test]
+    fn test_import_options_parsing() {
+        let options = parse(&[
+            "--storage",
+            "/var/verdaccio/storage",
+            "--htpasswd",
+            "/var/verdaccio/htpasswd",
+            "--include-cached",
+        ])
+        .unwrap();
+        assert_eq!(options.storage, PathBuf::from("/var/verdaccio/storage"));
+        assert_eq!(
+            options.htpasswd,
+            Some(PathBuf::from("/var/verdaccio/htpasswd"))
+        );
+        assert!(options.include_cached);
+
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--storage"]).is_err());
+        assert!(parse(&["--storage", "s", "--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_htpasswd() {
+        let users = VerdaccioImportService::parse_htpasswd(
+            "alice:$2y$10$abcdefghijklmnopqrstuu:autocreated 2024-01-01T00:00:00.000Z\n\
+             # comment\n\
+             bob:{SHA}qUqP5cyxm6YcTAhz05Hph5gvu9M=\n\n",
+        );
+        assert_eq!(
+            users,
+            vec![
+                HtpasswdUser {
+                    username: "alice".to_string(),
+                    password_hash: Some("$2y$10$abcdefghijklmnopqrstuu".to_string()),
+                },
+                HtpasswdUser {
+                    username: "bob".to_string(),
+                    password_hash: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_package() {
+        let storage = tempfile::tempdir().unwrap();
+        let dir = storage.path().join("@acme").join("widget");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("widget-1.0.0.tgz"), b"tarball").unwrap();
+        let document = json!({
+            "name": "@acme/widget",
+            "versions": {
+                "1.0.0": {
+                    "name": "@acme/widget",
+                    "version": "1.0.0",
+                    "license": "MIT",
+                    "_npmUser": { "name": "alice" },
+                    "dist": { "tarball": "http://localhost:4873/@acme/widget/-/widget-1.0.0.tgz" },
+                },
+                "1.1.0": {
+                    "name": "@acme/widget",
+                    "version": "1.1.0",
+                    "dist": { "tarball": "http://localhost:4873/@acme/widget/-/widget-1.1.0.tgz" },
+                },
+            },
+            "dist-tags": { "latest": "1.1.0", "stable": "1.0.0" },
+            "time": { "1.0.0": "2024-01-02T03:04:05.000Z" },
+            "_uplinks": {},
+        });
+        std::fs::write(dir.join("package.json"), document.to_string()).unwrap();
+
+        let dirs = VerdaccioImportService::package_dirs(storage.path()).unwrap();
+        assert_eq!(dirs, vec![dir.clone()]);
+
+        let stored = VerdaccioImportService::read_package(&dir).unwrap().unwrap();
+        assert!(!stored.cached);
+        assert_eq!(stored.publisher.as_deref(), Some("alice"));
+        assert_eq!(stored.missing_tarballs, vec!["@acme/widget@1.1.0"]);
+        assert_eq!(stored.archive.versions.len(), 1);
+        assert_eq!(
+            stored.archive.versions[0].files[0].filename,
+            "widget-1.0.0.tgz"
+        );
+        assert_eq!(stored.archive.license.as_deref(), Some("MIT"));
+        assert_eq!(
+            stored.archive.dist_tags,
+            HashMap::from([("stable".to_string(), "1.0.0".to_string())])
+        );
+
+        assert!(
+            VerdaccioImportService::read_package(storage.path())
+                .unwrap()
+                .is_none()
+        );
+    }
+}
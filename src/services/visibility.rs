@@ -0,0 +1,126 @@
+use crate::error::ApiError;
+use crate::models::organization::OrganizationRole;
+use crate::models::{
+    AuthenticatedUser, NewPackageVisibilityChange, Package, PackageVisibility,
+    PackageVisibilityChange, RegistryEvent,
+};
+use crate::state::AppState;
+use log::{info, warn};
+
+pub struct VisibilityService;
+
+impl VisibilityService {
+    /// Single entry point for switching a package between public and private. Records the
+    /// change, drops cached metadata and notifies event subscribers.
+    pub async fn set_visibility(
+        package: &str,
+        visibility: PackageVisibility,
+        reason: Option<String>,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<PackageVisibilityChange, ApiError> {
+        let pkg = Self::find_package(package, state)?;
+
+        if !Self::can_administer(&pkg, actor, state)? {
+            return Err(ApiError::Forbidden(
+                "You don't have permission to change the visibility of this package".to_string(),
+            ));
+        }
+
+        let current = PackageVisibility::from_visibility_str(&pkg.visibility)
+            .unwrap_or(PackageVisibility::Public);
+        if current == visibility {
+            return Err(ApiError::BadRequest(format!(
+                "Package '{package}' is already {visibility}"
+            )));
+        }
+
+        let change = state
+            .database
+            .set_package_visibility(&NewPackageVisibilityChange {
+                package_id: pkg.id,
+                package_name: pkg.name.clone(),
+                old_visibility: current.to_string(),
+                new_visibility: visibility.to_string(),
+                changed_by: Some(actor.user_id),
+                reason: reason.clone(),
+                created_at: chrono::Utc::now().naive_utc(),
+            })
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        // Listings and search query the packages table directly, so only cached
+        // documents need to go
+        if let Err(e) = state.cache.invalidate_metadata(package).await {
+            warn!("Failed to invalidate metadata cache for package {package}: {e}");
+        }
+
+        state.events.emit(RegistryEvent::PackageVisibilityChanged {
+            package: pkg.name.clone(),
+            from: current.to_string(),
+            to: visibility.to_string(),
+            actor: actor.username.clone(),
+            reason,
+        });
+
+        info!(
+            "User {} changed visibility of {package} from {current} to {visibility}",
+            actor.username
+        );
+
+        Ok(change)
+    }
+
+    /// Visibility change history of a package, newest first
+    pub fn history(
+        package: &str,
+        state: &AppState,
+    ) -> Result<(Package, Vec<PackageVisibilityChange>), ApiError> {
+        let pkg = Self::find_package(package, state)?;
+
+        let history = state
+            .database
+            .get_visibility_changes(pkg.id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        Ok((pkg, history))
+    }
+
+    fn find_package(package: &str, state: &AppState) -> Result<Package, ApiError> {
+        state
+            .database
+            .get_package_by_name(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .ok_or_else(|| ApiError::NotFound(format!("Package '{package}' not found")))
+    }
+
+    /// Registry admins, package admins and organization admins may change the visibility of
+    /// a package or transfer it
+    pub(crate) fn can_administer(
+        pkg: &Package,
+        user: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<bool, ApiError> {
+        if user.is_admin {
+            return Ok(true);
+        }
+
+        let is_package_admin = state
+            .database
+            .get_package_owners(&pkg.name)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .iter()
+            .any(|owner| owner.user_id == user.user_id && owner.permission_level == "admin");
+
+        if is_package_admin {
+            return Ok(true);
+        }
+
+        match pkg.organization_id {
+            Some(org_id) => state
+                .database
+                .check_organization_permission(org_id, user.user_id, OrganizationRole::Admin)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}"))),
+            None => Ok(false),
+        }
+    }
+}
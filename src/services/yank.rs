@@ -0,0 +1,168 @@
+use crate::error::ApiError;
+use crate::models::{AuthenticatedUser, NewAuditLogEntry, PackageVersion, RegistryEvent};
+use crate::state::AppState;
+use crate::versions;
+use log::{info, warn};
+use serde_json::{Value, json};
+
+pub struct YankService;
+
+impl YankService {
+    /// Yanks a published version, or restores it. Anyone who may publish the package can
+    /// yank its versions.
+    pub async fn set_yanked(
+        package: &str,
+        version: &str,
+        yanked: bool,
+        reason: Option<String>,
+        actor: &AuthenticatedUser,
+        state: &AppState,
+    ) -> Result<PackageVersion, ApiError> {
+        let pkg = state
+            .database
+            .get_package_by_name(package)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .filter(|pkg| pkg.author_id.is_some())
+            .ok_or_else(|| ApiError::NotFound(format!("Package '{package}' not found")))?;
+
+        let can_publish = actor.is_admin
+            || state
+                .database
+                .can_publish_package(package, actor.user_id)
+                .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+        if !can_publish {
+            return Err(ApiError::Forbidden(format!(
+                "You don't have permission to yank versions of '{package}'"
+            )));
+        }
+
+        let pkg_version = state
+            .database
+            .get_package_versions(pkg.id)
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?
+            .into_iter()
+            .find(|v| v.version == version)
+            .ok_or_else(|| {
+                ApiError::NotFound(format!("Package '{package}' version '{version}' not found"))
+            })?;
+
+        if pkg_version.yanked_at.is_some() == yanked {
+            let state_name = if yanked { "yanked" } else { "not yanked" };
+            return Err(ApiError::BadRequest(format!(
+                "{package}@{version} is already {state_name}"
+            )));
+        }
+
+        let yanked_at = yanked.then(|| chrono::Utc::now().naive_utc());
+        let reason = reason.filter(|_| yanked);
+        let pkg_version = state
+            .database
+            .set_version_yanked(pkg_version.id, yanked_at, reason.clone())
+            .map_err(|e| ApiError::InternalServerError(format!("Database error: {e}")))?;
+
+        if let Err(e) = state.cache.invalidate_metadata(package).await {
+            warn!("Failed to invalidate metadata cache for package {package}: {e}");
+        }
+
+        let action = if yanked {
+            "package.yank"
+        } else {
+            "package.unyank"
+        };
+        let entry = NewAuditLogEntry {
+            action: action.to_string(),
+            actor_id: Some(actor.user_id),
+            organization_id: pkg.organization_id,
+            package_name: Some(pkg.name.clone()),
+            version: Some(version.to_string()),
+            details: reason.map(|reason| json!({ "reason": reason }).to_string()),
+        };
+        if let Err(e) = state.database.create_audit_log_entry(&entry) {
+            warn!("Failed to write audit log entry {action}: {e}");
+        }
+
+        state.events.emit(RegistryEvent::VersionYanked {
+            package: pkg.name.clone(),
+            version: version.to_string(),
+            yanked,
+            actor: actor.username.clone(),
+        });
+
+        info!(
+            "User {} {} {package}@{version}",
+            actor.username,
+            if yanked { "yanked" } else { "restored" }
+        );
+        Ok(pkg_version)
+    }
+
+    /// Marks the document of a yanked version, so clients installing it by exact version
+    /// show why it was yanked
+    pub fn mark_version_document(document: &mut Value, pkg_version: &PackageVersion) {
+        if pkg_version.yanked_at.is_none() {
+            return;
+        }
+
+        let message = match &pkg_version.yank_reason {
+            Some(reason) => format!("This version has been yanked: {reason}"),
+            None => "This version has been yanked".to_string(),
+        };
+        document["deprecated"] = json!(message);
+        document["yanked"] = json!(true);
+    }
+
+    /// Drops dist-tags that point at versions missing from the package document and moves
+    /// `latest` to the highest remaining stable version, or prerelease when there is none
+    pub fn repair_dist_tags<'a>(
+        dist_tags: &mut std::collections::HashMap<String, String>,
+        versions: impl Iterator<Item = &'a String>,
+    ) {
+        let available: Vec<&String> = versions.collect();
+        dist_tags.retain(|_, version| available.contains(&&*version));
+
+        if dist_tags.contains_key("latest") {
+            return;
+        }
+
+        if let Some(latest) = versions::latest(available.iter().map(|v| v.as_str())) {
+            dist_tags.insert("latest".to_string(), latest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_repair_dist_tags() {
+        let versions: Vec<String> = ["1.0.0", "1.2.0", "2.0.0-rc.1"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        let mut tags = HashMap::from([
+            ("latest".to_string(), "1.3.0".to_string()),
+            ("next".to_string(), "2.0.0-rc.1".to_string()),
+            ("beta".to_string(), "2.0.0-beta.1".to_string()),
+        ]);
+
+        YankService::repair_dist_tags(&mut tags, versions.iter());
+        assert_eq!(
+            tags,
+            HashMap::from([
+                ("latest".to_string(), "1.2.0".to_string()),
+                ("next".to_string(), "2.0.0-rc.1".to_string()),
+            ])
+        );
+
+        let prereleases = vec!["2.0.0-rc.1".to_string()];
+        let mut tags = HashMap::new();
+        YankService::repair_dist_tags(&mut tags, prereleases.iter());
+        assert_eq!(tags["latest"], "2.0.0-rc.1");
+
+        let mut tags = HashMap::from([("latest".to_string(), "1.0.0".to_string())]);
+        YankService::repair_dist_tags(&mut tags, [].iter());
+        assert!(tags.is_empty());
+    }
+}
@@ -1,11 +1,35 @@
 use crate::config::AppConfig;
-use crate::services::{CacheService, DatabaseService};
+use crate::secrets::SecretStore;
+use crate::services::{
+    CacheService, ChangesFeedCursor, DashboardCache, DatabaseService, EventBus, JobScheduler,
+    LiveMetrics, MaintenanceMode, PluginHost, PolicyEngine, RateLimiter, UpstreamLimiter,
+};
 use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AppState {
     pub config: AppConfig,
     pub client: reqwest::Client,
     pub cache: Arc<CacheService>,
     pub database: Arc<DatabaseService>,
+    pub events: Arc<EventBus>,
+    pub maintenance: Arc<MaintenanceMode>,
+    pub secrets: Arc<SecretStore>,
+    pub policy: Arc<PolicyEngine>,
+    pub plugins: Arc<PluginHost>,
+    pub jobs: Arc<JobScheduler>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub dashboard: Arc<DashboardCache>,
+    pub changes_feed: Arc<ChangesFeedCursor>,
+    pub live_metrics: Arc<LiveMetrics>,
+    pub upstream_limiter: Arc<UpstreamLimiter>,
+}
+
+impl AppState {
+    /// Upstream registry URL, with credentials read from a file or Vault as last refreshed
+    pub fn upstream_registry(&self) -> String {
+        self.secrets
+            .get("CLEF_UPSTREAM_REGISTRY")
+            .unwrap_or_else(|| self.config.upstream_registry.clone())
+    }
 }
@@ -0,0 +1,177 @@
+//! Semver ordering, `latest` selection and npm range resolution
+
+use semver::{Version, VersionReq};
+use std::cmp::Ordering;
+
+/// Orders version strings by semver precedence. Invalid versions sort before valid ones,
+/// by their text.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// Sorts `items` by the version of each, highest first
+pub fn sort_descending<T>(items: &mut [T], version: impl Fn(&T) -> &str) {
+    items.sort_by(|a, b| compare(version(b), version(a)));
+}
+
+pub fn is_prerelease(version: &str) -> bool {
+    Version::parse(version).is_ok_and(|version| !version.pre.is_empty())
+}
+
+/// The version `latest` should point at: the highest stable version, or the highest
+/// prerelease when there is no stable one. Invalid versions are ignored.
+pub fn latest<'a>(versions: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let parsed: Vec<Version> = versions
+        .into_iter()
+        .filter_map(|version| Version::parse(version).ok())
+        .collect();
+    parsed
+        .iter()
+        .filter(|version| version.pre.is_empty())
+        .max()
+        .or_else(|| parsed.iter().max())
+        .map(ToString::to_string)
+}
+
+/// Parses an npm range such as `^1.2.0`, `~1.2`, `1.x`, `>=1.0.0 <2.0.0`,
+/// `1.0.0 - 1.4.0` or alternatives joined by `||`. A bare version matches only itself.
+pub fn parse_range(range: &str) -> Option<Vec<VersionReq>> {
+    range
+        .split("||")
+        .map(|alternative| VersionReq::parse(&comparators(alternative.trim())?).ok())
+        .collect()
+}
+
+/// Rewrites the space separated comparators of one npm range alternative into the comma
+/// separated form of the semver crate
+fn comparators(range: &str) -> Option<String> {
+    let tokens: Vec<&str> = range.split_whitespace().collect();
+    if tokens.is_empty() {
+        return Some("*".to_string());
+    }
+    if let [from, "-", to] = tokens[..] {
+        return Some(format!(
+            ">={}, <={}",
+            from.trim_start_matches('v'),
+            to.trim_start_matches('v')
+        ));
+    }
+
+    let mut parts = Vec::new();
+    let mut operator = String::new();
+    for token in tokens {
+        // `>= 1.2.0` puts a space between operator and version
+        if token.chars().all(|c| "<>=~^".contains(c)) {
+            operator.push_str(token);
+            continue;
+        }
+        let version_start = token.find(|c: char| !"<>=~^".contains(c))?;
+        let (op, version) = token.split_at(version_start);
+        operator.push_str(op);
+        let version = version.trim_start_matches('v');
+
+        let op = match operator.as_str() {
+            // Bare versions are exact in npm, caret requirements in the semver crate
+            "" if !matches!(version, "*" | "x" | "X") => "=",
+            op => op,
+        };
+        parts.push(format!("{op}{version}"));
+        operator.clear();
+    }
+    if !operator.is_empty() {
+        return None;
+    }
+    Some(parts.join(", "))
+}
+
+/// Whether `version` satisfies the npm `range`
+pub fn satisfies(version: &str, range: &str) -> bool {
+    match (Version::parse(version), parse_range(range)) {
+        (Ok(version), Some(requirements)) => requirements.iter().any(|req| req.matches(&version)),
+        _ => false,
+    }
+}
+
+/// The highest of `versions` satisfying the npm `range`
+pub fn max_satisfying<'a>(
+    versions: impl IntoIterator<Item = &'a str>,
+    range: &str,
+) -> Option<String> {
+    let requirements = parse_range(range)?;
+    versions
+        .into_iter()
+        .filter_map(|version| Version::parse(version).ok())
+        .filter(|version| requirements.iter().any(|req| req.matches(version)))
+        .max()
+        .map(|version| version.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering() {
+        let mut versions = vec!["1.9.0", "1.10.0", "2.0.0-beta.1", "2.0.0", "bogus", "1.2.0"];
+        sort_descending(&mut versions, |v| v);
+        assert_eq!(
+            versions,
+            vec!["2.0.0", "2.0.0-beta.1", "1.10.0", "1.9.0", "1.2.0", "bogus"]
+        );
+
+        assert_eq!(
+            latest(["1.9.0", "1.10.0", "1.2.0"]).as_deref(),
+            Some("1.10.0")
+        );
+        assert_eq!(
+            latest(["1.0.0", "2.0.0-beta.1", "not-a-version"]).as_deref(),
+            Some("1.0.0")
+        );
+        assert_eq!(
+            latest(["2.0.0-beta.2", "2.0.0-beta.10", "2.0.0-alpha.1"]).as_deref(),
+            Some("2.0.0-beta.10")
+        );
+        assert_eq!(latest(["latest"]), None);
+        assert!(is_prerelease("2.0.0-rc.1"));
+        assert!(!is_prerelease("2.0.0"));
+    }
+
+    #[test]
+    fn test_max_satisfying() {
+        let versions = [
+            "1.1.0",
+            "1.2.0",
+            "1.2.5",
+            "1.10.0",
+            "2.0.0-beta.1",
+            "2.0.0",
+            "2.1.0",
+            "3.0.0-rc.1",
+        ];
+        let resolve = |range| max_satisfying(versions, range);
+
+        assert_eq!(resolve("^1.2.0").as_deref(), Some("1.10.0"));
+        assert_eq!(resolve("~1.2.0").as_deref(), Some("1.2.5"));
+        assert_eq!(resolve("1.2").as_deref(), Some("1.2.5"));
+        assert_eq!(resolve("1.x").as_deref(), Some("1.10.0"));
+        assert_eq!(resolve("1.2.0").as_deref(), Some("1.2.0"));
+        assert_eq!(resolve("v1.2.0").as_deref(), Some("1.2.0"));
+        assert_eq!(resolve(">=1.2.0 <2.0.0").as_deref(), Some("1.10.0"));
+        assert_eq!(resolve(">= 1.2.0 < 1.3").as_deref(), Some("1.2.5"));
+        assert_eq!(resolve("1.1.0 - 1.2").as_deref(), Some("1.2.5"));
+        assert_eq!(resolve("^1.1.0 <1.2.0 || ^2").as_deref(), Some("2.1.0"));
+        assert_eq!(resolve("*").as_deref(), Some("2.1.0"));
+        assert_eq!(resolve("").as_deref(), Some("2.1.0"));
+        // Prereleases only match ranges that name a prerelease of the same version
+        assert_eq!(resolve("^3.0.0-rc.0").as_deref(), Some("3.0.0-rc.1"));
+        assert_eq!(resolve(">2.1.0"), None);
+        assert_eq!(resolve("^4"), None);
+        assert_eq!(resolve(">="), None);
+        assert_eq!(resolve("latest"), None);
+    }
+}
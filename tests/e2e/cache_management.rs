@@ -7,6 +7,24 @@ use std::time::Duration;
 mod tests {
     use super::*;
 
+    /// Polls a queued task until it finished, returning its result
+    fn wait_for_task(client: &ApiClient, id: i64) -> serde_json::Value {
+        for _ in 0..100 {
+            let task: serde_json::Value = client
+                .get(&format!("/api/v1/tasks/{id}"))
+                .send()
+                .unwrap()
+                .json()
+                .unwrap();
+            match task["status"].as_str() {
+                Some("succeeded") => return task["result"].clone(),
+                Some("failed") => panic!("Task {id} failed: {}", task["error"]),
+                _ => thread::sleep(Duration::from_millis(100)),
+            }
+        }
+        panic!("Task {id} didn't finish");
+    }
+
     #[test]
     #[serial]
     fn test_cache_stats_endpoint() {
@@ -58,7 +76,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // First, make some requests to populate cache
         let _ = client.get("/registry/lodash/-/lodash-4.17.21.tgz").send();
@@ -97,7 +115,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -159,7 +177,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -228,7 +246,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -287,7 +305,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -376,7 +394,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -425,7 +443,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -497,7 +515,7 @@ mod tests {
         let server1 =
             TestServer::with_shared_paths(shared_cache_dir.clone(), shared_db_path.clone());
         let handle1 = server1.start();
-        let client = ApiClient::new(server1.base_url.clone());
+        let client = ApiClient::new_admin(server1.base_url.clone());
 
         // Clear cache and make some requests to generate stats
         let _ = client.delete("/api/v1/cache").send();
@@ -552,7 +570,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -606,7 +624,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache to start fresh
         let _ = client.delete("/api/v1/cache").send();
@@ -651,7 +669,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -707,7 +725,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -759,7 +777,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -809,7 +827,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -821,16 +839,13 @@ mod tests {
         let _ = client.get("/registry/express").send();
         thread::sleep(Duration::from_millis(300));
 
-        // Test cache reprocess endpoint
-        let response = client
-            .client
-            .post(format!("{}/api/v1/cache/reprocess", server.base_url))
-            .send()
-            .unwrap();
+        // Test cache reprocess endpoint, which queues a task
+        let response = client.post("/api/v1/cache/reprocess").send().unwrap();
 
-        assert!(response.status().is_success());
+        assert_eq!(response.status().as_u16(), 202);
 
-        let result: serde_json::Value = response.json().unwrap();
+        let task: serde_json::Value = response.json().unwrap();
+        let result = wait_for_task(&client, task["id"].as_i64().unwrap());
         assert!(result["message"].as_str().unwrap().contains("completed"));
         assert!(result["processed_files"].is_number());
 
@@ -867,7 +882,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -915,7 +930,7 @@ mod tests {
         init_test_env();
         let server = TestServer::new();
         let _handle = server.start();
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -971,14 +986,11 @@ mod tests {
         );
 
         // Test reprocess with scoped packages
-        let reprocess_response = client
-            .client
-            .post(format!("{}/api/v1/cache/reprocess", server.base_url))
-            .send()
-            .unwrap();
+        let reprocess_response = client.post("/api/v1/cache/reprocess").send().unwrap();
 
         assert!(reprocess_response.status().is_success());
-        let reprocess_result: serde_json::Value = reprocess_response.json().unwrap();
+        let task: serde_json::Value = reprocess_response.json().unwrap();
+        let reprocess_result = wait_for_task(&client, task["id"].as_i64().unwrap());
         let processed_files = reprocess_result["processed_files"].as_u64().unwrap_or(0);
 
         println!(
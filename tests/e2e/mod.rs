@@ -38,6 +38,10 @@ fn ensure_binary_built() -> PathBuf {
         .clone()
 }
 
+/// Bootstrap admin account created by every test server
+pub const TEST_ADMIN_USERNAME: &str = "clef-admin";
+pub const TEST_ADMIN_PASSWORD: &str = "admin-password";
+
 /// Test server configuration
 pub struct TestServer {
     pub port: u16,
@@ -90,6 +94,8 @@ impl TestServer {
             .env("CLEF_UPSTREAM_REGISTRY", "https://registry.npmjs.org") // Add upstream registry
             .env("CLEF_CACHE_ENABLED", "true")
             .env("CLEF_CACHE_TTL_HOURS", "24")
+            .env("CLEF_ADMIN_USERNAME", TEST_ADMIN_USERNAME)
+            .env("CLEF_ADMIN_PASSWORD", TEST_ADMIN_PASSWORD)
             .env("RUST_LOG", "-") // Enable info logging to see our custom logs
             .stdout(Stdio::inherit()) // Show stdout for debugging
             .stderr(Stdio::inherit()); // Show stderr for debugging
@@ -495,6 +501,27 @@ impl ApiClient {
         }
     }
 
+    /// Create a client authenticated as the bootstrap admin configured by `TestServer`
+    pub fn new_admin(base_url: String) -> Self {
+        let mut client = Self::new(base_url);
+
+        let response = client
+            .post("/api/v1/login")
+            .json(&serde_json::json!({
+                "name": TEST_ADMIN_USERNAME,
+                "password": TEST_ADMIN_PASSWORD
+            }))
+            .send()
+            .expect("Failed to log in as admin");
+        let body: serde_json::Value = response.json().expect("Invalid admin login response");
+        let token = body["token"]
+            .as_str()
+            .expect("Admin login returned no token");
+
+        client.set_auth_token(token.to_string());
+        client
+    }
+
     pub fn set_auth_token(&mut self, token: String) {
         self.auth_token = Some(token);
     }
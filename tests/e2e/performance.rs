@@ -15,7 +15,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Give the server extra time to fully initialize
         thread::sleep(Duration::from_millis(1000));
@@ -255,7 +255,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -325,7 +325,7 @@ mod tests {
         let server = TestServer::new();
         let _handle = server.start();
 
-        let client = ApiClient::new(server.base_url.clone());
+        let client = ApiClient::new_admin(server.base_url.clone());
 
         // Clear cache first
         let _ = client.delete("/api/v1/cache").send();
@@ -509,6 +509,106 @@ mod tests {
         }
     }
 
+    #[test]
+    #[serial]
+    fn test_package_publish_two_tarballs() {
+        init_test_env();
+        let server = TestServer::new();
+        let _handle = server.start();
+
+        let mut client = ApiClient::new(server.base_url.clone());
+
+        // Setup authenticated user
+        if let Some(token) = setup_authenticated_user(&client) {
+            client.set_auth_token(token);
+
+            let first = b"first tarball content".to_vec();
+            let second = b"second tarball content".to_vec();
+            let attachment = |data: &[u8]| {
+                json!({
+                    "content_type": "application/octet-stream",
+                    "data": BASE64_STANDARD.encode(data),
+                    "length": data.len()
+                })
+            };
+            let version = |version: &str| {
+                json!({
+                    "name": "two-tarballs-package",
+                    "version": version,
+                    "license": "MIT",
+                    "dist": {
+                        "tarball": format!("{}/registry/two-tarballs-package/-/two-tarballs-package-{version}.tgz", server.base_url),
+                        "shasum": "dummy-shasum"
+                    }
+                })
+            };
+
+            // Each tarball is stored as the version whose dist.tarball names it
+            let publish_request = json!({
+                "_id": "two-tarballs-package",
+                "name": "two-tarballs-package",
+                "versions": {
+                    "1.1.0": version("1.1.0"),
+                    "1.0.0": version("1.0.0")
+                },
+                "_attachments": {
+                    "two-tarballs-package-1.1.0.tgz": attachment(&second),
+                    "two-tarballs-package-1.0.0.tgz": attachment(&first)
+                }
+            });
+
+            let response = client
+                .put("/registry/two-tarballs-package")
+                .json(&publish_request)
+                .send()
+                .unwrap();
+            let status = response.status();
+            assert!(
+                status.is_success(),
+                "Publishing two tarballs failed with status: {status} - {}",
+                response.text().unwrap_or_default()
+            );
+
+            for (version, expected) in [("1.0.0", &first), ("1.1.0", &second)] {
+                let response = client
+                    .get(&format!(
+                        "/registry/two-tarballs-package/-/two-tarballs-package-{version}.tgz"
+                    ))
+                    .send()
+                    .unwrap();
+                assert!(response.status().is_success());
+                assert_eq!(response.bytes().unwrap().to_vec(), *expected);
+            }
+
+            let response = client.get("/registry/two-tarballs-package").send().unwrap();
+            let metadata: serde_json::Value = response.json().unwrap();
+            assert_eq!(metadata["dist-tags"]["latest"], "1.1.0");
+
+            // A tarball no version names would overwrite another one, it's refused
+            let publish_request = json!({
+                "_id": "two-tarballs-package",
+                "name": "two-tarballs-package",
+                "versions": { "2.0.0": version("2.0.0") },
+                "_attachments": {
+                    "two-tarballs-package-2.0.0.tgz": attachment(&first),
+                    "two-tarballs-package-2.0.0-extra.tgz": attachment(&second)
+                }
+            });
+            let response = client
+                .put("/registry/two-tarballs-package")
+                .json(&publish_request)
+                .send()
+                .unwrap();
+            assert_eq!(response.status(), 400);
+            assert!(
+                response
+                    .text()
+                    .unwrap_or_default()
+                    .contains("two-tarballs-package-2.0.0-extra.tgz")
+            );
+        }
+    }
+
     #[test]
     #[serial]
     fn test_package_ownership_verification() {
@@ -1,6 +1,14 @@
+use base64::prelude::*;
+use clef::models::{RegisterRequest, UserRole};
+use clef::secrets::SecretStore;
+use clef::services::{
+    AuthService, ChangesFeedCursor, DashboardCache, EventBus, JobScheduler, LiveMetrics,
+    MaintenanceMode, PluginHost, PolicyEngine, RateLimiter, UpstreamLimiter,
+};
 use clef::{AppConfig, AppState, CacheService, DatabaseService};
 use rocket::Config;
-use rocket::http::Status;
+use rocket::data::{Limits, ToByteUnit};
+use rocket::http::{ContentType, Header, Method, Status};
 use rocket::local::blocking::Client;
 use rocket_cors::{AllowedOrigins, CorsOptions};
 use serial_test::serial;
@@ -55,6 +63,17 @@ fn create_test_rocket() -> TestRocket {
         client,
         cache,
         database,
+        events: Arc::new(EventBus::new()),
+        maintenance: Arc::new(MaintenanceMode::default()),
+        secrets: Arc::new(SecretStore::default()),
+        policy: Arc::new(PolicyEngine::default()),
+        plugins: Arc::new(PluginHost::default()),
+        jobs: Arc::new(JobScheduler::default()),
+        rate_limiter: Arc::new(RateLimiter::default()),
+        dashboard: Arc::new(DashboardCache::default()),
+        changes_feed: Arc::new(ChangesFeedCursor::default()),
+        live_metrics: Arc::new(LiveMetrics::default()),
+        upstream_limiter: Arc::new(UpstreamLimiter::default()),
     };
 
     // Configure CORS
@@ -67,6 +86,7 @@ fn create_test_rocket() -> TestRocket {
     let rocket_config = Config {
         port: state.config.port,
         address: state.config.host.parse().expect("Invalid host address"),
+        limits: Limits::default().limit("json", state.config.json_body_limit().bytes()),
         ..Config::default()
     };
 
@@ -74,7 +94,8 @@ fn create_test_rocket() -> TestRocket {
         .manage(state)
         .attach(cors)
         .attach(clef::RequestLogger)
-        .mount("/", clef::routes::get_routes());
+        .mount("/", clef::routes::get_routes())
+        .register("/", clef::routes::get_catchers());
 
     TestRocket {
         rocket,
@@ -82,6 +103,57 @@ fn create_test_rocket() -> TestRocket {
     }
 }
 
+/// Registers a user and returns a bearer token for it
+fn create_user(rocket: &rocket::Rocket<rocket::Build>, username: &str, role: UserRole) -> String {
+    let state = rocket.state::<AppState>().expect("app state");
+    AuthService::register_user(
+        &state.database,
+        RegisterRequest {
+            name: username.to_string(),
+            email: format!("{username}@example.com"),
+            password: "correct horse battery".to_string(),
+            invite_token: None,
+        },
+    )
+    .expect("registered user");
+    AuthService::set_user_role(&state.database, username, role).expect("user role");
+    let token = AuthService::create_token(
+        &state.database,
+        &state.secrets.token_keys(&state.config),
+        username,
+        false,
+    )
+    .expect("token");
+    format!("Bearer {token}")
+}
+
+/// An npm publish request for one version with a tarball holding `content`
+fn publish_request(name: &str, version: &str, content: &[u8]) -> serde_json::Value {
+    let filename = format!("{name}-{version}.tgz");
+    serde_json::json!({
+        "_id": name,
+        "name": name,
+        "dist-tags": { "latest": version },
+        "versions": {
+            version: {
+                "name": name,
+                "version": version,
+                "dist": {
+                    "tarball": format!("http://localhost/registry/{name}/-/{filename}"),
+                    "shasum": "dummy-shasum"
+                }
+            }
+        },
+        "_attachments": {
+            filename: {
+                "content_type": "application/octet-stream",
+                "data": BASE64_STANDARD.encode(content),
+                "length": content.len()
+            }
+        }
+    })
+}
+
 #[test]
 #[serial]
 fn test_health_check() {
@@ -95,6 +167,67 @@ fn test_health_check() {
     assert_eq!(json["status"], "ok");
 }
 
+#[test]
+#[serial]
+fn test_openapi_document() {
+    let test_rocket = create_test_rocket();
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let response = client.get("/api/v1/openapi.json").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().expect("Response body");
+    let json: serde_json::Value = serde_json::from_str(&body).expect("Valid JSON");
+    assert!(json["openapi"].as_str().unwrap().starts_with("3."));
+
+    // Every management route is documented
+    for route in clef::routes::get_routes() {
+        let path = route.uri.path().to_string();
+        if !path.starts_with("/api/v1/") || path == "/api/v1/openapi.json" {
+            continue;
+        }
+        let path = path.replace('<', "{").replace('>', "}");
+        let method = route.method.as_str().to_lowercase();
+        assert!(
+            json["paths"][&path][&method].is_object(),
+            "{method} {path} is missing from the OpenAPI document"
+        );
+    }
+}
+
+#[test]
+#[serial]
+fn test_private_registry_requires_auth() {
+    unsafe {
+        env::set_var("CLEF_REQUIRE_AUTH", "true");
+    }
+    let test_rocket = create_test_rocket();
+    unsafe {
+        env::remove_var("CLEF_REQUIRE_AUTH");
+    }
+
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let response = client.get("/registry/lodash").dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert!(response.headers().get_one("WWW-Authenticate").is_some());
+    let body = response.into_string().expect("valid response body");
+    let json: serde_json::Value = serde_json::from_str(&body).expect("Valid JSON");
+    assert_eq!(json["error"], "authentication required");
+
+    let response = client
+        .get("/registry/lodash")
+        .header(rocket::http::Header::new(
+            "Authorization",
+            "Bearer invalid-token",
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    // The rest of the API stays reachable so clients can log in
+    let response = client.get("/api/v1/health").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
 #[test]
 #[serial]
 fn test_package_metadata_success() {
@@ -246,6 +379,150 @@ fn test_cache_stats() {
     assert!(body.contains("\"cache_dir\":"));
 }
 
+#[test]
+#[serial]
+fn test_dashboard() {
+    let test_rocket = create_test_rocket();
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let response = client.get("/api/v1/dashboard").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: serde_json::Value = response.into_json().expect("valid response body");
+    assert_eq!(body["totals"]["packages"], 0);
+    assert_eq!(body["cache"]["enabled"], true);
+    assert!(body["recent_publishes"].as_array().unwrap().is_empty());
+    assert!(body["upstream"]["reachable"].is_boolean());
+}
+
+#[test]
+#[serial]
+fn test_live_metrics_falls_back_to_events() {
+    let test_rocket = create_test_rocket();
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let response = client.get("/api/v1/ws").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::EventStream));
+}
+
+#[test]
+#[serial]
+fn test_package_cache_analytics() {
+    let test_rocket = create_test_rocket();
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let response = client
+        .get("/api/v1/cache/analytics?package=left-pad&period=7d")
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let body: serde_json::Value = response.into_json().expect("valid response body");
+    assert_eq!(body["period"], "7d");
+    assert_eq!(body["packages"][0]["package"], "left-pad");
+    assert_eq!(body["packages"][0]["hits"], 0);
+
+    let response = client
+        .get("/api/v1/cache/analytics?package=left-pad&period=7x")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.get("/api/v1/cache/analytics").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+#[serial]
+fn test_package_metadata_head_matches_get() {
+    let test_rocket = create_test_rocket();
+    let state = test_rocket.rocket.state::<AppState>().expect("app state");
+    let cache_path = state.cache.get_metadata_cache_path("left-pad");
+    std::fs::create_dir_all(cache_path.parent().unwrap()).expect("cache directory");
+    let document = serde_json::json!({
+        "name": "left-pad",
+        "dist-tags": { "latest": "1.3.0" },
+        "versions": { "1.3.0": { "name": "left-pad", "version": "1.3.0" } },
+        "time": { "modified": "2024-01-02T03:04:05.000Z" }
+    });
+    std::fs::write(&cache_path, document.to_string()).expect("cached metadata");
+
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let get = client.get("/registry/left-pad").dispatch();
+    assert_eq!(get.status(), Status::Ok);
+    let head = client.head("/registry/left-pad").dispatch();
+    assert_eq!(head.status(), Status::Ok);
+
+    // Content-Length is only added when the response is written out, from the body size that
+    // Rocket keeps when stripping the body of HEAD responses
+    for header in ["Content-Type", "ETag", "Last-Modified"] {
+        assert!(get.headers().get_one(header).is_some(), "{header}");
+        assert_eq!(
+            head.headers().get_one(header),
+            get.headers().get_one(header),
+            "{header}"
+        );
+    }
+    assert_eq!(
+        get.headers().get_one("Last-Modified"),
+        Some("Tue, 02 Jan 2024 03:04:05 GMT")
+    );
+    assert_eq!(head.into_bytes().unwrap_or_default().len(), 0);
+}
+
+#[test]
+#[serial]
+fn test_tarball_if_modified_since() {
+    let test_rocket = create_test_rocket();
+    let state = test_rocket.rocket.state::<AppState>().expect("app state");
+    let last_modified = "Mon, 01 Apr 2024 12:00:00 GMT";
+    let tarball_path = state.cache.get_cache_path("left-pad", "left-pad-1.3.0.tgz");
+    std::fs::create_dir_all(tarball_path.parent().unwrap()).expect("cache directory");
+    std::fs::write(&tarball_path, b"tarball").expect("cached tarball");
+    std::fs::write(
+        state
+            .cache
+            .get_metadata_path("left-pad", "left-pad-1.3.0.tgz"),
+        format!("\"abc\"\n{last_modified}"),
+    )
+    .expect("cached tarball metadata");
+
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let tarball = "/registry/left-pad/-/left-pad-1.3.0.tgz";
+
+    let response = client.get(tarball).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Last-Modified"),
+        Some(last_modified)
+    );
+    assert_eq!(response.into_bytes().unwrap_or_default(), b"tarball");
+
+    let response = client
+        .get(tarball)
+        .header(Header::new("If-Modified-Since", last_modified))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotModified);
+    assert_eq!(
+        response.headers().get_one("Last-Modified"),
+        Some(last_modified)
+    );
+    assert!(response.into_bytes().unwrap_or_default().is_empty());
+
+    let response = client
+        .head(tarball)
+        .header(Header::new("If-Modified-Since", last_modified))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotModified);
+
+    let response = client
+        .get(tarball)
+        .header(Header::new(
+            "If-Modified-Since",
+            "Sun, 31 Mar 2024 12:00:00 GMT",
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
 #[test]
 #[serial]
 fn test_cache_health() {
@@ -260,6 +537,64 @@ fn test_cache_health() {
     assert!(body.contains("\"status\":"));
 }
 
+#[test]
+#[serial]
+fn test_admin_endpoints_require_admin_role() {
+    let test_rocket = create_test_rocket();
+    let alice = create_user(&test_rocket.rocket, "alice", UserRole::User);
+    let admin = create_user(&test_rocket.rocket, "root", UserRole::Admin);
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let request = |method: Method, path: &str, token: Option<&str>| {
+        let mut request = client.req(method, path.to_string());
+        if let Some(token) = token {
+            request.add_header(Header::new("Authorization", token.to_string()));
+        }
+        request.dispatch().status()
+    };
+
+    let endpoints = [
+        (Method::Get, "/api/v1/admin/users"),
+        (Method::Get, "/api/v1/admin/config"),
+        (Method::Put, "/api/v1/admin/users/alice/role"),
+        (Method::Delete, "/api/v1/admin/users/alice"),
+        (Method::Delete, "/api/v1/cache"),
+        (Method::Get, "/api/v1/cache/entries"),
+        (Method::Delete, "/api/v1/cache/packages/left-pad"),
+        (Method::Post, "/api/v1/cache/reprocess"),
+        (Method::Post, "/api/v1/cache/gc"),
+    ];
+    for (method, path) in endpoints {
+        assert_eq!(
+            request(method, path, None),
+            Status::Unauthorized,
+            "{method} {path}"
+        );
+        assert_eq!(
+            request(method, path, Some(&alice)),
+            Status::Forbidden,
+            "{method} {path}"
+        );
+    }
+
+    assert_eq!(
+        request(Method::Get, "/api/v1/admin/users", Some(&admin)),
+        Status::Ok
+    );
+    assert_eq!(
+        request(Method::Get, "/api/v1/cache/entries", Some(&admin)),
+        Status::Ok
+    );
+
+    // The refused requests changed nothing
+    let state = client.rocket().state::<AppState>().expect("app state");
+    let user = state
+        .database
+        .get_user_by_username("alice")
+        .expect("database")
+        .expect("alice");
+    assert!(!user.is_admin());
+}
+
 #[test]
 #[serial]
 fn test_static_files_index() {
@@ -375,3 +710,178 @@ fn test_static_files_head_requests() {
         panic!("No JS file found in assets");
     }
 }
+
+#[test]
+#[serial]
+fn test_staged_publish_promote_and_discard() {
+    unsafe {
+        env::set_var("CLEF_STAGED_PUBLISH_APPROVAL", "true");
+    }
+    let test_rocket = create_test_rocket();
+    unsafe {
+        env::remove_var("CLEF_STAGED_PUBLISH_APPROVAL");
+    }
+    let alice = create_user(&test_rocket.rocket, "alice", UserRole::User);
+    let admin = create_user(&test_rocket.rocket, "root", UserRole::Admin);
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let state = client.rocket().state::<AppState>().expect("app state");
+    let put = |path: &str, body: &serde_json::Value| {
+        client
+            .put(path.to_string())
+            .header(Header::new("Authorization", alice.clone()))
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch()
+    };
+    let stage = |version: &str, content: &[u8]| {
+        let response = put(
+            "/registry/staged-pkg?staged=true",
+            &publish_request("staged-pkg", version, content),
+        );
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().expect("valid response body");
+        body["staged_id"].as_i64().expect("staged id")
+    };
+    let staged_ids = || {
+        let response = client
+            .get("/api/v1/packages/staged-pkg/staged")
+            .header(Header::new("Authorization", alice.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().expect("valid response body");
+        body["staged"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|staged| staged["id"].as_i64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let published = |version: &str| {
+        let response = client.get("/registry/staged-pkg").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().expect("valid response body");
+        body["versions"].get(version).is_some()
+    };
+    let tarball = |version: &str| {
+        client
+            .get(format!("/registry/staged-pkg/-/staged-pkg-{version}.tgz"))
+            .dispatch()
+    };
+
+    // Alice owns the package once its first version is published
+    let response = put(
+        "/registry/staged-pkg",
+        &publish_request("staged-pkg", "1.0.0", b"first"),
+    );
+    assert_eq!(response.status(), Status::Ok);
+
+    // Staged versions aren't published until promoted, and not by whoever staged them
+    let id = stage("1.1.0", b"second");
+    assert_eq!(staged_ids(), vec![id]);
+    assert!(!published("1.1.0"));
+    let promote = format!("/api/v1/packages/staged-pkg/staged/{id}/promote");
+    let response = client
+        .post(promote.clone())
+        .header(Header::new("Authorization", alice.clone()))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(staged_ids(), vec![id]);
+
+    let response = client
+        .post(promote)
+        .header(Header::new("Authorization", admin.clone()))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(staged_ids().is_empty());
+    let response = tarball("1.1.0");
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_bytes().unwrap(), b"second");
+
+    // A promote that fails to store keeps the staged publish and its request
+    let id = stage("1.2.0", b"third");
+    let (staged, _) = state
+        .database
+        .get_staged_publish(id as i32)
+        .unwrap()
+        .unwrap();
+    let mut request: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&staged.request_path).unwrap()).unwrap();
+    request["_attachments"]["staged-pkg-1.2.0.tgz"]["data"] = "not base64!".into();
+    std::fs::write(&staged.request_path, request.to_string()).unwrap();
+    let response = client
+        .post(format!("/api/v1/packages/staged-pkg/staged/{id}/promote"))
+        .header(Header::new("Authorization", admin.clone()))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert_eq!(staged_ids(), vec![id]);
+    assert!(std::path::Path::new(&staged.request_path).exists());
+    assert!(!published("1.2.0"));
+
+    // Discarding drops the staged publish and its request
+    let response = client
+        .delete(format!("/api/v1/packages/staged-pkg/staged/{id}"))
+        .header(Header::new("Authorization", alice.clone()))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(staged_ids().is_empty());
+    assert!(!std::path::Path::new(&staged.request_path).exists());
+    assert!(!published("1.2.0"));
+}
+
+#[test]
+#[serial]
+fn test_large_publish_is_spooled() {
+    unsafe {
+        env::set_var(
+            "CLEF_MAX_PUBLISH_BODY_BYTES",
+            (16 * 1024 * 1024).to_string(),
+        );
+    }
+    let test_rocket = create_test_rocket();
+    unsafe {
+        env::remove_var("CLEF_MAX_PUBLISH_BODY_BYTES");
+    }
+    let alice = create_user(&test_rocket.rocket, "alice", UserRole::User);
+    let client = Client::tracked(test_rocket.rocket).expect("valid rocket instance");
+    let state = client.rocket().state::<AppState>().expect("app state");
+    let spool_dir = std::path::Path::new(&state.config.cache_dir).join("tmp");
+    let spooled = || {
+        std::fs::read_dir(&spool_dir)
+            .map(|entries| entries.count())
+            .unwrap_or(0)
+    };
+    let put = |body: String| {
+        client
+            .put("/registry/big-pkg")
+            .header(Header::new("Authorization", alice.clone()))
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch()
+            .status()
+    };
+
+    // Well over the JSON limit of other endpoints
+    let content: Vec<u8> = (0..6 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let request = publish_request("big-pkg", "1.0.0", &content);
+    assert_eq!(put(request.to_string()), Status::Ok);
+    assert_eq!(spooled(), 0);
+
+    let response = client
+        .get("/registry/big-pkg/-/big-pkg-1.0.0.tgz")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_bytes().expect("tarball"), content);
+
+    // The spooled request is removed when the tarball is invalid, the request can't be
+    // parsed or it is over the body limit
+    let mut invalid = publish_request("big-pkg", "1.1.0", &content);
+    invalid["_attachments"]["big-pkg-1.1.0.tgz"]["data"] = "not base64!".into();
+    assert_eq!(put(invalid.to_string()), Status::BadRequest);
+    assert_eq!(spooled(), 0);
+    let truncated = request.to_string()[..4 * 1024 * 1024].to_string();
+    assert_eq!(put(truncated), Status::BadRequest);
+    assert_eq!(spooled(), 0);
+    let oversized = publish_request("big-pkg", "2.0.0", &[content.clone(), content].concat());
+    assert_eq!(put(oversized.to_string()), Status::PayloadTooLarge);
+    assert_eq!(spooled(), 0);
+}
@@ -2,7 +2,7 @@ import { useQuery, useQueryClient } from "@tanstack/react-query";
 import { useEffect } from "react";
 import { api } from "@/api/client";
 import { useAnalyticsStore } from "@/stores/analytics";
-import type { AnalyticsApiResponse } from "@/types/analytics";
+import type { DashboardSummary } from "@/types/analytics";
 
 // Query keys
 export const analyticsKeys = {
@@ -10,9 +10,9 @@ export const analyticsKeys = {
   data: ["analytics", "data"] as const,
 };
 
-// API function
-const fetchAnalytics = async (): Promise<AnalyticsApiResponse> => {
-  return await api.get<AnalyticsApiResponse>("/api/v1/analytics");
+// API function, the summary is cached by the server for 30 seconds
+const fetchDashboard = async (): Promise<DashboardSummary> => {
+  return await api.get<DashboardSummary>("/api/v1/dashboard");
 };
 
 // Custom hooks
@@ -21,8 +21,8 @@ export const useAnalytics = () => {
 
   const query = useQuery({
     queryKey: analyticsKeys.data,
-    queryFn: fetchAnalytics,
-    staleTime: 5 * 60 * 1000, // 5 minutes
+    queryFn: fetchDashboard,
+    staleTime: 30 * 1000, // 30 seconds
     gcTime: 10 * 60 * 1000, // 10 minutes
     retry: 3,
     retryDelay: (attemptIndex) => Math.min(1000 * 2 ** attemptIndex, 30000),
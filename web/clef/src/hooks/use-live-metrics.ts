@@ -0,0 +1,44 @@
+import { useEffect, useState } from "react";
+import { api } from "@/api/client";
+import type { LiveMetricsSample } from "@/types/analytics";
+
+// One sample arrives per second, keep the last two minutes
+const MAX_SAMPLES = 120;
+
+// Streams live metrics over a WebSocket, falling back to server-sent events when the
+// WebSocket can't be opened
+export const useLiveMetrics = () => {
+  const [samples, setSamples] = useState<LiveMetricsSample[]>([]);
+
+  useEffect(() => {
+    const url = api.getFullUrl("/api/v1/ws");
+    const push = (data: string) => {
+      const sample = JSON.parse(data) as LiveMetricsSample;
+      setSamples((previous) => [...previous.slice(-(MAX_SAMPLES - 1)), sample]);
+    };
+
+    let events: EventSource | null = null;
+    let opened = false;
+    const socket = new WebSocket(url.replace(/^http/, "ws"));
+    socket.onopen = () => {
+      opened = true;
+    };
+    socket.onmessage = (message) => push(message.data);
+    socket.onerror = () => {
+      if (!opened && !events) {
+        events = new EventSource(url);
+        events.onmessage = (message) => push(message.data);
+      }
+    };
+
+    return () => {
+      socket.close();
+      events?.close();
+    };
+  }, []);
+
+  return {
+    samples,
+    latest: samples.length > 0 ? samples[samples.length - 1] : null,
+  };
+};
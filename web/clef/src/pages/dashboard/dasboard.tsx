@@ -1,13 +1,28 @@
-import { Database, HardDrive, Package, TrendingUp } from "lucide-react";
+import { Activity, Database, HardDrive, Package, TrendingUp } from "lucide-react";
 import { Link } from "react-router";
 import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
 import { Skeleton } from "@/components/ui/skeleton";
 import { useAnalytics } from "@/hooks/analytics";
+import { useLiveMetrics } from "@/hooks/use-live-metrics";
 import { formatBytes, formatNumber, roundNumber } from "@/lib/utils";
 
+// Requests per second of the last samples as a line
+function RequestRateChart({ values }: { values: number[] }) {
+  const max = Math.max(1, ...values);
+  const step = values.length > 1 ? 100 / (values.length - 1) : 100;
+  const points = values.map((value, i) => `${i * step},${30 - (value / max) * 30}`).join(" ");
+
+  return (
+    <svg viewBox="0 0 100 30" preserveAspectRatio="none" className="h-16 w-full text-primary">
+      <polyline points={points} fill="none" stroke="currentColor" strokeWidth="1" vectorEffect="non-scaling-stroke" />
+    </svg>
+  );
+}
+
 export function Dashboard() {
   const { data, isPending: isLoading, error } = useAnalytics();
+  const { samples, latest } = useLiveMetrics();
 
   if (error) {
     return (
@@ -31,8 +46,34 @@ export function Dashboard() {
           <h1 className="font-bold text-2xl tracking-tight sm:text-3xl">Dashboard</h1>
           <p className="text-muted-foreground text-sm sm:text-base">Package registry analytics overview</p>
         </div>
+        {data ? (
+          <p className="shrink-0 text-muted-foreground text-xs">
+            Upstream{" "}
+            {data.upstream.reachable
+              ? `reachable (${data.upstream.latency_ms} ms)`
+              : `unreachable${data.upstream.error ? `: ${data.upstream.error}` : ""}`}
+          </p>
+        ) : null}
       </div>
 
+      {/* Live Metrics */}
+      <Card>
+        <CardHeader className="flex flex-row items-center justify-between space-y-0 pb-2">
+          <CardTitle className="font-medium text-sm">Live</CardTitle>
+          <Activity className="h-4 w-4 text-muted-foreground" />
+        </CardHeader>
+        <CardContent className="space-y-2">
+          <RequestRateChart values={samples.map((sample) => sample.requests_per_sec)} />
+          <p className="text-muted-foreground text-xs">
+            {latest
+              ? `${roundNumber(latest.requests_per_sec, 1)} requests/s • ${
+                  latest.cache_hit_rate === null ? "no cache lookups" : `${roundNumber(latest.cache_hit_rate * 100, 1)}% cache hits`
+                } • ${formatNumber(latest.active_downloads)} active downloads`
+              : "Waiting for live metrics..."}
+          </p>
+        </CardContent>
+      </Card>
+
       {/* Overview Cards */}
       <div className="grid gap-4 sm:grid-cols-2 lg:grid-cols-4">
         {isLoading ? (
@@ -56,8 +97,11 @@ export function Dashboard() {
                 <Package className="h-4 w-4 text-muted-foreground" />
               </CardHeader>
               <CardContent>
-                <div className="font-bold text-xl sm:text-2xl">{formatNumber(data.total_packages)}</div>
-                <p className="text-muted-foreground text-xs">Packages in registry</p>
+                <div className="font-bold text-xl sm:text-2xl">{formatNumber(data.totals.packages)}</div>
+                <p className="text-muted-foreground text-xs">
+                  {formatNumber(data.totals.versions)} versions • {formatNumber(data.totals.users)} users •{" "}
+                  {formatNumber(data.totals.organizations)} orgs
+                </p>
               </CardContent>
             </Card>
 
@@ -67,7 +111,7 @@ export function Dashboard() {
                 <HardDrive className="h-4 w-4 text-muted-foreground" />
               </CardHeader>
               <CardContent>
-                <div className="font-bold text-xl sm:text-2xl">{data.total_size_mb.toFixed(1)} MB</div>
+                <div className="font-bold text-xl sm:text-2xl">{formatBytes(data.cache.size_bytes)}</div>
                 <p className="text-muted-foreground text-xs">Cached packages size</p>
               </CardContent>
             </Card>
@@ -78,7 +122,7 @@ export function Dashboard() {
                 <TrendingUp className="h-4 w-4 text-muted-foreground" />
               </CardHeader>
               <CardContent>
-                <div className="font-bold text-xl sm:text-2xl">{roundNumber(data.cache_hit_rate, 2)}%</div>
+                <div className="font-bold text-xl sm:text-2xl">{roundNumber(data.cache.hit_rate, 2)}%</div>
                 <p className="text-muted-foreground text-xs">Cache efficiency</p>
               </CardContent>
             </Card>
@@ -89,9 +133,9 @@ export function Dashboard() {
                 <Database className="h-4 w-4 text-muted-foreground" />
               </CardHeader>
               <CardContent>
-                <div className="font-bold text-xl sm:text-2xl">{data.metadata_cache_size_mb.toFixed(1)} MB</div>
+                <div className="font-bold text-xl sm:text-2xl">{formatBytes(data.cache.metadata_size_bytes)}</div>
                 <p className="text-muted-foreground text-xs">
-                  {formatNumber(data.metadata_cache_entries)} metadata files
+                  {formatNumber(data.cache.metadata_entries)} metadata files
                 </p>
               </CardContent>
             </Card>
@@ -121,9 +165,9 @@ export function Dashboard() {
                   </div>
                 ))}
               </div>
-            ) : data?.most_popular_packages && data.most_popular_packages.length > 0 ? (
+            ) : data?.top_packages && data.top_packages.length > 0 ? (
               <div className="space-y-1">
-                {data.most_popular_packages.map((pkg, index) => (
+                {data.top_packages.map((pkg, index) => (
                   <Link
                     key={pkg.name}
                     to={`/packages/${pkg.name}`}
@@ -158,8 +202,8 @@ export function Dashboard() {
 
         <Card>
           <CardHeader>
-            <CardTitle>Recent Packages</CardTitle>
-            <CardDescription>Latest packages added to registry</CardDescription>
+            <CardTitle>Recent Publishes</CardTitle>
+            <CardDescription>Latest versions published to the registry</CardDescription>
           </CardHeader>
           <CardContent>
             {isLoading ? (
@@ -174,24 +218,26 @@ export function Dashboard() {
                   </div>
                 ))}
               </div>
-            ) : data?.recent_packages && data.recent_packages.length > 0 ? (
+            ) : data?.recent_publishes && data.recent_publishes.length > 0 ? (
               <div className="space-y-1">
-                {data.recent_packages.slice(0, 5).map((recentPackage) => (
+                {data.recent_publishes.slice(0, 5).map((recent) => (
                   <Link
-                    key={recentPackage.package.id}
-                    to={`/packages/${recentPackage.package.name}`}
+                    key={`${recent.name}@${recent.version}`}
+                    to={`/packages/${recent.name}`}
                     className="flex items-start space-x-3 rounded-lg p-2 transition-colors hover:bg-muted/50"
                   >
                     <div className="flex h-8 w-8 shrink-0 items-center justify-center rounded-full bg-primary/10">
                       <Package className="h-4 w-4" />
                     </div>
                     <div className="min-w-0 flex-1 space-y-1">
-                      <p className="truncate font-medium text-sm leading-none">{recentPackage.package.name}</p>
+                      <p className="truncate font-medium text-sm leading-none">
+                        {recent.name}@{recent.version}
+                      </p>
                       <p className="text-muted-foreground text-xs">
-                        {recentPackage.package.description
-                          ? recentPackage.package.description.length > 60
-                            ? `${recentPackage.package.description.substring(0, 60)}...`
-                            : recentPackage.package.description
+                        {recent.description
+                          ? recent.description.length > 60
+                            ? `${recent.description.substring(0, 60)}...`
+                            : recent.description
                           : "No description"}
                       </p>
                     </div>
@@ -200,7 +246,7 @@ export function Dashboard() {
               </div>
             ) : (
               <div className="flex h-32 items-center justify-center">
-                <p className="text-muted-foreground text-sm">No recent packages</p>
+                <p className="text-muted-foreground text-sm">No recent publishes</p>
               </div>
             )}
           </CardContent>
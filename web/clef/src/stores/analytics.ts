@@ -1,15 +1,15 @@
 import { create } from "zustand";
-import type { AnalyticsData } from "@/types/analytics";
+import type { DashboardSummary } from "@/types/analytics";
 
 interface AnalyticsStoreState {
-  data: AnalyticsData | null;
+  data: DashboardSummary | null;
   isLoading: boolean;
   error: string | null;
   lastUpdated: string | null;
 }
 
 interface AnalyticsStoreActions {
-  setData: (data: AnalyticsData) => void;
+  setData: (data: DashboardSummary) => void;
   setLoading: (isLoading: boolean) => void;
   setError: (error: string | null) => void;
   clearData: () => void;
@@ -113,3 +113,46 @@ export interface AnalyticsData {
 
 // The API returns the data directly, not wrapped in a response object
 export type AnalyticsApiResponse = AnalyticsData;
+
+export interface RecentVersion {
+  name: string;
+  version: string;
+  description: string | null;
+  source: "published" | "proxied";
+  created_at: string;
+}
+
+export interface UpstreamStatus {
+  url: string;
+  reachable: boolean;
+  status: number | null;
+  latency_ms: number | null;
+  error: string | null;
+}
+
+export interface DashboardSummary {
+  totals: {
+    packages: number;
+    versions: number;
+    users: number;
+    organizations: number;
+  };
+  cache: {
+    enabled: boolean;
+    size_bytes: number;
+    hit_rate: number;
+    metadata_entries: number;
+    metadata_size_bytes: number;
+  };
+  top_packages: PopularPackage[];
+  recent_publishes: RecentVersion[];
+  upstream: UpstreamStatus;
+  generated_at: string;
+}
+
+export interface LiveMetricsSample {
+  requests_per_sec: number;
+  cache_hit_rate: number | null;
+  active_downloads: number;
+  sampled_at: string;
+}